@@ -0,0 +1,145 @@
+//! Address book of deployed contracts (`aliases.toml`)
+//!
+//! Maps friendly names to addresses per network, keyed by a network name
+//! from `fluent.toml` or by chain ID:
+//!
+//! ```toml
+//! [testnet]
+//! token-v2 = "0x1234..."
+//!
+//! [20993]
+//! faucet = "0xabcd..."
+//! ```
+//!
+//! Commands taking an address accept `@token-v2` instead. The file is read
+//! from the current directory or the closest parent that has one.
+
+use ethers::types::Address;
+use eyre::{eyre, Context, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// File name of the address book
+pub const ALIASES_FILE: &str = "aliases.toml";
+
+/// Aliases by network, then by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AddressBook {
+    pub networks: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl AddressBook {
+    /// `aliases.toml` of the closest directory at or above `start` that has one, else in `start`
+    pub fn path(start: &Path) -> PathBuf {
+        start
+            .ancestors()
+            .map(|dir| dir.join(ALIASES_FILE))
+            .find(|path| path.is_file())
+            .unwrap_or_else(|| start.join(ALIASES_FILE))
+    }
+
+    /// Read the address book at `path`; empty if it doesn't exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let networks = toml::from_str(&content)
+            .with_context(|| format!("Invalid address book {}", path.display()))?;
+        Ok(Self { networks })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, toml::to_string_pretty(&self.networks)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Add or replace an alias, returning the address it had before
+    pub fn add(&mut self, network: &str, name: &str, address: &str) -> Result<Option<String>> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        {
+            return Err(eyre!(
+                "Alias {} may only contain letters, digits, '-', '_' and '.'",
+                name
+            ));
+        }
+        address
+            .parse::<Address>()
+            .with_context(|| format!("{} is not an address", address))?;
+        Ok(self
+            .networks
+            .entry(network.to_string())
+            .or_default()
+            .insert(name.to_string(), address.to_string()))
+    }
+
+    /// The address of `@name` on the network, or `address` itself if it isn't an alias
+    ///
+    /// Aliases of the network name take precedence over those of the chain ID.
+    pub fn resolve(&self, address: &str, network: Option<&str>, chain_id: u64) -> Result<String> {
+        let Some(name) = address.strip_prefix('@') else {
+            return Ok(address.to_string());
+        };
+        let chain_id = chain_id.to_string();
+        let address = network
+            .into_iter()
+            .chain([chain_id.as_str()])
+            .find_map(|key| self.networks.get(key)?.get(name));
+        address.cloned().ok_or_else(|| match network {
+            Some(network) => eyre!(
+                "No alias {} for network {} or chain {} in {}",
+                name,
+                network,
+                chain_id,
+                ALIASES_FILE
+            ),
+            None => eyre!(
+                "No alias {} for chain {} in {}",
+                name,
+                chain_id,
+                ALIASES_FILE
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0x1111111111111111111111111111111111111111";
+    const FAUCET: &str = "0x2222222222222222222222222222222222222222";
+
+    #[test]
+    fn test_alias_resolution() {
+        let mut book = AddressBook::default();
+        book.add("testnet", "token-v2", TOKEN).unwrap();
+        book.add("20994", "faucet", FAUCET).unwrap();
+        assert!(book.add("testnet", "bad name", TOKEN).is_err());
+        assert!(book.add("testnet", "token", "0x12").is_err());
+
+        assert_eq!(
+            book.resolve("@token-v2", Some("testnet"), 20994).unwrap(),
+            TOKEN
+        );
+        assert_eq!(
+            book.resolve("@faucet", Some("testnet"), 20994).unwrap(),
+            FAUCET
+        );
+        assert_eq!(book.resolve(TOKEN, None, 1).unwrap(), TOKEN);
+        // Aliases don't leak across networks
+        assert!(book.resolve("@token-v2", None, 20994).is_err());
+        assert!(book.resolve("@faucet", Some("testnet"), 20993).is_err());
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = AddressBook::path(dir.path());
+        book.save(&path).unwrap();
+        assert_eq!(AddressBook::load(&path).unwrap(), book);
+    }
+}
@@ -0,0 +1,477 @@
+//! API keys, rate limits and quotas for the verification server
+//!
+//! Configured in the `[server]` section of `fluent.toml`:
+//!
+//! ```toml
+//! [server.anonymous]          # omit to require a key
+//! requests_per_minute = 10
+//! jobs_per_day = 20
+//!
+//! [[server.api_keys]]
+//! name = "explorer"
+//! key_env = "EXPLORER_API_KEY" # or `key = "..."`
+//! requests_per_minute = 120
+//! jobs_per_day = 2000
+//! admin = true                 # may purge cached results (`DELETE /cache`)
+//!
+//! [[server.rpc]]              # endpoints used to fetch deployed bytecode
+//! chain_id = 20994
+//! url = "https://rpc.testnet.fluent.xyz"
+//!
+//! [server.sandbox]            # build submissions in a locked-down container
+//! memory = "4g"
+//! ```
+//!
+//! Keys are sent as `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+//! Usage is counted in memory and resets when the server restarts.
+//! Anonymous usage is counted per client address.
+
+use crate::docker::Sandbox;
+use eyre::{eyre, Context, Result};
+use fluent_builder::ApiError;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Default configuration file read by `serve`
+pub const CONFIG_FILE: &str = "fluent.toml";
+
+const MINUTE: Duration = Duration::from_secs(60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `[server]` section of `fluent.toml`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Limits for requests without a key; such requests are rejected when unset
+    #[serde(default)]
+    pub anonymous: Option<Limits>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// RPC endpoints jobs may fetch bytecode from; jobs can't name others
+    #[serde(default)]
+    pub rpc: Vec<RpcEndpoint>,
+    /// Build submitted sources in a sandbox container
+    #[serde(default)]
+    pub sandbox: Option<Sandbox>,
+}
+
+impl Default for ServerConfig {
+    /// No keys and no anonymous access, so every request is rejected
+    fn default() -> Self {
+        Self {
+            anonymous: None,
+            api_keys: Vec::new(),
+            rpc: Vec::new(),
+            sandbox: None,
+        }
+    }
+}
+
+/// Per-client limits (unset means unlimited)
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Limits {
+    pub requests_per_minute: Option<u32>,
+    /// Verification jobs accepted per day
+    pub jobs_per_day: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Client name used in logs
+    pub name: String,
+    /// The key itself; prefer `key_env` to keep it out of the file
+    pub key: Option<String>,
+    /// Environment variable holding the key
+    pub key_env: Option<String>,
+    /// Allowed to call admin endpoints
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(flatten)]
+    pub limits: Limits,
+}
+
+/// RPC endpoint of a network
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RpcEndpoint {
+    pub chain_id: u64,
+    pub url: String,
+}
+
+impl ServerConfig {
+    /// Read the `[server]` section of a `fluent.toml`, if present
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut file: toml::Table = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        file.remove("server")
+            .map(|server| server.try_into())
+            .transpose()
+            .with_context(|| format!("Invalid [server] section in {}", path.display()))
+    }
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum Denied {
+    Unauthorized,
+    /// Authenticated, but not with an admin key
+    Forbidden,
+    RateLimited {
+        message: String,
+        retry_after: u64,
+    },
+}
+
+impl Denied {
+    /// Status code and JSON body of the refusal
+    pub fn response(&self) -> (u16, Value) {
+        let (status, error) = match self {
+            Denied::Unauthorized => (
+                401,
+                ApiError::new("Missing or invalid API key").with_code("unauthorized"),
+            ),
+            Denied::Forbidden => (
+                403,
+                ApiError::new("An admin API key is required").with_code("forbidden"),
+            ),
+            Denied::RateLimited {
+                message,
+                retry_after,
+            } => (
+                429,
+                ApiError {
+                    retry_after: Some(*retry_after),
+                    ..ApiError::new(message.clone()).with_code("rate_limited")
+                },
+            ),
+        };
+        (status, serde_json::to_value(error).unwrap_or_default())
+    }
+}
+
+struct Client {
+    name: String,
+    key: String,
+    limits: Limits,
+    admin: bool,
+}
+
+/// Usage of one client in the current windows
+struct Usage {
+    minute_start: Instant,
+    requests: u32,
+    day_start: Instant,
+    jobs: u32,
+}
+
+impl Usage {
+    /// Whether nothing counted is still limiting, so a fresh entry would be the same
+    fn is_idle(&self, now: Instant) -> bool {
+        now.duration_since(self.minute_start) >= MINUTE
+            && (self.jobs == 0 || now.duration_since(self.day_start) >= DAY)
+    }
+}
+
+/// Usage by client name, with idle entries dropped every minute
+struct UsageTable {
+    clients: HashMap<String, Usage>,
+    evicted_at: Instant,
+}
+
+/// Authenticates requests and enforces per-client limits
+///
+/// Requests without a key are counted per peer address, so one anonymous
+/// client can't use up the limits of all others.
+pub struct Guard {
+    clients: Vec<Client>,
+    anonymous: Option<Limits>,
+    usage: Mutex<UsageTable>,
+}
+
+impl Guard {
+    /// Resolve the configured keys
+    pub fn new(config: &ServerConfig) -> Result<Self> {
+        let clients = config
+            .api_keys
+            .iter()
+            .map(|api_key| {
+                let key = match (&api_key.key, &api_key.key_env) {
+                    (Some(key), None) => key.clone(),
+                    (None, Some(var)) => std::env::var(var).with_context(|| {
+                        format!(
+                            "API key '{}': environment variable {} is not set",
+                            api_key.name, var
+                        )
+                    })?,
+                    _ => {
+                        return Err(eyre!(
+                            "API key '{}' needs exactly one of `key` and `key_env`",
+                            api_key.name
+                        ))
+                    }
+                };
+                if key.is_empty() {
+                    return Err(eyre!("API key '{}' is empty", api_key.name));
+                }
+                Ok(Client {
+                    name: api_key.name.clone(),
+                    key,
+                    limits: api_key.limits.clone(),
+                    admin: api_key.admin,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            clients,
+            anonymous: config.anonymous.clone(),
+            usage: Mutex::new(UsageTable {
+                clients: HashMap::new(),
+                evicted_at: Instant::now(),
+            }),
+        })
+    }
+
+    /// Count a request from `peer`, returning the client name
+    ///
+    /// `submits_job` also checks the daily job quota; accepted jobs are
+    /// counted with [`Guard::record_job`].
+    pub fn check(
+        &self,
+        api_key: Option<&str>,
+        peer: Option<IpAddr>,
+        submits_job: bool,
+    ) -> Result<String, Denied> {
+        let (name, limits) = match api_key {
+            Some(api_key) => {
+                let client = self.client(api_key).ok_or(Denied::Unauthorized)?;
+                (client.name.clone(), &client.limits)
+            }
+            None => {
+                let limits = self.anonymous.as_ref().ok_or(Denied::Unauthorized)?;
+                let name = match peer {
+                    Some(peer) => format!("anonymous {}", peer),
+                    None => "anonymous".to_string(),
+                };
+                (name, limits)
+            }
+        };
+
+        let now = Instant::now();
+        let mut table = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(table.evicted_at) >= MINUTE {
+            table.clients.retain(|_, usage| !usage.is_idle(now));
+            table.evicted_at = now;
+        }
+        let usage = table.clients.entry(name.clone()).or_insert_with(|| Usage {
+            minute_start: now,
+            requests: 0,
+            day_start: now,
+            jobs: 0,
+        });
+        if now.duration_since(usage.minute_start) >= MINUTE {
+            usage.minute_start = now;
+            usage.requests = 0;
+        }
+        if now.duration_since(usage.day_start) >= DAY {
+            usage.day_start = now;
+            usage.jobs = 0;
+        }
+
+        if let Some(limit) = limits.requests_per_minute {
+            if usage.requests >= limit {
+                return Err(Denied::RateLimited {
+                    message: format!("Rate limit of {} requests per minute exceeded", limit),
+                    retry_after: retry_after(usage.minute_start + MINUTE, now),
+                });
+            }
+        }
+        if let Some(limit) = limits.jobs_per_day.filter(|_| submits_job) {
+            if usage.jobs >= limit {
+                return Err(Denied::RateLimited {
+                    message: format!("Quota of {} jobs per day exceeded", limit),
+                    retry_after: retry_after(usage.day_start + DAY, now),
+                });
+            }
+        }
+        usage.requests += 1;
+        Ok(name)
+    }
+
+    /// Refuse admin endpoints to clients without an admin key
+    pub fn check_admin(&self, api_key: Option<&str>) -> Result<(), Denied> {
+        let api_key = api_key.ok_or(Denied::Unauthorized)?;
+        match self.client(api_key) {
+            Some(client) if client.admin => Ok(()),
+            Some(_) => Err(Denied::Forbidden),
+            None => Err(Denied::Unauthorized),
+        }
+    }
+
+    fn client(&self, api_key: &str) -> Option<&Client> {
+        self.clients
+            .iter()
+            .find(|client| constant_time_eq(client.key.as_bytes(), api_key.as_bytes()))
+    }
+
+    /// Count an accepted job against the client's daily quota
+    pub fn record_job(&self, client: &str) {
+        let mut table = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(usage) = table.clients.get_mut(client) {
+            usage.jobs += 1;
+        }
+    }
+}
+
+/// Whole seconds until `reset`, at least 1
+fn retry_after(reset: Instant, now: Instant) -> u64 {
+    reset.saturating_duration_since(now).as_secs().max(1)
+}
+
+/// Compare keys without leaking the length of the matching prefix
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn explorer_guard(anonymous: Option<Limits>) -> Guard {
+        Guard::new(&ServerConfig {
+            anonymous,
+            api_keys: vec![ApiKeyConfig {
+                name: "explorer".to_string(),
+                key: Some("secret".to_string()),
+                key_env: None,
+                admin: false,
+                limits: Limits {
+                    requests_per_minute: Some(3),
+                    jobs_per_day: Some(1),
+                },
+            }],
+            rpc: Vec::new(),
+            sandbox: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_authentication() {
+        let guard = explorer_guard(None);
+        assert_eq!(guard.check(None, None, false), Err(Denied::Unauthorized));
+        assert_eq!(
+            guard.check(Some("wrong"), None, false),
+            Err(Denied::Unauthorized)
+        );
+        assert_eq!(
+            guard.check(Some("secret"), None, false).unwrap(),
+            "explorer"
+        );
+
+        let guard = explorer_guard(Some(Limits::default()));
+        assert_eq!(guard.check(None, None, true).unwrap(), "anonymous");
+        let peer = "192.0.2.1".parse().ok();
+        assert_eq!(
+            guard.check(None, peer, true).unwrap(),
+            "anonymous 192.0.2.1"
+        );
+
+        // Admin endpoints need a key marked `admin`
+        assert_eq!(guard.check_admin(None), Err(Denied::Unauthorized));
+        assert_eq!(guard.check_admin(Some("secret")), Err(Denied::Forbidden));
+    }
+
+    #[test]
+    fn test_limits() {
+        let guard = explorer_guard(None);
+        let client = guard.check(Some("secret"), None, true).unwrap();
+        guard.record_job(&client);
+
+        // Quota applies to job submissions only
+        let Err(Denied::RateLimited {
+            message,
+            retry_after,
+        }) = guard.check(Some("secret"), None, true)
+        else {
+            panic!("expected quota to be exceeded");
+        };
+        assert!(message.contains("jobs per day"));
+        assert!(retry_after > 60);
+        guard.check(Some("secret"), None, false).unwrap();
+        guard.check(Some("secret"), None, false).unwrap();
+
+        let denied = guard.check(Some("secret"), None, false).unwrap_err();
+        let (status, body) = denied.response();
+        assert_eq!(status, 429);
+        assert_eq!(body["code"], "rate_limited");
+        assert!(body["retry_after"].as_u64().unwrap() <= 60);
+    }
+
+    #[test]
+    fn test_anonymous_limits_per_peer() {
+        let guard = explorer_guard(Some(Limits {
+            requests_per_minute: Some(1),
+            jobs_per_day: None,
+        }));
+        let first = "192.0.2.1".parse().ok();
+        let second = "192.0.2.2".parse().ok();
+        guard.check(None, first, false).unwrap();
+        assert!(guard.check(None, first, false).is_err());
+        // Another peer has its own bucket
+        guard.check(None, second, false).unwrap();
+    }
+
+    #[test]
+    fn test_idle_usage() {
+        let start = Instant::now();
+        let usage = |jobs| Usage {
+            minute_start: start,
+            requests: 5,
+            day_start: start,
+            jobs,
+        };
+        assert!(!usage(0).is_idle(start));
+        assert!(usage(0).is_idle(start + MINUTE));
+        // A used job quota is kept for the rest of the day
+        assert!(!usage(1).is_idle(start + MINUTE));
+        assert!(usage(1).is_idle(start + DAY));
+    }
+
+    #[test]
+    fn test_load_config() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "[build]\nprofile = \"release\"\n").unwrap();
+        assert_eq!(ServerConfig::load(&path).unwrap(), None);
+
+        std::fs::write(
+            &path,
+            "[[server.api_keys]]\nname = \"ci\"\nkey_env = \"FLUENT_BUILDER_UNSET_TEST_KEY\"\n\
+             jobs_per_day = 5\n\n\
+             [server.sandbox]\nmemory = \"2g\"\n",
+        )
+        .unwrap();
+        let config = ServerConfig::load(&path).unwrap().unwrap();
+        assert_eq!(config.anonymous, None);
+        assert_eq!(config.api_keys[0].limits.jobs_per_day, Some(5));
+        assert_eq!(config.api_keys[0].limits.requests_per_minute, None);
+        assert!(!config.api_keys[0].admin);
+        let sandbox = config.sandbox.clone().unwrap();
+        assert_eq!(sandbox.memory, "2g");
+        assert_eq!(sandbox.pids_limit, Sandbox::default().pids_limit);
+
+        // Keys must be resolvable at startup
+        assert!(Guard::new(&config).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Defaults per role, e.g. CI vs developer machines
+//!
+//! Declared in `[profile.<name>]` sections of the project's `fluent.toml`:
+//!
+//! ```toml
+//! [profile.ci]
+//! strict = true
+//! cache_dir = "/cache/fluent-builder"
+//!
+//! [profile.dev]
+//! docker = false
+//! allow_dirty = true
+//! artifacts = ["abi", "metadata"]
+//! ```
+//!
+//! The profile is chosen with `--config-profile NAME`; otherwise `ci` when a
+//! CI environment is detected and `dev` elsewhere, if the section exists.
+//! Flags given on the command line apply on top of the profile.
+
+use crate::auth::CONFIG_FILE;
+use eyre::{eyre, Context, Result};
+use fluent_builder::ArtifactsConfig;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// `--config-profile` value that disables profiles, including auto-detection
+pub const NO_PROFILE: &str = "none";
+
+/// Environment variables set by common CI services
+const CI_ENV_VARS: &[&str] = &[
+    "CI",
+    "GITHUB_ACTIONS",
+    "GITLAB_CI",
+    "BUILDKITE",
+    "CIRCLECI",
+    "JENKINS_URL",
+    "TF_BUILD",
+];
+
+/// A `[profile.<name>]` section of `fluent.toml`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigProfile {
+    /// Profile name; empty when no profile is active
+    #[serde(skip)]
+    pub name: String,
+    /// Build in Docker; `false` is `--no-docker`
+    pub docker: Option<bool>,
+    /// `--strict`
+    pub strict: Option<bool>,
+    /// `--allow-dirty`
+    pub allow_dirty: Option<bool>,
+    /// Artifacts to write next to the bytecode
+    pub artifacts: Option<Vec<ArtifactKind>>,
+    /// Directory for fluent-builder's own caches, relative to fluent.toml
+    pub cache_dir: Option<PathBuf>,
+    /// Cargo target directory, relative to fluent.toml; `--target-dir` takes precedence
+    pub target_dir: Option<PathBuf>,
+}
+
+/// Optional artifact written by `compile`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    Abi,
+    Interface,
+    Metadata,
+}
+
+impl ConfigProfile {
+    /// The profile named on the command line, or the one detected for this environment
+    ///
+    /// A detected profile that `fluent.toml` does not declare is no profile;
+    /// a requested one is an error.
+    pub fn select(config_dir: &Path, requested: Option<&str>) -> Result<Self> {
+        if requested == Some(NO_PROFILE) {
+            return Ok(Self::default());
+        }
+        let path = config_dir.join(CONFIG_FILE);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) if requested.is_none() => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "--config-profile {} needs {}",
+                        requested.unwrap_or_default(),
+                        path.display()
+                    )
+                })
+            }
+        };
+
+        let name = requested.unwrap_or(if detect_ci(|var| std::env::var(var).ok()) {
+            "ci"
+        } else {
+            "dev"
+        });
+        let profile = Self::parse(&content, name, requested.is_some())
+            .with_context(|| format!("Invalid profile configuration in {}", path.display()))?;
+        Ok(profile
+            .map(|profile| profile.resolve_paths(config_dir))
+            .unwrap_or_default())
+    }
+
+    fn parse(content: &str, name: &str, required: bool) -> Result<Option<Self>> {
+        let mut file: toml::Table = toml::from_str(content)?;
+        let mut profiles: BTreeMap<String, ConfigProfile> = file
+            .remove("profile")
+            .map(|profiles| profiles.try_into())
+            .transpose()?
+            .unwrap_or_default();
+        match profiles.remove(name) {
+            Some(profile) => Ok(Some(Self {
+                name: name.to_string(),
+                ..profile
+            })),
+            None if !required => Ok(None),
+            None => {
+                let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                if known.is_empty() {
+                    Err(eyre!("No [profile.{}] section", name))
+                } else {
+                    Err(eyre!(
+                        "No [profile.{}] section (known profiles: {})",
+                        name,
+                        known.join(", ")
+                    ))
+                }
+            }
+        }
+    }
+
+    fn resolve_paths(mut self, config_dir: &Path) -> Self {
+        for dir in [&mut self.cache_dir, &mut self.target_dir]
+            .into_iter()
+            .flatten()
+        {
+            *dir = config_dir.join(&*dir);
+        }
+        self
+    }
+
+    /// Name to pin in a nested invocation (Docker, Nix), which can't see the CI environment
+    pub fn pinned_name(&self) -> &str {
+        if self.name.is_empty() {
+            NO_PROFILE
+        } else {
+            &self.name
+        }
+    }
+
+    /// Write only the artifacts of the profile, if it lists them
+    pub fn apply_artifacts(&self, artifacts: &mut ArtifactsConfig) {
+        if let Some(kinds) = &self.artifacts {
+            artifacts.generate_abi = kinds.contains(&ArtifactKind::Abi);
+            artifacts.generate_interface = kinds.contains(&ArtifactKind::Interface);
+            artifacts.generate_metadata = kinds.contains(&ArtifactKind::Metadata);
+        }
+    }
+}
+
+/// Whether one of the CI services' variables is set (and not `false`)
+pub fn detect_ci(var: impl Fn(&str) -> Option<String>) -> bool {
+    CI_ENV_VARS
+        .iter()
+        .any(|name| var(name).is_some_and(|value| !value.is_empty() && value != "false"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[networks.testnet]
+features = ["testnet"]
+
+[profile.ci]
+strict = true
+cache_dir = "/cache/fluent-builder"
+
+[profile.dev]
+docker = false
+artifacts = ["abi", "metadata"]
+target_dir = "target-dev"
+"#;
+
+    #[test]
+    fn test_profile_settings() {
+        let ci = ConfigProfile::parse(CONFIG, "ci", false).unwrap().unwrap();
+        assert_eq!(ci.name, "ci");
+        assert_eq!(ci.strict, Some(true));
+        assert_eq!(ci.docker, None);
+
+        let dev = ConfigProfile::parse(CONFIG, "dev", false)
+            .unwrap()
+            .unwrap()
+            .resolve_paths(Path::new("/project"));
+        assert_eq!(dev.docker, Some(false));
+        assert_eq!(dev.target_dir, Some(PathBuf::from("/project/target-dev")));
+        let mut artifacts = ArtifactsConfig::default();
+        dev.apply_artifacts(&mut artifacts);
+        assert!(
+            artifacts.generate_abi && artifacts.generate_metadata && !artifacts.generate_interface
+        );
+
+        // Only a requested profile must exist
+        assert_eq!(
+            ConfigProfile::parse(CONFIG, "staging", false).unwrap(),
+            None
+        );
+        let err = ConfigProfile::parse(CONFIG, "staging", true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No [profile.staging] section (known profiles: ci, dev)"
+        );
+        assert!(ConfigProfile::parse("[profile.ci]\ndocker = \"yes\"", "ci", false).is_err());
+
+        assert_eq!(ConfigProfile::default().pinned_name(), NO_PROFILE);
+        assert_eq!(ci.pinned_name(), "ci");
+    }
+
+    #[test]
+    fn test_detect_ci() {
+        assert!(detect_ci(
+            |var| (var == "GITHUB_ACTIONS").then(|| "true".to_string())
+        ));
+        assert!(!detect_ci(|var| (var == "CI").then(|| "false".to_string())));
+        assert!(!detect_ci(|_| None));
+    }
+}
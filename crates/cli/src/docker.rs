@@ -1,13 +1,295 @@
 //! Docker orchestration for reproducible builds
+//!
+//! Podman works as well; the engine is detected or chosen with `--runtime`.
 
 use eyre::{bail, eyre, Context, Result};
+use fluent_builder::{
+    output_interruptible, record_cache_use, status_interruptible, BuildEnvironment, BuilderError,
+    CacheEntry, CacheKind, CacheUsage, CleanupGuard, Timeouts,
+};
+use serde::Deserialize;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
+/// Selects a Docker-compatible engine, e.g. `podman`
+pub const CONTAINER_ENGINE_ENV: &str = "FLUENT_BUILDER_CONTAINER_ENGINE";
+
+/// Container runtime running reproducible builds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ContainerRuntime {
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    /// Docker if installed, else Podman if installed
+    fn detect() -> Self {
+        [Self::Docker, Self::Podman]
+            .into_iter()
+            .find(|runtime| on_path(runtime.command()))
+            .unwrap_or(Self::Docker)
+    }
+
+    /// The runtime of an engine command, e.g. `/usr/bin/podman`
+    fn of_engine(engine: &str) -> Self {
+        match Path::new(engine).file_stem().and_then(|stem| stem.to_str()) {
+            Some("podman") => Self::Podman,
+            _ => Self::Docker,
+        }
+    }
+
+    pub fn command(self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+        }
+    }
+
+    /// `--mount` value binding a host directory; Podman relabels it for SELinux hosts
+    fn bind_mount(self, source: &str, target: &str, readonly: bool) -> String {
+        let mut mount = format!("type=bind,source={},target={}", source, target);
+        if readonly {
+            mount.push_str(",readonly");
+        }
+        if self == Self::Podman {
+            mount.push_str(",relabel=shared");
+        }
+        mount
+    }
+
+    /// Reference to pull `image` by; Podman doesn't resolve short names to Docker Hub
+    fn pull_reference(self, image: &str) -> String {
+        let has_registry = image
+            .split_once('/')
+            .is_some_and(|(host, _)| host.contains(['.', ':']) || host == "localhost");
+        match self {
+            Self::Podman if !has_registry => format!("docker.io/{}", image),
+            _ => image.to_string(),
+        }
+    }
+
+    /// `images` filter matching the builder images; Podman names local builds `localhost/...`
+    fn image_filter(self) -> &'static str {
+        match self {
+            Self::Docker => "reference=fluent-builder-*",
+            Self::Podman => "reference=localhost/fluent-builder-*",
+        }
+    }
+
+    /// Go template printing a creation time as RFC 3339, which Docker already does
+    fn time_template(self, field: &str) -> String {
+        match self {
+            Self::Docker => format!("{{{{.{}}}}}", field),
+            Self::Podman => format!("{{{{.{}.Format \"2006-01-02T15:04:05Z07:00\"}}}}", field),
+        }
+    }
+}
+
+/// Whether an executable named `name` is on PATH
+fn on_path(name: &str) -> bool {
+    let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(&file).is_file()))
+}
+
+/// Volumes caching downloaded crates and compiled bytecode between reproducible builds
+const CACHE_VOLUMES: [&str; 3] = ["cargo-registry", "cargo-git", BUILD_CACHE_VOLUME];
+
+/// Volume holding the bytecode cache of containerized builds
+const BUILD_CACHE_VOLUME: &str = "fluent-builder-builds";
+
+/// Mount point of [`BUILD_CACHE_VOLUME`] in the container
+const BUILD_CACHE_MOUNT: &str = "/usr/local/fluent-builder/builds";
+
+/// Restrictions for building untrusted sources
+///
+/// The project is mounted read-only and the container runs without network,
+/// with a read-only root filesystem, no capabilities and resource limits.
+/// Cargo's home is a tmpfs rather than the shared registry volumes, so
+/// dependencies must be vendored in the project.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sandbox {
+    /// Memory limit, e.g. `4g` (no swap)
+    pub memory: String,
+    /// CPU limit, e.g. `2`
+    pub cpus: String,
+    /// Maximum number of processes
+    pub pids_limit: u32,
+    /// Size of the writable `/tmp`
+    pub tmpfs_size: String,
+    /// Seccomp profile; the engine's default profile when unset
+    pub seccomp_profile: Option<PathBuf>,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self {
+            memory: "4g".to_string(),
+            cpus: "2".to_string(),
+            pids_limit: 512,
+            tmpfs_size: "1g".to_string(),
+            seccomp_profile: None,
+        }
+    }
+}
+
+impl Sandbox {
+    /// `run` arguments applying the restrictions
+    fn run_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "--network",
+            "none",
+            "--read-only",
+            "--cap-drop",
+            "ALL",
+            "--security-opt",
+            "no-new-privileges",
+            "--memory",
+            &self.memory,
+            "--memory-swap",
+            &self.memory,
+            "--cpus",
+            &self.cpus,
+            "--pids-limit",
+            &self.pids_limit.to_string(),
+            "--tmpfs",
+            &format!("/tmp:rw,exec,size={}", self.tmpfs_size),
+            "--env",
+            "HOME=/tmp",
+            "--env",
+            "CARGO_HOME=/tmp/cargo",
+        ]
+        .map(str::to_string)
+        .to_vec();
+        if let Some(profile) = &self.seccomp_profile {
+            args.push("--security-opt".to_string());
+            args.push(format!("seccomp={}", profile.display()));
+        }
+        args
+    }
+}
+
+/// The engine set in FLUENT_BUILDER_CONTAINER_ENGINE, else `docker` or `podman`, whichever is
+/// installed
+fn engine() -> String {
+    std::env::var(CONTAINER_ENGINE_ENV)
+        .ok()
+        .filter(|engine| !engine.is_empty())
+        .unwrap_or_else(|| ContainerRuntime::detect().command().to_string())
+}
+
+fn runtime() -> ContainerRuntime {
+    ContainerRuntime::of_engine(&engine())
+}
+
+/// Image name as listed by the engine, without Podman's `localhost/` for local builds
+fn listed_image_name(listed: &str) -> &str {
+    listed.strip_prefix("localhost/").unwrap_or(listed)
+}
+
+/// Label of a versioned image naming the builder image it was built from
+const BASE_IMAGE_LABEL: &str = "xyz.fluent.builder.base-image";
+
 /// Docker image name format for fluent-builder
-fn image_name(sdk_version: &str, rust_version: &str) -> String {
-    format!("fluent-builder-{}-rust-{}", sdk_version, rust_version)
+fn image_name(sdk_tag: &str, rust_version: &str) -> String {
+    format!("fluent-builder-{}-rust-{}", sdk_tag, rust_version)
+}
+
+/// Tag of the builder image for an SDK version read from Cargo.lock, e.g. `v0.4.1` for `0.4.1`
+fn sdk_tag(sdk_version: &str) -> String {
+    if sdk_version.starts_with('v') {
+        sdk_version.to_string()
+    } else {
+        format!("v{}", sdk_version)
+    }
+}
+
+/// Digest, platform and base image of the local image `reference`, named `image` in metadata.json
+fn inspect_image(reference: &str, image: &str) -> Result<BuildEnvironment> {
+    let output = Command::new(engine())
+        .args([
+            "image",
+            "inspect",
+            "--format",
+            "{{.Id}}|{{.Os}}/{{.Architecture}}|{{json .RepoDigests}}|{{json .Config.Labels}}",
+            reference,
+        ])
+        .output()
+        .context("Failed to inspect Docker image")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to inspect image {}: {}",
+            reference,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_image_inspect(image, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse `<id>|<os>/<arch>|<repo digests>|<labels>`; Podman omits the `sha256:` of image IDs
+fn parse_image_inspect(image: &str, inspected: &str) -> Result<BuildEnvironment> {
+    let mut fields = inspected.trim().splitn(4, '|');
+    let (Some(id), Some(platform), Some(repo_digests), labels) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        bail!("Unexpected image inspect output: {}", inspected.trim());
+    };
+    let repo_digests: Vec<String> = serde_json::from_str(repo_digests).unwrap_or_default();
+    let labels: std::collections::BTreeMap<String, String> = labels
+        .and_then(|labels| serde_json::from_str(labels).ok())
+        .unwrap_or_default();
+    Ok(BuildEnvironment {
+        image: image.to_string(),
+        image_digest: if id.starts_with("sha256:") {
+            id.to_string()
+        } else {
+            format!("sha256:{}", id)
+        },
+        // Local builds have no digest a registry could serve
+        repo_digest: repo_digests
+            .into_iter()
+            .find(|digest| !digest.starts_with("localhost/")),
+        // Only a base pulled from a registry can be fetched again
+        base_image: labels
+            .get(BASE_IMAGE_LABEL)
+            .filter(|base| base.contains("@sha256:"))
+            .cloned(),
+        platform: platform.to_string(),
+        cli_version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+/// Where `run_reproducible` and `run_pinned` mount the workspace in the container
+const WORKSPACE_MOUNT: &str = "/workspace";
+
+/// Directory mounted at `/workspace` for the project at `project_root`, and the project's
+/// directory in the container
+///
+/// A workspace member is built with the whole workspace mounted, so the shared
+/// Cargo.lock, path dependencies and `target/` are where cargo expects them.
+/// `project_root` must be canonical.
+pub fn container_layout(project_root: &Path) -> (PathBuf, String) {
+    let workspace_root = fluent_builder::CargoLayout::locate(project_root)
+        .and_then(|layout| fluent_builder::canonicalize_path(&layout.workspace_root))
+        .ok()
+        .filter(|root| project_root.starts_with(root))
+        .unwrap_or_else(|| project_root.to_path_buf());
+    let workdir = container_workdir(
+        project_root
+            .strip_prefix(&workspace_root)
+            .unwrap_or(Path::new("")),
+    );
+    (workspace_root, workdir)
+}
+
+/// Directory in the container of a project at `member` relative to the workspace root
+fn container_workdir(member: &Path) -> String {
+    std::iter::once(WORKSPACE_MOUNT.into())
+        .chain(member.iter().map(|part| part.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join("/")
 }
 
 /// Run the compilation inside Docker container
@@ -16,53 +298,276 @@ pub fn run_reproducible(
     rust_version: &str,
     sdk_version: &str,
     command_args: &[String],
+    offline: bool,
 ) -> Result<()> {
     // Check if Docker is available
     check_docker_available()?;
 
-    // TODO: use real version after we move fluent-builder to the fluentbase-sdk
-    let sdk_version = "v0.1.0";
-
     // Canonicalize project path for proper mounting
-    let canonicalized_project_root = project_root
-        .canonicalize()
-        .context("Failed to canonicalize project directory")?;
+    let canonicalized_project_root = fluent_builder::canonicalize_path(project_root)?;
 
     // Create versioned image if needed
-    create_image(sdk_version, rust_version)?;
+    let image = create_image(sdk_version, rust_version, offline)?;
+    let environment = inspect_image(&image, &image)?;
+
+    // Add --no-docker to prevent recursion
+    let mut args = command_args.to_vec();
+    crate::insert_flags(&mut args, &["--no-docker"]);
 
     // Run compilation in container
     run_in_docker_container(
         &canonicalized_project_root,
-        sdk_version,
-        rust_version,
-        command_args,
+        &image,
+        &environment,
+        &args,
+        offline,
     )
 }
 
+/// Run fluent-builder with `args` in the image of a recorded [`BuildEnvironment`]
+///
+/// The image is looked up by digest, and pulled by its repo digest if it isn't
+/// available locally; a different image under the same name is never used.
+/// An image built locally is rebuilt from the builder image it was built from,
+/// pulled by the recorded digest. `args` run as-is, so the command must tell
+/// from [`fluent_builder::DOCKER_ENV`] that it runs in the container.
+pub fn run_pinned(project_root: &Path, recorded: &BuildEnvironment, args: &[String]) -> Result<()> {
+    check_docker_available()?;
+    let project_root = fluent_builder::canonicalize_path(project_root)?;
+
+    let environment = match inspect_image(&recorded.image_digest, &recorded.image) {
+        Ok(environment) => environment,
+        Err(_) => match (&recorded.repo_digest, &recorded.base_image) {
+            (Some(reference), _) => {
+                pull_image(reference, &recorded.platform)?;
+                inspect_image(&recorded.image_digest, &recorded.image)?
+            }
+            (None, Some(base_image)) => rebuild_image(&project_root, recorded, base_image)?,
+            (None, None) => bail!(
+                "Docker image {} ({}) is not available locally and was not built from an image \
+                 pulled from a registry, so it can't be fetched by digest.\nLoad the image the \
+                 contract was built with, e.g. with `{} load`, and try again.",
+                recorded.image,
+                recorded.image_digest,
+                runtime().command()
+            ),
+        },
+    };
+    let rebuilt = environment.base_image.is_some() && environment.base_image == recorded.base_image;
+    if environment.image_digest != recorded.image_digest && !rebuilt {
+        bail!(
+            "Image {} has digest {}, not {}",
+            recorded.image,
+            environment.image_digest,
+            recorded.image_digest
+        );
+    }
+
+    run_in_docker_container(
+        &project_root,
+        &environment.image_digest,
+        &environment,
+        args,
+        false,
+    )
+}
+
+/// Pull `reference` for `platform`, reporting progress on stderr so `--json` output stays clean
+fn pull_image(reference: &str, platform: &str) -> Result<()> {
+    eprintln!("Pulling {}...", reference);
+    let status = Command::new(engine())
+        .args([
+            "pull",
+            "--platform",
+            platform,
+            &runtime().pull_reference(reference),
+        ])
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to execute docker pull")?;
+    if !status.success() {
+        bail!("Failed to pull {}", reference);
+    }
+    Ok(())
+}
+
+/// Build the recorded image again from its pinned base image and the project's toolchain
+///
+/// The toolchain layers are downloaded again, so the image ID differs from the
+/// recorded one; the base image is the same.
+fn rebuild_image(
+    project_root: &Path,
+    recorded: &BuildEnvironment,
+    base_image: &str,
+) -> Result<BuildEnvironment> {
+    let (rust_version, _) = crate::detect_project_versions(project_root)?;
+    pull_image(base_image, &recorded.platform)?;
+    eprintln!(
+        "Rebuilding {} from {} for Rust {}...",
+        recorded.image, base_image, rust_version
+    );
+    build_versioned_image(&recorded.image, base_image, &rust_version)?;
+    let environment = inspect_image(&recorded.image, &recorded.image)?;
+    tracing::warn!(
+        "Image {} ({}) was not available and was rebuilt from {} as {}",
+        recorded.image,
+        recorded.image_digest,
+        base_image,
+        environment.image_digest
+    );
+    Ok(environment)
+}
+
+/// Run fluent-builder on untrusted sources inside a [`Sandbox`], returning its stdout
+///
+/// `readonly` and `writable` are extra (host path, container path) mounts;
+/// besides `/tmp`, the writable ones are the only places the build can write to.
+pub fn run_sandboxed(
+    project_root: &Path,
+    container: &str,
+    args: &[String],
+    sandbox: &Sandbox,
+    readonly: &[(&Path, &str)],
+    writable: &[(&Path, &str)],
+) -> Result<Vec<u8>> {
+    check_docker_available()?;
+
+    // Same image as run_reproducible; it is built (online) before the sandbox starts
+    let (rust_version, sdk_version) = crate::detect_project_versions(project_root)?;
+    let image = create_image(&sdk_version, &rust_version, false)?;
+    let environment = serde_json::to_string(&inspect_image(&image, &image)?)?;
+
+    let runtime = runtime();
+    let mut mounts = vec![runtime.bind_mount(
+        &fluent_builder::docker_host_path(&fluent_builder::canonicalize_path(project_root)?),
+        "/workspace",
+        true,
+    )];
+    let readonly = readonly.iter().map(|mount| (mount, true));
+    for ((host, target), readonly) in readonly.chain(writable.iter().map(|mount| (mount, false))) {
+        mounts.push(runtime.bind_mount(
+            &fluent_builder::docker_host_path(&fluent_builder::canonicalize_path(host)?),
+            target,
+            readonly,
+        ));
+    }
+
+    let mut cmd = Command::new(engine());
+    cmd.args([
+        "run",
+        "--rm",
+        "--name",
+        container,
+        "--platform",
+        "linux/amd64",
+    ]);
+    cmd.args(sandbox.run_args());
+    cmd.args([
+        "--env",
+        &format!("{}={}", fluent_builder::BUILD_ENVIRONMENT_ENV, environment),
+    ]);
+    // Run as the owner of the writable directories rather than root
+    if let Some(user) = writable.first().and_then(|(host, _)| host_user(host)) {
+        // Rootless Podman maps that user to a subordinate id unless kept
+        if runtime == ContainerRuntime::Podman {
+            cmd.arg("--userns=keep-id");
+        }
+        cmd.args(["--user", &user]);
+    }
+    for mount in &mounts {
+        cmd.args(["--mount", mount]);
+    }
+    cmd.args(["-w", "/workspace", &image, "fluent-builder"]);
+    let mut args = args.to_vec();
+    crate::insert_flags(&mut args, &["--no-docker"]);
+    cmd.args(args);
+
+    tracing::debug!("Running sandboxed command: {:?}", cmd);
+    let cleanup = remove_container_on_drop(container);
+    let output = output_interruptible(&mut cmd).context("Failed to execute sandbox container")?;
+    cleanup.disarm();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+        bail!(
+            "Build failed inside sandbox container:\n{}",
+            tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// `uid:gid` owning `path`
+#[cfg(unix)]
+fn host_user(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(format!("{}:{}", metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn host_user(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Force-remove `container` unless disarmed after it exited on its own
+fn remove_container_on_drop(container: &str) -> CleanupGuard {
+    let container = container.to_string();
+    CleanupGuard::new(move || {
+        let _ = Command::new(engine())
+            .args(["rm", "--force", &container])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    })
+}
+
+/// Cache key of a locally available image
+fn image_fact(name: &str) -> String {
+    format!("{}.image:{}", runtime().command(), name)
+}
+
 /// Check if Docker daemon is running and accessible
 fn check_docker_available() -> Result<()> {
-    let status = Command::new("docker")
+    let engine = engine();
+    let fact = format!("{}.available", runtime().command());
+    // Only a running daemon is cached; a stopped one may be started any moment
+    if crate::fact_cache().get(&fact).is_some() {
+        return Ok(());
+    }
+
+    let status = Command::new(&engine)
         .args(["info"])
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status()
-        .context("Failed to execute docker command")?;
+        .with_context(|| format!("Failed to execute {} command", engine))?;
 
     if !status.success() {
-        bail!(
-            "Docker is not installed or not running. Please start Docker and try again.\n\
-            To compile without Docker, use the --no-docker flag.\n\
-            Install Docker: https://docs.docker.com/get-docker/"
-        );
+        return Err(eyre::Report::new(BuilderError::DockerUnavailable {
+            runtime: engine.clone(),
+        })
+        .wrap_err(format!(
+            "{} is not installed or not running. Please start it and try again.\n\
+            To use Podman instead of Docker, pass --runtime podman.\n\
+            To compile without containers, use the --no-docker flag.\n\
+            Install Docker: https://docs.docker.com/get-docker/",
+            engine
+        )));
     }
 
+    crate::fact_cache().set(&fact, "true");
     Ok(())
 }
 
 /// Check if Docker image exists locally
 fn image_exists(name: &str) -> Result<bool> {
-    let output = Command::new("docker")
+    if crate::fact_cache().get(&image_fact(name)).is_some() {
+        return Ok(true);
+    }
+
+    let output = Command::new(engine())
         .args(["images", "-q", name])
         .output()
         .context("Failed to check Docker images")?;
@@ -73,39 +578,59 @@ fn image_exists(name: &str) -> Result<bool> {
     }
 
     // If output is empty, image doesn't exist
-    Ok(!output.stdout.is_empty())
+    let exists = !output.stdout.is_empty();
+    if exists {
+        crate::fact_cache().set(&image_fact(name), "true");
+    }
+    Ok(exists)
 }
 
-/// Create Docker image with specific SDK and Rust versions
-fn create_image(sdk_version: &str, rust_version: &str) -> Result<()> {
-    let name = image_name(sdk_version, rust_version);
+/// Create the Docker image for an SDK version from Cargo.lock and a Rust version, returning its
+/// name
+fn create_image(sdk_version: &str, rust_version: &str, offline: bool) -> Result<String> {
+    let sdk_tag = sdk_tag(sdk_version);
+    let name = image_name(&sdk_tag, rust_version);
 
-    if image_exists(&name)? {
+    let exists = image_exists(&name)?;
+    record_cache_use(CacheKind::Image, &name, Some(exists));
+    if exists {
         tracing::debug!("Using existing Docker image: {}", name);
-        return Ok(());
+        return Ok(name);
     }
 
-    println!(
+    // Building an image downloads toolchains and sources
+    if offline {
+        bail!(
+            "Docker image {} is not available locally and cannot be built with --offline.\n\
+            Build it once while online, or use --no-docker.",
+            name
+        );
+    }
+
+    eprintln!(
         "Building Docker image for Rust {} with SDK {} (one-time setup)...",
-        rust_version, sdk_version
+        rust_version, sdk_tag
     );
 
     // Determine base image name
-    let base_image = format!("fluentlabs/fluent-builder:{}", sdk_version);
+    let base_image = fluent_builder::builder_image(&sdk_tag);
 
     // Check if base image exists (locally or in registry)
     if !base_image_available(&base_image)? {
-        println!(
+        eprintln!(
             "Base image {} not found, building from source...",
             base_image
         );
-        build_base_image(sdk_version)?;
+        build_base_image(&sdk_tag)?;
     }
 
-    // Build versioned image with specific Rust toolchain
+    // Built from the registry digest, so verification can fetch the same base again
+    let base_image = inspect_image(&base_image, &base_image)?
+        .repo_digest
+        .unwrap_or(base_image);
     build_versioned_image(&name, &base_image, rust_version)?;
 
-    Ok(())
+    Ok(name)
 }
 
 /// Check if base image is available locally or can be pulled from registry
@@ -117,8 +642,8 @@ fn base_image_available(image: &str) -> Result<bool> {
 
     // Try to pull from registry
     tracing::debug!("Attempting to pull base image: {}", image);
-    let status = Command::new("docker")
-        .args(["pull", image])
+    let status = Command::new(engine())
+        .args(["pull", &runtime().pull_reference(image)])
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status()
@@ -128,13 +653,13 @@ fn base_image_available(image: &str) -> Result<bool> {
 }
 
 /// Build base fluent-builder image from source
-fn build_base_image(sdk_version: &str) -> Result<()> {
-    let image_name = format!("fluentlabs/fluent-builder:{}", sdk_version);
+fn build_base_image(sdk_tag: &str) -> Result<()> {
+    let image_name = fluent_builder::builder_image(sdk_tag);
 
     // For now, build from latest Rust
     // TODO: In production, checkout specific SDK tag and build
     let dockerfile = r#"
-FROM rust:latest AS builder
+FROM docker.io/library/rust:latest AS builder
 
 # Install build dependencies
 RUN apt-get update && apt-get install -y git && rm -rf /var/lib/apt/lists/*
@@ -145,7 +670,7 @@ RUN git clone https://github.com/fluentlabs-xyz/fluent-builder /tmp/fluent-build
 WORKDIR /tmp/fluent-builder
 RUN cargo build --release --manifest-path crates/cli/Cargo.toml
 
-FROM rust:latest
+FROM docker.io/library/rust:latest
 COPY --from=builder /tmp/fluent-builder/target/release/fluent-builder /usr/local/bin/fluent-builder
 
 # Verify installation
@@ -175,6 +700,7 @@ WORKDIR /workspace
 
 # Mark as fluent-builder Docker image
 ENV FLUENT_BUILDER_DOCKER=1
+LABEL {BASE_IMAGE_LABEL}="{base_image}"
 "#
     );
 
@@ -194,7 +720,7 @@ fn format_toolchain_version(rust_version: &str) -> String {
 
 /// Build Docker image from Dockerfile content
 fn build_docker_image(image_name: &str, dockerfile_content: &str) -> Result<()> {
-    let mut child = Command::new("docker")
+    let mut child = Command::new(engine())
         .args([
             "build",
             "--platform",
@@ -221,78 +747,218 @@ fn build_docker_image(image_name: &str, dockerfile_content: &str) -> Result<()>
     let status = child.wait().context("Docker build process failed")?;
 
     if !status.success() {
-        bail!("Docker build failed for image: {}", image_name);
+        return Err(BuilderError::DockerFailed {
+            reason: format!("Docker build failed for image: {}", image_name),
+        }
+        .into());
     }
 
     Ok(())
 }
 
-/// Run fluent-builder compilation inside Docker container
+/// Run fluent-builder inside a container of `image`, recording `environment` in the metadata
 fn run_in_docker_container(
     project_root: &Path,
-    sdk_version: &str,
-    rust_version: &str,
+    image: &str,
+    environment: &BuildEnvironment,
     args: &[String],
+    offline: bool,
 ) -> Result<()> {
-    let image = image_name(sdk_version, rust_version);
+    // Named so the container can be killed if we are interrupted
+    let container = format!("fluent-builder-{}", std::process::id());
 
-    // Convert project path to string
-    let project_path = project_root
-        .to_str()
-        .ok_or_else(|| eyre!("Project path contains invalid UTF-8"))?;
+    // Convert project path to a mount source (handles Windows drive letters)
+    if project_root.to_str().is_none() {
+        return Err(eyre!("Project path contains invalid UTF-8"));
+    }
+    let (workspace_root, workdir) = container_layout(project_root);
+    let workspace_path = fluent_builder::docker_host_path(&workspace_root);
+    let runtime = runtime();
+    for volume in CACHE_VOLUMES {
+        // The engine creates a missing volume, empty
+        let exists = engine_output(&["volume", "inspect", volume]).is_ok();
+        record_cache_use(CacheKind::Volume, volume, Some(exists));
+    }
+    let build_cache = format!("{}:{}", BUILD_CACHE_VOLUME, BUILD_CACHE_MOUNT);
+    let build_cache_env = format!(
+        "{}={}",
+        fluent_builder::BUILD_CACHE_DIR_ENV,
+        BUILD_CACHE_MOUNT
+    );
+    let environment_env = format!(
+        "{}={}",
+        fluent_builder::BUILD_ENVIRONMENT_ENV,
+        serde_json::to_string(environment)?
+    );
 
     // Build docker command
-    let mut cmd = Command::new("docker");
+    let mut cmd = Command::new(engine());
+    cmd.arg("run");
+    cmd.args(git_dir_args(&workspace_root, runtime)?);
     cmd.args([
-        "run",
         "--rm",
+        "--name",
+        &container,
         "--platform",
-        "linux/amd64", // Force consistent platform for reproducible builds
+        &environment.platform,
         "--network",
-        "host",
-        "-v",
-        &format!("{}:/workspace", project_path),
+        if offline { "none" } else { "host" },
+        "--mount",
+        &runtime.bind_mount(&workspace_path, WORKSPACE_MOUNT, false),
         "-v",
         "cargo-registry:/usr/local/cargo/registry",
         "-v",
         "cargo-git:/usr/local/cargo/git",
+        "-v",
+        &build_cache,
+        "-e",
+        &build_cache_env,
+        "-e",
+        &environment_env,
         "-w",
-        "/workspace",
-        &image,
+        &workdir,
+        image,
         "fluent-builder",
     ]);
 
     // Add all CLI arguments
     cmd.args(args);
 
-    // Add --no-docker to prevent recursion
-    cmd.arg("--no-docker");
-
     tracing::debug!("Running Docker command: {:?}", cmd);
 
+    let cleanup = remove_container_on_drop(&container);
+
     // Execute and inherit stdio for real-time output
-    let status = cmd
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .context("Failed to execute Docker container")?;
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    let status = status_interruptible(&mut cmd).context("Failed to execute Docker container")?;
+
+    // The container exited and --rm removed it
+    cleanup.disarm();
 
     if !status.success() {
-        bail!("Build failed inside Docker container");
+        return Err(BuilderError::DockerFailed {
+            reason: "Build failed inside Docker container".to_string(),
+        }
+        .into());
     }
 
     Ok(())
 }
 
+/// A fork node image reference pinned by digest, `<image>@sha256:<64 hex digits>`
+///
+/// A tag can move to another node between simulations; a digest always names the same one.
+pub fn parse_pinned_image(image: &str) -> Result<String> {
+    let pinned = image.split_once("@sha256:").is_some_and(|(name, digest)| {
+        !name.is_empty() && digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())
+    });
+    if !pinned {
+        bail!(
+            "{} is not pinned by digest; use <image>@sha256:<digest>, e.g. from `docker images \
+             --digests`",
+            image
+        );
+    }
+    Ok(image.to_string())
+}
+
+/// A forked chain running in a container, removed when dropped
+pub struct ForkNode {
+    /// RPC endpoint on the host
+    pub rpc_url: String,
+    _container: CleanupGuard,
+}
+
+/// Start a Fluent node forking `fork_url` in a container
+///
+/// The image must be pinned by digest and provide an `anvil` executable taking
+/// anvil's fork options; only a Fluent node executes rWASM.
+pub fn start_fork_node(image: &str, fork_url: &str, chain_id: u64) -> Result<ForkNode> {
+    parse_pinned_image(image)?;
+    check_docker_available()?;
+
+    // A free port for the node's RPC, released right before the container binds it
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .context("Failed to find a free port for the fork node")?
+        .port();
+    let container = format!("fluent-builder-fork-{}", std::process::id());
+
+    let output = Command::new(engine())
+        .args(["run", "--detach", "--rm", "--name", &container])
+        .args(["--publish", &format!("127.0.0.1:{}:8545", port)])
+        .args(["--entrypoint", "anvil", image])
+        .args(["--host", "0.0.0.0", "--port", "8545"])
+        .args(["--fork-url", fork_url, "--chain-id", &chain_id.to_string()])
+        .output()
+        .context("Failed to start the fork node container")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to start fork node from {}: {}",
+            image,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(ForkNode {
+        rpc_url: format!("http://127.0.0.1:{}", port),
+        _container: remove_container_on_drop(&container),
+    })
+}
+
+/// `run` arguments letting git inside the container see the project's repository
+///
+/// Only the project is mounted, so when `.git` is a file (a `git worktree` or
+/// submodule checkout) the repository directory it points to is mounted
+/// read-only at `/git` and selected with `GIT_DIR`. Otherwise git would
+/// report no repository and the build would fall back to an archive source.
+fn git_dir_args(project_root: &Path, runtime: ContainerRuntime) -> Result<Vec<String>> {
+    let dirs = match fluent_builder::git_dirs(project_root, Timeouts::default().git)? {
+        Some(dirs) if dirs.is_redirected() && dirs.toplevel == project_root => dirs,
+        _ => return Ok(Vec::new()),
+    };
+    let git_dir = dirs.git_dir.strip_prefix(&dirs.common_dir).map_err(|_| {
+        eyre!(
+            "{} is outside {}",
+            dirs.git_dir.display(),
+            dirs.common_dir.display()
+        )
+    })?;
+
+    Ok(vec![
+        "--mount".to_string(),
+        runtime.bind_mount(
+            &fluent_builder::docker_host_path(&dirs.common_dir),
+            "/git",
+            true,
+        ),
+        "--env".to_string(),
+        format!("GIT_DIR={}", Path::new("/git").join(git_dir).display()),
+        "--env".to_string(),
+        "GIT_WORK_TREE=/workspace".to_string(),
+        // The mounted repository is owned by the host user, not the container's
+        "--env".to_string(),
+        "GIT_CONFIG_COUNT=1".to_string(),
+        "--env".to_string(),
+        "GIT_CONFIG_KEY_0=safe.directory".to_string(),
+        "--env".to_string(),
+        "GIT_CONFIG_VALUE_0=*".to_string(),
+    ])
+}
+
 /// Clean up old Docker images keeping only the most recent ones
 pub fn cleanup_old_images(keep_recent: usize) -> Result<()> {
-    let output = Command::new("docker")
+    let runtime = runtime();
+    let output = Command::new(engine())
         .args([
             "images",
             "--format",
-            "{{.Repository}}:{{.Tag}}\t{{.CreatedAt}}",
+            &format!(
+                "{{{{.Repository}}}}:{{{{.Tag}}}}\t{}",
+                runtime.time_template("CreatedAt")
+            ),
             "--filter",
-            "reference=fluent-builder-*",
+            runtime.image_filter(),
         ])
         .output()
         .context("Failed to list Docker images")?;
@@ -306,8 +972,9 @@ pub fn cleanup_old_images(keep_recent: usize) -> Result<()> {
         .lines()
         .filter_map(|line| {
             let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() == 2 && parts[0].starts_with("fluent-builder-") {
-                Some((parts[0], parts[1]))
+            let name = listed_image_name(parts[0]);
+            if parts.len() == 2 && name.starts_with("fluent-builder-") {
+                Some((name, parts[1]))
             } else {
                 None
             }
@@ -325,7 +992,9 @@ pub fn cleanup_old_images(keep_recent: usize) -> Result<()> {
     for (image, _) in images.into_iter().skip(keep_recent) {
         tracing::info!("Removing old Docker image: {}", image);
 
-        let status = Command::new("docker")
+        // Images are checked by name, listed as name:tag
+        crate::fact_cache().invalidate(&image_fact(image.strip_suffix(":latest").unwrap_or(image)));
+        let status = Command::new(engine())
             .args(["rmi", image])
             .status()
             .context("Failed to remove Docker image")?;
@@ -338,6 +1007,161 @@ pub fn cleanup_old_images(keep_recent: usize) -> Result<()> {
     Ok(())
 }
 
+/// Builder images and cargo volumes, with their size and recorded usage
+pub fn cache_entries(usage: &CacheUsage) -> Result<Vec<CacheEntry>> {
+    let runtime = runtime();
+    let images = engine_output(&[
+        "images",
+        "--format",
+        "{{.Repository}}:{{.Tag}}",
+        "--filter",
+        runtime.image_filter(),
+    ])?;
+
+    let mut entries = Vec::new();
+    for image in images.lines() {
+        let format = format!("{{{{.Size}}}}\t{}", runtime.time_template("Created"));
+        let inspect = engine_output(&["image", "inspect", "--format", &format, image])?;
+        let (size, created) = inspect.split_once('\t').unwrap_or((&inspect, ""));
+        // Images are used by name, listed as name:tag
+        let image = listed_image_name(image);
+        let name = image.strip_suffix(":latest").unwrap_or(image);
+        entries.push(CacheEntry::new(
+            CacheKind::Image,
+            name,
+            size.trim().parse().unwrap_or_default(),
+            fluent_builder::parse_timestamp(created).unwrap_or_default(),
+            usage,
+        ));
+    }
+
+    for (name, size) in volume_sizes(runtime)? {
+        let created = engine_output(&[
+            "volume",
+            "inspect",
+            "--format",
+            &runtime.time_template("CreatedAt"),
+            &name,
+        ])?;
+        entries.push(CacheEntry::new(
+            CacheKind::Volume,
+            &name,
+            size,
+            fluent_builder::parse_timestamp(&created).unwrap_or_default(),
+            usage,
+        ));
+    }
+    Ok(entries)
+}
+
+/// Existing cache volumes with their size in bytes
+fn volume_sizes(runtime: ContainerRuntime) -> Result<Vec<(String, u64)>> {
+    match runtime {
+        // Volume sizes are only reported by `system df`, human-readable
+        ContainerRuntime::Docker => {
+            let df: serde_json::Value = serde_json::from_str(&engine_output(&[
+                "system",
+                "df",
+                "-v",
+                "--format",
+                "{{json .}}",
+            ])?)
+            .context("Failed to parse Docker disk usage")?;
+            Ok(df["Volumes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|volume| {
+                    let name = volume["Name"]
+                        .as_str()
+                        .filter(|name| CACHE_VOLUMES.contains(name))?;
+                    Some((
+                        name.to_string(),
+                        volume["Size"]
+                            .as_str()
+                            .and_then(parse_docker_size)
+                            .unwrap_or_default(),
+                    ))
+                })
+                .collect())
+        }
+        // Podman's `system df` can't be verbose and formatted; rootless volumes are readable
+        // directories
+        ContainerRuntime::Podman => {
+            let names = engine_output(&["volume", "ls", "--format", "{{.Name}}"])?;
+            names
+                .lines()
+                .filter(|name| CACHE_VOLUMES.contains(name))
+                .map(|name| {
+                    let mountpoint =
+                        engine_output(&["volume", "inspect", "--format", "{{.Mountpoint}}", name])?;
+                    Ok((name.to_string(), dir_size(Path::new(&mountpoint))))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Total size of the files under `dir`; unreadable entries count as empty
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            Ok(kind) if kind.is_file() => entry.metadata().map_or(0, |metadata| metadata.len()),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Remove an image or volume listed by [`cache_entries`]
+pub fn remove_cache(entry: &CacheEntry) -> Result<()> {
+    let args = match entry.kind {
+        CacheKind::Image => {
+            crate::fact_cache().invalidate(&image_fact(&entry.name));
+            vec!["rmi", entry.name.as_str()]
+        }
+        CacheKind::Volume => vec!["volume", "rm", entry.name.as_str()],
+        CacheKind::File => bail!("{} is not a Docker cache", entry.name),
+    };
+    engine_output(&args).map(drop)
+}
+
+/// Trimmed stdout of a successful engine command
+fn engine_output(args: &[&str]) -> Result<String> {
+    let output = Command::new(engine())
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {} {}", engine(), args.join(" ")))?;
+    if !output.status.success() {
+        bail!(
+            "{} {} failed: {}",
+            engine(),
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Bytes of a size printed by Docker, e.g. `1.2GB` or `512kB` (decimal units)
+fn parse_docker_size(size: &str) -> Option<u64> {
+    let split = size.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = size.split_at(split);
+    let exponent = match unit.to_ascii_uppercase().as_str() {
+        "B" => 0,
+        "KB" => 1,
+        "MB" => 2,
+        "GB" => 3,
+        "TB" => 4,
+        _ => return None,
+    };
+    Some((number.trim().parse::<f64>().ok()? * 1000f64.powi(exponent)) as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -355,6 +1179,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_image_inspect() {
+        let image = "fluent-builder-v0.1.0-rust-1.83.0";
+        let docker = parse_image_inspect(
+            image,
+            "sha256:4a1c|linux/amd64|[\"fluentlabs/fluent-builder@sha256:9f2e\"]\n",
+        )
+        .unwrap();
+        assert_eq!(docker.image_digest, "sha256:4a1c");
+        assert_eq!(docker.platform, "linux/amd64");
+        assert_eq!(
+            docker.repo_digest.as_deref(),
+            Some("fluentlabs/fluent-builder@sha256:9f2e")
+        );
+
+        // Podman: bare IDs, and local builds only have a `localhost/` digest
+        let podman = parse_image_inspect(
+            image,
+            "4a1c|linux/amd64|[\"localhost/fluent-builder@sha256:9f2e\"]",
+        )
+        .unwrap();
+        assert_eq!(podman.image_digest, "sha256:4a1c");
+        assert_eq!(podman.repo_digest, None);
+        assert_eq!(podman.base_image, None);
+        assert!(parse_image_inspect(image, "").is_err());
+
+        // A local build records the pinned base it was built from
+        let local = parse_image_inspect(
+            image,
+            "sha256:4a1c|linux/amd64|[]|{\"xyz.fluent.builder.base-image\":\
+             \"fluentlabs/fluent-builder@sha256:9f2e\"}",
+        )
+        .unwrap();
+        assert_eq!(local.repo_digest, None);
+        assert_eq!(
+            local.base_image.as_deref(),
+            Some("fluentlabs/fluent-builder@sha256:9f2e")
+        );
+        assert_eq!(sdk_tag("0.4.1"), "v0.4.1");
+        assert_eq!(sdk_tag("v0.4.1"), "v0.4.1");
+    }
+
+    #[test]
+    fn test_container_workdir() {
+        assert_eq!(container_workdir(Path::new("")), "/workspace");
+        assert_eq!(
+            container_workdir(Path::new("contracts").join("token").as_path()),
+            "/workspace/contracts/token"
+        );
+    }
+
     #[test]
     fn test_format_toolchain_version() {
         assert_eq!(
@@ -373,6 +1248,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sandbox_args() {
+        let args = Sandbox::default().run_args();
+        for flag in [
+            "--read-only",
+            "--cap-drop",
+            "no-new-privileges",
+            "CARGO_HOME=/tmp/cargo",
+        ] {
+            assert!(args.iter().any(|a| a == flag), "missing {}", flag);
+        }
+        assert_eq!(args[..2], ["--network", "none"]);
+        assert!(!args.iter().any(|a| a.starts_with("seccomp=")));
+
+        let sandbox = Sandbox {
+            seccomp_profile: Some(PathBuf::from("/etc/fluent/seccomp.json")),
+            ..Default::default()
+        };
+        assert_eq!(
+            sandbox.run_args().last().unwrap(),
+            "seccomp=/etc/fluent/seccomp.json"
+        );
+    }
+
+    #[test]
+    fn test_git_dir_args_for_worktree() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let main = fluent_builder::canonicalize_path(tmp.path())
+            .unwrap()
+            .join("main");
+        std::fs::create_dir(&main).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .current_dir(&main)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "-q"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "init"]);
+        let worktree = main.with_file_name("feature");
+        git(&["worktree", "add", "-q", worktree.to_str().unwrap()]);
+
+        // A plain checkout carries its repository in the mount
+        assert!(git_dir_args(&main, ContainerRuntime::Docker)
+            .unwrap()
+            .is_empty());
+
+        let args = git_dir_args(&worktree, ContainerRuntime::Docker).unwrap();
+        assert!(
+            args.contains(&"GIT_DIR=/git/worktrees/feature".to_string()),
+            "{:?}",
+            args
+        );
+        assert!(args[1].contains(&fluent_builder::docker_host_path(&main.join(".git"))));
+    }
+
+    #[test]
+    fn test_podman_runtime() {
+        assert_eq!(
+            ContainerRuntime::of_engine("docker"),
+            ContainerRuntime::Docker
+        );
+        assert_eq!(
+            ContainerRuntime::of_engine("/usr/bin/podman"),
+            ContainerRuntime::Podman
+        );
+
+        let podman = ContainerRuntime::Podman;
+        assert_eq!(
+            podman.bind_mount("/src", "/workspace", true),
+            "type=bind,source=/src,target=/workspace,readonly,relabel=shared"
+        );
+        assert_eq!(
+            ContainerRuntime::Docker.bind_mount("/src", "/workspace", false),
+            "type=bind,source=/src,target=/workspace"
+        );
+
+        assert_eq!(
+            podman.pull_reference("fluentlabs/fluent-builder:v0.1.0"),
+            "docker.io/fluentlabs/fluent-builder:v0.1.0"
+        );
+        assert_eq!(
+            podman.pull_reference("ghcr.io/fluentlabs-xyz/fluent:latest"),
+            "ghcr.io/fluentlabs-xyz/fluent:latest"
+        );
+        assert_eq!(
+            ContainerRuntime::Docker.pull_reference("fluentlabs/fluent-builder:v0.1.0"),
+            "fluentlabs/fluent-builder:v0.1.0"
+        );
+
+        assert_eq!(
+            ContainerRuntime::Docker.time_template("Created"),
+            "{{.Created}}"
+        );
+        assert_eq!(
+            podman.time_template("Created"),
+            r#"{{.Created.Format "2006-01-02T15:04:05Z07:00"}}"#
+        );
+        assert_eq!(
+            listed_image_name("localhost/fluent-builder-v0.1.0-rust-1.83.0:latest"),
+            "fluent-builder-v0.1.0-rust-1.83.0:latest"
+        );
+    }
+
+    #[test]
+    fn test_parse_docker_size() {
+        assert_eq!(parse_docker_size("1.5GB"), Some(1_500_000_000));
+        assert_eq!(parse_docker_size("512kB"), Some(512_000));
+        assert_eq!(parse_docker_size("0B"), Some(0));
+        assert_eq!(parse_docker_size("N/A"), None);
+    }
+
     #[test]
     #[ignore] // Requires Docker to be running
     fn test_docker_available() {
@@ -2,21 +2,45 @@
 //!
 //! Compiles and verifies Rust smart contracts for the Fluent blockchain.
 
+mod alias;
+mod auth;
+mod config_profile;
 mod docker;
+mod network;
+mod nix;
+mod server;
+mod signer;
+mod simulate;
+mod upgrade;
 
 use clap::{Parser, Subcommand};
+use config_profile::ConfigProfile;
 use ethers::{
-    providers::{Http, Middleware, Provider},
-    types::Address,
+    middleware::SignerMiddleware,
+    providers::{Http, Middleware, Provider, ProviderError},
+    signers::Signer,
+    types::{Address, TransactionRequest, H256},
 };
 use eyre::{Context, Result};
 use fluent_builder::{
-    build, create_verification_archive, save_artifacts, verify, ArchiveOptions,
-    CompileConfig, GitInfo, VerificationStatus,
+    build, create_verification_archive, generate_changelog, save_artifacts, verify, AbiChange,
+    AbiNaming, ArchiveOptions, BuildCache, BuildFeatures, BuildOutcome, BuildScheduler,
+    BuildStatus, BuilderError, CacheEntry, CacheKind, CacheUsage, ChangeKind, CleanupGuard,
+    CodeUnwrap, CompileConfig, ConstructorArgs, ContractArtifacts, ContractRegistry,
+    ContractSnapshot, DeployArtifact, DeploymentRecord, Deployments, FactCache, GcLimits, GitInfo,
+    HashAlgorithm, Job, JobStore, MismatchHint, PrecheckIssue, ProcessError, ProjectTemplate,
+    Provenance, RegistryEntry, ReproducibilityDiff, SavedPaths, ScheduledBuild, SelectorCollisions,
+    SizeLimitPolicy, SizeLimits, SizeReport, StandardJsonInput, StandardJsonOutput, Timeouts,
+    Unwrapping, VerificationStatus, VerifyPolicy, WorkspaceGraph,
 };
+use schemars::JsonSchema;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
-use std::path::PathBuf;
+use signer::{DeploySigner, SignerArgs};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
 use tracing::Level;
 
 /// Fluent smart contract compiler and verifier
@@ -34,6 +58,49 @@ struct Cli {
     /// Suppress all logging except errors
     #[arg(short, long, global = true)]
     quiet: bool,
+
+    /// Forbid network access (cargo --offline, local Docker images only, no RPC)
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Cargo target directory (use with an absolute --output-dir for read-only sources)
+    #[arg(long, global = true, value_name = "DIR")]
+    target_dir: Option<PathBuf>,
+
+    /// `[profile.<NAME>]` of fluent.toml to take defaults from (default: `ci` on CI, else `dev`;
+    /// `none` for no profile)
+    #[arg(long, global = true, value_name = "NAME")]
+    config_profile: Option<String>,
+
+    /// Rebuild and re-check Docker and RPC state instead of using cached results
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Container runtime for reproducible builds (default: Docker if installed, else Podman)
+    #[arg(long, global = true, value_enum)]
+    runtime: Option<docker::ContainerRuntime>,
+
+    /// Shape of the JSON output (defaults to the latest version)
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        default_value_t = OUTPUT_SCHEMA_VERSION,
+        value_parser = clap::value_parser!(u32).range(1..=OUTPUT_SCHEMA_VERSION as i64)
+    )]
+    schema_version: u32,
+
+    /// On failure, zip the error, logs, resolved config, tool versions and git status, redacted,
+    /// for a bug report
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = SUPPORT_BUNDLE_FILE
+    )]
+    support_bundle: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -56,8 +123,15 @@ enum Commands {
         #[arg(long, value_delimiter = ' ')]
         features: Vec<String>,
 
-        /// Do not activate default features
-        #[arg(long, default_value_t = true)]
+        /// Do not activate default features; `--no-default-features=false` activates them
+        #[arg(
+            long,
+            default_value_t = true,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true",
+            action = clap::ArgAction::Set
+        )]
         no_default_features: bool,
 
         /// Allow compilation with uncommitted changes (uses archive source instead of git)
@@ -68,9 +142,124 @@ enum Commands {
         #[arg(long)]
         no_docker: bool,
 
+        /// Build in a pinned Nix environment instead of Docker
+        #[arg(long, conflicts_with = "no_docker")]
+        nix: bool,
+
+        /// Target or variant of a multi-contract package (bin name, lib name, or variant label with
+        /// --features)
+        #[arg(long, value_name = "NAME")]
+        target_name: Option<String>,
+
+        /// Names in the ABI and interface: `preserve` Rust names or `camel-case` function,
+        /// parameter and field names; renamed functions keep their selectors
+        #[arg(long, value_name = "POLICY", default_value = "preserve")]
+        abi_naming: AbiNaming,
+
+        /// Build with the features and profile of a `[networks.<NAME>]` section in fluent.toml
+        #[arg(long, value_name = "NAME")]
+        network: Option<String>,
+
+        /// Save to <OUTPUT_DIR>/<name>/<rwasm hash prefix>/ with a `latest` symlink, keeping
+        /// earlier builds
+        #[arg(long)]
+        content_addressed: bool,
+
+        /// Fail instead of writing degraded artifacts (unparsable routers, no git remote, no
+        /// Cargo.lock)
+        #[arg(long)]
+        strict: bool,
+
+        /// Router functions sharing a 4-byte selector: `error` fails the build, `warn` only logs
+        /// them
+        #[arg(long, value_name = "POLICY", default_value = "error")]
+        selector_collisions: SelectorCollisions,
+
+        /// Largest lib.wasm, e.g. `2M` (default: Fluent's deployment limit of 3 MiB)
+        #[arg(long, value_name = "SIZE", value_parser = fluent_builder::parse_size)]
+        max_wasm_size: Option<u64>,
+
+        /// Largest lib.rwasm, e.g. `4M` (default: no limit)
+        #[arg(long, value_name = "SIZE", value_parser = fluent_builder::parse_size)]
+        max_rwasm_size: Option<u64>,
+
+        /// Bytecode over a size limit: `error` fails the build, `warn` only logs it with a
+        /// breakdown
+        #[arg(long, value_name = "POLICY", default_value = "error")]
+        size_limit: SizeLimitPolicy,
+
+        /// Build twice and fail if the rWASM differs: `local` builds twice where the build runs,
+        /// `docker` also builds on the host
+        #[arg(
+            long,
+            value_name = "MODE",
+            value_enum,
+            num_args = 0..=1,
+            default_missing_value = "local"
+        )]
+        assert_reproducible: Option<ReproducibilityMode>,
+
+        /// RUSTFLAGS for the contract build, recorded in metadata.json
+        #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
+        rustflags: Option<String>,
+
+        /// Also put files matching this glob, relative to the project root, in sources.tar.gz
+        /// (repeatable), e.g. `assets/**`
+        #[arg(long, value_name = "GLOB")]
+        archive_include: Vec<String>,
+
+        /// Leave files matching this glob out of sources.tar.gz (repeatable); Cargo.toml,
+        /// Cargo.lock and the toolchain file are always kept
+        #[arg(long, value_name = "GLOB")]
+        archive_exclude: Vec<String>,
+
         /// Output JSON to stdout
         #[arg(long)]
         json: bool,
+
+        /// Read a solc-style standard JSON request on stdin and write the result to stdout (builds
+        /// in a sandbox container unless --no-docker)
+        #[arg(long, conflicts_with_all = ["nix", "json"])]
+        standard_json: bool,
+
+        /// Extra arguments for `cargo build`, recorded in metadata.json
+        #[arg(last = true, value_name = "CARGO_ARGS")]
+        cargo_args: Vec<String>,
+    },
+
+    /// Rebuild the contract whenever src/, Cargo.toml or rust-toolchain.toml change (builds
+    /// locally)
+    Watch {
+        /// Path to the project root
+        #[arg(default_value = ".")]
+        project_root: PathBuf,
+
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output_dir: PathBuf,
+
+        /// Build profile
+        #[arg(long, default_value = "release")]
+        profile: String,
+
+        /// Space-separated list of features
+        #[arg(long, value_delimiter = ' ')]
+        features: Vec<String>,
+
+        /// Do not activate default features; `--no-default-features=false` activates them
+        #[arg(
+            long,
+            default_value_t = true,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true",
+            action = clap::ArgAction::Set
+        )]
+        no_default_features: bool,
+
+        /// Print each build as a JSON line
+        #[arg(long)]
+        json: bool,
     },
 
     /// Verify a deployed contract
@@ -79,23 +268,28 @@ enum Commands {
         #[arg(default_value = ".")]
         project_root: PathBuf,
 
-        /// Contract address
+        /// Contract address, or `@name` of an alias in aliases.toml
         #[arg(long)]
         address: String,
 
-        /// Chain ID
-        #[arg(long)]
-        chain_id: u64,
+        /// Chain ID (default: the network's `chain_id`)
+        #[arg(long, required_unless_present = "network")]
+        chain_id: Option<u64>,
 
-        /// RPC endpoint
-        #[arg(long)]
-        rpc: String,
+        /// RPC endpoint (default: the network's `rpc`)
+        #[arg(long, required_unless_present = "network")]
+        rpc: Option<String>,
 
-        /// Build profile
-        #[arg(long, default_value = "release")]
-        profile: String,
+        /// Rebuild with the settings of a `[networks.<NAME>]` section in fluent.toml and use its
+        /// RPC
+        #[arg(long, value_name = "NAME")]
+        network: Option<String>,
 
-        /// Space-separated list of features
+        /// Build profile (default: the one recorded in --metadata, else release)
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Space-separated list of features (default: the ones recorded in --metadata)
         #[arg(long, value_delimiter = ' ')]
         features: Vec<String>,
 
@@ -103,624 +297,6561 @@ enum Commands {
         #[arg(long, default_value_t = true)]
         no_default_features: bool,
 
+        /// Target or variant of a multi-contract package, as passed to `compile` (default: the
+        /// one recorded in --metadata)
+        #[arg(long, value_name = "NAME")]
+        target_name: Option<String>,
+
+        /// RUSTFLAGS for the rebuild, as passed to `compile` (default: the ones recorded in
+        /// --metadata)
+        #[arg(long, value_name = "FLAGS", allow_hyphen_values = true)]
+        rustflags: Option<String>,
+
+        /// metadata.json of the deployment: rebuild with its settings and sources, and explain
+        /// mismatches
+        #[arg(long)]
+        metadata: Option<PathBuf>,
+
+        /// Rebuild inside the Docker image recorded in --metadata, pinned by its digest
+        #[arg(long, requires = "metadata")]
+        docker: bool,
+
+        /// ABI-encoded constructor arguments of the deployment
+        #[arg(long, value_name = "HEX", conflicts_with = "constructor_values")]
+        constructor_args: Option<String>,
+
+        /// Constructor argument values, encoded with the contract ABI
+        #[arg(long, value_name = "VALUE", num_args = 1..)]
+        constructor_values: Vec<String>,
+
+        /// Deployment transaction, to check the constructor arguments against
+        #[arg(long, value_name = "HASH")]
+        deployment_tx: Option<String>,
+
+        /// Record the verified deployment in deployments.json
+        #[arg(long)]
+        record: bool,
+
+        /// Verify the code the address had at this block (needs an archive node), e.g. before an
+        /// upgrade
+        #[arg(long, value_name = "N")]
+        at_block: Option<u64>,
+
+        /// Verify against this git tag or commit instead of the working tree
+        #[arg(long, value_name = "REF")]
+        git_ref: Option<String>,
+
+        /// Header to strip from the deployed code: auto (known Fluent headers), none, or a hex
+        /// prefix
+        #[arg(long, value_name = "MODE", default_value = "auto")]
+        unwrap: CodeUnwrap,
+
+        /// Hash to compare bytecode with: sha256, or keccak256 (the chain's EXTCODEHASH)
+        #[arg(long, value_name = "ALGORITHM", default_value = "sha256")]
+        hash_algorithm: HashAlgorithm,
+
         /// Output JSON
         #[arg(long)]
         json: bool,
-    },
 
-    /// Docker-related utilities
-    Docker {
-        #[command(subcommand)]
-        command: DockerCommands,
+        /// Extra arguments for `cargo build`, as passed to `compile`
+        #[arg(last = true, value_name = "CARGO_ARGS")]
+        cargo_args: Vec<String>,
     },
-}
 
-#[derive(Subcommand, Debug)]
-enum DockerCommands {
-    /// Clean up old Docker images
-    Clean {
-        /// Number of recent images to keep
-        #[arg(long, default_value = "5")]
-        keep: usize,
+    /// Show size, hash and recorded builder provenance of a deployed contract
+    InspectDeployed {
+        /// Contract address, or `@name` of an alias in aliases.toml
+        #[arg(long)]
+        address: String,
+
+        /// Chain ID
+        #[arg(long)]
+        chain_id: u64,
+
+        /// RPC endpoint
+        #[arg(long)]
+        rpc: String,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
     },
-}
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "status")]
-enum Output {
-    #[serde(rename = "success")]
-    Success {
-        #[serde(flatten)]
-        data: SuccessData,
+    /// Check built artifacts against their metadata.json and the sources before deploying
+    Precheck {
+        /// Path to the project root
+        #[arg(default_value = ".")]
+        project_root: PathBuf,
+
+        /// Artifacts directory (default: the `<name>.wasm` directory in `<project_root>/out`)
+        #[arg(long, value_name = "DIR")]
+        artifacts_dir: Option<PathBuf>,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
     },
 
-    #[serde(rename = "error")]
-    Error { error_type: String, message: String },
-}
+    /// Print the topic0 hash of every event of the built contract, for indexers
+    Topics {
+        /// Path to the project root
+        #[arg(default_value = ".")]
+        project_root: PathBuf,
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "command")]
-enum SuccessData {
-    #[serde(rename = "compile")]
-    Compile {
-        contract_name: String,
-        rwasm_hash: String,
-        wasm_size: usize,
-        rwasm_size: usize,
-        has_abi: bool,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        output_dir: Option<String>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        git_info: Option<GitInfoJson>,
-        source_type: String,
+        /// Artifacts directory (default: the `<name>.wasm` directory in `<project_root>/out`)
+        #[arg(long, value_name = "DIR")]
+        artifacts_dir: Option<PathBuf>,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
     },
 
-    #[serde(rename = "verify")]
-    Verify {
-        verified: bool,
-        contract_name: String,
-        expected_hash: String,
-        actual_hash: String,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        abi: Option<serde_json::Value>,
-        compiler_version: String,
-        sdk_version: String,
+    /// Attribute the size of lib.wasm to sections, crates and functions
+    Size {
+        /// Path to the project root
+        #[arg(default_value = ".")]
+        project_root: PathBuf,
+
+        /// Artifacts directory (default: the `<name>.wasm` directory in `<project_root>/out`)
+        #[arg(long, value_name = "DIR")]
+        artifacts_dir: Option<PathBuf>,
+
+        /// WASM file to analyze instead of the artifacts' lib.wasm
+        #[arg(long, value_name = "FILE", conflicts_with = "artifacts_dir")]
+        wasm: Option<PathBuf>,
+
+        /// Number of crates and functions to list
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
     },
-}
 
-#[derive(Debug, Serialize)]
-struct GitInfoJson {
-    commit: String,
-    branch: String,
-    remote_url: String,
-    is_clean: bool,
-}
+    /// Generate code calling an external contract from its ABI
+    Bindgen {
+        /// abi.json, abi.txt, or a JSON array of declarations
+        #[arg(long, value_name = "FILE")]
+        abi: PathBuf,
 
-impl From<&GitInfo> for GitInfoJson {
-    fn from(info: &GitInfo) -> Self {
-        Self {
-            commit: info.commit_hash_short.clone(),
-            branch: info.branch.clone(),
-            remote_url: info.remote_url.clone(),
-            is_clean: !info.is_dirty,
-        }
-    }
-}
+        /// Target of the generated code; `rust-sdk` is a `#[client]` trait for fluentbase-sdk,
+        /// `alloy` an `alloy::sol!` interface for off-chain callers, `typescript` the ABI `as
+        /// const` for viem and wagmi
+        #[arg(long, value_parser = ["rust-sdk", "alloy", "typescript"], default_value = "rust-sdk")]
+        lang: String,
 
-fn main() {
-    let cli = Cli::parse();
+        /// Contract name (default: the ABI file name, or its artifacts directory for abi.json)
+        #[arg(long)]
+        name: Option<String>,
 
-    // Initialize logging
-    let log_level = if cli.quiet {
-        Level::ERROR
-    } else if cli.verbose {
-        Level::DEBUG
-    } else {
-        Level::INFO
-    };
+        /// Write the code to a file instead of stdout
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
 
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_target(false)
-        .with_writer(std::io::stderr)
-        .init();
+    /// Generate a Markdown changelog between two contract versions
+    Changelog {
+        /// Old version: git ref or artifacts directory
+        old: String,
 
-    let result = match cli.command {
-        Commands::Compile {
-            project_root,
-            output_dir,
-            profile,
-            features,
-            no_default_features,
-            allow_dirty,
-            no_docker,
-            json,
-        } => run_compile(
-            project_root,
-            output_dir,
-            profile,
-            features,
-            no_default_features,
-            allow_dirty,
-            no_docker,
-            json,
-        ),
-        Commands::Verify {
-            project_root,
-            address,
-            chain_id,
-            rpc,
-            profile,
-            features,
-            no_default_features,
-            json,
-        } => {
-            let runtime = tokio::runtime::Runtime::new().expect("Failed to create async runtime");
-            runtime.block_on(run_verify(
-                project_root,
-                address,
-                chain_id,
-                rpc,
-                profile,
-                features,
-                no_default_features,
-                json,
-            ))
-        }
-        Commands::Docker { command } => match command {
-            DockerCommands::Clean { keep } => docker::cleanup_old_images(keep),
-        },
-    };
+        /// New version: git ref or artifacts directory
+        new: String,
 
-    if let Err(e) = result {
-        output_error(e);
-        std::process::exit(1);
-    }
-}
+        /// Path to the project root (used to build git refs)
+        #[arg(long, default_value = ".")]
+        project_root: PathBuf,
 
-/// Early version detection for both Docker and local compilation
-fn detect_project_versions(project_root: &PathBuf) -> Result<(String, String)> {
-    // Read Rust version using existing function from builder
-    let rust_version = fluent_builder::read_rust_toolchain_version(project_root)?;
-    
-    // Read SDK version using existing function from builder
-    let sdk_version = fluent_builder::read_sdk_version_from_cargo_lock(project_root)?;
-    
-    tracing::info!("Detected Rust version: '{}'", rust_version);
-    tracing::info!("Detected SDK version: '{}'", sdk_version);
-    
-    Ok((rust_version, sdk_version))
-}
+        /// Write the changelog to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare two ABIs and flag changes that break existing callers; exits with 1 if any does
+    AbiDiff {
+        /// Old ABI: abi.json, abi.txt, or a JSON array of declarations
+        old: PathBuf,
+
+        /// New ABI
+        new: PathBuf,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Build all contracts in a workspace affected by changes since the last batch build
+    BuildWorkspace {
+        /// Path to the workspace root
+        #[arg(default_value = ".")]
+        workspace_root: PathBuf,
+
+        /// Output directory
+        #[arg(short, long, default_value = "out")]
+        output_dir: PathBuf,
+
+        /// Rebuild every contract regardless of the build cache
+        #[arg(long)]
+        force: bool,
+
+        /// Contracts built at once
+        #[arg(short, long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Build the contracts declared in contracts.toml
+    CompileAll {
+        /// Directory of contracts.toml (default: the closest one at or above the current directory)
+        #[arg(long, value_name = "DIR")]
+        registry: Option<PathBuf>,
+
+        /// Only build these contracts
+        #[arg(long = "contract", value_name = "NAME")]
+        contracts: Vec<String>,
+
+        /// Build the contracts deployed to this network, with its features
+        #[arg(long, value_name = "NAME")]
+        network: Option<String>,
+
+        /// Contracts built at once
+        #[arg(short, long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+
+        /// Do not use Docker for compilation (faster but less reproducible)
+        #[arg(long)]
+        no_docker: bool,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Verify the recorded deployments of the contracts declared in contracts.toml
+    VerifyAll {
+        /// Network whose deployments to verify
+        #[arg(long, value_name = "NAME")]
+        network: String,
+
+        /// RPC endpoint (default: the network's rpc)
+        #[arg(long)]
+        rpc: Option<String>,
+
+        /// Directory of contracts.toml (default: the closest one at or above the current directory)
+        #[arg(long, value_name = "DIR")]
+        registry: Option<PathBuf>,
+
+        /// Only verify these contracts
+        #[arg(long = "contract", value_name = "NAME")]
+        contracts: Vec<String>,
+    },
+
+    /// Deploy the built contracts declared in contracts.toml, in order
+    Deploy {
+        /// Network to deploy to
+        #[arg(long, value_name = "NAME")]
+        network: String,
+
+        /// RPC endpoint (default: the network's rpc)
+        #[arg(long)]
+        rpc: Option<String>,
+
+        /// Directory of contracts.toml (default: the closest one at or above the current directory)
+        #[arg(long, value_name = "DIR")]
+        registry: Option<PathBuf>,
+
+        /// Only deploy these contracts
+        #[arg(long = "contract", value_name = "NAME")]
+        contracts: Vec<String>,
+
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+
+    /// Deploy contracts of contracts.toml to a local fork of a network and call their view
+    /// functions
+    SimulateDeploy {
+        /// Network to fork
+        #[arg(long, value_name = "NAME")]
+        network: String,
+
+        /// RPC endpoint to fork (default: the network's rpc)
+        #[arg(long)]
+        rpc: Option<String>,
+
+        /// Use a running fork node (e.g. a Fluent local node) instead of starting one in Docker
+        #[arg(long, value_name = "URL")]
+        fork_rpc: Option<String>,
+
+        /// Fluent node image to fork with, pinned by digest (`<image>@sha256:<digest>`)
+        ///
+        /// The image must provide an `anvil` executable taking anvil's fork options.
+        #[arg(
+            long,
+            value_name = "IMAGE",
+            required_unless_present = "fork_rpc",
+            value_parser = docker::parse_pinned_image
+        )]
+        fork_image: Option<String>,
+
+        /// Directory of contracts.toml (default: the closest one at or above the current directory)
+        #[arg(long, value_name = "DIR")]
+        registry: Option<PathBuf>,
+
+        /// Only simulate these contracts
+        #[arg(long = "contract", value_name = "NAME")]
+        contracts: Vec<String>,
+
+        /// Deployer to fund on the fork (default: a random key)
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+
+    /// Deploy a new implementation of a proxied contract and switch the proxy to it
+    ///
+    /// Refuses to send anything if the new implementation drops functions or
+    /// moves storage of the verified implementation the proxy points to.
+    Upgrade {
+        /// Network of the proxy
+        #[arg(long, value_name = "NAME")]
+        network: String,
+
+        /// RPC endpoint (default: the network's rpc)
+        #[arg(long)]
+        rpc: Option<String>,
+
+        /// Directory of contracts.toml (default: the closest one at or above the current directory)
+        #[arg(long, value_name = "DIR")]
+        registry: Option<PathBuf>,
+
+        /// Contract of contracts.toml to deploy as the new implementation
+        #[arg(long, value_name = "NAME")]
+        contract: String,
+
+        /// Proxy address or @alias
+        #[arg(long, value_name = "ADDRESS")]
+        proxy: String,
+
+        /// Admin function of the proxy that switches the implementation
+        #[arg(long, value_name = "SIGNATURE", default_value = "upgradeTo(address)")]
+        upgrade_function: String,
+
+        #[command(flatten)]
+        signer: SignerArgs,
+    },
+
+    /// Build a release, write versioned artifacts and create an annotated git tag
+    TagRelease {
+        /// Release version (vX.Y.Z)
+        version: String,
+
+        /// Path to the project root
+        #[arg(long, default_value = ".")]
+        project_root: PathBuf,
+
+        /// Do not use Docker for compilation (faster but less reproducible)
+        #[arg(long)]
+        no_docker: bool,
+    },
+
+    /// Create a ready-to-build contract project
+    New {
+        /// Package name, also the name of the created directory
+        name: String,
+
+        /// Starting point: `erc20`, `storage` or `blank`
+        #[arg(long, value_name = "TEMPLATE", default_value = "blank")]
+        template: ProjectTemplate,
+
+        /// SDK version to pin, e.g. 0.6.0 (default: the latest release)
+        #[arg(long, value_name = "VERSION")]
+        sdk_version: Option<String>,
+
+        /// Directory to create (default: ./<NAME>)
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+
+    /// Apply structural fixes required by newer SDK/builder versions
+    Migrate {
+        /// Path to the project root
+        #[arg(default_value = ".")]
+        project_root: PathBuf,
+
+        /// fluentbase-sdk feature renamed by the SDK, as OLD=NEW (repeatable)
+        #[arg(long, value_name = "OLD=NEW", value_parser = fluent_builder::parse_feature_rename)]
+        rename_feature: Vec<(String, String)>,
+
+        /// Only show the diff
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
+
+        /// Apply without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// List SDK releases and pin a project to one
+    Sdk {
+        #[command(subcommand)]
+        command: SdkCommands,
+    },
+
+    /// Run the verification HTTP server (POST /jobs, GET /jobs/<id>)
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        bind: String,
+
+        /// Verifications running at the same time
+        #[arg(long, default_value_t = 2)]
+        workers: usize,
+
+        /// Verifications waiting for a worker before requests are rejected
+        #[arg(long, default_value_t = 64)]
+        queue: usize,
+
+        /// Directory for per-job target and output directories and the job store
+        #[arg(long)]
+        work_dir: Option<PathBuf>,
+
+        /// File with the `[server]` API keys and limits (default: ./fluent.toml if present)
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Build submissions in a sandbox container (no network, read-only, resource limits);
+        /// implied by a `[server.sandbox]` section
+        #[arg(long)]
+        sandbox: bool,
+
+        /// Keep the directories of failed jobs under the work directory for debugging
+        #[arg(long)]
+        keep_failed: bool,
+
+        /// Accept requests without an API key when no `[server.anonymous]` limits are configured
+        #[arg(long)]
+        allow_unauthenticated: bool,
+    },
+
+    /// Export verified deployments with their metadata and sources, for explorer backfill
+    Export {
+        /// Project roots whose deployments.json to export
+        #[arg(default_value = ".")]
+        project_roots: Vec<PathBuf>,
+
+        /// Write NDJSON to a file instead of stdout
+        #[arg(short, long, conflicts_with = "output_dir")]
+        output: Option<PathBuf>,
+
+        /// Write a `<chain_id>/<address>/` directory per build instead of NDJSON
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+    },
+
+    /// Print the genesis `alloc` entry preloading a built contract at an address, for local devnets
+    ExportGenesis {
+        /// Path to the project root
+        #[arg(default_value = ".")]
+        project_root: PathBuf,
+
+        /// Address the contract is preloaded at
+        #[arg(long)]
+        address: String,
+
+        /// Artifacts directory (default: the `<name>.wasm` directory in `<project_root>/out`)
+        #[arg(long, value_name = "DIR")]
+        artifacts_dir: Option<PathBuf>,
+
+        /// Balance of the contract in wei, decimal or 0x-prefixed hex
+        #[arg(long, value_name = "WEI")]
+        balance: Option<String>,
+
+        /// Storage slot to preset, as SLOT=VALUE (32-byte hex words); repeatable
+        #[arg(long, value_name = "SLOT=VALUE")]
+        storage: Vec<String>,
+
+        /// Add the entry to the `alloc` of this genesis file instead of printing it
+        #[arg(long, value_name = "FILE")]
+        genesis: Option<PathBuf>,
+    },
+
+    /// Inspect verification server jobs
+    Job {
+        #[command(subcommand)]
+        command: JobCommands,
+    },
+
+    /// Print the JSON Schema of a file or of the --json output, or the server's OpenAPI description
+    Schema {
+        /// Schema name (`openapi` for the server API); lists available schemas if omitted
+        name: Option<String>,
+    },
+
+    /// Manage contract address aliases (aliases.toml)
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommands,
+    },
+
+    /// Saved artifact directories
+    Artifacts {
+        #[command(subcommand)]
+        command: ArtifactsCommands,
+    },
+
+    /// Builder images, cargo volumes and other caches kept between builds
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Docker-related utilities
+    Docker {
+        #[command(subcommand)]
+        command: DockerCommands,
+    },
+
+    /// ABI tooling
+    #[command(hide = true)]
+    Abi {
+        #[command(subcommand)]
+        command: AbiCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AliasCommands {
+    /// Name an address, usable as `@NAME` wherever an address is accepted
+    Add {
+        /// Alias name
+        name: String,
+
+        /// Contract address
+        address: String,
+
+        /// Network of fluent.toml the address is on
+        #[arg(
+            long,
+            required_unless_present = "chain_id",
+            conflicts_with = "chain_id"
+        )]
+        network: Option<String>,
+
+        /// Chain ID the address is on
+        #[arg(long)]
+        chain_id: Option<u64>,
+    },
+
+    /// List aliases
+    List {
+        /// Only aliases of this network or chain ID
+        #[arg(long)]
+        network: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ArtifactsCommands {
+    /// Upgrade an artifacts directory written by an older builder to the current layout
+    Migrate {
+        /// Artifacts directory (e.g. out/token.wasm)
+        dir: PathBuf,
+
+        /// Project the artifacts were built from, to derive reproducibility.json
+        #[arg(long)]
+        project_root: Option<PathBuf>,
+
+        /// Only show the diff
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
+
+        /// Apply without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DockerCommands {
+    /// Clean up old Docker images
+    Clean {
+        /// Number of recent images to keep
+        #[arg(long, default_value = "5")]
+        keep: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommands {
+    /// Evict least recently used caches until they fit the limits
+    #[command(group = clap::ArgGroup::new("limits").required(true).multiple(true))]
+    Gc {
+        /// Total size to stay under, e.g. `20G`
+        #[arg(
+            long,
+            value_name = "SIZE",
+            value_parser = fluent_builder::parse_size,
+            group = "limits"
+        )]
+        max_size: Option<u64>,
+
+        /// Evict caches unused for longer, e.g. `30d`
+        #[arg(long, value_name = "AGE", value_parser = fluent_builder::parse_age, group = "limits")]
+        max_age: Option<std::time::Duration>,
+
+        /// Only list what would be evicted
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the size, last use and hit rate of each cache
+    Stats {
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum JobCommands {
+    /// Show the status, stage, logs and result of a job
+    Status {
+        /// Job id returned by `POST /jobs`
+        id: String,
+
+        /// Work directory of the server (`serve --work-dir`)
+        #[arg(long)]
+        work_dir: Option<PathBuf>,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Queue the builds of an `export` dump as jobs, verified when the server rebuilds them
+    Import {
+        /// NDJSON file, `-` for stdin, or a directory written with `export --output-dir`
+        input: PathBuf,
+
+        /// Work directory of the server (`serve --work-dir`)
+        #[arg(long)]
+        work_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SdkCommands {
+    /// List published SDK versions with their builder image and rWASM translator
+    List {
+        /// Path to the project root (marks the SDK version it uses)
+        #[arg(long, default_value = ".")]
+        project_root: PathBuf,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Update Cargo.toml and Cargo.lock to an SDK version
+    Upgrade {
+        /// SDK version, e.g. 0.6.0
+        version: String,
+
+        /// Path to the project root
+        #[arg(long, default_value = ".")]
+        project_root: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AbiCommands {
+    /// Convert an ABI between JSON and human-readable declarations (format of FILE is detected)
+    Convert {
+        /// abi.json, abi.txt, or a JSON array of declarations
+        file: PathBuf,
+    },
+
+    /// Print the ABI as a JSON line whenever the sources change, for editors and other tools
+    ///
+    /// While the sources don't parse, the last ABI is printed again with a `stale` error.
+    Watch {
+        /// Path to the project root
+        #[arg(default_value = ".")]
+        project_root: PathBuf,
+
+        /// Names in the ABI: `preserve` Rust names or `camel-case` function, parameter and field
+        /// names
+        #[arg(long, value_name = "POLICY", default_value = "preserve")]
+        abi_naming: AbiNaming,
+
+        /// How often to check the sources, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 300)]
+        interval_ms: u64,
+    },
+
+    /// Check selector generation against bundled solc reference vectors
+    #[command(hide = true)]
+    SelfTest {
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Builds compared by `compile --assert-reproducible`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ReproducibilityMode {
+    /// Two builds where the compilation runs, in Docker unless --no-docker
+    Local,
+    /// A build on the host against the Docker build
+    Docker,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "status")]
+enum Output {
+    #[serde(rename = "success")]
+    Success {
+        #[serde(flatten)]
+        data: SuccessData,
+    },
+
+    #[serde(rename = "error")]
+    Error { error_type: String, message: String },
+
+    #[serde(rename = "cancelled")]
+    Cancelled { message: String },
+}
+
+/// Exit code used when the command is interrupted (128 + SIGINT)
+const EXIT_CANCELLED: i32 = 130;
+
+/// Current version of the JSON output shape
+///
+/// Bump when a field is removed or renamed, keep the previous shape available
+/// through `--schema-version` and check in the new schema under `schemas/`.
+/// Version 1 is the unversioned output of earlier releases.
+const OUTPUT_SCHEMA_VERSION: u32 = 2;
+
+/// Output shape selected with `--schema-version`
+static SCHEMA_VERSION: OnceLock<u32> = OnceLock::new();
+
+/// Cached Docker and RPC checks, configured by `--no-cache`
+static FACTS: OnceLock<FactCache> = OnceLock::new();
+
+/// Directory of cached bytecode; `None` with `--no-cache`
+static BUILD_CACHE: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Defaults of the selected `[profile.<name>]` of fluent.toml
+static CONFIG_PROFILE: OnceLock<ConfigProfile> = OnceLock::new();
+
+/// Default path of `--support-bundle`
+const SUPPORT_BUNDLE_FILE: &str = "fluent-builder-support.zip";
+
+/// Bundle written for a nested run in a container or Nix shell, relative to the project root
+const NESTED_SUPPORT_BUNDLE_FILE: &str = ".fluent-builder-support.zip";
+
+/// Where `--support-bundle` writes the bundle on failure
+static SUPPORT_BUNDLE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Log output kept for the support bundle
+static CAPTURED_LOGS: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// Compile configuration of the running command, for the support bundle
+static RESOLVED_CONFIG: Mutex<Option<CompileConfig>> = Mutex::new(None);
+
+/// Bundle of a nested run, merged into the support bundle
+static NESTED_SUPPORT_BUNDLE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Active config profile; empty when none is selected
+fn active_profile() -> &'static ConfigProfile {
+    CONFIG_PROFILE.get_or_init(ConfigProfile::default)
+}
+
+/// Cache of environment checks shared by all commands
+pub(crate) fn fact_cache() -> &'static FactCache {
+    FACTS.get_or_init(|| {
+        FactCache::on_disk(FactCache::default_path(), fluent_builder::DEFAULT_FACT_TTL)
+    })
+}
+
+/// Where builds store and reuse compiled bytecode
+fn build_cache() -> Option<PathBuf> {
+    BUILD_CACHE
+        .get_or_init(|| Some(fluent_builder::default_build_cache_dir()))
+        .clone()
+}
+
+/// Output tagged with the schema version it follows
+#[derive(Serialize, JsonSchema)]
+struct VersionedOutput<'a> {
+    output_schema_version: u32,
+    #[serde(flatten)]
+    output: &'a Output,
+}
+
+impl Output {
+    /// Serialize in the requested output schema version
+    fn to_json(&self) -> serde_json::Result<String> {
+        match SCHEMA_VERSION
+            .get()
+            .copied()
+            .unwrap_or(OUTPUT_SCHEMA_VERSION)
+        {
+            1 => serde_json::to_string(self),
+            version => serde_json::to_string(&VersionedOutput {
+                output_schema_version: version,
+                output: self,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "command")]
+enum SuccessData {
+    #[serde(rename = "compile")]
+    Compile {
+        contract_name: String,
+        rwasm_hash: String,
+        wasm_size: usize,
+        rwasm_size: usize,
+        has_abi: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output_dir: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        git_info: Option<GitInfoJson>,
+        source_type: String,
+        /// Score of `reproducibility.json`, 0-100
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reproducibility_score: Option<u32>,
+        /// Comparison of the builds made for --assert-reproducible
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reproducibility_check: Option<Box<ReproducibilityDiff>>,
+    },
+
+    #[serde(rename = "verify")]
+    Verify {
+        verified: bool,
+        contract_name: String,
+        expected_hash: String,
+        actual_hash: String,
+        /// Algorithm of `expected_hash` and `actual_hash`
+        hash_algorithm: HashAlgorithm,
+        /// Header stripped from the deployed code before hashing
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unwrapping: Option<Box<Unwrapping>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        build_settings_mismatch: Option<String>,
+        /// Hex encoded constructor arguments of the deployment
+        #[serde(skip_serializing_if = "Option::is_none")]
+        constructor_args: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        hints: Vec<MismatchHint>,
+        /// HTML report comparing the recorded and the local build
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mismatch_report: Option<Box<PathBuf>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        abi: Option<Box<serde_json::Value>>,
+        compiler_version: String,
+        sdk_version: String,
+    },
+
+    #[serde(rename = "inspect_deployed")]
+    InspectDeployed {
+        address: String,
+        chain_id: u64,
+        bytecode_hash: String,
+        /// keccak256 of the bytecode, as returned by `EXTCODEHASH`
+        code_hash: String,
+        bytecode_size: usize,
+        /// Header stripped from the code before hashing
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unwrapping: Option<Unwrapping>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        provenance: Option<Provenance>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        build_features: Option<BuildFeatures>,
+    },
+
+    #[serde(rename = "precheck")]
+    Precheck {
+        passed: bool,
+        contract_name: String,
+        artifacts_dir: String,
+        issues: Vec<PrecheckIssue>,
+    },
+
+    #[serde(rename = "topics")]
+    Topics {
+        contract_name: String,
+        /// topic0 by event signature
+        topics: std::collections::BTreeMap<String, String>,
+    },
+
+    #[serde(rename = "size")]
+    Size {
+        #[serde(flatten)]
+        report: SizeReport,
+    },
+
+    #[serde(rename = "cache_stats")]
+    CacheStats {
+        total_size: u64,
+        caches: Vec<CacheEntry>,
+    },
+
+    #[serde(rename = "cache_gc")]
+    CacheGc {
+        dry_run: bool,
+        freed: u64,
+        evicted: Vec<CacheEntry>,
+        /// Caches that could not be removed, e.g. images used by a container
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        failed: Vec<String>,
+    },
+
+    #[serde(rename = "job_status")]
+    JobStatus { job: Box<Job> },
+
+    #[serde(rename = "sdk_list")]
+    SdkList {
+        releases: Vec<SdkReleaseJson>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        current: Option<String>,
+    },
+
+    #[serde(rename = "abi_self_test")]
+    AbiSelfTest {
+        passed: bool,
+        fixtures: usize,
+        signatures: usize,
+        failures: Vec<String>,
+    },
+
+    #[serde(rename = "abi_diff")]
+    AbiDiff {
+        /// Whether any change breaks existing callers or log decoders
+        breaking: bool,
+        changes: Vec<AbiChange>,
+    },
+
+    #[serde(rename = "build_workspace")]
+    BuildWorkspace {
+        contracts: Vec<ContractBuildJson>,
+        /// Contracts that failed or were skipped; the command exits non-zero unless empty
+        failed: Vec<String>,
+    },
+
+    #[serde(rename = "compile_all")]
+    CompileAll {
+        contracts: Vec<ContractBuildJson>,
+        /// Contracts that failed or were skipped; the command exits non-zero unless empty
+        failed: Vec<String>,
+    },
+}
+
+/// Outcome of one contract of `build-workspace` or `compile-all`
+#[derive(Debug, Serialize, JsonSchema)]
+struct ContractBuildJson {
+    name: String,
+    status: BuildStatus,
+    duration_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rwasm_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dir: Option<String>,
+    /// Why the contract failed or was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<&BuildOutcome<(String, PathBuf)>> for ContractBuildJson {
+    fn from(outcome: &BuildOutcome<(String, PathBuf)>) -> Self {
+        Self {
+            name: outcome.name.clone(),
+            status: outcome.status,
+            duration_secs: outcome.duration.as_secs_f64(),
+            rwasm_hash: outcome.value.as_ref().map(|(hash, _)| hash.clone()),
+            output_dir: outcome
+                .value
+                .as_ref()
+                .map(|(_, dir)| dir.display().to_string()),
+            error: outcome.error.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct SdkReleaseJson {
+    version: String,
+    tag: String,
+    commit: String,
+    docker_image: String,
+    rwasm_translator: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct GitInfoJson {
+    commit: String,
+    branch: String,
+    remote_url: String,
+    is_clean: bool,
+}
+
+impl From<&GitInfo> for GitInfoJson {
+    fn from(info: &GitInfo) -> Self {
+        Self {
+            commit: info.commit_hash_short.clone(),
+            branch: info.branch.clone(),
+            remote_url: info.remote_url.clone(),
+            is_clean: !info.is_dirty,
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    SCHEMA_VERSION
+        .set(cli.schema_version)
+        .expect("schema version set once");
+    if cli.no_cache {
+        FACTS
+            .set(FactCache::disabled())
+            .expect("fact cache set once");
+        BUILD_CACHE.set(None).expect("build cache set once");
+    }
+    if let Some(runtime) = cli.runtime {
+        std::env::set_var(docker::CONTAINER_ENGINE_ENV, runtime.command());
+    }
+
+    // Profiles are read from the fluent.toml of the project being built
+    let config_dir = match &cli.command {
+        Commands::Compile { project_root, .. }
+        | Commands::Watch { project_root, .. }
+        | Commands::TagRelease { project_root, .. } => project_root.clone(),
+        _ => PathBuf::from("."),
+    };
+    if let Some(path) = &cli.support_bundle {
+        SUPPORT_BUNDLE
+            .set(path.clone())
+            .expect("support bundle set once");
+    }
+    let profile = match ConfigProfile::select(&config_dir, cli.config_profile.as_deref()) {
+        Ok(profile) => profile,
+        Err(e) => exit_with_error(e, &config_dir),
+    };
+    if let Some(cache_dir) = &profile.cache_dir {
+        std::env::set_var(fluent_builder::CACHE_DIR_ENV, cache_dir);
+    }
+    let target_dir = cli
+        .target_dir
+        .clone()
+        .or_else(|| profile.target_dir.clone());
+    CONFIG_PROFILE
+        .set(profile)
+        .expect("config profile set once");
+
+    // Initialize logging
+    let log_level = if cli.quiet {
+        Level::ERROR
+    } else if cli.verbose {
+        Level::DEBUG
+    } else {
+        Level::INFO
+    };
+
+    // Colors would end up in the bundled logs as escape codes
+    let capture = cli.support_bundle.is_some();
+    tracing_subscriber::fmt()
+        .with_max_level(log_level)
+        .with_target(false)
+        .with_ansi(!capture)
+        .with_writer(move || LogWriter { capture })
+        .init();
+
+    if let Err(e) = ctrlc::set_handler(handle_interrupt) {
+        tracing::warn!("Failed to install signal handler: {}", e);
+    }
+
+    // Cargo invocations made by library helpers (workspace metadata, changelog
+    // builds) do not take a CompileConfig, so rely on cargo's own switch as well
+    let offline = cli.offline;
+    if offline {
+        std::env::set_var("CARGO_NET_OFFLINE", "true");
+    }
+    // Picked up by CompileConfig::target_directory and by cargo itself
+    if let Some(target_dir) = &target_dir {
+        let target_dir = std::env::current_dir()
+            .map(|cwd| cwd.join(target_dir))
+            .unwrap_or_else(|_| target_dir.clone());
+        std::env::set_var("CARGO_TARGET_DIR", target_dir);
+    }
+
+    let result = match cli.command {
+        Commands::Compile {
+            standard_json: true,
+            no_docker,
+            ..
+        } => run_standard_json(no_docker, offline),
+        Commands::Compile {
+            project_root,
+            nix: true,
+            ..
+        } => run_nix_compile(project_root, offline),
+        Commands::Compile {
+            project_root,
+            output_dir,
+            profile,
+            features,
+            no_default_features,
+            allow_dirty,
+            no_docker,
+            target_name,
+            abi_naming,
+            network,
+            content_addressed,
+            strict,
+            selector_collisions,
+            max_wasm_size,
+            max_rwasm_size,
+            size_limit,
+            assert_reproducible,
+            rustflags,
+            archive_include,
+            archive_exclude,
+            json,
+            cargo_args,
+            ..
+        } => run_compile(
+            project_root,
+            CompileOptions {
+                output_dir,
+                profile,
+                features,
+                no_default_features,
+                allow_dirty,
+                no_docker,
+                target_name,
+                abi_naming,
+                network,
+                content_addressed,
+                strict,
+                selector_collisions,
+                size_limits: SizeLimits {
+                    max_wasm_size: max_wasm_size
+                        .map(|size| size as usize)
+                        .or(SizeLimits::default().max_wasm_size),
+                    max_rwasm_size: max_rwasm_size.map(|size| size as usize),
+                    policy: size_limit,
+                },
+                assert_reproducible,
+                rustflags,
+                archive_include,
+                archive_exclude,
+                cargo_args,
+            },
+            json,
+            offline,
+        ),
+        Commands::Verify { .. } if offline => Err(BuilderError::Offline {
+            operation: "Verification".to_string(),
+        }
+        .into()),
+        // Re-run in the recorded image; inside it, verify as usual
+        Commands::Verify {
+            project_root,
+            metadata: Some(metadata),
+            docker: true,
+            json,
+            ..
+        } if !std::env::var(fluent_builder::DOCKER_ENV).is_ok_and(|v| v == "1") => {
+            run_verify_in_docker(&project_root, &metadata, json)
+        }
+        Commands::Verify {
+            project_root,
+            address,
+            chain_id,
+            rpc,
+            network,
+            profile,
+            features,
+            no_default_features,
+            target_name,
+            rustflags,
+            metadata,
+            docker: _,
+            constructor_args,
+            constructor_values,
+            deployment_tx,
+            record,
+            at_block,
+            git_ref,
+            unwrap,
+            hash_algorithm,
+            json,
+            cargo_args,
+        } => {
+            let rebuild = RebuildOptions {
+                network,
+                metadata,
+                profile,
+                features,
+                no_default_features,
+                target_name,
+                rustflags,
+                cargo_args,
+            };
+            let deployment = DeploymentOptions {
+                constructor_args,
+                constructor_values,
+                deployment_tx,
+                record,
+                at_block,
+                git_ref,
+                unwrap,
+                hash_algorithm,
+            };
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to create async runtime");
+            runtime.block_on(run_verify(
+                project_root,
+                address,
+                chain_id,
+                rpc,
+                rebuild,
+                deployment,
+                json,
+            ))
+        }
+        Commands::InspectDeployed { .. } if offline => Err(BuilderError::Offline {
+            operation: "Inspecting a deployment".to_string(),
+        }
+        .into()),
+        Commands::InspectDeployed {
+            address,
+            chain_id,
+            rpc,
+            json,
+        } => {
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to create async runtime");
+            runtime.block_on(run_inspect_deployed(address, chain_id, rpc, json))
+        }
+        Commands::Precheck {
+            project_root,
+            artifacts_dir,
+            json,
+        } => run_precheck(&project_root, artifacts_dir, json),
+        Commands::Topics {
+            project_root,
+            artifacts_dir,
+            json,
+        } => run_topics(&project_root, artifacts_dir, json),
+        Commands::Size {
+            project_root,
+            artifacts_dir,
+            wasm,
+            top,
+            json,
+        } => run_size(&project_root, artifacts_dir, wasm, top, json),
+        Commands::Bindgen {
+            abi,
+            lang,
+            name,
+            output,
+        } => run_bindgen(&abi, &lang, name, output.as_deref()),
+        Commands::Changelog {
+            old,
+            new,
+            project_root,
+            output,
+        } => run_changelog(&old, &new, &project_root, output),
+        Commands::AbiDiff { old, new, json } => run_abi_diff(&old, &new, json),
+        Commands::BuildWorkspace {
+            workspace_root,
+            output_dir,
+            force,
+            jobs,
+            json,
+        } => run_build_workspace(workspace_root, output_dir, force, jobs, json, offline),
+        Commands::CompileAll {
+            registry,
+            contracts,
+            network,
+            jobs,
+            no_docker,
+            json,
+        } => run_compile_all(
+            registry, &contracts, network, jobs, no_docker, json, offline,
+        ),
+        Commands::VerifyAll { .. }
+        | Commands::Deploy { .. }
+        | Commands::SimulateDeploy { .. }
+        | Commands::Upgrade { .. }
+            if offline =>
+        {
+            Err(eyre::eyre!(
+                "Verifying and deploying talk to the network's RPC and cannot run with --offline"
+            ))
+        }
+        Commands::VerifyAll {
+            network,
+            rpc,
+            registry,
+            contracts,
+        } => {
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to create async runtime");
+            runtime.block_on(run_verify_all(registry, &contracts, &network, rpc))
+        }
+        Commands::Deploy {
+            network,
+            rpc,
+            registry,
+            contracts,
+            signer,
+        } => {
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to create async runtime");
+            runtime.block_on(run_deploy(registry, &contracts, &network, rpc, &signer))
+        }
+        Commands::SimulateDeploy {
+            network,
+            rpc,
+            fork_rpc,
+            fork_image,
+            registry,
+            contracts,
+            signer,
+        } => {
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to create async runtime");
+            runtime.block_on(run_simulate_deploy(
+                registry,
+                &contracts,
+                &network,
+                rpc,
+                fork_rpc,
+                fork_image.as_deref(),
+                &signer,
+            ))
+        }
+        Commands::Upgrade {
+            network,
+            rpc,
+            registry,
+            contract,
+            proxy,
+            upgrade_function,
+            signer,
+        } => {
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to create async runtime");
+            runtime.block_on(run_upgrade(
+                registry,
+                &contract,
+                &network,
+                rpc,
+                &proxy,
+                &upgrade_function,
+                &signer,
+            ))
+        }
+        Commands::TagRelease {
+            version,
+            project_root,
+            no_docker,
+        } => run_tag_release(&version, project_root, no_docker, offline),
+        Commands::New {
+            name,
+            template,
+            sdk_version,
+            path,
+        } => run_new(&name, template, sdk_version.as_deref(), path, offline),
+        Commands::Migrate {
+            project_root,
+            rename_feature,
+            dry_run,
+            yes,
+        } => run_migrate(&project_root, &rename_feature, dry_run, yes),
+        Commands::Sdk { .. } if offline => Err(BuilderError::Offline {
+            operation: format!("Listing SDK releases of {}", fluent_builder::SDK_REPOSITORY),
+        }
+        .into()),
+        Commands::Sdk { command } => match command {
+            SdkCommands::List { project_root, json } => run_sdk_list(&project_root, json),
+            SdkCommands::Upgrade {
+                version,
+                project_root,
+            } => run_sdk_upgrade(&version, &project_root),
+        },
+        Commands::Serve {
+            bind,
+            workers,
+            queue,
+            work_dir,
+            config: config_path,
+            sandbox,
+            keep_failed,
+            allow_unauthenticated,
+        } => {
+            // Facts outlive a single request in the daemon
+            let _ = FACTS.set(FactCache::in_memory(fluent_builder::DEFAULT_FACT_TTL));
+            let mut config = fluent_builder::PoolConfig {
+                workers,
+                queue_capacity: queue,
+                keep_failed,
+                ..Default::default()
+            };
+            if let Some(work_dir) = work_dir {
+                config.work_dir = work_dir;
+            }
+            config.store = Some(config.work_dir.join(JobStore::FILE_NAME));
+            load_server_config(config_path, allow_unauthenticated).and_then(|server_config| {
+                let guard = auth::Guard::new(&server_config)?;
+                if server_config.rpc.is_empty() {
+                    tracing::info!(
+                        "No [[server.rpc]] endpoints configured; jobs must give \
+                         deployed_bytecode_hash"
+                    );
+                }
+                let sandbox = server_config
+                    .sandbox
+                    .or_else(|| sandbox.then(docker::Sandbox::default));
+                if sandbox.is_none() {
+                    tracing::warn!(
+                        "Builds run unsandboxed; extra_cargo_args and rustflags are rejected. \
+                         Pass --sandbox when accepting untrusted sources"
+                    );
+                }
+                config.sandboxed = sandbox.is_some();
+                server::serve(&bind, config, guard, server_config.rpc, sandbox)
+            })
+        }
+        Commands::Job { command } => match command {
+            JobCommands::Status { id, work_dir, json } => run_job_status(&id, work_dir, json),
+            JobCommands::Import { input, work_dir } => run_job_import(&input, work_dir),
+        },
+        Commands::Export {
+            project_roots,
+            output,
+            output_dir,
+        } => run_export(&project_roots, output, output_dir),
+        Commands::ExportGenesis {
+            project_root,
+            address,
+            artifacts_dir,
+            balance,
+            storage,
+            genesis,
+        } => run_export_genesis(
+            &project_root,
+            &address,
+            artifacts_dir,
+            balance.as_deref(),
+            &storage,
+            genesis.as_deref(),
+        ),
+        Commands::Schema { name } => run_schema(name.as_deref()),
+        Commands::Alias { command } => match command {
+            AliasCommands::Add {
+                name,
+                address,
+                network,
+                chain_id,
+            } => run_alias_add(&name, &address, network, chain_id),
+            AliasCommands::List { network } => run_alias_list(network.as_deref()),
+        },
+        Commands::Artifacts { command } => match command {
+            ArtifactsCommands::Migrate {
+                dir,
+                project_root,
+                dry_run,
+                yes,
+            } => run_artifacts_migrate(&dir, project_root.as_deref(), dry_run, yes),
+        },
+        Commands::Cache { command } => match command {
+            CacheCommands::Gc {
+                max_size,
+                max_age,
+                dry_run,
+                json,
+            } => run_cache_gc(GcLimits { max_size, max_age }, dry_run, json),
+            CacheCommands::Stats { json } => run_cache_stats(json),
+        },
+        Commands::Docker { command } => match command {
+            DockerCommands::Clean { keep } => docker::cleanup_old_images(keep),
+        },
+        Commands::Watch {
+            project_root,
+            output_dir,
+            profile,
+            features,
+            no_default_features,
+            json,
+        } => run_watch(
+            project_root,
+            output_dir,
+            profile,
+            features,
+            no_default_features,
+            json,
+            offline,
+        ),
+        Commands::Abi { command } => match command {
+            AbiCommands::Convert { file } => run_abi_convert(&file),
+            AbiCommands::Watch {
+                project_root,
+                abi_naming,
+                interval_ms,
+            } => run_abi_watch(&project_root, abi_naming, interval_ms),
+            AbiCommands::SelfTest { json } => run_abi_self_test(json),
+        },
+    };
+
+    if let Err(e) = result {
+        exit_with_error(e, &config_dir);
+    }
+}
+
+/// Log writer to stderr, also keeping the output for the support bundle when `capture` is set
+struct LogWriter {
+    capture: bool,
+}
+
+impl std::io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.capture {
+            if let Ok(mut logs) = CAPTURED_LOGS.lock() {
+                logs.extend_from_slice(buf);
+            }
+        }
+        std::io::Write::write_all(&mut std::io::stderr(), buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::Write::flush(&mut std::io::stderr())
+    }
+}
+
+/// Keep the compile configuration for the support bundle
+fn record_resolved_config(config: &CompileConfig) {
+    if let Ok(mut resolved) = RESOLVED_CONFIG.lock() {
+        *resolved = Some(config.clone());
+    }
+}
+
+/// Report `error`, write the support bundle if one was requested, and exit
+fn exit_with_error(error: eyre::Report, project_root: &Path) -> ! {
+    let bundle = SUPPORT_BUNDLE
+        .get()
+        .map(|path| (path, write_support_bundle(path, &error, project_root)));
+    output_error(error);
+    match bundle {
+        Some((path, Ok(()))) => eprintln!("Support bundle written to {}", path.display()),
+        Some((_, Err(e))) => eprintln!("Failed to write support bundle: {:#}", e),
+        None => {}
+    }
+    std::process::exit(1);
+}
+
+/// Zip what is known about the failed run to `path`, see `--support-bundle`
+fn write_support_bundle(path: &Path, error: &eyre::Report, project_root: &Path) -> Result<()> {
+    let mut bundle = fluent_builder::SupportBundle::new();
+    bundle.add("error.txt", format!("{:?}", error));
+    bundle.add(
+        "command.txt",
+        std::env::args().collect::<Vec<_>>().join(" "),
+    );
+    bundle.add("config-profile.txt", format!("{:#?}", active_profile()));
+    if let Ok(fluent_toml) = std::fs::read_to_string(project_root.join("fluent.toml")) {
+        bundle.add("fluent.toml", fluent_toml);
+    }
+    let resolved = RESOLVED_CONFIG
+        .lock()
+        .ok()
+        .and_then(|config| config.clone());
+    if let Some(config) = &resolved {
+        bundle.add("compile-config.json", serde_json::to_string_pretty(config)?);
+        // Metadata of the failed build, if it got that far
+        if let Ok(metadata) = find_artifacts_dir(&config.output_directory())
+            .and_then(|dir| Ok(std::fs::read_to_string(dir.join("metadata.json"))?))
+        {
+            bundle.add("metadata.json", metadata);
+        }
+    }
+    bundle.add_environment();
+    bundle.add_git_status(project_root);
+    if let Ok(logs) = CAPTURED_LOGS.lock() {
+        bundle.add("logs.txt", String::from_utf8_lossy(&logs).into_owned());
+    }
+    let nested = NESTED_SUPPORT_BUNDLE
+        .lock()
+        .ok()
+        .and_then(|nested| nested.clone());
+    if let Some(nested) = nested.filter(|nested| nested.exists()) {
+        bundle.add_bundle("nested", &nested)?;
+        let _ = std::fs::remove_file(&nested);
+    }
+    bundle.write(path)
+}
+
+/// Have a nested run in `project_root`, which it sees at `nested_root`, write its own support
+/// bundle there
+///
+/// The nested bundle holds the container's or Nix shell's build logs and is
+/// merged into this run's bundle.
+fn forward_support_bundle(args: &mut Vec<String>, project_root: &Path, nested_root: &str) {
+    args.retain(|arg| arg != "--support-bundle" && !arg.starts_with("--support-bundle="));
+    if SUPPORT_BUNDLE.get().is_none() {
+        return;
+    }
+    let flag = format!(
+        "--support-bundle={}/{}",
+        nested_root.trim_end_matches('/'),
+        NESTED_SUPPORT_BUNDLE_FILE
+    );
+    insert_flags(args, &[&flag]);
+    if let Ok(mut nested) = NESTED_SUPPORT_BUNDLE.lock() {
+        *nested = Some(project_root.join(NESTED_SUPPORT_BUNDLE_FILE));
+    }
+}
+
+/// Early version detection for both Docker and local compilation
+fn detect_project_versions(project_root: &Path) -> Result<(String, String)> {
+    // Read Rust version using existing function from builder
+    let rust_version = fluent_builder::read_rust_toolchain_version(project_root)?;
+
+    // Read SDK version using existing function from builder
+    let sdk_version = fluent_builder::read_sdk_version_from_cargo_lock(project_root)?;
+
+    tracing::info!("Detected Rust version: '{}'", rust_version);
+    tracing::info!("Detected SDK version: '{}'", sdk_version);
+
+    Ok((rust_version, sdk_version))
+}
+
+/// Settings of `compile`
+struct CompileOptions {
+    output_dir: PathBuf,
+    profile: String,
+    features: Vec<String>,
+    no_default_features: bool,
+    allow_dirty: bool,
+    no_docker: bool,
+    target_name: Option<String>,
+    abi_naming: AbiNaming,
+    /// `[networks.<NAME>]` section of fluent.toml to build for
+    network: Option<String>,
+    content_addressed: bool,
+    strict: bool,
+    selector_collisions: SelectorCollisions,
+    size_limits: SizeLimits,
+    assert_reproducible: Option<ReproducibilityMode>,
+    rustflags: Option<String>,
+    /// Globs of the extra files of the source archive
+    archive_include: Vec<String>,
+    /// Globs of the files left out of the source archive
+    archive_exclude: Vec<String>,
+    cargo_args: Vec<String>,
+}
 
 fn run_compile(
+    project_root: PathBuf,
+    options: CompileOptions,
+    json: bool,
+    offline: bool,
+) -> Result<()> {
+    let CompileOptions {
+        output_dir,
+        profile,
+        features,
+        no_default_features,
+        allow_dirty,
+        no_docker,
+        target_name,
+        abi_naming,
+        network,
+        content_addressed,
+        strict,
+        selector_collisions,
+        size_limits,
+        assert_reproducible,
+        rustflags,
+        archive_include,
+        archive_exclude,
+        cargo_args,
+    } = options;
+    // Resolve project root to absolute path first
+    let project_root = fluent_builder::canonicalize_path(&project_root)?;
+
+    // Early version detection - fail fast if prerequisites missing
+    let (rust_version, sdk_version) = detect_project_versions(&project_root)?;
+
+    tracing::info!("Detected Rust version: {}", rust_version);
+    tracing::info!("Detected SDK version: {}", sdk_version);
+
+    let defaults = active_profile();
+    if !defaults.name.is_empty() {
+        tracing::info!("Using config profile {}", defaults.name);
+    }
+    let no_docker = no_docker || defaults.docker == Some(false);
+    let allow_dirty = allow_dirty || defaults.allow_dirty == Some(true);
+    let strict = strict || defaults.strict == Some(true);
+
+    // Create compilation config
+    let mut config = CompileConfig::new(project_root.clone());
+    config.output_dir = output_dir;
+    config.profile = profile;
+    config.features = features;
+    config.no_default_features = no_default_features;
+    config.offline = offline;
+    config.target_name = target_name;
+    config.artifacts.abi_naming = abi_naming;
+    config.artifacts.content_addressed_output = content_addressed;
+    config.artifacts.selector_collisions = selector_collisions;
+    config.artifacts.archive_include = archive_include;
+    config.artifacts.archive_exclude = archive_exclude;
+    config.size_limits = size_limits;
+    config.strict = strict;
+    config.extra_cargo_args = cargo_args;
+    config.rustflags = rustflags;
+    config.build_cache = build_cache();
+    // Reused bytecode would compare a build with itself
+    if assert_reproducible.is_some() {
+        config.build_cache = None;
+    }
+    defaults.apply_artifacts(&mut config.artifacts);
+    if let Some(name) = network {
+        let network = network::Network::load(&project_root, &name)?;
+        network.apply(
+            &mut config.profile,
+            &mut config.features,
+            &mut config.no_default_features,
+        );
+        tracing::info!(
+            "Building for network {}: {}",
+            name,
+            BuildFeatures::from_config(&config)
+        );
+        config.network = Some(name);
+    }
+
+    record_resolved_config(&config);
+
+    // Catch project setup errors before starting a container
+    fluent_builder::validate_contract(&config)?;
+
+    let in_container = std::env::var(fluent_builder::DOCKER_ENV).is_ok_and(|v| v == "1");
+    if assert_reproducible == Some(ReproducibilityMode::Docker) && no_docker && !in_container {
+        return Err(eyre::eyre!(
+            "--assert-reproducible docker compares with a Docker build and cannot run with \
+             --no-docker"
+        ));
+    }
+
+    // If Docker is requested (default), run in container and exit
+    if !no_docker {
+        if !json {
+            println!("🐳 Running compilation in Docker for reproducible builds...");
+            println!("   (Use --no-docker for faster local compilation)");
+
+            // Warn about non-reproducible nightly
+            if rust_version == "nightly" {
+                println!(
+                    "⚠️  Warning: Using 'nightly' without a specific date may not be reproducible"
+                );
+                println!("   Consider using 'nightly-YYYY-MM-DD' in rust-toolchain.toml");
+            }
+        }
+
+        // The container compares its build with this one
+        let _host_build = match assert_reproducible {
+            Some(ReproducibilityMode::Docker) => Some(build_on_host(&config)?),
+            _ => None,
+        };
+
+        // Pass all CLI arguments to Docker along with detected versions
+        let mut args = pin_config_profile(std::env::args().skip(1).collect());
+        let (_, workdir) = docker::container_layout(&project_root);
+        forward_support_bundle(&mut args, &project_root, &workdir);
+        return docker::run_reproducible(
+            &project_root,
+            &rust_version,
+            &sdk_version,
+            &args,
+            offline,
+        );
+    }
+
+    // --- Local compilation starts here ---
+
+    // Check Git repository status
+    let git_info =
+        fluent_builder::detect_git_info_with_timeout(&config.project_root, config.timeouts.git)?;
+
+    // Validate Git state unless --allow-dirty is specified
+    if !allow_dirty {
+        match &git_info {
+            None => {
+                return Err(eyre::Report::new(BuilderError::NotAGitRepository)
+                    .wrap_err("Initialize a Git repository or use --allow-dirty flag."));
+            }
+            Some(git) if git.is_dirty => {
+                return Err(eyre::Report::new(BuilderError::GitDirty {
+                    dirty_files: git.dirty_files_count,
+                })
+                .wrap_err(format!(
+                    "Repository has {} uncommitted changes.\n\
+                     \n\
+                     To fix this:\n\
+                     1. Commit your changes: git add . && git commit -m \"Your message\"\n\
+                     2. Or stash them: git stash\n\
+                     3. Or use --allow-dirty flag",
+                    git.dirty_files_count
+                )));
+            }
+            _ => {} // Clean repository, continue
+        }
+    }
+
+    // Determine source type for metadata
+    // - Clean Git repo → use Git source
+    // - Dirty repo or --allow-dirty → use archive source
+    config.use_git_source = matches!((&git_info, allow_dirty), (Some(git), false) if !git.is_dirty);
+
+    // Perform compilation
+    let (result, reproducibility) = match assert_reproducible {
+        Some(ReproducibilityMode::Local) => {
+            let (result, diff) =
+                fluent_builder::check_reproducibility(&config).context("Compilation failed")?;
+            (result, Some(diff))
+        }
+        Some(ReproducibilityMode::Docker) => {
+            let host = read_host_build(&config)?;
+            let result = build(&config).context("Compilation failed")?;
+            let diff = fluent_builder::compare_builds("host", &host, "docker", &result.outputs);
+            (result, Some(diff))
+        }
+        None => (build(&config).context("Compilation failed")?, None),
+    };
+    let rwasm_hash = format!("0x{:x}", Sha256::digest(&result.outputs.rwasm));
+
+    // Output results based on format
+    if json {
+        output_json_results(
+            &result,
+            &rwasm_hash,
+            &git_info,
+            &config,
+            reproducibility.as_ref(),
+        )?;
+    } else {
+        output_human_results(&result, &rwasm_hash, &git_info, &config)?;
+        if let Some(diff) = &reproducibility {
+            print_reproducibility_check(diff);
+        }
+    }
+
+    if reproducibility.is_some_and(|diff| !diff.reproducible) {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Where the host build of `--assert-reproducible docker` is left for the container
+fn host_build_dir(config: &CompileConfig) -> PathBuf {
+    config.output_directory().join(".host-build")
+}
+
+/// Build on the host for `--assert-reproducible docker`; the build is removed when the guard drops
+fn build_on_host(config: &CompileConfig) -> Result<CleanupGuard> {
+    let mut config = config.clone();
+    config.use_git_source = false;
+    tracing::info!("Building on the host to compare with the Docker build");
+    let result = build(&config).context("Compilation failed on the host")?;
+
+    let dir = host_build_dir(&config);
+    let guard = CleanupGuard::remove_path(&dir);
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    std::fs::write(dir.join("lib.wasm"), &result.outputs.wasm)?;
+    std::fs::write(dir.join("lib.rwasm"), &result.outputs.rwasm)?;
+    Ok(guard)
+}
+
+/// The bytecode [`build_on_host`] left for the container
+fn read_host_build(config: &CompileConfig) -> Result<fluent_builder::CompilationOutputs> {
+    let dir = host_build_dir(config);
+    let read = |file: &str| {
+        std::fs::read(dir.join(file)).with_context(|| {
+            format!(
+                "No host build in {}; run --assert-reproducible docker without --no-docker",
+                dir.display()
+            )
+        })
+    };
+    Ok(fluent_builder::CompilationOutputs {
+        wasm: read("lib.wasm")?,
+        rwasm: read("lib.rwasm")?,
+    })
+}
+
+/// Outcome of `--assert-reproducible`
+fn print_reproducibility_check(diff: &ReproducibilityDiff) {
+    let (first, second) = (&diff.first.label, &diff.second.label);
+    if diff.reproducible {
+        println!(
+            "\n🔁 Reproducible: the {} and {} builds produced the same rWASM",
+            first, second
+        );
+        return;
+    }
+    println!(
+        "\n❌ Not reproducible: the {} and {} builds differ",
+        first, second
+    );
+    for build in [&diff.first, &diff.second] {
+        println!("   {} rWASM hash: 0x{}", build.label, build.rwasm_hash);
+    }
+    if diff.first.wasm_hash == diff.second.wasm_hash {
+        println!("   The WASM is identical; the rWASM translation differs");
+    }
+    if !diff.sections.is_empty() {
+        println!("   Differing sections: {}", diff.sections.join(", "));
+    }
+    if !diff.functions.is_empty() {
+        let shown: Vec<&str> = diff.functions.iter().take(10).map(String::as_str).collect();
+        let more = diff.functions.len() - shown.len();
+        let more = if more > 0 {
+            format!(" and {} more", more)
+        } else {
+            String::new()
+        };
+        println!("   Differing functions: {}{}", shown.join(", "), more);
+    }
+    for string in &diff.embedded {
+        let kind = match string.kind {
+            fluent_builder::EmbeddedKind::Path => "path",
+            fluent_builder::EmbeddedKind::Timestamp => "timestamp",
+            fluent_builder::EmbeddedKind::Other => "string",
+        };
+        println!(
+            "   Only in the {} build ({}): {}",
+            string.build, kind, string.value
+        );
+    }
+}
+
+/// Pass the active config profile on to a nested invocation, which can't detect CI itself
+fn pin_config_profile(mut args: Vec<String>) -> Vec<String> {
+    let own_args = args.iter().take_while(|arg| *arg != "--");
+    if !own_args
+        .into_iter()
+        .any(|arg| arg.starts_with("--config-profile"))
+    {
+        insert_flags(
+            &mut args,
+            &["--config-profile", active_profile().pinned_name()],
+        );
+    }
+    args
+}
+
+/// Re-run the compile command inside a pinned Nix environment
+fn run_nix_compile(project_root: PathBuf, offline: bool) -> Result<()> {
+    let project_root = fluent_builder::canonicalize_path(&project_root)?;
+    let (rust_version, sdk_version) = detect_project_versions(&project_root)?;
+
+    // Pass the original arguments through, minus the flag that brought us here
+    let mut args = pin_config_profile(std::env::args().skip(1).filter(|a| a != "--nix").collect());
+    forward_support_bundle(&mut args, &project_root, &project_root.to_string_lossy());
+    nix::run_reproducible(&project_root, &rust_version, &sdk_version, &args, offline)
+}
+
+/// Add `flags` to the arguments of a nested invocation, ahead of the cargo arguments after `--`
+pub(crate) fn insert_flags(args: &mut Vec<String>, flags: &[&str]) {
+    let at = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    args.splice(at..at, flags.iter().map(|flag| flag.to_string()));
+}
+
+/// Compile the standard JSON request on stdin; build failures are reported in the output, not the
+/// exit code
+///
+/// The inlined sources are untrusted, so they are built in a sandbox container unless `no_docker`
+/// is set, in which case build flags are rejected.
+fn run_standard_json(no_docker: bool, offline: bool) -> Result<()> {
+    use std::io::Read;
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("Failed to read standard JSON from stdin")?;
+    let output = if no_docker {
+        fluent_builder::compile_standard_json(&input, &std::env::temp_dir(), offline, false)
+    } else {
+        run_standard_json_sandboxed(&input)
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+/// Build a standard JSON request in a sandbox container, as `serve --sandbox` builds jobs
+fn run_standard_json_sandboxed(input: &str) -> StandardJsonOutput {
+    let input = match StandardJsonInput::parse(input) {
+        Ok(input) => input,
+        Err(e) => return StandardJsonOutput::error("JSONError", e),
+    };
+    let workspace = match fluent_builder::Sandbox::create(
+        &std::env::temp_dir(),
+        &format!("fluent-builder-standard-json-{}", std::process::id()),
+    ) {
+        Ok(workspace) => workspace,
+        Err(e) => return StandardJsonOutput::error("IOError", e),
+    };
+    let cleanup = workspace.cleanup_guard();
+    let output = match input.write_sources(&workspace.sources_dir) {
+        Ok(()) => build_standard_json_sandboxed(&input, &workspace)
+            .unwrap_or_else(|e| StandardJsonOutput::error("CompilerError", e)),
+        Err(e) => StandardJsonOutput::error("IOError", e),
+    };
+    cleanup.disarm();
+    workspace.finish(output.errors.is_empty());
+    output
+}
+
+/// Vendor the dependencies of the written sources and compile them offline in a sandbox container
+fn build_standard_json_sandboxed(
+    input: &StandardJsonInput,
+    workspace: &fluent_builder::Sandbox,
+) -> Result<StandardJsonOutput> {
+    let project_root = &workspace.sources_dir;
+
+    // Fetched here, where network access is trusted; the build itself stays offline
+    let vendor_dir = workspace.root.join("vendor");
+    let cargo_config = fluent_builder::vendor_dependencies(
+        project_root,
+        &vendor_dir,
+        "/vendor",
+        Timeouts::default().cargo_vendor,
+    )?;
+    let cargo_config_path = workspace.root.join("cargo-config.toml");
+    std::fs::write(&cargo_config_path, cargo_config)
+        .with_context(|| format!("Failed to write {}", cargo_config_path.display()))?;
+
+    let settings = &input.settings;
+    let mut args: Vec<String> = [
+        "--target-dir",
+        "/target",
+        "--offline",
+        "compile",
+        ".",
+        "--output-dir",
+        "/out",
+        "--allow-dirty",
+        "--json",
+        "--profile",
+        &settings.profile,
+    ]
+    .map(str::to_string)
+    .to_vec();
+    args.push(format!(
+        "--no-default-features={}",
+        settings.no_default_features
+    ));
+    if !settings.features.is_empty() {
+        args.extend(["--features".to_string(), settings.features.join(" ")]);
+    }
+    if let Some(target_name) = &settings.target_name {
+        args.extend(["--target-name".to_string(), target_name.clone()]);
+    }
+    if settings.abi_naming == AbiNaming::CamelCase {
+        args.extend(["--abi-naming".to_string(), "camel-case".to_string()]);
+    }
+    if let Some(rustflags) = &settings.rustflags {
+        args.extend(["--rustflags".to_string(), rustflags.clone()]);
+    }
+    if !settings.extra_cargo_args.is_empty() {
+        args.push("--".to_string());
+        args.extend(settings.extra_cargo_args.iter().cloned());
+    }
+
+    let stdout = docker::run_sandboxed(
+        project_root,
+        &workspace.container_name,
+        &args,
+        &docker::Sandbox::default(),
+        &[
+            (&vendor_dir, "/vendor"),
+            (&cargo_config_path, "/.cargo/config.toml"),
+        ],
+        &[
+            (&workspace.target_dir, "/target"),
+            (&workspace.output_dir, "/out"),
+        ],
+    )?;
+    let output: serde_json::Value =
+        serde_json::from_slice(&stdout).context("Unexpected output from the sandboxed build")?;
+    let artifacts_dir = output["output_dir"]
+        .as_str()
+        .ok_or_else(|| eyre::eyre!("Sandboxed build did not write artifacts: {}", output))?;
+    StandardJsonOutput::from_artifacts(
+        &input.contract_file(),
+        &workspace
+            .output_dir
+            .join(server::sandboxed_artifacts_dir(artifacts_dir)?),
+    )
+}
+
+/// Output compilation results as JSON
+fn output_json_results(
+    result: &fluent_builder::CompilationResult,
+    rwasm_hash: &str,
+    git_info: &Option<GitInfo>,
+    config: &CompileConfig,
+    reproducibility: Option<&ReproducibilityDiff>,
+) -> Result<()> {
+    let output = Output::Success {
+        data: SuccessData::Compile {
+            contract_name: result.contract.name.clone(),
+            rwasm_hash: rwasm_hash.to_string(),
+            wasm_size: result.outputs.wasm.len(),
+            rwasm_size: result.outputs.rwasm.len(),
+            has_abi: result
+                .artifacts
+                .as_ref()
+                .map(|a| !a.abi.is_empty())
+                .unwrap_or(false),
+            output_dir: result.artifacts.as_ref().map(|_| {
+                config
+                    .artifacts
+                    .artifacts_dir(
+                        Path::new(""),
+                        result.contract.artifact_name(),
+                        &result.outputs.rwasm,
+                    )
+                    .display()
+                    .to_string()
+            }),
+            git_info: git_info.as_ref().map(GitInfoJson::from),
+            source_type: if config.use_git_source {
+                "git"
+            } else {
+                "archive"
+            }
+            .to_string(),
+            reproducibility_score: result
+                .artifacts
+                .as_ref()
+                .and_then(|a| a.reproducibility.as_ref())
+                .map(|scorecard| scorecard.score),
+            reproducibility_check: reproducibility.cloned().map(Box::new),
+        },
+    };
+    println!("{}", output.to_json()?);
+    Ok(())
+}
+
+/// Summary of the reproducibility scorecard with fixes for failed checks
+fn print_reproducibility(scorecard: &fluent_builder::ReproducibilityScorecard) {
+    println!("\n🔁 Reproducibility score: {}/100", scorecard.score);
+    for check in scorecard.failed() {
+        println!("   ❌ {}", check.message);
+        if let Some(remediation) = &check.remediation {
+            println!("      → {}", remediation);
+        }
+    }
+    for warning in &scorecard.warnings {
+        println!("   ⚠️  {}", warning);
+    }
+}
+
+/// Output compilation results in human-readable format
+fn output_human_results(
+    result: &fluent_builder::CompilationResult,
+    rwasm_hash: &str,
+    git_info: &Option<GitInfo>,
+    config: &CompileConfig,
+) -> Result<()> {
+    // Show Git repository info if available
+    if let Some(git) = git_info {
+        println!(
+            "📦 Git repository: {} @ {}",
+            git.branch, git.commit_hash_short
+        );
+        if git.is_dirty {
+            println!("⚠️  Warning: Compiling with uncommitted changes (archive source)");
+        }
+    }
+
+    println!("✅ Successfully compiled {}", result.contract.name);
+    println!(
+        "⏱️  Compilation time: {:.2}s",
+        result.duration.as_secs_f64()
+    );
+    if result.cached {
+        println!("♻️  Reused the cached build of unchanged sources (--no-cache rebuilds)");
+    }
+
+    // If artifacts were generated, save and display them
+    if let Some(artifacts) = &result.artifacts {
+        let saved = save_artifacts(
+            artifacts,
+            result.contract.artifact_name(),
+            &result.outputs.wasm,
+            &result.outputs.rwasm,
+            &config.output_directory(),
+            &config.artifacts,
+        )?;
+
+        // Display source type from metadata
+        match &artifacts.metadata.source {
+            fluent_builder::Source::Git {
+                repository, commit, ..
+            } => {
+                println!("\n📦 Source type: Git");
+                println!("   Repository: {}", repository);
+                println!("   Commit: {}", &commit[..8]);
+            }
+            fluent_builder::Source::Archive { .. } => {
+                println!("\n📦 Source type: Archive");
+            }
+        }
+
+        // Display output location and files
+        println!("\n📁 Output directory: {}", saved.output_dir.display());
+        println!("📄 Generated files:");
+        println!("   - lib.wasm ({} bytes)", result.outputs.wasm.len());
+        println!("   - lib.rwasm ({} bytes)", result.outputs.rwasm.len());
+        println!("   - rWASM hash: {}", rwasm_hash);
+        println!("   - {}", fluent_builder::DEPLOY_FILE);
+
+        // List optional artifacts
+        if saved.abi_path.is_some() {
+            println!("   - abi.json");
+        }
+        if saved.human_readable_abi_path.is_some() {
+            println!("   - {}", fluent_builder::HUMAN_READABLE_ABI_FILE);
+        }
+        if saved.typescript_bindings_path.is_some() {
+            println!("   - {}", fluent_builder::TYPESCRIPT_BINDINGS_FILE);
+        }
+        if saved.rust_bindings_path.is_some() {
+            println!("   - {}", fluent_builder::RUST_BINDINGS_FILE);
+        }
+        if saved.interface_path.is_some() {
+            println!("   - interface.sol");
+        }
+        for path in &saved.facet_paths {
+            if let Some(name) = path.file_name() {
+                println!("   - {}", name.to_string_lossy());
+            }
+        }
+        if saved.metadata_path.is_some() {
+            println!("   - metadata.json");
+        }
+        if saved.reproducibility_path.is_some() {
+            println!("   - {}", fluent_builder::REPRODUCIBILITY_FILE);
+        }
+
+        if let Some(scorecard) = &artifacts.reproducibility {
+            print_reproducibility(scorecard);
+        }
+
+        // Create source archive if using archive source
+        if !config.use_git_source {
+            let archive_path = saved.output_dir.join("sources.tar.gz");
+            let archive_options = ArchiveOptions {
+                include_globs: config.artifacts.archive_include.clone(),
+                exclude_globs: config.artifacts.archive_exclude.clone(),
+                ..Default::default()
+            };
+
+            create_verification_archive(&config.project_root, &archive_path, &archive_options)?;
+            println!("   - sources.tar.gz");
+        }
+    } else {
+        // Minimal output when artifacts are disabled
+        println!("\n📊 Compilation results:");
+        println!("   - WASM size: {} bytes", result.outputs.wasm.len());
+        println!("   - rWASM size: {} bytes", result.outputs.rwasm.len());
+        println!("   - rWASM hash: {}", rwasm_hash);
+        println!("\n⚠️  No artifacts saved (generation disabled in config)");
+    }
+
+    Ok(())
+}
+
+/// Constructor arguments of a verified deployment, whether to record it and how to read its code
+struct DeploymentOptions {
+    constructor_args: Option<String>,
+    constructor_values: Vec<String>,
+    deployment_tx: Option<String>,
+    record: bool,
+    /// Block of an earlier version of the code at the address
+    at_block: Option<u64>,
+    /// Source revision of that version
+    git_ref: Option<String>,
+    unwrap: CodeUnwrap,
+    hash_algorithm: HashAlgorithm,
+}
+
+/// Rebuild settings of `verify`; those given take precedence over the ones in `metadata`
+struct RebuildOptions {
+    /// `[networks.<NAME>]` section of fluent.toml to build for
+    network: Option<String>,
+    /// metadata.json of the deployment
+    metadata: Option<PathBuf>,
+    profile: Option<String>,
+    features: Vec<String>,
+    no_default_features: bool,
+    target_name: Option<String>,
+    rustflags: Option<String>,
+    cargo_args: Vec<String>,
+}
+
+/// Re-run the verify command inside the Docker image recorded in `metadata`
+fn run_verify_in_docker(project_root: &Path, metadata: &Path, json: bool) -> Result<()> {
+    let recorded = fluent_builder::Metadata::read(metadata)?;
+    let environment = recorded.build_environment.ok_or_else(|| {
+        eyre::eyre!(
+            "{} records no Docker image: the contract was built without Docker or by an older \
+             fluent-builder",
+            metadata.display()
+        )
+    })?;
+    if !json {
+        println!(
+            "🐳 Verifying in {} ({})",
+            environment.image, environment.image_digest
+        );
+    }
+    docker::run_pinned(
+        project_root,
+        &environment,
+        &pin_config_profile(std::env::args().skip(1).collect()),
+    )
+}
+
+async fn run_verify(
+    project_root: PathBuf,
+    address: String,
+    chain_id: Option<u64>,
+    rpc: Option<String>,
+    rebuild: RebuildOptions,
+    mut deployment: DeploymentOptions,
+    json: bool,
+) -> Result<()> {
+    let RebuildOptions {
+        network,
+        metadata,
+        profile,
+        features,
+        no_default_features,
+        target_name,
+        rustflags,
+        cargo_args,
+    } = rebuild;
+    let hash_algorithm = deployment.hash_algorithm;
+    // Endpoint of the network; its build settings are applied below, so both sides target the same
+    // one
+    let network_settings = network
+        .as_deref()
+        .map(|name| network::Network::load(&project_root, name))
+        .transpose()?;
+    let (rpc, chain_id) = match (&network, &network_settings) {
+        (Some(name), Some(settings)) => settings.endpoint(name, rpc, chain_id)?,
+        _ => (
+            rpc.ok_or_else(|| eyre::eyre!("--rpc is required without --network"))?,
+            chain_id.ok_or_else(|| eyre::eyre!("--chain-id is required without --network"))?,
+        ),
+    };
+
+    let address = alias::AddressBook::load(&alias::AddressBook::path(&project_root))?.resolve(
+        &address,
+        network.as_deref(),
+        chain_id,
+    )?;
+
+    // Metadata recorded at deployment, if provided
+    let recorded_metadata = metadata
+        .as_ref()
+        .map(|path| -> Result<serde_json::Value> {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))
+        })
+        .transpose()?;
+    // The recorded build settings and sources, unless given on the command line
+    let recorded_build = metadata
+        .as_deref()
+        .map(|path| {
+            fluent_builder::Metadata::read(path).with_context(|| {
+                format!(
+                    "Can't rebuild with the settings recorded in {} (upgrade it with \
+                     `fluent-builder artifacts migrate`)",
+                    path.display()
+                )
+            })
+        })
+        .transpose()?;
+    if let (None, Some(fluent_builder::Source::Git { commit, .. })) = (
+        &deployment.git_ref,
+        recorded_build.as_ref().map(|m| &m.source),
+    ) {
+        deployment.git_ref = Some(commit.clone());
+    }
+
+    // Fetch deployed bytecode
+    let fetched = fetch_bytecode_at(&address, &rpc, chain_id, deployment.at_block).await?;
+    let (bytecode, deployed_unwrapping) = deployment.unwrap.apply(&fetched)?;
+    if let Some(unwrapping) = &deployed_unwrapping {
+        tracing::info!("Stripped {} from the deployed code", unwrapping);
+    }
+    let deployed_hash = format!("0x{}", hash_algorithm.hash(bytecode));
+    // The builder provenance of the deployment, from its recorded metadata or deployments.json
+    let deployed_provenance = match &recorded_metadata {
+        Some(metadata) => Provenance::from_metadata(metadata)
+            .map(Some)
+            .context("No builder provenance in --metadata"),
+        None => Deployments::load(&project_root).map(|deployments| {
+            deployments
+                .deployment(chain_id, &address, deployment.at_block)
+                .and_then(|recorded| recorded.provenance.clone())
+        }),
+    }
+    .unwrap_or_else(|e| {
+        tracing::warn!("Not diagnosing builder differences: {:#}", e);
+        None
+    });
+
+    let constructor_args = match (deployment.constructor_args, deployment.constructor_values) {
+        (Some(args), _) => Some(ConstructorArgs::Encoded(
+            hex::decode(args.trim_start_matches("0x"))
+                .context("--constructor-args is not valid hex")?,
+        )),
+        (None, values) if !values.is_empty() => Some(ConstructorArgs::Values(values)),
+        _ => None,
+    };
+    let deployment_data = match &deployment.deployment_tx {
+        Some(tx) => Some(fetch_deployment_data(tx, &address, &rpc).await?),
+        None => None,
+    };
+
+    // Sources of an earlier version, checked out until verification is done
+    let checkout = match &deployment.git_ref {
+        Some(git_ref) => Some(fluent_builder::WorktreeGuard::create(
+            &project_root,
+            git_ref,
+        )?),
+        None => None,
+    };
+    let source_root = match &checkout {
+        Some(checkout) => {
+            let project_path =
+                fluent_builder::get_project_path_in_repo(&project_root, Timeouts::default().git)?;
+            checkout.path.join(project_path)
+        }
+        None => project_root.clone(),
+    };
+
+    // Build compilation config
+    // Verify always uses the provided directory as-is (no git source)
+    let mut compile_config = CompileConfig::new(source_root.clone());
+    if let Some(checkout) = &checkout {
+        checkout.configure(&mut compile_config);
+    }
+    compile_config.no_default_features = no_default_features;
+    if let Some(recorded) = &recorded_build {
+        recorded
+            .compilation_settings
+            .build_cfg
+            .apply(&mut compile_config);
+        for difference in recorded.source_differences(&source_root) {
+            tracing::warn!("{}", difference);
+        }
+        // The rebuild describes its sources the way the recorded build did
+        if let fluent_builder::Source::Archive {
+            include_globs,
+            exclude_globs,
+            ..
+        } = &recorded.source
+        {
+            compile_config
+                .artifacts
+                .archive_include
+                .clone_from(include_globs);
+            compile_config
+                .artifacts
+                .archive_exclude
+                .clone_from(exclude_globs);
+        }
+    }
+    if let Some(profile) = profile {
+        compile_config.profile = profile;
+    }
+    if !features.is_empty() {
+        compile_config.features = features;
+    }
+    if !cargo_args.is_empty() {
+        compile_config.extra_cargo_args = cargo_args;
+    }
+    if target_name.is_some() {
+        compile_config.target_name = target_name;
+    }
+    if rustflags.is_some() {
+        compile_config.rustflags = rustflags;
+    }
+    if let Some(settings) = &network_settings {
+        settings.apply(
+            &mut compile_config.profile,
+            &mut compile_config.features,
+            &mut compile_config.no_default_features,
+        );
+        compile_config.network = network;
+    }
+    compile_config.use_git_source = false; // Always use archive/plain directory for verify
+    compile_config.build_cache = build_cache();
+    // Reports stay in the project, not in a temporary checkout
+    let report_dir = CompileConfig::new(project_root.clone()).output_directory();
+
+    record_resolved_config(&compile_config);
+
+    // Run verification
+    let verify_config = fluent_builder::VerifyConfig {
+        source: source_root.clone().into(),
+        deployed_bytecode_hash: deployed_hash.clone(),
+        hash_algorithm,
+        compile_config: Some(compile_config),
+        recorded_metadata,
+        deployed_provenance,
+        deployed_unwrapping,
+        constructor_args,
+        deployment_data,
+    };
+
+    let verification_result = verify(verify_config).context("Verification failed")?;
+
+    // Side-by-side report of the recorded and the local build for mismatch investigations
+    let mismatch_report = match (
+        &verification_result.status,
+        &verification_result.compilation_result,
+    ) {
+        (
+            VerificationStatus::Mismatch { .. } | VerificationStatus::BuildSettingsMismatch { .. },
+            Some(result),
+        ) => {
+            let recorded = match &metadata {
+                Some(path) => fluent_builder::BuildSnapshot::from_metadata_file(path)?,
+                None => fluent_builder::BuildSnapshot {
+                    label: "deployment (no --metadata)".to_string(),
+                    ..Default::default()
+                },
+            };
+            let report = fluent_builder::MismatchReport {
+                contract: verification_result.contract_name.clone(),
+                expected_hash: deployed_hash.clone(),
+                actual_hash: format!("0x{}", hash_algorithm.hash(&result.outputs.rwasm)),
+                hints: verification_result.hints.clone(),
+                recorded,
+                local: fluent_builder::BuildSnapshot::from_compilation(result, &source_root)?,
+            };
+            Some(report.write(&report_dir)?)
+        }
+        _ => None,
+    };
+
+    if deployment.record && verification_result.status.is_success() {
+        let (provenance, build_features) = verification_result
+            .compilation_result
+            .as_ref()
+            .and_then(|result| result.artifacts.as_ref())
+            .map(|artifacts| recorded_build_info(&artifacts.metadata))
+            .unwrap_or_default();
+        let mut deployments = Deployments::load(&project_root)?;
+        deployments.record_deployment(DeploymentRecord {
+            chain_id,
+            address: address.clone(),
+            contract: verification_result.contract_name.clone(),
+            rwasm_hash: bytecode_hash(bytecode),
+            constructor_args: verification_result.constructor_args.clone(),
+            block: deployment.at_block,
+            git_ref: deployment.git_ref.clone(),
+            commit: checkout.as_ref().map(|c| c.commit.clone()),
+            provenance,
+            build_features,
+            verified_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        });
+        deployments.save(&project_root)?;
+    }
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::Verify {
+                verified: verification_result.status.is_success(),
+                contract_name: verification_result.contract_name.clone(),
+                expected_hash: match &verification_result.status {
+                    VerificationStatus::Success => deployed_hash.clone(),
+                    VerificationStatus::Mismatch { expected, .. } => expected.clone(),
+                    _ => deployed_hash.clone(),
+                },
+                actual_hash: match &verification_result.status {
+                    VerificationStatus::Success => deployed_hash.clone(),
+                    VerificationStatus::Mismatch { actual, .. } => actual.clone(),
+                    _ => verification_result
+                        .compilation_result
+                        .as_ref()
+                        .map(|r| hash_algorithm.hash(&r.outputs.rwasm))
+                        .unwrap_or_default(),
+                },
+                hash_algorithm: verification_result.hash_algorithm,
+                unwrapping: verification_result.unwrapping.clone().map(Box::new),
+                build_settings_mismatch: match &verification_result.status {
+                    VerificationStatus::BuildSettingsMismatch { expected, actual } => {
+                        Some(format!(
+                            "recorded build used {}, local build used {}",
+                            expected, actual
+                        ))
+                    }
+                    _ => None,
+                },
+                constructor_args: verification_result.constructor_args.clone(),
+                hints: verification_result.hints.clone(),
+                mismatch_report: mismatch_report.clone().map(Box::new),
+                abi: if verification_result.status.is_success() {
+                    verification_result
+                        .compilation_result
+                        .as_ref()
+                        .and_then(|r| r.artifacts.as_ref())
+                        .filter(|a| !a.abi.is_empty())
+                        .and_then(|a| serde_json::to_value(&a.abi).ok())
+                        .map(Box::new)
+                } else {
+                    None
+                },
+                compiler_version: verification_result
+                    .compilation_result
+                    .as_ref()
+                    .map(|r| r.runtime_info.rust.version.clone())
+                    .unwrap_or_default(),
+                sdk_version: verification_result
+                    .compilation_result
+                    .as_ref()
+                    .map(|r| format!("{}-{}", r.runtime_info.sdk.tag, r.runtime_info.sdk.commit))
+                    .unwrap_or_default(),
+            },
+        };
+        println!("{}", output.to_json()?);
+    } else {
+        if verification_result.status.is_success() {
+            println!("✅ Contract verified successfully!");
+            println!("📝 Contract name: {}", verification_result.contract_name);
+            println!("🔍 Bytecode hash matches: {}", deployed_hash);
+
+            println!("\n📋 Contract details:");
+            println!("   Address: {}", address);
+            println!("   Chain ID: {}", chain_id);
+            if let Some(block) = deployment.at_block {
+                println!("   Code at block: {}", block);
+            }
+            if let (Some(git_ref), Some(checkout)) = (&deployment.git_ref, &checkout) {
+                println!("   Source: {} ({})", git_ref, checkout.commit);
+            }
+            if let Some(version) = &verification_result.legacy_builder {
+                println!("   Rebuilt as fluent-builder {}", version);
+            }
+            if let Some(unwrapping) = &verification_result.unwrapping {
+                println!("   Stripped: {}", unwrapping);
+            }
+            if let Some(args) = &verification_result.constructor_args {
+                println!("   Constructor args: {}", args);
+            }
+
+            if let Some(result) = &verification_result.compilation_result {
+                println!("\n🛠️  Build details:");
+                println!("   Compiler: {}", result.runtime_info.rust.version);
+                println!(
+                    "   SDK version: {}-{}",
+                    result.runtime_info.sdk.tag, result.runtime_info.sdk.commit
+                );
+            }
+        } else {
+            println!("❌ Verification failed!");
+            println!("📝 Contract name: {}", verification_result.contract_name);
+
+            match &verification_result.status {
+                VerificationStatus::Mismatch { expected, actual } => {
+                    println!("\n🔍 Hash comparison:");
+                    println!("   Expected: {}", expected);
+                    println!("   Actual:   {}", actual);
+                    if let Some(unwrapping) = &verification_result.unwrapping {
+                        println!(
+                            "   Stripped {} from the deployed code; pass --unwrap none to compare \
+                             it as returned",
+                            unwrapping
+                        );
+                    }
+                }
+                VerificationStatus::BuildSettingsMismatch { expected, actual } => {
+                    println!("\n⚙️  Build settings differ from the recorded build:");
+                    println!("   Recorded: {}", expected);
+                    println!("   Local:    {}", actual);
+                }
+                VerificationStatus::ConstructorArgsMismatch { expected, actual } => {
+                    println!("\n🔍 Bytecode matches, but the constructor arguments differ:");
+                    println!("   Deployed: {}", expected);
+                    println!("   Supplied: {}", actual);
+                }
+                VerificationStatus::CompilationFailed(error) => {
+                    println!("⚠️  Compilation error: {}", error);
+                }
+                _ => {}
+            }
+
+            if !verification_result.hints.is_empty() {
+                println!("\n💡 Likely causes:");
+                for (i, hint) in verification_result.hints.iter().enumerate() {
+                    println!("   {}. {}", i + 1, hint.message);
+                }
+            } else if matches!(
+                verification_result.status,
+                VerificationStatus::Mismatch { .. }
+            ) {
+                println!(
+                    "\n💡 Pass --metadata <metadata.json> of the deployment to diagnose the mismatch"
+                );
+            }
+            if let Some(path) = &mismatch_report {
+                println!("\n📄 Side-by-side report: {}", path.display());
+            }
+        }
+    }
+
+    if !verification_result.status.is_success() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Show what is known about a deployed contract without building anything
+async fn run_inspect_deployed(
+    address: String,
+    chain_id: u64,
+    rpc: String,
+    json: bool,
+) -> Result<()> {
+    let address = alias::AddressBook::load(&alias::AddressBook::path(Path::new(".")))?
+        .resolve(&address, None, chain_id)?;
+    let fetched = fetch_bytecode(&address, &rpc, chain_id).await?;
+    let (bytecode, unwrapping) = CodeUnwrap::Auto.apply(&fetched)?;
+    let bytecode_hash = bytecode_hash(bytecode);
+    // Recorded when the contract was deployed or verified from this project
+    let (provenance, build_features) = Deployments::load(Path::new("."))?
+        .deployment(chain_id, &address, None)
+        .map(|recorded| (recorded.provenance.clone(), recorded.build_features.clone()))
+        .unwrap_or_default();
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::InspectDeployed {
+                address,
+                chain_id,
+                bytecode_hash,
+                code_hash: format!("0x{}", HashAlgorithm::Keccak256.hash(bytecode)),
+                bytecode_size: bytecode.len(),
+                unwrapping,
+                provenance,
+                build_features,
+            },
+        };
+        println!("{}", output.to_json()?);
+        return Ok(());
+    }
+
+    println!("📋 Contract {} on chain {}", address, chain_id);
+    println!("   Bytecode size: {} bytes", bytecode.len());
+    println!("   Bytecode hash: {}", bytecode_hash);
+    println!(
+        "   Code hash: 0x{}",
+        HashAlgorithm::Keccak256.hash(bytecode)
+    );
+    if let Some(unwrapping) = &unwrapping {
+        println!("   Stripped: {}", unwrapping);
+    }
+
+    match &provenance {
+        Some(provenance) => {
+            println!("\n🛠️  Built with {}", provenance);
+            if provenance.builder_version != fluent_builder::VERSION {
+                println!(
+                    "   ⚠️  You are running fluent-builder {}; verify with {} for a matching build",
+                    fluent_builder::VERSION,
+                    provenance.builder_version
+                );
+            }
+        }
+        None => println!(
+            "\n⚠️  No deployment at this address in {}; its provenance is unknown",
+            Deployments::FILE_NAME
+        ),
+    }
+    if let Some(build_features) = &build_features {
+        println!("   Build settings: {}", build_features);
+    }
+
+    Ok(())
+}
+
+/// Provenance and build settings recorded in the metadata of a deployed build
+fn recorded_build_info(
+    metadata: &fluent_builder::Metadata,
+) -> (Option<Provenance>, Option<BuildFeatures>) {
+    match serde_json::to_value(metadata) {
+        Ok(metadata) => (
+            Provenance::from_metadata(&metadata).ok(),
+            BuildFeatures::from_metadata(&metadata).ok(),
+        ),
+        Err(_) => (None, None),
+    }
+}
+
+/// API keys, limits, RPC endpoints and sandbox for `serve` from `--config` or `./fluent.toml`
+fn load_server_config(
+    path: Option<PathBuf>,
+    allow_unauthenticated: bool,
+) -> Result<auth::ServerConfig> {
+    let mut server_config = match path {
+        Some(path) => auth::ServerConfig::load(&path)?
+            .ok_or_else(|| eyre::eyre!("No [server] section in {}", path.display()))?,
+        None if Path::new(auth::CONFIG_FILE).exists() => {
+            auth::ServerConfig::load(Path::new(auth::CONFIG_FILE))?.unwrap_or_default()
+        }
+        None => auth::ServerConfig::default(),
+    };
+    if allow_unauthenticated && server_config.anonymous.is_none() {
+        server_config.anonymous = Some(auth::Limits::default());
+    }
+    if server_config.api_keys.is_empty() && server_config.anonymous.is_none() {
+        return Err(eyre::eyre!(
+            "No API keys configured; add [[server.api_keys]] to the config file, \
+             or pass --allow-unauthenticated to accept anonymous requests"
+        ));
+    }
+    if server_config.api_keys.is_empty() {
+        tracing::warn!("No API keys configured; the server accepts unauthenticated requests");
+    } else if server_config.anonymous.is_some() {
+        tracing::info!(
+            "{} API keys configured, anonymous access allowed",
+            server_config.api_keys.len()
+        );
+    }
+    Ok(server_config)
+}
+
+/// Print a job from the store of a (possibly running) verification server
+fn run_job_status(id: &str, work_dir: Option<PathBuf>, json: bool) -> Result<()> {
+    let work_dir = work_dir.unwrap_or_else(|| fluent_builder::PoolConfig::default().work_dir);
+    let store_path = work_dir.join(JobStore::FILE_NAME);
+    if !store_path.exists() {
+        return Err(eyre::eyre!(
+            "No job store at {}. Pass the --work-dir the server was started with",
+            store_path.display()
+        ));
+    }
+    let job = JobStore::open(&store_path)?
+        .load(id)?
+        .ok_or_else(|| eyre::eyre!("Unknown job {}", id))?;
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::JobStatus { job: Box::new(job) },
+        };
+        println!("{}", output.to_json()?);
+        return Ok(());
+    }
+
+    println!("📋 Job {}: {:?}", job.id, job.status);
+    println!("   Project: {}", job.request.project_root.display());
+    if let Some(stage) = &job.stage {
+        println!("   Stage: {}", stage);
+    }
+    if let (Some(started), Some(finished)) = (job.started_at, job.finished_at) {
+        println!("   Took: {}s", finished.saturating_sub(started));
+    }
+    if let Some(cached_from) = &job.cached_from {
+        println!("   Reused the result of job {}", cached_from);
+    }
+    if let Some(outcome) = &job.outcome {
+        let result = if outcome.verified {
+            "✅ verified"
+        } else {
+            "❌ mismatch"
+        };
+        println!("\n{} {}", result, outcome.contract_name);
+        println!("   Expected: {}", outcome.expected_hash);
+        println!("   Actual:   {}", outcome.actual_hash);
+        for hint in &outcome.hints {
+            println!("   💡 {}", hint.message);
+        }
+    }
+    if let Some(error) = &job.error {
+        println!("\n⚠️  {}", error);
+    }
+    if !job.logs.is_empty() {
+        println!("\n📜 Log:");
+        for line in &job.logs {
+            println!("   {}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Export the verified deployments of projects as NDJSON or a directory dump
+fn run_export(
+    project_roots: &[PathBuf],
+    output: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+) -> Result<()> {
+    let mut builds = Vec::new();
+    for project_root in project_roots {
+        builds.extend(fluent_builder::export_verified_builds(project_root)?);
+    }
+
+    match (output, output_dir) {
+        (_, Some(dir)) => {
+            fluent_builder::write_dump_dir(&builds, &dir)?;
+            eprintln!(
+                "📦 Exported {} verified builds to {}",
+                builds.len(),
+                dir.display()
+            );
+        }
+        (Some(file), None) => {
+            let writer = std::io::BufWriter::new(
+                std::fs::File::create(&file)
+                    .with_context(|| format!("Failed to create {}", file.display()))?,
+            );
+            fluent_builder::write_ndjson(&builds, writer)?;
+            eprintln!(
+                "📦 Exported {} verified builds to {}",
+                builds.len(),
+                file.display()
+            );
+        }
+        (None, None) => fluent_builder::write_ndjson(&builds, std::io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+/// Load an `export` dump into the job store of a verification server
+fn run_job_import(input: &Path, work_dir: Option<PathBuf>) -> Result<()> {
+    let builds = if input == Path::new("-") {
+        fluent_builder::read_ndjson(std::io::stdin().lock())?
+    } else if input.is_dir() {
+        fluent_builder::read_dump_dir(input)?
+    } else {
+        let file = std::fs::File::open(input)
+            .with_context(|| format!("Failed to open {}", input.display()))?;
+        fluent_builder::read_ndjson(std::io::BufReader::new(file))?
+    };
+
+    let work_dir = work_dir.unwrap_or_else(|| fluent_builder::PoolConfig::default().work_dir);
+    std::fs::create_dir_all(&work_dir)
+        .with_context(|| format!("Failed to create {}", work_dir.display()))?;
+    let store = JobStore::open(&work_dir.join(JobStore::FILE_NAME))?;
+    let ids = fluent_builder::import_verified_builds(&store, &work_dir, &builds)?;
+    println!(
+        "✅ Queued {} builds in {}; the server verifies them when it starts",
+        ids.len(),
+        work_dir.display()
+    );
+    Ok(())
+}
+
+/// Schema of the `--json` output of all commands
+const OUTPUT_SCHEMA: &str = "output";
+
+/// Schema of the `--json` output in a given version
+fn output_schema(version: u32) -> serde_json::Value {
+    match version {
+        1 => fluent_builder::serialized_schema::<Output>(),
+        _ => fluent_builder::serialized_schema::<VersionedOutput>(),
+    }
+}
+
+fn run_alias_add(
+    name: &str,
+    address: &str,
+    network: Option<String>,
+    chain_id: Option<u64>,
+) -> Result<()> {
+    let network = network
+        .or_else(|| chain_id.map(|id| id.to_string()))
+        .ok_or_else(|| eyre::eyre!("--network or --chain-id is required"))?;
+    let path = alias::AddressBook::path(Path::new("."));
+    let mut book = alias::AddressBook::load(&path)?;
+    match book.add(&network, name, address)? {
+        Some(previous) if previous != address => {
+            println!("@{} on {}: {} -> {}", name, network, previous, address)
+        }
+        _ => println!("@{} on {}: {}", name, network, address),
+    }
+    book.save(&path)
+}
+
+fn run_alias_list(network: Option<&str>) -> Result<()> {
+    let book = alias::AddressBook::load(&alias::AddressBook::path(Path::new(".")))?;
+    for (key, aliases) in book
+        .networks
+        .iter()
+        .filter(|(key, _)| network.is_none_or(|n| n == *key))
+    {
+        println!("[{}]", key);
+        for (name, address) in aliases {
+            println!("  @{:<24} {}", name, address);
+        }
+    }
+    Ok(())
+}
+
+/// All managed caches; Docker ones are skipped when the engine is unavailable
+fn managed_caches(usage: &CacheUsage) -> Result<Vec<CacheEntry>> {
+    let mut caches = fluent_builder::cache_dir_entries(&fluent_builder::user_cache_dir(), usage)?;
+    match docker::cache_entries(usage) {
+        Ok(entries) => caches.extend(entries),
+        Err(e) => tracing::warn!("Skipping Docker caches: {:#}", e),
+    }
+    Ok(caches)
+}
+
+/// Print the size, last use and hit rate of the managed caches
+fn run_cache_stats(json: bool) -> Result<()> {
+    let caches = managed_caches(&CacheUsage::load(&CacheUsage::default_path()))?;
+    let total_size = caches.iter().map(|c| c.size).sum();
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::CacheStats { total_size, caches },
+        };
+        println!("{}", output.to_json()?);
+        return Ok(());
+    }
+
+    let now = unix_now();
+    println!(
+        "{:<7} {:<44} {:>10} {:>10} {:>9}",
+        "KIND", "NAME", "SIZE", "LAST USED", "HIT RATE"
+    );
+    for cache in &caches {
+        let hit_rate = match cache.hits + cache.misses {
+            0 => "-".to_string(),
+            uses => format!("{}%", cache.hits * 100 / uses),
+        };
+        println!(
+            "{:<7} {:<44} {:>10} {:>10} {:>9}",
+            serde_json::to_value(cache.kind)?
+                .as_str()
+                .unwrap_or_default(),
+            cache.name,
+            fluent_builder::format_size(cache.size),
+            format!("{}d ago", now.saturating_sub(cache.last_used) / 86400),
+            hit_rate
+        );
+    }
+    println!("Total: {}", fluent_builder::format_size(total_size));
+    Ok(())
+}
+
+/// Evict managed caches, least recently used first, until they fit `limits`
+fn run_cache_gc(limits: GcLimits, dry_run: bool, json: bool) -> Result<()> {
+    let usage_path = CacheUsage::default_path();
+    let usage = CacheUsage::load(&usage_path);
+    let evicted = fluent_builder::plan_gc(&managed_caches(&usage)?, limits, unix_now());
+
+    let mut failed = Vec::new();
+    if !dry_run {
+        for cache in &evicted {
+            let result = match cache.kind {
+                CacheKind::File => {
+                    let path = fluent_builder::user_cache_dir().join(&cache.name);
+                    if path.is_dir() {
+                        std::fs::remove_dir_all(&path)
+                    } else {
+                        std::fs::remove_file(&path)
+                    }
+                    .with_context(|| format!("Failed to remove {}", path.display()))
+                }
+                _ => docker::remove_cache(cache),
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to evict {}: {:#}", cache.name, e);
+                failed.push(cache.name.clone());
+            }
+        }
+        // Builds may have recorded uses since the usage was loaded
+        CacheUsage::update(&usage_path, |usage| {
+            for cache in evicted.iter().filter(|c| !failed.contains(&c.name)) {
+                usage.forget(cache.kind, &cache.name);
+            }
+        })?;
+    }
+    let freed = evicted
+        .iter()
+        .filter(|c| !failed.contains(&c.name))
+        .map(|c| c.size)
+        .sum();
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::CacheGc {
+                dry_run,
+                freed,
+                evicted,
+                failed,
+            },
+        };
+        println!("{}", output.to_json()?);
+    } else if evicted.is_empty() {
+        println!("✅ Caches are within the limits");
+    } else {
+        for cache in evicted.iter().filter(|c| !failed.contains(&c.name)) {
+            println!(
+                "   - {} ({})",
+                cache.name,
+                fluent_builder::format_size(cache.size)
+            );
+        }
+        let verb = if dry_run { "Would free" } else { "Freed" };
+        println!("🧹 {} {}", verb, fluent_builder::format_size(freed));
+    }
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// OpenAPI description of the verification server, printed by `schema`
+const OPENAPI_SCHEMA: &str = "openapi";
+
+/// Print a JSON Schema, or the list of schema names
+fn run_schema(name: Option<&str>) -> Result<()> {
+    let Some(name) = name else {
+        for name in fluent_builder::SCHEMA_NAMES
+            .iter()
+            .chain([&OUTPUT_SCHEMA, &OPENAPI_SCHEMA])
+        {
+            println!("{}", name);
+        }
+        return Ok(());
+    };
+
+    let schema = if name == OUTPUT_SCHEMA {
+        output_schema(
+            SCHEMA_VERSION
+                .get()
+                .copied()
+                .unwrap_or(OUTPUT_SCHEMA_VERSION),
+        )
+    } else if name == OPENAPI_SCHEMA {
+        fluent_builder::openapi()
+    } else {
+        fluent_builder::json_schema(name).ok_or_else(|| {
+            eyre::eyre!(
+                "Unknown schema '{}'. Available schemas: {}, {}, {}",
+                name,
+                fluent_builder::SCHEMA_NAMES.join(", "),
+                OUTPUT_SCHEMA,
+                OPENAPI_SCHEMA
+            )
+        })?
+    };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+/// Generate a changelog between two versions given as git refs or artifact directories
+fn run_changelog(old: &str, new: &str, project_root: &Path, output: Option<PathBuf>) -> Result<()> {
+    let load = |version: &str| {
+        let path = PathBuf::from(version);
+        if path.is_dir() {
+            ContractSnapshot::from_artifacts_dir(&path)
+        } else {
+            tracing::info!("Building {} for changelog...", version);
+            ContractSnapshot::from_git_ref(project_root, version, &Timeouts::default())
+        }
+    };
+
+    let changelog = generate_changelog(&load(old)?, &load(new)?);
+    let markdown = changelog.to_markdown();
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, &markdown)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("📝 Changelog written to {}", path.display());
+        }
+        None => print!("{}", markdown),
+    }
+
+    Ok(())
+}
+
+/// Print the changes between two ABIs, failing on breaking ones
+fn run_abi_diff(old: &Path, new: &Path, json: bool) -> Result<()> {
+    let diff = fluent_builder::diff_abi(&read_abi_file(old)?, &read_abi_file(new)?);
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::AbiDiff {
+                breaking: diff.is_breaking(),
+                changes: diff.changes.clone(),
+            },
+        };
+        println!("{}", output.to_json()?);
+    } else if diff.is_empty() {
+        println!("✅ No ABI changes");
+    } else {
+        for change in &diff.changes {
+            let (marker, declaration) = match change.change {
+                ChangeKind::Added => ("+", change.new.as_deref()),
+                ChangeKind::Removed => ("-", change.old.as_deref()),
+                ChangeKind::Changed => ("~", change.new.as_deref()),
+            };
+            let selector = change
+                .new_selector
+                .as_ref()
+                .or(change.old_selector.as_ref());
+            let mut line = format!("{} {}", marker, declaration.unwrap_or_default());
+            if let Some(selector) = selector {
+                line.push_str(&format!("  {}", selector));
+            }
+            if change.breaking {
+                line.push_str("  (breaking)");
+            }
+            println!("{}", line);
+            if let (ChangeKind::Changed, Some(old)) = (change.change, &change.old) {
+                println!("    was {}", old);
+            }
+            if !change.details.is_empty() {
+                println!("    {}", change.details.join(", "));
+            }
+        }
+        let breaking = diff.changes.iter().filter(|change| change.breaking).count();
+        if breaking > 0 {
+            println!(
+                "\n❌ {} of {} changes break existing callers",
+                breaking,
+                diff.changes.len()
+            );
+        } else {
+            println!("\n✅ {} changes, none breaking", diff.changes.len());
+        }
+    }
+
+    if diff.is_breaking() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Rebuild workspace contracts whose sources or path dependencies changed
+fn run_build_workspace(
+    workspace_root: PathBuf,
+    output_dir: PathBuf,
+    force: bool,
+    jobs: usize,
+    json: bool,
+    offline: bool,
+) -> Result<()> {
+    let workspace_root = fluent_builder::canonicalize_path(&workspace_root)?;
+
+    let graph = WorkspaceGraph::load(&workspace_root)?;
+    let fingerprints = graph.fingerprints()?;
+    let cache = if force {
+        BuildCache::default()
+    } else {
+        BuildCache::load(&graph.root)
+    };
+
+    let affected = graph.affected_contracts(&fingerprints, &cache)?;
+    if !json {
+        if affected.is_empty() {
+            println!("✅ All contracts are up to date");
+            return Ok(());
+        }
+        println!("🔨 Building {} affected contract(s):", affected.len());
+        for member in &affected {
+            println!("   - {}", member.name);
+        }
+    }
+
+    let scheduled: Vec<ScheduledBuild> = affected
+        .iter()
+        .map(|member| ScheduledBuild {
+            name: member.name.clone(),
+            dependencies: member.path_dependencies.clone(),
+        })
+        .collect();
+    let outcomes = BuildScheduler::new(jobs).run(&scheduled, |index| {
+        let mut config = CompileConfig::new(&affected[index].manifest_dir);
+        config.output_dir = workspace_root.join(&output_dir);
+        config.offline = offline;
+        config.build_cache = build_cache();
+        build_and_save(&affected[index].name, &config, json)
+    });
+
+    // Failed contracts have no fingerprint, so the next run builds them again
+    let mut fingerprints = fingerprints;
+    for outcome in outcomes
+        .iter()
+        .filter(|outcome| outcome.status != BuildStatus::Succeeded)
+    {
+        fingerprints.remove(&outcome.name);
+    }
+    BuildCache { fingerprints }.save(&graph.root)?;
+
+    report_batch_build(&outcomes, json, |contracts, failed| {
+        SuccessData::BuildWorkspace { contracts, failed }
+    })
+}
+
+/// Build a contract and save its artifacts, returning the rWASM hash and the artifacts directory
+fn build_and_save(name: &str, config: &CompileConfig, quiet: bool) -> Result<(String, PathBuf)> {
+    let result = build(config).and_then(|result| {
+        let output_dir = match &result.artifacts {
+            Some(artifacts) => {
+                save_artifacts(
+                    artifacts,
+                    result.contract.artifact_name(),
+                    &result.outputs.wasm,
+                    &result.outputs.rwasm,
+                    &config.output_directory(),
+                    &config.artifacts,
+                )?
+                .output_dir
+            }
+            None => config.output_directory(),
+        };
+        Ok((result, output_dir))
+    });
+    match &result {
+        Ok((result, _)) if !quiet => {
+            println!("✅ Built {} ({:.2}s)", name, result.duration.as_secs_f64())
+        }
+        Err(error) if !quiet => println!("❌ {}: {:#}", name, error),
+        _ => {}
+    }
+    let (result, output_dir) = result?;
+    Ok((
+        format!("0x{:x}", Sha256::digest(&result.outputs.rwasm)),
+        output_dir,
+    ))
+}
+
+/// Print the outcomes of a batch build; fails if any contract was not built
+fn report_batch_build(
+    outcomes: &[BuildOutcome<(String, PathBuf)>],
+    json: bool,
+    data: impl FnOnce(Vec<ContractBuildJson>, Vec<String>) -> SuccessData,
+) -> Result<()> {
+    let failed: Vec<&str> = outcomes
+        .iter()
+        .filter(|outcome| outcome.status != BuildStatus::Succeeded)
+        .map(|outcome| outcome.name.as_str())
+        .collect();
+    if json {
+        let output = Output::Success {
+            data: data(
+                outcomes.iter().map(ContractBuildJson::from).collect(),
+                failed.iter().map(|name| name.to_string()).collect(),
+            ),
+        };
+        println!("{}", output.to_json()?);
+        if !failed.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    for outcome in outcomes
+        .iter()
+        .filter(|outcome| outcome.status == BuildStatus::Skipped)
+    {
+        println!(
+            "⏭️  {}: {}",
+            outcome.name,
+            outcome.error.as_deref().unwrap_or_default()
+        );
+    }
+    if !failed.is_empty() {
+        return Err(eyre::eyre!("Failed to build {}", failed.join(", ")));
+    }
+    Ok(())
+}
+
+/// `contracts.toml` in `dir`, or the closest one at or above the current directory
+fn load_registry(dir: Option<PathBuf>) -> Result<ContractRegistry> {
+    match dir {
+        Some(dir) => ContractRegistry::load(&fluent_builder::canonicalize_path(&dir)?),
+        None => ContractRegistry::find(&std::env::current_dir()?)?.ok_or_else(|| {
+            eyre::eyre!(
+                "No {} in the current directory or its parents; pass --registry",
+                fluent_builder::REGISTRY_FILE
+            )
+        }),
+    }
+}
+
+/// Network settings from the contract's fluent.toml, or from the registry's
+fn load_registry_network(
+    registry: &ContractRegistry,
+    contract_dir: &Path,
+    name: &str,
+) -> Result<network::Network> {
+    if contract_dir.join(auth::CONFIG_FILE).exists() {
+        network::Network::load(contract_dir, name)
+    } else {
+        network::Network::load(&registry.root, name)
+    }
+}
+
+/// Build every contract of the registry into its own `out` directory
+fn run_compile_all(
+    registry: Option<PathBuf>,
+    contracts: &[String],
+    network: Option<String>,
+    jobs: usize,
+    no_docker: bool,
+    json: bool,
+    offline: bool,
+) -> Result<()> {
+    let registry = load_registry(registry)?;
+    let selected = registry.select(contracts, network.as_deref())?;
+
+    // Like compile, build in Docker unless told otherwise
+    if !(no_docker || active_profile().docker == Some(false)) {
+        let contract_dirs: Vec<PathBuf> = selected
+            .iter()
+            .map(|entry| registry.contract_dir(entry))
+            .collect();
+        let (rust_version, sdk_version) = registry_versions(&registry.root, &contract_dirs)?;
+        if !json {
+            println!("🐳 Running compilation in Docker for reproducible builds...");
+            println!("   (Use --no-docker for faster local compilation)");
+        }
+        let mut args = vec![
+            "compile-all".to_string(),
+            "--jobs".to_string(),
+            jobs.to_string(),
+        ];
+        for entry in &selected {
+            args.extend(["--contract".to_string(), entry.name.clone()]);
+        }
+        if let Some(network) = &network {
+            args.extend(["--network".to_string(), network.clone()]);
+        }
+        if json {
+            args.push("--json".to_string());
+        }
+        if offline {
+            args.push("--offline".to_string());
+        }
+        return docker::run_reproducible(
+            &registry.root,
+            &rust_version,
+            &sdk_version,
+            &pin_config_profile(args),
+            offline,
+        );
+    }
+    if !json {
+        println!(
+            "🔨 Building {} contract(s) from {}:",
+            selected.len(),
+            registry.root.display()
+        );
+    }
+
+    let scheduled: Vec<ScheduledBuild> = selected
+        .iter()
+        .map(|entry| ScheduledBuild {
+            name: entry.name.clone(),
+            dependencies: Vec::new(),
+        })
+        .collect();
+    let outcomes = BuildScheduler::new(jobs).run(&scheduled, |index| {
+        let contract_dir = registry.contract_dir(selected[index]);
+        let mut config = CompileConfig::new(&contract_dir);
+        config.offline = offline;
+        config.build_cache = build_cache();
+        if let Some(name) = &network {
+            let network = load_registry_network(&registry, &contract_dir, name)?;
+            network.apply(
+                &mut config.profile,
+                &mut config.features,
+                &mut config.no_default_features,
+            );
+            config.network = Some(name.clone());
+        }
+        build_and_save(&selected[index].name, &config, json)
+    });
+
+    report_batch_build(&outcomes, json, |contracts, failed| {
+        SuccessData::CompileAll { contracts, failed }
+    })
+}
+
+/// Toolchain and SDK version shared by registry contracts built in one container
+///
+/// The container mounts the registry root, so every contract must be inside it.
+fn registry_versions(root: &Path, contract_dirs: &[PathBuf]) -> Result<(String, String)> {
+    let root = fluent_builder::canonicalize_path(root)?;
+    let mut versions: Option<(String, String)> = None;
+    for dir in contract_dirs {
+        if !fluent_builder::canonicalize_path(dir)?.starts_with(&root) {
+            return Err(eyre::eyre!(
+                "{} is outside {}, which is all the Docker build sees; use --no-docker",
+                dir.display(),
+                root.display()
+            ));
+        }
+        let found = detect_project_versions(dir)?;
+        match &versions {
+            Some(first) if *first != found => {
+                return Err(eyre::eyre!(
+                    "Contracts use different toolchains ({} with SDK {}, {} with SDK {}); build \
+                     them separately or use --no-docker",
+                    first.0,
+                    first.1,
+                    found.0,
+                    found.1
+                ))
+            }
+            Some(_) => {}
+            None => versions = Some(found),
+        }
+    }
+    versions.ok_or_else(|| eyre::eyre!("No contracts selected"))
+}
+
+/// Verify the deployments recorded for every contract of the registry on a network
+async fn run_verify_all(
+    registry: Option<PathBuf>,
+    contracts: &[String],
+    network_name: &str,
+    rpc: Option<String>,
+) -> Result<()> {
+    let registry = load_registry(registry)?;
+    let mut failed = Vec::new();
+
+    for entry in registry.select(contracts, Some(network_name))? {
+        if entry.verify == VerifyPolicy::Skip {
+            println!("⏭️  {}: verification skipped", entry.name);
+            continue;
+        }
+        let contract_dir = registry.contract_dir(entry);
+        let network = load_registry_network(&registry, &contract_dir, network_name)?;
+        let (rpc, chain_id) = network.endpoint(network_name, rpc.clone(), None)?;
+
+        let deployments: Vec<DeploymentRecord> = Deployments::load(&contract_dir)?
+            .deployments
+            .into_iter()
+            // Earlier versions at an address were verified against their own ref with `verify
+            // --at-block`
+            .filter(|d| d.chain_id == chain_id && d.block.is_none())
+            .collect();
+        if deployments.is_empty() {
+            println!(
+                "⚠️  {}: no deployment on chain {} in deployments.json",
+                entry.name, chain_id
+            );
+            if entry.verify == VerifyPolicy::Required {
+                failed.push(entry.name.clone());
+            }
+            continue;
+        }
+
+        for deployment in deployments {
+            let fetched = fetch_bytecode(&deployment.address, &rpc, chain_id).await?;
+            let result = verify_registry_deployment(
+                &contract_dir,
+                &network,
+                network_name,
+                &fetched,
+                &deployment,
+                None,
+            )
+            .with_context(|| format!("Verification of {} failed", entry.name))?;
+            if result.status.is_success() {
+                println!("✅ {} at {}", entry.name, deployment.address);
+                continue;
+            }
+            println!(
+                "❌ {} at {}: {}",
+                entry.name,
+                deployment.address,
+                failure_reason(&result.status)
+            );
+            for hint in &result.hints {
+                println!("   💡 {}", hint.message);
+            }
+            if entry.verify == VerifyPolicy::Required {
+                failed.push(entry.name.clone());
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(eyre::eyre!(
+            "Deployments of {} did not verify",
+            failed.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Why a registry deployment did not verify
+fn failure_reason(status: &VerificationStatus) -> String {
+    match status {
+        VerificationStatus::Mismatch { expected, actual } => {
+            format!("deployed {}, built {}", expected, actual)
+        }
+        VerificationStatus::BuildSettingsMismatch { expected, actual } => {
+            format!(
+                "recorded build used {}, local build used {}",
+                expected, actual
+            )
+        }
+        VerificationStatus::ConstructorArgsMismatch { .. } => {
+            "constructor arguments differ".to_string()
+        }
+        VerificationStatus::CompilationFailed(error) => {
+            format!("compilation failed: {}", error)
+        }
+        VerificationStatus::Success => "verified".to_string(),
+    }
+}
+
+/// Rebuild a registry contract with the settings of `network` and compare it with deployed code
+fn verify_registry_deployment(
+    contract_dir: &Path,
+    network: &network::Network,
+    network_name: &str,
+    deployed_code: &[u8],
+    deployment: &DeploymentRecord,
+    deployment_data: Option<Vec<u8>>,
+) -> Result<fluent_builder::VerificationResult> {
+    let mut compile_config = CompileConfig::new(contract_dir);
+    network.apply(
+        &mut compile_config.profile,
+        &mut compile_config.features,
+        &mut compile_config.no_default_features,
+    );
+    compile_config.network = Some(network_name.to_string());
+    compile_config.use_git_source = false;
+    compile_config.build_cache = build_cache();
+
+    let (bytecode, deployed_unwrapping) = CodeUnwrap::Auto.apply(deployed_code)?;
+    let constructor_args = deployment
+        .constructor_args
+        .as_deref()
+        .map(|args| hex::decode(args.trim_start_matches("0x")).map(ConstructorArgs::Encoded))
+        .transpose()
+        .context("Recorded constructor arguments are not valid hex")?;
+    verify(fluent_builder::VerifyConfig {
+        source: contract_dir.to_path_buf().into(),
+        deployed_bytecode_hash: bytecode_hash(bytecode),
+        hash_algorithm: HashAlgorithm::Sha256,
+        compile_config: Some(compile_config),
+        recorded_metadata: None,
+        deployed_provenance: deployment.provenance.clone(),
+        deployed_unwrapping,
+        constructor_args,
+        deployment_data,
+    })
+}
+
+/// Deploy the prechecked artifacts of every contract of the registry, in declaration order
+///
+/// Every endpoint must serve the network's chain before anything is sent. The
+/// deployed code is compared with the artifacts' rWASM hash, and deployments of
+/// verified contracts are rebuilt and recorded in deployments.json if the
+/// rebuild matches.
+async fn run_deploy(
+    registry: Option<PathBuf>,
+    contracts: &[String],
+    network_name: &str,
+    rpc: Option<String>,
+    signer: &SignerArgs,
+) -> Result<()> {
+    let registry = load_registry(registry)?;
+    let wallet = signer.signer().await?;
+    let selected = registry.select(contracts, Some(network_name))?;
+
+    let mut endpoints = Vec::new();
+    for entry in &selected {
+        let contract_dir = registry.contract_dir(entry);
+        let network = load_registry_network(&registry, &contract_dir, network_name)?;
+        let (rpc, chain_id) = network.endpoint(network_name, rpc.clone(), None)?;
+        check_chain_id(&rpc, chain_id).await?;
+        endpoints.push((contract_dir, network, rpc, chain_id));
+    }
+
+    for (entry, (contract_dir, network, rpc, chain_id)) in selected.into_iter().zip(endpoints) {
+        let (artifacts, calldata, constructor_args) = prepare_deployment(entry, &contract_dir)?;
+        let artifact = artifacts.deploy;
+
+        println!(
+            "🚀 Deploying {} to {} (chain {})",
+            entry.name, network_name, chain_id
+        );
+        let address = send_deployment(calldata.clone(), &wallet, &rpc, chain_id).await?;
+        println!("   Address: {}", address);
+
+        if entry.verify == VerifyPolicy::Skip {
+            continue;
+        }
+        let fetched = fetch_bytecode(&address, &rpc, chain_id).await?;
+        let (bytecode, _) = CodeUnwrap::Auto.apply(&fetched)?;
+        let (provenance, build_features) = recorded_build_info(&artifacts.metadata);
+        let record = DeploymentRecord {
+            chain_id,
+            address,
+            contract: artifact.contract.clone(),
+            rwasm_hash: bytecode_hash(bytecode),
+            constructor_args,
+            block: None,
+            git_ref: None,
+            commit: None,
+            provenance,
+            build_features,
+            verified_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+
+        // Only a rebuild from the sources makes the deployment verified
+        let failure = if !matches_artifact(&artifact, bytecode) {
+            Some("does not match its artifacts".to_string())
+        } else {
+            println!("   🔨 Rebuilding {} to verify the deployment", entry.name);
+            let result = verify_registry_deployment(
+                &contract_dir,
+                &network,
+                network_name,
+                &fetched,
+                &record,
+                Some(calldata),
+            )?;
+            (!result.status.is_success()).then(|| {
+                format!(
+                    "does not match a rebuild: {}",
+                    failure_reason(&result.status)
+                )
+            })
+        };
+        if let Some(failure) = failure {
+            let message = format!("Code deployed for {} {}", entry.name, failure);
+            if entry.verify == VerifyPolicy::Required {
+                return Err(eyre::eyre!(message));
+            }
+            println!("   ⚠️  {}; not recorded", message);
+            continue;
+        }
+
+        let mut deployments = Deployments::load(&contract_dir)?;
+        deployments.record_deployment(record);
+        deployments.save(&contract_dir)?;
+        println!("   ✅ Deployed code matches a rebuild; recorded in deployments.json");
+    }
+
+    Ok(())
+}
+
+/// Checked artifacts of a registry contract, its deployment transaction data and hex encoded
+/// constructor arguments
+///
+/// Only artifacts that still match their sources and metadata are deployed.
+fn prepare_deployment(
+    entry: &RegistryEntry,
+    contract_dir: &Path,
+) -> Result<(ContractArtifacts, Vec<u8>, Option<String>)> {
+    let artifacts_dir = find_artifacts_dir(&contract_dir.join("out"))?;
+    let report = fluent_builder::precheck(contract_dir, &artifacts_dir)?;
+    if !report.passed() {
+        return Err(eyre::eyre!(
+            "Artifacts of {} are stale ({}); run compile-all first",
+            entry.name,
+            report
+                .issues
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        ));
+    }
+
+    let artifacts = ContractArtifacts::load(&artifacts_dir)?;
+    let constructor_args = entry.encoded_constructor_args(&artifacts.deploy.constructor.inputs)?;
+    let calldata = artifacts.deploy.deployment_data(&constructor_args)?;
+    let recorded_args =
+        (!constructor_args.is_empty()).then(|| format!("0x{}", hex::encode(&constructor_args)));
+    Ok((artifacts, calldata, recorded_args))
+}
+
+/// Whether deployed (unwrapped) code is the rWASM of the artifact
+pub(crate) fn matches_artifact(artifact: &DeployArtifact, bytecode: &[u8]) -> bool {
+    let (algorithm, recorded) = HashAlgorithm::split_tagged(&artifact.rwasm_hash);
+    algorithm.unwrap_or_default().hash(bytecode) == recorded.trim_start_matches("0x")
+}
+
+/// Deploy registry contracts to a fork of the network and call their view functions
+async fn run_simulate_deploy(
+    registry: Option<PathBuf>,
+    contracts: &[String],
+    network_name: &str,
+    rpc: Option<String>,
+    fork_rpc: Option<String>,
+    fork_image: Option<&str>,
+    signer: &SignerArgs,
+) -> Result<()> {
+    let registry = load_registry(registry)?;
+    // The real deployer key keeps nonces and addresses as on the network; nothing is spent
+    let wallet = signer.try_signer().await?;
+
+    let mut failed = Vec::new();
+    for entry in registry.select(contracts, Some(network_name))? {
+        let contract_dir = registry.contract_dir(entry);
+        let network = load_registry_network(&registry, &contract_dir, network_name)?;
+        let (rpc, chain_id) = network.endpoint(network_name, rpc.clone(), None)?;
+        let (artifacts, calldata, _) = prepare_deployment(entry, &contract_dir)?;
+
+        // A fresh fork per contract, so each starts from the network's state
+        let node = match (&fork_rpc, fork_image) {
+            (Some(_), _) => None,
+            (None, Some(image)) => {
+                println!("🍴 Forking {} ({}) with {}", network_name, rpc, image);
+                Some(docker::start_fork_node(image, &rpc, chain_id)?)
+            }
+            (None, None) => return Err(eyre::eyre!("Pass --fork-image or --fork-rpc")),
+        };
+        let fork_url = fork_rpc
+            .as_deref()
+            .or(node.as_ref().map(|node| node.rpc_url.as_str()))
+            .unwrap_or_default();
+
+        println!(
+            "🧪 Simulating deployment of {} on a fork of {} (chain {})",
+            entry.name, network_name, chain_id
+        );
+        let report =
+            simulate::simulate(fork_url, chain_id, calldata, &artifacts, wallet.clone()).await?;
+        println!("   Address: {}", report.address);
+        println!("   Deployment gas: {}", report.deploy_gas);
+        if report.code_matches {
+            println!("   ✅ Deployed code matches the artifacts");
+        } else {
+            println!(
+                "   ❌ Deployed code does not match the artifacts (does the fork node execute \
+                 rWASM?)"
+            );
+        }
+        for call in &report.calls {
+            match (&call.gas, &call.error) {
+                (_, Some(error)) => println!("   ❌ {}: {}", call.signature, error),
+                (Some(gas), None) => println!("   ✅ {}: {} gas", call.signature, gas),
+                (None, None) => println!("   ✅ {}", call.signature),
+            }
+        }
+        if !report.passed() {
+            failed.push(entry.name.clone());
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(eyre::eyre!("Simulation failed for {}", failed.join(", ")));
+    }
+    println!(
+        "✅ All simulated deployments succeeded; nothing was sent to {}",
+        network_name
+    );
+    Ok(())
+}
+
+/// Deploy a registry contract as the new implementation of `proxy` and switch the proxy to it
+///
+/// The implementation the proxy points to must be a verified deployment in
+/// deployments.json whose artifacts are still under `out/`; the upgrade is
+/// checked against them before any transaction is sent.
+async fn run_upgrade(
+    registry: Option<PathBuf>,
+    contract: &str,
+    network_name: &str,
+    rpc: Option<String>,
+    proxy: &str,
+    upgrade_function: &str,
+    signer: &SignerArgs,
+) -> Result<()> {
+    let registry = load_registry(registry)?;
+    let wallet = signer.signer().await?;
+    let entry = registry
+        .select(&[contract.to_string()], Some(network_name))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| eyre::eyre!("{} is not deployed to {}", contract, network_name))?;
+    let contract_dir = registry.contract_dir(entry);
+    let network = load_registry_network(&registry, &contract_dir, network_name)?;
+    let (rpc, chain_id) = network.endpoint(network_name, rpc, None)?;
+    let proxy: Address = alias::AddressBook::load(&alias::AddressBook::path(&contract_dir))?
+        .resolve(proxy, Some(network_name), chain_id)?
+        .parse()
+        .context("Invalid proxy address")?;
+
+    let (artifacts, calldata, constructor_args) = prepare_deployment(entry, &contract_dir)?;
+    let next_dir = find_artifacts_dir(&contract_dir.join("out"))?;
+    // Validate the signature before anything is deployed
+    upgrade::upgrade_calldata(upgrade_function, Address::zero())?;
+
+    // The implementation the proxy points to, as verified and recorded by deploy
+    let current = upgrade::implementation_of(&rpc, proxy)
+        .await?
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "Proxy {:?} has no implementation in its EIP-1967 slot",
+                proxy
+            )
+        })?;
+    let deployments = Deployments::load(&contract_dir)?;
+    let record = deployments
+        .deployments
+        .iter()
+        .rev()
+        .find(|record| {
+            record.chain_id == chain_id && record.address.parse::<Address>().ok() == Some(current)
+        })
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "Implementation {:?} of the proxy is not a verified deployment in deployments.json",
+                current
+            )
+        })?;
+    let current_dir =
+        fluent_builder::find_artifacts_by_hash(&contract_dir.join("out"), &record.rwasm_hash)
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "No artifacts under out/ for implementation {:?}; rebuild its release first",
+                    current
+                )
+            })?;
+
+    println!(
+        "🔎 Checking {} against implementation {:?}",
+        entry.name, current
+    );
+    let report = fluent_builder::check_upgrade(&contract_dir, &current_dir, &next_dir)?;
+    if !report.passed() {
+        for issue in &report.issues {
+            println!("   ❌ {}", issue);
+        }
+        return Err(eyre::eyre!(
+            "Upgrade of {} is not safe; nothing was sent",
+            entry.name
+        ));
+    }
+    println!(
+        "   ✅ ABI and storage layout are compatible ({} storage slots)",
+        report.storage.len()
+    );
+
+    println!(
+        "🚀 Deploying the new implementation of {} to {} (chain {})",
+        entry.name, network_name, chain_id
+    );
+    let address = send_deployment(calldata, &wallet, &rpc, chain_id).await?;
+    println!("   Address: {}", address);
+    let fetched = fetch_bytecode(&address, &rpc, chain_id).await?;
+    let (bytecode, _) = CodeUnwrap::Auto.apply(&fetched)?;
+    if !matches_artifact(&artifacts.deploy, bytecode) {
+        return Err(eyre::eyre!(
+            "Code deployed at {} does not match the artifacts of {}; the proxy was not switched",
+            address,
+            entry.name
+        ));
+    }
+    let (provenance, build_features) = recorded_build_info(&artifacts.metadata);
+    let mut deployments = deployments;
+    deployments.record_deployment(DeploymentRecord {
+        chain_id,
+        address: address.clone(),
+        contract: artifacts.deploy.contract.clone(),
+        rwasm_hash: bytecode_hash(bytecode),
+        constructor_args,
+        block: None,
+        git_ref: None,
+        commit: None,
+        provenance,
+        build_features,
+        verified_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    });
+    deployments.save(&contract_dir)?;
+    println!("   ✅ Deployed code matches the artifacts; recorded in deployments.json");
+
+    let implementation: Address = address.parse()?;
+    println!("🔀 Switching proxy {:?} with {}", proxy, upgrade_function);
+    let calldata = upgrade::upgrade_calldata(upgrade_function, implementation)?;
+    let tx_hash = upgrade::switch_implementation(&rpc, chain_id, &wallet, proxy, calldata).await?;
+    println!("   Transaction: {:?}", tx_hash);
+    match upgrade::implementation_of(&rpc, proxy).await? {
+        Some(switched) if switched == implementation => {
+            println!("✅ Proxy {:?} now points to {}", proxy, address)
+        }
+        other => println!(
+            "⚠️  Proxy's EIP-1967 slot holds {:?} instead of {}; check the proxy's upgrade \
+             function",
+            other, address
+        ),
+    }
+    Ok(())
+}
+
+/// Build a tagged release into `out/releases/<version>/`
+fn run_tag_release(
+    version: &str,
+    project_root: PathBuf,
+    no_docker: bool,
+    offline: bool,
+) -> Result<()> {
+    let project_root = fluent_builder::canonicalize_path(&project_root)?;
+    let (tag, _) = fluent_builder::parse_release_tag(version)?;
+
+    let timeouts = Timeouts::default();
+    fluent_builder::check_release_preconditions(&project_root, &tag, &timeouts)?;
+
+    let release_dir = fluent_builder::release_dir(&project_root, &tag);
+    let output_dir = Path::new(fluent_builder::RELEASES_DIR).join(&tag);
+
+    println!("🏷️  Building release {}...", tag);
+
+    // Remove the release directory if the build does not complete
+    let partial_guard = (!release_dir.exists()).then(|| CleanupGuard::remove_path(&release_dir));
+
+    let artifacts_dir = if !(no_docker || active_profile().docker == Some(false)) {
+        let (rust_version, sdk_version) = detect_project_versions(&project_root)?;
+        // Releases get every artifact, whatever the profile leaves out
+        let mut args = vec![
+            "compile".to_string(),
+            ".".to_string(),
+            "--output-dir".to_string(),
+            output_dir.display().to_string(),
+            "--strict".to_string(),
+            "--config-profile".to_string(),
+            config_profile::NO_PROFILE.to_string(),
+        ];
+        if offline {
+            args.push("--offline".to_string());
+        }
+        docker::run_reproducible(&project_root, &rust_version, &sdk_version, &args, offline)?;
+        find_artifacts_dir(&release_dir)?
+    } else {
+        let mut config = CompileConfig::new(&project_root);
+        config.output_dir = output_dir;
+        config.use_git_source = true;
+        config.offline = offline;
+        config.timeouts = timeouts.clone();
+        // Partial artifacts must not be released
+        config.strict = true;
+
+        let result = build(&config).context("Compilation failed")?;
+        let artifacts = result
+            .artifacts
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("Artifact generation is required for releases"))?;
+        save_artifacts(
+            artifacts,
+            result.contract.artifact_name(),
+            &result.outputs.wasm,
+            &result.outputs.rwasm,
+            &config.output_directory(),
+            &config.artifacts,
+        )?
+        .output_dir
+    };
+
+    if let Some(guard) = partial_guard {
+        guard.disarm();
+    }
+
+    let record = fluent_builder::finalize_release(&project_root, &tag, &artifacts_dir, &timeouts)?;
+
+    println!("✅ Created tag {} for {}", record.version, record.contract);
+    println!("   rWASM hash: {}", record.rwasm_hash);
+    println!("📁 Artifacts: {}", record.artifacts_dir);
+    println!("📝 Updated CHANGELOG.md and deployments.json");
+    println!("\nNext steps:");
+    println!(
+        "   git add CHANGELOG.md deployments.json && git commit -m \"Release {}\"",
+        tag
+    );
+    println!("   git push origin {}", tag);
+
+    Ok(())
+}
+
+/// Locate the `<name>.wasm` artifacts directory written to `output_dir` by a build
+///
+/// Several candidates are an error rather than a guess in directory order.
+fn find_artifacts_dir(output_dir: &Path) -> Result<PathBuf> {
+    let mut found: Vec<PathBuf> = std::fs::read_dir(output_dir)
+        .with_context(|| format!("Failed to read {}", output_dir.display()))?
+        .flatten()
+        .map(|e| e.path())
+        .filter_map(|p| {
+            let latest = p.join(fluent_builder::LATEST_LINK);
+            if p.is_dir() && p.extension().is_some_and(|ext| ext == "wasm") {
+                Some(p)
+            } else {
+                // Content-addressed output keeps one directory per build
+                latest.is_dir().then_some(latest)
+            }
+        })
+        .collect();
+    found.sort();
+    match found.as_slice() {
+        [] => Err(eyre::eyre!(
+            "No artifacts found in {}",
+            output_dir.display()
+        )),
+        [dir] => Ok(dir.clone()),
+        dirs => Err(eyre::eyre!(
+            "Several artifacts in {}: {}; remove the stale ones",
+            output_dir.display(),
+            dirs.iter()
+                .filter_map(|dir| dir.strip_prefix(output_dir).ok())
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Print or merge the genesis account preloading the built contract at `address`
+fn run_export_genesis(
+    project_root: &Path,
+    address: &str,
+    artifacts_dir: Option<PathBuf>,
+    balance: Option<&str>,
+    storage: &[String],
+    genesis: Option<&Path>,
+) -> Result<()> {
+    let artifacts_dir = match artifacts_dir {
+        Some(dir) => dir,
+        None => find_artifacts_dir(&project_root.join("out"))?,
+    };
+    let rwasm_path = artifacts_dir.join("lib.rwasm");
+    let rwasm = std::fs::read(&rwasm_path)
+        .with_context(|| format!("Failed to read {}", rwasm_path.display()))?;
+
+    let mut account = fluent_builder::GenesisAccount::contract(&rwasm);
+    if let Some(balance) = balance {
+        let wei = match balance.strip_prefix("0x") {
+            Some(hex) => ethers::types::U256::from_str_radix(hex, 16).map_err(|e| e.to_string()),
+            None => ethers::types::U256::from_dec_str(balance).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| eyre::eyre!("Invalid balance {}: {}", balance, e))?;
+        account.balance = format!("{:#x}", wei);
+    }
+    for slot in storage {
+        let (key, value) = slot
+            .split_once('=')
+            .ok_or_else(|| eyre::eyre!("Invalid storage slot {}; expected SLOT=VALUE", slot))?;
+        account
+            .storage
+            .insert(storage_word(key)?, storage_word(value)?);
+    }
+    let address = fluent_builder::normalize_genesis_address(address)?;
+    let accounts = std::collections::BTreeMap::from([(address.clone(), account)]);
+
+    match genesis {
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let mut genesis: serde_json::Value = serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            fluent_builder::merge_into_genesis(&mut genesis, &accounts)?;
+            std::fs::write(path, serde_json::to_string_pretty(&genesis)? + "\n")
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "✅ Preloaded {} at {} in {}",
+                artifacts_dir.display(),
+                address,
+                path.display()
+            );
+        }
+        None => println!("{}", serde_json::to_string_pretty(&accounts)?),
+    }
+    Ok(())
+}
+
+/// A storage slot or value as a 0x-prefixed 32-byte word
+fn storage_word(value: &str) -> Result<String> {
+    let word = ethers::types::U256::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|e| eyre::eyre!("Invalid storage word {}: {}", value, e))?;
+    let mut bytes = [0u8; 32];
+    word.to_big_endian(&mut bytes);
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+/// Print event topics of built artifacts, computed from their ABI
+fn run_topics(project_root: &Path, artifacts_dir: Option<PathBuf>, json: bool) -> Result<()> {
+    let artifacts_dir = match artifacts_dir {
+        Some(dir) => dir,
+        None => find_artifacts_dir(&project_root.join("out"))?,
+    };
+    let artifacts = ContractArtifacts::load(&artifacts_dir)?;
+    let topics = fluent_builder::extract_event_topics(&artifacts.abi);
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::Topics {
+                contract_name: artifacts.metadata.contract.name.clone(),
+                topics,
+            },
+        };
+        println!("{}", output.to_json()?);
+    } else if topics.is_empty() {
+        println!("{} declares no events", artifacts.metadata.contract.name);
+    } else {
+        for (signature, topic) in &topics {
+            println!("{}  {}", topic, signature);
+        }
+    }
+    Ok(())
+}
+
+/// Print where the bytes of lib.wasm go, largest first
+fn run_size(
+    project_root: &Path,
+    artifacts_dir: Option<PathBuf>,
+    wasm: Option<PathBuf>,
+    top: usize,
+    json: bool,
+) -> Result<()> {
+    let wasm_path = match wasm {
+        Some(path) => path,
+        None => {
+            let artifacts_dir = match artifacts_dir {
+                Some(dir) => dir,
+                None => find_artifacts_dir(&project_root.join("out"))?,
+            };
+            SavedPaths::discover(&artifacts_dir)?.wasm_path
+        }
+    };
+    let wasm = std::fs::read(&wasm_path)
+        .with_context(|| format!("Failed to read {}", wasm_path.display()))?;
+    let mut report = fluent_builder::analyze_wasm_size(&wasm)
+        .with_context(|| format!("Failed to parse {}", wasm_path.display()))?;
+    report.truncate(top);
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::Size { report },
+        };
+        println!("{}", output.to_json()?);
+        return Ok(());
+    }
+
+    println!(
+        "{}: {}",
+        wasm_path.display(),
+        fluent_builder::format_size(report.total as u64)
+    );
+    for (title, entries) in [
+        ("Sections", &report.sections),
+        ("Crates", &report.crates),
+        ("Functions", &report.functions),
+    ] {
+        if entries.is_empty() {
+            continue;
+        }
+        println!("\n{}:", title);
+        for entry in entries {
+            println!(
+                "  {:>10}  {:>5.1}%  {}",
+                fluent_builder::format_size(entry.size as u64),
+                entry.percent,
+                entry.name
+            );
+        }
+    }
+    if report.functions.is_empty() {
+        println!("\nNo function bodies found");
+    } else if report
+        .functions
+        .iter()
+        .all(|function| function.name.starts_with("func["))
+    {
+        println!(
+            "\nThe module has no name section; build without stripping symbols to see function \
+             names"
+        );
+    }
+    Ok(())
+}
+
+/// Generate a Rust SDK client, alloy or TypeScript bindings for the contract described by an ABI
+/// file
+fn run_bindgen(
+    abi_path: &Path,
+    lang: &str,
+    name: Option<String>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let abi = read_abi_file(abi_path)?;
+    let name = match name {
+        Some(name) => name,
+        None => contract_name_of_abi_file(abi_path)?,
+    };
+    let code = match lang {
+        "alloy" => fluent_builder::generate_alloy_bindings(&name, &abi)?,
+        "typescript" => fluent_builder::generate_typescript_bindings(&name, &abi)?,
+        _ => fluent_builder::generate_rust_client(&name, &abi)?,
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(path, code)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "✅ Wrote {} bindings of {} to {}",
+                lang,
+                name,
+                path.display()
+            );
+        }
+        None => print!("{}", code),
+    }
+    Ok(())
+}
+
+/// JSON ABI or human-readable declarations
+fn read_abi_file(path: &Path) -> Result<fluent_builder::Abi> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    match serde_json::from_str::<fluent_builder::Abi>(&content) {
+        Ok(abi) => Ok(abi),
+        _ => fluent_builder::parse_human_readable_abi(&content),
+    }
+}
+
+/// `erc20.json` -> `erc20`, `out/token.wasm/abi.json` -> `token`
+fn contract_name_of_abi_file(path: &Path) -> Result<String> {
+    let stem = |p: &Path| p.file_stem().and_then(|s| s.to_str()).map(str::to_string);
+    let name = match stem(path) {
+        Some(name) if name == "abi" => path.parent().and_then(|dir| {
+            let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+            stem(&dir)
+        }),
+        name => name,
+    };
+    name.ok_or_else(|| {
+        eyre::eyre!(
+            "Cannot derive a contract name from {}; pass --name",
+            path.display()
+        )
+    })
+}
+
+/// Refuse stale or modified artifacts, without building anything
+fn run_precheck(project_root: &Path, artifacts_dir: Option<PathBuf>, json: bool) -> Result<()> {
+    let artifacts_dir = match artifacts_dir {
+        Some(dir) => dir,
+        None => find_artifacts_dir(&project_root.join("out"))?,
+    };
+    let report = fluent_builder::precheck(project_root, &artifacts_dir)?;
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::Precheck {
+                passed: report.passed(),
+                contract_name: report.contract.clone(),
+                artifacts_dir: artifacts_dir.display().to_string(),
+                issues: report.issues.clone(),
+            },
+        };
+        println!("{}", output.to_json()?);
+    } else if report.passed() {
+        println!(
+            "✅ Artifacts of {} in {} match metadata.json and the sources",
+            report.contract,
+            artifacts_dir.display()
+        );
+    } else {
+        println!(
+            "❌ Artifacts of {} in {} can't be deployed:",
+            report.contract,
+            artifacts_dir.display()
+        );
+        for issue in &report.issues {
+            println!("   - {}", issue);
+        }
+        println!("\n💡 Rebuild with `fluent-builder compile` before deploying");
+    }
+
+    if !report.passed() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Show the migration diff and apply it after confirmation
+fn run_migrate(
+    project_root: &Path,
+    feature_renames: &[(String, String)],
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    let changes = fluent_builder::plan_migration(project_root, feature_renames)?;
+
+    if changes.is_empty() {
+        println!("✅ Project is up to date, nothing to migrate");
+        return Ok(());
+    }
+
+    println!("🔧 Planned changes:");
+    for change in &changes {
+        println!("   - {}: {}", change.path.display(), change.reason);
+    }
+    println!();
+    for change in &changes {
+        print!("{}", change.diff());
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !yes && !confirm("\nApply these changes?")? {
+        println!("Aborted, no files changed");
+        return Ok(());
+    }
+
+    fluent_builder::apply_migration(project_root, &changes)?;
+    println!("✅ Applied {} change(s)", changes.len());
+
+    Ok(())
+}
+
+/// Show how an artifacts directory would be upgraded and upgrade it after confirmation
+fn run_artifacts_migrate(
+    dir: &Path,
+    project_root: Option<&Path>,
+    dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    let migration = fluent_builder::plan_artifacts_migration(dir, project_root)?;
+
+    for skipped in &migration.skipped {
+        println!("⚠️  Not derivable, left out: {}", skipped);
+    }
+    if migration.is_empty() {
+        println!(
+            "✅ {} is in the current layout, nothing to migrate",
+            dir.display()
+        );
+        return Ok(());
+    }
+
+    println!("🔧 Planned changes in {}:", dir.display());
+    for (from, to) in &migration.renames {
+        println!("   - rename {} -> {}", from, to);
+    }
+    for change in &migration.changes {
+        println!("   - {}: {}", change.path.display(), change.reason);
+    }
+    println!();
+    for change in &migration.changes {
+        print!("{}", change.diff());
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !yes && !confirm("\nApply these changes?")? {
+        println!("Aborted, no files changed");
+        return Ok(());
+    }
+
+    migration.apply()?;
+    println!(
+        "✅ Applied {} change(s); recorded hashes are unchanged",
+        migration.renames.len() + migration.changes.len()
+    );
+
+    Ok(())
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no
+fn confirm(question: &str) -> Result<bool> {
+    use std::io::Write;
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// List SDK releases, newest first
+fn run_sdk_list(project_root: &Path, json: bool) -> Result<()> {
+    let releases = fluent_builder::list_sdk_releases(&Timeouts::default())?;
+    let current = fluent_builder::read_sdk_version_from_cargo_lock(project_root)
+        .ok()
+        .map(|v| v.split('-').next().unwrap_or_default().to_string());
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::SdkList {
+                releases: releases
+                    .iter()
+                    .rev()
+                    .map(|r| SdkReleaseJson {
+                        version: r.version.to_string(),
+                        tag: r.tag.clone(),
+                        commit: r.commit.clone(),
+                        docker_image: fluent_builder::builder_image(&r.tag),
+                        rwasm_translator: r.rwasm_translator(),
+                    })
+                    .collect(),
+                current,
+            },
+        };
+        println!("{}", output.to_json()?);
+        return Ok(());
+    }
+
+    println!("📦 fluentbase-sdk releases:");
+    for release in releases.iter().rev() {
+        let marker = if current.as_deref() == Some(release.version.to_string().as_str()) {
+            " (current)"
+        } else {
+            ""
+        };
+        println!("   {}{}", release.tag, marker);
+        println!("      commit:  {}", release.commit);
+        println!(
+            "      image:   {}",
+            fluent_builder::builder_image(&release.tag)
+        );
+        println!("      rWASM:   {}", release.rwasm_translator());
+    }
+
+    Ok(())
+}
+
+/// Scaffold a contract project pinned to an SDK release
+fn run_new(
+    name: &str,
+    template: ProjectTemplate,
+    sdk_version: Option<&str>,
+    path: Option<PathBuf>,
+    offline: bool,
+) -> Result<()> {
+    let sdk_tag = match sdk_version {
+        // Trust the given version when the releases can't be listed
+        Some(version) if offline => format!("v{}", version.trim_start_matches('v')),
+        _ if offline => {
+            return Err(eyre::eyre!(
+                "The latest SDK release is fetched from {}; pass --sdk-version with --offline",
+                fluent_builder::SDK_REPOSITORY
+            ))
+        }
+        Some(version) => {
+            let releases = fluent_builder::list_sdk_releases(&Timeouts::default())?;
+            fluent_builder::resolve_sdk_release(&releases, version)?
+                .tag
+                .clone()
+        }
+        None => {
+            let releases = fluent_builder::list_sdk_releases(&Timeouts::default())?;
+            releases
+                .last()
+                .ok_or_else(|| eyre::eyre!("No SDK releases published"))?
+                .tag
+                .clone()
+        }
+    };
+
+    let dir = path.unwrap_or_else(|| PathBuf::from(name));
+    let files = fluent_builder::new_project(&dir, name, template, &sdk_tag)?;
+
+    println!(
+        "✅ Created {} with fluentbase-sdk {}",
+        dir.display(),
+        sdk_tag
+    );
+    for file in &files {
+        println!("   - {}", file.display());
+    }
+    println!("\nNext steps:");
+    println!("   cd {}", dir.display());
+    println!("   git init && git add . && git commit -m \"Initial commit\"");
+    println!("   fluent-builder compile");
+
+    Ok(())
+}
+
+/// Pin a project to an SDK release
+fn run_sdk_upgrade(version: &str, project_root: &Path) -> Result<()> {
+    let releases = fluent_builder::list_sdk_releases(&Timeouts::default())?;
+    let release = fluent_builder::resolve_sdk_release(&releases, version)?;
+
+    let upgrade = fluent_builder::upgrade_sdk(project_root, release, false, &Timeouts::default())?;
+
+    println!("✅ Upgraded to fluentbase-sdk {}", release.tag);
+    println!("📝 Updated in {}:", upgrade.manifest_path.display());
+    for name in &upgrade.updated {
+        println!("   - {}", name);
+    }
+    println!(
+        "🐳 Builder image: {}",
+        fluent_builder::builder_image(&release.tag)
+    );
+    println!("⚙️  rWASM translator: {}", release.rwasm_translator());
+
+    Ok(())
+}
+
+/// Print a JSON ABI as human-readable declarations, or declarations as a JSON ABI
+fn run_abi_convert(file: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    match serde_json::from_str::<fluent_builder::Abi>(&content) {
+        Ok(abi) => {
+            for declaration in fluent_builder::format_human_readable_abi(&abi) {
+                println!("{}", declaration);
+            }
+        }
+        _ => {
+            let abi = fluent_builder::parse_human_readable_abi(&content)?;
+            println!("{}", serde_json::to_string_pretty(&abi)?);
+        }
+    }
+    Ok(())
+}
+
+/// One build of `watch`, printed as a JSON line
+#[derive(Serialize)]
+struct WatchBuild {
+    contract_name: String,
+    rwasm_hash: String,
+    wasm_size: usize,
+    rwasm_size: usize,
+    /// Change since the previous successful build
+    wasm_size_change: i64,
+    rwasm_size_change: i64,
+}
+
+/// Rebuild the project each time its sources change, until interrupted
+fn run_watch(
     project_root: PathBuf,
     output_dir: PathBuf,
     profile: String,
     features: Vec<String>,
     no_default_features: bool,
-    allow_dirty: bool,
-    no_docker: bool,
     json: bool,
+    offline: bool,
 ) -> Result<()> {
-    // Resolve project root to absolute path first
-    let project_root = project_root
-        .canonicalize()
-        .context("Failed to resolve project path")?;
-    
-    // Early version detection - fail fast if prerequisites missing
-    let (rust_version, sdk_version) = detect_project_versions(&project_root)?;
-    
-    tracing::info!("Detected Rust version: {}", rust_version);
-    tracing::info!("Detected SDK version: {}", sdk_version);
+    let mut config = CompileConfig::new(fluent_builder::canonicalize_path(&project_root)?);
+    config.output_dir = output_dir;
+    config.profile = profile;
+    config.features = features;
+    config.no_default_features = no_default_features;
+    config.offline = offline;
+    // Work in progress is rarely committed
+    config.use_git_source = false;
+    config.build_cache = build_cache();
+    active_profile().apply_artifacts(&mut config.artifacts);
+
+    if !json {
+        println!(
+            "👀 Watching {} (Ctrl-C to stop)",
+            config.project_root.display()
+        );
+    }
+    let mut previous: Option<(usize, usize)> = None;
+    fluent_builder::watch(&config, |result| {
+        let saved = result.and_then(|result| {
+            if let Some(artifacts) = &result.artifacts {
+                save_artifacts(
+                    artifacts,
+                    result.contract.artifact_name(),
+                    &result.outputs.wasm,
+                    &result.outputs.rwasm,
+                    &config.output_directory(),
+                    &config.artifacts,
+                )?;
+            }
+            Ok(result)
+        });
+        let result = match saved {
+            Ok(result) => result,
+            Err(e) if json => {
+                println!("{}", serde_json::json!({ "error": format!("{:#}", e) }));
+                return std::ops::ControlFlow::Continue(());
+            }
+            Err(e) => {
+                println!("❌ {:#}", e);
+                return std::ops::ControlFlow::Continue(());
+            }
+        };
+
+        let sizes = (result.outputs.wasm.len(), result.outputs.rwasm.len());
+        let (wasm_before, rwasm_before) = previous.unwrap_or(sizes);
+        previous = Some(sizes);
+        let build = WatchBuild {
+            contract_name: result.contract.name.clone(),
+            rwasm_hash: format!("0x{:x}", Sha256::digest(&result.outputs.rwasm)),
+            wasm_size: sizes.0,
+            rwasm_size: sizes.1,
+            wasm_size_change: sizes.0 as i64 - wasm_before as i64,
+            rwasm_size_change: sizes.1 as i64 - rwasm_before as i64,
+        };
+        if json {
+            match serde_json::to_string(&build) {
+                Ok(line) => println!("{}", line),
+                Err(e) => tracing::warn!("Failed to serialize the build: {}", e),
+            }
+        } else {
+            println!(
+                "✅ {} in {:.2}s: lib.wasm {} bytes ({:+}), lib.rwasm {} bytes ({:+}), rWASM hash \
+                 {}",
+                build.contract_name,
+                result.duration.as_secs_f64(),
+                build.wasm_size,
+                build.wasm_size_change,
+                build.rwasm_size,
+                build.rwasm_size_change,
+                build.rwasm_hash
+            );
+        }
+        std::ops::ControlFlow::Continue(())
+    })
+}
+
+/// Print the ABI of the project each time it changes, until interrupted
+fn run_abi_watch(project_root: &Path, naming: AbiNaming, interval_ms: u64) -> Result<()> {
+    let mut watcher = fluent_builder::AbiWatcher::new(project_root, naming)?;
+    let mut previous = None;
+    loop {
+        let snapshot = watcher.refresh()?;
+        if previous.as_ref() != Some(&snapshot) {
+            println!("{}", serde_json::to_string(&snapshot)?);
+            previous = Some(snapshot);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(interval_ms));
+    }
+}
+
+/// Run selector generation against the bundled ABI reference vectors
+fn run_abi_self_test(json: bool) -> Result<()> {
+    let report = fluent_builder::run_abi_self_test()?;
+
+    let failures: Vec<String> = report
+        .failures
+        .iter()
+        .map(|f| {
+            format!(
+                "{}: {} (expected {}, got {})",
+                f.fixture,
+                f.signature,
+                f.expected.as_deref().unwrap_or("none"),
+                f.actual.as_deref().unwrap_or("none")
+            )
+        })
+        .collect();
+
+    if json {
+        let output = Output::Success {
+            data: SuccessData::AbiSelfTest {
+                passed: report.is_success(),
+                fixtures: report.fixtures,
+                signatures: report.signatures,
+                failures,
+            },
+        };
+        println!("{}", output.to_json()?);
+    } else if report.is_success() {
+        println!(
+            "✅ ABI self-test passed: {} signatures across {} fixtures",
+            report.signatures, report.fixtures
+        );
+    } else {
+        println!("❌ ABI self-test found {} divergences:", failures.len());
+        for failure in &failures {
+            println!("   - {}", failure);
+        }
+    }
+
+    if !report.is_success() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Fetch the bytecode of a deployed contract
+pub(crate) async fn fetch_bytecode(address: &str, rpc_url: &str, chain_id: u64) -> Result<Vec<u8>> {
+    fetch_bytecode_at(address, rpc_url, chain_id, None).await
+}
+
+/// Code at `address` as of `block` (latest if None); older blocks need an archive node
+async fn fetch_bytecode_at(
+    address: &str,
+    rpc_url: &str,
+    chain_id: u64,
+    block: Option<u64>,
+) -> Result<Vec<u8>> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create provider")?;
+
+    // Verify chain ID matches; an endpoint's chain id is cached between invocations
+    let cache_key = format!("rpc.chain_id:{}", rpc_url);
+    let network_chain_id = match fact_cache()
+        .get(&cache_key)
+        .and_then(|id| id.parse::<u64>().ok())
+    {
+        Some(id) => id,
+        None => {
+            let id = with_rpc_timeout("eth_chainId", provider.get_chainid())
+                .await?
+                .context("Failed to get chain ID")?
+                .as_u64();
+            fact_cache().set(&cache_key, id.to_string());
+            id
+        }
+    };
+
+    if network_chain_id != chain_id {
+        return Err(eyre::eyre!(
+            "Chain ID mismatch: expected {}, got {}",
+            chain_id,
+            network_chain_id
+        ));
+    }
+
+    // Parse address
+    let contract_address: Address = address.parse().context("Invalid contract address")?;
+
+    // Get bytecode
+    let bytecode = with_rpc_timeout(
+        "eth_getCode",
+        provider.get_code(contract_address, block.map(Into::into)),
+    )
+    .await?
+    .with_context(|| match block {
+        Some(block) => format!(
+            "Failed to fetch contract bytecode at block {} (this needs an archive node)",
+            block
+        ),
+        None => "Failed to fetch contract bytecode".to_string(),
+    })?;
+
+    if bytecode.is_empty() {
+        return Err(match block {
+            Some(block) => eyre::eyre!(
+                "No bytecode found at address {} at block {}",
+                address,
+                block
+            ),
+            None => eyre::eyre!("No bytecode found at address {}", address),
+        });
+    }
+
+    Ok(bytecode.to_vec())
+}
+
+/// Fail unless `rpc_url` serves `chain_id`, so nothing is sent to another chain
+async fn check_chain_id(rpc_url: &str, chain_id: u64) -> Result<()> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create provider")?;
+    let network_chain_id = with_rpc_timeout("eth_chainId", provider.get_chainid())
+        .await?
+        .context("Failed to get chain ID")?
+        .as_u64();
+    if network_chain_id != chain_id {
+        return Err(eyre::eyre!(
+            "Chain ID mismatch: {} serves chain {}, expected {}",
+            rpc_url,
+            network_chain_id,
+            chain_id
+        ));
+    }
+    Ok(())
+}
+
+/// Send a contract creation transaction and return the created address
+async fn send_deployment(
+    calldata: Vec<u8>,
+    wallet: &DeploySigner,
+    rpc_url: &str,
+    chain_id: u64,
+) -> Result<String> {
+    check_chain_id(rpc_url, chain_id).await?;
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create provider")?;
+    let client = SignerMiddleware::new(provider, wallet.clone().with_chain_id(chain_id));
+
+    let transaction = TransactionRequest::new().data(calldata);
+    let pending = with_rpc_timeout(
+        "eth_sendRawTransaction",
+        client.send_transaction(transaction, None),
+    )
+    .await?
+    .context("Failed to send the deployment transaction")?;
+    let receipt = pending
+        .await
+        .context("Failed to wait for the deployment transaction")?
+        .ok_or_else(|| eyre::eyre!("Deployment transaction was dropped"))?;
+    if receipt.status != Some(1.into()) {
+        return Err(eyre::eyre!(
+            "Deployment transaction {:?} reverted",
+            receipt.transaction_hash
+        ));
+    }
+    receipt
+        .contract_address
+        .map(|address| format!("{:?}", address))
+        .ok_or_else(|| eyre::eyre!("Deployment transaction created no contract"))
+}
+
+/// Input of a contract creation transaction (init code and constructor arguments)
+async fn fetch_deployment_data(tx_hash: &str, address: &str, rpc_url: &str) -> Result<Vec<u8>> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create provider")?;
+    let hash: H256 = tx_hash.parse().context("Invalid transaction hash")?;
+
+    let tx = with_rpc_timeout("eth_getTransactionByHash", provider.get_transaction(hash))
+        .await?
+        .context("Failed to fetch deployment transaction")?
+        .ok_or_else(|| eyre::eyre!("Transaction {} not found", tx_hash))?;
+    if tx.to.is_some() {
+        return Err(eyre::eyre!(
+            "Transaction {} does not create a contract",
+            tx_hash
+        ));
+    }
+
+    // The transaction must have created the contract being verified
+    let contract_address: Address = address.parse().context("Invalid contract address")?;
+    let receipt = with_rpc_timeout(
+        "eth_getTransactionReceipt",
+        provider.get_transaction_receipt(hash),
+    )
+    .await?
+    .context("Failed to fetch deployment receipt")?
+    .ok_or_else(|| eyre::eyre!("No receipt for transaction {}", tx_hash))?;
+    if receipt.contract_address != Some(contract_address) {
+        return Err(eyre::eyre!(
+            "Transaction {} did not deploy {}",
+            tx_hash,
+            address
+        ));
+    }
+
+    Ok(tx.input.to_vec())
+}
+
+/// Hash of deployed bytecode, as compared by verify
+pub(crate) fn bytecode_hash(bytecode: &[u8]) -> String {
+    format!("0x{:x}", Sha256::digest(bytecode))
+}
+
+/// Bound an RPC request by the default RPC timeout
+async fn with_rpc_timeout<T>(
+    operation: &str,
+    request: impl std::future::Future<Output = T>,
+) -> Result<T> {
+    let Some(timeout) = Timeouts::default().rpc else {
+        return Ok(request.await);
+    };
+
+    tokio::time::timeout(timeout, request).await.map_err(|_| {
+        ProcessError::TimedOut {
+            operation: format!("RPC {}", operation),
+            timeout,
+        }
+        .into()
+    })
+}
+
+/// Handle SIGINT/SIGTERM: stop child processes, remove containers, worktrees
+/// and partial outputs, then report the cancellation
+fn handle_interrupt() {
+    fluent_builder::run_pending_cleanups();
+
+    let output = Output::Cancelled {
+        message: "Operation cancelled by signal".to_string(),
+    };
+    eprintln!("{}", output.to_json().unwrap());
+    std::process::exit(EXIT_CANCELLED);
+}
+
+fn output_error(error: eyre::Report) {
+    let timed_out = error
+        .chain()
+        .any(|e| e.downcast_ref::<ProcessError>().is_some());
+    let rpc_failed = error
+        .chain()
+        .any(|e| e.downcast_ref::<ProviderError>().is_some());
+
+    let error_type = match BuilderError::find(&error) {
+        Some(builder_error) => builder_error.kind(),
+        None if timed_out => "timeout",
+        None if rpc_failed => "network_error",
+        None => "unknown_error",
+    };
+
+    let output = Output::Error {
+        error_type: error_type.to_string(),
+        message: format!("{:#}", error),
+    };
+
+    eprintln!("{}", output.to_json().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_parsing() {
+        let cli = Cli::parse_from(["fluent-builder", "compile"]);
+        assert!(matches!(cli.command, Commands::Compile { .. }));
+
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "verify",
+            "--address",
+            "0x123",
+            "--chain-id",
+            "20993",
+            "--rpc",
+            "https://rpc.endpoint",
+        ]);
+        assert!(matches!(cli.command, Commands::Verify { .. }));
+    }
+
+    #[test]
+    fn test_verify_historical_version() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "verify",
+            "--network",
+            "testnet",
+            "--address",
+            "0x123",
+            "--at-block",
+            "1200000",
+            "--git-ref",
+            "v1.0.0",
+            "--record",
+        ]);
+        if let Commands::Verify {
+            at_block,
+            git_ref,
+            record,
+            ..
+        } = cli.command
+        {
+            assert_eq!(at_block, Some(1_200_000));
+            assert_eq!(git_ref.as_deref(), Some("v1.0.0"));
+            assert!(record);
+        } else {
+            panic!("expected verify command");
+        }
+    }
+
+    #[test]
+    fn test_verify_in_docker() {
+        let args = [
+            "fluent-builder",
+            "verify",
+            "--network",
+            "testnet",
+            "--address",
+            "0x123",
+            "--docker",
+        ];
+        assert!(
+            Cli::try_parse_from(args).is_err(),
+            "--docker needs the recorded image"
+        );
+
+        let cli =
+            Cli::parse_from([&args[..], &["--metadata", "out/token.wasm/metadata.json"]].concat());
+        if let Commands::Verify {
+            docker, metadata, ..
+        } = cli.command
+        {
+            assert!(docker);
+            assert_eq!(
+                metadata,
+                Some(PathBuf::from("out/token.wasm/metadata.json"))
+            );
+        } else {
+            panic!("expected verify command");
+        }
+    }
+
+    #[test]
+    fn test_verify_from_metadata() {
+        // Build settings not given on the command line come from --metadata
+        let args = [
+            "fluent-builder",
+            "verify",
+            "--network",
+            "testnet",
+            "--address",
+            "0x123",
+            "--metadata",
+            "metadata.json",
+        ];
+        if let Commands::Verify {
+            profile,
+            features,
+            rustflags,
+            ..
+        } = Cli::parse_from(args).command
+        {
+            assert_eq!((profile, features), (None, Vec::new()));
+            assert_eq!(rustflags, None);
+        } else {
+            panic!("expected verify command");
+        }
+        if let Commands::Verify {
+            profile, rustflags, ..
+        } = Cli::parse_from(
+            [
+                &args[..],
+                &["--profile", "debug", "--rustflags", "-C opt-level=s"],
+            ]
+            .concat(),
+        )
+        .command
+        {
+            assert_eq!(profile.as_deref(), Some("debug"));
+            assert_eq!(rustflags.as_deref(), Some("-C opt-level=s"));
+        } else {
+            panic!("expected verify command");
+        }
+    }
+
+    #[test]
+    fn test_verify_constructor_args() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "verify",
+            "--address",
+            "0x123",
+            "--chain-id",
+            "20993",
+            "--rpc",
+            "https://rpc.endpoint",
+            "--constructor-values",
+            "1000",
+            "0xabc",
+            "--record",
+        ]);
+        if let Commands::Verify {
+            constructor_values,
+            constructor_args,
+            record,
+            at_block,
+            ..
+        } = cli.command
+        {
+            assert_eq!(constructor_values, vec!["1000", "0xabc"]);
+            assert_eq!(constructor_args, None);
+            assert!(record);
+            assert_eq!(at_block, None);
+        } else {
+            panic!("expected verify command");
+        }
+
+        let conflict = Cli::try_parse_from([
+            "fluent-builder",
+            "verify",
+            "--address",
+            "0x123",
+            "--chain-id",
+            "20993",
+            "--rpc",
+            "https://rpc.endpoint",
+            "--constructor-args",
+            "0x00",
+            "--constructor-values",
+            "1",
+        ]);
+        assert!(conflict.is_err());
+    }
+
+    #[test]
+    fn test_compile_settings() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            "--profile",
+            "debug",
+            "--features",
+            "test feature2",
+            "--no-default-features",
+        ]);
+
+        if let Commands::Compile {
+            profile,
+            features,
+            no_default_features,
+            ..
+        } = cli.command
+        {
+            assert_eq!(profile, "debug");
+            assert_eq!(features, vec!["test", "feature2"]);
+            assert!(no_default_features);
+        }
+
+        // The server passes the setting of a job explicitly
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            "--no-default-features=false",
+            ".",
+        ]);
+        if let Commands::Compile {
+            no_default_features,
+            project_root,
+            ..
+        } = cli.command
+        {
+            assert!(!no_default_features);
+            assert_eq!(project_root, PathBuf::from("."));
+        }
+
+        // watch builds the same way
+        for (args, expected) in [
+            (&[][..], true),
+            (&["--no-default-features"][..], true),
+            (&["--no-default-features=false"][..], false),
+        ] {
+            let cli = Cli::parse_from(["fluent-builder", "watch"].iter().chain(args));
+            let Commands::Watch {
+                no_default_features,
+                ..
+            } = cli.command
+            else {
+                panic!("expected watch command");
+            };
+            assert_eq!(no_default_features, expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_abi_naming_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--abi-naming", "camel-case"]);
+        let Commands::Compile { abi_naming, .. } = cli.command else {
+            panic!("expected compile command");
+        };
+        assert_eq!(abi_naming, AbiNaming::CamelCase);
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "compile", "--abi-naming", "snake"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_archive_glob_flags() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            "--archive-include",
+            "assets/**",
+            "--archive-include",
+            "contracts/*.sol",
+            "--archive-exclude",
+            "src/generated/**",
+        ]);
+        let Commands::Compile {
+            archive_include,
+            archive_exclude,
+            ..
+        } = cli.command
+        else {
+            panic!("expected compile command");
+        };
+        assert_eq!(archive_include, vec!["assets/**", "contracts/*.sol"]);
+        assert_eq!(archive_exclude, vec!["src/generated/**"]);
+    }
+
+    #[test]
+    fn test_new_command() {
+        let cli = Cli::parse_from(["fluent-builder", "new", "my-token", "--template", "erc20"]);
+        if let Commands::New {
+            name,
+            template,
+            sdk_version,
+            path,
+        } = cli.command
+        {
+            assert_eq!(name, "my-token");
+            assert_eq!(template, ProjectTemplate::Erc20);
+            assert!(sdk_version.is_none() && path.is_none());
+        } else {
+            panic!("expected new command");
+        }
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "new", "token", "--template", "nft"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_selector_collisions_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile {
+                selector_collisions: SelectorCollisions::Error,
+                ..
+            }
+        ));
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--selector-collisions", "warn"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile {
+                selector_collisions: SelectorCollisions::Warn,
+                ..
+            }
+        ));
+        assert!(Cli::try_parse_from([
+            "fluent-builder",
+            "compile",
+            "--selector-collisions",
+            "ignore"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_size_limit_flags() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            "--max-wasm-size",
+            "2M",
+            "--size-limit",
+            "warn",
+        ]);
+        if let Commands::Compile {
+            max_wasm_size,
+            max_rwasm_size,
+            size_limit,
+            ..
+        } = cli.command
+        {
+            assert_eq!(max_wasm_size, Some(2 << 20));
+            assert_eq!(max_rwasm_size, None);
+            assert_eq!(size_limit, SizeLimitPolicy::Warn);
+        } else {
+            panic!("expected compile command");
+        }
+        let cli = Cli::parse_from(["fluent-builder", "compile"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile {
+                size_limit: SizeLimitPolicy::Error,
+                ..
+            }
+        ));
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "compile", "--max-wasm-size", "big"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_assert_reproducible_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile {
+                assert_reproducible: None,
+                ..
+            }
+        ));
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--assert-reproducible"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile {
+                assert_reproducible: Some(ReproducibilityMode::Local),
+                ..
+            }
+        ));
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            "--assert-reproducible=docker",
+            "./token",
+        ]);
+        if let Commands::Compile {
+            assert_reproducible,
+            project_root,
+            ..
+        } = cli.command
+        {
+            assert_eq!(assert_reproducible, Some(ReproducibilityMode::Docker));
+            assert_eq!(project_root, PathBuf::from("./token"));
+        } else {
+            panic!("expected compile command");
+        }
+    }
+
+    #[test]
+    fn test_simulate_deploy_command() {
+        let image = format!("ghcr.io/fluentlabs-xyz/fluent@sha256:{}", "ab".repeat(32));
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "simulate-deploy",
+            "--network",
+            "devnet",
+            "--fork-image",
+            &image,
+            "--contract",
+            "token",
+        ]);
+        if let Commands::SimulateDeploy {
+            network,
+            fork_rpc,
+            fork_image,
+            contracts,
+            ..
+        } = cli.command
+        {
+            assert_eq!(network, "devnet");
+            assert!(fork_rpc.is_none());
+            assert_eq!(fork_image, Some(image));
+            assert_eq!(contracts, vec!["token"]);
+        } else {
+            panic!("expected simulate-deploy command");
+        }
+
+        // The node image is pinned by digest unless a running node is used
+        let simulate = |args: &[&str]| {
+            Cli::try_parse_from(
+                ["fluent-builder", "simulate-deploy", "--network", "devnet"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+        assert!(simulate(&[]).is_err());
+        assert!(simulate(&["--fork-image", "ghcr.io/fluentlabs-xyz/fluent:latest"]).is_err());
+        assert!(simulate(&["--fork-rpc", "http://127.0.0.1:8545"]).is_ok());
+    }
+
+    #[test]
+    fn test_runtime_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--runtime", "podman"]);
+        assert_eq!(cli.runtime, Some(docker::ContainerRuntime::Podman));
+        assert!(Cli::try_parse_from(["fluent-builder", "compile", "--runtime", "lxc"]).is_err());
+    }
+
+    #[test]
+    fn test_standard_json_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--standard-json"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile {
+                standard_json: true,
+                ..
+            }
+        ));
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            "--standard-json",
+            "--no-docker",
+        ]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile {
+                standard_json: true,
+                no_docker: true,
+                ..
+            }
+        ));
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "compile", "--standard-json", "--json"])
+                .is_err()
+        );
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "compile", "--standard-json", "--nix"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_config_profile_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--config-profile", "ci"]);
+        assert_eq!(cli.config_profile.as_deref(), Some("ci"));
+        let cli = Cli::parse_from(["fluent-builder", "compile"]);
+        assert!(cli.config_profile.is_none());
+
+        let args = pin_config_profile(vec!["compile".to_string()]);
+        assert_eq!(args, ["compile", "--config-profile", "none"]);
+        let args = vec!["compile".to_string(), "--config-profile=dev".to_string()];
+        assert_eq!(pin_config_profile(args.clone()), args);
+    }
+
+    #[test]
+    fn test_strict_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--strict"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile { strict: true, .. }
+        ));
+        let cli = Cli::parse_from(["fluent-builder", "compile"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Compile { strict: false, .. }
+        ));
+    }
+
+    #[test]
+    fn test_cache_commands() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "cache",
+            "gc",
+            "--max-size",
+            "20G",
+            "--max-age",
+            "30d",
+        ]);
+        if let Commands::Cache {
+            command:
+                CacheCommands::Gc {
+                    max_size,
+                    max_age,
+                    dry_run,
+                    json,
+                },
+        } = cli.command
+        {
+            assert_eq!(max_size, Some(20 << 30));
+            assert_eq!(max_age, Some(std::time::Duration::from_secs(30 * 86400)));
+            assert!(!dry_run && !json);
+        } else {
+            panic!("expected cache gc command");
+        }
 
-    // If Docker is requested (default), run in container and exit
-    if !no_docker {
-        if !json {
-            println!("🐳 Running compilation in Docker for reproducible builds...");
-            println!("   (Use --no-docker for faster local compilation)");
-            
-            // Warn about non-reproducible nightly
-            if rust_version == "nightly" {
-                println!("⚠️  Warning: Using 'nightly' without a specific date may not be reproducible");
-                println!("   Consider using 'nightly-YYYY-MM-DD' in rust-toolchain.toml");
+        // At least one limit is required
+        assert!(Cli::try_parse_from(["fluent-builder", "cache", "gc"]).is_err());
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "cache", "gc", "--max-size", "20X"]).is_err()
+        );
+        assert!(matches!(
+            Cli::parse_from(["fluent-builder", "cache", "stats", "--json"]).command,
+            Commands::Cache {
+                command: CacheCommands::Stats { json: true }
             }
+        ));
+    }
+
+    #[test]
+    fn test_bindgen_command() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "bindgen",
+            "--abi",
+            "erc20.json",
+            "--lang",
+            "rust-sdk",
+        ]);
+        if let Commands::Bindgen {
+            abi,
+            lang,
+            name,
+            output,
+        } = cli.command
+        {
+            assert_eq!(abi, PathBuf::from("erc20.json"));
+            assert_eq!(lang, "rust-sdk");
+            assert!(name.is_none() && output.is_none());
+        } else {
+            panic!("expected bindgen command");
         }
-        
-        // Pass all CLI arguments to Docker along with detected versions
-        let args: Vec<String> = std::env::args().skip(1).collect();
-        return docker::run_reproducible(&project_root, &rust_version, &sdk_version, &args);
+        assert!(Cli::try_parse_from([
+            "fluent-builder",
+            "bindgen",
+            "--abi",
+            "erc20.json",
+            "--lang",
+            "go"
+        ])
+        .is_err());
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "bindgen",
+            "--abi",
+            "erc20.json",
+            "--lang",
+            "typescript",
+        ]);
+        assert!(matches!(cli.command, Commands::Bindgen { lang, .. } if lang == "typescript"));
+
+        assert_eq!(
+            contract_name_of_abi_file(Path::new("abis/erc20.json")).unwrap(),
+            "erc20"
+        );
+        assert_eq!(
+            contract_name_of_abi_file(Path::new("out/token.wasm/abi.json")).unwrap(),
+            "token"
+        );
     }
 
-    // --- Local compilation starts here ---
-    
-    // Create compilation config
-    let mut config = CompileConfig::new(project_root);
-    config.output_dir = output_dir;
-    config.profile = profile;
-    config.features = features;
-    config.no_default_features = no_default_features;
+    #[test]
+    fn test_topics_command() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "topics",
+            "--artifacts-dir",
+            "out/token.wasm",
+            "--json",
+        ]);
+        if let Commands::Topics {
+            project_root,
+            artifacts_dir,
+            json,
+        } = cli.command
+        {
+            assert_eq!(project_root, PathBuf::from("."));
+            assert_eq!(artifacts_dir, Some(PathBuf::from("out/token.wasm")));
+            assert!(json);
+        } else {
+            panic!("expected topics command");
+        }
+    }
 
-    // Check Git repository status
-    let git_info = fluent_builder::detect_git_info(&config.project_root)?;
-    
-    // Validate Git state unless --allow-dirty is specified
-    if !allow_dirty {
-        match &git_info {
-            None => {
-                return Err(eyre::eyre!(
-                    "Project is not in a Git repository.\n\
-                     Initialize a Git repository or use --allow-dirty flag."
-                ));
+    #[test]
+    fn test_size_command() {
+        let cli = Cli::parse_from(["fluent-builder", "size", "--wasm", "lib.wasm", "--top", "5"]);
+        if let Commands::Size {
+            wasm, top, json, ..
+        } = cli.command
+        {
+            assert_eq!(wasm, Some(PathBuf::from("lib.wasm")));
+            assert_eq!(top, 5);
+            assert!(!json);
+        } else {
+            panic!("expected size command");
+        }
+        assert!(matches!(
+            Cli::parse_from(["fluent-builder", "size"]).command,
+            Commands::Size { top: 20, .. }
+        ));
+        assert!(Cli::try_parse_from([
+            "fluent-builder",
+            "size",
+            "--wasm",
+            "a.wasm",
+            "--artifacts-dir",
+            "out"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_upgrade_command() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "upgrade",
+            "--network",
+            "devnet",
+            "--contract",
+            "token",
+            "--proxy",
+            "@token",
+        ]);
+        if let Commands::Upgrade {
+            contract,
+            proxy,
+            upgrade_function,
+            ..
+        } = cli.command
+        {
+            assert_eq!(contract, "token");
+            assert_eq!(proxy, "@token");
+            assert_eq!(upgrade_function, "upgradeTo(address)");
+        } else {
+            panic!("expected upgrade command");
+        }
+        assert!(Cli::try_parse_from([
+            "fluent-builder",
+            "upgrade",
+            "--network",
+            "devnet",
+            "--contract",
+            "token"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_registry_commands() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile-all",
+            "--contract",
+            "token",
+            "--contract",
+            "bridge",
+        ]);
+        if let Commands::CompileAll {
+            registry,
+            contracts,
+            network,
+            jobs,
+            ..
+        } = cli.command
+        {
+            assert!(registry.is_none() && network.is_none());
+            assert_eq!(jobs, 1);
+            assert_eq!(contracts, vec!["token", "bridge"]);
+        } else {
+            panic!("expected compile-all command");
+        }
+        let cli = Cli::parse_from(["fluent-builder", "compile-all", "--no-docker"]);
+        assert!(matches!(
+            cli.command,
+            Commands::CompileAll {
+                no_docker: true,
+                ..
             }
-            Some(git) if git.is_dirty => {
-                return Err(eyre::eyre!(
-                    "Repository has {} uncommitted changes.\n\
-                     \n\
-                     To fix this:\n\
-                     1. Commit your changes: git add . && git commit -m \"Your message\"\n\
-                     2. Or stash them: git stash\n\
-                     3. Or use --allow-dirty flag",
-                    git.dirty_files_count
-                ));
+        ));
+
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "deploy",
+            "--network",
+            "testnet",
+            "--registry",
+            "repo",
+        ]);
+        assert!(matches!(
+            cli.command,
+            Commands::Deploy {
+                registry: Some(_),
+                ..
             }
-            _ => {} // Clean repository, continue
+        ));
+        // Verifying and deploying always target one network
+        assert!(Cli::try_parse_from(["fluent-builder", "verify-all"]).is_err());
+        assert!(Cli::try_parse_from(["fluent-builder", "deploy"]).is_err());
+    }
+
+    #[test]
+    fn test_find_artifacts_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(find_artifacts_dir(dir.path()).is_err());
+
+        std::fs::create_dir(dir.path().join("token.wasm")).unwrap();
+        std::fs::create_dir(dir.path().join(".host-build")).unwrap();
+        assert_eq!(
+            find_artifacts_dir(dir.path()).unwrap(),
+            dir.path().join("token.wasm")
+        );
+
+        // Not whichever directory happens to be listed first
+        std::fs::create_dir(dir.path().join("old_token.wasm")).unwrap();
+        let err = find_artifacts_dir(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("old_token.wasm, token.wasm"), "{}", err);
+    }
+
+    #[test]
+    fn test_registry_versions_outside_root() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().join("contracts");
+        std::fs::create_dir_all(&root).unwrap();
+        let err = registry_versions(&root, &[dir.path().to_path_buf()]).unwrap_err();
+        assert!(err.to_string().contains("use --no-docker"), "{}", err);
+    }
+
+    #[test]
+    fn test_cargo_args_passthrough() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            ".",
+            "--",
+            "--config",
+            "opt-level=2",
+            "--locked",
+        ]);
+        if let Commands::Compile {
+            cargo_args,
+            project_root,
+            ..
+        } = cli.command
+        {
+            assert_eq!(cargo_args, vec!["--config", "opt-level=2", "--locked"]);
+            assert_eq!(project_root, PathBuf::from("."));
         }
     }
 
-    // Determine source type for metadata
-    // - Clean Git repo → use Git source
-    // - Dirty repo or --allow-dirty → use archive source
-    config.use_git_source = match (&git_info, allow_dirty) {
-        (Some(git), false) if !git.is_dirty => true,
-        _ => false,
-    };
+    #[test]
+    fn test_nested_flags_precede_cargo_args() {
+        let args = pin_config_profile(
+            ["compile", ".", "--", "--locked"]
+                .map(String::from)
+                .to_vec(),
+        );
+        let profile = active_profile().pinned_name();
+        assert_eq!(
+            args,
+            [
+                "compile",
+                ".",
+                "--config-profile",
+                profile,
+                "--",
+                "--locked"
+            ]
+        );
 
-    // Perform compilation
-    let result = build(&config).context("Compilation failed")?;
-    let rwasm_hash = format!("0x{:x}", Sha256::digest(&result.outputs.rwasm));
+        let mut args = args;
+        insert_flags(&mut args, &["--no-docker"]);
+        assert_eq!(
+            args,
+            [
+                "compile",
+                ".",
+                "--config-profile",
+                profile,
+                "--no-docker",
+                "--",
+                "--locked"
+            ]
+        );
+        let cli = Cli::parse_from(std::iter::once("fluent-builder".to_string()).chain(args));
+        if let Commands::Compile {
+            no_docker,
+            cargo_args,
+            ..
+        } = cli.command
+        {
+            assert!(no_docker);
+            assert_eq!(cargo_args, ["--locked"]);
+        } else {
+            panic!("expected compile command");
+        }
 
-    // Output results based on format
-    if json {
-        output_json_results(&result, &rwasm_hash, &git_info, config.use_git_source)?;
-    } else {
-        output_human_results(&result, &rwasm_hash, &git_info, &config)?;
+        // A profile given only as a cargo argument is not the builder's
+        let args = pin_config_profile(
+            ["compile", "--", "--config-profile"]
+                .map(String::from)
+                .to_vec(),
+        );
+        assert_eq!(
+            args,
+            [
+                "compile",
+                "--config-profile",
+                profile,
+                "--",
+                "--config-profile"
+            ]
+        );
+
+        let mut args = vec!["compile".to_string()];
+        insert_flags(&mut args, &["--no-docker"]);
+        assert_eq!(args, ["compile", "--no-docker"]);
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_signer_flags() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "deploy",
+            "--network",
+            "testnet",
+            "--keystore",
+            "key.json",
+        ]);
+        let Commands::Deploy { signer, .. } = cli.command else {
+            panic!("expected deploy command");
+        };
+        assert_eq!(
+            signer.source(),
+            Some(signer::KeySource::Keystore(PathBuf::from("key.json")))
+        );
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "upgrade",
+            "--network",
+            "testnet",
+            "--contract",
+            "token",
+            "--proxy",
+            "@token",
+            "--mnemonic-env",
+            "DEPLOY_MNEMONIC",
+            "--mnemonic-index",
+            "2",
+        ]);
+        let Commands::Upgrade { signer, .. } = cli.command else {
+            panic!("expected upgrade command");
+        };
+        assert_eq!(
+            signer.source(),
+            Some(signer::KeySource::Mnemonic {
+                env: "DEPLOY_MNEMONIC".to_string(),
+                index: 2
+            })
+        );
+        assert!(Cli::try_parse_from([
+            "fluent-builder",
+            "deploy",
+            "--network",
+            "testnet",
+            "--keystore",
+            "key.json",
+            "--mnemonic-env",
+            "M",
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from([
+            "fluent-builder",
+            "deploy",
+            "--network",
+            "testnet",
+            "--mnemonic-index",
+            "1"
+        ])
+        .is_err());
+    }
 
-/// Output compilation results as JSON
-fn output_json_results(
-    result: &fluent_builder::CompilationResult,
-    rwasm_hash: &str,
-    git_info: &Option<GitInfo>,
-    use_git_source: bool,
-) -> Result<()> {
-    let output = Output::Success {
-        data: SuccessData::Compile {
-            contract_name: result.contract.name.clone(),
-            rwasm_hash: rwasm_hash.to_string(),
-            wasm_size: result.outputs.wasm.len(),
-            rwasm_size: result.outputs.rwasm.len(),
-            has_abi: result
-                .artifacts
-                .as_ref()
-                .map(|a| !a.abi.is_empty())
-                .unwrap_or(false),
-            output_dir: result.artifacts.as_ref().map(|_| {
-                format!("{}.wasm", result.contract.name)
-            }),
-            git_info: git_info.as_ref().map(GitInfoJson::from),
-            source_type: if use_git_source { "git" } else { "archive" }.to_string(),
-        },
-    };
-    println!("{}", serde_json::to_string(&output)?);
-    Ok(())
-}
+    #[test]
+    fn test_rustflags_flag() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            ".",
+            "--rustflags",
+            "-C target-feature=+bulk-memory",
+        ]);
+        if let Commands::Compile { rustflags, .. } = cli.command {
+            assert_eq!(rustflags.as_deref(), Some("-C target-feature=+bulk-memory"));
+        }
+    }
 
-/// Output compilation results in human-readable format
-fn output_human_results(
-    result: &fluent_builder::CompilationResult,
-    rwasm_hash: &str,
-    git_info: &Option<GitInfo>,
-    config: &CompileConfig,
-) -> Result<()> {
-    // Show Git repository info if available
-    if let Some(git) = git_info {
-        println!("📦 Git repository: {} @ {}", git.branch, git.commit_hash_short);
-        if git.is_dirty {
-            println!("⚠️  Warning: Compiling with uncommitted changes (archive source)");
+    #[test]
+    fn test_network_flag() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "verify",
+            "--address",
+            "0x1234",
+            "--network",
+            "testnet",
+        ]);
+        let Commands::Verify {
+            network,
+            rpc,
+            chain_id,
+            ..
+        } = cli.command
+        else {
+            panic!("expected verify command");
+        };
+        assert_eq!(network.as_deref(), Some("testnet"));
+        assert_eq!((rpc, chain_id), (None, None));
+        // The endpoint comes from the network or the command line
+        let err =
+            Cli::try_parse_from(["fluent-builder", "verify", "--address", "0x1234"]).unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--network", "testnet"]);
+        let Commands::Compile { network, .. } = cli.command else {
+            panic!("expected compile command");
+        };
+        assert_eq!(network.as_deref(), Some("testnet"));
+    }
+
+    #[test]
+    fn test_allow_dirty_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--allow-dirty"]);
+
+        if let Commands::Compile { allow_dirty, .. } = cli.command {
+            assert!(allow_dirty);
         }
     }
 
-    println!("✅ Successfully compiled {}", result.contract.name);
-    println!("⏱️  Compilation time: {:.2}s", result.duration.as_secs_f64());
+    #[test]
+    fn test_no_docker_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--no-docker"]);
 
-    // If artifacts were generated, save and display them
-    if let Some(artifacts) = &result.artifacts {
-        let saved = save_artifacts(
-            artifacts,
-            &result.contract.name,
-            &result.outputs.wasm,
-            &result.outputs.rwasm,
-            &config.output_directory(),
-            &config.artifacts,
-        )?;
+        if let Commands::Compile { no_docker, .. } = cli.command {
+            assert!(no_docker);
+        }
+    }
 
-        // Display source type from metadata
-        match &artifacts.metadata.source {
-            fluent_builder::Source::Git { repository, commit, .. } => {
-                println!("\n📦 Source type: Git");
-                println!("   Repository: {}", repository);
-                println!("   Commit: {}", &commit[..8]);
-            }
-            fluent_builder::Source::Archive { .. } => {
-                println!("\n📦 Source type: Archive");
-            }
+    #[test]
+    fn test_cancelled_output() {
+        let output = Output::Cancelled {
+            message: "Operation cancelled by signal".to_string(),
+        };
+        let json: serde_json::Value = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["status"], "cancelled");
+        assert_eq!(json["message"], "Operation cancelled by signal");
+    }
+
+    #[test]
+    fn test_target_dir_flag() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "compile",
+            "/src/token",
+            "--target-dir",
+            "/tmp/target",
+            "--output-dir",
+            "/tmp/out",
+        ]);
+        assert_eq!(cli.target_dir, Some(PathBuf::from("/tmp/target")));
+    }
+
+    #[test]
+    fn test_offline_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--offline"]);
+        assert!(cli.offline);
+
+        let cli = Cli::parse_from(["fluent-builder", "--offline", "build-workspace"]);
+        assert!(cli.offline);
+        assert!(matches!(cli.command, Commands::BuildWorkspace { .. }));
+    }
+
+    #[test]
+    fn test_nix_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--nix"]);
+
+        if let Commands::Compile { nix, no_docker, .. } = cli.command {
+            assert!(nix);
+            assert!(!no_docker);
+        } else {
+            panic!("expected compile command");
         }
-        
-        // Display output location and files
-        println!("\n📁 Output directory: {}", saved.output_dir.display());
-        println!("📄 Generated files:");
-        println!("   - lib.wasm ({} bytes)", result.outputs.wasm.len());
-        println!("   - lib.rwasm ({} bytes)", result.outputs.rwasm.len());
-        println!("   - rWASM hash: {}", rwasm_hash);
-        
-        // List optional artifacts
-        if saved.abi_path.is_some() {
-            println!("   - abi.json");
+
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "compile", "--nix", "--no-docker"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_changelog_command() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "changelog",
+            "v1.0.0",
+            "HEAD",
+            "-o",
+            "CHANGES.md",
+        ]);
+
+        if let Commands::Changelog {
+            old, new, output, ..
+        } = cli.command
+        {
+            assert_eq!(old, "v1.0.0");
+            assert_eq!(new, "HEAD");
+            assert_eq!(output, Some(PathBuf::from("CHANGES.md")));
+        } else {
+            panic!("expected changelog command");
         }
-        if saved.interface_path.is_some() {
-            println!("   - interface.sol");
+    }
+
+    #[test]
+    fn test_abi_diff_command() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "abi-diff",
+            "v1/abi.json",
+            "abi.txt",
+            "--json",
+        ]);
+
+        if let Commands::AbiDiff { old, new, json } = cli.command {
+            assert_eq!(old, PathBuf::from("v1/abi.json"));
+            assert_eq!(new, PathBuf::from("abi.txt"));
+            assert!(json);
+        } else {
+            panic!("expected abi-diff command");
         }
-        if saved.metadata_path.is_some() {
-            println!("   - metadata.json");
+    }
+
+    #[test]
+    fn test_build_workspace_command() {
+        let cli = Cli::parse_from(["fluent-builder", "build-workspace", "contracts", "--force"]);
+
+        if let Commands::BuildWorkspace {
+            workspace_root,
+            output_dir,
+            force,
+            ..
+        } = cli.command
+        {
+            assert_eq!(workspace_root, PathBuf::from("contracts"));
+            assert_eq!(output_dir, PathBuf::from("out"));
+            assert!(force);
+        } else {
+            panic!("expected build-workspace command");
         }
 
-        // Create source archive if using archive source
-        if !config.use_git_source {
-            let archive_path = saved.output_dir.join("sources.tar.gz");
-            let archive_options = ArchiveOptions::default();
-            
-            create_verification_archive(
-                &config.project_root,
-                &archive_path,
-                &archive_options,
-            )?;
-            println!("   - sources.tar.gz");
+        let cli = Cli::parse_from(["fluent-builder", "build-workspace", "-j", "4", "--json"]);
+        assert!(matches!(
+            cli.command,
+            Commands::BuildWorkspace {
+                jobs: 4,
+                json: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_tag_release_command() {
+        let cli = Cli::parse_from(["fluent-builder", "tag-release", "v1.2.0", "--no-docker"]);
+
+        if let Commands::TagRelease {
+            version,
+            project_root,
+            no_docker,
+        } = cli.command
+        {
+            assert_eq!(version, "v1.2.0");
+            assert_eq!(project_root, PathBuf::from("."));
+            assert!(no_docker);
+        } else {
+            panic!("expected tag-release command");
         }
-    } else {
-        // Minimal output when artifacts are disabled
-        println!("\n📊 Compilation results:");
-        println!("   - WASM size: {} bytes", result.outputs.wasm.len());
-        println!("   - rWASM size: {} bytes", result.outputs.rwasm.len());
-        println!("   - rWASM hash: {}", rwasm_hash);
-        println!("\n⚠️  No artifacts saved (generation disabled in config)");
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_abi_self_test_command() {
+        let cli = Cli::parse_from(["fluent-builder", "abi", "self-test", "--json"]);
 
-async fn run_verify(
-    project_root: PathBuf,
-    address: String,
-    chain_id: u64,
-    rpc: String,
-    profile: String,
-    features: Vec<String>,
-    no_default_features: bool,
-    json: bool,
-) -> Result<()> {
-    // Fetch deployed bytecode hash
-    let deployed_hash = fetch_bytecode_hash(&address, &rpc, chain_id).await?;
+        if let Commands::Abi {
+            command: AbiCommands::SelfTest { json },
+        } = cli.command
+        {
+            assert!(json);
+        } else {
+            panic!("expected abi self-test command");
+        }
 
-    // Build compilation config
-    // Verify always uses the provided directory as-is (no git source)
-    let mut compile_config = CompileConfig::new(project_root.clone());
-    compile_config.profile = profile;
-    compile_config.features = features;
-    compile_config.no_default_features = no_default_features;
-    compile_config.use_git_source = false; // Always use archive/plain directory for verify
+        let cli = Cli::parse_from(["fluent-builder", "abi", "watch", "--interval-ms", "100"]);
+        if let Commands::Abi {
+            command:
+                AbiCommands::Watch {
+                    project_root,
+                    abi_naming,
+                    interval_ms,
+                },
+        } = cli.command
+        {
+            assert_eq!(project_root, PathBuf::from("."));
+            assert_eq!(abi_naming, AbiNaming::Preserve);
+            assert_eq!(interval_ms, 100);
+        } else {
+            panic!("expected abi watch command");
+        }
 
-    // Run verification
-    let verify_config = fluent_builder::VerifyConfig {
-        project_root,
-        deployed_bytecode_hash: deployed_hash.clone(),
-        compile_config: Some(compile_config),
-    };
+        let cli = Cli::parse_from(["fluent-builder", "abi", "convert", "out/token/abi.txt"]);
+        assert!(matches!(
+            cli.command,
+            Commands::Abi {
+                command: AbiCommands::Convert { file },
+            } if file == Path::new("out/token/abi.txt")
+        ));
+    }
 
-    let verification_result = verify(verify_config).context("Verification failed")?;
+    #[test]
+    fn test_compile_target_name() {
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--target-name", "token-lite"]);
 
-    if json {
-        let output = Output::Success {
-            data: SuccessData::Verify {
-                verified: verification_result.status.is_success(),
-                contract_name: verification_result.contract_name.clone(),
-                expected_hash: match &verification_result.status {
-                    VerificationStatus::Success => deployed_hash.clone(),
-                    VerificationStatus::Mismatch { expected, .. } => expected.clone(),
-                    _ => deployed_hash.clone(),
-                },
-                actual_hash: match &verification_result.status {
-                    VerificationStatus::Success => deployed_hash.clone(),
-                    VerificationStatus::Mismatch { actual, .. } => actual.clone(),
-                    _ => String::new(),
-                },
-                abi: if verification_result.status.is_success() {
-                    verification_result
-                        .compilation_result
-                        .as_ref()
-                        .and_then(|r| r.artifacts.as_ref())
-                        .filter(|a| !a.abi.is_empty())
-                        .and_then(|a| serde_json::to_value(&a.abi).ok())
-                } else {
-                    None
-                },
-                compiler_version: verification_result
-                    .compilation_result
-                    .as_ref()
-                    .map(|r| r.runtime_info.rust.version.clone())
-                    .unwrap_or_default(),
-                sdk_version: verification_result
-                    .compilation_result
-                    .as_ref()
-                    .map(|r| format!("{}-{}", r.runtime_info.sdk.tag, r.runtime_info.sdk.commit))
-                    .unwrap_or_default(),
-            },
+        if let Commands::Compile { target_name, .. } = cli.command {
+            assert_eq!(target_name.as_deref(), Some("token-lite"));
+        } else {
+            panic!("expected compile command");
+        }
+
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "verify",
+            "--address",
+            "0x123",
+            "--network",
+            "devnet",
+            "--target-name",
+            "token-lite",
+        ]);
+        let Commands::Verify { target_name, .. } = cli.command else {
+            panic!("expected verify command");
         };
-        println!("{}", serde_json::to_string(&output)?);
-    } else {
-        if verification_result.status.is_success() {
-            println!("✅ Contract verified successfully!");
-            println!("📝 Contract name: {}", verification_result.contract_name);
-            println!("🔍 Bytecode hash matches: {}", deployed_hash);
-            
-            println!("\n📋 Contract details:");
-            println!("   Address: {}", address);
-            println!("   Chain ID: {}", chain_id);
+        assert_eq!(target_name.as_deref(), Some("token-lite"));
+    }
 
-            if let Some(result) = &verification_result.compilation_result {
-                println!("\n🛠️  Build details:");
-                println!("   Compiler: {}", result.runtime_info.rust.version);
-                println!(
-                    "   SDK version: {}-{}",
-                    result.runtime_info.sdk.tag, result.runtime_info.sdk.commit
-                );
-            }
+    #[test]
+    fn test_inspect_deployed_command() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "inspect-deployed",
+            "--address",
+            "0x1234",
+            "--chain-id",
+            "20993",
+            "--rpc",
+            "https://rpc.dev.gblend.xyz",
+        ]);
+        if let Commands::InspectDeployed {
+            address,
+            chain_id,
+            json,
+            ..
+        } = cli.command
+        {
+            assert_eq!(address, "0x1234");
+            assert_eq!(chain_id, 20993);
+            assert!(!json);
         } else {
-            println!("❌ Verification failed!");
-            println!("📝 Contract name: {}", verification_result.contract_name);
-
-            match &verification_result.status {
-                VerificationStatus::Mismatch { expected, actual } => {
-                    println!("\n🔍 Hash comparison:");
-                    println!("   Expected: {}", expected);
-                    println!("   Actual:   {}", actual);
-                }
-                VerificationStatus::CompilationFailed(error) => {
-                    println!("⚠️  Compilation error: {}", error);
-                }
-                _ => {}
-            }
+            panic!("expected inspect-deployed command");
         }
     }
 
-    if !verification_result.status.is_success() {
-        std::process::exit(1);
+    #[test]
+    fn test_output_schema() {
+        let schema = output_schema(OUTPUT_SCHEMA_VERSION).to_string();
+        for command in [
+            "compile",
+            "verify",
+            "inspect_deployed",
+            "precheck",
+            "sdk_list",
+        ] {
+            assert!(schema.contains(&format!("\"{}\"", command)), "{}", command);
+        }
+        assert!(run_schema(Some("metadata")).is_ok());
+        assert!(run_schema(Some("unknown")).is_err());
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_output_schema_versions() {
+        let published: &[(u32, &str)] = &[
+            (1, include_str!("../schemas/output.v1.json")),
+            (2, include_str!("../schemas/output.v2.json")),
+        ];
+        for (version, published) in published {
+            let published: serde_json::Value = serde_json::from_str(published).unwrap();
+            let changes = fluent_builder::breaking_changes(&published, &output_schema(*version));
+            assert!(
+                changes.is_empty(),
+                "breaking changes to output v{}: {:?}; bump OUTPUT_SCHEMA_VERSION instead",
+                version,
+                changes
+            );
+        }
 
-/// Fetch bytecode hash from deployed contract
-async fn fetch_bytecode_hash(address: &str, rpc_url: &str, chain_id: u64) -> Result<String> {
-    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create provider")?;
+        let output = Output::Cancelled {
+            message: "cancelled".to_string(),
+        };
+        let json: serde_json::Value = serde_json::from_str(&output.to_json().unwrap()).unwrap();
+        assert_eq!(json["output_schema_version"], OUTPUT_SCHEMA_VERSION);
+        assert_eq!(json["status"], "cancelled");
 
-    // Verify chain ID matches
-    let network_chain_id = provider
-        .get_chainid()
-        .await
-        .context("Failed to get chain ID")?;
+        let cli = Cli::parse_from(["fluent-builder", "--schema-version", "1", "schema"]);
+        assert_eq!(cli.schema_version, 1);
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "--schema-version", "3", "schema"]).is_err()
+        );
+    }
 
-    if network_chain_id.as_u64() != chain_id {
-        return Err(eyre::eyre!(
-            "Chain ID mismatch: expected {}, got {}",
-            chain_id,
-            network_chain_id
-        ));
+    #[test]
+    fn test_serve_command() {
+        let cli = Cli::parse_from(["fluent-builder", "serve", "--workers", "4"]);
+        if let Commands::Serve {
+            bind,
+            workers,
+            queue,
+            work_dir,
+            config,
+            sandbox,
+            keep_failed,
+            allow_unauthenticated,
+        } = cli.command
+        {
+            assert_eq!(bind, "127.0.0.1:8080");
+            assert_eq!(workers, 4);
+            assert_eq!(queue, 64);
+            assert!(work_dir.is_none());
+            assert!(config.is_none());
+            assert!(!sandbox);
+            assert!(!keep_failed);
+            assert!(!allow_unauthenticated);
+        } else {
+            panic!("expected serve command");
+        }
     }
 
-    // Parse address
-    let contract_address: Address = address.parse().context("Invalid contract address")?;
+    #[test]
+    fn test_job_status_command() {
+        let cli = Cli::parse_from(["fluent-builder", "job", "status", "42-abc", "--json"]);
 
-    // Get bytecode
-    let bytecode = provider
-        .get_code(contract_address, None)
-        .await
-        .context("Failed to fetch contract bytecode")?;
+        if let Commands::Job {
+            command: JobCommands::Status { id, work_dir, json },
+        } = cli.command
+        {
+            assert_eq!(id, "42-abc");
+            assert!(work_dir.is_none());
+            assert!(json);
+        } else {
+            panic!("expected job status command");
+        }
+    }
 
-    if bytecode.is_empty() {
-        return Err(eyre::eyre!("No bytecode found at address {}", address));
+    #[test]
+    fn test_export_genesis_command() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "export-genesis",
+            "--address",
+            "0x00000000000000000000000000000000000000aa",
+            "--storage",
+            "0x0=0x2a",
+            "--genesis",
+            "genesis.json",
+        ]);
+        if let Commands::ExportGenesis {
+            address,
+            storage,
+            genesis,
+            balance,
+            ..
+        } = cli.command
+        {
+            assert_eq!(address, "0x00000000000000000000000000000000000000aa");
+            assert_eq!(storage, vec!["0x0=0x2a".to_string()]);
+            assert_eq!(genesis, Some(PathBuf::from("genesis.json")));
+            assert!(balance.is_none());
+        } else {
+            panic!("expected export-genesis command");
+        }
+        assert_eq!(
+            storage_word("0x2a").unwrap(),
+            "0x000000000000000000000000000000000000000000000000000000000000002a"
+        );
     }
 
-    // Calculate hash
-    let hash = format!("0x{:x}", Sha256::digest(&bytecode));
-    Ok(hash)
-}
+    #[test]
+    fn test_support_bundle_flag() {
+        let cli = Cli::parse_from(["fluent-builder", "--support-bundle", "compile"]);
+        assert_eq!(cli.support_bundle, Some(PathBuf::from(SUPPORT_BUNDLE_FILE)));
+        assert!(matches!(cli.command, Commands::Compile { .. }));
+        let cli = Cli::parse_from(["fluent-builder", "compile", "--support-bundle=bug.zip"]);
+        assert_eq!(cli.support_bundle, Some(PathBuf::from("bug.zip")));
+        assert_eq!(
+            Cli::parse_from(["fluent-builder", "compile"]).support_bundle,
+            None
+        );
 
-fn output_error(error: eyre::Report) {
-    let error_type = if error.to_string().contains("uncommitted changes") {
-        "git_dirty_state"
-    } else if error.to_string().contains("not in a Git repository") {
-        "no_git_repository"
-    } else if error.to_string().contains("Compilation failed") {
-        "compilation_failed"
-    } else if error.to_string().contains("Docker") {
-        "docker_error"
-    } else if error.to_string().contains("Failed to fetch") {
-        "network_error"
-    } else {
-        "unknown_error"
-    };
+        // Nested runs don't inherit the outer bundle path
+        let mut args = vec![
+            "compile".to_string(),
+            "--support-bundle=bug.zip".to_string(),
+            "--".to_string(),
+        ];
+        forward_support_bundle(&mut args, Path::new("/project"), "/workspace");
+        assert_eq!(args, ["compile", "--"]);
+    }
 
-    let output = Output::Error {
-        error_type: error_type.to_string(),
-        message: error.to_string(),
-    };
+    #[test]
+    fn test_export_and_import_commands() {
+        let cli = Cli::parse_from(["fluent-builder", "export", "a", "b", "--output-dir", "dump"]);
 
-    eprintln!("{}", serde_json::to_string(&output).unwrap());
-}
+        if let Commands::Export {
+            project_roots,
+            output,
+            output_dir,
+        } = cli.command
+        {
+            assert_eq!(project_roots, vec![PathBuf::from("a"), PathBuf::from("b")]);
+            assert!(output.is_none());
+            assert_eq!(output_dir, Some(PathBuf::from("dump")));
+        } else {
+            panic!("expected export command");
+        }
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "export", "-o", "x", "--output-dir", "y"])
+                .is_err()
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let cli = Cli::parse_from(["fluent-builder", "job", "import", "-"]);
+        if let Commands::Job {
+            command: JobCommands::Import { input, work_dir },
+        } = cli.command
+        {
+            assert_eq!(input, PathBuf::from("-"));
+            assert!(work_dir.is_none());
+        } else {
+            panic!("expected job import command");
+        }
+    }
 
     #[test]
-    fn test_cli_parsing() {
-        let cli = Cli::parse_from(&["fluent-builder", "compile"]);
-        assert!(matches!(cli.command, Commands::Compile { .. }));
-
-        let cli = Cli::parse_from(&[
+    fn test_migrate_command() {
+        let cli = Cli::parse_from([
             "fluent-builder",
-            "verify",
-            "--address",
-            "0x123",
-            "--chain-id",
-            "20993",
-            "--rpc",
-            "https://rpc.endpoint",
+            "migrate",
+            "--dry-run",
+            "--rename-feature",
+            "std-old=std",
         ]);
-        assert!(matches!(cli.command, Commands::Verify { .. }));
+
+        if let Commands::Migrate {
+            project_root,
+            rename_feature,
+            dry_run,
+            yes,
+        } = cli.command
+        {
+            assert_eq!(project_root, PathBuf::from("."));
+            assert_eq!(
+                rename_feature,
+                vec![("std-old".to_string(), "std".to_string())]
+            );
+            assert!(dry_run);
+            assert!(!yes);
+        } else {
+            panic!("expected migrate command");
+        }
+
+        assert!(Cli::try_parse_from(["fluent-builder", "migrate", "--dry-run", "--yes"]).is_err());
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "migrate", "--rename-feature", "std"]).is_err()
+        );
     }
 
     #[test]
-    fn test_compile_settings() {
-        let cli = Cli::parse_from(&[
+    fn test_artifacts_migrate_command() {
+        let cli = Cli::parse_from([
             "fluent-builder",
-            "compile",
-            "--profile",
-            "debug",
-            "--features",
-            "test feature2",
-            "--no-default-features",
+            "artifacts",
+            "migrate",
+            "out/token.wasm",
+            "-y",
         ]);
 
-        if let Commands::Compile {
-            profile,
-            features,
-            no_default_features,
-            ..
-        } = cli.command {
-            assert_eq!(profile, "debug");
-            assert_eq!(features, vec!["test", "feature2"]);
-            assert!(no_default_features);
+        if let Commands::Artifacts {
+            command:
+                ArtifactsCommands::Migrate {
+                    dir,
+                    project_root,
+                    dry_run,
+                    yes,
+                },
+        } = cli.command
+        {
+            assert_eq!(dir, PathBuf::from("out/token.wasm"));
+            assert!(project_root.is_none());
+            assert!(!dry_run);
+            assert!(yes);
+        } else {
+            panic!("expected artifacts migrate command");
         }
     }
 
     #[test]
-    fn test_allow_dirty_flag() {
-        let cli = Cli::parse_from(&["fluent-builder", "compile", "--allow-dirty"]);
+    fn test_sdk_upgrade_command() {
+        let cli = Cli::parse_from(["fluent-builder", "sdk", "upgrade", "0.6.0"]);
 
-        if let Commands::Compile { allow_dirty, .. } = cli.command {
-            assert!(allow_dirty);
+        if let Commands::Sdk {
+            command:
+                SdkCommands::Upgrade {
+                    version,
+                    project_root,
+                },
+        } = cli.command
+        {
+            assert_eq!(version, "0.6.0");
+            assert_eq!(project_root, PathBuf::from("."));
+        } else {
+            panic!("expected sdk upgrade command");
         }
     }
 
     #[test]
-    fn test_no_docker_flag() {
-        let cli = Cli::parse_from(&["fluent-builder", "compile", "--no-docker"]);
-
-        if let Commands::Compile { no_docker, .. } = cli.command {
-            assert!(no_docker);
+    fn test_alias_commands() {
+        let cli = Cli::parse_from([
+            "fluent-builder",
+            "alias",
+            "add",
+            "token-v2",
+            "0x1234",
+            "--network",
+            "testnet",
+        ]);
+        if let Commands::Alias {
+            command:
+                AliasCommands::Add {
+                    name,
+                    network,
+                    chain_id,
+                    ..
+                },
+        } = cli.command
+        {
+            assert_eq!(name, "token-v2");
+            assert_eq!(network.as_deref(), Some("testnet"));
+            assert_eq!(chain_id, None);
+        } else {
+            panic!("expected alias add command");
         }
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "alias", "add", "token-v2", "0x1234"]).is_err()
+        );
+        assert!(
+            Cli::try_parse_from(["fluent-builder", "alias", "list", "--network", "20993"]).is_ok()
+        );
     }
 
     #[test]
     fn test_docker_clean_command() {
-        let cli = Cli::parse_from(&["fluent-builder", "docker", "clean", "--keep", "3"]);
+        let cli = Cli::parse_from(["fluent-builder", "docker", "clean", "--keep", "3"]);
 
-        if let Commands::Docker { command: DockerCommands::Clean { keep } } = cli.command {
+        if let Commands::Docker {
+            command: DockerCommands::Clean { keep },
+        } = cli.command
+        {
             assert_eq!(keep, 3);
         }
     }
-}
\ No newline at end of file
+}
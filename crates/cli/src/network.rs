@@ -0,0 +1,197 @@
+//! Per-network build settings
+//!
+//! Declared in the `[networks]` section of the project's `fluent.toml`:
+//!
+//! ```toml
+//! [networks.testnet]
+//! features = ["testnet"]
+//! profile = "release"          # optional, overrides --profile
+//! rpc = "https://rpc.testnet.fluent.xyz"
+//! chain_id = 20994
+//! ```
+//!
+//! `compile --network testnet` builds with these features and `verify
+//! --network testnet` rebuilds with the same ones against the network's RPC,
+//! so a testnet build is never verified against a mainnet deployment.
+
+use crate::auth::CONFIG_FILE;
+use eyre::{eyre, Context, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+
+/// A `[networks.<name>]` section of `fluent.toml`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Network {
+    /// Features enabled in addition to `--features`
+    #[serde(default)]
+    pub features: Vec<String>,
+    pub profile: Option<String>,
+    pub no_default_features: Option<bool>,
+    /// RPC endpoint used by verify when `--rpc` is not given
+    pub rpc: Option<String>,
+    /// Chain ID the RPC endpoint must report
+    pub chain_id: Option<u64>,
+}
+
+impl Network {
+    /// Read `[networks.<name>]` from the `fluent.toml` of a project
+    pub fn load(project_root: &Path, name: &str) -> Result<Self> {
+        let path = project_root.join(CONFIG_FILE);
+        if !path.exists() {
+            return Err(eyre!(
+                "--network {} needs a [networks.{}] section in {}, which does not exist",
+                name,
+                name,
+                path.display()
+            ));
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content, name)
+            .with_context(|| format!("Invalid network configuration in {}", path.display()))
+    }
+
+    fn parse(content: &str, name: &str) -> Result<Self> {
+        let mut file: toml::Table = toml::from_str(content)?;
+        let mut networks: BTreeMap<String, Network> = file
+            .remove("networks")
+            .map(|networks| networks.try_into())
+            .transpose()?
+            .unwrap_or_default();
+        networks.remove(name).ok_or_else(|| {
+            let known: Vec<&str> = networks.keys().map(String::as_str).collect();
+            if known.is_empty() {
+                eyre!("No [networks.{}] section", name)
+            } else {
+                eyre!(
+                    "No [networks.{}] section (known networks: {})",
+                    name,
+                    known.join(", ")
+                )
+            }
+        })
+    }
+
+    /// Apply the network's build settings on top of the command line ones
+    pub fn apply(
+        &self,
+        profile: &mut String,
+        features: &mut Vec<String>,
+        no_default_features: &mut bool,
+    ) {
+        if let Some(network_profile) = &self.profile {
+            *profile = network_profile.clone();
+        }
+        for feature in &self.features {
+            if !features.contains(feature) {
+                features.push(feature.clone());
+            }
+        }
+        if let Some(network_no_default_features) = self.no_default_features {
+            *no_default_features = network_no_default_features;
+        }
+    }
+
+    /// RPC endpoint and chain ID of network `name`, unless given on the command line
+    pub fn endpoint(
+        &self,
+        name: &str,
+        rpc: Option<String>,
+        chain_id: Option<u64>,
+    ) -> Result<(String, u64)> {
+        if let (Some(given), Some(expected)) = (chain_id, self.chain_id) {
+            if given != expected {
+                return Err(eyre!(
+                    "--chain-id {} does not match chain {} of network {}",
+                    given,
+                    expected,
+                    name
+                ));
+            }
+        }
+        let rpc = rpc
+            .or_else(|| self.rpc.clone())
+            .ok_or_else(|| eyre!("Network {} has no rpc; pass --rpc", name))?;
+        let chain_id = chain_id
+            .or(self.chain_id)
+            .ok_or_else(|| eyre!("Network {} has no chain_id; pass --chain-id", name))?;
+        Ok((rpc, chain_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[server]
+api_keys = []
+
+[networks.testnet]
+features = ["testnet"]
+rpc = "https://rpc.testnet.fluent.xyz"
+chain_id = 20994
+
+[networks.mainnet]
+profile = "production"
+"#;
+
+    #[test]
+    fn test_network_settings() {
+        let testnet = Network::parse(CONFIG, "testnet").unwrap();
+        let (mut profile, mut features, mut no_default_features) =
+            ("release".to_string(), vec!["std".to_string()], true);
+        testnet.apply(&mut profile, &mut features, &mut no_default_features);
+        assert_eq!(profile, "release");
+        assert_eq!(features, vec!["std", "testnet"]);
+        assert!(no_default_features);
+
+        let mainnet = Network::parse(CONFIG, "mainnet").unwrap();
+        mainnet.apply(&mut profile, &mut features, &mut no_default_features);
+        assert_eq!(profile, "production");
+
+        let err = Network::parse(CONFIG, "devnet").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No [networks.devnet] section (known networks: mainnet, testnet)"
+        );
+    }
+
+    #[test]
+    fn test_network_endpoint() {
+        let testnet = Network::parse(CONFIG, "testnet").unwrap();
+        assert_eq!(
+            testnet.endpoint("testnet", None, None).unwrap(),
+            ("https://rpc.testnet.fluent.xyz".to_string(), 20994)
+        );
+        // A mainnet chain ID with the testnet build settings is refused
+        assert_eq!(
+            testnet
+                .endpoint("testnet", None, Some(25363))
+                .unwrap_err()
+                .to_string(),
+            "--chain-id 25363 does not match chain 20994 of network testnet"
+        );
+
+        // Without an endpoint in fluent.toml it comes from the command line
+        let mainnet = Network::parse(CONFIG, "mainnet").unwrap();
+        assert_eq!(
+            mainnet
+                .endpoint("mainnet", None, Some(25363))
+                .unwrap_err()
+                .to_string(),
+            "Network mainnet has no rpc; pass --rpc"
+        );
+        assert_eq!(
+            mainnet
+                .endpoint(
+                    "mainnet",
+                    Some("http://localhost:8545".to_string()),
+                    Some(25363)
+                )
+                .unwrap(),
+            ("http://localhost:8545".to_string(), 25363)
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Nix orchestration for reproducible builds without Docker
+
+use eyre::{bail, Context, Result};
+use fluent_builder::{status_interruptible, CompileConfig, NixInput};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A GitHub flake input pinned to a commit and the NAR hash of its source
+struct Pin {
+    name: &'static str,
+    owner: &'static str,
+    repo: &'static str,
+    rev: &'static str,
+    nar_hash: &'static str,
+}
+
+/// nixpkgs for build tools, from the nixos-24.11 branch
+const NIXPKGS: Pin = Pin {
+    name: "nixpkgs",
+    owner: "NixOS",
+    repo: "nixpkgs",
+    rev: "50ab793786d9de88ee30ec4e4c24fb4236fc2674",
+    nar_hash: "sha256-/bVBlRpECLVzjV19t5KMdMFWSwKLtb5RyXdjz3LJT+g=",
+};
+
+/// rust-overlay providing exact Rust toolchains
+const RUST_OVERLAY: Pin = Pin {
+    name: "rust-overlay",
+    owner: "oxalica",
+    repo: "rust-overlay",
+    rev: "10faa81b4c0135a04716cbd1649260d82b2890cd",
+    nar_hash: "sha256-pFDY0pQ4R4Cu8XDZ8eEE1jX5aAAvOM9/Zng4T7Hnzk4=",
+};
+
+impl Pin {
+    /// Flake input attribute set; Nix refuses the input if its content differs from `nar_hash`
+    fn flake_input(&self) -> String {
+        format!(
+            concat!(
+                r#"{} = {{ type = "github"; owner = "{}"; repo = "{}"; "#,
+                r#"rev = "{}"; narHash = "{}"; }};"#,
+            ),
+            self.name, self.owner, self.repo, self.rev, self.nar_hash
+        )
+    }
+
+    /// The input as recorded in metadata.json
+    fn locked(&self) -> NixInput {
+        NixInput {
+            name: self.name.to_string(),
+            url: format!("github:{}/{}", self.owner, self.repo),
+            rev: self.rev.to_string(),
+            nar_hash: self.nar_hash.to_string(),
+        }
+    }
+}
+
+/// Run the compilation inside a pinned Nix development shell
+pub fn run_reproducible(
+    project_root: &Path,
+    rust_version: &str,
+    sdk_version: &str,
+    command_args: &[String],
+    offline: bool,
+) -> Result<()> {
+    if cfg!(windows) {
+        bail!("Nix builds are not supported on Windows. Use Docker or --no-docker instead.");
+    }
+    check_nix_available()?;
+
+    let flake_dir = write_flake(project_root, rust_version, sdk_version)?;
+    let flake_ref = format!("path:{}", flake_dir.display());
+
+    let derivation = derivation_path(&flake_ref, offline)?;
+    tracing::info!("Nix build environment: {}", derivation);
+
+    let inputs = serde_json::to_string(&[NIXPKGS.locked(), RUST_OVERLAY.locked()])?;
+
+    let exe = std::env::current_exe().context("Failed to locate fluent-builder executable")?;
+    let mut args = command_args.to_vec();
+    crate::insert_flags(&mut args, &["--no-docker"]);
+
+    let mut cmd = nix_command(offline);
+    cmd.args(["develop", &flake_ref, "--ignore-environment"])
+        .args([
+            "--keep",
+            "HOME",
+            "--keep",
+            fluent_builder::NIX_DERIVATION_ENV,
+        ])
+        .args(["--keep", fluent_builder::NIX_INPUTS_ENV])
+        .arg("--command")
+        .arg(exe)
+        .args(args)
+        .env(fluent_builder::NIX_DERIVATION_ENV, &derivation)
+        .env(fluent_builder::NIX_INPUTS_ENV, inputs)
+        .current_dir(project_root);
+
+    tracing::debug!("Running Nix command: {:?}", cmd);
+
+    cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+    let status = status_interruptible(&mut cmd).context("Failed to execute nix develop")?;
+
+    if !status.success() {
+        bail!("Build failed inside Nix environment");
+    }
+
+    Ok(())
+}
+
+/// `nix` invocation with flakes enabled
+///
+/// In offline mode only the local store is used; missing paths are an error.
+fn nix_command(offline: bool) -> Command {
+    let mut cmd = Command::new("nix");
+    cmd.args(["--extra-experimental-features", "nix-command flakes"]);
+    if offline {
+        cmd.arg("--offline");
+    }
+    cmd
+}
+
+/// Check if Nix with flakes support is installed
+fn check_nix_available() -> Result<()> {
+    let available = nix_command(false)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+
+    if !available {
+        bail!(
+            "Nix is not installed. Install Nix with flakes enabled and try again.\n\
+            Install Nix: https://nixos.org/download"
+        );
+    }
+
+    Ok(())
+}
+
+/// Write the generated flake under the cargo target directory
+fn write_flake(project_root: &Path, rust_version: &str, sdk_version: &str) -> Result<PathBuf> {
+    let flake_dir = CompileConfig::new(project_root)
+        .target_directory()
+        .join("fluent-builder")
+        .join("nix");
+    std::fs::create_dir_all(&flake_dir)
+        .with_context(|| format!("Failed to create {}", flake_dir.display()))?;
+
+    std::fs::write(
+        flake_dir.join("flake.nix"),
+        flake_content(rust_version, sdk_version),
+    )
+    .context("Failed to write flake.nix")?;
+
+    Ok(flake_dir)
+}
+
+/// Resolve the derivation of the development shell
+fn derivation_path(flake_ref: &str, offline: bool) -> Result<String> {
+    let installable = format!("{}#devShells.{}.default", flake_ref, current_system()?);
+    let output = nix_command(offline)
+        .args(["path-info", "--derivation", &installable])
+        .output()
+        .context("Failed to execute nix path-info")?;
+
+    if !output.status.success() {
+        bail!(
+            "Failed to evaluate Nix build environment: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Nix system double of the host (e.g. x86_64-linux)
+fn current_system() -> Result<String> {
+    let output = nix_command(false)
+        .args([
+            "eval",
+            "--impure",
+            "--raw",
+            "--expr",
+            "builtins.currentSystem",
+        ])
+        .output()
+        .context("Failed to execute nix eval")?;
+
+    if !output.status.success() {
+        bail!("Failed to detect Nix system");
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// rust-overlay attribute selecting the exact toolchain
+fn toolchain_attr(rust_version: &str) -> String {
+    match rust_version.strip_prefix("nightly-") {
+        Some(date) => format!("rust-bin.nightly.\"{}\".minimal", date),
+        None if rust_version == "nightly" => "rust-bin.nightly.latest.minimal".to_string(),
+        None => format!("rust-bin.stable.\"{}\".minimal", rust_version),
+    }
+}
+
+/// Flake pinning the Rust toolchain and build tools
+fn flake_content(rust_version: &str, sdk_version: &str) -> String {
+    let toolchain = toolchain_attr(rust_version);
+    let nixpkgs = NIXPKGS.flake_input();
+    let rust_overlay = RUST_OVERLAY.flake_input();
+
+    format!(
+        r#"# Generated by fluent-builder. Do not edit.
+# Rust {rust_version}, SDK {sdk_version}
+{{
+  inputs = {{
+    {nixpkgs}
+    {rust_overlay}
+    rust-overlay.inputs.nixpkgs.follows = "nixpkgs";
+  }};
+
+  outputs = {{ nixpkgs, rust-overlay, ... }}:
+    let
+      forAllSystems = nixpkgs.lib.genAttrs [
+        "x86_64-linux" "aarch64-linux" "x86_64-darwin" "aarch64-darwin"
+      ];
+    in {{
+      devShells = forAllSystems (system:
+        let
+          pkgs = import nixpkgs {{ inherit system; overlays = [ rust-overlay.overlays.default ]; }};
+          rust = pkgs.{toolchain}.override {{
+            targets = [ "wasm32-unknown-unknown" ];
+            extensions = [ "rust-src" ];
+          }};
+        in {{
+          default = pkgs.mkShell {{
+            packages = [ rust pkgs.git pkgs.cacert ];
+            FLUENT_BUILDER_NIX = "1";
+          }};
+        }});
+    }};
+}}
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toolchain_attr() {
+        assert_eq!(
+            toolchain_attr("1.83.0"),
+            "rust-bin.stable.\"1.83.0\".minimal"
+        );
+        assert_eq!(
+            toolchain_attr("nightly-2024-01-15"),
+            "rust-bin.nightly.\"2024-01-15\".minimal"
+        );
+        assert_eq!(toolchain_attr("nightly"), "rust-bin.nightly.latest.minimal");
+    }
+
+    #[test]
+    fn test_flake_content() {
+        let flake = flake_content("1.83.0", "0.4.1");
+        assert!(flake.contains("# Rust 1.83.0, SDK 0.4.1"));
+        assert!(flake.contains("pkgs.rust-bin.stable.\"1.83.0\".minimal.override"));
+        assert!(flake.contains(&format!(
+            concat!(
+                r#"nixpkgs = {{ type = "github"; owner = "NixOS"; repo = "nixpkgs"; "#,
+                r#"rev = "{}"; narHash = "{}"; }};"#,
+            ),
+            NIXPKGS.rev, NIXPKGS.nar_hash
+        )));
+    }
+
+    #[test]
+    fn test_inputs_are_pinned() {
+        for pin in [&NIXPKGS, &RUST_OVERLAY] {
+            assert!(
+                pin.rev.len() == 40 && pin.rev.chars().all(|c| c.is_ascii_hexdigit()),
+                "{}",
+                pin.name
+            );
+            assert!(
+                pin.nar_hash.starts_with("sha256-") && pin.nar_hash.ends_with('='),
+                "{}",
+                pin.name
+            );
+        }
+        assert_eq!(NIXPKGS.locked().url, "github:NixOS/nixpkgs");
+    }
+}
@@ -0,0 +1,700 @@
+//! HTTP verification server
+//!
+//! `POST /jobs` queues a verification and returns its id, `GET /jobs/<id>`
+//! reports its status and result. Requests are answered on a few threads and
+//! jobs run on a bounded worker pool. Every route but `/health` and
+//! `/openapi.json` goes through the API key and rate limit [`Guard`]. Bodies
+//! are the types of [`fluent_builder::openapi`].
+//! Deployed bytecode is only fetched from the configured RPC endpoints.
+//! With a [`Sandbox`], submitted sources are built in a locked-down container
+//! from dependencies vendored beforehand on the host.
+
+use crate::{
+    auth::{Denied, Guard, RpcEndpoint},
+    docker::Sandbox,
+};
+use eyre::{eyre, Context, Result};
+use fluent_builder::{
+    ApiError, CachePurged, HashAlgorithm, HealthResponse, JobAccepted, JobError, JobExecutor,
+    JobLog, JobOutcome, JobStatus, PoolConfig, VerifyRequest, WorkerPool, OPENAPI_PATH,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    io::Read,
+    net::IpAddr,
+    path::{Component, Path},
+    sync::Arc,
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Largest request body read; verification requests are small JSON documents
+const MAX_BODY: u64 = 1024 * 1024;
+
+/// Threads answering requests; builds run on the worker pool, so these mostly wait on I/O
+const HANDLER_THREADS: usize = 8;
+
+/// Serve verification requests until the process is stopped
+pub fn serve(
+    bind: &str,
+    config: PoolConfig,
+    guard: Guard,
+    rpc: Vec<RpcEndpoint>,
+    sandbox: Option<Sandbox>,
+) -> Result<()> {
+    let pool = WorkerPool::new(config, executor(rpc, sandbox))?;
+    let server = Server::http(bind).map_err(|e| eyre!("Failed to listen on {}: {}", bind, e))?;
+    tracing::info!("Verification server listening on {}", bind);
+
+    // A slow client only holds up its own thread
+    std::thread::scope(|scope| {
+        for _ in 0..HANDLER_THREADS {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    respond(&pool, &guard, request);
+                }
+            });
+        }
+    });
+
+    pool.shutdown();
+    Ok(())
+}
+
+/// Answer a single request
+fn respond(pool: &WorkerPool, guard: &Guard, mut request: Request) {
+    let api_key = api_key(request.headers());
+    let peer = request.remote_addr().map(|addr| addr.ip());
+    // Unauthenticated requests are refused before their body is read
+    let authorized = authorize(
+        guard,
+        request.method(),
+        request.url(),
+        api_key.as_deref(),
+        peer,
+    );
+    let (status, response) = match authorized {
+        Ok(client) => match read_body(request.as_reader()) {
+            Ok(body) => dispatch(
+                pool,
+                guard,
+                client.as_deref(),
+                request.method(),
+                request.url(),
+                &body,
+            ),
+            Err(response) => response,
+        },
+        Err(denied) => denied.response(),
+    };
+    tracing::debug!("{} {} -> {}", request.method(), request.url(), status);
+
+    let retry_after = response["retry_after"].as_u64();
+    let mut response = Response::from_string(response.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"));
+    if status == 401 {
+        response.add_header(header("WWW-Authenticate", "Bearer"));
+    }
+    if let Some(seconds) = retry_after {
+        response.add_header(header("Retry-After", &seconds.to_string()));
+    }
+    if let Err(e) = request.respond(response) {
+        tracing::warn!("Failed to send response: {}", e);
+    }
+}
+
+/// Authenticate and rate limit a request from `peer`, returning the client name
+///
+/// Public routes need no key and have no client.
+fn authorize(
+    guard: &Guard,
+    method: &Method,
+    url: &str,
+    api_key: Option<&str>,
+    peer: Option<IpAddr>,
+) -> Result<Option<String>, Denied> {
+    let path = url.split('?').next().unwrap_or(url).trim_end_matches('/');
+    if path == "/health" || path == OPENAPI_PATH {
+        return Ok(None);
+    }
+
+    let submits_job = *method == Method::Post && path == "/jobs";
+    let client = guard.check(api_key, peer, submits_job)?;
+    if path == "/cache" {
+        guard.check_admin(api_key)?;
+    }
+    Ok(Some(client))
+}
+
+/// Route an authorized request, counting accepted jobs against the client's quota
+fn dispatch(
+    pool: &WorkerPool,
+    guard: &Guard,
+    client: Option<&str>,
+    method: &Method,
+    url: &str,
+    body: &str,
+) -> (u16, Value) {
+    let (status, response) = route(pool, method, url, body);
+    if let (Some(client), true) = (client, *method == Method::Post && status == 202) {
+        tracing::info!("Job {} submitted by {}", response["id"], client);
+        guard.record_job(client);
+    }
+    (status, response)
+}
+
+/// Read a request body of at most [`MAX_BODY`] bytes
+fn read_body(reader: impl Read) -> Result<String, (u16, Value)> {
+    let mut body = String::new();
+    reader
+        .take(MAX_BODY + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| error(400, format!("Failed to read request body: {}", e)))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(error(
+            413,
+            format!("Request body is larger than {} bytes", MAX_BODY),
+        ));
+    }
+    Ok(body)
+}
+
+/// Handle a request, returning the status code and JSON body
+fn route(pool: &WorkerPool, method: &Method, url: &str, body: &str) -> (u16, Value) {
+    let path = url.split('?').next().unwrap_or(url).trim_end_matches('/');
+
+    match (method, path) {
+        (Method::Get, "/health") => ok(
+            200,
+            HealthResponse {
+                status: "ok".to_string(),
+                queued: pool.queued(),
+            },
+        ),
+        (Method::Get, OPENAPI_PATH) => (200, fluent_builder::openapi()),
+        (Method::Post, "/jobs") => match serde_json::from_str::<VerifyRequest>(body) {
+            Ok(request) => match pool.submit(request) {
+                Ok(id) => ok(
+                    202,
+                    JobAccepted {
+                        id,
+                        status: JobStatus::Queued,
+                    },
+                ),
+                // Both mean "try again later"
+                Err(e @ (JobError::QueueFull { .. } | JobError::ShutDown)) => {
+                    error(503, e.to_string())
+                }
+                Err(e @ JobError::Rejected { .. }) => error(400, e.to_string()),
+            },
+            Err(e) => error(400, format!("Invalid verification request: {}", e)),
+        },
+        (Method::Get, _) if path.starts_with("/jobs/") => match pool.job(&path["/jobs/".len()..]) {
+            Some(job) => ok(200, job),
+            None => error(404, "Unknown job"),
+        },
+        (Method::Delete, "/cache") => {
+            let bytecode_hash = query_param(url, "bytecode_hash");
+            match pool.purge_cache(bytecode_hash) {
+                Ok(removed) => ok(200, CachePurged { removed }),
+                Err(e) => error(500, format!("{:#}", e)),
+            }
+        }
+        _ => error(404, format!("No route for {} {}", method, path)),
+    }
+}
+
+fn ok(status: u16, body: impl Serialize) -> (u16, Value) {
+    (status, serde_json::to_value(body).unwrap_or_default())
+}
+
+fn error(status: u16, message: impl Into<String>) -> (u16, Value) {
+    ok(status, ApiError::new(message))
+}
+
+/// Value of a query string parameter (not percent-decoded)
+fn query_param<'a>(url: &'a str, name: &str) -> Option<&'a str> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Key from `Authorization: Bearer <key>` or `X-API-Key`
+fn api_key(headers: &[Header]) -> Option<String> {
+    headers.iter().find_map(|h| {
+        if h.field.equiv("Authorization") {
+            h.value
+                .as_str()
+                .strip_prefix("Bearer ")
+                .map(|key| key.trim().to_string())
+        } else if h.field.equiv("X-API-Key") {
+            Some(h.value.as_str().trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("valid header")
+}
+
+fn executor(rpc: Vec<RpcEndpoint>, sandbox: Option<Sandbox>) -> Arc<JobExecutor> {
+    Arc::new(
+        move |request: &VerifyRequest, workspace: &fluent_builder::Sandbox, log: &JobLog| {
+            let request = resolve_bytecode_hash(request, &rpc, log)?;
+            if let Some(outcome) = log.cached(&request) {
+                return Ok(outcome);
+            }
+            match &sandbox {
+                Some(sandbox) => run_sandboxed_job(&request, workspace, log, sandbox),
+                None => fluent_builder::run_verify_job(&request, workspace, log),
+            }
+        },
+    )
+}
+
+/// Fetch the deployed bytecode hash if only an address was given
+fn resolve_bytecode_hash(
+    request: &VerifyRequest,
+    endpoints: &[RpcEndpoint],
+    log: &JobLog,
+) -> Result<VerifyRequest> {
+    if request.deployed_bytecode_hash.is_some() {
+        return Ok(request.clone());
+    }
+
+    let (Some(address), Some(chain_id)) = (&request.address, request.chain_id) else {
+        return Err(eyre!(
+            "Either deployed_bytecode_hash or address and chain_id are required"
+        ));
+    };
+    let rpc = allowed_rpc(endpoints, chain_id, request.rpc.as_deref())?;
+    log.stage("fetching bytecode");
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let fetched = runtime.block_on(crate::fetch_bytecode(address, rpc, chain_id))?;
+    let (bytecode, unwrapping) = request.unwrap.apply(&fetched)?;
+    if let Some(unwrapping) = unwrapping {
+        log.log(format!("Stripped {} from the deployed code", unwrapping));
+    }
+
+    let mut request = request.clone();
+    request.deployed_bytecode_hash = Some(format!("0x{}", request.hash_algorithm.hash(bytecode)));
+    Ok(request)
+}
+
+/// Configured endpoint of `chain_id`; a requested URL must be one of them
+///
+/// Jobs never make the server connect to a URL of their choosing.
+fn allowed_rpc<'a>(
+    endpoints: &'a [RpcEndpoint],
+    chain_id: u64,
+    requested: Option<&str>,
+) -> Result<&'a str> {
+    let mut configured = endpoints
+        .iter()
+        .filter(|endpoint| endpoint.chain_id == chain_id);
+    let endpoint = match requested {
+        Some(url) => configured
+            .find(|endpoint| endpoint.url.trim_end_matches('/') == url.trim_end_matches('/')),
+        None => configured.next(),
+    };
+    endpoint
+        .map(|endpoint| endpoint.url.as_str())
+        .ok_or_else(|| match requested {
+            Some(url) => eyre!(
+                "RPC endpoint {} is not configured for chain {} on this server",
+                url,
+                chain_id
+            ),
+            None => eyre!(
+                "No RPC endpoint is configured for chain {} on this server",
+                chain_id
+            ),
+        })
+}
+
+/// Vendor dependencies, compile offline in a sandbox container and compare the rWASM hash
+fn run_sandboxed_job(
+    request: &VerifyRequest,
+    workspace: &fluent_builder::Sandbox,
+    log: &JobLog,
+    sandbox: &Sandbox,
+) -> Result<JobOutcome> {
+    let expected_hash = request.deployed_bytecode_hash.clone().unwrap_or_default();
+
+    // Fetched here, where network access is trusted; the build itself stays offline
+    log.stage("vendoring dependencies");
+    let vendor_dir = workspace.root.join("vendor");
+    let cargo_config = fluent_builder::vendor_dependencies(
+        &request.project_root,
+        &vendor_dir,
+        "/vendor",
+        fluent_builder::Timeouts::default().cargo_vendor,
+    )?;
+    // Cargo reads config from the parent directories of /workspace as well
+    let cargo_config_path = workspace.root.join("cargo-config.toml");
+    std::fs::write(&cargo_config_path, cargo_config)
+        .with_context(|| format!("Failed to write {}", cargo_config_path.display()))?;
+
+    let mut args: Vec<String> = [
+        "--target-dir",
+        "/target",
+        "--offline",
+        "compile",
+        ".",
+        "--output-dir",
+        "/out",
+        "--allow-dirty",
+        "--json",
+        "--profile",
+        &request.profile,
+    ]
+    .map(str::to_string)
+    .to_vec();
+    args.push(format!(
+        "--no-default-features={}",
+        request.no_default_features
+    ));
+    if !request.features.is_empty() {
+        args.extend(["--features".to_string(), request.features.join(" ")]);
+    }
+    if let Some(target_name) = &request.target_name {
+        args.extend(["--target-name".to_string(), target_name.clone()]);
+    }
+    if let Some(rustflags) = &request.rustflags {
+        args.extend(["--rustflags".to_string(), rustflags.clone()]);
+    }
+    if !request.extra_cargo_args.is_empty() {
+        args.push("--".to_string());
+        args.extend(request.extra_cargo_args.iter().cloned());
+    }
+
+    log.stage("building in sandbox");
+    let stdout = crate::docker::run_sandboxed(
+        &request.project_root,
+        &workspace.container_name,
+        &args,
+        sandbox,
+        &[
+            (&vendor_dir, "/vendor"),
+            (&cargo_config_path, "/.cargo/config.toml"),
+        ],
+        &[
+            (&workspace.target_dir, "/target"),
+            (&workspace.output_dir, "/out"),
+        ],
+    )?;
+    let output: Value =
+        serde_json::from_slice(&stdout).context("Unexpected output from the sandboxed build")?;
+    let actual_hash = match request.hash_algorithm {
+        HashAlgorithm::Sha256 => output["rwasm_hash"]
+            .as_str()
+            .ok_or_else(|| eyre!("Sandboxed build did not report an rWASM hash: {}", output))?
+            .to_string(),
+        algorithm => {
+            // The build reports sha256 only; hash the rWASM it wrote instead
+            let artifacts_dir = output["output_dir"]
+                .as_str()
+                .ok_or_else(|| eyre!("Sandboxed build did not write artifacts: {}", output))?;
+            let rwasm_path = workspace
+                .output_dir
+                .join(sandboxed_artifacts_dir(artifacts_dir)?)
+                .join("lib.rwasm");
+            algorithm.hash_file(&rwasm_path)?
+        }
+    };
+
+    let normalize = |hash: &str| hash.trim_start_matches("0x").to_lowercase();
+    Ok(JobOutcome {
+        verified: normalize(&actual_hash) == normalize(&expected_hash),
+        contract_name: output["contract_name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        expected_hash,
+        actual_hash,
+        hints: Vec::new(),
+        // Deployment data is not fetched, so the arguments are recorded as given
+        constructor_args: request.constructor_args.clone(),
+    })
+}
+
+/// Artifacts directory reported by a sandboxed build, relative to its output directory
+///
+/// The build ran untrusted code, so anything that could lead out of the job's
+/// output directory is refused.
+pub(crate) fn sandboxed_artifacts_dir(reported: &str) -> Result<&Path> {
+    let path = Path::new(reported);
+    if path.as_os_str().is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(eyre!(
+            "Sandboxed build reported an artifacts directory outside its output: {}",
+            reported
+        ));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Authenticate and rate limit a request, then route it, as `serve` does
+    fn handle(
+        pool: &WorkerPool,
+        guard: &Guard,
+        method: &Method,
+        url: &str,
+        api_key: Option<&str>,
+        body: &str,
+    ) -> (u16, Value) {
+        match authorize(guard, method, url, api_key, None) {
+            Ok(client) => dispatch(pool, guard, client.as_deref(), method, url, body),
+            Err(denied) => denied.response(),
+        }
+    }
+
+    #[test]
+    fn test_routes() {
+        let dir = TempDir::new().unwrap();
+        let pool = WorkerPool::new(
+            PoolConfig {
+                workers: 1,
+                queue_capacity: 4,
+                work_dir: dir.path().to_path_buf(),
+                store: None,
+                keep_failed: false,
+                sandboxed: false,
+            },
+            executor(Vec::new(), None),
+        )
+        .unwrap();
+
+        let (status, body) = route(&pool, &Method::Post, "/jobs", "{}");
+        assert_eq!(status, 400);
+        assert!(body["error"].as_str().unwrap().contains("project_root"));
+
+        let (status, body) = route(&pool, &Method::Post, "/jobs", r#"{"project_root": "."}"#);
+        assert_eq!(status, 202);
+        let id = body["id"].as_str().unwrap().to_string();
+
+        let (status, body) = route(&pool, &Method::Get, &format!("/jobs/{}", id), "");
+        assert_eq!(status, 200);
+        assert_eq!(body["request"]["profile"], "release");
+
+        assert_eq!(route(&pool, &Method::Get, "/jobs/unknown", "").0, 404);
+        assert_eq!(route(&pool, &Method::Delete, "/jobs", "").0, 404);
+        assert_eq!(route(&pool, &Method::Get, "/health", "").0, 200);
+
+        // Every documented route is served
+        let (status, spec) = route(&pool, &Method::Get, OPENAPI_PATH, "");
+        assert_eq!(status, 200);
+        for (path, operations) in spec["paths"].as_object().unwrap() {
+            let path = path.replace("{id}", &id);
+            for method in operations.as_object().unwrap().keys() {
+                let method: Method = method.to_uppercase().parse().unwrap();
+                let (status, body) = route(&pool, &method, &path, "{}");
+                assert!(
+                    !body["error"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .starts_with("No route"),
+                    "{} {} -> {}",
+                    method,
+                    path,
+                    status
+                );
+            }
+        }
+
+        pool.shutdown();
+    }
+
+    #[test]
+    fn test_auth_and_rate_limits() {
+        use crate::auth::{ApiKeyConfig, Limits, ServerConfig};
+
+        let dir = TempDir::new().unwrap();
+        let pool = WorkerPool::new(
+            PoolConfig {
+                workers: 1,
+                queue_capacity: 4,
+                work_dir: dir.path().to_path_buf(),
+                store: None,
+                keep_failed: false,
+                sandboxed: false,
+            },
+            executor(Vec::new(), None),
+        )
+        .unwrap();
+        let guard = Guard::new(&ServerConfig {
+            anonymous: None,
+            api_keys: vec![ApiKeyConfig {
+                name: "explorer".to_string(),
+                key: Some("secret".to_string()),
+                key_env: None,
+                admin: false,
+                limits: Limits {
+                    requests_per_minute: None,
+                    jobs_per_day: Some(1),
+                },
+            }],
+            rpc: Vec::new(),
+            sandbox: None,
+        })
+        .unwrap();
+        let job = r#"{"project_root": "."}"#;
+
+        assert_eq!(
+            handle(&pool, &guard, &Method::Get, "/health", None, "").0,
+            200
+        );
+        let (status, body) = handle(&pool, &guard, &Method::Post, "/jobs", None, job);
+        assert_eq!(status, 401);
+        assert_eq!(body["code"], "unauthorized");
+
+        // Rejected requests don't use up the quota
+        assert_eq!(
+            handle(&pool, &guard, &Method::Post, "/jobs", Some("secret"), "{}").0,
+            400
+        );
+        assert_eq!(
+            handle(&pool, &guard, &Method::Post, "/jobs", Some("secret"), job).0,
+            202
+        );
+        let (status, body) = handle(&pool, &guard, &Method::Post, "/jobs", Some("secret"), job);
+        assert_eq!(status, 429);
+        assert!(body["retry_after"].as_u64().unwrap() > 0);
+
+        // Purging the result cache is reserved for admin keys
+        assert_eq!(
+            handle(&pool, &guard, &Method::Delete, "/cache", Some("secret"), "").0,
+            403
+        );
+        assert_eq!(
+            handle(&pool, &guard, &Method::Delete, "/cache", None, "").0,
+            401
+        );
+        let (status, body) = route(&pool, &Method::Delete, "/cache?bytecode_hash=0xabc", "");
+        assert_eq!((status, body["removed"].as_u64()), (200, Some(0)));
+        assert_eq!(
+            query_param("/cache?a=1&bytecode_hash=0xab", "bytecode_hash"),
+            Some("0xab")
+        );
+        assert_eq!(
+            query_param("/cache?bytecode_hash_x=1", "bytecode_hash"),
+            None
+        );
+
+        let auth = Header::from_bytes("authorization", "Bearer secret").unwrap();
+        assert_eq!(api_key(&[auth]).as_deref(), Some("secret"));
+        assert_eq!(
+            api_key(&[header("X-API-Key", "other")]).as_deref(),
+            Some("other")
+        );
+        assert_eq!(api_key(&[header("Authorization", "Basic abc")]), None);
+
+        pool.shutdown();
+    }
+
+    #[test]
+    fn test_body_limit() {
+        assert_eq!(read_body(&b"{}"[..]).unwrap(), "{}");
+        let large = vec![b' '; MAX_BODY as usize + 1];
+        assert_eq!(read_body(&large[..]).unwrap_err().0, 413);
+        assert_eq!(read_body(&[0xff, 0xfe][..]).unwrap_err().0, 400);
+
+        // The key is checked before the body is read
+        let guard = Guard::new(&crate::auth::ServerConfig::default()).unwrap();
+        assert_eq!(
+            authorize(&guard, &Method::Post, "/jobs", None, None),
+            Err(Denied::Unauthorized)
+        );
+        assert_eq!(
+            authorize(&guard, &Method::Get, "/health", None, None),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_job_without_bytecode_source_fails() {
+        let dir = TempDir::new().unwrap();
+        let pool = WorkerPool::new(
+            PoolConfig {
+                workers: 1,
+                queue_capacity: 1,
+                work_dir: dir.path().to_path_buf(),
+                store: None,
+                keep_failed: false,
+                sandboxed: false,
+            },
+            executor(Vec::new(), None),
+        )
+        .unwrap();
+
+        let (_, body) = route(&pool, &Method::Post, "/jobs", r#"{"project_root": "."}"#);
+        let path = format!("/jobs/{}", body["id"].as_str().unwrap());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        let job = loop {
+            let (_, job) = route(&pool, &Method::Get, &path, "");
+            if job["status"] == "failed" || std::time::Instant::now() > deadline {
+                break job;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert!(job["error"]
+            .as_str()
+            .unwrap()
+            .contains("address and chain_id"));
+        pool.shutdown();
+    }
+
+    #[test]
+    fn test_only_configured_rpc_endpoints() {
+        let endpoints = [
+            RpcEndpoint {
+                chain_id: 20994,
+                url: "https://rpc.testnet.fluent.xyz/".to_string(),
+            },
+            RpcEndpoint {
+                chain_id: 25363,
+                url: "https://rpc.fluent.xyz".to_string(),
+            },
+        ];
+        assert_eq!(
+            allowed_rpc(&endpoints, 20994, None).unwrap(),
+            "https://rpc.testnet.fluent.xyz/"
+        );
+        assert_eq!(
+            allowed_rpc(&endpoints, 20994, Some("https://rpc.testnet.fluent.xyz")).unwrap(),
+            "https://rpc.testnet.fluent.xyz/"
+        );
+
+        // Another chain's endpoint, or any other address, is refused
+        assert!(allowed_rpc(&endpoints, 20994, Some("https://rpc.fluent.xyz")).is_err());
+        let err = allowed_rpc(&endpoints, 20994, Some("http://169.254.169.254/")).unwrap_err();
+        assert!(err.to_string().contains("is not configured"));
+        assert!(allowed_rpc(&endpoints, 1, None).is_err());
+    }
+
+    #[test]
+    fn test_sandboxed_artifacts_dir() {
+        assert_eq!(
+            sandboxed_artifacts_dir("token/1a2b3c4d").unwrap(),
+            Path::new("token/1a2b3c4d")
+        );
+        for reported in ["", "/etc", "../token", "token/../..", "./token"] {
+            assert!(
+                sandboxed_artifacts_dir(reported).is_err(),
+                "{} was accepted",
+                reported
+            );
+        }
+    }
+}
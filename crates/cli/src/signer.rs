@@ -0,0 +1,313 @@
+//! Keys that sign deployment and upgrade transactions
+//!
+//! The key comes from a Ledger device, an encrypted JSON keystore, a BIP-39
+//! mnemonic held in an environment variable, or the raw key in
+//! DEPLOY_PRIVATE_KEY, so keys don't have to be passed on the command line.
+//! Ledger support needs the CLI's `ledger` feature, which links hidapi.
+
+#[cfg(feature = "ledger")]
+use ethers::signers::{HDPath, Ledger, LedgerError};
+use ethers::{
+    signers::{coins_bip39::English, LocalWallet, MnemonicBuilder, Signer, WalletError},
+    types::{
+        transaction::{eip2718::TypedTransaction, eip712::Eip712},
+        Address, Signature,
+    },
+};
+use eyre::{Context, Result};
+use std::path::PathBuf;
+#[cfg(feature = "ledger")]
+use std::sync::Arc;
+
+/// Environment variable holding the private key `deploy` signs with
+pub const DEPLOY_KEY_ENV: &str = "DEPLOY_PRIVATE_KEY";
+
+/// Environment variable holding the password of `--keystore`
+pub const KEYSTORE_PASSWORD_ENV: &str = "DEPLOY_KEYSTORE_PASSWORD";
+
+/// Where the signing key of a command comes from
+#[derive(clap::Args, Debug, Clone, Default, PartialEq)]
+pub struct SignerArgs {
+    /// Sign with a Ledger device, unlocked with the Ethereum app open
+    #[arg(long, conflicts_with_all = ["keystore", "mnemonic_env"])]
+    pub ledger: bool,
+
+    /// Account of the Ledger to sign with (Ledger Live derivation path m/44'/60'/INDEX'/0/0)
+    #[arg(long, value_name = "INDEX", default_value_t = 0, requires = "ledger")]
+    pub ledger_index: usize,
+
+    /// Encrypted JSON keystore to sign with (password from DEPLOY_KEYSTORE_PASSWORD)
+    #[arg(long, value_name = "PATH", conflicts_with = "mnemonic_env")]
+    pub keystore: Option<PathBuf>,
+
+    /// Environment variable holding a BIP-39 mnemonic to sign with
+    #[arg(long, value_name = "VAR")]
+    pub mnemonic_env: Option<String>,
+
+    /// Account of the mnemonic to sign with (derivation path m/44'/60'/0'/0/INDEX)
+    #[arg(
+        long,
+        value_name = "INDEX",
+        default_value_t = 0,
+        requires = "mnemonic_env"
+    )]
+    pub mnemonic_index: u32,
+}
+
+/// Source of a signing key, in order of precedence
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeySource {
+    Ledger { index: usize },
+    Keystore(PathBuf),
+    Mnemonic { env: String, index: u32 },
+    PrivateKeyEnv,
+}
+
+impl SignerArgs {
+    /// Key source selected by the flags, or DEPLOY_PRIVATE_KEY if it is set
+    pub fn source(&self) -> Option<KeySource> {
+        if self.ledger {
+            return Some(KeySource::Ledger {
+                index: self.ledger_index,
+            });
+        }
+        if let Some(path) = &self.keystore {
+            return Some(KeySource::Keystore(path.clone()));
+        }
+        if let Some(env) = &self.mnemonic_env {
+            return Some(KeySource::Mnemonic {
+                env: env.clone(),
+                index: self.mnemonic_index,
+            });
+        }
+        std::env::var_os(DEPLOY_KEY_ENV).map(|_| KeySource::PrivateKeyEnv)
+    }
+
+    /// Signer to sign with; fails if no key is configured
+    pub async fn signer(&self) -> Result<DeploySigner> {
+        self.try_signer().await?.ok_or_else(|| {
+            eyre::eyre!(
+                "No signing key: pass --ledger, --keystore or --mnemonic-env, or set {} to the \
+                 deployer's private key",
+                DEPLOY_KEY_ENV
+            )
+        })
+    }
+
+    /// Signer to sign with, if a key is configured
+    pub async fn try_signer(&self) -> Result<Option<DeploySigner>> {
+        match self.source() {
+            Some(source) => source.signer().await.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl KeySource {
+    pub async fn signer(&self) -> Result<DeploySigner> {
+        match self {
+            KeySource::Ledger { index } => ledger_signer(*index).await,
+            _ => self
+                .wallet(|name| std::env::var(name).ok())
+                .map(DeploySigner::Local),
+        }
+    }
+
+    /// Wallet of a key held locally, reading environment variables with `env`
+    fn wallet(&self, env: impl Fn(&str) -> Option<String>) -> Result<LocalWallet> {
+        match self {
+            KeySource::Ledger { .. } => eyre::bail!("A Ledger key is not held locally"),
+            KeySource::Keystore(path) => {
+                let password = env(KEYSTORE_PASSWORD_ENV).ok_or_else(|| {
+                    eyre::eyre!(
+                        "Set {} to the password of {}",
+                        KEYSTORE_PASSWORD_ENV,
+                        path.display()
+                    )
+                })?;
+                LocalWallet::decrypt_keystore(path, password)
+                    .with_context(|| format!("Failed to decrypt keystore {}", path.display()))
+            }
+            KeySource::Mnemonic { env: name, index } => {
+                let phrase = env(name)
+                    .ok_or_else(|| eyre::eyre!("Set {} to the mnemonic to sign with", name))?;
+                MnemonicBuilder::<English>::default()
+                    .phrase(phrase.trim())
+                    .index(*index)
+                    .and_then(|builder| builder.build())
+                    .with_context(|| format!("{} is not a valid mnemonic", name))
+            }
+            KeySource::PrivateKeyEnv => {
+                let private_key = env(DEPLOY_KEY_ENV).ok_or_else(|| {
+                    eyre::eyre!("Set {} to the deployer's private key", DEPLOY_KEY_ENV)
+                })?;
+                private_key
+                    .trim()
+                    .trim_start_matches("0x")
+                    .parse()
+                    .map_err(|_| eyre::eyre!("{} is not a valid private key", DEPLOY_KEY_ENV))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ledger")]
+async fn ledger_signer(index: usize) -> Result<DeploySigner> {
+    // The chain ID is set per transaction by with_chain_id
+    let ledger = Ledger::new(HDPath::LedgerLive(index), 1)
+        .await
+        .context("Failed to connect to the Ledger; is it unlocked with the Ethereum app open?")?;
+    Ok(DeploySigner::Ledger {
+        chain_id: ledger.chain_id(),
+        ledger: Arc::new(ledger),
+    })
+}
+
+#[cfg(not(feature = "ledger"))]
+async fn ledger_signer(_index: usize) -> Result<DeploySigner> {
+    eyre::bail!(
+        "This fluent-builder was built without Ledger support; install it with `--features ledger`"
+    )
+}
+
+/// Key that signs transactions: held locally or on a Ledger device
+#[derive(Debug, Clone)]
+pub enum DeploySigner {
+    Local(LocalWallet),
+    /// The device is shared by every clone; `chain_id` is set on the transactions it signs
+    #[cfg(feature = "ledger")]
+    Ledger {
+        ledger: Arc<Ledger>,
+        chain_id: u64,
+    },
+}
+
+/// Error of a [`DeploySigner`]
+#[derive(Debug, thiserror::Error)]
+pub enum SignerError {
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+    #[cfg(feature = "ledger")]
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+}
+
+#[async_trait::async_trait]
+impl Signer for DeploySigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, SignerError> {
+        match self {
+            DeploySigner::Local(wallet) => Ok(wallet.sign_message(message).await?),
+            #[cfg(feature = "ledger")]
+            DeploySigner::Ledger { ledger, .. } => Ok(ledger.sign_message(message).await?),
+        }
+    }
+
+    async fn sign_transaction(&self, message: &TypedTransaction) -> Result<Signature, SignerError> {
+        match self {
+            DeploySigner::Local(wallet) => Ok(wallet.sign_transaction(message).await?),
+            #[cfg(feature = "ledger")]
+            DeploySigner::Ledger { ledger, chain_id } => {
+                let mut message = message.clone();
+                if message.chain_id().is_none() {
+                    message.set_chain_id(*chain_id);
+                }
+                Ok(ledger.sign_transaction(&message).await?)
+            }
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, SignerError> {
+        match self {
+            DeploySigner::Local(wallet) => Ok(wallet.sign_typed_data(payload).await?),
+            #[cfg(feature = "ledger")]
+            DeploySigner::Ledger { ledger, .. } => Ok(ledger.sign_typed_data(payload).await?),
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            DeploySigner::Local(wallet) => wallet.address(),
+            #[cfg(feature = "ledger")]
+            DeploySigner::Ledger { ledger, .. } => ledger.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            DeploySigner::Local(wallet) => wallet.chain_id(),
+            #[cfg(feature = "ledger")]
+            DeploySigner::Ledger { chain_id, .. } => *chain_id,
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            DeploySigner::Local(wallet) => DeploySigner::Local(wallet.with_chain_id(chain_id)),
+            #[cfg(feature = "ledger")]
+            DeploySigner::Ledger { ledger, .. } => DeploySigner::Ledger {
+                ledger,
+                chain_id: chain_id.into(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    /// Environment with only `name` set to `value`
+    fn env<'a>(name: &'a str, value: &'a str) -> impl Fn(&str) -> Option<String> + 'a {
+        move |var| (var == name).then(|| value.to_string())
+    }
+
+    #[test]
+    fn test_mnemonic_signer() {
+        let source = KeySource::Mnemonic {
+            env: "DEPLOY_MNEMONIC".to_string(),
+            index: 1,
+        };
+        let wallet = source.wallet(env("DEPLOY_MNEMONIC", MNEMONIC)).unwrap();
+        assert_eq!(
+            format!("{:?}", wallet.address()),
+            "0x70997970c51812dc3a010c7d01b50e0d17dc79c8"
+        );
+        assert!(source.wallet(env("OTHER_MNEMONIC", MNEMONIC)).is_err());
+    }
+
+    #[test]
+    fn test_keystore_signer() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (wallet, name) = LocalWallet::new_keystore(
+            dir.path(),
+            &mut ethers::core::rand::thread_rng(),
+            "secret",
+            None,
+        )
+        .unwrap();
+        let source = KeySource::Keystore(dir.path().join(name));
+        let decrypted = source.wallet(env(KEYSTORE_PASSWORD_ENV, "secret")).unwrap();
+        assert_eq!(decrypted.address(), wallet.address());
+        assert!(source.wallet(env(KEYSTORE_PASSWORD_ENV, "wrong")).is_err());
+    }
+
+    #[test]
+    fn test_ledger_takes_precedence() {
+        let args = SignerArgs {
+            ledger: true,
+            ledger_index: 3,
+            ..Default::default()
+        };
+        assert_eq!(args.source(), Some(KeySource::Ledger { index: 3 }));
+    }
+}
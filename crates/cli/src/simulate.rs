@@ -0,0 +1,201 @@
+//! Rehearsing deployments on a forked chain
+//!
+//! The deployment is sent to a local fork node, so it runs against the
+//! network's real state without spending funds. The deployer is funded on
+//! the fork with anvil's `anvil_setBalance` (or `hardhat_setBalance`).
+
+use crate::signer::DeploySigner;
+use ethers::{
+    middleware::SignerMiddleware,
+    providers::{Http, Middleware, Provider},
+    signers::{LocalWallet, Signer},
+    types::{Address, TransactionRequest, U256},
+};
+use eyre::{Context, Result};
+use fluent_builder::{Abi, AbiEntry, CodeUnwrap, ContractArtifacts};
+use std::time::Duration;
+
+/// Balance given to the deployer on the fork (1000 ether)
+const FORK_BALANCE: u128 = 1_000_000_000_000_000_000_000;
+
+/// How long a freshly started fork node may take to answer
+const FORK_READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Outcome of a view function called after the simulated deployment
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallReport {
+    /// e.g. `totalSupply()`
+    pub signature: String,
+    pub gas: Option<u64>,
+    /// Revert or RPC error
+    pub error: Option<String>,
+}
+
+/// Outcome of a simulated deployment
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub address: String,
+    pub deploy_gas: u64,
+    /// Whether the fork stored the artifact's rWASM
+    pub code_matches: bool,
+    pub calls: Vec<CallReport>,
+}
+
+impl SimulationReport {
+    pub fn passed(&self) -> bool {
+        self.code_matches && self.calls.iter().all(|call| call.error.is_none())
+    }
+}
+
+/// Deploy `calldata` on the fork at `rpc_url` and call the contract's view functions
+///
+/// Without `wallet` a random deployer is used.
+pub async fn simulate(
+    rpc_url: &str,
+    chain_id: u64,
+    calldata: Vec<u8>,
+    artifacts: &ContractArtifacts,
+    wallet: Option<DeploySigner>,
+) -> Result<SimulationReport> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create fork provider")?;
+    wait_until_ready(&provider, chain_id).await?;
+
+    let wallet = wallet.unwrap_or_else(|| {
+        DeploySigner::Local(LocalWallet::new(&mut ethers::core::rand::thread_rng()))
+    });
+    fund(&provider, wallet.address()).await?;
+    let client = SignerMiddleware::new(provider.clone(), wallet.with_chain_id(chain_id));
+
+    let receipt = client
+        .send_transaction(TransactionRequest::new().data(calldata), None)
+        .await
+        .context("Deployment was rejected by the fork")?
+        .await
+        .context("Failed to wait for the deployment on the fork")?
+        .ok_or_else(|| eyre::eyre!("Deployment transaction was dropped by the fork"))?;
+    if receipt.status != Some(1.into()) {
+        return Err(eyre::eyre!(
+            "Deployment reverted on the fork (gas used: {:?})",
+            receipt.gas_used
+        ));
+    }
+    let address = receipt
+        .contract_address
+        .ok_or_else(|| eyre::eyre!("Deployment created no contract on the fork"))?;
+
+    let code = provider
+        .get_code(address, None)
+        .await
+        .context("Failed to fetch code from the fork")?;
+    let (bytecode, _) = CodeUnwrap::Auto.apply(&code)?;
+
+    let mut calls = Vec::new();
+    for signature in view_calls(&artifacts.abi) {
+        let selector = ethers::utils::id(&signature);
+        let call = TransactionRequest::new()
+            .to(address)
+            .data(selector.to_vec())
+            .into();
+        let report = match provider.call(&call, None).await {
+            Ok(_) => CallReport {
+                gas: provider
+                    .estimate_gas(&call, None)
+                    .await
+                    .ok()
+                    .map(|gas| gas.as_u64()),
+                signature,
+                error: None,
+            },
+            Err(e) => CallReport {
+                signature,
+                gas: None,
+                error: Some(e.to_string()),
+            },
+        };
+        calls.push(report);
+    }
+
+    Ok(SimulationReport {
+        address: format!("{:?}", address),
+        deploy_gas: receipt.gas_used.unwrap_or_default().as_u64(),
+        code_matches: crate::matches_artifact(&artifacts.deploy, bytecode),
+        calls,
+    })
+}
+
+/// Signatures of the view and pure functions that take no arguments
+pub fn view_calls(abi: &Abi) -> Vec<String> {
+    abi.iter()
+        .filter_map(AbiEntry::as_function)
+        .filter(|function| function.state_mutability.is_read_only() && function.inputs.is_empty())
+        .map(|function| format!("{}()", function.name))
+        .collect()
+}
+
+/// Wait for a node that may still be starting, and check it forks the expected chain
+async fn wait_until_ready(provider: &Provider<Http>, chain_id: u64) -> Result<()> {
+    let started = std::time::Instant::now();
+    let id = loop {
+        match provider.get_chainid().await {
+            Ok(id) => break id.as_u64(),
+            Err(e) if started.elapsed() > FORK_READY_TIMEOUT => {
+                return Err(eyre::eyre!(
+                    "Fork node did not answer within {:?}: {}",
+                    FORK_READY_TIMEOUT,
+                    e
+                ))
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(500)).await,
+        }
+    };
+    if id != chain_id {
+        return Err(eyre::eyre!(
+            "Fork node runs chain {}, expected {}",
+            id,
+            chain_id
+        ));
+    }
+    Ok(())
+}
+
+/// Give `address` a balance on the fork
+async fn fund(provider: &Provider<Http>, address: Address) -> Result<()> {
+    let balance = U256::from(FORK_BALANCE);
+    for method in ["anvil_setBalance", "hardhat_setBalance"] {
+        if provider
+            .request::<_, serde_json::Value>(method, (address, balance))
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+    }
+    Err(eyre::eyre!(
+        "Fork node does not support anvil_setBalance; is --fork-rpc a fork node rather than the \
+         network?"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_view_calls() {
+        let abi = serde_json::from_value(json!([
+            {"type": "function", "name": "totalSupply", "inputs": [], "stateMutability": "view"},
+            {"type": "function", "name": "decimals", "inputs": [], "stateMutability": "pure"},
+            {
+                "type": "function",
+                "name": "balanceOf",
+                "inputs": [{"type": "address"}],
+                "stateMutability": "view"
+            },
+            {"type": "function", "name": "mint", "inputs": [], "stateMutability": "nonpayable"},
+            {"type": "event", "name": "Transfer", "inputs": []},
+        ]))
+        .unwrap();
+        assert_eq!(view_calls(&abi), vec!["totalSupply()", "decimals()"]);
+    }
+}
@@ -0,0 +1,87 @@
+//! Switching a proxy to a new implementation
+//!
+//! Proxies are expected to keep their implementation in the EIP-1967 slot,
+//! which is read before and after the switch.
+
+use crate::signer::DeploySigner;
+use ethers::{
+    middleware::SignerMiddleware,
+    providers::{Http, Middleware, Provider},
+    signers::Signer,
+    types::{Address, TransactionRequest, H256},
+};
+use eyre::{Context, Result};
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+const IMPLEMENTATION_SLOT: &str =
+    "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
+/// Implementation a proxy points to, if it keeps one in the EIP-1967 slot
+pub async fn implementation_of(rpc_url: &str, proxy: Address) -> Result<Option<Address>> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create provider")?;
+    let slot: H256 = IMPLEMENTATION_SLOT.parse()?;
+    let value = provider
+        .get_storage_at(proxy, slot, None)
+        .await
+        .context("Failed to read the proxy's implementation slot")?;
+    let implementation = Address::from_slice(&value.as_bytes()[12..]);
+    Ok((!implementation.is_zero()).then_some(implementation))
+}
+
+/// Calldata of `signature`, an admin function taking the new implementation's address
+pub fn upgrade_calldata(signature: &str, implementation: Address) -> Result<Vec<u8>> {
+    if !signature.ends_with("(address)") {
+        return Err(eyre::eyre!(
+            "Upgrade function {} must take the implementation address as its only argument",
+            signature
+        ));
+    }
+    let mut calldata = ethers::utils::id(signature).to_vec();
+    calldata.extend_from_slice(H256::from(implementation).as_bytes());
+    Ok(calldata)
+}
+
+/// Send the upgrade call to `proxy` and wait for it to succeed
+pub async fn switch_implementation(
+    rpc_url: &str,
+    chain_id: u64,
+    wallet: &DeploySigner,
+    proxy: Address,
+    calldata: Vec<u8>,
+) -> Result<H256> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create provider")?;
+    let client = SignerMiddleware::new(provider, wallet.clone().with_chain_id(chain_id));
+
+    let receipt = client
+        .send_transaction(TransactionRequest::new().to(proxy).data(calldata), None)
+        .await
+        .context("Failed to send the upgrade transaction")?
+        .await
+        .context("Failed to wait for the upgrade transaction")?
+        .ok_or_else(|| eyre::eyre!("Upgrade transaction was dropped"))?;
+    if receipt.status != Some(1.into()) {
+        return Err(eyre::eyre!(
+            "Upgrade transaction {:?} reverted; is the deployer the proxy's admin?",
+            receipt.transaction_hash
+        ));
+    }
+    Ok(receipt.transaction_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_calldata() {
+        let implementation: Address = "0x1111111111111111111111111111111111111111"
+            .parse()
+            .unwrap();
+        let calldata = upgrade_calldata("upgradeTo(address)", implementation).unwrap();
+        assert_eq!(hex::encode(&calldata[..4]), "3659cfe6");
+        assert_eq!(&calldata[4..16], &[0u8; 12]);
+        assert_eq!(&calldata[16..], implementation.as_bytes());
+
+        assert!(upgrade_calldata("upgradeToAndCall(address,bytes)", implementation).is_err());
+    }
+}
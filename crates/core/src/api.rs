@@ -0,0 +1,356 @@
+//! HTTP API of the verification server
+//!
+//! Request and response bodies of `fluent-builder serve`, shared by the
+//! server and Rust clients, and the OpenAPI description generated from
+//! them so explorers can generate clients in other languages. The
+//! description is served at [`OPENAPI_PATH`]. Clients only need the `rpc`
+//! feature; the job queue and its SQLite store are behind `server`.
+
+use crate::{CodeUnwrap, HashAlgorithm, MismatchHint};
+use schemars::{generate::SchemaSettings, JsonSchema, SchemaGenerator};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Path the server serves its OpenAPI description at
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+/// A request to verify a deployed contract against sources on the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct VerifyRequest {
+    /// Contract sources on the server, relative to its work directory
+    pub project_root: PathBuf,
+    /// Hash of the deployed bytecode; otherwise fetched via `address` and `chain_id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deployed_bytecode_hash: Option<String>,
+    /// Algorithm of `deployed_bytecode_hash`; `keccak256` is the chain's `EXTCODEHASH`
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// How code fetched via `address` is unwrapped before hashing
+    #[serde(default)]
+    pub unwrap: CodeUnwrap,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    /// One of the server's RPC endpoints for `chain_id`; its first one when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc: Option<String>,
+    #[serde(default = "default_profile")]
+    pub profile: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default = "default_no_default_features")]
+    pub no_default_features: bool,
+    /// Target or variant of a multi-contract package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_name: Option<String>,
+    /// Hex encoded constructor arguments of the deployment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constructor_args: Option<String>,
+    /// Arguments passed to `cargo build` as-is; only accepted by sandboxed servers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_cargo_args: Vec<String>,
+    /// RUSTFLAGS of the build; only accepted by sandboxed servers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustflags: Option<String>,
+}
+
+fn default_profile() -> String {
+    "release".to_string()
+}
+
+fn default_no_default_features() -> bool {
+    true
+}
+
+/// Lifecycle of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job will not change anymore
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+/// Result of a verification job that ran to completion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct JobOutcome {
+    pub verified: bool,
+    pub contract_name: String,
+    pub expected_hash: String,
+    pub actual_hash: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<MismatchHint>,
+    /// Hex encoded constructor arguments the contract was verified with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constructor_args: Option<String>,
+}
+
+/// A queued, running or finished job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Job {
+    pub id: String,
+    pub request: VerifyRequest,
+    pub status: JobStatus,
+    /// Unix timestamps in seconds
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+    /// What a running job is doing, e.g. `building`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    /// Progress messages, most recent last
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<String>,
+    /// Set when the job succeeded (the contract may still not match)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<JobOutcome>,
+    /// Set when the job failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Job whose cached result was reused instead of building
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_from: Option<String>,
+}
+
+/// `GET /health`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HealthResponse {
+    /// Always `ok`
+    pub status: String,
+    /// Jobs waiting for a worker
+    pub queued: usize,
+}
+
+/// `POST /jobs` when the job was queued
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct JobAccepted {
+    /// Poll `GET /jobs/{id}` for the result
+    pub id: String,
+    pub status: JobStatus,
+}
+
+/// `DELETE /cache`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CachePurged {
+    /// Cached results removed
+    pub removed: usize,
+}
+
+/// Body of every error response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ApiError {
+    pub error: String,
+    /// Machine-readable reason: `unauthorized`, `forbidden` or `rate_limited`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Seconds until a rate limited request may be retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            code: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+}
+
+/// OpenAPI 3.1 description of the verification server
+pub fn openapi() -> Value {
+    let settings = || {
+        SchemaSettings::draft2020_12().with(|settings| {
+            settings.definitions_path = "/components/schemas".into();
+            settings.meta_schema = None;
+        })
+    };
+
+    // Requests are described as parsed (defaulted fields are optional), responses as written
+    let mut requests = settings().for_deserialize().into_generator();
+    requests.subschema_for::<VerifyRequest>();
+    let mut request_schemas = requests.take_definitions(true);
+    let request_body = request_schemas.remove("VerifyRequest").unwrap_or_default();
+
+    let mut responses = settings().for_serialize().into_generator();
+    let health = schema_ref::<HealthResponse>(&mut responses);
+    let accepted = schema_ref::<JobAccepted>(&mut responses);
+    let job = schema_ref::<Job>(&mut responses);
+    let purged = schema_ref::<CachePurged>(&mut responses);
+    let error = schema_ref::<ApiError>(&mut responses);
+    let mut schemas = responses.take_definitions(true);
+    for (name, schema) in request_schemas {
+        schemas.entry(name).or_insert(schema);
+    }
+
+    let response = |description: &str, schema: &Value| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": schema } },
+        })
+    };
+    let denied = |description: &str| response(description, &error);
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "fluent-builder verification server",
+            "version": crate::VERSION,
+            "description":
+                "Rebuilds contract sources on the server and compares them with deployed bytecode.",
+        },
+        "paths": {
+            "/health": {
+                "get": {
+                    "operationId": "health",
+                    "summary": "Liveness and queue length",
+                    "security": [],
+                    "responses": { "200": response("Server is up", &health) },
+                },
+            },
+            OPENAPI_PATH: {
+                "get": {
+                    "operationId": "openapi",
+                    "summary": "This document",
+                    "security": [],
+                    "responses": {
+                        "200": response("OpenAPI description", &json!({ "type": "object" })),
+                    },
+                },
+            },
+            "/jobs": {
+                "post": {
+                    "operationId": "submitJob",
+                    "summary": "Queue a verification",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": request_body } },
+                    },
+                    "responses": {
+                        "202": response("Queued", &accepted),
+                        "400": denied("Invalid request"),
+                        "401": denied("Missing or invalid API key"),
+                        "429": denied("Rate limit or daily job quota exceeded"),
+                        "503": denied("Queue full or shutting down; retry later"),
+                    },
+                },
+            },
+            "/jobs/{id}": {
+                "get": {
+                    "operationId": "getJob",
+                    "summary": "Status, logs and outcome of a job",
+                    "parameters": [
+                        {
+                            "name": "id",
+                            "in": "path",
+                            "required": true,
+                            "schema": { "type": "string" },
+                        },
+                    ],
+                    "responses": {
+                        "200": response("The job", &job),
+                        "401": denied("Missing or invalid API key"),
+                        "404": denied("Unknown job"),
+                        "429": denied("Rate limit exceeded"),
+                    },
+                },
+            },
+            "/cache": {
+                "delete": {
+                    "operationId": "purgeCache",
+                    "summary": "Remove cached verification results (admin keys only)",
+                    "parameters": [
+                        {
+                            "name": "bytecode_hash",
+                            "in": "query",
+                            "required": false,
+                            "description": "Only results of this deployed bytecode hash",
+                            "schema": { "type": "string" },
+                        },
+                    ],
+                    "responses": {
+                        "200": response("Results removed", &purged),
+                        "401": denied("Missing or invalid API key"),
+                        "403": denied("Not an admin key"),
+                        "429": denied("Rate limit exceeded"),
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-API-Key" },
+            },
+        },
+        "security": [{ "bearer": [] }, { "apiKey": [] }, {}],
+    })
+}
+
+fn schema_ref<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    generator.subschema_for::<T>().to_value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `$ref`s anywhere in `value`
+    fn refs(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    found.push(reference.clone());
+                }
+                map.values().for_each(|v| refs(v, found));
+            }
+            Value::Array(items) => items.iter().for_each(|v| refs(v, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_openapi_references_resolve() {
+        let spec = openapi();
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+        assert!(found.contains(&"#/components/schemas/Job".to_string()));
+        for reference in found {
+            let pointer = reference.trim_start_matches('#');
+            assert!(spec.pointer(pointer).is_some(), "dangling {}", reference);
+        }
+
+        // Fields with defaults are optional in requests
+        let request =
+            &spec["paths"]["/jobs"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(request["required"], json!(["project_root"]));
+    }
+
+    #[test]
+    fn test_error_body() {
+        let error = ApiError::new("Unknown job").with_code("not_found");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({ "error": "Unknown job", "code": "not_found" })
+        );
+    }
+}
@@ -1,15 +1,17 @@
-use eyre::{ensure, Result};
+use crate::hashing::digest_reader;
+use eyre::{ensure, Context, Result};
 use flate2::{write::GzEncoder, Compression};
+use ignore::overrides::{Override, OverrideBuilder};
 use sha2::{Digest, Sha256};
 use std::{
-    fs,
-    io::Write,
+    collections::BTreeSet,
+    fs, io,
     path::{Path, PathBuf},
 };
-use tar::Builder;
+use tar::{Builder, HeaderMode};
 use walkdir::WalkDir;
 use zip::write::FileOptions;
-use zip::{CompressionMethod, ZipWriter};
+use zip::{CompressionMethod, DateTime, ZipWriter};
 
 /// Archive format options
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -31,6 +33,11 @@ pub struct ArchiveOptions {
     pub compression_level: u32,
     /// Use .gitignore rules if present
     pub respect_gitignore: bool,
+    /// Non-Rust files to include, as globs relative to the project root, e.g. `assets/**`
+    pub include_globs: Vec<String>,
+    /// Files to leave out, as globs relative to the project root; `Cargo.toml` and the other
+    /// critical files are always kept
+    pub exclude_globs: Vec<String>,
 }
 
 impl Default for ArchiveOptions {
@@ -40,6 +47,8 @@ impl Default for ArchiveOptions {
             only_compilation_files: true,
             compression_level: 6,
             respect_gitignore: true,
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
         }
     }
 }
@@ -59,6 +68,9 @@ pub struct ArchiveInfo {
     pub project_path: String,
 }
 
+/// Macros reading a file at compile time
+const INCLUDE_MACROS: &[&str] = &["include!", "include_str!", "include_bytes!"];
+
 const CRITICAL_FILES: &[&str] = &[
     "Cargo.toml",
     "Cargo.lock",
@@ -82,21 +94,28 @@ pub fn create_verification_archive(
         ignore::gitignore::Gitignore::empty()
     };
 
+    let include = globs(project_root, &options.include_globs).context("Invalid include glob")?;
+    let exclude = globs(project_root, &options.exclude_globs).context("Invalid exclude glob")?;
+    let excluded = |path: &Path| exclude.matched(path, false).is_whitelist();
+
     let mut files = Vec::new();
+    let mut seen = BTreeSet::new();
 
     // Collect critical files
     for &critical in CRITICAL_FILES {
         let path = project_root.join(critical);
         if path.exists() {
+            seen.insert(path.clone());
             files.push(path);
         }
     }
 
-    // Collect source files
+    // Collect source files, build scripts included, and the files matching an include glob
     for entry in WalkDir::new(project_root)
         .into_iter()
         .filter_entry(|e| {
-            !e.path().components().any(|c| {
+            let relative = e.path().strip_prefix(project_root).unwrap_or(e.path());
+            !relative.components().any(|c| {
                 matches!(
                     c.as_os_str().to_str(),
                     Some("target" | "out" | "node_modules")
@@ -104,17 +123,41 @@ pub fn create_verification_archive(
             })
         })
         .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
     {
         let path = entry.path();
-        if path.extension().map_or(false, |ext| ext == "rs")
+        let wanted = path.extension().is_some_and(|ext| ext == "rs")
+            || include.matched(path, false).is_whitelist();
+        if wanted
             && !gitignore.matched(path, false).is_ignore()
+            && !excluded(path)
+            && seen.insert(path.to_path_buf())
         {
             files.push(path.to_path_buf());
         }
     }
 
+    // Collect the files the sources include at compile time
+    let sources: Vec<PathBuf> = files
+        .iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .cloned()
+        .collect();
+    for source in sources {
+        for path in included_files(&source, project_root) {
+            if !excluded(&path) && seen.insert(path.clone()) {
+                files.push(path);
+            }
+        }
+    }
+
     ensure!(!files.is_empty(), "No source files found");
 
+    // Same sources, same archive: entries in path order, whatever order the walk found them in
+    files.sort_by_cached_key(|file| {
+        crate::paths::to_slash(file.strip_prefix(project_root).unwrap_or(file))
+    });
+
     // Create output directory
     fs::create_dir_all(output_path.parent().unwrap())?;
 
@@ -131,6 +174,8 @@ pub fn create_verification_archive(
             let tar_gz = fs::File::create(output_path)?;
             let encoder = GzEncoder::new(tar_gz, Compression::new(options.compression_level));
             let mut tar = Builder::new(encoder);
+            // Fixed mtime, uid and gid; modes normalized to 644 or 755
+            tar.mode(HeaderMode::Deterministic);
 
             for file in &files {
                 let relative_path = file.strip_prefix(project_root).unwrap();
@@ -146,28 +191,35 @@ pub fn create_verification_archive(
             let zip_file = fs::File::create(output_path)?;
             let mut zip = ZipWriter::new(zip_file);
 
+            // Entries default to the current time; use the earliest zip timestamp instead
             let options = FileOptions::default()
                 .compression_method(CompressionMethod::Deflated)
-                .compression_level(Some(options.compression_level as i32));
+                .compression_level(Some(options.compression_level as i32))
+                .last_modified_time(DateTime::default());
 
             for file in &files {
                 let relative_path = file.strip_prefix(project_root).unwrap();
                 // Add project directory name as prefix
                 let archive_path = Path::new(project_dir_name).join(relative_path);
-                let archive_path_str = archive_path.to_string_lossy();
+                // Zip entries always use forward slashes
+                let archive_path_str = crate::paths::to_slash(&archive_path);
+                let mode = if is_executable(&fs::metadata(file)?) {
+                    0o755
+                } else {
+                    0o644
+                };
 
-                zip.start_file(&archive_path_str.into_owned(), options)?;
-                zip.write_all(&fs::read(file)?)?;
+                zip.start_file(archive_path_str, options.unix_permissions(mode))?;
+                io::copy(&mut fs::File::open(file)?, &mut zip)?;
             }
 
             zip.finish()?;
         }
     }
 
-    // Calculate hash and size
-    let content = fs::read(output_path)?;
-    let hash = format!("{:x}", Sha256::digest(&content));
-    let size = content.len() as u64;
+    // Calculate hash and size without loading the archive
+    let (hasher, size) = digest_reader(Sha256::new(), fs::File::open(output_path)?)?;
+    let hash = format!("{:x}", hasher.finalize());
 
     Ok(ArchiveInfo {
         path: output_path.into(),
@@ -178,6 +230,112 @@ pub fn create_verification_archive(
     })
 }
 
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o100 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Glob matcher rooted at `root`; matched paths are whitelisted
+fn globs(root: &Path, globs: &[String]) -> Result<Override> {
+    let mut builder = OverrideBuilder::new(root);
+    for glob in globs {
+        builder.add(glob).with_context(|| format!("'{}'", glob))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Files inside `project_root` that `source` reads with `include!`, `include_str!` or
+/// `include_bytes!`
+///
+/// Paths are resolved like rustc does: relative to the source file, or to
+/// the manifest directory with `concat!(env!("CARGO_MANIFEST_DIR"), ..)`.
+/// Paths built from other variables, such as `OUT_DIR`, are generated and
+/// skipped.
+fn included_files(source: &Path, project_root: &Path) -> Vec<PathBuf> {
+    let (Ok(content), Ok(root)) = (fs::read_to_string(source), project_root.canonicalize()) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    for name in INCLUDE_MACROS {
+        for arguments in macro_arguments(&content, name) {
+            let literals = string_literals(arguments);
+            let path = if arguments.contains("env!") {
+                match literals.split_first() {
+                    Some((var, rest)) if var == "CARGO_MANIFEST_DIR" => {
+                        project_root.join(rest.concat().trim_start_matches('/'))
+                    }
+                    _ => continue,
+                }
+            } else if let [literal] = literals.as_slice() {
+                source.parent().unwrap_or(project_root).join(literal)
+            } else {
+                continue;
+            };
+            // Keep paths relative to the project root free of `..`
+            let Ok(path) = path.canonicalize() else {
+                continue;
+            };
+            if let Ok(relative) = path.strip_prefix(&root) {
+                if path.is_file() {
+                    files.push(project_root.join(relative));
+                }
+            }
+        }
+    }
+    files
+}
+
+/// Text between the parentheses of each invocation of the macro `name`
+fn macro_arguments<'a>(content: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    content.match_indices(name).filter_map(move |(start, _)| {
+        let preceding = content[..start].chars().next_back();
+        if preceding.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+        let rest = content[start + name.len()..]
+            .trim_start()
+            .strip_prefix('(')?;
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+        for (i, c) in rest.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '(' if !in_string => depth += 1,
+                ')' if !in_string && depth == 0 => return Some(&rest[..i]),
+                ')' if !in_string => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    })
+}
+
+/// Contents of the string literals in `text`
+fn string_literals(text: &str) -> Vec<String> {
+    let mut literals = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (&mut current, c) {
+            (None, '"') => current = Some(String::new()),
+            (Some(_), '"') => literals.extend(current.take()),
+            (Some(literal), '\\') => literal.extend(chars.next()),
+            (Some(literal), c) => literal.push(c),
+            (None, _) => {}
+        }
+    }
+    literals
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -204,6 +362,125 @@ mod tests {
         // Should use the immediate parent directory name
         assert_eq!(info.project_path, "token-contract");
 
+        Ok(())
+    }
+    #[test]
+    fn test_non_rust_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let project = temp_dir.path().join("token");
+        for dir in ["src/generated", "abi", "assets", "contracts"] {
+            fs::create_dir_all(project.join(dir))?;
+        }
+        fs::write(project.join("Cargo.toml"), "[package]\nname = \"token\"")?;
+        fs::write(project.join("build.rs"), "fn main() {}")?;
+        fs::write(
+            project.join("src/lib.rs"),
+            r#"const ABI: &str = include_str!("../abi/token.json");
+const LOGO: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/logo.bin"));
+include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+my_include!("../assets/unused.bin");"#,
+        )?;
+        fs::write(project.join("src/generated/mod.rs"), "// generated")?;
+        fs::write(project.join("abi/token.json"), "[]")?;
+        fs::write(project.join("assets/logo.bin"), [0u8, 1])?;
+        fs::write(project.join("assets/unused.bin"), [0u8])?;
+        fs::write(project.join("contracts/IToken.sol"), "interface IToken {}")?;
+        fs::write(project.join("contracts/notes.md"), "# Notes")?;
+
+        let output_path = temp_dir.path().join("token.tar.gz");
+        let options = ArchiveOptions {
+            include_globs: vec!["contracts/*.sol".to_string()],
+            exclude_globs: vec!["src/generated/**".to_string()],
+            ..ArchiveOptions::default()
+        };
+        let info = create_verification_archive(&project, &output_path, &options)?;
+
+        let mut archive =
+            tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(&output_path)?));
+        let mut entries = archive
+            .entries()?
+            .map(|entry| Ok(crate::paths::to_slash(&entry?.path()?)))
+            .collect::<Result<Vec<String>>>()?;
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                "token/Cargo.toml",
+                "token/abi/token.json",
+                "token/assets/logo.bin",
+                "token/build.rs",
+                "token/contracts/IToken.sol",
+                "token/src/lib.rs",
+            ]
+        );
+        assert_eq!(info.file_count, 6);
+
+        let invalid = ArchiveOptions {
+            include_globs: vec!["assets/[".to_string()],
+            ..ArchiveOptions::default()
+        };
+        assert!(create_verification_archive(&project, &output_path, &invalid).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_archives_are_deterministic() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let project = temp_dir.path().join("token");
+        fs::create_dir_all(project.join("src/nested"))?;
+        fs::write(project.join("Cargo.toml"), "[package]\nname = \"token\"")?;
+        fs::write(project.join("src/lib.rs"), "mod nested;")?;
+        fs::write(project.join("src/nested/mod.rs"), "// nested")?;
+
+        for (format, extension) in [
+            (ArchiveFormat::TarGz, "tar.gz"),
+            (ArchiveFormat::Zip, "zip"),
+        ] {
+            let options = ArchiveOptions {
+                format,
+                ..ArchiveOptions::default()
+            };
+            let path = |name: &str| temp_dir.path().join(format!("{}.{}", name, extension));
+            let first = create_verification_archive(&project, &path("first"), &options)?;
+
+            // Touch the sources and change their permissions; the content stays the same
+            for file in ["Cargo.toml", "src/lib.rs", "src/nested/mod.rs"] {
+                let file = fs::File::options().append(true).open(project.join(file))?;
+                file.set_modified(
+                    std::time::SystemTime::now() + std::time::Duration::from_secs(3600),
+                )?;
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(
+                    project.join("src/lib.rs"),
+                    fs::Permissions::from_mode(0o600),
+                )?;
+            }
+            let second = create_verification_archive(&project, &path("second"), &options)?;
+
+            assert_eq!(first.hash, second.hash, "{:?}", format);
+            assert_eq!(fs::read(&first.path)?, fs::read(&second.path)?);
+        }
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(
+            temp_dir.path().join("first.tar.gz"),
+        )?));
+        let entries = archive
+            .entries()?
+            .map(|entry| Ok(crate::paths::to_slash(&entry?.path()?)))
+            .collect::<Result<Vec<String>>>()?;
+        assert_eq!(
+            entries,
+            [
+                "token/Cargo.toml",
+                "token/src/lib.rs",
+                "token/src/nested/mod.rs"
+            ]
+        );
+
         Ok(())
     }
 }
@@ -1,23 +1,422 @@
-use eyre::Result;
-use serde_json::Value;
+use super::sanitize::Sanitizer;
+use crate::{
+    parser::{parse_deploy, parse_errors, parse_routers, CustomError, Declarations, RouterFacet},
+    BuilderError,
+};
+use convert_case::{Case, Casing};
+use eyre::{Context, Result};
+use fluentbase_sdk_derive_core::router::Router;
+#[cfg(feature = "build")]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+    str::FromStr,
+};
 
-/// Solidity ABI represented as JSON values
-pub type Abi = Vec<Value>;
+/// Solidity ABI, the entries of abi.json
+pub type Abi = Vec<AbiEntry>;
 
-/// Generates ABI from parsed routers
-pub fn generate(routers: &[fluentbase_sdk_derive_core::router::Router]) -> Result<Abi> {
-    if routers.is_empty() {
-        return Ok(Vec::new());
+/// An entry of a Solidity ABI, tagged by its `type`
+///
+/// Serializes with the keys in alphabetical order, like solc, so abi.json
+/// keeps the layout it had as untyped JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AbiEntry {
+    Function(AbiFunction),
+    Constructor(AbiConstructor),
+    Fallback(AbiFallback),
+    Receive(AbiFallback),
+    Event(AbiEvent),
+    Error(AbiError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AbiFunction {
+    #[serde(default)]
+    pub inputs: Vec<Param>,
+    pub name: String,
+    #[serde(default)]
+    pub outputs: Vec<Param>,
+    #[serde(default)]
+    pub state_mutability: StateMutability,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AbiConstructor {
+    #[serde(default)]
+    pub inputs: Vec<Param>,
+    #[serde(default)]
+    pub state_mutability: StateMutability,
+}
+
+/// Fallback or receive function; the latter is always payable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct AbiFallback {
+    #[serde(default)]
+    pub state_mutability: StateMutability,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+pub struct AbiEvent {
+    #[serde(default)]
+    pub anonymous: bool,
+    #[serde(default)]
+    pub inputs: Vec<Param>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+pub struct AbiError {
+    #[serde(default)]
+    pub inputs: Vec<Param>,
+    pub name: String,
+}
+
+/// Parameter of a function, event or error, or a field of a tuple
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Param {
+    /// Fields of a `tuple`, `tuple[]` or `tuple[N]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<Param>,
+    /// Whether an event parameter is a topic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexed: Option<bool>,
+    /// Type as declared, e.g. `struct Order[]` for `tuple[]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_type: Option<String>,
+    /// Empty for unnamed parameters
+    #[serde(default)]
+    pub name: String,
+    /// ABI type, e.g. `uint256` or `tuple[2]`
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum StateMutability {
+    Pure,
+    View,
+    #[default]
+    Nonpayable,
+    Payable,
+}
+
+impl AbiEntry {
+    /// The `type` of the entry, e.g. `function`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AbiEntry::Function(_) => "function",
+            AbiEntry::Constructor(_) => "constructor",
+            AbiEntry::Fallback(_) => "fallback",
+            AbiEntry::Receive(_) => "receive",
+            AbiEntry::Event(_) => "event",
+            AbiEntry::Error(_) => "error",
+        }
+    }
+
+    /// Name of a function, event or error
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            AbiEntry::Function(function) => Some(&function.name),
+            AbiEntry::Event(event) => Some(&event.name),
+            AbiEntry::Error(error) => Some(&error.name),
+            AbiEntry::Constructor(_) | AbiEntry::Fallback(_) | AbiEntry::Receive(_) => None,
+        }
+    }
+
+    pub fn inputs(&self) -> &[Param] {
+        match self {
+            AbiEntry::Function(function) => &function.inputs,
+            AbiEntry::Constructor(constructor) => &constructor.inputs,
+            AbiEntry::Event(event) => &event.inputs,
+            AbiEntry::Error(error) => &error.inputs,
+            AbiEntry::Fallback(_) | AbiEntry::Receive(_) => &[],
+        }
+    }
+
+    /// Outputs of a function; other entries have none
+    pub fn outputs(&self) -> &[Param] {
+        self.as_function()
+            .map(|function| function.outputs.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Mutability of a function, constructor, fallback or receive function
+    pub fn state_mutability(&self) -> Option<StateMutability> {
+        match self {
+            AbiEntry::Function(function) => Some(function.state_mutability),
+            AbiEntry::Constructor(constructor) => Some(constructor.state_mutability),
+            AbiEntry::Fallback(fallback) | AbiEntry::Receive(fallback) => {
+                Some(fallback.state_mutability)
+            }
+            AbiEntry::Event(_) | AbiEntry::Error(_) => None,
+        }
+    }
+
+    pub fn as_function(&self) -> Option<&AbiFunction> {
+        match self {
+            AbiEntry::Function(function) => Some(function),
+            _ => None,
+        }
+    }
+
+    /// Inputs followed by outputs
+    pub(crate) fn params_mut(&mut self) -> impl Iterator<Item = &mut Param> {
+        let (inputs, outputs): (&mut [Param], &mut [Param]) = match self {
+            AbiEntry::Function(function) => (&mut function.inputs, &mut function.outputs),
+            AbiEntry::Constructor(constructor) => (&mut constructor.inputs, &mut []),
+            AbiEntry::Event(event) => (&mut event.inputs, &mut []),
+            AbiEntry::Error(error) => (&mut error.inputs, &mut []),
+            AbiEntry::Fallback(_) | AbiEntry::Receive(_) => (&mut [], &mut []),
+        };
+        inputs.iter_mut().chain(outputs)
+    }
+}
+
+impl AbiFunction {
+    /// Canonical signature, e.g. `transfer(address,uint256)`
+    pub fn signature(&self) -> String {
+        format_signature(&self.name, &self.inputs)
+    }
+}
+
+impl Serialize for AbiEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        // `type` sorts after every other key
+        #[derive(Serialize)]
+        struct Tagged<'a, T> {
+            #[serde(flatten)]
+            entry: &'a T,
+            r#type: &'static str,
+        }
+        let r#type = self.kind();
+        match self {
+            AbiEntry::Function(entry) => Tagged { entry, r#type }.serialize(serializer),
+            AbiEntry::Constructor(entry) => Tagged { entry, r#type }.serialize(serializer),
+            AbiEntry::Fallback(entry) | AbiEntry::Receive(entry) => {
+                Tagged { entry, r#type }.serialize(serializer)
+            }
+            AbiEntry::Event(entry) => Tagged { entry, r#type }.serialize(serializer),
+            AbiEntry::Error(entry) => Tagged { entry, r#type }.serialize(serializer),
+        }
+    }
+}
+
+impl Param {
+    pub fn new(name: impl Into<String>, ty: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ty: ty.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl StateMutability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StateMutability::Pure => "pure",
+            StateMutability::View => "view",
+            StateMutability::Nonpayable => "nonpayable",
+            StateMutability::Payable => "payable",
+        }
+    }
+
+    /// Whether calls can't change state
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, StateMutability::Pure | StateMutability::View)
+    }
+}
+
+/// How Rust names appear in the generated ABI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum AbiNaming {
+    /// Names as written in Rust, matching the selectors the router dispatches on
+    #[default]
+    Preserve,
+    /// camelCase function, parameter and struct field names as Solidity callers expect
+    ///
+    /// A renamed function keeps the selector of its Rust name, which the
+    /// router dispatches on; metadata.json lists it under
+    /// `dispatched_selectors`. Event and error names are kept, their topics
+    /// and selectors are what the contract emits.
+    CamelCase,
+}
+
+impl AbiNaming {
+    pub fn is_preserve(&self) -> bool {
+        *self == AbiNaming::Preserve
+    }
+}
+
+impl FromStr for AbiNaming {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(AbiNaming::Preserve),
+            "camel_case" | "camel-case" | "camelCase" => Ok(AbiNaming::CamelCase),
+            _ => Err(format!(
+                "unknown ABI naming '{}', expected preserve or camel-case",
+                s
+            )),
+        }
+    }
+}
+
+/// Generates the combined ABI of all parsed routers
+///
+/// Functions exported by several facets appear once. The parameters of the
+/// declared constructor, if it takes any, become the constructor entry, listed
+/// first; custom errors follow the functions.
+pub fn generate(routers: &[RouterFacet], declarations: &Declarations) -> Result<Abi> {
+    let facets = routers
+        .iter()
+        .map(|facet| Ok((facet.name.as_str(), generate_router(&facet.router)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let constructor = match &declarations.deploy {
+        Some(deploy) => generate_constructor(deploy)?,
+        None => None,
+    };
+    let errors = generate_errors(&declarations.errors)?;
+    Ok(constructor
+        .into_iter()
+        .chain(merge(facets)?)
+        .chain(errors)
+        .collect())
+}
+
+/// Constructor entry taking the parameters of the `#[constructor]` method
+pub fn generate_constructor(deploy: &Router) -> Result<Option<AbiEntry>> {
+    Ok(generate_router(deploy)?
+        .into_iter()
+        .find_map(|entry| match entry {
+            AbiEntry::Function(function) => Some(constructor_of(function)),
+            _ => None,
+        }))
+}
+
+/// The constructor is parsed as a router function; its mutability follows the receiver, not the
+/// constructor's
+fn constructor_of(deploy: AbiFunction) -> AbiEntry {
+    AbiEntry::Constructor(AbiConstructor {
+        inputs: deploy.inputs,
+        state_mutability: StateMutability::Nonpayable,
+    })
+}
+
+/// Merge the ABIs of several facets, keeping one of entries with the same signature
+///
+/// Fails if entries share a signature but differ otherwise (outputs, mutability),
+/// since the ABI can describe only one of them.
+fn merge<'a>(facets: impl IntoIterator<Item = (&'a str, Abi)>) -> Result<Abi> {
+    let mut entries: Abi = Vec::new();
+    let mut seen: HashMap<String, (&str, usize)> = HashMap::new();
+
+    for (facet, abi) in facets {
+        for entry in abi {
+            let key = signature(&entry).unwrap_or_else(|| entry.kind().to_string());
+            match seen.get(&key) {
+                Some(&(first, index)) if entries[index] != entry => {
+                    return Err(BuilderError::InvalidContract {
+                        reason: format!(
+                            "{} of {} differs from the one of {}, and the ABI can describe only \
+                             one of them",
+                            key, facet, first
+                        ),
+                    }
+                    .into());
+                }
+                Some(_) => {}
+                None => {
+                    seen.insert(key, (facet, entries.len()));
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Error entries of custom errors, whose fields the SDK describes as method parameters
+pub fn generate_errors(errors: &[CustomError]) -> Result<Abi> {
+    errors
+        .iter()
+        .map(|error| {
+            let mut inputs = generate_router(&error.router)?
+                .into_iter()
+                .find_map(|entry| match entry {
+                    AbiEntry::Function(function) => Some(function.inputs),
+                    _ => None,
+                })
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "The SDK could not describe the fields of error {}",
+                        error.name
+                    )
+                })?;
+            if error.unnamed {
+                inputs.iter_mut().for_each(|param| param.name.clear());
+            }
+            Ok(AbiEntry::Error(AbiError {
+                inputs,
+                name: error.name.clone(),
+            }))
+        })
+        .collect()
+}
+
+/// ABI of the routers in `main_source` as `compile` writes it, without compiling
+///
+/// Fails when router functions share a selector, as `compile` does by default.
+pub fn generate_from_source(main_source: &Path, naming: AbiNaming) -> Result<Abi> {
+    let routers = parse_routers(main_source)?;
+    let collisions = router_collisions(&routers)?;
+    if !collisions.is_empty() {
+        eyre::bail!(
+            "Selector collision, calls would be misrouted: {}; rename the functions",
+            collisions.join("; ")
+        );
     }
+    let declarations = Declarations {
+        deploy: parse_deploy(main_source)?,
+        errors: parse_errors(main_source)?,
+    };
+    let mut abi = apply_naming(&generate(&routers, &declarations)?, naming);
+    Sanitizer::new(&abi).apply(&mut abi)?;
+    Ok(abi)
+}
 
-    // Take first router for now
-    let router = &routers[0];
+/// Generates ABI of a single router
+pub fn generate_router(router: &Router) -> Result<Abi> {
     let mut entries = Vec::new();
 
     for method in router.available_methods() {
         if let Ok(func_abi) = method.parsed_signature().function_abi() {
             if let Ok(json) = func_abi.to_json_value() {
-                entries.push(json);
+                entries.push(
+                    serde_json::from_value(json).context("Unexpected ABI entry from the SDK")?,
+                );
             }
         }
     }
@@ -25,13 +424,692 @@ pub fn generate(routers: &[fluentbase_sdk_derive_core::router::Router]) -> Resul
     Ok(entries)
 }
 
+/// Rename functions, parameters and struct fields according to `naming`
+///
+/// Event and error names are kept: their topics and selectors are what the
+/// contract emits.
+pub fn apply_naming(abi: &Abi, naming: AbiNaming) -> Abi {
+    let mut abi = abi.clone();
+    if naming == AbiNaming::CamelCase {
+        abi.iter_mut().for_each(rename_camel_case);
+    }
+    abi
+}
+
+fn rename_camel_case(entry: &mut AbiEntry) {
+    if let AbiEntry::Function(function) = entry {
+        function.name = camel_case(&function.name);
+    }
+    entry.params_mut().for_each(rename_param_camel_case);
+}
+
+/// Selectors the router dispatches renamed functions on, by their signature in `renamed`
+///
+/// Only functions whose signature differs from `original` are listed; both
+/// ABIs list the same functions in the same order.
+#[cfg(feature = "build")]
+pub fn dispatched_selectors(original: &Abi, renamed: &Abi) -> BTreeMap<String, String> {
+    let functions = |abi: &'_ Abi| {
+        abi.iter()
+            .filter_map(AbiEntry::as_function)
+            .map(AbiFunction::signature)
+            .collect::<Vec<_>>()
+    };
+    functions(original)
+        .into_iter()
+        .zip(functions(renamed))
+        .filter(|(original, renamed)| original != renamed)
+        .map(|(original, renamed)| (renamed, selector(&original)))
+        .collect()
+}
+
+fn rename_param_camel_case(param: &mut Param) {
+    param.name = camel_case(&param.name);
+    param
+        .components
+        .iter_mut()
+        .for_each(rename_param_camel_case);
+}
+
+/// `transfer_from` -> `transferFrom`; names without underscores and leading underscores are kept
+fn camel_case(name: &str) -> String {
+    let trimmed = name.trim_start_matches('_');
+    if !trimmed.contains('_') {
+        return name.to_string();
+    }
+    let prefix = &name[..name.len() - trimmed.len()];
+    format!("{}{}", prefix, trimmed.to_case(Case::Camel))
+}
+
+/// Canonical type of an ABI parameter as used in signatures, with tuples expanded
+///
+/// `{"type": "tuple[]", "components": [address, bytes]}` becomes `(address,bytes)[]`.
+pub fn canonical_type(param: &Param) -> String {
+    match param.ty.strip_prefix("tuple") {
+        Some(array_suffix) => {
+            let types = param
+                .components
+                .iter()
+                .map(canonical_type)
+                .collect::<Vec<_>>();
+            format!("({}){}", types.join(","), array_suffix)
+        }
+        None => param.ty.clone(),
+    }
+}
+
+/// Canonical signature of a function, event or error ABI entry, e.g. `transfer(address,uint256)`
+pub fn signature(entry: &AbiEntry) -> Option<String> {
+    Some(format_signature(entry.name()?, entry.inputs()))
+}
+
+fn format_signature(name: &str, inputs: &[Param]) -> String {
+    let types = inputs.iter().map(canonical_type).collect::<Vec<_>>();
+    format!("{}({})", name, types.join(","))
+}
+
+/// 4-byte selector of a canonical signature as a 0x-prefixed hex string
+pub fn selector(signature: &str) -> String {
+    let hash = Keccak256::digest(signature.as_bytes());
+    format!("0x{}", hex::encode(&hash[..4]))
+}
+
+/// Functions sharing a 4-byte selector across `facets`, as `Facet.signature` by selector
+///
+/// Only different signatures collide; the same signature in two facets is one function of the
+/// merged ABI.
+pub fn selector_collisions<'a>(
+    facets: impl IntoIterator<Item = (&'a str, &'a Abi)>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut functions: BTreeMap<String, Vec<(&str, String)>> = BTreeMap::new();
+    for (facet, abi) in facets {
+        for signature in abi
+            .iter()
+            .filter_map(AbiEntry::as_function)
+            .map(AbiFunction::signature)
+        {
+            functions
+                .entry(selector(&signature))
+                .or_default()
+                .push((facet, signature));
+        }
+    }
+    functions
+        .into_iter()
+        .filter(|(_, functions)| {
+            let signatures = functions.iter().map(|(_, signature)| signature);
+            signatures.collect::<BTreeSet<_>>().len() > 1
+        })
+        .map(|(selector, functions)| {
+            let functions = functions
+                .into_iter()
+                .map(|(facet, signature)| format!("{}.{}", facet, signature))
+                .collect();
+            (selector, functions)
+        })
+        .collect()
+}
+
+/// Selector collisions between the functions of `routers`, one description per selector
+pub(crate) fn router_collisions(routers: &[RouterFacet]) -> Result<Vec<String>> {
+    let router_abis = routers
+        .iter()
+        .map(|facet| Ok((facet.name.as_str(), generate_router(&facet.router)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let collisions = selector_collisions(router_abis.iter().map(|(name, abi)| (*name, abi)));
+    Ok(collisions
+        .iter()
+        .map(|(selector, functions)| format!("{} is shared by {}", selector, functions.join(", ")))
+        .collect())
+}
+
+/// topic0 of a canonical event signature (full keccak256) as a 0x-prefixed hex string
+pub fn topic(signature: &str) -> String {
+    format!("0x{}", hex::encode(Keccak256::digest(signature.as_bytes())))
+}
+
+/// Changes between two versions of an ABI
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+pub struct AbiDiff {
+    pub changes: Vec<AbiChange>,
+}
+
+/// An added, removed or changed ABI entry
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+pub struct AbiChange {
+    pub change: ChangeKind,
+    /// `type` of the entry, e.g. `function`
+    pub kind: String,
+    /// Name of a function, event or error; the kind for other entries
+    pub name: String,
+    /// Declaration before the change, e.g. `function balanceOf(address owner) view returns
+    /// (uint256)`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    /// Declaration after the change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+    /// Selector, or topic0 of an event, before the change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_selector: Option<String>,
+    /// Selector, or topic0 of an event, after the change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_selector: Option<String>,
+    /// What changed, e.g. `outputs changed` or `view → nonpayable`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+    /// Whether existing callers or log decoders stop working
+    pub breaking: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl AbiDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Whether any change breaks existing callers or log decoders
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(|change| change.breaking)
+    }
+}
+
+impl AbiChange {
+    fn new(change: ChangeKind, old: Option<&AbiEntry>, new: Option<&AbiEntry>) -> Self {
+        let entry = new.or(old).expect("an entry on either side");
+        Self {
+            change,
+            kind: entry.kind().to_string(),
+            name: entry.name().unwrap_or(entry.kind()).to_string(),
+            old: old.map(super::human_readable::format_entry),
+            new: new.map(super::human_readable::format_entry),
+            old_selector: old.and_then(entry_selector),
+            new_selector: new.and_then(entry_selector),
+            details: Vec::new(),
+            breaking: change == ChangeKind::Removed && breaks_callers(entry),
+        }
+    }
+}
+
+/// Compare two versions of an ABI
+///
+/// Entries are matched by signature. An entry whose signature changed is
+/// matched by name when it is the only one of its name on both sides, and
+/// reported as changed. Removing a function, event, fallback or receive
+/// function breaks callers, and so do changes to its selector, outputs or
+/// indexed parameters, and stricter mutability. Errors and the constructor
+/// are reported but never breaking.
+pub fn diff(old: &Abi, new: &Abi) -> AbiDiff {
+    let key = |entry: &AbiEntry| signature(entry).unwrap_or_else(|| entry.kind().to_string());
+    let old_entries: BTreeMap<String, &AbiEntry> =
+        old.iter().map(|entry| (key(entry), entry)).collect();
+    let new_entries: BTreeMap<String, &AbiEntry> =
+        new.iter().map(|entry| (key(entry), entry)).collect();
+    let removed: Vec<&AbiEntry> = old_entries
+        .iter()
+        .filter(|(key, _)| !new_entries.contains_key(*key))
+        .map(|(_, entry)| *entry)
+        .collect();
+    let added: Vec<&AbiEntry> = new_entries
+        .iter()
+        .filter(|(key, _)| !old_entries.contains_key(*key))
+        .map(|(_, entry)| *entry)
+        .collect();
+
+    let mut changes = Vec::new();
+    for (key, old_entry) in &old_entries {
+        if let Some(new_entry) = new_entries.get(key) {
+            changes.extend(changed(old_entry, new_entry, Vec::new()));
+        }
+    }
+
+    // A changed signature is a removal and an addition, unless the name identifies the entry
+    let mut renamed = Vec::new();
+    for old_entry in &removed {
+        let same_name = |entry: &&&AbiEntry| {
+            entry.kind() == old_entry.kind() && entry.name() == old_entry.name()
+        };
+        let removed_named: Vec<_> = removed.iter().filter(same_name).collect();
+        let added_named: Vec<_> = added.iter().filter(same_name).collect();
+        match (removed_named.as_slice(), added_named.as_slice()) {
+            ([_], [new_entry]) => {
+                let detail = if old_entry.kind() == "event" {
+                    "topic changed"
+                } else {
+                    "selector changed"
+                };
+                let signature_change = (detail.to_string(), breaks_callers(old_entry));
+                changes.extend(changed(old_entry, new_entry, vec![signature_change]));
+                renamed.push(**new_entry);
+            }
+            _ => changes.push(AbiChange::new(ChangeKind::Removed, Some(old_entry), None)),
+        }
+    }
+    for new_entry in added {
+        if !renamed.iter().any(|entry| std::ptr::eq(*entry, new_entry)) {
+            changes.push(AbiChange::new(ChangeKind::Added, None, Some(new_entry)));
+        }
+    }
+
+    changes.sort_by(|a, b| (&a.kind, &a.name).cmp(&(&b.kind, &b.name)));
+    AbiDiff { changes }
+}
+
+/// The change between two versions of an entry, if any, starting with `details`
+fn changed(old: &AbiEntry, new: &AbiEntry, mut details: Vec<(String, bool)>) -> Option<AbiChange> {
+    let types = |params: &[Param]| params.iter().map(canonical_type).collect::<Vec<_>>();
+    let names = |params: &[Param]| {
+        params
+            .iter()
+            .map(|param| param.name.clone())
+            .collect::<Vec<_>>()
+    };
+
+    if types(old.inputs()) != types(new.inputs()) {
+        // Functions, events and errors are matched by these types unless their signature changed
+        if details.is_empty() {
+            details.push(("inputs changed".to_string(), true));
+        }
+    } else if names(old.inputs()) != names(new.inputs())
+        || names(old.outputs()) != names(new.outputs())
+    {
+        details.push(("parameter names changed".to_string(), false));
+    }
+    if types(old.outputs()) != types(new.outputs()) {
+        details.push(("outputs changed".to_string(), true));
+    }
+    if let (Some(old), Some(new)) = (old.state_mutability(), new.state_mutability()) {
+        if old != new {
+            let stricter = (old.is_read_only() && !new.is_read_only())
+                || (old == StateMutability::Payable && new != StateMutability::Payable);
+            details.push((format!("{} → {}", old.as_str(), new.as_str()), stricter));
+        }
+    }
+    if let (AbiEntry::Event(old), AbiEntry::Event(new)) = (old, new) {
+        let indexed = |event: &AbiEvent| {
+            event
+                .inputs
+                .iter()
+                .map(|param| param.indexed == Some(true))
+                .collect::<Vec<_>>()
+        };
+        if indexed(old) != indexed(new) {
+            details.push(("indexed parameters changed".to_string(), true));
+        }
+        if old.anonymous != new.anonymous {
+            details.push((
+                if new.anonymous {
+                    "now anonymous"
+                } else {
+                    "no longer anonymous"
+                }
+                .to_string(),
+                true,
+            ));
+        }
+    }
+
+    if details.is_empty() {
+        return None;
+    }
+    let mut change = AbiChange::new(ChangeKind::Changed, Some(old), Some(new));
+    change.breaking = breaks_callers(old) && details.iter().any(|(_, breaking)| *breaking);
+    change.details = details.into_iter().map(|(detail, _)| detail).collect();
+    Some(change)
+}
+
+/// Whether removing or changing the entry affects deployed callers; errors and the constructor
+/// don't
+fn breaks_callers(entry: &AbiEntry) -> bool {
+    !matches!(entry, AbiEntry::Error(_) | AbiEntry::Constructor(_))
+}
+
+/// Selector of a function or error, topic0 of an event
+fn entry_selector(entry: &AbiEntry) -> Option<String> {
+    let signature = signature(entry)?;
+    Some(match entry {
+        AbiEntry::Event(_) => topic(&signature),
+        _ => selector(&signature),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::{json, Value};
+
+    fn entry(value: Value) -> AbiEntry {
+        serde_json::from_value(value).unwrap()
+    }
 
     #[test]
     fn test_empty_routers() {
-        let abi = generate(&[]).unwrap();
+        let abi = generate(&[], &Declarations::default()).unwrap();
         assert!(abi.is_empty());
     }
+
+    #[test]
+    fn test_deploy_constructor() {
+        let AbiEntry::Function(deploy) = entry(json!({
+            "name": "deploy",
+            "type": "function",
+            "inputs": [{"name": "owner", "type": "address"}, {"name": "supply", "type": "uint256"}],
+            "outputs": [],
+            "stateMutability": "view"
+        })) else {
+            panic!("expected a function");
+        };
+        let constructor = constructor_of(deploy);
+        assert_eq!(constructor.kind(), "constructor");
+        assert_eq!(
+            constructor.state_mutability(),
+            Some(StateMutability::Nonpayable)
+        );
+        assert_eq!(
+            constructor
+                .inputs()
+                .iter()
+                .map(canonical_type)
+                .collect::<Vec<_>>(),
+            ["address", "uint256"]
+        );
+        assert_eq!(signature(&constructor), None);
+    }
+
+    #[test]
+    fn test_merge_facets() {
+        let balance = |output: &str| {
+            entry(json!({
+                "name": "balance",
+                "type": "function",
+                "inputs": [],
+                "outputs": [{"name": "", "type": output}]
+            }))
+        };
+        let transfer = entry(json!({
+            "name": "transfer",
+            "type": "function",
+            "inputs": [{"name": "to", "type": "address"}],
+            "outputs": []
+        }));
+        let admin =
+            entry(json!({"name": "pause", "type": "function", "inputs": [], "outputs": []}));
+
+        let abi = merge([
+            ("Token", vec![balance("uint256"), transfer.clone()]),
+            ("Admin", vec![admin.clone(), transfer.clone()]),
+        ])
+        .unwrap();
+        assert_eq!(abi, vec![balance("uint256"), transfer, admin]);
+    }
+
+    #[test]
+    fn test_merge_conflicting_facets() {
+        let balance = |output: &str| {
+            entry(json!({
+                "name": "balance",
+                "type": "function",
+                "inputs": [],
+                "outputs": [{"name": "", "type": output}]
+            }))
+        };
+
+        let err = merge([
+            ("Token", vec![balance("uint256")]),
+            ("Legacy", vec![balance("uint64")]),
+        ])
+        .unwrap_err();
+        assert!(matches!(
+            BuilderError::find(&err),
+            Some(BuilderError::InvalidContract { .. })
+        ));
+        assert!(err
+            .to_string()
+            .contains("balance() of Legacy differs from the one of Token"));
+    }
+
+    #[test]
+    fn test_conflicting_routers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("lib.rs");
+        std::fs::write(
+            &source,
+            r#"
+            use fluentbase_sdk::{derive::router, SharedAPI, U256};
+
+            pub trait Token {
+                fn balance(&self) -> U256;
+            }
+
+            pub trait Legacy {
+                fn balance(&self) -> u64;
+            }
+
+            pub struct Contract<SDK> {
+                sdk: SDK,
+            }
+
+            #[router]
+            impl<SDK: SharedAPI> Token for Contract<SDK> {
+                fn balance(&self) -> U256 {
+                    U256::ZERO
+                }
+            }
+
+            #[router]
+            impl<SDK: SharedAPI> Legacy for Contract<SDK> {
+                fn balance(&self) -> u64 {
+                    0
+                }
+            }
+        "#,
+        )
+        .unwrap();
+
+        let err = generate_from_source(&source, AbiNaming::Preserve).unwrap_err();
+        assert!(matches!(
+            BuilderError::find(&err),
+            Some(BuilderError::InvalidContract { .. })
+        ));
+    }
+
+    #[test]
+    fn test_camel_case_naming() {
+        let abi = vec![entry(json!({
+            "name": "transfer_from",
+            "type": "function",
+            "inputs": [
+                {"name": "_from", "type": "address"},
+                {
+                    "name": "order",
+                    "type": "tuple",
+                    "components": [{"name": "min_amount", "type": "uint256"}]
+                }
+            ],
+            "outputs": [{"name": "balanceOf", "type": "uint256"}]
+        }))];
+
+        assert_eq!(apply_naming(&abi, AbiNaming::Preserve), abi);
+        assert!(dispatched_selectors(&abi, &abi).is_empty());
+        let renamed = apply_naming(&abi, AbiNaming::CamelCase);
+        assert_eq!(
+            signature(&renamed[0]).unwrap(),
+            "transferFrom(address,(uint256))"
+        );
+        // The router still dispatches on the selector of the Rust name
+        assert_eq!(
+            dispatched_selectors(&abi, &renamed),
+            BTreeMap::from([(
+                "transferFrom(address,(uint256))".to_string(),
+                selector("transfer_from(address,(uint256))")
+            )])
+        );
+        assert_eq!(renamed[0].inputs()[0].name, "_from");
+        assert_eq!(renamed[0].inputs()[1].components[0].name, "minAmount");
+        assert_eq!(renamed[0].outputs()[0].name, "balanceOf");
+    }
+
+    #[test]
+    fn test_selector_collisions() {
+        let function = |name: &str, input: &str| {
+            entry(json!({
+                "name": name,
+                "type": "function",
+                "inputs": [{"name": "x", "type": input}],
+                "outputs": []
+            }))
+        };
+        let token = vec![function("burn", "uint256"), function("transfer", "uint256")];
+        let storage = vec![function("collate_propagate_storage", "bytes16")];
+        let admin = vec![function("transfer", "uint256")];
+
+        assert!(selector_collisions([("Token", &token)]).is_empty());
+        let collisions =
+            selector_collisions([("Token", &token), ("Storage", &storage), ("Admin", &admin)]);
+        // The same signature in two facets is not a collision
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(
+            collisions[&selector("burn(uint256)")],
+            [
+                "Token.burn(uint256)",
+                "Storage.collate_propagate_storage(bytes16)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_canonical_signature() {
+        let entry = entry(json!({
+            "name": "aggregate3",
+            "type": "function",
+            "inputs": [{
+                "name": "calls",
+                "type": "tuple[]",
+                "components": [
+                    {"name": "target", "type": "address"},
+                    {"name": "allowFailure", "type": "bool"},
+                    {"name": "callData", "type": "bytes"}
+                ]
+            }]
+        }));
+
+        let signature = signature(&entry).unwrap();
+        assert_eq!(signature, "aggregate3((address,bool,bytes)[])");
+        assert_eq!(selector(&signature), "0x82ad56cb");
+        assert_eq!(selector("transfer(address,uint256)"), "0xa9059cbb");
+        assert_eq!(
+            topic("Transfer(address,address,uint256)"),
+            "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+    }
+
+    #[test]
+    fn test_serialized_layout() {
+        let json = concat!(
+            r#"[{"inputs":[{"components":[{"internalType":"address","name":"maker","#,
+            r#""type":"address"}],"internalType":"struct Order","name":"order","type":"tuple"}],"#,
+            r#""name":"fill","outputs":[],"stateMutability":"payable","type":"function"},"#,
+            r#"{"anonymous":false,"inputs":[{"indexed":true,"name":"from","type":"address"}],"#,
+            r#""name":"Filled","type":"event"},{"stateMutability":"payable","type":"receive"}]"#,
+        );
+        let abi: Abi = serde_json::from_str(json).unwrap();
+        assert_eq!(abi[0].state_mutability(), Some(StateMutability::Payable));
+        assert_eq!(abi[1].inputs()[0].indexed, Some(true));
+        assert_eq!(abi[2].kind(), "receive");
+        assert_eq!(serde_json::to_string(&abi).unwrap(), json);
+
+        // Fields left out by other tools take their defaults
+        let entry = entry(json!({"type": "function", "name": "ping"}));
+        assert_eq!(entry.state_mutability(), Some(StateMutability::Nonpayable));
+        assert!(serde_json::from_value::<AbiEntry>(
+            json!({"type": "modifier", "name": "onlyOwner"})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let parse = |declarations: &[&str]| {
+            super::super::human_readable::parse(&declarations.join("\n")).unwrap()
+        };
+        let old = parse(&[
+            "constructor(uint256 supply)",
+            "function transfer(address to, uint256 amount) returns (bool)",
+            "function balanceOf(address owner) view returns (uint256)",
+            "function approve(address spender, uint256 amount) returns (bool)",
+            "function burn(uint256 amount)",
+            "function deposit() payable",
+            "event Transfer(address indexed from, address indexed to, uint256 value)",
+            "error InsufficientBalance(uint256 available)",
+        ]);
+        assert!(diff(&old, &old).is_empty());
+
+        let new = parse(&[
+            "constructor(uint256 supply, address owner)",
+            "function transfer(address to, uint256 amount) returns (bool)",
+            "function balanceOf(address account) view returns (uint256)",
+            "function approve(address spender, uint256 amount, uint64 deadline) returns (bool)",
+            "function mint(address to, uint256 amount)",
+            "function deposit()",
+            "event Transfer(address indexed from, address to, uint256 value)",
+        ]);
+        let changes = diff(&old, &new);
+        assert!(changes.is_breaking());
+        let summary: Vec<(ChangeKind, &str, &str, bool)> = changes
+            .changes
+            .iter()
+            .map(|c| (c.change, c.kind.as_str(), c.name.as_str(), c.breaking))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (ChangeKind::Changed, "constructor", "constructor", false),
+                (ChangeKind::Removed, "error", "InsufficientBalance", false),
+                (ChangeKind::Changed, "event", "Transfer", true),
+                (ChangeKind::Changed, "function", "approve", true),
+                (ChangeKind::Changed, "function", "balanceOf", false),
+                (ChangeKind::Removed, "function", "burn", true),
+                (ChangeKind::Changed, "function", "deposit", true),
+                (ChangeKind::Added, "function", "mint", false),
+            ]
+        );
+
+        let change = |name: &str| changes.changes.iter().find(|c| c.name == name).unwrap();
+        assert_eq!(change("constructor").details, ["inputs changed"]);
+        assert_eq!(change("Transfer").details, ["indexed parameters changed"]);
+        assert_eq!(change("approve").details, ["selector changed"]);
+        assert_eq!(
+            change("approve").old_selector.as_deref(),
+            Some("0x095ea7b3")
+        );
+        assert_ne!(
+            change("approve").new_selector,
+            change("approve").old_selector
+        );
+        assert_eq!(change("balanceOf").details, ["parameter names changed"]);
+        assert_eq!(change("deposit").details, ["payable → nonpayable"]);
+        assert_eq!(
+            change("burn").old.as_deref(),
+            Some("function burn(uint256 amount)")
+        );
+        assert_eq!(change("mint").new_selector.as_deref(), Some("0x40c10f19"));
+
+        // Relaxing mutability keeps callers working
+        let relaxed = diff(
+            &parse(&["function deposit()"]),
+            &parse(&["function deposit() payable"]),
+        );
+        assert!(!relaxed.is_breaking());
+        assert_eq!(relaxed.changes[0].details, ["nonpayable → payable"]);
+    }
 }
@@ -0,0 +1,270 @@
+//! Rust SDK callers for external contracts, generated from their ABI
+//!
+//! The inverse of interface generation: functions of a foreign ABI become a
+//! trait for the SDK's `#[client]` macro, which encodes the calldata and
+//! decodes the return values. Each function keeps its selector through
+//! `#[function_id]`. Events and errors are not bound.
+
+use super::{
+    abi::{Abi, AbiEntry},
+    interface::{return_params, SolType},
+};
+use convert_case::{Case, Casing};
+use eyre::Result;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Rust keywords that can't be used as parameter or function names
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+    "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct", "super",
+    "trait", "true", "try", "type", "typeof", "unsafe", "use", "where", "while", "yield",
+];
+
+/// Generates a `#[client]` trait named `I<ContractName>` for the functions of `abi`
+pub fn generate_rust_client(contract_name: &str, abi: &Abi) -> Result<String> {
+    let mut types = TypeMapper::default();
+    let mut methods = Vec::new();
+    let mut names: HashMap<String, usize> = HashMap::new();
+
+    for function in abi.iter().filter_map(AbiEntry::as_function) {
+        let signature = function.signature();
+        let base_name = rust_identifier(&function.name.to_case(Case::Snake));
+        // Overloads share a name in Solidity but not in a Rust trait
+        let count = names.entry(base_name.clone()).or_insert(0);
+        *count += 1;
+        let name = if *count == 1 {
+            base_name
+        } else {
+            format!("{}_{}", base_name, count)
+        };
+
+        let receiver = if function.state_mutability.is_read_only() {
+            "&self"
+        } else {
+            "&mut self"
+        };
+        let mut params = vec![receiver.to_string()];
+        for (i, input) in function.inputs.iter().enumerate() {
+            let param_name = match input.name.as_str() {
+                "" => format!("arg{}", i),
+                name => rust_identifier(&name.to_case(Case::Snake)),
+            };
+            params.push(format!(
+                "{}: {}",
+                param_name,
+                types.rust_type(&SolType::from_param(input))
+            ));
+        }
+
+        let outputs: Vec<String> = return_params(function)
+            .iter()
+            .map(|output| types.rust_type(&SolType::from_param(output)))
+            .collect();
+        let returns = match outputs.as_slice() {
+            [] => String::new(),
+            [single] => format!(" -> {}", single),
+            several => format!(" -> ({})", several.join(", ")),
+        };
+
+        methods.push(format!(
+            "    #[function_id(\"{}\")]\n    fn {}({}){};",
+            signature,
+            name,
+            params.join(", "),
+            returns
+        ));
+    }
+
+    let mut imports: Vec<&str> = vec!["derive::{client, function_id}"];
+    if !types.structs.is_empty() {
+        imports.insert(0, "codec::Codec");
+    }
+    imports.extend(types.imports.iter().copied());
+
+    let mut code = String::new();
+    code.push_str(&format!(
+        "// Auto-generated from the ABI of {} by fluent-builder bindgen\n",
+        contract_name
+    ));
+    code.push_str(&format!(
+        "use fluentbase_sdk::{{{}}};\n\n",
+        imports.join(", ")
+    ));
+    for definition in &types.structs {
+        code.push_str(definition);
+        code.push_str("\n\n");
+    }
+    code.push_str("#[client(mode = \"solidity\")]\n");
+    code.push_str(&format!(
+        "pub trait I{} {{\n",
+        contract_name.to_case(Case::Pascal)
+    ));
+    code.push_str(&methods.join("\n\n"));
+    if !methods.is_empty() {
+        code.push('\n');
+    }
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Maps ABI types to SDK types, collecting struct definitions and imports
+#[derive(Default)]
+struct TypeMapper {
+    structs: Vec<String>,
+    seen_structs: HashSet<String>,
+    imports: BTreeSet<&'static str>,
+}
+
+impl TypeMapper {
+    fn rust_type(&mut self, ty: &SolType) -> String {
+        match ty {
+            SolType::Elementary(name) => self.elementary(name),
+            SolType::Struct { name, fields } => {
+                let name = name.to_case(Case::Pascal);
+                if self.seen_structs.insert(name.clone()) {
+                    let body: Vec<String> = fields
+                        .iter()
+                        .map(|(field, field_ty)| {
+                            format!(
+                                "    pub {}: {},",
+                                rust_identifier(&field.to_case(Case::Snake)),
+                                self.rust_type(field_ty)
+                            )
+                        })
+                        .collect();
+                    self.structs.push(format!(
+                        "#[derive(Codec, Debug, Clone, Default, PartialEq)]\npub struct {} {{\n{}\n\
+                         }}",
+                        name,
+                        body.join("\n")
+                    ));
+                }
+                name
+            }
+            SolType::Tuple(components) => {
+                let types: Vec<String> = components.iter().map(|c| self.rust_type(c)).collect();
+                format!("({})", types.join(", "))
+            }
+            SolType::Array {
+                element,
+                len: Some(len),
+            } => format!("[{}; {}]", self.rust_type(element), len),
+            SolType::Array { element, len: None } => format!("Vec<{}>", self.rust_type(element)),
+        }
+    }
+
+    fn elementary(&mut self, name: &str) -> String {
+        let mut import = |ty: &'static str| {
+            self.imports.insert(ty);
+            ty.to_string()
+        };
+        // Integers are widened to the next Rust type; the selector keeps the declared width
+        let bits = |prefix: &str| {
+            name.strip_prefix(prefix)
+                .map(|n| n.parse::<usize>().unwrap_or(256))
+        };
+        match name {
+            "address" => import("Address"),
+            "bool" => "bool".to_string(),
+            "string" => "String".to_string(),
+            "bytes" => import("Bytes"),
+            "function" => format!("{}<24>", import("FixedBytes")),
+            _ if name.starts_with("bytes") => {
+                format!("{}<{}>", import("FixedBytes"), &name["bytes".len()..])
+            }
+            _ if name.starts_with("uint") => match bits("uint").unwrap_or(256) {
+                n @ (8 | 16 | 32 | 64 | 128) => format!("u{}", n),
+                n if n < 128 => format!("u{}", n.next_power_of_two().max(8)),
+                _ => import("U256"),
+            },
+            _ if name.starts_with("int") => match bits("int").unwrap_or(256) {
+                n @ (8 | 16 | 32 | 64 | 128) => format!("i{}", n),
+                n if n < 128 => format!("i{}", n.next_power_of_two().max(8)),
+                _ => import("I256"),
+            },
+            // Unknown types are left for the compiler to report
+            other => other.to_string(),
+        }
+    }
+}
+
+/// `type` -> `r#type`, `self` -> `self_`
+fn rust_identifier(name: &str) -> String {
+    match name {
+        "self" | "super" | "crate" | "Self" => format!("{}_", name),
+        _ if RUST_KEYWORDS.contains(&name) => format!("r#{}", name),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rust_client() {
+        let abi: Abi = serde_json::from_value(json!([
+            {"type": "function", "name": "balanceOf", "stateMutability": "view",
+                "inputs": [{"name": "owner", "type": "address"}],
+                "outputs": [{"name": "", "type": "uint256"}]},
+            {"type": "function", "name": "transfer", "stateMutability": "nonpayable",
+                "inputs": [
+                    {"name": "to", "type": "address"},
+                    {"name": "amount", "type": "uint256"}
+                ],
+                "outputs": [{"name": "", "type": "bool"}]},
+            {"type": "function", "name": "transfer", "stateMutability": "nonpayable",
+                "inputs": [
+                    {"name": "to", "type": "address"},
+                    {"name": "amount", "type": "uint256"},
+                    {"name": "", "type": "bytes"}
+                ],
+                "outputs": []},
+            {"type": "function", "name": "fill", "stateMutability": "payable",
+                "inputs": [{"name": "orders", "type": "tuple[]",
+                    "internalType": "struct Exchange.Order[]", "components": [
+                    {"name": "maker", "type": "address"},
+                    {"name": "expiresAt", "type": "uint40"},
+                    {"name": "salt", "type": "bytes32"}
+                ]}, {"name": "type", "type": "uint8[2]"}],
+                "outputs": [
+                    {"name": "filled", "type": "uint256"},
+                    {"name": "change", "type": "int256"}
+                ]},
+            {"type": "event", "name": "Transfer", "inputs": []},
+        ]))
+        .unwrap();
+
+        let code = generate_rust_client("exchange", &abi).unwrap();
+        assert_eq!(
+            code,
+            r#"// Auto-generated from the ABI of exchange by fluent-builder bindgen
+use fluentbase_sdk::{codec::Codec, derive::{client, function_id}, Address, Bytes, FixedBytes, I256, U256};
+
+#[derive(Codec, Debug, Clone, Default, PartialEq)]
+pub struct Order {
+    pub maker: Address,
+    pub expires_at: u64,
+    pub salt: FixedBytes<32>,
+}
+
+#[client(mode = "solidity")]
+pub trait IExchange {
+    #[function_id("balanceOf(address)")]
+    fn balance_of(&self, owner: Address) -> U256;
+
+    #[function_id("transfer(address,uint256)")]
+    fn transfer(&mut self, to: Address, amount: U256) -> bool;
+
+    #[function_id("transfer(address,uint256,bytes)")]
+    fn transfer_2(&mut self, to: Address, amount: U256, arg2: Bytes);
+
+    #[function_id("fill((address,uint40,bytes32)[],uint8[2])")]
+    fn fill(&mut self, orders: Vec<Order>, r#type: [u8; 2]) -> (U256, I256);
+}
+"#
+        );
+    }
+}
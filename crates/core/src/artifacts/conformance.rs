@@ -0,0 +1,155 @@
+//! ABI conformance checks against bundled reference vectors
+//!
+//! Each fixture mirrors solc output for a contract: its `abi` and the
+//! `methodIdentifiers` map from canonical signature to selector. Running the
+//! fixtures through our selector generation catches divergences in type
+//! canonicalization before they reach generated metadata.
+
+use super::{abi::Abi, extract_function_selectors};
+use eyre::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Reference fixtures bundled with the library
+const FIXTURES: &[(&str, &str)] = &[
+    ("erc20", include_str!("../../fixtures/abi/erc20.json")),
+    (
+        "multicall3",
+        include_str!("../../fixtures/abi/multicall3.json"),
+    ),
+    (
+        "swap_router",
+        include_str!("../../fixtures/abi/swap_router.json"),
+    ),
+    (
+        "type_edge_cases",
+        include_str!("../../fixtures/abi/type_edge_cases.json"),
+    ),
+];
+
+/// Reference output for a single contract
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReferenceFixture {
+    abi: Abi,
+    /// Canonical signature -> selector without 0x prefix
+    method_identifiers: BTreeMap<String, String>,
+}
+
+/// Outcome of running all reference fixtures
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    /// Number of fixtures checked
+    pub fixtures: usize,
+    /// Number of function signatures compared
+    pub signatures: usize,
+    /// Divergences from the reference output
+    pub failures: Vec<ConformanceFailure>,
+}
+
+impl ConformanceReport {
+    /// Check if all signatures matched the reference
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A single divergence from the reference output
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConformanceFailure {
+    /// Fixture name
+    pub fixture: String,
+    /// Canonical signature involved in the failure
+    pub signature: String,
+    /// Expected selector (None if we generated a signature the reference lacks)
+    pub expected: Option<String>,
+    /// Actual selector (None if we failed to generate the signature)
+    pub actual: Option<String>,
+}
+
+/// Run selector generation over all bundled reference fixtures
+pub fn run_abi_self_test() -> Result<ConformanceReport> {
+    let mut report = ConformanceReport::default();
+
+    for (name, content) in FIXTURES {
+        let fixture: ReferenceFixture = serde_json::from_str(content)
+            .with_context(|| format!("Invalid ABI reference fixture: {}", name))?;
+
+        report.fixtures += 1;
+        report.signatures += fixture.method_identifiers.len();
+        report.failures.extend(check_fixture(name, &fixture));
+    }
+
+    Ok(report)
+}
+
+/// Compare generated selectors for a fixture with its reference identifiers
+fn check_fixture(name: &str, fixture: &ReferenceFixture) -> Vec<ConformanceFailure> {
+    let actual = extract_function_selectors(&fixture.abi);
+    let mut failures = Vec::new();
+
+    for (signature, expected) in &fixture.method_identifiers {
+        let expected = format!("0x{}", expected.trim_start_matches("0x"));
+        let actual = actual.get(signature).cloned();
+
+        if actual.as_deref() != Some(expected.as_str()) {
+            failures.push(ConformanceFailure {
+                fixture: name.to_string(),
+                signature: signature.clone(),
+                expected: Some(expected),
+                actual,
+            });
+        }
+    }
+
+    for (signature, selector) in &actual {
+        if !fixture.method_identifiers.contains_key(signature) {
+            failures.push(ConformanceFailure {
+                fixture: name.to_string(),
+                signature: signature.clone(),
+                expected: None,
+                actual: Some(selector.clone()),
+            });
+        }
+    }
+
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reference_vectors_conform() {
+        let report = run_abi_self_test().unwrap();
+        assert_eq!(report.fixtures, FIXTURES.len());
+        assert!(report.signatures > 0);
+        assert!(report.is_success(), "divergences: {:#?}", report.failures);
+    }
+
+    #[test]
+    fn test_detects_selector_divergence() {
+        let fixture = ReferenceFixture {
+            abi: serde_json::from_value(json!([{
+                "name": "transfer",
+                "type": "function",
+                "inputs": [
+                    {"name": "to", "type": "address"},
+                    {"name": "amount", "type": "uint256"}
+                ]
+            }]))
+            .unwrap(),
+            method_identifiers: BTreeMap::from([(
+                "transfer(address,uint128)".to_string(),
+                "deadbeef".to_string(),
+            )]),
+        };
+
+        let failures = check_fixture("broken", &fixture);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].actual, None);
+        assert_eq!(failures[1].signature, "transfer(address,uint256)");
+    }
+}
@@ -0,0 +1,236 @@
+//! Deployment payload (`deploy.json`)
+//!
+//! Fluent deploys Rust contracts from their WASM module: the module is the
+//! init code of a regular CREATE/CREATE2 transaction, the node translates it
+//! to rWASM and runs the contract's `deploy` entrypoint, which reads the
+//! ABI-encoded constructor arguments appended after the module. This file
+//! records that payload so deploy tooling doesn't have to know the convention.
+
+use super::{
+    abi::{Abi, AbiEntry, Param},
+    metadata::Metadata,
+};
+use ethabi::token::{LenientTokenizer, Tokenizer};
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// File name of the deployment payload in the contract artifacts directory
+pub const DEPLOY_FILE: &str = "deploy.json";
+
+/// Format of the init code, as expected by the SDK deployment convention
+pub const INIT_CODE_FORMAT: &str = "wasm";
+
+/// Address of a contract deployed with CREATE
+pub const CREATE_ADDRESS_FORMULA: &str = "keccak256(rlp([sender, nonce]))[12:]";
+
+/// Address of a contract deployed with CREATE2
+pub const CREATE2_ADDRESS_FORMULA: &str =
+    "keccak256(0xff ++ sender ++ salt ++ keccak256(init_code ++ constructor_args))[12:]";
+
+/// Everything needed to deploy a compiled contract
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DeployArtifact {
+    pub schema_version: u32,
+    pub contract: String,
+    /// Format of `init_code`; the chain stores its rWASM translation
+    pub init_code_format: String,
+    /// Hex encoded init code (`lib.wasm`)
+    pub init_code: String,
+    /// keccak256 of `init_code`, for CREATE2 address prediction without constructor arguments
+    pub init_code_hash: String,
+    /// Hash of the rWASM code stored on chain, as recorded in metadata.json
+    pub rwasm_hash: String,
+    pub constructor: Constructor,
+    /// Transaction data of the deployment
+    pub calldata: String,
+    pub address: AddressFormulas,
+    /// SDK release whose deployment convention this payload follows
+    pub sdk_tag: String,
+}
+
+/// Constructor arguments appended to the init code
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Constructor {
+    /// Constructor inputs from the ABI, empty if the contract takes none
+    pub inputs: Vec<Param>,
+    /// How the arguments are encoded
+    pub encoding: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AddressFormulas {
+    pub create: String,
+    pub create2: String,
+}
+
+impl DeployArtifact {
+    /// Deployment transaction data for already ABI-encoded constructor arguments
+    pub fn deployment_data(&self, constructor_args: &[u8]) -> Result<Vec<u8>> {
+        let mut calldata = hex::decode(self.init_code.trim_start_matches("0x"))?;
+        calldata.extend_from_slice(constructor_args);
+        Ok(calldata)
+    }
+}
+
+/// Build the deployment payload of a compiled contract
+pub fn generate(wasm: &[u8], abi: &Abi, metadata: &Metadata) -> DeployArtifact {
+    let inputs = abi
+        .iter()
+        .find(|entry| matches!(entry, AbiEntry::Constructor(_)))
+        .map(|constructor| constructor.inputs().to_vec())
+        .unwrap_or_default();
+
+    DeployArtifact {
+        schema_version: 1,
+        contract: metadata.contract.name.clone(),
+        init_code_format: INIT_CODE_FORMAT.to_string(),
+        init_code: format!("0x{}", hex::encode(wasm)),
+        init_code_hash: format!("0x{}", hex::encode(Keccak256::digest(wasm))),
+        rwasm_hash: metadata.bytecode.rwasm.hash.clone(),
+        constructor: Constructor {
+            inputs,
+            encoding: "abi".to_string(),
+        },
+        calldata: "init_code ++ constructor_args".to_string(),
+        address: AddressFormulas {
+            create: CREATE_ADDRESS_FORMULA.to_string(),
+            create2: CREATE2_ADDRESS_FORMULA.to_string(),
+        },
+        sdk_tag: metadata.compilation_settings.sdk.tag.clone(),
+    }
+}
+
+/// ABI-encode constructor arguments given as strings, e.g. `100` or `0xabc...`
+pub fn encode_constructor_args(inputs: &[Param], values: &[String]) -> Result<Vec<u8>> {
+    if inputs.len() != values.len() {
+        return Err(eyre::eyre!(
+            "Constructor takes {} argument(s), {} given",
+            inputs.len(),
+            values.len()
+        ));
+    }
+    let tokens = inputs
+        .iter()
+        .zip(values)
+        .map(|(input, value)| {
+            let param: ethabi::Param = serde_json::to_value(input)
+                .and_then(serde_json::from_value)
+                .context("Invalid constructor input in ABI")?;
+            LenientTokenizer::tokenize(&param.kind, value).with_context(|| {
+                format!(
+                    "Invalid value for constructor argument {} ({}): {}",
+                    param.name, param.kind, value
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ethabi::encode(&tokens))
+}
+
+/// Constructor arguments of a deployment, if `deployment_data` starts with `init_code`
+pub fn strip_init_code<'a>(deployment_data: &'a [u8], init_code: &[u8]) -> Option<&'a [u8]> {
+    deployment_data.strip_prefix(init_code)
+}
+
+/// Address of a contract deployed by `sender` with CREATE at `nonce`
+pub fn create_address(sender: &[u8; 20], nonce: u64) -> [u8; 20] {
+    // RLP of [sender, nonce]; the list is always shorter than 56 bytes
+    let nonce_bytes: Vec<u8> = nonce
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|b| *b == 0)
+        .collect();
+    let encoded_nonce = match nonce_bytes.as_slice() {
+        [] => vec![0x80],
+        [byte] if *byte < 0x80 => vec![*byte],
+        bytes => [&[0x80 + bytes.len() as u8], bytes].concat(),
+    };
+
+    let mut rlp = vec![0xc0 + (21 + encoded_nonce.len()) as u8, 0x94];
+    rlp.extend_from_slice(sender);
+    rlp.extend_from_slice(&encoded_nonce);
+    address_of(&Keccak256::digest(&rlp))
+}
+
+/// Address of a contract deployed by `sender` with CREATE2
+///
+/// `init_code_hash` covers the init code followed by the constructor arguments.
+pub fn create2_address(sender: &[u8; 20], salt: &[u8; 32], init_code_hash: &[u8; 32]) -> [u8; 20] {
+    let mut hasher = Keccak256::new();
+    hasher.update([0xff]);
+    hasher.update(sender);
+    hasher.update(salt);
+    hasher.update(init_code_hash);
+    address_of(&hasher.finalize())
+}
+
+fn address_of(hash: &[u8]) -> [u8; 20] {
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(hex: &str) -> [u8; 20] {
+        hex::decode(hex).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_create_address() {
+        let sender = address("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0");
+        assert_eq!(
+            create_address(&sender, 0),
+            address("cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d")
+        );
+        assert_eq!(
+            create_address(&sender, 1),
+            address("343c43a37d37dff08ae8c4a11544c718abb4fcf8")
+        );
+        assert_eq!(
+            create_address(&sender, 2),
+            address("f778b86fa74e846c4f0a1fbd1335fe81c00a0c91")
+        );
+    }
+
+    #[test]
+    fn test_constructor_args() {
+        let inputs = vec![
+            Param::new("supply", "uint256"),
+            Param::new("owner", "address"),
+        ];
+        let values = [
+            "1000".to_string(),
+            "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0".to_string(),
+        ];
+        let encoded = encode_constructor_args(&inputs, &values).unwrap();
+        assert_eq!(encoded.len(), 64);
+        assert_eq!(encoded[30..32], [0x03, 0xe8]);
+        assert_eq!(
+            encoded[44..],
+            address("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0")
+        );
+
+        assert!(encode_constructor_args(&inputs, &values[..1]).is_err());
+        assert!(encode_constructor_args(&inputs, &["abc".to_string(), values[1].clone()]).is_err());
+
+        let wasm = [0x00, 0x61, 0x73, 0x6d];
+        let deployment_data = [&wasm[..], &encoded].concat();
+        assert_eq!(strip_init_code(&deployment_data, &wasm), Some(&encoded[..]));
+        assert_eq!(strip_init_code(&encoded, &wasm), None);
+    }
+
+    #[test]
+    fn test_create2_address() {
+        // EIP-1014 example 0
+        let init_code_hash: [u8; 32] = Keccak256::digest([0x00]).into();
+        assert_eq!(
+            create2_address(&[0; 20], &[0; 32], &init_code_hash),
+            address("4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38")
+        );
+    }
+}
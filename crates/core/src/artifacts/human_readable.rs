@@ -0,0 +1,332 @@
+//! Human-readable ABI, one declaration per line
+//!
+//! The format of ethers and viem: `function transfer(address to, uint256 amount) returns (bool)`.
+//! Tuples are written inline, e.g. `(address target, bytes data)[] calls`.
+
+use super::abi::{
+    Abi, AbiConstructor, AbiEntry, AbiError, AbiEvent, AbiFallback, AbiFunction, Param,
+    StateMutability,
+};
+use eyre::Result;
+
+/// File written next to abi.json
+pub const HUMAN_READABLE_ABI_FILE: &str = "abi.txt";
+
+/// Declarations of the ABI entries, in order
+pub fn format(abi: &Abi) -> Vec<String> {
+    abi.iter().map(format_entry).collect()
+}
+
+/// Declaration of one entry, e.g. `function transfer(address to, uint256 amount) returns (bool)`
+pub(crate) fn format_entry(entry: &AbiEntry) -> String {
+    let inputs = format_params(entry.inputs());
+    let payable = entry.state_mutability() == Some(StateMutability::Payable);
+
+    match entry {
+        AbiEntry::Function(function) => {
+            let mut declaration = format!("function {}({})", function.name, inputs);
+            if function.state_mutability != StateMutability::Nonpayable {
+                declaration.push(' ');
+                declaration.push_str(function.state_mutability.as_str());
+            }
+            if !function.outputs.is_empty() {
+                declaration.push_str(&format!(" returns ({})", format_params(&function.outputs)));
+            }
+            declaration
+        }
+        AbiEntry::Event(event) if event.anonymous => {
+            format!("event {}({}) anonymous", event.name, inputs)
+        }
+        AbiEntry::Event(event) => format!("event {}({})", event.name, inputs),
+        AbiEntry::Error(error) => format!("error {}({})", error.name, inputs),
+        AbiEntry::Constructor(_) if payable => format!("constructor({}) payable", inputs),
+        AbiEntry::Constructor(_) => format!("constructor({})", inputs),
+        AbiEntry::Fallback(_) if payable => "fallback() external payable".to_string(),
+        AbiEntry::Fallback(_) => "fallback() external".to_string(),
+        AbiEntry::Receive(_) => "receive() external payable".to_string(),
+    }
+}
+
+fn format_params(params: &[Param]) -> String {
+    params
+        .iter()
+        .map(format_param)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_param(param: &Param) -> String {
+    let mut formatted = match param.ty.strip_prefix("tuple") {
+        Some(array_suffix) => format!("({}){}", format_params(&param.components), array_suffix),
+        None => param.ty.clone(),
+    };
+    if param.indexed == Some(true) {
+        formatted.push_str(" indexed");
+    }
+    if !param.name.is_empty() {
+        formatted.push(' ');
+        formatted.push_str(&param.name);
+    }
+    formatted
+}
+
+/// Parse a human-readable ABI into ABI entries
+///
+/// Accepts one declaration per line (blank lines and `//` comments are skipped)
+/// or a JSON array of declarations.
+pub fn parse(text: &str) -> Result<Abi> {
+    let declarations: Vec<String> = match serde_json::from_str::<Vec<String>>(text) {
+        Ok(declarations) => declarations,
+        Err(_) => text.lines().map(str::to_string).collect(),
+    };
+    declarations
+        .iter()
+        .map(|line| line.trim().trim_end_matches(';').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with("//"))
+        .map(|line| {
+            parse_entry(line).map_err(|e| eyre::eyre!("Invalid ABI declaration `{}`: {}", line, e))
+        })
+        .collect()
+}
+
+fn parse_entry(line: &str) -> Result<AbiEntry> {
+    let open = line.find('(').ok_or_else(|| eyre::eyre!("expected `(`"))?;
+    let mut head = line[..open].split_whitespace();
+    let (kind, name) = match (head.next(), head.next()) {
+        (Some(kind @ ("function" | "event" | "error")), Some(name)) => (kind, name),
+        (Some(kind @ ("constructor" | "fallback" | "receive")), None) => (kind, ""),
+        // `transfer(address,uint256)` is shorthand for a function
+        (Some(name), None) => ("function", name),
+        _ => {
+            return Err(eyre::eyre!(
+                "expected `function`, `event`, `error` or `constructor` and a name"
+            ))
+        }
+    };
+
+    let close = matching_paren(line, open)?;
+    let inputs = parse_params(&line[open + 1..close])?;
+    let rest = line[close + 1..].trim();
+    let (modifiers, outputs) = match rest.find("returns") {
+        Some(at) => {
+            let returns = rest[at + "returns".len()..].trim_start();
+            if !returns.starts_with('(') {
+                return Err(eyre::eyre!("expected `(` after `returns`"));
+            }
+            let close = matching_paren(returns, 0)?;
+            (&rest[..at], parse_params(&returns[1..close])?)
+        }
+        None => (rest, Vec::new()),
+    };
+    let modifiers: Vec<&str> = modifiers.split_whitespace().collect();
+    let state_mutability = [
+        ("view", StateMutability::View),
+        ("pure", StateMutability::Pure),
+        ("payable", StateMutability::Payable),
+        ("constant", StateMutability::View),
+    ]
+    .into_iter()
+    .find(|(modifier, _)| modifiers.contains(modifier))
+    .map(|(_, mutability)| mutability)
+    .unwrap_or_default();
+    let name = name.to_string();
+
+    Ok(match kind {
+        "function" => AbiEntry::Function(AbiFunction {
+            inputs,
+            name,
+            outputs,
+            state_mutability,
+        }),
+        "event" => AbiEntry::Event(AbiEvent {
+            anonymous: modifiers.contains(&"anonymous"),
+            inputs: inputs
+                .into_iter()
+                .map(|input| Param {
+                    indexed: Some(input.indexed.unwrap_or(false)),
+                    ..input
+                })
+                .collect(),
+            name,
+        }),
+        "error" => AbiEntry::Error(AbiError { inputs, name }),
+        "constructor" => AbiEntry::Constructor(AbiConstructor {
+            inputs,
+            state_mutability,
+        }),
+        "receive" => AbiEntry::Receive(AbiFallback {
+            state_mutability: StateMutability::Payable,
+        }),
+        _ => AbiEntry::Fallback(AbiFallback { state_mutability }),
+    })
+}
+
+/// Parameters separated by top-level commas
+fn parse_params(list: &str) -> Result<Vec<Param>> {
+    let mut params = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                params.push(parse_param(&list[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !list[start..].trim().is_empty() || !params.is_empty() {
+        params.push(parse_param(&list[start..])?);
+    }
+    Ok(params)
+}
+
+fn parse_param(param: &str) -> Result<Param> {
+    let param = param.trim();
+    let mut value = Param::default();
+
+    // `(address a, bytes b)[] name` or `tuple(address a, bytes b)[] name`
+    let rest = match param
+        .strip_prefix("tuple")
+        .unwrap_or(param)
+        .strip_prefix('(')
+    {
+        Some(_) => {
+            let open = param.find('(').unwrap_or_default();
+            let close = matching_paren(param, open)?;
+            let after = &param[close + 1..];
+            let suffix_len = after.find(char::is_whitespace).unwrap_or(after.len());
+            value.ty = format!("tuple{}", &after[..suffix_len]);
+            value.components = parse_params(&param[open + 1..close])?;
+            &after[suffix_len..]
+        }
+        None => {
+            let ty = param
+                .split_whitespace()
+                .next()
+                .ok_or_else(|| eyre::eyre!("empty parameter"))?;
+            value.ty = elementary_type(ty).ok_or_else(|| eyre::eyre!("unknown type `{}`", ty))?;
+            &param[ty.len()..]
+        }
+    };
+
+    let mut name = "";
+    for word in rest.split_whitespace() {
+        match word {
+            "indexed" => value.indexed = Some(true),
+            "memory" | "calldata" | "storage" => {}
+            _ if name.is_empty() => name = word,
+            _ => {
+                return Err(eyre::eyre!(
+                    "unexpected `{}` in parameter `{}`",
+                    word,
+                    param
+                ))
+            }
+        }
+    }
+    value.name = name.to_string();
+    Ok(value)
+}
+
+/// Canonical name of an elementary type, possibly an array; `uint[]` becomes `uint256[]`
+fn elementary_type(ty: &str) -> Option<String> {
+    let base_len = ty.find('[').unwrap_or(ty.len());
+    let (base, arrays) = ty.split_at(base_len);
+    let dimensions_valid = arrays.split_terminator(']').all(|d| {
+        d.strip_prefix('[')
+            .is_some_and(|n| n.is_empty() || n.parse::<usize>().is_ok_and(|n| n > 0))
+    });
+    if !dimensions_valid || !(arrays.is_empty() || arrays.ends_with(']')) {
+        return None;
+    }
+
+    let bits = |n: &str| {
+        n.parse::<usize>()
+            .is_ok_and(|n| n % 8 == 0 && (8..=256).contains(&n))
+    };
+    let base = match base {
+        "address" | "bool" | "string" | "bytes" | "function" => base.to_string(),
+        "uint" | "int" => format!("{}256", base),
+        _ if base.strip_prefix("uint").is_some_and(bits)
+            || base.strip_prefix("int").is_some_and(bits) =>
+        {
+            base.to_string()
+        }
+        _ if base
+            .strip_prefix("bytes")
+            .and_then(|n| n.parse::<usize>().ok())
+            .is_some_and(|n| (1..=32).contains(&n)) =>
+        {
+            base.to_string()
+        }
+        _ => return None,
+    };
+    Some(format!("{}{}", base, arrays))
+}
+
+/// Index of the `)` closing the `(` at `open`
+fn matching_paren(s: &str, open: usize) -> Result<usize> {
+    let mut depth = 0usize;
+    for (i, c) in s[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return Ok(open + i),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    Err(eyre::eyre!("unbalanced parentheses"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::abi::signature;
+
+    const DECLARATIONS: &[&str] = &[
+        "constructor(uint256 supply)",
+        "function transfer(address to, uint256 amount) returns (bool)",
+        "function balanceOf(address owner) view returns (uint256)",
+        "function aggregate((address target, bytes data)[] calls) payable returns (bytes[])",
+        "event Transfer(address indexed from, address indexed to, uint256 value)",
+        "error InsufficientBalance(uint256 available, uint256 required)",
+        "receive() external payable",
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        let abi = parse(&DECLARATIONS.join("\n")).unwrap();
+        assert_eq!(format(&abi), DECLARATIONS);
+
+        assert_eq!(abi[1].state_mutability(), Some(StateMutability::Nonpayable));
+        assert_eq!(abi[1].outputs(), [Param::new("", "bool")]);
+        assert_eq!(signature(&abi[3]).unwrap(), "aggregate((address,bytes)[])");
+        assert_eq!(abi[4].inputs()[2].indexed, Some(false));
+    }
+
+    #[test]
+    fn test_parse_variants() {
+        let json =
+            serde_json::to_string(&["function totalSupply() external view returns (uint256);"])
+                .unwrap();
+        let abi = parse(&json).unwrap();
+        assert_eq!(
+            format(&abi),
+            ["function totalSupply() view returns (uint256)"]
+        );
+
+        let abi =
+            parse("// ERC-20\n\ntransfer(address,uint256)\nfunction f(tuple(uint8 a) memory s)")
+                .unwrap();
+        assert_eq!(signature(&abi[0]).unwrap(), "transfer(address,uint256)");
+        assert_eq!(signature(&abi[1]).unwrap(), "f((uint8))");
+
+        let abi = parse("function f(uint[2][] xs)").unwrap();
+        assert_eq!(signature(&abi[0]).unwrap(), "f(uint256[2][])");
+        assert!(parse("function f(uint257 x)").is_err());
+        assert!(parse("function f(Adress to)").is_err());
+        assert!(parse("function f(address").is_err());
+    }
+}
@@ -1,9 +1,8 @@
 //! Solidity interface generation from ABI
 
-use super::abi::Abi;
+use super::abi::{Abi, AbiEntry, AbiFunction, Param, StateMutability};
 use convert_case::{Case, Casing};
 use eyre::Result;
-use serde_json::Value;
 use std::collections::HashSet;
 
 /// Generates a Solidity interface from contract ABI
@@ -14,18 +13,38 @@ pub fn generate(contract_name: &str, abi: &Abi) -> Result<String> {
     interface.push_str("// SPDX-License-Identifier: MIT\n");
     interface.push_str("// Auto-generated from Rust source\n");
     interface.push_str("pragma solidity ^0.8.0;\n\n");
-    interface.push_str(&format!("interface I{} {{\n", contract_name.to_case(Case::Pascal)));
+    interface.push_str(&declare_interface(contract_name, abi, false)?);
+    Ok(interface)
+}
+
+/// `interface I<Name> { ... }` with the functions and errors of `abi` and the structs they use
+///
+/// With `events`, events are declared too; `interface.sol` leaves them out,
+/// `sol!` bindings need them to decode logs.
+pub(super) fn declare_interface(contract_name: &str, abi: &Abi, events: bool) -> Result<String> {
+    let mut interface = format!("interface I{} {{\n", contract_name.to_case(Case::Pascal));
 
     // Extract and add struct definitions
     let mut seen_structs = HashSet::new();
     let mut struct_definitions = Vec::new();
 
-    for entry in abi.iter().filter(|e| e["type"] == "function") {
-        if let Some(inputs) = entry.get("inputs").and_then(Value::as_array) {
-            collect_structs(inputs, &mut seen_structs, &mut struct_definitions);
-        }
-        if let Some(outputs) = entry.get("outputs").and_then(Value::as_array) {
-            collect_structs(outputs, &mut seen_structs, &mut struct_definitions);
+    for entry in abi {
+        match entry {
+            AbiEntry::Function(function) => {
+                collect_structs(&function.inputs, &mut seen_structs, &mut struct_definitions);
+                collect_structs(
+                    &return_params(function),
+                    &mut seen_structs,
+                    &mut struct_definitions,
+                );
+            }
+            AbiEntry::Event(_) if events => {
+                collect_structs(entry.inputs(), &mut seen_structs, &mut struct_definitions);
+            }
+            AbiEntry::Error(_) => {
+                collect_structs(entry.inputs(), &mut seen_structs, &mut struct_definitions);
+            }
+            _ => {}
         }
     }
 
@@ -37,8 +56,31 @@ pub fn generate(contract_name: &str, abi: &Abi) -> Result<String> {
         }
     }
 
+    for entry in abi {
+        let declaration = match entry {
+            AbiEntry::Event(event) if events => {
+                let anonymous = if event.anonymous { " anonymous" } else { "" };
+                format!(
+                    "event {}({}){};",
+                    event.name,
+                    format_log_parameters(&event.inputs),
+                    anonymous
+                )
+            }
+            AbiEntry::Error(error) => format!(
+                "error {}({});",
+                error.name,
+                format_log_parameters(&error.inputs)
+            ),
+            _ => continue,
+        };
+        interface.push_str("    ");
+        interface.push_str(&declaration);
+        interface.push('\n');
+    }
+
     // Add functions
-    for func in abi.iter().filter(|e| e["type"] == "function") {
+    for func in abi.iter().filter_map(AbiEntry::as_function) {
         interface.push_str("    ");
         interface.push_str(&format_function(func)?);
         interface.push('\n');
@@ -48,50 +90,93 @@ pub fn generate(contract_name: &str, abi: &Abi) -> Result<String> {
     Ok(interface)
 }
 
-fn format_function(func: &Value) -> Result<String> {
-    let name = func["name"].as_str().unwrap_or_default();
-    let empty_vec = Vec::new();
-    let inputs = func["inputs"].as_array().unwrap_or(&empty_vec);
-    let outputs = func["outputs"].as_array().unwrap_or(&empty_vec);
-    let mutability = func["stateMutability"].as_str().unwrap_or("nonpayable");
+/// Parameters of an event or error, which have no data location
+fn format_log_parameters(params: &[Param]) -> String {
+    params
+        .iter()
+        .map(|param| {
+            let mut formatted = SolType::from_param(param).to_string();
+            if param.indexed == Some(true) {
+                formatted.push_str(" indexed");
+            }
+            if !param.name.is_empty() {
+                formatted.push(' ');
+                formatted.push_str(&param.name);
+            }
+            formatted
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_function(func: &AbiFunction) -> Result<String> {
+    let name = &func.name;
+    let outputs = return_params(func);
 
-    let params = inputs.iter().map(format_parameter).collect::<Vec<_>>().join(", ");
+    let params = func
+        .inputs
+        .iter()
+        .map(|p| format_parameter(p, ParamKind::Input))
+        .collect::<Vec<_>>()
+        .join(", ");
 
     let returns = if outputs.is_empty() {
         String::new()
     } else {
-        let ret_params = outputs.iter().map(format_parameter).collect::<Vec<_>>().join(", ");
+        let ret_params = outputs
+            .iter()
+            .map(|p| format_parameter(p, ParamKind::Output))
+            .collect::<Vec<_>>()
+            .join(", ");
         format!(" returns ({ret_params})")
     };
 
-    let mut_str = match mutability {
-        "pure" => " pure",
-        "view" => " view",
-        "payable" => " payable",
-        _ => "",
+    let mut_str = match func.state_mutability {
+        StateMutability::Pure => " pure",
+        StateMutability::View => " view",
+        StateMutability::Payable => " payable",
+        StateMutability::Nonpayable => "",
     };
 
-    Ok(format!("function {name}({params}) external{mut_str}{returns};"))
+    Ok(format!(
+        "function {name}({params}) external{mut_str}{returns};"
+    ))
 }
 
-fn format_parameter(param: &Value) -> String {
-    let name = param["name"].as_str().unwrap_or("");
-    let internal_type = param.get("internalType").and_then(Value::as_str);
+/// Returns the output parameters of a function as they should appear in `returns (...)`
+///
+/// A single anonymous tuple output is how multiple Rust return values are encoded,
+/// so it is flattened into separate (named, when available) return values.
+pub(super) fn return_params(func: &AbiFunction) -> Vec<Param> {
+    match func.outputs.as_slice() {
+        [single] if is_anonymous_tuple(single) => single.components.clone(),
+        outputs => outputs.to_vec(),
+    }
+}
 
-    // Use internal type for structs, otherwise use regular type
-    let ty = if let Some(internal) = internal_type {
-        if let Some(struct_name) = internal.strip_prefix("struct ") {
-            struct_name.to_string()
-        } else {
-            format_sol_type(param)
-        }
-    } else {
-        format_sol_type(param)
-    };
+/// Checks whether a parameter is a plain `tuple` without a struct name or a parameter name
+fn is_anonymous_tuple(param: &Param) -> bool {
+    param.ty == "tuple"
+        && param.name.is_empty()
+        && param
+            .internal_type
+            .as_deref()
+            .and_then(struct_name_from_internal_type)
+            .is_none()
+}
+
+/// Whether a parameter is a function input or a return value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamKind {
+    Input,
+    Output,
+}
 
-    // Add data location for complex types
-    let location = get_data_location(&ty, internal_type);
-    let location_str = match location {
+fn format_parameter(param: &Param, kind: ParamKind) -> String {
+    let name = &param.name;
+    let ty = SolType::from_param(param);
+
+    let location_str = match get_data_location(&ty, kind) {
         Some(DataLocation::Memory) => " memory",
         Some(DataLocation::Calldata) => " calldata",
         None => "",
@@ -104,84 +189,172 @@ fn format_parameter(param: &Value) -> String {
     }
 }
 
-fn format_sol_type(param: &Value) -> String {
-    let param_type = param["type"].as_str().unwrap_or("unknown");
+/// Recursive model of a Solidity type as described by an ABI parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum SolType {
+    /// Elementary type such as `uint256`, `bytes32`, `string` or `bytes`
+    Elementary(String),
+    /// Named struct, resolved from `internalType`
+    Struct {
+        name: String,
+        fields: Vec<(String, SolType)>,
+    },
+    /// Tuple without a struct name or a parameter name to derive one from
+    Tuple(Vec<SolType>),
+    /// Dynamic (`T[]`) or fixed-size (`T[N]`) array
+    Array {
+        element: Box<SolType>,
+        len: Option<usize>,
+    },
+}
 
-    if param_type == "tuple" {
-        // Check if it's a named struct
-        if let Some(internal_type) = param.get("internalType").and_then(Value::as_str) {
-            if let Some(stripped) = internal_type.strip_prefix("struct ") {
-                return stripped.to_string();
+impl SolType {
+    /// Builds the type model from an ABI parameter (`type`, `internalType`, `components`)
+    pub(super) fn from_param(param: &Param) -> Self {
+        let (base, dims) = split_array_suffixes(&param.ty);
+
+        let mut ty = if base == "tuple" {
+            let components = &param.components;
+            // Prefer the struct name from `internalType`, otherwise name the struct
+            // after the parameter so it can still be declared in the interface
+            let struct_name = param
+                .internal_type
+                .as_deref()
+                .and_then(struct_name_from_internal_type)
+                .or_else(|| (!param.name.is_empty()).then(|| param.name.to_case(Case::Pascal)));
+
+            match struct_name {
+                Some(name) => SolType::Struct {
+                    name,
+                    fields: components
+                        .iter()
+                        .map(|c| {
+                            let field_name = if c.name.is_empty() {
+                                "_".to_string()
+                            } else {
+                                c.name.clone()
+                            };
+                            (field_name, SolType::from_param(c))
+                        })
+                        .collect(),
+                },
+                None => SolType::Tuple(components.iter().map(SolType::from_param).collect()),
             }
+        } else {
+            SolType::Elementary(base.to_string())
+        };
+
+        for len in dims {
+            ty = SolType::Array {
+                element: Box::new(ty),
+                len,
+            };
         }
 
-        // Handle anonymous tuples
-        if let Some(components) = param.get("components").and_then(Value::as_array) {
-            let component_types =
-                components.iter().map(format_sol_type).collect::<Vec<_>>().join(",");
-            format!("({component_types})")
-        } else {
-            "tuple".to_string()
+        ty
+    }
+
+    /// Whether values of this type require an explicit data location
+    fn is_reference(&self) -> bool {
+        match self {
+            SolType::Elementary(name) => name == "string" || name == "bytes",
+            SolType::Struct { .. } | SolType::Tuple(_) | SolType::Array { .. } => true,
         }
-    } else if let Some(base_type) = param_type.strip_suffix("[]") {
-        // Handle array types
-        let formatted_base = format_sol_type(&serde_json::json!({ "type": base_type }));
-        format!("{formatted_base}[]")
-    } else {
-        // Return primitive types as-is
-        param_type.to_string()
     }
 }
 
+impl std::fmt::Display for SolType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolType::Elementary(name) => write!(f, "{name}"),
+            SolType::Struct { name, .. } => write!(f, "{name}"),
+            SolType::Tuple(components) => {
+                let types = components
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                write!(f, "({})", types.join(","))
+            }
+            SolType::Array {
+                element,
+                len: Some(len),
+            } => write!(f, "{element}[{len}]"),
+            SolType::Array { element, len: None } => write!(f, "{element}[]"),
+        }
+    }
+}
+
+/// Splits `uint256[3][]` into `("uint256", [Some(3), None])`, innermost dimension first
+fn split_array_suffixes(ty: &str) -> (&str, Vec<Option<usize>>) {
+    let base_end = ty.find('[').unwrap_or(ty.len());
+    let dims = ty[base_end..]
+        .split_terminator(']')
+        .map(|dim| dim.trim_start_matches('[').parse().ok())
+        .collect();
+    (&ty[..base_end], dims)
+}
+
+/// Extracts `Item` from internal types like `struct Item`, `struct Item[2][]` or `struct Lib.Item`
+pub(super) fn struct_name_from_internal_type(internal_type: &str) -> Option<String> {
+    let name = internal_type.strip_prefix("struct ")?;
+    let name = name.split('[').next().unwrap_or(name);
+    let name = name.rsplit('.').next().unwrap_or(name);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
 #[derive(Debug, Clone, Copy)]
 enum DataLocation {
     Memory,
     Calldata,
 }
 
-fn get_data_location(ty: &str, internal_type: Option<&str>) -> Option<DataLocation> {
-    match (ty, internal_type) {
-        (_, Some(t)) if t.starts_with("struct ") => Some(DataLocation::Memory),
-        ("string", _) | ("bytes", _) => Some(DataLocation::Calldata),
-        (t, _) if t.ends_with("[]") => Some(DataLocation::Memory),
-        (t, _) if t.starts_with("(") && t.ends_with(")") => Some(DataLocation::Memory), // tuples
-        _ => None,
+fn get_data_location(ty: &SolType, kind: ParamKind) -> Option<DataLocation> {
+    if !ty.is_reference() {
+        return None;
+    }
+
+    match (ty, kind) {
+        // Return values can only live in memory
+        (_, ParamKind::Output) => Some(DataLocation::Memory),
+        (SolType::Elementary(_), ParamKind::Input) => Some(DataLocation::Calldata),
+        (_, ParamKind::Input) => Some(DataLocation::Memory),
     }
 }
 
-fn collect_structs(params: &[Value], seen: &mut HashSet<String>, structs: &mut Vec<String>) {
+fn collect_structs(params: &[Param], seen: &mut HashSet<String>, structs: &mut Vec<String>) {
     for param in params {
-        if param["type"] == "tuple" {
-            if let Some(internal_type) = param.get("internalType").and_then(Value::as_str) {
-                if let Some(struct_name) = internal_type.strip_prefix("struct ") {
-                    if seen.insert(struct_name.to_string()) {
-                        if let Some(components) = param.get("components").and_then(Value::as_array)
-                        {
-                            let fields = components
-                                .iter()
-                                .map(|field| {
-                                    let field_name = field["name"].as_str().unwrap_or("_");
-                                    let field_type = format_sol_type(field);
-                                    format!("        {field_type} {field_name};")
-                                })
-                                .collect::<Vec<_>>()
-                                .join("\n");
-
-                            structs
-                                .push(format!("    struct {struct_name} {{\n{fields}\n    }}"));
-
-                            // Recursively collect nested structs
-                            collect_structs(components, seen, structs);
-                        }
-                    }
-                }
+        collect_type_structs(&SolType::from_param(param), seen, structs);
+    }
+}
+
+/// Emits a definition for every struct reachable from `ty`
+fn collect_type_structs(ty: &SolType, seen: &mut HashSet<String>, structs: &mut Vec<String>) {
+    match ty {
+        SolType::Struct { name, fields } => {
+            if !seen.insert(name.clone()) {
+                return;
             }
-        } else if param["type"].as_str().map(|t| t.ends_with("[]")).unwrap_or(false) {
-            // For arrays, check the base type
-            if let Some(components) = param.get("components").and_then(Value::as_array) {
-                collect_structs(components, seen, structs);
+
+            let body = fields
+                .iter()
+                .map(|(field_name, field_ty)| format!("        {field_ty} {field_name};"))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            structs.push(format!("    struct {name} {{\n{body}\n    }}"));
+
+            // Recursively collect nested structs, including those behind arrays
+            for (_, field_ty) in fields {
+                collect_type_structs(field_ty, seen, structs);
+            }
+        }
+        SolType::Tuple(components) => {
+            for component in components {
+                collect_type_structs(component, seen, structs);
             }
         }
+        SolType::Array { element, .. } => collect_type_structs(element, seen, structs),
+        SolType::Elementary(_) => {}
     }
 }
 
@@ -189,12 +362,16 @@ fn collect_structs(params: &[Value], seen: &mut HashSet<String>, structs: &mut V
 mod tests {
     use super::*;
     use insta::assert_snapshot;
-    use serde_json::json;
+    use serde_json::{json, Value};
+
+    fn param(value: Value) -> Param {
+        serde_json::from_value(value).unwrap()
+    }
 
     #[test]
     fn test_simple_erc20_interface() {
-        let abi = vec![
-            json!({
+        let abi = serde_json::from_value(json!([
+            {
                 "name": "transfer",
                 "type": "function",
                 "inputs": [
@@ -203,8 +380,8 @@ mod tests {
                 ],
                 "outputs": [{"name": "", "type": "bool", "internalType": "bool"}],
                 "stateMutability": "nonpayable"
-            }),
-            json!({
+            },
+            {
                 "name": "balanceOf",
                 "type": "function",
                 "inputs": [
@@ -212,8 +389,8 @@ mod tests {
                 ],
                 "outputs": [{"name": "", "type": "uint256", "internalType": "uint256"}],
                 "stateMutability": "view"
-            }),
-            json!({
+            },
+            {
                 "name": "approve",
                 "type": "function",
                 "inputs": [
@@ -222,8 +399,9 @@ mod tests {
                 ],
                 "outputs": [{"name": "", "type": "bool", "internalType": "bool"}],
                 "stateMutability": "nonpayable"
-            }),
-        ];
+            },
+        ]))
+        .unwrap();
 
         let interface = generate("ERC20Token", &abi).unwrap();
         assert_snapshot!("erc20_interface", interface);
@@ -231,7 +409,7 @@ mod tests {
 
     #[test]
     fn test_complex_structs_interface() {
-        let abi = vec![json!({
+        let abi = serde_json::from_value(json!([{
             "name": "submitOrder",
             "type": "function",
             "inputs": [{
@@ -256,7 +434,8 @@ mod tests {
             }],
             "outputs": [{"name": "success", "type": "bool", "internalType": "bool"}],
             "stateMutability": "payable"
-        })];
+        }]))
+        .unwrap();
 
         let interface = generate("OrderManager", &abi).unwrap();
         assert_snapshot!("complex_structs_interface", interface);
@@ -264,36 +443,37 @@ mod tests {
 
     #[test]
     fn test_all_function_mutabilities() {
-        let abi = vec![
-            json!({
+        let abi = serde_json::from_value(json!([
+            {
                 "name": "pureFunction",
                 "type": "function",
                 "inputs": [{"name": "x", "type": "uint256", "internalType": "uint256"}],
                 "outputs": [{"name": "", "type": "uint256", "internalType": "uint256"}],
                 "stateMutability": "pure"
-            }),
-            json!({
+            },
+            {
                 "name": "viewFunction",
                 "type": "function",
                 "inputs": [],
                 "outputs": [{"name": "", "type": "string", "internalType": "string"}],
                 "stateMutability": "view"
-            }),
-            json!({
+            },
+            {
                 "name": "payableFunction",
                 "type": "function",
                 "inputs": [{"name": "data", "type": "bytes", "internalType": "bytes"}],
                 "outputs": [],
                 "stateMutability": "payable"
-            }),
-            json!({
+            },
+            {
                 "name": "nonpayableFunction",
                 "type": "function",
                 "inputs": [],
                 "outputs": [],
                 "stateMutability": "nonpayable"
-            }),
-        ];
+            },
+        ]))
+        .unwrap();
 
         let interface = generate("MixedContract", &abi).unwrap();
         assert_snapshot!("all_mutabilities_interface", interface);
@@ -301,7 +481,7 @@ mod tests {
 
     #[test]
     fn test_arrays_and_complex_types() {
-        let abi = vec![json!({
+        let abi = serde_json::from_value(json!([{
             "name": "processData",
             "type": "function",
             "inputs": [
@@ -338,7 +518,8 @@ mod tests {
                 }
             ],
             "stateMutability": "nonpayable"
-        })];
+        }]))
+        .unwrap();
 
         let interface = generate("DataProcessor", &abi).unwrap();
         assert_snapshot!("arrays_and_complex_types", interface);
@@ -353,7 +534,7 @@ mod tests {
 
     #[test]
     fn test_nested_structs_interface() {
-        let abi = vec![json!({
+        let abi = serde_json::from_value(json!([{
             "name": "updateConfig",
             "type": "function",
             "inputs": [{
@@ -384,9 +565,228 @@ mod tests {
             }],
             "outputs": [],
             "stateMutability": "nonpayable"
-        })];
+        }]))
+        .unwrap();
 
         let interface = generate("ConfigManager", &abi).unwrap();
         assert_snapshot!("nested_structs_interface", interface);
     }
+
+    #[test]
+    fn test_fixed_arrays_and_bytes_n() {
+        let abi = serde_json::from_value(json!([{
+            "name": "setRoots",
+            "type": "function",
+            "inputs": [
+                {"name": "values", "type": "uint256[3]", "internalType": "uint256[3]"},
+                {"name": "root", "type": "bytes32", "internalType": "bytes32"},
+                {"name": "proofs", "type": "bytes32[]", "internalType": "bytes32[]"},
+                {"name": "grid", "type": "uint8[2][]", "internalType": "uint8[2][]"}
+            ],
+            "outputs": [
+                {"name": "", "type": "bytes4[2]", "internalType": "bytes4[2]"}
+            ],
+            "stateMutability": "nonpayable"
+        }]))
+        .unwrap();
+
+        let interface = generate("FixedArrays", &abi).unwrap();
+        assert_snapshot!("fixed_arrays_and_bytes_n", interface);
+    }
+
+    #[test]
+    fn test_dynamic_types_inside_structs() {
+        let abi = serde_json::from_value(json!([{
+            "name": "register",
+            "type": "function",
+            "inputs": [{
+                "name": "batch",
+                "type": "tuple[2]",
+                "internalType": "struct Batch[2]",
+                "components": [
+                    {"name": "hashes", "type": "bytes32[]", "internalType": "bytes32[]"},
+                    {"name": "labels", "type": "string[]", "internalType": "string[]"},
+                    {
+                        "name": "entries",
+                        "type": "tuple[]",
+                        "internalType": "struct Entry[]",
+                        "components": [
+                            {"name": "key", "type": "bytes", "internalType": "bytes"},
+                            {"name": "weights", "type": "uint256[4]", "internalType": "uint256[4]"}
+                        ]
+                    }
+                ]
+            }],
+            "outputs": [],
+            "stateMutability": "nonpayable"
+        }]))
+        .unwrap();
+
+        let interface = generate("Registry", &abi).unwrap();
+        assert_snapshot!("dynamic_types_inside_structs", interface);
+    }
+
+    #[test]
+    fn test_string_arrays_and_dynamic_returns() {
+        let abi = serde_json::from_value(json!([{
+            "name": "names",
+            "type": "function",
+            "inputs": [
+                {"name": "ids", "type": "string[]", "internalType": "string[]"},
+                {"name": "fixedIds", "type": "string[2]", "internalType": "string[2]"}
+            ],
+            "outputs": [
+                {"name": "", "type": "string", "internalType": "string"},
+                {"name": "", "type": "bytes", "internalType": "bytes"},
+                {"name": "", "type": "string[]", "internalType": "string[]"}
+            ],
+            "stateMutability": "view"
+        }]))
+        .unwrap();
+
+        let interface = generate("Names", &abi).unwrap();
+        assert_snapshot!("string_arrays_and_dynamic_returns", interface);
+    }
+
+    #[test]
+    fn test_sol_type_model() {
+        let ty = SolType::from_param(&param(json!({"type": "uint256[3][]"})));
+        assert_eq!(ty.to_string(), "uint256[3][]");
+        assert!(ty.is_reference());
+
+        let ty = SolType::from_param(&param(json!({"type": "bytes32"})));
+        assert_eq!(ty, SolType::Elementary("bytes32".to_string()));
+        assert!(!ty.is_reference());
+
+        let ty = SolType::from_param(&param(json!({
+            "type": "tuple[2][]",
+            "internalType": "struct Lib.Point[2][]",
+            "components": [{"name": "x", "type": "int128", "internalType": "int128"}]
+        })));
+        assert_eq!(ty.to_string(), "Point[2][]");
+
+        assert_eq!(split_array_suffixes("address"), ("address", vec![]));
+        assert_eq!(
+            split_array_suffixes("bool[][5]"),
+            ("bool", vec![None, Some(5)])
+        );
+    }
+
+    #[test]
+    fn test_struct_and_named_returns() {
+        let abi = serde_json::from_value(json!([
+            {
+                "name": "getPosition",
+                "type": "function",
+                "inputs": [{"name": "owner", "type": "address", "internalType": "address"}],
+                "outputs": [{
+                    "name": "position",
+                    "type": "tuple",
+                    "internalType": "struct Position",
+                    "components": [
+                        {"name": "size", "type": "uint256", "internalType": "uint256"},
+                        {"name": "entryPrice", "type": "uint256", "internalType": "uint256"}
+                    ]
+                }],
+                "stateMutability": "view"
+            },
+            {
+                "name": "reserves",
+                "type": "function",
+                "inputs": [],
+                "outputs": [{
+                    "name": "",
+                    "type": "tuple",
+                    "components": [
+                        {"name": "reserve0", "type": "uint112", "internalType": "uint112"},
+                        {"name": "reserve1", "type": "uint112", "internalType": "uint112"},
+                        {"name": "", "type": "string", "internalType": "string"}
+                    ]
+                }],
+                "stateMutability": "view"
+            },
+            {
+                "name": "limits",
+                "type": "function",
+                "inputs": [],
+                "outputs": [
+                    {"name": "min", "type": "uint256", "internalType": "uint256"},
+                    {
+                        "name": "window",
+                        "type": "tuple",
+                        "components": [
+                            {"name": "start", "type": "uint64", "internalType": "uint64"},
+                            {"name": "end", "type": "uint64", "internalType": "uint64"}
+                        ]
+                    }
+                ],
+                "stateMutability": "view"
+            },
+        ]))
+        .unwrap();
+
+        let interface = generate("Pool", &abi).unwrap();
+        assert_snapshot!("struct_and_named_returns", interface);
+    }
+
+    #[test]
+    fn test_return_params_flattening() {
+        let function = |value: Value| -> AbiFunction { serde_json::from_value(value).unwrap() };
+        let func = function(json!({
+            "name": "pair",
+            "outputs": [{
+                "name": "",
+                "type": "tuple",
+                "components": [
+                    {"name": "a", "type": "uint256"},
+                    {"name": "b", "type": "bool"}
+                ]
+            }]
+        }));
+        let returns = return_params(&func);
+        assert_eq!(returns.len(), 2);
+        assert_eq!(returns[0].name, "a");
+
+        // Struct outputs are kept as a single value
+        let func = function(json!({
+            "name": "pair",
+            "outputs": [{
+                "name": "",
+                "type": "tuple",
+                "internalType": "struct Pair",
+                "components": [{"name": "a", "type": "uint256"}]
+            }]
+        }));
+        assert_eq!(return_params(&func).len(), 1);
+    }
+
+    #[test]
+    fn test_custom_errors() {
+        let abi = serde_json::from_value(json!([
+            {
+                "name": "pause",
+                "type": "function",
+                "inputs": [],
+                "outputs": [],
+                "stateMutability": "nonpayable"
+            },
+            {"name": "Paused", "type": "event", "inputs": [], "anonymous": false},
+            {
+                "name": "InsufficientBalance",
+                "type": "error",
+                "inputs": [
+                    {"name": "available", "type": "uint256"},
+                    {"name": "required", "type": "uint256"}
+                ]
+            },
+            {"name": "Unauthorized", "type": "error", "inputs": []}
+        ]))
+        .unwrap();
+
+        let interface = generate("Token", &abi).unwrap();
+        assert!(interface
+            .contains("    error InsufficientBalance(uint256 available, uint256 required);\n"));
+        assert!(interface.contains("    error Unauthorized();\n"));
+        assert!(!interface.contains("event Paused"));
+    }
 }
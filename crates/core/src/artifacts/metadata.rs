@@ -3,18 +3,23 @@
 //! CRITICAL: The JSON schema produced by these structures is a contract
 //! with external systems and must not be changed.
 
-use crate::builder::{ContractInfo, RustInfo, SdkInfo};
-use crate::config::CompileConfig;
-use crate::GitInfo;
+use super::{nondeterminism::ReproducibilityWarning, sanitize::RenamedIdentifier};
+use crate::builder::{
+    parse_sdk_version, read_rust_toolchain_version, read_sdk_version_from_cargo_lock, ContractInfo,
+    RustInfo, SdkInfo,
+};
+use crate::config::{AbiNaming, CompileConfig};
+use crate::{GitInfo, HashAlgorithm};
 use eyre::Result;
-use serde::Serialize;
-use std::collections::BTreeMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
 
 /// Root metadata structure for contract verification
 ///
 /// This combines static config + runtime detected info to create
 /// a complete picture for reproducible builds.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Metadata {
     pub schema_version: u32,
     pub contract: ContractInfo,
@@ -25,19 +30,80 @@ pub struct Metadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub solidity_compatibility: Option<SolidityCompatibility>,
     pub dependencies: Dependencies,
+    /// Root of the enclosing Cargo workspace relative to the project, e.g. `../..`
     #[serde(skip_serializing_if = "Option::is_none")]
     pub workspace_root: Option<String>,
     pub toolchain_hash: String,
     pub source_tree_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nix_derivation: Option<String>,
+    /// Locked flake inputs of the Nix build environment
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub nix_inputs: Vec<NixInput>,
+    /// Whether the contract was built inside the fluent-builder Docker image
+    #[serde(default)]
+    pub docker: bool,
+    /// The Docker image and CLI of a Docker build
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub build_environment: Option<BuildEnvironment>,
+    /// fluent-builder version that produced the bytecode; empty in older metadata
+    #[serde(default)]
+    pub builder_version: String,
+    /// Code that may make the build depend on the build machine
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub reproducibility_warnings: Vec<ReproducibilityWarning>,
+}
+
+impl Metadata {
+    /// Read a metadata.json
+    pub fn read(path: &Path) -> Result<Self> {
+        super::read_json_file(path)
+    }
+
+    /// Recorded toolchain and SDK versions the sources at `project_root` don't pin
+    ///
+    /// Both come from the sources (`rust-toolchain.toml`, `Cargo.lock`), so a
+    /// rebuild can only reproduce them from the recorded revision.
+    pub fn source_differences(&self, project_root: &Path) -> Vec<String> {
+        let mut differences = Vec::new();
+        let recorded = &self.compilation_settings;
+        if let Ok(rust) = read_rust_toolchain_version(project_root) {
+            if rust != recorded.rust.version {
+                differences.push(format!(
+                    "Rust toolchain is {}, the recorded build used {}",
+                    rust, recorded.rust.version
+                ));
+            }
+        }
+        if let Ok(sdk) =
+            read_sdk_version_from_cargo_lock(project_root).map(|v| parse_sdk_version(&v))
+        {
+            let commit_differs =
+                sdk.commit != recorded.sdk.commit && recorded.sdk.commit != "unknown";
+            if sdk.tag != recorded.sdk.tag || commit_differs {
+                differences.push(format!(
+                    "fluentbase-sdk is {} ({}), the recorded build used {} ({})",
+                    sdk.tag, sdk.commit, recorded.sdk.tag, recorded.sdk.commit
+                ));
+            }
+        }
+        differences
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub enum Source {
     #[serde(rename = "archive")]
     Archive {
         archive_path: String,
         project_path: String,
+        /// Globs of the files the archive holds besides the sources, e.g. `assets/**`
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        include_globs: Vec<String>,
+        /// Globs of the files left out of the archive
+        #[serde(skip_serializing_if = "Vec::is_empty", default)]
+        exclude_globs: Vec<String>,
     },
     #[serde(rename = "git")]
     Git {
@@ -52,6 +118,8 @@ impl Default for Source {
         Source::Archive {
             archive_path: String::new(),
             project_path: String::new(),
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
         }
     }
 }
@@ -62,6 +130,8 @@ impl Source {
         Source::Archive {
             archive_path: "./source.tar.gz".to_string(),
             project_path: project_path.into(),
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
         }
     }
 
@@ -69,10 +139,10 @@ impl Source {
     /// Returns error if git info is not suitable for git source
     pub fn git(git_info: &GitInfo, project_path: impl Into<String>) -> Result<Self> {
         if git_info.is_dirty {
-            return Err(eyre::eyre!(
-                "Cannot use git source: repository has {} uncommitted changes",
-                git_info.dirty_files_count
-            ));
+            return Err(eyre::Report::new(crate::BuilderError::GitDirty {
+                dirty_files: git_info.dirty_files_count,
+            })
+            .wrap_err("Cannot use git source"));
         }
 
         Ok(Source::Git {
@@ -93,7 +163,7 @@ impl Source {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CompilationSettings {
     pub rust: RustInfo,
     pub sdk: SdkInfo,
@@ -101,13 +171,39 @@ pub struct CompilationSettings {
 }
 
 /// Build configuration from CompileConfig
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BuildConfig {
     pub profile: String,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub features: Vec<String>,
     pub no_default_features: bool,
     pub locked: bool,
+    /// Network the build was made for, if selected with `--network`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub network: Option<String>,
+    /// Arguments passed to `cargo build` as-is
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub extra_cargo_args: Vec<String>,
+    /// RUSTFLAGS set by fluent-builder
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rustflags: Option<String>,
+    /// Target or variant of a multi-contract package, if not the default library
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub target_name: Option<String>,
+}
+
+impl BuildConfig {
+    /// Set the recorded settings on `config`, to rebuild the way the recorded build did
+    pub fn apply(&self, config: &mut CompileConfig) {
+        config.profile = self.profile.clone();
+        config.features = self.features.clone();
+        config.no_default_features = self.no_default_features;
+        config.locked = self.locked;
+        config.network = self.network.clone();
+        config.extra_cargo_args = self.extra_cargo_args.clone();
+        config.rustflags = self.rustflags.clone();
+        config.target_name = self.target_name.clone();
+    }
 }
 
 impl From<&CompileConfig> for BuildConfig {
@@ -117,31 +213,140 @@ impl From<&CompileConfig> for BuildConfig {
             features: config.features.clone(),
             no_default_features: config.no_default_features,
             locked: config.locked,
+            network: config.network.clone(),
+            extra_cargo_args: config.extra_cargo_args.clone(),
+            rustflags: config.rustflags.clone(),
+            target_name: config.target_name.clone(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BytecodeInfo {
+    /// Algorithm of the `hash` fields; sha256 in older metadata
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
     pub wasm: ArtifactInfo,
     pub rwasm: ArtifactInfo,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ArtifactInfo {
     pub hash: String,
     pub size: usize,
     pub path: String,
+    /// Chain-native code hash (keccak256, as returned by `EXTCODEHASH`) of deployed code
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub code_hash: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SolidityCompatibility {
     pub abi_path: String,
     pub interface_path: String,
     pub function_selectors: BTreeMap<String, String>,
+    /// topic0 of every non-anonymous event, by signature
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub event_topics: BTreeMap<String, String>,
+    /// 4-byte selector of every custom error, by signature
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub error_selectors: BTreeMap<String, String>,
+    /// Routers of multi-router (diamond-style) contracts; omitted for a single router
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub facets: Vec<FacetInfo>,
+    /// Naming policy applied to the Rust names
+    #[serde(skip_serializing_if = "AbiNaming::is_preserve", default)]
+    pub naming: AbiNaming,
+    /// Names Solidity reserves and duplicate struct names, renamed in the ABI
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub renamed_identifiers: Vec<RenamedIdentifier>,
+    /// Selectors the router dispatches renamed functions on, by their signature in the ABI
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub dispatched_selectors: BTreeMap<String, String>,
+}
+
+/// Outputs of one router in a multi-router contract
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FacetInfo {
+    pub name: String,
+    pub abi_path: String,
+    pub interface_path: String,
+    pub function_selectors: BTreeMap<String, String>,
+    /// topic0 of every non-anonymous event, by signature
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub event_topics: BTreeMap<String, String>,
+    /// Selectors the router dispatches renamed functions on, by their signature in the ABI
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub dispatched_selectors: BTreeMap<String, String>,
+}
+
+/// A flake input of the Nix build environment, locked to a revision and content hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NixInput {
+    /// Input name in the flake, e.g. `nixpkgs`
+    pub name: String,
+    /// Flake reference without the revision, e.g. `github:NixOS/nixpkgs`
+    pub url: String,
+    pub rev: String,
+    /// NAR hash of the input's source, `sha256-...`
+    pub nar_hash: String,
+}
+
+impl NixInput {
+    /// Inputs passed to the build by the CLI's Nix backend, if any
+    pub fn from_env() -> Vec<Self> {
+        let Ok(value) = std::env::var(crate::builder::NIX_INPUTS_ENV) else {
+            return Vec::new();
+        };
+        serde_json::from_str(&value)
+            .inspect_err(|e| {
+                tracing::warn!("Ignoring invalid {}: {}", crate::builder::NIX_INPUTS_ENV, e)
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The Docker image a contract was built in
+///
+/// Image tags can be rebuilt or re-pushed with different contents, the digest can't.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BuildEnvironment {
+    /// Image name, e.g. `fluent-builder-v0.1.0-rust-1.83.0`
+    pub image: String,
+    /// Image ID, `sha256:...`
+    pub image_digest: String,
+    /// Registry reference pinning the image, e.g. `fluentlabs/fluent-builder@sha256:...`, if it was
+    /// pulled
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub repo_digest: Option<String>,
+    /// Registry reference of the builder image this image was built from, e.g.
+    /// `fluentlabs/fluent-builder@sha256:...`; verification rebuilds the image from it if the image
+    /// itself can't be fetched
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub base_image: Option<String>,
+    /// Platform of the image, e.g. `linux/amd64`
+    pub platform: String,
+    /// Version of the fluent-builder CLI that started the container
+    pub cli_version: String,
+}
+
+impl BuildEnvironment {
+    /// Environment passed into the container by the CLI, if any
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(crate::builder::BUILD_ENVIRONMENT_ENV).ok()?;
+        serde_json::from_str(&value)
+            .inspect_err(|e| {
+                tracing::warn!(
+                    "Ignoring invalid {}: {}",
+                    crate::builder::BUILD_ENVIRONMENT_ENV,
+                    e
+                )
+            })
+            .ok()
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Dependencies {
     pub cargo_lock_hash: String,
 }
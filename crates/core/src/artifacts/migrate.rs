@@ -0,0 +1,386 @@
+//! Upgrading artifact directories written by older fluent-builder versions
+//!
+//! Early builds wrote `verification_metadata.json` and bytecode named after
+//! the contract, and metadata without the fields later versions require.
+//! Migration renames files to the current layout and fills in what can be
+//! derived from the files themselves. Recorded hashes are checked, never
+//! rewritten, so a migrated directory still describes the same bytecode.
+
+use super::{
+    deploy::{self, DEPLOY_FILE},
+    metadata::Metadata,
+    reproducibility::{self, REPRODUCIBILITY_FILE},
+    Abi,
+};
+use crate::{
+    builder::hash_sources,
+    compat::{Compat, UNVERSIONED},
+    migrate::{apply_migration, FileChange},
+    HashAlgorithm,
+};
+use eyre::{Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Name of metadata.json before it was renamed
+pub const LEGACY_METADATA_FILE: &str = "verification_metadata.json";
+
+/// Changes bringing an artifacts directory to the current layout
+#[derive(Debug, Clone, Default)]
+pub struct ArtifactsMigration {
+    pub dir: PathBuf,
+    /// Files to rename (old, new), relative to `dir`, applied first
+    pub renames: Vec<(String, String)>,
+    /// Files to write, relative to `dir`
+    pub changes: Vec<FileChange>,
+    /// Missing files that can't be derived from the directory
+    pub skipped: Vec<String>,
+}
+
+impl ArtifactsMigration {
+    /// Whether the directory is already current
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && self.changes.is_empty()
+    }
+
+    /// Rename and write the planned files
+    pub fn apply(&self) -> Result<()> {
+        for (from, to) in &self.renames {
+            std::fs::rename(self.dir.join(from), self.dir.join(to)).with_context(|| {
+                format!(
+                    "Failed to rename {} to {} in {}",
+                    from,
+                    to,
+                    self.dir.display()
+                )
+            })?;
+        }
+        apply_migration(&self.dir, &self.changes)
+    }
+}
+
+/// Inspect an artifacts directory and compute its migration, without touching files
+///
+/// `project_root` is used to derive `reproducibility.json`; by default the
+/// project two levels up (`<project>/out/<name>.wasm`) is tried.
+pub fn plan_artifacts_migration(
+    dir: &Path,
+    project_root: Option<&Path>,
+) -> Result<ArtifactsMigration> {
+    let mut migration = ArtifactsMigration {
+        dir: dir.to_path_buf(),
+        ..Default::default()
+    };
+
+    let metadata_file = ["metadata.json", LEGACY_METADATA_FILE]
+        .into_iter()
+        .find(|name| dir.join(name).is_file())
+        .ok_or_else(|| eyre::eyre!("No metadata.json in {}", dir.display()))?;
+    let before = std::fs::read_to_string(dir.join(metadata_file))?;
+    let mut metadata: Value = serde_json::from_str(&before)
+        .with_context(|| format!("Failed to parse {}", metadata_file))?;
+    if metadata_file == LEGACY_METADATA_FILE {
+        migration.renames.push((
+            LEGACY_METADATA_FILE.to_string(),
+            "metadata.json".to_string(),
+        ));
+    }
+
+    let contract = metadata["contract"]["name"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    for key in ["wasm", "rwasm"] {
+        let file = format!("lib.{}", key);
+        let candidates = [
+            metadata["bytecode"][key]["path"]
+                .as_str()
+                .unwrap_or(&file)
+                .to_string(),
+            file.clone(),
+            format!("{}.{}", contract, key),
+            format!("{}.{}", contract.replace('-', "_"), key),
+        ];
+        let found = candidates
+            .iter()
+            .find(|name| dir.join(name).is_file())
+            .ok_or_else(|| eyre::eyre!("No {} bytecode in {}", key, dir.display()))?;
+        if *found != file {
+            migration.renames.push((found.clone(), file.clone()));
+        }
+        upgrade_artifact_info(
+            &mut metadata["bytecode"][key],
+            &dir.join(found),
+            &file,
+            key == "rwasm",
+        )?;
+    }
+
+    let bytecode = &mut metadata["bytecode"];
+    if bytecode.get("hash_algorithm").is_none() {
+        bytecode["hash_algorithm"] = HashAlgorithm::Sha256.name().into();
+    }
+    // Not recorded before these fields existed
+    if metadata.get("builder_version").is_none() {
+        metadata["builder_version"] = UNVERSIONED.into();
+    }
+    if metadata.get("docker").is_none() {
+        metadata["docker"] = false.into();
+    }
+    for pointer in [
+        "/dependencies/cargo_lock_hash",
+        "/toolchain_hash",
+        "/source_tree_hash",
+    ] {
+        if let Some(hash) = metadata.pointer_mut(pointer) {
+            *hash = tag_sha256(hash.as_str().unwrap_or_default()).into();
+        }
+    }
+
+    let parsed: Metadata = serde_json::from_value(metadata).with_context(|| {
+        format!(
+            "{} can't be upgraded to the current metadata format",
+            metadata_file
+        )
+    })?;
+    let after = serde_json::to_string_pretty(&parsed)?;
+    if after != before {
+        migration.changes.push(FileChange {
+            path: PathBuf::from("metadata.json"),
+            before: Some(before),
+            after: Some(after),
+            reason: "add fields required by the current metadata format".to_string(),
+        });
+    }
+
+    if !dir.join(DEPLOY_FILE).is_file() {
+        let wasm_file = migration.renamed_from("lib.wasm");
+        let wasm = std::fs::read(dir.join(wasm_file))?;
+        let abi: Abi = match dir.join("abi.json") {
+            path if path.is_file() => serde_json::from_str(&std::fs::read_to_string(&path)?)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            _ => Vec::new(),
+        };
+        migration.changes.push(FileChange {
+            path: PathBuf::from(DEPLOY_FILE),
+            before: None,
+            after: Some(serde_json::to_string_pretty(&deploy::generate(
+                &wasm, &abi, &parsed,
+            ))?),
+            reason: "derive the deployment payload from lib.wasm".to_string(),
+        });
+    }
+
+    if !dir.join(REPRODUCIBILITY_FILE).is_file() {
+        let project_root = project_root
+            .map(Path::to_path_buf)
+            .or_else(|| dir.parent()?.parent().map(Path::to_path_buf))
+            .filter(|root| root.join("Cargo.toml").is_file());
+        match project_root {
+            Some(root) if sources_unchanged(&root, &parsed)? => {
+                migration.changes.push(FileChange {
+                    path: PathBuf::from(REPRODUCIBILITY_FILE),
+                    before: None,
+                    after: Some(serde_json::to_string_pretty(&reproducibility::evaluate(
+                        &root, &parsed,
+                    )?)?),
+                    reason: "evaluate the reproducibility of the build".to_string(),
+                })
+            }
+            Some(_) => migration.skipped.push(format!(
+                "{}: the project sources changed since the build",
+                REPRODUCIBILITY_FILE
+            )),
+            None => migration.skipped.push(format!(
+                "{}: needs the project sources (pass the project root)",
+                REPRODUCIBILITY_FILE
+            )),
+        }
+    }
+
+    Ok(migration)
+}
+
+impl ArtifactsMigration {
+    /// Current name of a file that is renamed to `file`
+    fn renamed_from<'a>(&'a self, file: &'a str) -> &'a str {
+        self.renames
+            .iter()
+            .find(|(_, to)| to == file)
+            .map_or(file, |(from, _)| from.as_str())
+    }
+}
+
+/// Check a `bytecode.<key>` entry against its file and fill in missing fields
+fn upgrade_artifact_info(info: &mut Value, path: &Path, file: &str, code_hash: bool) -> Result<()> {
+    let actual = HashAlgorithm::Sha256.hash_file(path)?;
+    let recorded = tag_sha256(info["hash"].as_str().unwrap_or_default());
+    if recorded != format!("sha256:{}", actual) {
+        return Err(eyre::eyre!(
+            "{} does not match the hash {} in metadata; refusing to migrate modified artifacts",
+            path.display(),
+            recorded
+        ));
+    }
+    info["hash"] = recorded.into();
+    info["path"] = file.into();
+    if info.get("size").is_none() {
+        info["size"] = std::fs::metadata(path)?.len().into();
+    }
+    if code_hash && info.get("code_hash").is_none() {
+        info["code_hash"] =
+            format!("keccak256:{}", HashAlgorithm::Keccak256.hash_file(path)?).into();
+    }
+    Ok(())
+}
+
+/// Tag a bare sha256 hex digest; tagged and other values are kept
+fn tag_sha256(hash: &str) -> String {
+    let bare = hash.trim_start_matches("0x");
+    if bare.len() == 64 && bare.chars().all(|c| c.is_ascii_hexdigit()) {
+        format!("sha256:{}", bare.to_lowercase())
+    } else {
+        hash.to_string()
+    }
+}
+
+/// Whether the sources still hash to the tree the artifacts were built from
+fn sources_unchanged(project_root: &Path, metadata: &Metadata) -> Result<bool> {
+    let compat = Compat::for_version(&metadata.builder_version)?;
+    Ok(format!("sha256:{}", hash_sources(project_root, &compat)?) == metadata.source_tree_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        artifacts::{generate, save_artifacts, CompiledContract},
+        builder::{ContractInfo, RuntimeInfo, RustInfo, SdkInfo},
+        CompileConfig, ContractArtifacts,
+    };
+
+    /// Artifacts as written by a builder predating deploy.json, tagged hashes and lib.* names
+    ///
+    /// `project` must not be hidden: that builder hashed sources by absolute path and skipped dot
+    /// directories.
+    fn legacy_artifacts(project: &Path) -> PathBuf {
+        std::fs::write(
+            project.join("Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let contract = ContractInfo {
+            name: "token".to_string(),
+            version: "0.1.0".to_string(),
+            target: None,
+        };
+        let runtime_info = RuntimeInfo {
+            rust: RustInfo {
+                version: "1.83.0".to_string(),
+                target: "wasm32-unknown-unknown".to_string(),
+            },
+            sdk: SdkInfo {
+                tag: "v0.1.0".to_string(),
+                commit: "unknown".to_string(),
+            },
+            built_at: 0,
+            source_tree_hash: hash_sources(project, &Compat::for_version(UNVERSIONED).unwrap())
+                .unwrap(),
+            nix_derivation: None,
+            nix_inputs: Vec::new(),
+            docker: false,
+            build_environment: None,
+            workspace_root: None,
+        };
+        let config = CompileConfig::new(project);
+        let (wasm, rwasm) = (b"\0asm\x01\0\0\0".as_slice(), b"rwasm".as_slice());
+        let artifacts = generate(
+            CompiledContract {
+                contract: &contract,
+                wasm,
+                rwasm,
+                routers: &[],
+                declarations: &Default::default(),
+            },
+            project,
+            &config,
+            &runtime_info,
+            super::super::metadata::Source::archive("."),
+        )
+        .unwrap();
+        let dir = save_artifacts(
+            &artifacts,
+            "token",
+            wasm,
+            rwasm,
+            &config.output_directory(),
+            &config.artifacts,
+        )
+        .unwrap()
+        .output_dir;
+
+        let mut metadata: Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("metadata.json")).unwrap())
+                .unwrap();
+        let bytecode = metadata["bytecode"].as_object_mut().unwrap();
+        bytecode.remove("hash_algorithm");
+        for (key, bytes) in [("wasm", wasm), ("rwasm", rwasm)] {
+            let info = bytecode[key].as_object_mut().unwrap();
+            info.insert("hash".to_string(), HashAlgorithm::Sha256.hash(bytes).into());
+            info.insert("path".to_string(), format!("token.{}", key).into());
+            info.remove("code_hash");
+            std::fs::rename(
+                dir.join(format!("lib.{}", key)),
+                dir.join(format!("token.{}", key)),
+            )
+            .unwrap();
+        }
+        let fields = metadata.as_object_mut().unwrap();
+        fields.remove("builder_version");
+        fields.remove("docker");
+        std::fs::write(dir.join(LEGACY_METADATA_FILE), metadata.to_string()).unwrap();
+        for file in ["metadata.json", DEPLOY_FILE, REPRODUCIBILITY_FILE] {
+            std::fs::remove_file(dir.join(file)).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_migrate_legacy_layout() {
+        let project = tempfile::Builder::new().prefix("token").tempdir().unwrap();
+        let dir = legacy_artifacts(project.path());
+        assert!(ContractArtifacts::load(&dir).is_err());
+
+        let migration = plan_artifacts_migration(&dir, None).unwrap();
+        assert_eq!(migration.renames.len(), 3);
+        assert!(migration.skipped.is_empty(), "{:?}", migration.skipped);
+        migration.apply().unwrap();
+
+        let loaded = ContractArtifacts::load(&dir).unwrap();
+        assert_eq!(loaded.metadata.builder_version, UNVERSIONED);
+        assert_eq!(
+            loaded.metadata.bytecode.rwasm.hash,
+            HashAlgorithm::Sha256.tagged(b"rwasm")
+        );
+        assert_eq!(
+            loaded.metadata.bytecode.rwasm.code_hash,
+            Some(HashAlgorithm::Keccak256.tagged(b"rwasm"))
+        );
+        assert!(plan_artifacts_migration(&dir, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_refuses_modified_bytecode() {
+        let project = tempfile::Builder::new().prefix("token").tempdir().unwrap();
+        let dir = legacy_artifacts(project.path());
+        std::fs::write(dir.join("token.rwasm"), b"tampered").unwrap();
+        let err = plan_artifacts_migration(&dir, None).unwrap_err();
+        assert!(err.to_string().contains("refusing"), "{}", err);
+
+        // Sources changed since the build, so the scorecard is left out rather than guessed
+        std::fs::write(dir.join("token.rwasm"), b"rwasm").unwrap();
+        std::fs::write(project.path().join("lib.rs"), "// changed").unwrap();
+        let migration = plan_artifacts_migration(&dir, None).unwrap();
+        assert_eq!(migration.skipped.len(), 1);
+    }
+}
@@ -1,44 +1,124 @@
 //! Artifact generation for compiled contracts
 
+#[cfg(feature = "build")]
 use crate::{
-    builder::{hash_bytes, ContractInfo, RuntimeInfo},
-    config::CompileConfig,
+    builder::{ContractInfo, RuntimeInfo},
+    cleanup::CleanupGuard,
+    config::{AbiNaming, CompileConfig, SelectorCollisions},
+    parser::{Declarations, RouterFacet},
+    HashAlgorithm,
 };
+#[cfg(feature = "build")]
+use convert_case::{Case, Casing};
+#[cfg(feature = "build")]
 use eyre::{Context, Result};
-use serde_json::Value;
+#[cfg(feature = "build")]
 use sha2::{Digest, Sha256};
-use sha3::Keccak256;
 use std::collections::BTreeMap;
+#[cfg(feature = "build")]
 use std::path::{Path, PathBuf};
 
 pub mod abi;
+pub mod alloy;
+pub mod bindgen;
+pub mod conformance;
+#[cfg(feature = "build")]
+pub mod deploy;
+pub mod human_readable;
 pub mod interface;
+#[cfg(feature = "build")]
 pub mod metadata;
+#[cfg(feature = "build")]
+pub mod migrate;
+#[cfg(feature = "build")]
+pub mod nondeterminism;
+#[cfg(feature = "build")]
+pub mod reproducibility;
+pub mod sanitize;
+pub mod typescript;
 
-/// Solidity ABI represented as JSON values
-pub type Abi = Vec<Value>;
+pub use abi::{
+    Abi, AbiConstructor, AbiEntry, AbiError, AbiEvent, AbiFallback, AbiFunction, Param,
+    StateMutability,
+};
 
+#[cfg(feature = "build")]
 /// All artifacts generated for a compiled contract
 #[derive(Debug)]
 pub struct ContractArtifacts {
+    /// Combined ABI of all routers
     pub abi: Abi,
     pub interface: String,
+    /// Per-router outputs, only for contracts exposing more than one router
+    pub facets: Vec<FacetArtifacts>,
     pub metadata: metadata::Metadata,
+    /// Reproducibility scorecard; missing in artifacts saved before it was added
+    pub reproducibility: Option<reproducibility::ReproducibilityScorecard>,
+    pub deploy: deploy::DeployArtifact,
+    /// Cargo.lock the contract was built with, saved next to the artifacts
+    pub cargo_lock: Option<String>,
 }
 
+#[cfg(feature = "build")]
+/// ABI and interface of a single router of a multi-router contract
+#[derive(Debug)]
+pub struct FacetArtifacts {
+    pub name: String,
+    pub abi: Abi,
+    pub interface: String,
+    /// Selectors of functions renamed in the ABI, see [`abi::dispatched_selectors`]
+    pub dispatched_selectors: BTreeMap<String, String>,
+}
+
+#[cfg(feature = "build")]
+impl FacetArtifacts {
+    /// ABI file name, e.g. `abi.token_api.json`
+    pub fn abi_file_name(&self) -> String {
+        format!("abi.{}.json", self.name.to_case(Case::Snake))
+    }
+
+    /// Interface file name, e.g. `ITokenApi.sol`
+    pub fn interface_file_name(&self) -> String {
+        format!("I{}.sol", self.name.to_case(Case::Pascal))
+    }
+}
+
+#[cfg(feature = "build")]
+/// A compiled contract and what its sources declare, the input of [`generate`]
+#[derive(Debug, Clone, Copy)]
+pub struct CompiledContract<'a> {
+    pub contract: &'a ContractInfo,
+    pub wasm: &'a [u8],
+    pub rwasm: &'a [u8],
+    pub routers: &'a [RouterFacet],
+    pub declarations: &'a Declarations,
+}
+
+#[cfg(feature = "build")]
 /// Generate all artifacts from compilation data
 pub fn generate(
-    contract: &ContractInfo,
-    wasm: &[u8],
-    rwasm: &[u8],
-    routers: &[fluentbase_sdk_derive_core::router::Router],
+    compiled: CompiledContract<'_>,
     project_root: &Path,
     config: &CompileConfig,
     runtime_info: &RuntimeInfo,
     source: metadata::Source,
 ) -> Result<ContractArtifacts> {
-    // Generate ABI
-    let abi = abi::generate(routers)?;
+    let CompiledContract {
+        contract,
+        wasm,
+        routers,
+        declarations,
+        ..
+    } = compiled;
+
+    // Generate ABI, with Rust names renamed by the naming policy
+    let naming = config.artifacts.abi_naming;
+    check_selector_collisions(routers, config.artifacts.selector_collisions)?;
+    let rust_abi = abi::generate(routers, declarations)?;
+    let mut abi = abi::apply_naming(&rust_abi, naming);
+    let sanitizer = sanitize::Sanitizer::new(&abi);
+    let renamed_identifiers = sanitizer.apply(&mut abi)?;
+    let dispatched_selectors = abi::dispatched_selectors(&rust_abi, &abi);
 
     // Generate Solidity interface
     let interface = if !abi.is_empty() {
@@ -47,38 +127,133 @@ pub fn generate(
         String::new()
     };
 
+    // Namespace each router separately when the contract exposes several
+    let facets = if routers.len() > 1 {
+        generate_facets(routers, naming, &sanitizer)?
+    } else {
+        Vec::new()
+    };
+
     // Create metadata
-    let metadata = create_metadata(
-        contract,
-        config,
-        runtime_info,
-        wasm,
-        rwasm,
-        &abi,
-        project_root,
-        source,
-    )?;
+    let mut metadata = create_metadata(compiled, &abi, project_root, config, runtime_info, source)?;
+    if let Some(solidity) = &mut metadata.solidity_compatibility {
+        solidity.facets = facets.iter().map(facet_info).collect();
+        solidity.naming = naming;
+        solidity.renamed_identifiers = renamed_identifiers;
+        // What the router dispatches on, not what the renamed signature hashes to
+        solidity
+            .function_selectors
+            .extend(dispatched_selectors.clone());
+        solidity.dispatched_selectors = dispatched_selectors;
+    }
+    let reproducibility = reproducibility::evaluate(project_root, &metadata)?;
+    metadata.reproducibility_warnings = reproducibility.warnings.clone();
+    let deploy = deploy::generate(wasm, &abi, &metadata);
+    let cargo_lock = std::fs::read_to_string(crate::builder::cargo_lock_path(project_root)).ok();
 
     Ok(ContractArtifacts {
         abi,
         interface,
+        facets,
         metadata,
+        reproducibility: Some(reproducibility),
+        deploy,
+        cargo_lock,
     })
 }
 
+#[cfg(feature = "build")]
+/// Fail or warn, per `policy`, when router functions share the selector they are dispatched on
+fn check_selector_collisions(routers: &[RouterFacet], policy: SelectorCollisions) -> Result<()> {
+    let descriptions = abi::router_collisions(routers)?;
+    match policy {
+        _ if descriptions.is_empty() => Ok(()),
+        SelectorCollisions::Error => Err(eyre::eyre!(
+            "Selector collision, calls would be misrouted: {}; rename the functions or build with \
+             --selector-collisions warn",
+            descriptions.join("; ")
+        )),
+        SelectorCollisions::Warn => {
+            for description in descriptions {
+                tracing::warn!(
+                    "Selector collision, calls may be misrouted: {}",
+                    description
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "build")]
+/// Generate ABI and interface of each router
+fn generate_facets(
+    routers: &[RouterFacet],
+    naming: AbiNaming,
+    sanitizer: &sanitize::Sanitizer,
+) -> Result<Vec<FacetArtifacts>> {
+    routers
+        .iter()
+        .map(|facet| {
+            let rust_abi = abi::generate_router(&facet.router)?;
+            let mut abi = abi::apply_naming(&rust_abi, naming);
+            sanitizer.apply(&mut abi)?;
+            let dispatched_selectors = abi::dispatched_selectors(&rust_abi, &abi);
+            let interface = if abi.is_empty() {
+                String::new()
+            } else {
+                interface::generate(&facet.name, &abi)?
+            };
+            Ok(FacetArtifacts {
+                name: facet.name.clone(),
+                abi,
+                interface,
+                dispatched_selectors,
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "build")]
+/// Metadata entry of a router
+fn facet_info(facet: &FacetArtifacts) -> metadata::FacetInfo {
+    metadata::FacetInfo {
+        name: facet.name.clone(),
+        abi_path: facet.abi_file_name(),
+        interface_path: facet.interface_file_name(),
+        function_selectors: extract_function_selectors(&facet.abi)
+            .into_iter()
+            .chain(facet.dispatched_selectors.clone())
+            .collect(),
+        event_topics: extract_event_topics(&facet.abi),
+        dispatched_selectors: facet.dispatched_selectors.clone(),
+    }
+}
+
+#[cfg(feature = "build")]
 /// Create metadata structure
 fn create_metadata(
-    contract: &ContractInfo,
-    config: &CompileConfig,
-    runtime_info: &RuntimeInfo,
-    wasm: &[u8],
-    rwasm: &[u8],
+    compiled: CompiledContract<'_>,
     abi: &Abi,
     project_root: &Path,
+    config: &CompileConfig,
+    runtime_info: &RuntimeInfo,
     source: metadata::Source,
 ) -> Result<metadata::Metadata> {
+    let CompiledContract {
+        contract,
+        wasm,
+        rwasm,
+        ..
+    } = compiled;
+
     // Calculate Cargo.lock hash
-    let cargo_lock_hash = calculate_cargo_lock_hash(project_root)?;
+    // Workspace members share the Cargo.lock of the workspace
+    let lock_dir = match &runtime_info.workspace_root {
+        Some(workspace_root) => project_root.join(workspace_root),
+        None => project_root.to_path_buf(),
+    };
+    let cargo_lock_hash = calculate_cargo_lock_hash(&lock_dir, config.strict)?;
 
     // Calculate toolchain hash
     let toolchain_hash = calculate_toolchain_hash(
@@ -98,15 +273,18 @@ fn create_metadata(
         },
         built_at: runtime_info.built_at,
         bytecode: metadata::BytecodeInfo {
+            hash_algorithm: HashAlgorithm::Sha256,
             wasm: metadata::ArtifactInfo {
-                hash: format!("sha256:{}", hash_bytes(wasm)),
+                hash: HashAlgorithm::Sha256.tagged(wasm),
                 size: wasm.len(),
                 path: "lib.wasm".to_string(),
+                code_hash: None,
             },
             rwasm: metadata::ArtifactInfo {
-                hash: format!("sha256:{}", hash_bytes(rwasm)),
+                hash: HashAlgorithm::Sha256.tagged(rwasm),
                 size: rwasm.len(),
                 path: "lib.rwasm".to_string(),
+                code_hash: Some(HashAlgorithm::Keccak256.tagged(rwasm)),
             },
         },
         solidity_compatibility: if abi.is_empty() {
@@ -116,28 +294,46 @@ fn create_metadata(
                 abi_path: "abi.json".to_string(),
                 interface_path: "interface.sol".to_string(),
                 function_selectors: extract_function_selectors(abi),
+                event_topics: extract_event_topics(abi),
+                error_selectors: extract_error_selectors(abi),
+                facets: Vec::new(),
+                naming: AbiNaming::default(),
+                renamed_identifiers: Vec::new(),
+                dispatched_selectors: BTreeMap::new(),
             })
         },
         dependencies: metadata::Dependencies {
             cargo_lock_hash: format!("sha256:{}", cargo_lock_hash),
         },
-        workspace_root: None,
+        workspace_root: runtime_info.workspace_root.clone(),
         toolchain_hash,
         source_tree_hash: format!("sha256:{}", runtime_info.source_tree_hash),
+        nix_derivation: runtime_info.nix_derivation.clone(),
+        nix_inputs: runtime_info.nix_inputs.clone(),
+        docker: runtime_info.docker,
+        build_environment: runtime_info.build_environment.clone(),
+        reproducibility_warnings: Vec::new(),
+        builder_version: crate::VERSION.to_string(),
     })
 }
 
-/// Calculate Cargo.lock hash
-fn calculate_cargo_lock_hash(project_root: &Path) -> Result<String> {
+#[cfg(feature = "build")]
+/// Calculate Cargo.lock hash; strict builds require a Cargo.lock
+fn calculate_cargo_lock_hash(project_root: &Path, strict: bool) -> Result<String> {
     let cargo_lock_path = project_root.join("Cargo.lock");
     if cargo_lock_path.exists() {
-        let content = std::fs::read(&cargo_lock_path)?;
-        Ok(hash_bytes(&content))
+        HashAlgorithm::Sha256.hash_file(&cargo_lock_path)
+    } else if strict {
+        Err(eyre::eyre!(
+            "No Cargo.lock in {} (strict mode); run `cargo generate-lockfile` and commit it",
+            project_root.display()
+        ))
     } else {
         Ok("no-cargo-lock".to_string())
     }
 }
 
+#[cfg(feature = "build")]
 /// Calculate combined toolchain hash
 fn calculate_toolchain_hash(rustc_version: &str, sdk_tag: &str, sdk_commit: &str) -> String {
     let mut hasher = Sha256::new();
@@ -151,37 +347,61 @@ fn calculate_toolchain_hash(rustc_version: &str, sdk_tag: &str, sdk_commit: &str
 fn extract_function_selectors(abi: &Abi) -> BTreeMap<String, String> {
     let mut selectors = BTreeMap::new();
 
-    for func in abi.iter().filter(|e| e["type"] == "function") {
-        if let Some(name) = func["name"].as_str() {
-            let empty_vec = vec![];
-            let inputs = func["inputs"].as_array().unwrap_or(&empty_vec);
-            let types: Vec<String> = inputs
-                .iter()
-                .filter_map(|i| i["type"].as_str())
-                .map(|s| s.to_string())
-                .collect();
-
-            let signature = format!("{}({})", name, types.join(","));
-            let hash = Keccak256::digest(signature.as_bytes());
-            let selector = format!("0x{}", hex::encode(&hash[..4]));
-
-            selectors.insert(signature, selector);
-        }
+    for func in abi.iter().filter_map(AbiEntry::as_function) {
+        let signature = func.signature();
+        let selector = abi::selector(&signature);
+        selectors.insert(signature, selector);
     }
 
     selectors
 }
 
+/// 4-byte selectors of the custom errors of an ABI, which revert data starts with
+pub fn extract_error_selectors(abi: &Abi) -> BTreeMap<String, String> {
+    abi.iter()
+        .filter(|e| matches!(e, AbiEntry::Error(_)))
+        .filter_map(abi::signature)
+        .map(|signature| {
+            let selector = abi::selector(&signature);
+            (signature, selector)
+        })
+        .collect()
+}
+
+/// topic0 hashes of the events of an ABI; anonymous events have none
+pub fn extract_event_topics(abi: &Abi) -> BTreeMap<String, String> {
+    abi.iter()
+        .filter(|e| matches!(e, AbiEntry::Event(event) if !event.anonymous))
+        .filter_map(abi::signature)
+        .map(|signature| {
+            let topic = abi::topic(&signature);
+            (signature, topic)
+        })
+        .collect()
+}
+
+#[cfg(feature = "build")]
 /// Information about saved artifact files
 pub struct SavedPaths {
     pub output_dir: PathBuf,
     pub wasm_path: PathBuf,
     pub rwasm_path: PathBuf,
     pub abi_path: Option<PathBuf>,
+    /// abi.json as one human-readable declaration per line
+    pub human_readable_abi_path: Option<PathBuf>,
+    /// abi.json as a typed TypeScript module
+    pub typescript_bindings_path: Option<PathBuf>,
+    /// abi.json as an alloy `sol!` interface
+    pub rust_bindings_path: Option<PathBuf>,
     pub interface_path: Option<PathBuf>,
+    /// Per-router ABI and interface files of multi-router contracts
+    pub facet_paths: Vec<PathBuf>,
     pub metadata_path: Option<PathBuf>,
+    pub reproducibility_path: Option<PathBuf>,
+    pub deploy_path: PathBuf,
 }
 
+#[cfg(feature = "build")]
 /// Save artifacts to disk
 pub fn save_artifacts(
     artifacts: &ContractArtifacts,
@@ -192,7 +412,11 @@ pub fn save_artifacts(
     config: &crate::config::ArtifactsConfig,
 ) -> Result<SavedPaths> {
     // Create contract-specific directory
-    let contract_dir = output_dir.join(format!("{}.wasm", contract_name));
+    let contract_dir = config.artifacts_dir(output_dir, contract_name, rwasm);
+
+    // Don't leave a half-written directory behind if saving fails
+    let partial_guard = (!contract_dir.exists()).then(|| CleanupGuard::remove_path(&contract_dir));
+
     std::fs::create_dir_all(&contract_dir)
         .with_context(|| format!("Failed to create directory: {}", contract_dir.display()))?;
 
@@ -203,13 +427,30 @@ pub fn save_artifacts(
     let rwasm_path = contract_dir.join("lib.rwasm");
     std::fs::write(&rwasm_path, rwasm)?;
 
+    let deploy_path = contract_dir.join(deploy::DEPLOY_FILE);
+    std::fs::write(
+        &deploy_path,
+        serde_json::to_string_pretty(&artifacts.deploy)?,
+    )?;
+
+    // Dependency versions of the build, for comparing it with a later rebuild
+    if let Some(cargo_lock) = &artifacts.cargo_lock {
+        std::fs::write(contract_dir.join(CARGO_LOCK_FILE), cargo_lock)?;
+    }
+
     let mut saved = SavedPaths {
         output_dir: contract_dir.clone(),
         wasm_path,
         rwasm_path,
         abi_path: None,
+        human_readable_abi_path: None,
+        typescript_bindings_path: None,
+        rust_bindings_path: None,
         interface_path: None,
+        facet_paths: Vec::new(),
         metadata_path: None,
+        reproducibility_path: None,
+        deploy_path,
     };
 
     // Save ABI if requested and not empty
@@ -222,6 +463,26 @@ pub fn save_artifacts(
         };
         std::fs::write(&abi_path, json)?;
         saved.abi_path = Some(abi_path);
+
+        let human_readable_path = contract_dir.join(human_readable::HUMAN_READABLE_ABI_FILE);
+        let mut declarations = human_readable::format(&artifacts.abi).join("\n");
+        declarations.push('\n');
+        std::fs::write(&human_readable_path, declarations)?;
+        saved.human_readable_abi_path = Some(human_readable_path);
+
+        let bindings_path = contract_dir.join(typescript::TYPESCRIPT_BINDINGS_FILE);
+        std::fs::write(
+            &bindings_path,
+            typescript::generate_typescript_bindings(contract_name, &artifacts.abi)?,
+        )?;
+        saved.typescript_bindings_path = Some(bindings_path);
+
+        let bindings_path = contract_dir.join(alloy::RUST_BINDINGS_FILE);
+        std::fs::write(
+            &bindings_path,
+            alloy::generate_alloy_bindings(contract_name, &artifacts.abi)?,
+        )?;
+        saved.rust_bindings_path = Some(bindings_path);
     }
 
     // Save interface if requested and not empty
@@ -231,7 +492,26 @@ pub fn save_artifacts(
         saved.interface_path = Some(interface_path);
     }
 
-    // Save metadata if requested
+    // Save per-router outputs of multi-router contracts
+    for facet in &artifacts.facets {
+        if config.generate_abi && !facet.abi.is_empty() {
+            let path = contract_dir.join(facet.abi_file_name());
+            let json = if config.pretty_json {
+                serde_json::to_string_pretty(&facet.abi)?
+            } else {
+                serde_json::to_string(&facet.abi)?
+            };
+            std::fs::write(&path, json)?;
+            saved.facet_paths.push(path);
+        }
+        if config.generate_interface && !facet.interface.is_empty() {
+            let path = contract_dir.join(facet.interface_file_name());
+            std::fs::write(&path, &facet.interface)?;
+            saved.facet_paths.push(path);
+        }
+    }
+
+    // Save metadata and the reproducibility scorecard if requested
     if config.generate_metadata {
         let metadata_path = contract_dir.join("metadata.json");
         let json = if config.pretty_json {
@@ -241,9 +521,405 @@ pub fn save_artifacts(
         };
         std::fs::write(&metadata_path, json)?;
         saved.metadata_path = Some(metadata_path);
+
+        if let Some(scorecard) = &artifacts.reproducibility {
+            let reproducibility_path = contract_dir.join(reproducibility::REPRODUCIBILITY_FILE);
+            std::fs::write(
+                &reproducibility_path,
+                serde_json::to_string_pretty(scorecard)?,
+            )?;
+            saved.reproducibility_path = Some(reproducibility_path);
+        }
+    }
+
+    if config.content_addressed_output {
+        link_latest(&contract_dir)?;
     }
 
+    if let Some(guard) = partial_guard {
+        guard.disarm();
+    }
     tracing::info!("✅ Artifacts saved to: {}", contract_dir.display());
 
     Ok(saved)
 }
+
+#[cfg(feature = "build")]
+/// Copy of the build's Cargo.lock in an artifacts directory
+pub const CARGO_LOCK_FILE: &str = "Cargo.lock";
+
+#[cfg(feature = "build")]
+/// Name of the symlink to the newest content-addressed artifacts directory
+pub const LATEST_LINK: &str = "latest";
+
+#[cfg(feature = "build")]
+/// Point `<name>/latest` at a content-addressed artifacts directory
+///
+/// The link is replaced by a rename, so readers never see it missing.
+fn link_latest(contract_dir: &Path) -> Result<()> {
+    let (Some(parent), Some(target)) = (contract_dir.parent(), contract_dir.file_name()) else {
+        return Ok(());
+    };
+    let link = parent.join(LATEST_LINK);
+    let staged = parent.join(format!(".{}.{}", LATEST_LINK, std::process::id()));
+    let _ = std::fs::remove_file(&staged);
+
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(target, &staged);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_dir(target, &staged);
+    #[cfg(not(any(unix, windows)))]
+    let linked: std::io::Result<()> = Err(std::io::ErrorKind::Unsupported.into());
+
+    if let Err(e) = linked {
+        // e.g. Windows without symlink privileges; the hash directory is still complete
+        tracing::warn!("Failed to link {}: {}", link.display(), e);
+        return Ok(());
+    }
+    std::fs::rename(&staged, &link).with_context(|| format!("Failed to update {}", link.display()))
+}
+
+#[cfg(feature = "build")]
+impl SavedPaths {
+    /// Find the files of an artifacts directory written by [`save_artifacts`]
+    pub fn discover(dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(eyre::eyre!(
+                "Artifacts directory not found: {}",
+                dir.display()
+            ));
+        }
+        let required = |name: &str| {
+            let path = dir.join(name);
+            if path.is_file() {
+                Ok(path)
+            } else {
+                Err(eyre::eyre!("No {} in {}", name, dir.display()))
+            }
+        };
+        let optional = |name: &str| Some(dir.join(name)).filter(|path| path.is_file());
+
+        let mut facet_paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or_default();
+                (name.starts_with("abi.") && name.ends_with(".json") && name != "abi.json")
+                    || (name.starts_with('I') && name.ends_with(".sol"))
+            })
+            .collect();
+        facet_paths.sort();
+
+        Ok(Self {
+            output_dir: dir.to_path_buf(),
+            wasm_path: required("lib.wasm")?,
+            rwasm_path: required("lib.rwasm")?,
+            abi_path: optional("abi.json"),
+            human_readable_abi_path: optional(human_readable::HUMAN_READABLE_ABI_FILE),
+            typescript_bindings_path: optional(typescript::TYPESCRIPT_BINDINGS_FILE),
+            rust_bindings_path: optional(alloy::RUST_BINDINGS_FILE),
+            interface_path: optional("interface.sol"),
+            facet_paths,
+            metadata_path: optional("metadata.json"),
+            reproducibility_path: optional(reproducibility::REPRODUCIBILITY_FILE),
+            deploy_path: required(deploy::DEPLOY_FILE)?,
+        })
+    }
+}
+
+#[cfg(feature = "build")]
+impl ContractArtifacts {
+    /// Read back an artifacts directory written by [`save_artifacts`]
+    ///
+    /// Fails unless the bytecode matches the hashes in metadata.json and the
+    /// init code in deploy.json, so consumers never get mismatched files.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let paths = SavedPaths::discover(dir)?;
+        let metadata_path = paths.metadata_path.as_deref().ok_or_else(|| {
+            eyre::eyre!(
+                "No metadata.json in {}; save artifacts with metadata",
+                dir.display()
+            )
+        })?;
+        let metadata: metadata::Metadata = read_json_file(metadata_path)?;
+
+        let wasm = std::fs::read(&paths.wasm_path)?;
+        let rwasm = std::fs::read(&paths.rwasm_path)?;
+        for (file, bytes, recorded) in [
+            ("lib.wasm", &wasm, &metadata.bytecode.wasm.hash),
+            ("lib.rwasm", &rwasm, &metadata.bytecode.rwasm.hash),
+        ] {
+            let (algorithm, digest) = HashAlgorithm::split_tagged(recorded);
+            let algorithm = algorithm.unwrap_or(metadata.bytecode.hash_algorithm);
+            if algorithm.hash(bytes) != digest {
+                return Err(eyre::eyre!(
+                    "{} in {} does not match the hash {} in metadata.json",
+                    file,
+                    dir.display(),
+                    recorded
+                ));
+            }
+        }
+
+        let deploy: deploy::DeployArtifact = read_json_file(&paths.deploy_path)?;
+        if deploy.deployment_data(&[])? != wasm || deploy.rwasm_hash != metadata.bytecode.rwasm.hash
+        {
+            return Err(eyre::eyre!(
+                "{} in {} does not match lib.wasm",
+                deploy::DEPLOY_FILE,
+                dir.display()
+            ));
+        }
+
+        let reproducibility = match &paths.reproducibility_path {
+            Some(path) => Some(read_json_file(path)?),
+            None => None,
+        };
+        let abi = match &paths.abi_path {
+            Some(path) => read_json_file(path)?,
+            None => Vec::new(),
+        };
+        let interface = match &paths.interface_path {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => String::new(),
+        };
+
+        let mut facets = Vec::new();
+        for facet in metadata
+            .solidity_compatibility
+            .iter()
+            .flat_map(|s| &s.facets)
+        {
+            let abi_path = dir.join(&facet.abi_path);
+            let interface_path = dir.join(&facet.interface_path);
+            facets.push(FacetArtifacts {
+                name: facet.name.clone(),
+                abi: if abi_path.is_file() {
+                    read_json_file(&abi_path)?
+                } else {
+                    Vec::new()
+                },
+                interface: std::fs::read_to_string(&interface_path).unwrap_or_default(),
+                dispatched_selectors: facet.dispatched_selectors.clone(),
+            });
+        }
+
+        Ok(Self {
+            abi,
+            interface,
+            facets,
+            metadata,
+            reproducibility,
+            deploy,
+            cargo_lock: std::fs::read_to_string(dir.join(CARGO_LOCK_FILE)).ok(),
+        })
+    }
+}
+
+#[cfg(feature = "build")]
+fn read_json_file<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(all(test, feature = "build"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_facet_file_names() {
+        let facet = FacetArtifacts {
+            name: "TokenAPI".to_string(),
+            abi: Vec::new(),
+            interface: String::new(),
+            dispatched_selectors: BTreeMap::new(),
+        };
+        assert_eq!(facet.abi_file_name(), "abi.token_api.json");
+        assert_eq!(facet.interface_file_name(), "ITokenApi.sol");
+    }
+
+    /// Artifacts of a `token` contract in `project` with the given bytecode
+    fn token_artifacts(
+        project: &Path,
+        wasm: &[u8],
+        rwasm: &[u8],
+    ) -> (ContractArtifacts, CompileConfig) {
+        let contract = ContractInfo {
+            name: "token".to_string(),
+            version: "0.1.0".to_string(),
+            target: None,
+        };
+        let runtime_info = RuntimeInfo {
+            rust: crate::builder::RustInfo {
+                version: "1.83.0".to_string(),
+                target: "wasm32-unknown-unknown".to_string(),
+            },
+            sdk: crate::builder::SdkInfo {
+                tag: "v0.1.0".to_string(),
+                commit: "unknown".to_string(),
+            },
+            built_at: 0,
+            source_tree_hash: String::new(),
+            nix_derivation: None,
+            nix_inputs: Vec::new(),
+            docker: false,
+            build_environment: None,
+            workspace_root: None,
+        };
+        std::fs::write(
+            project.join("Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        let config = CompileConfig::new(project);
+        let artifacts = generate(
+            CompiledContract {
+                contract: &contract,
+                wasm,
+                rwasm,
+                routers: &[],
+                declarations: &Default::default(),
+            },
+            project,
+            &config,
+            &runtime_info,
+            metadata::Source::archive("."),
+        )
+        .unwrap();
+        (artifacts, config)
+    }
+
+    #[test]
+    fn test_load_saved_artifacts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (wasm, rwasm) = (b"\0asm\x01\0\0\0".as_slice(), b"rwasm".as_slice());
+        std::fs::write(dir.path().join("Cargo.lock"), "version = 3\n").unwrap();
+        let (artifacts, config) = token_artifacts(dir.path(), wasm, rwasm);
+        let saved = save_artifacts(
+            &artifacts,
+            "token",
+            wasm,
+            rwasm,
+            dir.path(),
+            &config.artifacts,
+        )
+        .unwrap();
+
+        let loaded = ContractArtifacts::load(&saved.output_dir).unwrap();
+        assert_eq!(loaded.deploy, artifacts.deploy);
+        assert_eq!(loaded.cargo_lock.as_deref(), Some("version = 3\n"));
+        assert_eq!(
+            loaded.metadata.bytecode.rwasm.hash,
+            artifacts.metadata.bytecode.rwasm.hash
+        );
+        assert_eq!(
+            SavedPaths::discover(&saved.output_dir)
+                .unwrap()
+                .metadata_path,
+            saved.metadata_path
+        );
+
+        // Artifacts of schema version 1 saved before these fields and files were added
+        let metadata_path = saved.metadata_path.as_ref().unwrap();
+        let mut metadata: serde_json::Value = read_json_file(metadata_path).unwrap();
+        for field in ["docker", "builder_version", "reproducibility_warnings"] {
+            metadata.as_object_mut().unwrap().remove(field);
+        }
+        metadata["bytecode"]
+            .as_object_mut()
+            .unwrap()
+            .remove("hash_algorithm");
+        std::fs::write(metadata_path, metadata.to_string()).unwrap();
+        std::fs::remove_file(saved.reproducibility_path.as_ref().unwrap()).unwrap();
+        let loaded = ContractArtifacts::load(&saved.output_dir).unwrap();
+        assert_eq!(loaded.metadata.schema_version, 1);
+        assert_eq!(
+            loaded.metadata.bytecode.hash_algorithm,
+            HashAlgorithm::Sha256
+        );
+        assert!(loaded.reproducibility.is_none());
+
+        std::fs::write(&saved.rwasm_path, b"tampered").unwrap();
+        let err = ContractArtifacts::load(&saved.output_dir).unwrap_err();
+        assert!(err.to_string().contains("lib.rwasm"), "{}", err);
+    }
+
+    #[test]
+    fn test_rebuild_from_metadata() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (mut artifacts, _) = token_artifacts(dir.path(), b"\0asm\x01\0\0\0", b"rwasm");
+        let build_cfg = &mut artifacts.metadata.compilation_settings.build_cfg;
+        build_cfg.features = vec!["testnet".to_string()];
+        build_cfg.network = Some("testnet".to_string());
+        build_cfg.extra_cargo_args = vec![
+            "--config".to_string(),
+            "profile.release.lto=true".to_string(),
+        ];
+        build_cfg.rustflags = Some("-C target-feature=+bulk-memory".to_string());
+
+        let mut config = CompileConfig::new(dir.path());
+        config.profile = "debug".to_string();
+        build_cfg.apply(&mut config);
+        assert_eq!(config.profile, "release");
+        assert_eq!(config.features, ["testnet"]);
+        assert_eq!(config.network.as_deref(), Some("testnet"));
+        assert_eq!(config.extra_cargo_args, build_cfg.extra_cargo_args);
+        assert_eq!(config.rustflags, build_cfg.rustflags);
+
+        let metadata = &artifacts.metadata;
+        std::fs::write(dir.path().join("rust-toolchain"), "1.83.0\n").unwrap();
+        assert!(metadata.source_differences(dir.path()).is_empty());
+        std::fs::write(dir.path().join("rust-toolchain"), "1.84.0\n").unwrap();
+        assert_eq!(
+            metadata.source_differences(dir.path()),
+            ["Rust toolchain is 1.84.0, the recorded build used 1.83.0"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_content_addressed_output() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let wasm = b"\0asm\x01\0\0\0".as_slice();
+        let mut saved = Vec::new();
+        for rwasm in [b"rwasm v1".as_slice(), b"rwasm v2".as_slice()] {
+            let (artifacts, mut config) = token_artifacts(dir.path(), wasm, rwasm);
+            config.artifacts.content_addressed_output = true;
+            saved.push(
+                save_artifacts(
+                    &artifacts,
+                    "token",
+                    wasm,
+                    rwasm,
+                    dir.path(),
+                    &config.artifacts,
+                )
+                .unwrap(),
+            );
+        }
+
+        // Both builds are kept, and latest points at the second
+        let hash = HashAlgorithm::Sha256.hash(b"rwasm v1");
+        assert_eq!(
+            saved[0].output_dir,
+            dir.path().join("token").join(&hash[..12])
+        );
+        assert!(saved[0].rwasm_path.is_file());
+        let latest = dir.path().join("token").join(LATEST_LINK);
+        assert_eq!(
+            std::fs::read_link(&latest).unwrap(),
+            saved[1].output_dir.file_name().unwrap()
+        );
+        assert_eq!(
+            ContractArtifacts::load(&latest).unwrap().deploy,
+            ContractArtifacts::load(&saved[1].output_dir)
+                .unwrap()
+                .deploy
+        );
+    }
+}
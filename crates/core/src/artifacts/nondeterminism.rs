@@ -0,0 +1,341 @@
+//! Static scan for code that makes builds depend on the build machine
+//!
+//! Looks through the build script and `src/` for environment variable reads,
+//! timestamps, randomness and includes of generated files, the usual causes of
+//! verification failures that are not explained by the recorded build settings.
+
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use syn::{punctuated::Punctuated, spanned::Spanned, visit::Visit, Expr, Token};
+
+/// Environment variables cargo sets for every build
+const CARGO_BUILD_ENV: &[&str] = &[
+    "OUT_DIR",
+    "TARGET",
+    "HOST",
+    "PROFILE",
+    "OPT_LEVEL",
+    "DEBUG",
+    "NUM_JOBS",
+    "RUSTC",
+    "RUSTDOC",
+    "RUSTC_LINKER",
+    "RUSTC_WRAPPER",
+];
+
+/// What makes the flagged code non-deterministic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NondeterminismKind {
+    /// Reads an environment variable cargo does not control
+    EnvVar,
+    /// Reads the current time
+    Timestamp,
+    /// Uses random values
+    Randomness,
+    /// Includes a file that is generated or not part of the sources
+    GeneratedInclude,
+}
+
+/// A code location that may make the build differ between machines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReproducibilityWarning {
+    pub kind: NondeterminismKind,
+    /// File relative to the project root
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ReproducibilityWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+/// Scan the build script and sources of a package
+pub fn scan(project_root: &Path, manifest: &toml::Value) -> Result<Vec<ReproducibilityWarning>> {
+    let mut warnings = Vec::new();
+
+    let build_script = match manifest.get("package").and_then(|p| p.get("build")) {
+        Some(toml::Value::String(path)) => Some(project_root.join(path)),
+        Some(toml::Value::Boolean(false)) => None,
+        _ => Some(project_root.join("build.rs")),
+    };
+    if let Some(build_script) = build_script.filter(|path| path.exists()) {
+        scan_file(project_root, &build_script, true, &mut warnings)?;
+    }
+
+    let mut sources: Vec<_> = walkdir::WalkDir::new(project_root.join("src"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "rs"))
+        .map(|e| e.into_path())
+        .collect();
+    sources.sort();
+    for path in sources {
+        scan_file(project_root, &path, false, &mut warnings)?;
+    }
+
+    Ok(warnings)
+}
+
+fn scan_file(
+    project_root: &Path,
+    path: &Path,
+    build_script: bool,
+    warnings: &mut Vec<ReproducibilityWarning>,
+) -> Result<()> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    // Files that don't parse are reported by the compiler
+    let Ok(ast) = syn::parse_file(&content) else {
+        return Ok(());
+    };
+
+    let mut scanner = Scanner {
+        dir: path.parent().unwrap_or(project_root),
+        build_script,
+        found: Vec::new(),
+    };
+    scanner.visit_file(&ast);
+
+    let file = crate::to_slash(path.strip_prefix(project_root).unwrap_or(path));
+    warnings.extend(scanner.found.into_iter().map(|(kind, line, message)| {
+        ReproducibilityWarning {
+            kind,
+            file: file.clone(),
+            line,
+            message,
+        }
+    }));
+    Ok(())
+}
+
+struct Scanner<'a> {
+    /// Directory of the scanned file, for resolving includes
+    dir: &'a Path,
+    /// Runtime environment reads only matter in build scripts
+    build_script: bool,
+    found: Vec<(NondeterminismKind, usize, String)>,
+}
+
+impl Scanner<'_> {
+    fn flag(&mut self, kind: NondeterminismKind, span: proc_macro2::Span, message: String) {
+        self.found.push((kind, span.start().line, message));
+    }
+
+    fn flag_env_var(&mut self, span: proc_macro2::Span, var: Option<String>, compile_time: bool) {
+        let var = match var {
+            Some(var) if var.starts_with("CARGO") || CARGO_BUILD_ENV.contains(&var.as_str()) => {
+                return
+            }
+            Some(var) => format!("environment variable {}", var),
+            None => "a computed environment variable".to_string(),
+        };
+        let when = if compile_time { " at compile time" } else { "" };
+        self.flag(
+            NondeterminismKind::EnvVar,
+            span,
+            format!("reads {}{}", var, when),
+        );
+    }
+}
+
+impl<'ast> Visit<'ast> for Scanner<'_> {
+    fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+        // Tests are not part of the contract
+        if !node.attrs.iter().any(is_cfg_test) {
+            syn::visit::visit_item_mod(self, node);
+        }
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+        if !node
+            .attrs
+            .iter()
+            .any(|attr| is_cfg_test(attr) || attr.path().is_ident("test"))
+        {
+            syn::visit::visit_item_fn(self, node);
+        }
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let Expr::Path(func) = &*node.func {
+            let segments: Vec<String> = func
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            let last = segments.last().map(String::as_str).unwrap_or_default();
+            let owner = segments.len().checked_sub(2).map(|i| segments[i].as_str());
+
+            match (owner, last) {
+                (_, "var" | "var_os") if self.build_script => self.flag_env_var(
+                    node.span(),
+                    node.args.first().and_then(string_literal),
+                    false,
+                ),
+                (_, "vars" | "vars_os") if self.build_script => self.flag(
+                    NondeterminismKind::EnvVar,
+                    node.span(),
+                    "reads all environment variables".to_string(),
+                ),
+                (
+                    Some("SystemTime" | "Instant" | "Utc" | "Local" | "OffsetDateTime"),
+                    "now" | "now_utc",
+                ) => self.flag(
+                    NondeterminismKind::Timestamp,
+                    node.span(),
+                    format!("reads the current time ({})", segments.join("::")),
+                ),
+                (_, "thread_rng" | "getrandom" | "from_entropy")
+                | (Some("rand"), "random" | "rng")
+                | (Some("RandomState"), "new") => self.flag(
+                    NondeterminismKind::Randomness,
+                    node.span(),
+                    format!("uses random values ({})", segments.join("::")),
+                ),
+                _ => {}
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let name = node
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default();
+        match name.as_str() {
+            "env" | "option_env" => {
+                let var = node
+                    .parse_body::<Expr>()
+                    .ok()
+                    .as_ref()
+                    .and_then(string_literal);
+                self.flag_env_var(node.span(), var, true);
+            }
+            "include" | "include_str" | "include_bytes" => {
+                match node
+                    .parse_body::<Expr>()
+                    .ok()
+                    .as_ref()
+                    .and_then(string_literal)
+                {
+                    Some(file) if self.dir.join(&file).exists() => {}
+                    Some(file) => self.flag(
+                        NondeterminismKind::GeneratedInclude,
+                        node.span(),
+                        format!("{}! of {}, which is not in the sources", name, file),
+                    ),
+                    None => self.flag(
+                        NondeterminismKind::GeneratedInclude,
+                        node.span(),
+                        format!("{}! of a generated file", name),
+                    ),
+                }
+            }
+            // Arguments of format!, println!, vec!, ... are plain tokens to syn
+            _ => {
+                if let Ok(args) =
+                    node.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                {
+                    for arg in &args {
+                        self.visit_expr(arg);
+                    }
+                }
+            }
+        }
+        syn::visit::visit_macro(self, node);
+    }
+}
+
+fn is_cfg_test(attr: &syn::Attribute) -> bool {
+    attr.path().is_ident("cfg")
+        && attr
+            .parse_args::<syn::Ident>()
+            .is_ok_and(|arg| arg == "test")
+}
+
+fn string_literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => Some(lit.value()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("build.rs"),
+            r#"
+fn main() {
+    let out = std::env::var("OUT_DIR").unwrap();
+    let features = env::var("CARGO_FEATURE_STD").is_ok();
+    let key = std::env::var("DEPLOYER_KEY");
+    let stamp = std::time::SystemTime::now();
+}
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/abi.json"), "[]").unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            r#"
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const COMMIT: &str = env!("GIT_HASH");
+const ABI: &str = include_str!("abi.json");
+include!(concat!(env!("OUT_DIR"), "/generated.rs"));
+
+fn salt() -> u64 {
+    let hash = format!("{:?}", rand::random::<u64>());
+    std::env::var("IGNORED_AT_RUNTIME").ok();
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    const SEED: &str = env!("TEST_SEED");
+}
+"#,
+        )
+        .unwrap();
+
+        let manifest: toml::Value = toml::from_str("[package]\nname = \"token\"\n").unwrap();
+        let warnings = scan(dir.path(), &manifest).unwrap();
+        let found: Vec<(NondeterminismKind, &str, usize)> = warnings
+            .iter()
+            .map(|w| (w.kind, w.file.as_str(), w.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (NondeterminismKind::EnvVar, "build.rs", 5),
+                (NondeterminismKind::Timestamp, "build.rs", 6),
+                (NondeterminismKind::EnvVar, "src/lib.rs", 3),
+                (NondeterminismKind::GeneratedInclude, "src/lib.rs", 5),
+                (NondeterminismKind::Randomness, "src/lib.rs", 8),
+            ]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "build.rs:5: reads environment variable DEPLOYER_KEY"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Reproducibility scorecard (`reproducibility.json`)
+//!
+//! Scores the risk factors that make a build hard to reproduce by someone
+//! verifying the deployed contract, with a fix for each failed check.
+
+use super::{
+    metadata::{Metadata, Source},
+    nondeterminism::{self, ReproducibilityWarning},
+};
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+
+/// File name of the scorecard in the contract artifacts directory
+pub const REPRODUCIBILITY_FILE: &str = "reproducibility.json";
+
+/// Result of the reproducibility checks of a build
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReproducibilityScorecard {
+    /// Weighted share of passed checks, 0-100
+    pub score: u32,
+    pub checks: Vec<ReproducibilityCheck>,
+    /// Code that may make the build depend on the build machine
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ReproducibilityWarning>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReproducibilityCheck {
+    pub id: String,
+    pub passed: bool,
+    /// Points of the score this check is worth
+    pub weight: u32,
+    pub message: String,
+    /// How to fix a failed check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+impl ReproducibilityScorecard {
+    /// Checks that did not pass
+    pub fn failed(&self) -> impl Iterator<Item = &ReproducibilityCheck> {
+        self.checks.iter().filter(|check| !check.passed)
+    }
+}
+
+/// Evaluate the reproducibility of a build described by `metadata`
+pub fn evaluate(project_root: &Path, metadata: &Metadata) -> Result<ReproducibilityScorecard> {
+    let project_root = &crate::canonicalize_path(project_root)?;
+    let manifest = read_manifest(&project_root.join("Cargo.toml"))?;
+    let toolchain = &metadata.compilation_settings.rust.version;
+    let source_root = source_root(project_root, &metadata.source);
+
+    let mut checks = vec![
+        check(
+            "toolchain_pinned",
+            20,
+            is_pinned_toolchain(toolchain),
+            format!("Rust toolchain: {}", toolchain),
+            "Pin an exact version in rust-toolchain.toml, e.g. channel = \"1.83.0\" or \
+             \"nightly-2024-01-15\"",
+        ),
+        check(
+            "committed_sources",
+            20,
+            matches!(metadata.source, Source::Git { .. }),
+            match &metadata.source {
+                Source::Git { commit, .. } => format!("Built from git commit {}", commit),
+                Source::Archive { .. } => "Built from uncommitted sources".to_string(),
+            },
+            "Commit all changes and build without --allow-dirty so the source is a public commit",
+        ),
+        check(
+            "lockfile",
+            20,
+            has_lockfile(project_root),
+            "Cargo.lock pins dependency versions".to_string(),
+            "Generate Cargo.lock with `cargo generate-lockfile` and commit it",
+        ),
+    ];
+
+    let external = external_path_dependencies(project_root, source_root, &manifest);
+    checks.push(check(
+        "no_external_path_dependencies",
+        15,
+        external.is_empty(),
+        if external.is_empty() {
+            "All path dependencies are part of the source".to_string()
+        } else {
+            format!(
+                "Path dependencies outside the source: {}",
+                external.join(", ")
+            )
+        },
+        "Depend on published crates or git revisions, or move the crates into the repository",
+    ));
+
+    let warnings = nondeterminism::scan(project_root, &manifest)?;
+    checks.push(check(
+        "deterministic_code",
+        10,
+        warnings.is_empty(),
+        if warnings.is_empty() {
+            "No environment reads, timestamps, randomness or generated includes found".to_string()
+        } else {
+            format!(
+                "{} location(s) in build.rs or src/ may depend on the build machine",
+                warnings.len()
+            )
+        },
+        "Remove or pin the flagged environment reads, timestamps, random values and generated \
+         includes",
+    ));
+
+    let isolated = metadata.docker || metadata.nix_derivation.is_some();
+    checks.push(check(
+        "isolated_build",
+        15,
+        isolated,
+        if isolated {
+            "Built in the pinned Docker or Nix environment".to_string()
+        } else {
+            "Built with the local toolchain".to_string()
+        },
+        "Build without --no-docker (or with --nix) to use the pinned build environment",
+    ));
+
+    let total: u32 = checks.iter().map(|c| c.weight).sum();
+    let passed: u32 = checks.iter().filter(|c| c.passed).map(|c| c.weight).sum();
+    Ok(ReproducibilityScorecard {
+        score: passed * 100 / total,
+        checks,
+        warnings,
+    })
+}
+
+fn check(
+    id: &str,
+    weight: u32,
+    passed: bool,
+    message: String,
+    remediation: &str,
+) -> ReproducibilityCheck {
+    ReproducibilityCheck {
+        id: id.to_string(),
+        passed,
+        weight,
+        message,
+        remediation: (!passed).then(|| remediation.to_string()),
+    }
+}
+
+/// `1.83.0`, `nightly-2024-01-15` and `beta-2024-01-15` are pinned; `stable`, `nightly` or `1.83`
+/// are not
+fn is_pinned_toolchain(version: &str) -> bool {
+    let is_date = |date: &str| {
+        let parts: Vec<&str> = date.split('-').collect();
+        parts.len() == 3
+            && parts
+                .iter()
+                .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+    };
+    match version.split_once('-') {
+        Some(("nightly" | "beta", date)) => is_date(date),
+        _ => {
+            let parts: Vec<&str> = version.split('.').collect();
+            parts.len() == 3
+                && parts
+                    .iter()
+                    .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+        }
+    }
+}
+
+/// Root of the tree the build source covers (the repository for git sources)
+fn source_root<'a>(project_root: &'a Path, source: &Source) -> &'a Path {
+    let project_path = match source {
+        Source::Git { project_path, .. } | Source::Archive { project_path, .. } => project_path,
+    };
+    let depth = Path::new(project_path)
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .count();
+    project_root.ancestors().nth(depth).unwrap_or(project_root)
+}
+
+/// Cargo.lock of the package or of an enclosing workspace
+fn has_lockfile(project_root: &Path) -> bool {
+    project_root
+        .ancestors()
+        .any(|dir| dir.join("Cargo.lock").exists())
+}
+
+/// Path dependencies resolving outside `source_root`
+fn external_path_dependencies(
+    project_root: &Path,
+    source_root: &Path,
+    manifest: &toml::Value,
+) -> Vec<String> {
+    let source_root =
+        crate::canonicalize_path(source_root).unwrap_or_else(|_| source_root.to_path_buf());
+    let mut external = Vec::new();
+    for table in ["dependencies", "build-dependencies"] {
+        let Some(deps) = manifest.get(table).and_then(|d| d.as_table()) else {
+            continue;
+        };
+        for (name, dep) in deps {
+            let Some(path) = dep.get("path").and_then(|p| p.as_str()) else {
+                continue;
+            };
+            let resolved = project_root.join(path);
+            let resolved = crate::canonicalize_path(&resolved).unwrap_or(resolved);
+            if !resolved.starts_with(&source_root) {
+                external.push(name.clone());
+            }
+        }
+    }
+    external
+}
+
+fn read_manifest(path: &Path) -> Result<toml::Value> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pinned_toolchain() {
+        assert!(is_pinned_toolchain("1.83.0"));
+        assert!(is_pinned_toolchain("nightly-2024-01-15"));
+        assert!(!is_pinned_toolchain("nightly"));
+        assert!(!is_pinned_toolchain("stable"));
+        assert!(!is_pinned_toolchain("1.83"));
+    }
+
+    #[test]
+    fn test_source_root() {
+        let source = Source::Git {
+            repository: "https://github.com/fluentlabs-xyz/examples".to_string(),
+            commit: "abc".to_string(),
+            project_path: "contracts/token".to_string(),
+        };
+        assert_eq!(
+            source_root(Path::new("/repo/contracts/token"), &source),
+            Path::new("/repo")
+        );
+    }
+}
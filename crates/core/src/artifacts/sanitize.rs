@@ -0,0 +1,388 @@
+//! Solidity-safe names in the generated ABI
+//!
+//! Rust allows names Solidity reserves (`address`, `bytes`, `delete`) and
+//! structs with the same name in different modules. Both make the generated
+//! interface uncompilable, so such parameter, field and struct names are
+//! renamed in the ABI itself, keeping the ABI, interface and selectors
+//! consistent, and the renames are recorded in metadata.json. A function name
+//! is part of its selector and can't be renamed, so a reserved one is an error.
+
+use super::{
+    abi::{Abi, AbiEntry, Param},
+    interface::struct_name_from_internal_type,
+};
+use crate::BuilderError;
+use eyre::Result;
+#[cfg(feature = "build")]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Keywords, reserved words and type names that can't be used as identifiers
+const RESERVED: &[&str] = &[
+    "abstract",
+    "address",
+    "after",
+    "alias",
+    "anonymous",
+    "apply",
+    "as",
+    "assembly",
+    "auto",
+    "bool",
+    "break",
+    "byte",
+    "bytes",
+    "calldata",
+    "case",
+    "catch",
+    "constant",
+    "constructor",
+    "continue",
+    "contract",
+    "copyof",
+    "days",
+    "default",
+    "define",
+    "delete",
+    "do",
+    "else",
+    "emit",
+    "enum",
+    "ether",
+    "event",
+    "external",
+    "fallback",
+    "false",
+    "final",
+    "fixed",
+    "for",
+    "function",
+    "gwei",
+    "hours",
+    "if",
+    "immutable",
+    "implements",
+    "import",
+    "in",
+    "indexed",
+    "inline",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "let",
+    "library",
+    "macro",
+    "mapping",
+    "match",
+    "memory",
+    "minutes",
+    "modifier",
+    "mutable",
+    "new",
+    "null",
+    "of",
+    "override",
+    "partial",
+    "payable",
+    "pragma",
+    "private",
+    "promise",
+    "public",
+    "pure",
+    "receive",
+    "reference",
+    "relocatable",
+    "return",
+    "returns",
+    "sealed",
+    "seconds",
+    "sizeof",
+    "static",
+    "storage",
+    "string",
+    "struct",
+    "supports",
+    "switch",
+    "this",
+    "true",
+    "try",
+    "type",
+    "typedef",
+    "typeof",
+    "ufixed",
+    "uint",
+    "unchecked",
+    "unicode",
+    "using",
+    "var",
+    "view",
+    "virtual",
+    "weeks",
+    "wei",
+    "while",
+];
+
+/// What kind of identifier was renamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum IdentifierKind {
+    Parameter,
+    Field,
+    Struct,
+}
+
+/// A name changed to make the interface compile
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(JsonSchema))]
+pub struct RenamedIdentifier {
+    pub kind: IdentifierKind,
+    /// Where the name appears, e.g. `transfer.address` for a parameter of `transfer`
+    pub location: String,
+    pub original: String,
+    pub renamed: String,
+}
+
+/// Renames planned for an ABI, applicable to it and to subsets of it (facets)
+pub struct Sanitizer {
+    /// (struct name, definition) -> unique name
+    structs: HashMap<(String, String), String>,
+}
+
+impl Sanitizer {
+    /// Plan unique names for structs that share a name but differ in their fields
+    pub fn new(abi: &Abi) -> Self {
+        let mut definitions: Vec<(String, String)> = Vec::new();
+        for param in abi
+            .iter()
+            .flat_map(|entry| entry.inputs().iter().chain(entry.outputs()))
+        {
+            collect_structs(param, &mut definitions);
+        }
+
+        let mut taken: BTreeSet<String> =
+            definitions.iter().map(|(name, _)| name.clone()).collect();
+        let mut structs = HashMap::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for (name, definition) in definitions {
+            if structs.contains_key(&(name.clone(), definition.clone())) {
+                continue;
+            }
+            let count = seen.entry(name.clone()).or_default();
+            *count += 1;
+
+            let mut unique = if is_reserved(&name) {
+                format!("{}_", name)
+            } else {
+                name.clone()
+            };
+            if *count > 1 {
+                let mut n = *count;
+                while taken.contains(&format!("{}{}", name, n)) {
+                    n += 1;
+                }
+                unique = format!("{}{}", name, n);
+                taken.insert(unique.clone());
+            }
+            structs.insert((name, definition), unique);
+        }
+
+        Self { structs }
+    }
+
+    /// Rename reserved and duplicate names in `abi`, returning what was renamed
+    ///
+    /// Fails if a function has a name Solidity reserves.
+    pub fn apply(&self, abi: &mut Abi) -> Result<Vec<RenamedIdentifier>> {
+        let mut renamed = BTreeSet::new();
+        for entry in abi.iter_mut() {
+            let function = entry.name().unwrap_or_default().to_string();
+            if matches!(entry, AbiEntry::Function(_)) && is_reserved(&function) {
+                return Err(BuilderError::InvalidContract {
+                    reason: format!(
+                        "function `{}` has a name Solidity reserves, so its interface can't \
+                         compile; \
+                         rename the method",
+                        function
+                    ),
+                }
+                .into());
+            }
+            for param in entry.params_mut() {
+                self.rename_param(param, &function, IdentifierKind::Parameter, &mut renamed);
+            }
+        }
+        Ok(renamed.into_iter().collect())
+    }
+
+    fn rename_param(
+        &self,
+        param: &mut Param,
+        owner: &str,
+        kind: IdentifierKind,
+        renamed: &mut BTreeSet<RenamedIdentifier>,
+    ) {
+        let name = param.name.clone();
+        if let Some(safe) = safe_name(&name) {
+            renamed.insert(RenamedIdentifier {
+                kind,
+                location: format!("{}.{}", owner, name),
+                original: name.clone(),
+                renamed: safe.clone(),
+            });
+            param.name = safe;
+        }
+
+        let Some((struct_name, definition)) = struct_definition(param) else {
+            return;
+        };
+        let unique = self
+            .structs
+            .get(&(struct_name.clone(), definition))
+            .cloned()
+            .unwrap_or_else(|| struct_name.clone());
+        if unique != struct_name {
+            let internal_type = param.internal_type.clone().unwrap_or_default();
+            renamed.insert(RenamedIdentifier {
+                kind: IdentifierKind::Struct,
+                location: internal_type.trim_start_matches("struct ").to_string(),
+                original: struct_name.clone(),
+                renamed: unique.clone(),
+            });
+            // Keep the array suffix, drop the module qualifier
+            let suffix = internal_type
+                .find('[')
+                .map(|i| &internal_type[i..])
+                .unwrap_or_default();
+            param.internal_type = Some(format!("struct {}{}", unique, suffix));
+        }
+        for component in &mut param.components {
+            self.rename_param(component, &unique, IdentifierKind::Field, renamed);
+        }
+    }
+}
+
+/// Structs reachable from a parameter, in declaration order
+fn collect_structs(param: &Param, definitions: &mut Vec<(String, String)>) {
+    if let Some(definition) = struct_definition(param) {
+        definitions.push(definition);
+    }
+    for component in &param.components {
+        collect_structs(component, definitions);
+    }
+}
+
+/// Struct name from `internalType` and its fields, identifying the definition
+fn struct_definition(param: &Param) -> Option<(String, String)> {
+    let name = struct_name_from_internal_type(param.internal_type.as_deref()?)?;
+    Some((name, serde_json::to_string(&param.components).ok()?))
+}
+
+/// Name with a trailing underscore if Solidity reserves it
+fn safe_name(name: &str) -> Option<String> {
+    is_reserved(name).then(|| format!("{}_", name))
+}
+
+fn is_reserved(name: &str) -> bool {
+    if RESERVED.contains(&name) {
+        return true;
+    }
+    // Sized types: uint8..uint256, int8..int256, bytes1..bytes32
+    ["uint", "int", "bytes"].iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|size| size.parse::<u16>().is_ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn item(qualifier: &str, fields: Value) -> Value {
+        json!({
+            "name": "item",
+            "type": "tuple",
+            "internalType": format!("struct {}Item", qualifier),
+            "components": fields,
+        })
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut abi = serde_json::from_value(json!([
+            {
+                "name": "remove",
+                "type": "function",
+                "inputs": [
+                    {"name": "address", "type": "address"},
+                    item("orders.", json!([{"name": "bytes", "type": "bytes"}])),
+                ],
+                "outputs": [],
+            },
+            {
+                "name": "store",
+                "type": "function",
+                "inputs": [item("", json!([{"name": "bytes", "type": "bytes"}]))],
+                "outputs": [item("assets.", json!([{"name": "id", "type": "uint256"}]))],
+            },
+        ]))
+        .unwrap();
+
+        let renamed = Sanitizer::new(&abi).apply(&mut abi).unwrap();
+        assert_eq!(abi[0].name(), Some("remove"));
+        assert_eq!(abi[0].inputs()[0].name, "address_");
+        assert_eq!(
+            abi[0].inputs()[1].internal_type.as_deref(),
+            Some("struct orders.Item")
+        );
+        assert_eq!(abi[0].inputs()[1].components[0].name, "bytes_");
+        // Same definition keeps the name, a different one is numbered
+        assert_eq!(
+            abi[1].inputs()[0].internal_type.as_deref(),
+            Some("struct Item")
+        );
+        assert_eq!(
+            abi[1].outputs()[0].internal_type.as_deref(),
+            Some("struct Item2")
+        );
+
+        let renamed: Vec<(IdentifierKind, &str, &str)> = renamed
+            .iter()
+            .map(|r| (r.kind, r.location.as_str(), r.renamed.as_str()))
+            .collect();
+        assert_eq!(
+            renamed,
+            vec![
+                (IdentifierKind::Parameter, "remove.address", "address_"),
+                (IdentifierKind::Field, "Item.bytes", "bytes_"),
+                (IdentifierKind::Struct, "assets.Item", "Item2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reserved_function_name() {
+        let mut abi: Abi = serde_json::from_value(json!([
+            {"name": "delete", "type": "function", "inputs": [], "outputs": []},
+        ]))
+        .unwrap();
+        let err = Sanitizer::new(&abi).apply(&mut abi).unwrap_err();
+        assert!(matches!(
+            BuilderError::find(&err),
+            Some(BuilderError::InvalidContract { .. })
+        ));
+        assert_eq!(abi[0].name(), Some("delete"));
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        assert!(is_reserved("uint256"));
+        assert!(is_reserved("bytes32"));
+        assert!(is_reserved("mapping"));
+        assert!(!is_reserved("bytesLength"));
+        assert!(!is_reserved("amount"));
+    }
+}
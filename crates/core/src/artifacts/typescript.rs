@@ -0,0 +1,105 @@
+//! TypeScript bindings generated from the ABI
+//!
+//! The ABI is exported with a `const` assertion, from which viem, wagmi and
+//! abitype infer the argument and return types of every function and event,
+//! so frontends get typed calls without copying abi.json by hand. Selectors
+//! and event topics are exported next to it for indexers and raw calls.
+
+use super::{abi::Abi, extract_event_topics, extract_function_selectors};
+use convert_case::{Case, Casing};
+use eyre::Result;
+use std::collections::BTreeMap;
+
+/// File written next to abi.json
+pub const TYPESCRIPT_BINDINGS_FILE: &str = "bindings.ts";
+
+/// Generates a TypeScript module exporting `<contractName>Abi` `as const` and its selectors
+pub fn generate_typescript_bindings(contract_name: &str, abi: &Abi) -> Result<String> {
+    let name = typescript_identifier(&contract_name.to_case(Case::Camel));
+    let type_name = typescript_identifier(&contract_name.to_case(Case::Pascal));
+
+    let mut bindings = String::new();
+    bindings.push_str("// Auto-generated from Rust source\n\n");
+    bindings.push_str(&format!(
+        "export const {}Abi = {} as const;\n\n",
+        name,
+        serde_json::to_string_pretty(abi)?
+    ));
+    bindings.push_str(&format!(
+        "export type {}Abi = typeof {}Abi;\n",
+        type_name, name
+    ));
+
+    for (suffix, hashes) in [
+        ("Selectors", extract_function_selectors(abi)),
+        ("EventTopics", extract_event_topics(abi)),
+    ] {
+        bindings.push_str(&format!(
+            "\nexport const {}{} = {} as const;\n",
+            name,
+            suffix,
+            object_literal(&hashes)?
+        ));
+    }
+    Ok(bindings)
+}
+
+/// `{ "signature": "0x…", … }`, one entry per line
+fn object_literal(entries: &BTreeMap<String, String>) -> Result<String> {
+    if entries.is_empty() {
+        return Ok("{}".to_string());
+    }
+    let lines = entries
+        .iter()
+        .map(|(key, value)| {
+            Ok(format!(
+                "  {}: {},",
+                serde_json::to_string(key)?,
+                serde_json::to_string(value)?
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!("{{\n{}\n}}", lines.join("\n")))
+}
+
+/// Prefixes names that aren't valid identifiers, e.g. of crates starting with a digit
+fn typescript_identifier(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.to_string()
+    } else {
+        format!("_{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typescript_bindings() {
+        let abi = super::super::human_readable::parse(
+            "function transfer(address to, uint256 amount) returns (bool)\n\
+             event Transfer(address indexed from, address indexed to, uint256 value)",
+        )
+        .unwrap();
+        let bindings = generate_typescript_bindings("my-token", &abi).unwrap();
+
+        assert!(bindings.starts_with(
+            "// Auto-generated from Rust source\n\nexport const myTokenAbi = [\n  {\n"
+        ));
+        assert!(bindings.contains("    \"name\": \"transfer\",\n"));
+        assert!(bindings.contains("] as const;\n\nexport type MyTokenAbi = typeof myTokenAbi;\n"));
+        assert!(bindings.contains(
+            "export const myTokenSelectors = {\n  \"transfer(address,uint256)\": \"0xa9059cbb\",\n\
+             } as const;\n"
+        ));
+        assert!(bindings.contains(
+            "\"Transfer(address,address,uint256)\": \
+             \"0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef\","
+        ));
+
+        let bindings = generate_typescript_bindings("1inch", &Vec::new()).unwrap();
+        assert!(bindings.contains("export const _1InchAbi = [] as const;"));
+        assert!(bindings.contains("export const _1InchEventTopics = {} as const;"));
+    }
+}
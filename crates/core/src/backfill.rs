@@ -0,0 +1,472 @@
+//! Bulk export and import of verified builds
+//!
+//! Lets an explorer backfill its verified-contract database from a team's CI
+//! history: export collects the deployments a project verified with
+//! `verify --record`, with the metadata and sources of the matching build,
+//! and import loads such a dump into a verification server's job store as
+//! queued jobs, verified by rebuilding them like any other job.
+//!
+//! A dump is either NDJSON, one [`VerifiedBuild`] per line, or a directory
+//! with a `<chain_id>/<address>/` directory per build holding `build.json`,
+//! `metadata.json` and `sources.tar.gz`.
+
+use crate::Deployments;
+#[cfg(all(feature = "archive", feature = "server"))]
+use crate::{
+    api::{Job, JobStatus, VerifyRequest},
+    BuildFeatures, CodeUnwrap, HashAlgorithm, JobStore,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+/// Build record inside each directory of a directory dump
+pub const BUILD_FILE: &str = "build.json";
+
+/// Source archive written next to the artifacts of builds from uncommitted sources
+const SOURCES_FILE: &str = "sources.tar.gz";
+
+const METADATA_FILE: &str = "metadata.json";
+
+/// A verified deployment with what is needed to show and re-verify it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct VerifiedBuild {
+    pub schema_version: u32,
+    pub chain_id: u64,
+    pub address: String,
+    /// Contract name from Cargo.toml
+    pub contract: String,
+    /// Hash of the deployed rWASM code
+    pub rwasm_hash: String,
+    /// Hex encoded constructor arguments, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constructor_args: Option<String>,
+    /// Verification timestamp
+    pub verified_at: u64,
+    /// metadata.json of the build, if its artifacts were found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    /// Base64 encoded sources.tar.gz of a build from uncommitted sources;
+    /// builds from git are located by `metadata.source`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<String>,
+}
+
+/// Verified deployments of a project with the metadata and sources of their builds
+///
+/// Builds are matched to deployments by rWASM hash among the artifacts under
+/// `<project_root>/out`, including release artifacts.
+pub fn export_verified_builds(project_root: &Path) -> Result<Vec<VerifiedBuild>> {
+    let deployments = Deployments::load(project_root)?;
+    let artifacts = find_artifacts(&project_root.join("out"));
+
+    deployments
+        .deployments
+        .iter()
+        .map(|deployment| {
+            let artifacts_dir = artifacts.get(&hash_digits(&deployment.rwasm_hash));
+            if artifacts_dir.is_none() {
+                tracing::warn!(
+                    "No artifacts for {} at {} on chain {}; exporting it without metadata",
+                    deployment.contract,
+                    deployment.address,
+                    deployment.chain_id
+                );
+            }
+            let metadata = artifacts_dir
+                .map(|dir| read_json(&dir.join(METADATA_FILE)))
+                .transpose()?;
+            let sources = artifacts_dir
+                .map(|dir| dir.join(SOURCES_FILE))
+                .filter(|path| path.exists())
+                .map(|path| {
+                    std::fs::read(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))
+                })
+                .transpose()?
+                .map(|archive| STANDARD.encode(archive));
+
+            Ok(VerifiedBuild {
+                schema_version: 1,
+                chain_id: deployment.chain_id,
+                address: deployment.address.clone(),
+                contract: deployment.contract.clone(),
+                rwasm_hash: deployment.rwasm_hash.clone(),
+                constructor_args: deployment.constructor_args.clone(),
+                verified_at: deployment.verified_at,
+                metadata,
+                sources,
+            })
+        })
+        .collect()
+}
+
+/// Write builds as NDJSON, one per line
+pub fn write_ndjson(builds: &[VerifiedBuild], mut writer: impl Write) -> Result<()> {
+    for build in builds {
+        serde_json::to_writer(&mut writer, build)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read an NDJSON dump, skipping blank lines
+pub fn read_ndjson(reader: impl BufRead) -> Result<Vec<VerifiedBuild>> {
+    let mut builds = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        builds.push(
+            serde_json::from_str(&line)
+                .with_context(|| format!("Invalid build on line {}", number + 1))?,
+        );
+    }
+    Ok(builds)
+}
+
+/// Write builds as a directory dump
+pub fn write_dump_dir(builds: &[VerifiedBuild], dir: &Path) -> Result<()> {
+    for build in builds {
+        let build_dir = dir
+            .join(build.chain_id.to_string())
+            .join(parse_address(&build.address)?);
+        std::fs::create_dir_all(&build_dir)
+            .with_context(|| format!("Failed to create {}", build_dir.display()))?;
+
+        if let Some(metadata) = &build.metadata {
+            std::fs::write(
+                build_dir.join(METADATA_FILE),
+                serde_json::to_string_pretty(metadata)? + "\n",
+            )?;
+        }
+        if let Some(sources) = &build.sources {
+            let archive = STANDARD.decode(sources).context("Invalid base64 sources")?;
+            std::fs::write(build_dir.join(SOURCES_FILE), archive)?;
+        }
+        let record = VerifiedBuild {
+            metadata: None,
+            sources: None,
+            ..build.clone()
+        };
+        std::fs::write(
+            build_dir.join(BUILD_FILE),
+            serde_json::to_string_pretty(&record)? + "\n",
+        )?;
+    }
+    Ok(())
+}
+
+/// Read a directory dump written by [`write_dump_dir`]
+pub fn read_dump_dir(dir: &Path) -> Result<Vec<VerifiedBuild>> {
+    let mut build_files: Vec<PathBuf> = walkdir::WalkDir::new(dir)
+        .min_depth(3)
+        .max_depth(3)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == BUILD_FILE)
+        .map(|e| e.into_path())
+        .collect();
+    build_files.sort();
+
+    build_files
+        .iter()
+        .map(|path| {
+            let build_dir = path.parent().unwrap_or(dir);
+            let mut build: VerifiedBuild = serde_json::from_value(read_json(path)?)
+                .with_context(|| format!("Invalid build in {}", path.display()))?;
+            let metadata_path = build_dir.join(METADATA_FILE);
+            if metadata_path.exists() {
+                build.metadata = Some(read_json(&metadata_path)?);
+            }
+            let sources_path = build_dir.join(SOURCES_FILE);
+            if sources_path.exists() {
+                build.sources = Some(STANDARD.encode(std::fs::read(&sources_path)?));
+            }
+            Ok(build)
+        })
+        .collect()
+}
+
+/// Record builds as queued jobs of a verification server
+///
+/// A dump is only a claim: the server rebuilds each job when it next starts,
+/// and only a matching rebuild marks it verified. Metadata and sources go to
+/// `<work_dir>/<job id>/`, the sources unpacked under `sources/`. Importing a
+/// build twice replaces its job. Returns the job ids.
+#[cfg(all(feature = "archive", feature = "server"))]
+pub fn import_verified_builds(
+    store: &JobStore,
+    work_dir: &Path,
+    builds: &[VerifiedBuild],
+) -> Result<Vec<String>> {
+    builds
+        .iter()
+        .map(|build| {
+            let address = parse_address(&build.address)?;
+            let id = format!("import-{}-{}", build.chain_id, address);
+            let job_dir = work_dir.join(&id);
+            std::fs::create_dir_all(&job_dir)
+                .with_context(|| format!("Failed to create {}", job_dir.display()))?;
+
+            if let Some(metadata) = &build.metadata {
+                std::fs::write(
+                    job_dir.join(METADATA_FILE),
+                    serde_json::to_string_pretty(metadata)? + "\n",
+                )?;
+            }
+            let project_root = match &build.sources {
+                Some(sources) => {
+                    let archive = STANDARD
+                        .decode(sources)
+                        .with_context(|| format!("Invalid base64 sources of {}", build.address))?;
+                    let sources_dir = job_dir.join("sources");
+                    tar::Archive::new(flate2::read::GzDecoder::new(archive.as_slice()))
+                        .unpack(&sources_dir)
+                        .with_context(|| {
+                            format!("Failed to unpack sources of {}", build.address)
+                        })?;
+                    sources_dir
+                }
+                None => job_dir.clone(),
+            };
+
+            let settings = build
+                .metadata
+                .as_ref()
+                .and_then(|metadata| BuildFeatures::from_metadata(metadata).ok())
+                .unwrap_or_else(|| BuildFeatures::new("release", &[], true));
+            let job = Job {
+                id: id.clone(),
+                request: VerifyRequest {
+                    project_root,
+                    deployed_bytecode_hash: Some(build.rwasm_hash.clone()),
+                    hash_algorithm: HashAlgorithm::Sha256,
+                    unwrap: CodeUnwrap::default(),
+                    address: Some(address),
+                    chain_id: Some(build.chain_id),
+                    rpc: None,
+                    profile: settings.profile,
+                    features: settings.features,
+                    no_default_features: settings.no_default_features,
+                    target_name: None,
+                    constructor_args: build.constructor_args.clone(),
+                    extra_cargo_args: build
+                        .metadata
+                        .as_ref()
+                        .and_then(|metadata| {
+                            metadata.pointer("/compilation_settings/build_cfg/extra_cargo_args")
+                        })
+                        .and_then(|args| serde_json::from_value(args.clone()).ok())
+                        .unwrap_or_default(),
+                    rustflags: build
+                        .metadata
+                        .as_ref()
+                        .and_then(|metadata| {
+                            metadata.pointer("/compilation_settings/build_cfg/rustflags")
+                        })
+                        .and_then(|flags| flags.as_str().map(str::to_string)),
+                },
+                status: JobStatus::Queued,
+                created_at: build.verified_at,
+                started_at: None,
+                finished_at: None,
+                stage: None,
+                logs: vec![format!(
+                    "Imported from a verified builds dump as {}; not verified until rebuilt",
+                    build.contract
+                )],
+                outcome: None,
+                error: None,
+                cached_from: None,
+            };
+            store.save(&job)?;
+            Ok(id)
+        })
+        .collect()
+}
+
+/// `0x` and 40 lowercase hex digits; anything else is refused before it names a path
+fn parse_address(address: &str) -> Result<String> {
+    let digits = address.strip_prefix("0x").unwrap_or(address);
+    match hex::decode(digits) {
+        Ok(bytes) if bytes.len() == 20 => Ok(format!("0x{}", hex::encode(bytes))),
+        _ => Err(eyre::eyre!("Invalid contract address {:?}", address)),
+    }
+}
+
+/// Artifacts directories under `out_dir` by the hex digits of their rWASM hash
+pub(crate) fn find_artifacts(out_dir: &Path) -> HashMap<String, PathBuf> {
+    walkdir::WalkDir::new(out_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == METADATA_FILE)
+        .filter_map(|e| {
+            let metadata = read_json(e.path()).ok()?;
+            let hash = metadata.pointer("/bytecode/rwasm/hash")?.as_str()?;
+            Some((hash_digits(hash), e.path().parent()?.to_path_buf()))
+        })
+        .collect()
+}
+
+/// `0x...` (deployments) and `sha256:...` (metadata) hashes compare by their digits
+pub(crate) fn hash_digits(hash: &str) -> String {
+    hash.trim_start_matches("0x")
+        .trim_start_matches("sha256:")
+        .to_lowercase()
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(all(test, feature = "archive", feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::DeploymentRecord;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn sources_archive() -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let content = b"[package]\nname = \"token\"\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "Cargo.toml", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    const TOKEN: &str = "0xABABABABABABABABABABABABABABABABABABABCD";
+    const OTHER: &str = "0x1212121212121212121212121212121212121234";
+
+    fn project() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let artifacts = dir.path().join("out/token.wasm");
+        std::fs::create_dir_all(&artifacts).unwrap();
+        std::fs::write(
+            artifacts.join(METADATA_FILE),
+            json!({
+                "bytecode": {"rwasm": {"hash": "sha256:AA"}},
+                "compilation_settings": {
+                    "build_cfg": {
+                        "profile": "release",
+                        "features": ["testnet"],
+                        "no_default_features": true
+                    }
+                },
+            })
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(artifacts.join(SOURCES_FILE), sources_archive()).unwrap();
+
+        let mut deployments = Deployments::default();
+        for (address, hash) in [(TOKEN, "0xaa"), (OTHER, "0xbb")] {
+            deployments.record_deployment(DeploymentRecord {
+                chain_id: 20994,
+                address: address.to_string(),
+                contract: "token".to_string(),
+                rwasm_hash: hash.to_string(),
+                constructor_args: None,
+                block: None,
+                git_ref: None,
+                commit: None,
+                provenance: None,
+                build_features: None,
+                verified_at: 100,
+            });
+        }
+        deployments.save(dir.path()).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_export_and_dump_formats() {
+        let project = project();
+        let builds = export_verified_builds(project.path()).unwrap();
+        assert_eq!(builds.len(), 2);
+        assert!(builds[0].metadata.is_some() && builds[0].sources.is_some());
+        // No artifacts with the hash of the second deployment
+        assert!(builds[1].metadata.is_none() && builds[1].sources.is_none());
+
+        let mut ndjson = Vec::new();
+        write_ndjson(&builds, &mut ndjson).unwrap();
+        assert_eq!(ndjson.iter().filter(|b| **b == b'\n').count(), 2);
+        assert_eq!(read_ndjson(ndjson.as_slice()).unwrap(), builds);
+
+        let dump = TempDir::new().unwrap();
+        write_dump_dir(&builds, dump.path()).unwrap();
+        assert!(dump
+            .path()
+            .join("20994/0xabababababababababababababababababababcd")
+            .join(SOURCES_FILE)
+            .exists());
+        let mut read = read_dump_dir(dump.path()).unwrap();
+        read.sort_by(|a, b| b.address.cmp(&a.address));
+        assert_eq!(read, builds);
+    }
+
+    #[test]
+    fn test_import() {
+        let project = project();
+        let builds = export_verified_builds(project.path()).unwrap();
+
+        let work_dir = TempDir::new().unwrap();
+        let store = JobStore::open(&work_dir.path().join(JobStore::FILE_NAME)).unwrap();
+        let ids = import_verified_builds(&store, work_dir.path(), &builds).unwrap();
+        assert_eq!(
+            ids,
+            vec![
+                "import-20994-0xabababababababababababababababababababcd",
+                "import-20994-0x1212121212121212121212121212121212121234"
+            ]
+        );
+        // Importing again replaces the jobs
+        import_verified_builds(&store, work_dir.path(), &builds).unwrap();
+        assert_eq!(store.jobs().unwrap().len(), 2);
+
+        // Queued for a rebuild rather than taken on trust
+        let job = store
+            .load("import-20994-0xabababababababababababababababababababcd")
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.status, JobStatus::Queued);
+        assert!(job.outcome.is_none());
+        assert_eq!(job.request.features, vec!["testnet"]);
+        assert!(job.request.project_root.join("Cargo.toml").exists());
+    }
+
+    #[test]
+    fn test_invalid_address() {
+        let mut build = export_verified_builds(project().path()).unwrap().remove(0);
+        build.address = "../../../tmp/token".to_string();
+
+        let work_dir = TempDir::new().unwrap();
+        let store = JobStore::open(&work_dir.path().join(JobStore::FILE_NAME)).unwrap();
+        let err = import_verified_builds(&store, work_dir.path(), &[build.clone()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid contract address \"../../../tmp/token\""
+        );
+        assert!(store.jobs().unwrap().is_empty());
+        assert!(write_dump_dir(&[build], work_dir.path()).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Feature set, profile and provenance of a build
+//!
+//! Both are recorded in metadata.json, and for a deployment in
+//! deployments.json, so a binary compiled with the wrong features or builder
+//! can be told apart from other mismatches. Nothing is added to the bytecode:
+//! the translation to rWASM would drop it, and it would make builds of
+//! different fluent-builder releases differ.
+
+use crate::config::CompileConfig;
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Build settings that change the produced bytecode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BuildFeatures {
+    pub profile: String,
+    /// Sorted and deduplicated
+    #[serde(default)]
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+}
+
+impl BuildFeatures {
+    pub fn new(profile: &str, features: &[String], no_default_features: bool) -> Self {
+        let mut features = features.to_vec();
+        features.sort();
+        features.dedup();
+        Self {
+            profile: profile.to_string(),
+            features,
+            no_default_features,
+        }
+    }
+
+    /// Settings a config compiles with
+    pub fn from_config(config: &CompileConfig) -> Self {
+        Self::new(
+            &config.profile,
+            &config.features,
+            config.no_default_features,
+        )
+    }
+
+    /// Settings recorded in a metadata.json (`compilation_settings.build_cfg`)
+    pub fn from_metadata(metadata: &Value) -> Result<Self> {
+        let build_cfg = metadata
+            .pointer("/compilation_settings/build_cfg")
+            .ok_or_else(|| eyre::eyre!("metadata has no compilation_settings.build_cfg"))?;
+        let recorded: Self =
+            serde_json::from_value(build_cfg.clone()).context("Invalid build_cfg in metadata")?;
+        Ok(Self::new(
+            &recorded.profile,
+            &recorded.features,
+            recorded.no_default_features,
+        ))
+    }
+}
+
+impl fmt::Display for BuildFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "profile {}", self.profile)?;
+        if self.features.is_empty() {
+            write!(f, ", no features")?;
+        } else {
+            write!(f, ", features [{}]", self.features.join(", "))?;
+        }
+        if !self.no_default_features {
+            write!(f, ", default features")?;
+        }
+        Ok(())
+    }
+}
+
+/// Builder version and pipeline options a binary was produced with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Provenance {
+    /// fluent-builder version
+    pub builder_version: String,
+    /// Whether cargo ran with `--locked`
+    pub locked: bool,
+    /// Selected cargo target or variant, if not the default library
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_name: Option<String>,
+}
+
+impl Provenance {
+    /// Provenance of a build of `config` by this fluent-builder, or by the version it reproduces
+    pub fn from_config(config: &CompileConfig) -> Self {
+        Self {
+            builder_version: config
+                .compat
+                .as_ref()
+                .map_or(crate::VERSION, |compat| &compat.builder_version)
+                .to_string(),
+            locked: config.locked,
+            target_name: config.target_name.clone(),
+        }
+    }
+
+    /// Provenance recorded in a metadata.json
+    pub fn from_metadata(metadata: &Value) -> Result<Self> {
+        let builder_version = metadata["builder_version"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("metadata has no builder_version"))?;
+        let build_cfg = metadata
+            .pointer("/compilation_settings/build_cfg")
+            .ok_or_else(|| eyre::eyre!("metadata has no compilation_settings.build_cfg"))?;
+        Ok(Self {
+            builder_version: builder_version.to_string(),
+            locked: build_cfg["locked"].as_bool().unwrap_or_default(),
+            target_name: build_cfg["target_name"].as_str().map(str::to_string),
+        })
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fluent-builder {}", self.builder_version)?;
+        if self.locked {
+            write!(f, ", --locked")?;
+        }
+        if let Some(target_name) = &self.target_name {
+            write!(f, ", target {}", target_name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_features_are_normalized() {
+        let build = BuildFeatures::new("release", &["b".into(), "a".into(), "a".into()], true);
+        assert_eq!(build.features, vec!["a", "b"]);
+        assert_eq!(
+            build,
+            BuildFeatures::new("release", &["a".into(), "b".into()], true)
+        );
+    }
+
+    #[test]
+    fn test_provenance_from_metadata() {
+        let metadata = json!({
+            "builder_version": "0.1.0",
+            "compilation_settings": {
+                "build_cfg": {
+                    "profile": "release",
+                    "no_default_features": true,
+                    "locked": true,
+                    "target_name": "token-lite"
+                }
+            }
+        });
+        let provenance = Provenance::from_metadata(&metadata).unwrap();
+        assert_eq!(provenance.builder_version, "0.1.0");
+        assert!(provenance.locked);
+        assert_eq!(provenance.target_name.as_deref(), Some("token-lite"));
+        assert!(Provenance::from_metadata(&json!({"builder_version": "0.1.0"})).is_err());
+    }
+
+    #[test]
+    fn test_provenance_from_config() {
+        let mut config = CompileConfig::new(".");
+        config.target_name = Some("token-lite".to_string());
+        let provenance = Provenance::from_config(&config);
+        assert_eq!(provenance.builder_version, crate::VERSION);
+        assert_eq!(provenance.target_name.as_deref(), Some("token-lite"));
+
+        config.compat = Some(crate::Compat::for_version("0.1.0").unwrap());
+        assert_eq!(Provenance::from_config(&config).builder_version, "0.1.0");
+    }
+
+    #[test]
+    fn test_from_metadata() {
+        let metadata = json!({
+            "compilation_settings": {
+                "build_cfg": {
+                    "profile": "release",
+                    "features": ["std"],
+                    "no_default_features": true,
+                    "locked": true
+                }
+            }
+        });
+        let build = BuildFeatures::from_metadata(&metadata).unwrap();
+        assert_eq!(build, BuildFeatures::new("release", &["std".into()], true));
+        assert_eq!(build.to_string(), "profile release, features [std]");
+
+        assert!(BuildFeatures::from_metadata(&json!({})).is_err());
+    }
+}
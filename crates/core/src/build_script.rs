@@ -0,0 +1,148 @@
+//! Compiling contracts from the build script of a dependent crate
+//!
+//! A host application can embed contract bytecode at compile time:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     fluent_builder::build_script::compile_contract("../contracts/token").unwrap();
+//! }
+//!
+//! // src/lib.rs: defines TOKEN_WASM, TOKEN_RWASM and TOKEN_RWASM_HASH
+//! include!(concat!(env!("OUT_DIR"), "/token.rs"));
+//! ```
+//!
+//! The contract is built with its own toolchain into a target directory under
+//! `OUT_DIR`, so the nested cargo never waits on the outer build's lock.
+
+use crate::{build, get_rwasm_hash, CompileConfig};
+use convert_case::{Case, Casing};
+use eyre::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Variables cargo sets for build scripts that would leak the host build's
+/// toolchain and flags into the nested contract build
+pub(crate) const OUTER_CARGO_ENV: &[&str] = &[
+    "RUSTUP_TOOLCHAIN",
+    "RUSTC",
+    "RUSTC_WRAPPER",
+    "RUSTC_WORKSPACE_WRAPPER",
+    "RUSTDOC",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_TARGET_DIR",
+    "CARGO_BUILD_TARGET",
+];
+
+/// Whether the process is a cargo build script
+pub(crate) fn in_build_script() -> bool {
+    ["OUT_DIR", "HOST", "TARGET"]
+        .iter()
+        .all(|var| std::env::var_os(var).is_some())
+}
+
+/// Bytecode of a contract compiled by a build script
+#[derive(Debug, Clone)]
+pub struct EmbeddedContract {
+    pub name: String,
+    pub wasm_path: PathBuf,
+    pub rwasm_path: PathBuf,
+    /// sha256 of the rWASM code, as compared by verify
+    pub rwasm_hash: String,
+    /// `<OUT_DIR>/<name>.rs` defining the bytecode and hash constants
+    pub constants_path: PathBuf,
+}
+
+/// Compile the contract at `contract_dir` (relative to the calling crate) into `OUT_DIR`
+pub fn compile_contract(contract_dir: impl AsRef<Path>) -> Result<EmbeddedContract> {
+    compile_contract_with(contract_dir, |_| {})
+}
+
+/// Compile the contract at `contract_dir` into `OUT_DIR`, adjusting the default settings first
+pub fn compile_contract_with(
+    contract_dir: impl AsRef<Path>,
+    configure: impl FnOnce(&mut CompileConfig),
+) -> Result<EmbeddedContract> {
+    let out_dir = PathBuf::from(
+        std::env::var_os("OUT_DIR")
+            .ok_or_else(|| eyre::eyre!("compile_contract must be called from a build script"))?,
+    );
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let contract_dir = manifest_dir.join(contract_dir);
+
+    let mut config = CompileConfig::new(&contract_dir);
+    config.use_git_source = false;
+    config.target_dir = Some(out_dir.join("fluent-target"));
+    config.output_dir = out_dir.clone();
+    configure(&mut config);
+
+    for path in ["src", "Cargo.toml", "Cargo.lock", "rust-toolchain.toml"] {
+        println!(
+            "cargo:rerun-if-changed={}",
+            contract_dir.join(path).display()
+        );
+    }
+
+    let result = build(&config)
+        .with_context(|| format!("Failed to compile contract at {}", contract_dir.display()))?;
+    let name = result.contract.artifact_name().to_string();
+    let artifacts_dir = out_dir.join(format!("{}.wasm", name));
+    std::fs::create_dir_all(&artifacts_dir)
+        .with_context(|| format!("Failed to create {}", artifacts_dir.display()))?;
+
+    let wasm_path = artifacts_dir.join("lib.wasm");
+    let rwasm_path = artifacts_dir.join("lib.rwasm");
+    std::fs::write(&wasm_path, &result.outputs.wasm)?;
+    std::fs::write(&rwasm_path, &result.outputs.rwasm)?;
+
+    let rwasm_hash = get_rwasm_hash(&result);
+    let constants_path = out_dir.join(format!("{}.rs", name));
+    std::fs::write(
+        &constants_path,
+        constants_source(&name, &wasm_path, &rwasm_path, &rwasm_hash),
+    )
+    .with_context(|| format!("Failed to write {}", constants_path.display()))?;
+
+    Ok(EmbeddedContract {
+        name,
+        wasm_path,
+        rwasm_path,
+        rwasm_hash,
+        constants_path,
+    })
+}
+
+/// Rust source defining `<NAME>_WASM`, `<NAME>_RWASM` and `<NAME>_RWASM_HASH`
+fn constants_source(name: &str, wasm_path: &Path, rwasm_path: &Path, rwasm_hash: &str) -> String {
+    let prefix = name.to_case(Case::UpperSnake);
+    format!(
+        "pub const {prefix}_WASM: &[u8] = include_bytes!({:?});\n\
+         pub const {prefix}_RWASM: &[u8] = include_bytes!({:?});\n\
+         pub const {prefix}_RWASM_HASH: &str = {:?};\n",
+        wasm_path.display().to_string(),
+        rwasm_path.display().to_string(),
+        rwasm_hash,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants_source() {
+        let source = constants_source(
+            "power-calculator",
+            Path::new("/out/a.wasm"),
+            Path::new("/out/a.rwasm"),
+            "ab12",
+        );
+        assert_eq!(
+            source,
+            "pub const POWER_CALCULATOR_WASM: &[u8] = include_bytes!(\"/out/a.wasm\");\n\
+             pub const POWER_CALCULATOR_RWASM: &[u8] = include_bytes!(\"/out/a.rwasm\");\n\
+             pub const POWER_CALCULATOR_RWASM_HASH: &str = \"ab12\";\n"
+        );
+    }
+}
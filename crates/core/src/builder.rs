@@ -1,7 +1,20 @@
 //! Core WASM compilation logic
 
-use crate::{artifacts, config::CompileConfig, parser};
+use crate::{
+    artifacts::{
+        self,
+        metadata::{BuildEnvironment, NixInput},
+    },
+    compat::Compat,
+    config::CompileConfig,
+    error::BuilderError,
+    hashing::digest_reader,
+    parser,
+    workspace::CargoLayout,
+    HashAlgorithm,
+};
 use eyre::{Context, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{
@@ -24,13 +37,25 @@ pub struct CompilationResult {
     pub runtime_info: RuntimeInfo,
     /// Total compilation time
     pub duration: Duration,
+    /// Whether the bytecode came from [`CompileConfig::build_cache`]
+    pub cached: bool,
 }
 
 /// Contract information from Cargo.toml (static info)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ContractInfo {
     pub name: String,
     pub version: String,
+    /// Selected target or variant of a multi-contract package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl ContractInfo {
+    /// Name of the artifact directory (`<name>.wasm`)
+    pub fn artifact_name(&self) -> &str {
+        self.target.as_deref().unwrap_or(&self.name)
+    }
 }
 
 /// Runtime information detected during compilation
@@ -44,17 +69,39 @@ pub struct RuntimeInfo {
     pub built_at: u64,
     /// Source tree hash
     pub source_tree_hash: String,
+    /// Nix derivation of the build environment, when built through the Nix backend
+    pub nix_derivation: Option<String>,
+    /// Locked flake inputs of the Nix build environment
+    pub nix_inputs: Vec<NixInput>,
+    /// Whether the build ran inside the fluent-builder Docker image
+    pub docker: bool,
+    /// The image and CLI of a Docker build, as passed by the CLI
+    pub build_environment: Option<BuildEnvironment>,
+    /// Root of the enclosing Cargo workspace relative to the project, e.g. `../..`
+    pub workspace_root: Option<String>,
 }
 
+/// Environment variable set by the Nix backend to the build environment derivation
+pub const NIX_DERIVATION_ENV: &str = "FLUENT_BUILDER_NIX_DERIVATION";
+
+/// Environment variable the Nix backend sets to the JSON list of locked [`NixInput`]s
+pub const NIX_INPUTS_ENV: &str = "FLUENT_BUILDER_NIX_INPUTS";
+
+/// Environment variable set to "1" in the fluent-builder Docker image
+pub const DOCKER_ENV: &str = "FLUENT_BUILDER_DOCKER";
+
+/// Environment variable the CLI sets to the JSON [`BuildEnvironment`] of a Docker build
+pub const BUILD_ENVIRONMENT_ENV: &str = "FLUENT_BUILDER_BUILD_ENVIRONMENT";
+
 /// Rust compiler information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RustInfo {
     pub version: String, // Version from rust-toolchain.toml like "1.83.0" or "nightly-2024-01-15"
     pub target: String,  // Always "wasm32-unknown-unknown" for now
 }
 
 /// SDK version information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SdkInfo {
     pub tag: String,    // Version tag like "0.1.0"
     pub commit: String, // Git commit hash or "unknown"
@@ -67,19 +114,56 @@ pub struct CompilationOutputs {
     pub rwasm: Vec<u8>,
 }
 
+/// Stage of a build, reported by [`compile_with_progress`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BuildEvent {
+    /// `cargo build` was started
+    CargoStarted,
+    /// A line cargo wrote to stderr: progress and rendered diagnostics
+    CargoOutputLine { line: String },
+    /// WASM bytecode is built, including the embedded build sections
+    WasmReady { size: usize },
+    /// rWASM translation finished
+    RwasmReady { size: usize },
+    /// WASM and rWASM were found in the build cache; cargo is not run
+    CacheHit,
+    /// ABI, interface and metadata were generated
+    ArtifactsGenerated,
+}
+
 /// Compile a Rust smart contract to WASM and rWASM
 pub fn build(config: &CompileConfig) -> Result<CompilationResult> {
+    compile_with_progress(config, |_| {})
+}
+
+/// [`build`], calling `on_event` as the build moves through its stages
+///
+/// Events are delivered on the calling thread. Async callers can run this
+/// on a blocking task and forward the events over a channel.
+pub fn compile_with_progress(
+    config: &CompileConfig,
+    on_event: impl Fn(BuildEvent),
+) -> Result<CompilationResult> {
     let start = std::time::Instant::now();
 
     // Validate configuration
     config.validate()?;
 
-    // Parse contract metadata and validate it's a Fluent contract
+    // Validate the project before the expensive build
     let cargo_toml_path = config.project_root.join("Cargo.toml");
-    let contract = parse_contract_info(&cargo_toml_path)?;
+    let contract = crate::contract::validate_contract(config)?;
+
+    // Workspace members share Cargo.lock and target/ with the workspace
+    let layout = CargoLayout::locate(&config.project_root).with_context(|| {
+        format!(
+            "Failed to locate the Cargo workspace of {}",
+            config.project_root.display()
+        )
+    })?;
 
     // Get SDK version from Cargo.lock
-    let sdk_version_string = read_sdk_version_from_cargo_lock(&config.project_root)?;
+    let sdk_version_string = read_sdk_version(&layout.cargo_lock())?;
     let sdk = parse_sdk_version(&sdk_version_string);
 
     tracing::info!(
@@ -90,16 +174,52 @@ pub fn build(config: &CompileConfig) -> Result<CompilationResult> {
     );
 
     // Detect Git information for source tracking
-    let git_info = crate::git::detect_git_info(&config.project_root)?;
+    let git_info =
+        crate::git::detect_git_info_with_timeout(&config.project_root, config.timeouts.git)?;
     log_git_status(&git_info);
 
-    // Compile to WASM
-    let wasm_bytecode = compile_to_wasm(config, &contract.name)?;
-    tracing::info!("WASM size: {} bytes", wasm_bytecode.len());
+    // Reuse the bytecode of an identical earlier build
+    let compat = config.compat.clone().unwrap_or_else(Compat::current);
+    let cache_key = match &config.build_cache {
+        Some(_) => Some(crate::output_cache::cache_key(config, &compat)?),
+        None => None,
+    };
+    let cached = config
+        .build_cache
+        .as_deref()
+        .zip(cache_key.as_deref())
+        .and_then(|(dir, key)| crate::output_cache::lookup(dir, key));
+    let is_cached = cached.is_some();
+    if let Some(dir) = &config.build_cache {
+        crate::cache::record_cache_dir_use(dir, is_cached);
+    }
 
-    // Compile to rWASM
-    let rwasm_bytecode = compile_to_rwasm(&wasm_bytecode)?;
-    tracing::info!("rWASM size: {} bytes", rwasm_bytecode.len());
+    let (wasm_bytecode, rwasm_bytecode) = match cached {
+        Some(outputs) => {
+            tracing::info!(
+                "Reusing cached build {}",
+                cache_key.as_deref().unwrap_or_default()
+            );
+            on_event(BuildEvent::CacheHit);
+            on_event(BuildEvent::WasmReady {
+                size: outputs.wasm.len(),
+            });
+            on_event(BuildEvent::RwasmReady {
+                size: outputs.rwasm.len(),
+            });
+            (outputs.wasm, outputs.rwasm)
+        }
+        None => {
+            let outputs = compile_bytecode(config, &target_directory(config, &layout), &on_event)?;
+            if let (Some(dir), Some(key)) = (&config.build_cache, &cache_key) {
+                if let Err(e) = crate::output_cache::store(dir, key, &outputs) {
+                    tracing::warn!("Failed to cache the build: {:#}", e);
+                }
+            }
+            (outputs.wasm, outputs.rwasm)
+        }
+    };
+    crate::size_limits::check_size_limits(&config.size_limits, &wasm_bytecode, &rwasm_bytecode)?;
 
     // Read Rust version from rust-toolchain.toml
     let rust_version = read_rust_toolchain_version(&config.project_root)?;
@@ -113,12 +233,19 @@ pub fn build(config: &CompileConfig) -> Result<CompilationResult> {
         rust,
         sdk,
         built_at: current_timestamp(),
-        source_tree_hash: calculate_source_hash(&config.project_root)?,
+        source_tree_hash: hash_sources(&config.project_root, &compat)?,
+        nix_derivation: std::env::var(NIX_DERIVATION_ENV)
+            .ok()
+            .filter(|d| !d.is_empty()),
+        nix_inputs: NixInput::from_env(),
+        docker: std::env::var(DOCKER_ENV).is_ok_and(|v| v == "1"),
+        build_environment: BuildEnvironment::from_env(),
+        workspace_root: layout.relative_workspace_root(&config.project_root),
     };
 
     // Generate artifacts if requested
     let artifacts = if should_generate_artifacts(&config.artifacts) {
-        Some(generate_contract_artifacts(
+        let artifacts = generate_contract_artifacts(
             &contract,
             &wasm_bytecode,
             &rwasm_bytecode,
@@ -126,7 +253,9 @@ pub fn build(config: &CompileConfig) -> Result<CompilationResult> {
             config,
             &runtime_info,
             &git_info,
-        )?)
+        )?;
+        on_event(BuildEvent::ArtifactsGenerated);
+        Some(artifacts)
     } else {
         None
     };
@@ -143,11 +272,45 @@ pub fn build(config: &CompileConfig) -> Result<CompilationResult> {
         artifacts,
         runtime_info,
         duration,
+        cached: is_cached,
+    })
+}
+
+/// The configured target directory, or the one cargo uses for the package's workspace
+fn target_directory(config: &CompileConfig, layout: &CargoLayout) -> PathBuf {
+    if config.target_dir.is_some() {
+        config.target_directory()
+    } else {
+        layout.target_directory.clone()
+    }
+}
+
+/// Build WASM and translate it to rWASM
+fn compile_bytecode(
+    config: &CompileConfig,
+    target_dir: &Path,
+    on_event: &impl Fn(BuildEvent),
+) -> Result<CompilationOutputs> {
+    let wasm_bytecode = compile_to_wasm(config, target_dir, on_event)?;
+    tracing::info!("WASM size: {} bytes", wasm_bytecode.len());
+    on_event(BuildEvent::WasmReady {
+        size: wasm_bytecode.len(),
+    });
+
+    let rwasm_bytecode = compile_to_rwasm(&wasm_bytecode, config.timeouts.rwasm)?;
+    tracing::info!("rWASM size: {} bytes", rwasm_bytecode.len());
+    on_event(BuildEvent::RwasmReady {
+        size: rwasm_bytecode.len(),
+    });
+
+    Ok(CompilationOutputs {
+        wasm: wasm_bytecode,
+        rwasm: rwasm_bytecode,
     })
 }
 
 /// Parse contract name and version from Cargo.toml and validate it's a Fluent contract
-fn parse_contract_info(cargo_toml_path: &Path) -> Result<ContractInfo> {
+pub(crate) fn parse_contract_info(cargo_toml_path: &Path) -> Result<ContractInfo> {
     let content = std::fs::read_to_string(cargo_toml_path)
         .with_context(|| format!("Failed to read {}", cargo_toml_path.display()))?;
 
@@ -180,25 +343,48 @@ fn parse_contract_info(cargo_toml_path: &Path) -> Result<ContractInfo> {
         .unwrap_or(false);
 
     if !has_sdk {
-        return Err(eyre::eyre!(
-            "Not a Fluent contract: no fluentbase-sdk dependency found in Cargo.toml"
-        ));
+        return Err(BuilderError::NotAFluentContract {
+            reason: "no fluentbase-sdk dependency found in Cargo.toml".to_string(),
+        }
+        .into());
     }
 
-    Ok(ContractInfo { name, version })
+    Ok(ContractInfo {
+        name,
+        version,
+        target: None,
+    })
 }
 
-/// Read SDK version from Cargo.lock
+/// Read SDK version from the Cargo.lock of the project's workspace
 pub fn read_sdk_version_from_cargo_lock(project_root: &Path) -> Result<String> {
-    let cargo_lock_path = project_root.join("Cargo.lock");
+    read_sdk_version(&cargo_lock_path(project_root))
+}
+
+/// Cargo.lock of the project's workspace, or of the project if that can't be located
+pub(crate) fn cargo_lock_path(project_root: &Path) -> PathBuf {
+    match CargoLayout::locate(project_root) {
+        Ok(layout) => layout.cargo_lock(),
+        Err(e) => {
+            tracing::debug!(
+                "Looking for Cargo.lock in {}: {:#}",
+                project_root.display(),
+                e
+            );
+            project_root.join("Cargo.lock")
+        }
+    }
+}
 
+/// Read SDK version from a Cargo.lock
+fn read_sdk_version(cargo_lock_path: &Path) -> Result<String> {
     if !cargo_lock_path.exists() {
         return Err(eyre::eyre!(
             "Cargo.lock not found. Run 'cargo build' first to generate it."
         ));
     }
 
-    let content = std::fs::read_to_string(&cargo_lock_path)?;
+    let content = std::fs::read_to_string(cargo_lock_path)?;
     let lock_file: toml::Value = toml::from_str(&content)?;
 
     let packages = lock_file
@@ -231,7 +417,7 @@ pub fn read_sdk_version_from_cargo_lock(project_root: &Path) -> Result<String> {
 }
 
 /// Parse SDK version into components
-fn parse_sdk_version(version: &str) -> SdkInfo {
+pub(crate) fn parse_sdk_version(version: &str) -> SdkInfo {
     match version.split_once('-') {
         Some((tag, commit)) => SdkInfo {
             tag: tag.to_string(),
@@ -301,7 +487,7 @@ fn validate_rust_version(channel: &str) -> Result<()> {
 }
 
 /// Find the main source file, respecting custom paths in Cargo.toml
-fn find_main_source(project_root: &Path, cargo_toml_path: &Path) -> Result<PathBuf> {
+pub(crate) fn find_main_source(project_root: &Path, cargo_toml_path: &Path) -> Result<PathBuf> {
     let content = std::fs::read_to_string(cargo_toml_path)
         .with_context(|| format!("Failed to read {}", cargo_toml_path.display()))?;
 
@@ -341,10 +527,19 @@ fn find_main_source(project_root: &Path, cargo_toml_path: &Path) -> Result<PathB
 }
 
 /// Compile Rust project to WASM
-fn compile_to_wasm(config: &CompileConfig, contract_name: &str) -> Result<Vec<u8>> {
+fn compile_to_wasm(
+    config: &CompileConfig,
+    target_dir: &Path,
+    on_event: &impl Fn(BuildEvent),
+) -> Result<Vec<u8>> {
+    let target = crate::contract::resolve_build_target(config)?;
+
     let mut cmd = Command::new("cargo");
     cmd.current_dir(&config.project_root)
-        .args(["build", "--target", config.target()]);
+        .args(["build", "--target", config.target()])
+        .args(target.cargo_args())
+        // Artifact paths on stdout, diagnostics still rendered on stderr
+        .arg("--message-format=json-render-diagnostics");
 
     // Add profile
     match config.profile.as_str() {
@@ -363,23 +558,57 @@ fn compile_to_wasm(config: &CompileConfig, contract_name: &str) -> Result<Vec<u8
     if config.locked {
         cmd.arg("--locked");
     }
+    if config.offline {
+        cmd.arg("--offline");
+    }
+    check_extra_cargo_args(&config.extra_cargo_args)?;
+    cmd.args(&config.extra_cargo_args);
+
+    // Explicit target dir so nothing is written into a read-only source tree
+    cmd.arg("--target-dir").arg(target_dir);
+
+    // Inside a build script, don't build the contract with the host crate's toolchain and flags
+    if crate::build_script::in_build_script() {
+        for var in crate::build_script::OUTER_CARGO_ENV {
+            cmd.env_remove(var);
+        }
+    }
+    // CARGO_ENCODED_RUSTFLAGS would take precedence over RUSTFLAGS
+    if let Some(rustflags) = &config.rustflags {
+        cmd.env_remove("CARGO_ENCODED_RUSTFLAGS")
+            .env("RUSTFLAGS", rustflags);
+    }
 
     tracing::debug!("Running: {:?}", cmd);
 
-    let output = cmd.output().context("Failed to execute cargo build")?;
+    on_event(BuildEvent::CargoStarted);
+    let output = crate::process::output_with_timeout_lines(
+        &mut cmd,
+        "cargo build",
+        config.timeouts.cargo_build,
+        |line| {
+            on_event(BuildEvent::CargoOutputLine {
+                line: line.to_string(),
+            })
+        },
+    )?;
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(eyre::eyre!("Cargo build failed:\n{}", stderr));
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(match crate::error::missing_toolchain(&stderr) {
+            Some(toolchain) => BuilderError::MissingToolchain { toolchain },
+            None => BuilderError::CargoBuildFailed { stderr },
+        }
+        .into());
     }
 
-    // Find the compiled WASM file
-    let wasm_filename = format!("{}.wasm", contract_name.replace('-', "_"));
-    let wasm_path = config
-        .project_root
-        .join("target")
-        .join(config.target())
-        .join(&config.profile)
-        .join(&wasm_filename);
+    // Find the compiled WASM file where cargo reports it, or where the profile puts it
+    let wasm_filename = target.wasm_file_name();
+    let wasm_path = wasm_artifact_path(&output.stdout, &wasm_filename).unwrap_or_else(|| {
+        target_dir
+            .join(config.target())
+            .join(profile_dir(&config.profile))
+            .join(&wasm_filename)
+    });
 
     if !wasm_path.exists() {
         return Err(eyre::eyre!(
@@ -392,15 +621,102 @@ fn compile_to_wasm(config: &CompileConfig, contract_name: &str) -> Result<Vec<u8
     std::fs::read(&wasm_path).with_context(|| format!("Failed to read {}", wasm_path.display()))
 }
 
+/// Cargo options fluent-builder sets itself, which extra arguments must not override
+const MODELED_CARGO_ARGS: &[&str] = &[
+    "--target",
+    "--target-dir",
+    "--release",
+    "-r",
+    "--profile",
+    "--features",
+    "-F",
+    "--all-features",
+    "--no-default-features",
+    "--message-format",
+    "--lib",
+    "--bin",
+    "--bins",
+    "--example",
+    "--package",
+    "-p",
+    "--workspace",
+    "--manifest-path",
+    "--locked",
+    "--offline",
+    "--frozen",
+];
+
+/// Refuse extra cargo arguments that would override what fluent-builder sets
+fn check_extra_cargo_args(args: &[String]) -> Result<()> {
+    match args.iter().find(|arg| is_modeled_cargo_arg(arg)) {
+        Some(arg) => Err(eyre::eyre!(
+            "Extra cargo argument {} is set by fluent-builder; use its own option instead",
+            arg
+        )),
+        None => Ok(()),
+    }
+}
+
+fn is_modeled_cargo_arg(arg: &str) -> bool {
+    let name = arg.split('=').next().unwrap_or(arg);
+    // Short options take their value attached too, as in `-Ftestnet`
+    let short = match arg.strip_prefix('-') {
+        Some(rest) if !rest.starts_with('-') => arg.get(..2),
+        _ => None,
+    };
+    MODELED_CARGO_ARGS.contains(&name) || short.is_some_and(|s| MODELED_CARGO_ARGS.contains(&s))
+}
+
+/// Path of `wasm_filename` in the `compiler-artifact` messages of `cargo build
+/// --message-format=json`
+fn wasm_artifact_path(stdout: &[u8], wasm_filename: &str) -> Option<PathBuf> {
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-artifact")
+        .flat_map(|message| {
+            message["filenames"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|f| f.as_str().map(PathBuf::from))
+                .collect::<Vec<_>>()
+        })
+        .rfind(|path| path.file_name().is_some_and(|name| name == wasm_filename))
+}
+
+/// Directory cargo writes a profile's outputs to: `dev` and `test` share `debug`, `bench` uses
+/// `release`
+fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        custom => custom,
+    }
+}
+
 /// Convert WASM to rWASM
-fn compile_to_rwasm(wasm_bytecode: &[u8]) -> Result<Vec<u8>> {
-    let result = fluentbase_types::compile_wasm_to_rwasm(wasm_bytecode)
-        .map_err(|e| eyre::eyre!("rWASM compilation failed: {:?}", e))?;
-    Ok(result.rwasm_bytecode.to_vec())
+fn compile_to_rwasm(wasm_bytecode: &[u8], timeout: Option<Duration>) -> Result<Vec<u8>> {
+    let wasm_bytecode = wasm_bytecode.to_vec();
+    crate::process::run_with_timeout("rWASM translation", timeout, move || {
+        fluentbase_types::compile_wasm_to_rwasm(&wasm_bytecode)
+            .map(|result| result.rwasm_bytecode.to_vec())
+            .map_err(|e| {
+                BuilderError::RwasmCompilationFailed {
+                    reason: format!("{:?}", e),
+                }
+                .into()
+            })
+    })?
 }
 
 /// Calculate SHA256 hash of source files
-fn calculate_source_hash(project_root: &Path) -> Result<String> {
+pub(crate) fn calculate_source_hash(project_root: &Path) -> Result<String> {
+    hash_sources(project_root, &Compat::current())
+}
+
+/// Source tree hash as computed by the builder version of `compat`
+pub(crate) fn hash_sources(project_root: &Path, compat: &Compat) -> Result<String> {
     let mut hasher = Sha256::new();
     let mut file_count = 0;
 
@@ -421,8 +737,13 @@ fn calculate_source_hash(project_root: &Path) -> Result<String> {
     {
         let path = entry.path();
 
-        // Skip build outputs and hidden directories
-        if should_skip_path(path) {
+        // Skip build outputs and hidden directories inside the project
+        let checked = if compat.project_relative_source_hash {
+            path.strip_prefix(project_root).unwrap_or(path)
+        } else {
+            path
+        };
+        if should_skip_path(checked) {
             continue;
         }
 
@@ -438,8 +759,9 @@ fn calculate_source_hash(project_root: &Path) -> Result<String> {
                 .unwrap_or(false);
 
         if should_include {
-            let content = std::fs::read(path)?;
-            hasher.update(&content);
+            let file = std::fs::File::open(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            hasher = digest_reader(hasher, file)?.0;
             file_count += 1;
         }
     }
@@ -449,7 +771,7 @@ fn calculate_source_hash(project_root: &Path) -> Result<String> {
 }
 
 /// Check if path should be skipped for source hashing
-fn should_skip_path(path: &Path) -> bool {
+pub(crate) fn should_skip_path(path: &Path) -> bool {
     path.components().any(|c| {
         c.as_os_str()
             .to_str()
@@ -468,21 +790,73 @@ fn generate_contract_artifacts(
     runtime_info: &RuntimeInfo,
     git_info: &Option<crate::GitInfo>,
 ) -> Result<artifacts::ContractArtifacts> {
-    // Find and parse routers
+    // Find and parse routers, the constructor and custom errors
     let main_source = find_main_source(&config.project_root, cargo_toml_path)?;
-    let routers = parser::parse_routers(&main_source).unwrap_or_else(|e| {
-        tracing::warn!("Failed to parse routers: {}", e);
-        vec![]
-    });
+    let routers = match parser::parse_routers(&main_source) {
+        Ok(routers) => routers,
+        Err(e) if config.strict => {
+            return Err(e.wrap_err(format!(
+                "Failed to parse routers of {} (strict mode)",
+                main_source.display()
+            )))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to parse routers: {}", e);
+            vec![]
+        }
+    };
+    let deploy = match parser::parse_deploy(&main_source) {
+        Ok(deploy) => deploy,
+        Err(e) if config.strict => {
+            return Err(e.wrap_err(format!(
+                "Failed to parse the constructor of {} (strict mode)",
+                main_source.display()
+            )))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to parse constructor parameters: {}", e);
+            None
+        }
+    };
+    let errors = match parser::parse_errors(&main_source) {
+        Ok(errors) => errors,
+        Err(e) if config.strict => {
+            return Err(e.wrap_err(format!(
+                "Failed to parse the custom errors of {} (strict mode)",
+                main_source.display()
+            )))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to parse custom errors: {}", e);
+            vec![]
+        }
+    };
 
     // Determine source type
-    let source = determine_source_type(&config.project_root, git_info);
+    let mut source = determine_source_type(
+        &config.project_root,
+        git_info,
+        config.strict,
+        config.timeouts.git,
+    )?;
+    if let artifacts::metadata::Source::Archive {
+        include_globs,
+        exclude_globs,
+        ..
+    } = &mut source
+    {
+        include_globs.clone_from(&config.artifacts.archive_include);
+        exclude_globs.clone_from(&config.artifacts.archive_exclude);
+    }
 
     artifacts::generate(
-        contract,
-        wasm_bytecode,
-        rwasm_bytecode,
-        &routers,
+        artifacts::CompiledContract {
+            contract,
+            wasm: wasm_bytecode,
+            rwasm: rwasm_bytecode,
+            routers: &routers,
+            declarations: &parser::Declarations { deploy, errors },
+        },
         &config.project_root,
         config,
         runtime_info,
@@ -491,14 +865,33 @@ fn generate_contract_artifacts(
 }
 
 /// Determine source type based on Git state
+///
+/// In strict mode a git source must name a remote and the project's path in it.
 fn determine_source_type(
     project_root: &Path,
     git_info: &Option<crate::GitInfo>,
-) -> artifacts::metadata::Source {
-    match git_info {
+    strict: bool,
+    git_timeout: Option<Duration>,
+) -> Result<artifacts::metadata::Source> {
+    Ok(match git_info {
         Some(git) if !git.is_dirty => {
-            let project_path = crate::git::get_project_path_in_repo(project_root)
-                .unwrap_or_else(|_| ".".to_string());
+            if strict && git.remote_url.is_empty() {
+                return Err(eyre::eyre!(
+                    "Repository has no remote URL, so the git source can't be fetched (strict \
+                     mode); \
+                     add a remote with `git remote add origin <url>`"
+                ));
+            }
+            let project_path = match crate::git::get_project_path_in_repo(project_root, git_timeout)
+            {
+                Ok(path) => path,
+                Err(e) if strict => {
+                    return Err(
+                        e.wrap_err("Failed to locate the project in its repository (strict mode)")
+                    )
+                }
+                Err(_) => ".".to_string(),
+            };
 
             artifacts::metadata::Source::Git {
                 repository: git.remote_url.clone(),
@@ -506,11 +899,8 @@ fn determine_source_type(
                 project_path,
             }
         }
-        _ => artifacts::metadata::Source::Archive {
-            archive_path: "./source.tar.gz".to_string(),
-            project_path: ".".to_string(),
-        },
-    }
+        _ => artifacts::metadata::Source::archive("."),
+    })
 }
 
 /// Log Git repository status
@@ -555,7 +945,7 @@ fn should_generate_artifacts(config: &crate::config::ArtifactsConfig) -> bool {
 
 /// Hash bytes to SHA256 hex string
 pub fn hash_bytes(data: &[u8]) -> String {
-    format!("{:x}", Sha256::digest(data))
+    HashAlgorithm::Sha256.hash(data)
 }
 
 /// Get rWASM hash from compilation result
@@ -567,3 +957,95 @@ pub fn get_rwasm_hash(result: &CompilationResult) -> String {
 pub fn get_wasm_hash(result: &CompilationResult) -> String {
     hash_bytes(&result.outputs.wasm)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasm_artifact_path() {
+        let stdout = [
+            concat!(
+                r#"{"reason":"compiler-artifact","target":{"name":"fluentbase_sdk"},"#,
+                r#""filenames":["/t/wasm32-unknown-unknown/size/deps/libfluentbase_sdk.rlib"]}"#,
+            ),
+            concat!(
+                r#"{"reason":"compiler-artifact","target":{"name":"token"},"#,
+                r#""filenames":["/t/wasm32-unknown-unknown/size/token.wasm","#,
+                r#""/t/wasm32-unknown-unknown/size/libtoken.rlib"]}"#,
+            ),
+            r#"{"reason":"build-finished","success":true}"#,
+        ]
+        .join("\n");
+
+        assert_eq!(
+            wasm_artifact_path(stdout.as_bytes(), "token.wasm"),
+            Some(PathBuf::from("/t/wasm32-unknown-unknown/size/token.wasm"))
+        );
+        assert_eq!(wasm_artifact_path(stdout.as_bytes(), "vault.wasm"), None);
+        assert_eq!(wasm_artifact_path(b"not json", "token.wasm"), None);
+    }
+
+    #[test]
+    fn test_build_event_json() {
+        assert_eq!(
+            serde_json::to_value(BuildEvent::CargoStarted).unwrap(),
+            serde_json::json!({"event": "cargo_started"})
+        );
+        assert_eq!(
+            serde_json::to_value(BuildEvent::WasmReady { size: 1024 }).unwrap(),
+            serde_json::json!({"event": "wasm_ready", "size": 1024})
+        );
+    }
+
+    #[test]
+    fn test_modeled_cargo_args() {
+        assert!(is_modeled_cargo_arg("--features"));
+        assert!(is_modeled_cargo_arg("--target-dir=/tmp"));
+        assert!(is_modeled_cargo_arg("-Ftestnet"));
+        assert!(is_modeled_cargo_arg("--manifest-path=../other/Cargo.toml"));
+        assert!(!is_modeled_cargo_arg("--config"));
+        assert!(!is_modeled_cargo_arg("-Ztrim-paths"));
+
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        check_extra_cargo_args(&args(&["--config", "net.git-fetch-with-cli=true"])).unwrap();
+        assert_eq!(
+            check_extra_cargo_args(&args(&["-Ztrim-paths", "--frozen"]))
+                .unwrap_err()
+                .to_string(),
+            "Extra cargo argument --frozen is set by fluent-builder; use its own option instead"
+        );
+    }
+
+    #[test]
+    fn test_profile_dir() {
+        assert_eq!(profile_dir("release"), "release");
+        assert_eq!(profile_dir("debug"), "debug");
+        assert_eq!(profile_dir("dev"), "debug");
+        assert_eq!(profile_dir("test"), "debug");
+        assert_eq!(profile_dir("bench"), "release");
+        assert_eq!(profile_dir("size"), "size");
+    }
+
+    #[test]
+    fn test_strict_source_type() {
+        let git = Some(crate::GitInfo {
+            remote_url: String::new(),
+            commit_hash: "a".repeat(40),
+            commit_hash_short: "a".repeat(7),
+            branch: "main".to_string(),
+            is_dirty: false,
+            dirty_files_count: 0,
+        });
+        let project_root = Path::new("/nonexistent");
+
+        let source = determine_source_type(project_root, &git, false, None).unwrap();
+        assert!(
+            matches!(source, artifacts::metadata::Source::Git { repository, project_path, .. }
+            if repository.is_empty() && project_path == ".")
+        );
+        assert!(determine_source_type(project_root, &git, true, None).is_err());
+        // Archive sources don't depend on git
+        assert!(determine_source_type(project_root, &None, true, None).is_ok());
+    }
+}
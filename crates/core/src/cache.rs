@@ -0,0 +1,556 @@
+//! Usage tracking and garbage collection of managed caches
+//!
+//! Builder images, the cargo registry volumes shared by Docker builds and the
+//! per-user cache directory grow with every SDK and toolchain version. Their
+//! last use and hit counts are recorded in a small usage file, so they can be
+//! evicted least recently used first. Concurrent builds update the file under
+//! a lock file and replace it by renaming, so no update is lost or torn.
+
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// File recording cache usage, inside the per-user cache directory
+pub const CACHE_USAGE_FILE: &str = "cache-usage.json";
+
+/// Overrides the per-user cache directory
+pub const CACHE_DIR_ENV: &str = "FLUENT_BUILDER_CACHE_DIR";
+
+/// How long an update of the usage file waits for another process to release it
+const USAGE_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Age after which a lock is taken to be left behind by a process that died holding it
+const STALE_USAGE_LOCK: Duration = Duration::from_secs(30);
+
+/// Kind of a managed cache
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheKind {
+    /// Builder image (`fluent-builder-<sdk>-rust-<toolchain>`)
+    Image,
+    /// Docker volume with downloaded crates or compiled bytecode
+    Volume,
+    /// File or directory in the per-user cache directory
+    File,
+}
+
+impl CacheKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            CacheKind::Image => "image",
+            CacheKind::Volume => "volume",
+            CacheKind::File => "file",
+        }
+    }
+}
+
+/// A cache that can be evicted as a whole
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CacheEntry {
+    pub kind: CacheKind,
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Unix timestamp in seconds; the creation time if no use was recorded
+    pub last_used: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheEntry {
+    /// Entry with the usage recorded for it, or `created` as its last use
+    pub fn new(kind: CacheKind, name: &str, size: u64, created: u64, usage: &CacheUsage) -> Self {
+        let recorded = usage.entries.get(&usage_key(kind, name));
+        Self {
+            kind,
+            name: name.to_string(),
+            size,
+            last_used: recorded.map_or(created, |u| u.last_used.max(created)),
+            hits: recorded.map_or(0, |u| u.hits),
+            misses: recorded.map_or(0, |u| u.misses),
+        }
+    }
+}
+
+/// Recorded uses of the managed caches
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheUsage {
+    #[serde(default)]
+    entries: BTreeMap<String, Usage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Usage {
+    /// Unix timestamp in seconds
+    last_used: u64,
+    #[serde(default)]
+    hits: u64,
+    #[serde(default)]
+    misses: u64,
+}
+
+impl CacheUsage {
+    /// `$XDG_CACHE_HOME/fluent-builder/cache-usage.json`
+    pub fn default_path() -> PathBuf {
+        user_cache_dir().join(CACHE_USAGE_FILE)
+    }
+
+    /// Load recorded usage; a missing or corrupt file is empty
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Replace the file at `path`; readers see either the old or the new usage
+    pub fn save(&self, path: &Path) -> Result<()> {
+        // Unique per save, also for saves from several threads of one process
+        static SAVES: AtomicUsize = AtomicUsize::new(0);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let partial = sibling(
+            path,
+            &format!(
+                "{}.{}.tmp",
+                std::process::id(),
+                SAVES.fetch_add(1, Ordering::Relaxed)
+            ),
+        );
+        let guard = crate::CleanupGuard::remove_path(&partial);
+        std::fs::write(&partial, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        std::fs::rename(&partial, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        drop(guard);
+        Ok(())
+    }
+
+    /// Load, change and save the usage at `path` while holding its lock
+    pub fn update(path: &Path, change: impl FnOnce(&mut Self)) -> Result<()> {
+        let _lock = UsageLock::acquire(path)?;
+        let mut usage = Self::load(path);
+        change(&mut usage);
+        usage.save(path)
+    }
+
+    /// Record a use of a cache: `Some(true)` if it had what was needed, `Some(false)` if it had to
+    /// be filled
+    pub fn record(&mut self, kind: CacheKind, name: &str, hit: Option<bool>) {
+        let usage = self.entries.entry(usage_key(kind, name)).or_default();
+        usage.last_used = now();
+        match hit {
+            Some(true) => usage.hits += 1,
+            Some(false) => usage.misses += 1,
+            None => {}
+        }
+    }
+
+    /// Drop the usage of an evicted cache
+    pub fn forget(&mut self, kind: CacheKind, name: &str) {
+        self.entries.remove(&usage_key(kind, name));
+    }
+}
+
+fn usage_key(kind: CacheKind, name: &str) -> String {
+    format!("{}:{}", kind.prefix(), name)
+}
+
+/// Record the use of a cache for `cache stats` and `cache gc`; failures are only logged
+pub fn record_cache_use(kind: CacheKind, name: &str, hit: Option<bool>) {
+    let result = CacheUsage::update(&CacheUsage::default_path(), |usage| {
+        usage.record(kind, name, hit)
+    });
+    if let Err(e) = result {
+        tracing::debug!("Failed to record cache usage: {:#}", e);
+    }
+}
+
+/// Record the use of a cache directory; only directories in the per-user cache directory are
+/// managed
+pub(crate) fn record_cache_dir_use(dir: &Path, hit: bool) {
+    let cache_dir = user_cache_dir();
+    let entry = dir
+        .strip_prefix(&cache_dir)
+        .ok()
+        .and_then(|relative| relative.components().next());
+    if let Some(entry) = entry {
+        record_cache_use(
+            CacheKind::File,
+            &entry.as_os_str().to_string_lossy(),
+            Some(hit),
+        );
+    }
+}
+
+/// `<path>.<suffix>`, next to `path`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Exclusive lock of the usage file, held while the lock file exists
+struct UsageLock {
+    path: PathBuf,
+}
+
+impl UsageLock {
+    fn acquire(usage_path: &Path) -> Result<Self> {
+        let path = sibling(usage_path, "lock");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let deadline = Instant::now() + USAGE_LOCK_TIMEOUT;
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = std::fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > STALE_USAGE_LOCK);
+                    if stale {
+                        tracing::debug!("Removing stale lock {}", path.display());
+                        let _ = std::fs::remove_file(&path);
+                    } else if Instant::now() > deadline {
+                        return Err(eyre::eyre!(
+                            "Timed out waiting for {}; remove it if no build is running",
+                            path.display()
+                        ));
+                    } else {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to create {}", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for UsageLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Per-user cache directory: `$FLUENT_BUILDER_CACHE_DIR`, or `$XDG_CACHE_HOME/fluent-builder`
+pub fn user_cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(CACHE_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("fluent-builder")
+}
+
+/// Top-level files and directories of `dir`, except the usage file
+pub fn cache_dir_entries(dir: &Path, usage: &CacheUsage) -> Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+    for entry in read_dir {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // The usage file, its lock and partial writes
+        if name.starts_with(CACHE_USAGE_FILE) {
+            continue;
+        }
+        let mut size = 0;
+        let mut modified = 0;
+        for file in walkdir::WalkDir::new(entry.path())
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let Ok(metadata) = file.metadata() else {
+                continue;
+            };
+            if metadata.is_file() {
+                size += metadata.len();
+            }
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+            modified = modified.max(mtime.map_or(0, |d| d.as_secs()));
+        }
+        entries.push(CacheEntry::new(
+            CacheKind::File,
+            &name,
+            size,
+            modified,
+            usage,
+        ));
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// Limits enforced by garbage collection
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GcLimits {
+    /// Total size in bytes to stay under
+    pub max_size: Option<u64>,
+    /// Evict caches unused for longer
+    pub max_age: Option<Duration>,
+}
+
+/// Entries to evict, least recently used first
+///
+/// Everything unused for longer than `max_age` goes, then the least recently
+/// used entries until the rest fits in `max_size`.
+pub fn plan_gc(entries: &[CacheEntry], limits: GcLimits, now: u64) -> Vec<CacheEntry> {
+    let mut by_last_use: Vec<&CacheEntry> = entries.iter().collect();
+    by_last_use.sort_by_key(|entry| entry.last_used);
+
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut evicted = Vec::new();
+    for entry in by_last_use {
+        let expired = limits
+            .max_age
+            .is_some_and(|age| now.saturating_sub(entry.last_used) > age.as_secs());
+        let over_size = limits.max_size.is_some_and(|max| total > max);
+        if expired || over_size {
+            total -= entry.size;
+            evicted.push(entry.clone());
+        }
+    }
+    evicted
+}
+
+/// `20G`, `512M`, `1.5GiB` (binary units) or a number of bytes
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| eyre::eyre!("Invalid size `{}`", size))?;
+    let exponent = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        _ => {
+            return Err(eyre::eyre!(
+                "Unknown size unit in `{}`; use K, M, G or T",
+                size
+            ))
+        }
+    };
+    Ok((number * 1024f64.powi(exponent)) as u64)
+}
+
+/// `30d`, `12h`, `2w`, `90m` or `45s`
+pub fn parse_age(age: &str) -> Result<Duration> {
+    let age = age.trim();
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (number, unit) = age.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| eyre::eyre!("Invalid age `{}`", age))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => {
+            return Err(eyre::eyre!(
+                "Unknown age unit in `{}`; use s, m, h, d or w",
+                age
+            ))
+        }
+    };
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// `1.5 GiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// RFC 3339 timestamp (as printed by Docker) in Unix seconds
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    chrono::DateTime::parse_from_rfc3339(timestamp.trim())
+        .ok()
+        .and_then(|t| u64::try_from(t.timestamp()).ok())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u64, last_used: u64) -> CacheEntry {
+        CacheEntry::new(
+            CacheKind::Image,
+            name,
+            size,
+            last_used,
+            &CacheUsage::default(),
+        )
+    }
+
+    #[test]
+    fn test_plan_gc() {
+        let day = 86400;
+        let now = 100 * day;
+        let entries = [
+            entry("a-day", 10, now - day),
+            entry("forty-days", 5, now - 40 * day),
+            entry("ten-days", 30, now - 10 * day),
+            entry("five-days", 20, now - 5 * day),
+        ];
+        let names =
+            |evicted: Vec<CacheEntry>| evicted.into_iter().map(|e| e.name).collect::<Vec<_>>();
+
+        let max_age = GcLimits {
+            max_size: None,
+            max_age: Some(Duration::from_secs(30 * day)),
+        };
+        assert_eq!(names(plan_gc(&entries, max_age, now)), ["forty-days"]);
+
+        // Least recently used first, until the rest fits
+        let max_size = GcLimits {
+            max_size: Some(30),
+            max_age: None,
+        };
+        assert_eq!(
+            names(plan_gc(&entries, max_size, now)),
+            ["forty-days", "ten-days"]
+        );
+        let max_size = GcLimits {
+            max_size: Some(20),
+            max_age: None,
+        };
+        assert_eq!(
+            names(plan_gc(&entries, max_size, now)),
+            ["forty-days", "ten-days", "five-days"]
+        );
+
+        assert!(plan_gc(&entries, GcLimits::default(), now).is_empty());
+    }
+
+    #[test]
+    fn test_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CACHE_USAGE_FILE);
+
+        let mut usage = CacheUsage::load(&path);
+        usage.record(
+            CacheKind::Image,
+            "fluent-builder-0.4.0-rust-1.88",
+            Some(false),
+        );
+        usage.record(
+            CacheKind::Image,
+            "fluent-builder-0.4.0-rust-1.88",
+            Some(true),
+        );
+        usage.record(CacheKind::Volume, "cargo-registry", None);
+        usage.save(&path).unwrap();
+
+        let usage = CacheUsage::load(&path);
+        let image = CacheEntry::new(
+            CacheKind::Image,
+            "fluent-builder-0.4.0-rust-1.88",
+            1,
+            0,
+            &usage,
+        );
+        assert_eq!((image.hits, image.misses), (1, 1));
+        assert!(image.last_used > 0);
+        // Usage is per kind
+        assert_eq!(
+            CacheEntry::new(CacheKind::File, "cargo-registry", 1, 7, &usage).last_used,
+            7
+        );
+
+        std::fs::write(dir.path().join("facts.json"), "{}").unwrap();
+        let files = cache_dir_entries(dir.path(), &usage).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!((files[0].name.as_str(), files[0].size), ("facts.json", 2));
+    }
+
+    #[test]
+    fn test_concurrent_usage_updates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CACHE_USAGE_FILE);
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        CacheUsage::update(&path, |usage| {
+                            usage.record(CacheKind::File, "builds", Some(true))
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+
+        let usage = CacheUsage::load(&path);
+        assert_eq!(
+            CacheEntry::new(CacheKind::File, "builds", 1, 0, &usage).hits,
+            80
+        );
+        // Only the usage file is left
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_size("20G").unwrap(), 20 << 30);
+        assert_eq!(parse_size("1.5GiB").unwrap(), 3 << 29);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert!(parse_size("20X").is_err());
+        assert_eq!(parse_age("30d").unwrap(), Duration::from_secs(30 * 86400));
+        assert!(parse_age("30").is_err());
+        assert_eq!(format_size(3 << 29), "1.5 GiB");
+        assert_eq!(parse_timestamp("1970-01-02T00:00:00.5Z"), Some(86400));
+    }
+}
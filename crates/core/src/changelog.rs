@@ -0,0 +1,546 @@
+//! Changelog generation between two contract versions
+
+use crate::{
+    artifacts::{Abi, AbiEntry, AbiFunction},
+    build,
+    cleanup::CleanupGuard,
+    get_rwasm_hash, CompileConfig, Sandbox, Timeouts,
+};
+use eyre::{Context, Result};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Everything the changelog compares for one version of a contract
+#[derive(Debug, Clone, Default)]
+pub struct ContractSnapshot {
+    /// Human-readable label (git ref or directory name)
+    pub label: String,
+    /// Contract version from Cargo.toml, if known
+    pub contract_version: Option<String>,
+    /// Generated ABI
+    pub abi: Abi,
+    /// WASM size in bytes
+    pub wasm_size: Option<usize>,
+    /// rWASM size in bytes
+    pub rwasm_size: Option<usize>,
+    /// rWASM hash as recorded in metadata
+    pub rwasm_hash: Option<String>,
+    /// Package versions from Cargo.lock (name -> versions)
+    pub dependencies: BTreeMap<String, Vec<String>>,
+}
+
+impl ContractSnapshot {
+    /// Load a snapshot from a saved artifacts directory (`out/<name>.wasm/`)
+    ///
+    /// A `Cargo.lock` placed next to the artifacts is used for dependency changes.
+    pub fn from_artifacts_dir(dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(eyre::eyre!(
+                "Artifacts directory not found: {}",
+                dir.display()
+            ));
+        }
+
+        let abi = match read_json(&dir.join("abi.json"))? {
+            Some(abi) => serde_json::from_value(abi).context("abi.json must contain a JSON ABI")?,
+            None => Vec::new(),
+        };
+
+        let mut snapshot = ContractSnapshot {
+            label: dir.display().to_string(),
+            abi,
+            ..Default::default()
+        };
+
+        if let Some(metadata) = read_json(&dir.join("metadata.json"))? {
+            snapshot.contract_version = metadata["contract"]["version"].as_str().map(String::from);
+            snapshot.wasm_size = metadata["bytecode"]["wasm"]["size"]
+                .as_u64()
+                .map(|s| s as usize);
+            snapshot.rwasm_size = metadata["bytecode"]["rwasm"]["size"]
+                .as_u64()
+                .map(|s| s as usize);
+            snapshot.rwasm_hash = metadata["bytecode"]["rwasm"]["hash"]
+                .as_str()
+                .map(String::from);
+        } else {
+            snapshot.wasm_size = file_size(&dir.join("lib.wasm"));
+            snapshot.rwasm_size = file_size(&dir.join("lib.rwasm"));
+        }
+
+        let cargo_lock = dir.join("Cargo.lock");
+        if cargo_lock.exists() {
+            snapshot.dependencies = parse_cargo_lock(&std::fs::read_to_string(&cargo_lock)?)?;
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Build the contract as of `git_ref` in a temporary worktree and snapshot the result
+    pub fn from_git_ref(project_root: &Path, git_ref: &str, timeouts: &Timeouts) -> Result<Self> {
+        let project_path = crate::git::get_project_path_in_repo(project_root, timeouts.git)?;
+        let worktree = WorktreeGuard::create(project_root, git_ref)?;
+        let checkout_root = worktree.path.join(&project_path);
+
+        let mut config = CompileConfig::new(&checkout_root);
+        config.use_git_source = false;
+        config.timeouts = timeouts.clone();
+        worktree.configure(&mut config);
+
+        let result =
+            build(&config).with_context(|| format!("Failed to build contract at {}", git_ref))?;
+
+        let dependencies = match std::fs::read_to_string(checkout_root.join("Cargo.lock")) {
+            Ok(content) => parse_cargo_lock(&content)?,
+            Err(_) => BTreeMap::new(),
+        };
+
+        Ok(ContractSnapshot {
+            label: git_ref.to_string(),
+            contract_version: Some(result.contract.version.clone()),
+            abi: result
+                .artifacts
+                .as_ref()
+                .map(|a| a.abi.clone())
+                .unwrap_or_default(),
+            wasm_size: Some(result.outputs.wasm.len()),
+            rwasm_size: Some(result.outputs.rwasm.len()),
+            rwasm_hash: Some(format!("sha256:{}", get_rwasm_hash(&result))),
+            dependencies,
+        })
+    }
+}
+
+/// Differences between two contract versions
+#[derive(Debug, Clone, Default)]
+pub struct Changelog {
+    pub old_label: String,
+    pub new_label: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// Function signatures present only in the new version
+    pub functions_added: Vec<String>,
+    /// Function signatures present only in the old version
+    pub functions_removed: Vec<String>,
+    /// (old, new) WASM size
+    pub wasm_size: (Option<usize>, Option<usize>),
+    /// (old, new) rWASM size
+    pub rwasm_size: (Option<usize>, Option<usize>),
+    /// Whether the rWASM hash changed (None if unknown)
+    pub bytecode_changed: Option<bool>,
+    /// Dependency version changes from Cargo.lock
+    pub dependencies: Vec<DependencyChange>,
+}
+
+/// A single package change between two Cargo.lock files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyChange {
+    pub name: String,
+    /// Versions before the change (empty if added)
+    pub old: Vec<String>,
+    /// Versions after the change (empty if removed)
+    pub new: Vec<String>,
+}
+
+/// Compare two snapshots
+pub fn generate_changelog(old: &ContractSnapshot, new: &ContractSnapshot) -> Changelog {
+    let old_functions = function_signatures(&old.abi);
+    let new_functions = function_signatures(&new.abi);
+
+    let bytecode_changed = match (&old.rwasm_hash, &new.rwasm_hash) {
+        (Some(old_hash), Some(new_hash)) => Some(old_hash != new_hash),
+        _ => None,
+    };
+
+    let mut dependencies = Vec::new();
+    for (name, old_versions) in &old.dependencies {
+        let new_versions = new.dependencies.get(name).cloned().unwrap_or_default();
+        if *old_versions != new_versions {
+            dependencies.push(DependencyChange {
+                name: name.clone(),
+                old: old_versions.clone(),
+                new: new_versions,
+            });
+        }
+    }
+    for (name, new_versions) in &new.dependencies {
+        if !old.dependencies.contains_key(name) {
+            dependencies.push(DependencyChange {
+                name: name.clone(),
+                old: Vec::new(),
+                new: new_versions.clone(),
+            });
+        }
+    }
+    dependencies.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Changelog {
+        old_label: old.label.clone(),
+        new_label: new.label.clone(),
+        old_version: old.contract_version.clone(),
+        new_version: new.contract_version.clone(),
+        functions_added: new_functions.difference(&old_functions).cloned().collect(),
+        functions_removed: old_functions.difference(&new_functions).cloned().collect(),
+        wasm_size: (old.wasm_size, new.wasm_size),
+        rwasm_size: (old.rwasm_size, new.rwasm_size),
+        bytecode_changed,
+        dependencies,
+    }
+}
+
+impl Changelog {
+    /// Render the changelog as Markdown suitable for release notes
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+
+        let title = match (&self.old_version, &self.new_version) {
+            (Some(old), Some(new)) if old != new => format!("{} → {}", old, new),
+            _ => format!("{} → {}", self.old_label, self.new_label),
+        };
+        md.push_str(&format!("# Changelog: {}\n\n", title));
+        md.push_str(&self.render_sections("##"));
+        md
+    }
+
+    /// Render the changelog as a section of a `CHANGELOG.md` file
+    pub fn to_release_notes(&self, heading: &str) -> String {
+        let mut md = format!("## {}\n\n", heading);
+        md.push_str(&self.render_sections("###"));
+        md
+    }
+
+    /// Render the ABI, bytecode and dependency sections at the given heading level
+    fn render_sections(&self, level: &str) -> String {
+        let mut md = String::new();
+
+        md.push_str(&format!("{} ABI\n\n", level));
+        if self.functions_added.is_empty() && self.functions_removed.is_empty() {
+            md.push_str("No ABI changes.\n");
+        } else {
+            for signature in &self.functions_added {
+                md.push_str(&format!("- Added `{}`\n", signature));
+            }
+            for signature in &self.functions_removed {
+                md.push_str(&format!("- **Removed** `{}` (breaking)\n", signature));
+            }
+        }
+
+        md.push_str(&format!("\n{} Bytecode\n\n", level));
+        md.push_str("| Artifact | Old | New | Delta |\n");
+        md.push_str("| :--- | ---: | ---: | ---: |\n");
+        for (name, (old, new)) in [("WASM", self.wasm_size), ("rWASM", self.rwasm_size)] {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                name,
+                format_size(old),
+                format_size(new),
+                format_delta(old, new)
+            ));
+        }
+        match self.bytecode_changed {
+            Some(true) => md.push_str("\nrWASM hash changed.\n"),
+            Some(false) => md.push_str("\nrWASM hash unchanged.\n"),
+            None => {}
+        }
+
+        md.push_str(&format!("\n{} Dependencies\n\n", level));
+        if self.dependencies.is_empty() {
+            md.push_str("No dependency changes.\n");
+        } else {
+            for change in &self.dependencies {
+                let line = match (change.old.is_empty(), change.new.is_empty()) {
+                    (true, _) => format!("- Added `{}` {}\n", change.name, change.new.join(", ")),
+                    (_, true) => format!("- Removed `{}` {}\n", change.name, change.old.join(", ")),
+                    _ => format!(
+                        "- `{}` {} → {}\n",
+                        change.name,
+                        change.old.join(", "),
+                        change.new.join(", ")
+                    ),
+                };
+                md.push_str(&line);
+            }
+        }
+
+        md
+    }
+}
+
+/// Canonical signatures of all functions in an ABI
+fn function_signatures(abi: &Abi) -> std::collections::BTreeSet<String> {
+    abi.iter()
+        .filter_map(AbiEntry::as_function)
+        .map(AbiFunction::signature)
+        .collect()
+}
+
+/// Parse package versions from Cargo.lock content
+pub(crate) fn parse_cargo_lock(content: &str) -> Result<BTreeMap<String, Vec<String>>> {
+    let lock_file: toml::Value = toml::from_str(content).context("Invalid Cargo.lock format")?;
+    let mut packages: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for package in lock_file
+        .get("package")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let (Some(name), Some(version)) = (
+            package.get("name").and_then(|n| n.as_str()),
+            package.get("version").and_then(|v| v.as_str()),
+        ) {
+            packages
+                .entry(name.to_string())
+                .or_default()
+                .push(version.to_string());
+        }
+    }
+
+    for versions in packages.values_mut() {
+        versions.sort();
+    }
+
+    Ok(packages)
+}
+
+fn read_json(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(value))
+}
+
+fn file_size(path: &Path) -> Option<usize> {
+    std::fs::metadata(path).ok().map(|m| m.len() as usize)
+}
+
+fn format_size(size: Option<usize>) -> String {
+    size.map(|s| format!("{} B", s))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_delta(old: Option<usize>, new: Option<usize>) -> String {
+    match (old, new) {
+        (Some(old), Some(new)) => format!("{:+} B", new as i64 - old as i64),
+        _ => "-".to_string(),
+    }
+}
+
+/// Git worktree in the sources of a sandbox, removed on drop or interruption
+pub struct WorktreeGuard {
+    /// Root of the checkout (the repository root, not the project)
+    pub path: PathBuf,
+    /// Commit the ref resolved to
+    pub commit: String,
+    sandbox: Sandbox,
+    _cleanup: CleanupGuard,
+}
+
+impl WorktreeGuard {
+    /// Check out `git_ref` of the repository containing `project_root`
+    pub fn create(project_root: &Path, git_ref: &str) -> Result<Self> {
+        let output = Command::new("git")
+            .current_dir(project_root)
+            .args([
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{commit}}", git_ref),
+            ])
+            .output()
+            .context("Failed to execute git rev-parse")?;
+        if !output.status.success() {
+            return Err(eyre::eyre!("Unknown git ref {}", git_ref));
+        }
+        let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        let sanitized: String = git_ref
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let sandbox = Sandbox::create(
+            &std::env::temp_dir(),
+            &format!(
+                "fluent-builder-worktree-{}-{}",
+                std::process::id(),
+                sanitized
+            ),
+        )?;
+        let path = sandbox.sources_dir.clone();
+
+        let output = Command::new("git")
+            .current_dir(project_root)
+            .args(["worktree", "add", "--detach"])
+            .arg(&path)
+            .arg(&commit)
+            .output()
+            .context("Failed to execute git worktree add")?;
+
+        if !output.status.success() {
+            sandbox.finish(false);
+            return Err(eyre::eyre!(
+                "Failed to check out {}: {}",
+                git_ref,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let repo = project_root.to_path_buf();
+        let worktree = path.clone();
+        let root = sandbox.root.clone();
+        let cleanup = CleanupGuard::new(move || {
+            let removed = Command::new("git")
+                .current_dir(&repo)
+                .args(["worktree", "remove", "--force"])
+                .arg(&worktree)
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false);
+
+            if !removed {
+                tracing::warn!(
+                    "Failed to remove temporary worktree: {}",
+                    worktree.display()
+                );
+            }
+            if let Err(e) = std::fs::remove_dir_all(&root) {
+                tracing::warn!("Failed to remove {}: {}", root.display(), e);
+            }
+        });
+
+        Ok(Self {
+            path,
+            commit,
+            sandbox,
+            _cleanup: cleanup,
+        })
+    }
+
+    /// Build into the sandbox of the checkout rather than a target directory inside it
+    pub fn configure(&self, config: &mut CompileConfig) {
+        self.sandbox.configure(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::Param;
+    use insta::assert_snapshot;
+    use tempfile::TempDir;
+
+    fn function(name: &str, inputs: &[&str]) -> AbiEntry {
+        AbiEntry::Function(AbiFunction {
+            inputs: inputs.iter().map(|t| Param::new("", *t)).collect(),
+            name: name.to_string(),
+            outputs: Vec::new(),
+            state_mutability: Default::default(),
+        })
+    }
+
+    #[test]
+    fn test_changelog_markdown() {
+        let old = ContractSnapshot {
+            label: "v1.0.0".to_string(),
+            contract_version: Some("1.0.0".to_string()),
+            abi: vec![
+                function("transfer", &["address", "uint256"]),
+                function("burn", &["uint256"]),
+            ],
+            wasm_size: Some(12000),
+            rwasm_size: Some(15000),
+            rwasm_hash: Some("sha256:aa".to_string()),
+            dependencies: BTreeMap::from([
+                ("fluentbase-sdk".to_string(), vec!["0.4.1".to_string()]),
+                ("hex".to_string(), vec!["0.4.3".to_string()]),
+            ]),
+        };
+        let new = ContractSnapshot {
+            label: "v1.1.0".to_string(),
+            contract_version: Some("1.1.0".to_string()),
+            abi: vec![
+                function("transfer", &["address", "uint256"]),
+                function("mint", &["address"]),
+            ],
+            wasm_size: Some(12500),
+            rwasm_size: Some(14800),
+            rwasm_hash: Some("sha256:bb".to_string()),
+            dependencies: BTreeMap::from([
+                ("fluentbase-sdk".to_string(), vec!["0.5.0".to_string()]),
+                ("serde".to_string(), vec!["1.0.200".to_string()]),
+            ]),
+        };
+
+        let changelog = generate_changelog(&old, &new);
+        assert_eq!(changelog.functions_added, vec!["mint(address)"]);
+        assert_eq!(changelog.functions_removed, vec!["burn(uint256)"]);
+        assert_eq!(changelog.dependencies.len(), 3);
+        assert_snapshot!("changelog_markdown", changelog.to_markdown());
+    }
+
+    #[test]
+    fn test_snapshot_from_artifacts_dir() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("abi.json"),
+            serde_json::to_string(&vec![function("ping", &[])]).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("lib.wasm"), [0u8; 10]).unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"hex\"\nversion = \"0.4.3\"\n",
+        )
+        .unwrap();
+
+        let snapshot = ContractSnapshot::from_artifacts_dir(dir.path()).unwrap();
+        assert_eq!(snapshot.abi.len(), 1);
+        assert_eq!(snapshot.wasm_size, Some(10));
+        assert_eq!(snapshot.rwasm_size, None);
+        assert_eq!(snapshot.dependencies["hex"], vec!["0.4.3"]);
+    }
+
+    #[test]
+    fn test_worktree_of_tag() {
+        let dir = TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .current_dir(dir.path())
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?}", args);
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        git(&["init", "-q"]);
+        std::fs::write(dir.path().join("lib.rs"), "// v1").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "v1"]);
+        git(&["tag", "-a", "v1.0.0", "-m", "v1.0.0"]);
+        let v1 = git(&["rev-parse", "HEAD"]);
+        std::fs::write(dir.path().join("lib.rs"), "// v2").unwrap();
+        git(&["commit", "-q", "-am", "v2"]);
+
+        let worktree = WorktreeGuard::create(dir.path(), "v1.0.0").unwrap();
+        // An annotated tag resolves to its commit
+        assert_eq!(worktree.commit, v1);
+        assert_eq!(
+            std::fs::read_to_string(worktree.path.join("lib.rs")).unwrap(),
+            "// v1"
+        );
+        let path = worktree.path.clone();
+        drop(worktree);
+        assert!(!path.exists());
+
+        assert!(WorktreeGuard::create(dir.path(), "v9.9.9").is_err());
+    }
+}
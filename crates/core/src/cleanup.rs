@@ -0,0 +1,220 @@
+//! Scoped cleanup of temporary state on failure or interruption
+//!
+//! Guards run their action when dropped (including during a panic). Pending
+//! actions are also kept in a process-wide registry so a signal handler can run
+//! them before exiting, since destructors do not run on Ctrl-C.
+
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    process::{Command, ExitStatus, Output, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+type Action = Box<dyn FnOnce() + Send>;
+
+/// Pending cleanup actions, keyed by the guard that registered them
+pub(crate) struct Registry {
+    actions: Mutex<BTreeMap<u64, Action>>,
+    next_id: AtomicU64,
+}
+
+impl Registry {
+    pub(crate) const fn new() -> Self {
+        Self {
+            actions: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn actions(&self) -> MutexGuard<'_, BTreeMap<u64, Action>> {
+        self.actions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Register a cleanup action with this registry
+    pub(crate) fn guard(&'static self, action: impl FnOnce() + Send + 'static) -> CleanupGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.actions().insert(id, Box::new(action));
+        CleanupGuard { id, registry: self }
+    }
+
+    /// Run all pending cleanup actions of this registry
+    pub(crate) fn run_pending(&self) {
+        let actions = std::mem::take(&mut *self.actions());
+        for (_, action) in actions {
+            action();
+        }
+    }
+}
+
+/// The process-wide registry drained by [`run_pending_cleanups`]
+static REGISTRY: Registry = Registry::new();
+
+/// Runs a cleanup action when dropped unless disarmed
+#[must_use = "the cleanup runs as soon as the guard is dropped"]
+pub struct CleanupGuard {
+    id: u64,
+    registry: &'static Registry,
+}
+
+impl CleanupGuard {
+    /// Register a cleanup action
+    pub fn new(action: impl FnOnce() + Send + 'static) -> Self {
+        REGISTRY.guard(action)
+    }
+
+    /// Remove a file or directory (if present) on cleanup
+    pub fn remove_path(path: impl Into<PathBuf>) -> Self {
+        Self::new(remove_path_action(path.into()))
+    }
+
+    /// Keep the state: drop the guard without running its action
+    pub fn disarm(self) {
+        self.registry.actions().remove(&self.id);
+    }
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        // Release the lock before running the action, which may register guards itself
+        let action = self.registry.actions().remove(&self.id);
+        if let Some(action) = action {
+            action();
+        }
+    }
+}
+
+fn remove_path_action(path: PathBuf) -> impl FnOnce() + Send + 'static {
+    move || {
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => tracing::debug!("Removed {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Run a command to completion, capturing its output
+///
+/// Like [`Command::output`], but the child is terminated if pending cleanups
+/// run while it is still alive.
+pub fn output_interruptible(cmd: &mut Command) -> std::io::Result<Output> {
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let guard = terminate_on_cleanup(child.id());
+    let output = child.wait_with_output();
+    guard.disarm();
+    output
+}
+
+/// Run a command to completion with inherited stdio
+///
+/// Like [`Command::status`], but the child is terminated if pending cleanups
+/// run while it is still alive.
+pub fn status_interruptible(cmd: &mut Command) -> std::io::Result<ExitStatus> {
+    let mut child = cmd.spawn()?;
+
+    let guard = terminate_on_cleanup(child.id());
+    let status = child.wait();
+    guard.disarm();
+    status
+}
+
+/// Guard that terminates a child process if cleanups run before it is disarmed
+pub(crate) fn terminate_on_cleanup(pid: u32) -> CleanupGuard {
+    CleanupGuard::new(move || {
+        tracing::debug!("Terminating child process {}", pid);
+
+        #[cfg(unix)]
+        let result = Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .status();
+        #[cfg(windows)]
+        let result = Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/T", "/F"])
+            .status();
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to terminate child process {}: {}", pid, e);
+        }
+    })
+}
+
+/// Run all pending cleanup actions, e.g. from a Ctrl-C handler
+pub fn run_pending_cleanups() {
+    REGISTRY.run_pending();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::AtomicUsize, Arc};
+    use tempfile::TempDir;
+
+    fn counter() -> (Arc<AtomicUsize>, impl FnOnce() + Send + 'static) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let handle = count.clone();
+        (count, move || {
+            handle.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    #[test]
+    fn test_guard_runs_on_drop() {
+        static REGISTRY: Registry = Registry::new();
+        let (count, action) = counter();
+        drop(REGISTRY.guard(action));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let (count, action) = counter();
+        REGISTRY.guard(action).disarm();
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_pending_cleanups_run_once() {
+        // A registry of its own, so guards of concurrently running tests are left alone
+        static REGISTRY: Registry = Registry::new();
+        let (count, action) = counter();
+        let guard = REGISTRY.guard(action);
+
+        REGISTRY.run_pending();
+        drop(guard);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(REGISTRY.actions().is_empty());
+    }
+
+    #[test]
+    fn test_output_interruptible() {
+        let output = output_interruptible(Command::new("git").arg("--version")).unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("git version"));
+    }
+
+    #[test]
+    fn test_remove_path() {
+        static REGISTRY: Registry = Registry::new();
+        let dir = TempDir::new().unwrap();
+        let partial = dir.path().join("token.wasm");
+        std::fs::create_dir_all(&partial).unwrap();
+        std::fs::write(partial.join("lib.wasm"), [0u8; 4]).unwrap();
+
+        drop(REGISTRY.guard(remove_path_action(partial.clone())));
+        assert!(!partial.exists());
+
+        // Missing paths are not an error
+        drop(REGISTRY.guard(remove_path_action(dir.path().join("missing"))));
+    }
+}
@@ -0,0 +1,182 @@
+//! Behavior of earlier fluent-builder versions
+//!
+//! Verification rebuilds a deployment with the running builder, so a change
+//! to what the builder adds to the bytecode or how it hashes sources would
+//! break verification of everything deployed before it. Such changes are
+//! listed in [`BEHAVIOR_CHANGES`] with the first release that has them, and
+//! verification rebuilds with the behavior of the version that made the
+//! deployment.
+//!
+//! When changing such behavior, add an entry with the upcoming release, a
+//! field to [`Compat`], and keep the old behavior behind that field.
+
+use crate::build_info::Provenance;
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version assumed for metadata written before the builder version was recorded
+pub const UNVERSIONED: &str = "0.0.0";
+
+/// A behavior that changed between fluent-builder releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behavior {
+    /// Hidden and `target`/`out` directories are skipped only inside the
+    /// project, not when they are ancestors of it
+    ProjectRelativeSourceHash,
+}
+
+pub struct BehaviorChange {
+    /// First release with the behavior
+    pub since: &'static str,
+    pub behavior: Behavior,
+    /// What the release added, e.g. "hashing sources of projects inside hidden directories"
+    pub description: &'static str,
+}
+
+/// Changes that make builds of earlier versions differ, oldest first
+pub const BEHAVIOR_CHANGES: &[BehaviorChange] = &[BehaviorChange {
+    since: "0.2.0",
+    behavior: Behavior::ProjectRelativeSourceHash,
+    description: "hashing sources of projects inside hidden directories",
+}];
+
+/// How a fluent-builder version produced bytecode and metadata
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Compat {
+    /// Version whose behavior is reproduced, also recorded in metadata.json
+    pub builder_version: String,
+    pub project_relative_source_hash: bool,
+}
+
+impl Compat {
+    /// Behavior of the running builder
+    pub fn current() -> Self {
+        Self {
+            builder_version: crate::VERSION.to_string(),
+            project_relative_source_hash: true,
+        }
+    }
+
+    /// Behavior of a fluent-builder release
+    pub fn for_version(version: &str) -> Result<Self> {
+        if version == crate::VERSION {
+            return Ok(Self::current());
+        }
+        let parsed = Version::parse(version)
+            .with_context(|| format!("Invalid fluent-builder version '{}'", version))?;
+        let has = |behavior: Behavior| {
+            BEHAVIOR_CHANGES
+                .iter()
+                .filter(|change| change.behavior == behavior)
+                .all(|change| {
+                    parsed
+                        >= Version::parse(change.since).expect("valid version in BEHAVIOR_CHANGES")
+                })
+        };
+        Ok(Self {
+            builder_version: version.to_string(),
+            project_relative_source_hash: has(Behavior::ProjectRelativeSourceHash),
+        })
+    }
+
+    /// Behavior of the builder that made a deployment, if known
+    ///
+    /// The provenance of the deployment's build is preferred over
+    /// metadata.json; metadata without a builder version predates it.
+    pub fn of_deployment(
+        provenance: Option<&Provenance>,
+        metadata: Option<&Value>,
+    ) -> Result<Option<Self>> {
+        if let Some(provenance) = provenance {
+            return Self::for_version(&provenance.builder_version).map(Some);
+        }
+        metadata
+            .map(|metadata| {
+                Self::for_version(metadata["builder_version"].as_str().unwrap_or(UNVERSIONED))
+            })
+            .transpose()
+    }
+
+    /// Whether this differs from the running builder
+    pub fn is_legacy(&self) -> bool {
+        *self != Self::current()
+    }
+
+    /// Changes this version predates, i.e. the legacy behaviors applied
+    pub fn legacy_behaviors(&self) -> Vec<&'static BehaviorChange> {
+        BEHAVIOR_CHANGES
+            .iter()
+            .filter(|change| !self.has(change.behavior))
+            .collect()
+    }
+
+    fn has(&self, behavior: Behavior) -> bool {
+        match behavior {
+            Behavior::ProjectRelativeSourceHash => self.project_relative_source_hash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compat_for_version() {
+        assert!(!Compat::for_version(crate::VERSION).unwrap().is_legacy());
+
+        let old = Compat::for_version("0.1.0-alpha.1").unwrap();
+        assert!(!old.project_relative_source_hash);
+        assert_eq!(old.legacy_behaviors().len(), BEHAVIOR_CHANGES.len());
+
+        let new = Compat::for_version("0.2.0").unwrap();
+        assert!(new.project_relative_source_hash && new.legacy_behaviors().is_empty());
+        assert!(Compat::for_version("latest").is_err());
+    }
+
+    #[test]
+    fn test_behavior_changes_are_released() {
+        // A change newer than the workspace version would count this very build as legacy
+        let current = Version::parse(crate::VERSION).unwrap();
+        for change in BEHAVIOR_CHANGES {
+            assert!(
+                Version::parse(change.since).unwrap() <= current,
+                "{} is after {}",
+                change.since,
+                current
+            );
+        }
+        assert_eq!(
+            Compat::for_version(crate::VERSION).unwrap(),
+            Compat::current()
+        );
+    }
+
+    #[test]
+    fn test_compat_of_deployment() {
+        assert_eq!(Compat::of_deployment(None, None).unwrap(), None);
+
+        let unversioned = Compat::of_deployment(None, Some(&json!({})))
+            .unwrap()
+            .unwrap();
+        assert_eq!(unversioned.builder_version, UNVERSIONED);
+        assert!(!unversioned.project_relative_source_hash);
+
+        // The provenance of the deployed build wins over metadata
+        let provenance = Provenance {
+            builder_version: "0.1.5".to_string(),
+            locked: true,
+            target_name: None,
+        };
+        let metadata = json!({"builder_version": "0.3.0"});
+        let compat = Compat::of_deployment(Some(&provenance), Some(&metadata))
+            .unwrap()
+            .unwrap();
+        assert_eq!(compat.builder_version, "0.1.5");
+        assert_eq!(compat, Compat::for_version("0.1.5").unwrap());
+    }
+}
@@ -1,8 +1,14 @@
 //! Configuration for WASM contract compilation
 
+pub(crate) use crate::artifacts::abi::AbiNaming;
+use crate::compat::Compat;
 use eyre::Result;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
 /// Configuration for compiling a Rust smart contract
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -30,6 +36,90 @@ pub struct CompileConfig {
 
     /// Whether to use git source (requires clean public repo)
     pub use_git_source: bool,
+
+    /// Whether to forbid network access (passes --offline to cargo)
+    #[serde(default)]
+    pub offline: bool,
+
+    /// Cargo target directory (defaults to $CARGO_TARGET_DIR, then `<project_root>/target`)
+    ///
+    /// Set this together with an absolute `output_dir` to build from a read-only source tree.
+    #[serde(default)]
+    pub target_dir: Option<PathBuf>,
+
+    /// Time limits for external commands
+    #[serde(default)]
+    pub timeouts: Timeouts,
+
+    /// Target to build in a multi-contract package: a `[[bin]]` name, the
+    /// library name, or a label for a feature-gated variant of the library
+    ///
+    /// Artifacts are written to `<target_name>.wasm`.
+    #[serde(default)]
+    pub target_name: Option<String>,
+
+    /// Network from `fluent.toml` whose build settings were applied, recorded in metadata
+    #[serde(default)]
+    pub network: Option<String>,
+
+    /// Arguments passed to `cargo build` as-is (e.g. `--config`, `-Ztrim-paths`), recorded in
+    /// metadata
+    #[serde(default)]
+    pub extra_cargo_args: Vec<String>,
+
+    /// RUSTFLAGS of the contract build (e.g. `-C target-feature=+bulk-memory`), recorded in
+    /// metadata; replaces RUSTFLAGS of the environment and `build.rustflags` of
+    /// `.cargo/config.toml`
+    #[serde(default)]
+    pub rustflags: Option<String>,
+
+    /// Behavior of an earlier fluent-builder release to reproduce (verification
+    /// of older deployments); the running builder's behavior when unset
+    #[serde(default)]
+    pub compat: Option<Compat>,
+
+    /// Fail instead of writing degraded artifacts: an empty ABI from routers
+    /// that don't parse, a git source without a remote URL, or metadata
+    /// without a Cargo.lock hash. For release and verification builds.
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Directory caching WASM and rWASM by sources, toolchain and settings;
+    /// unchanged projects skip cargo and the rWASM translation. None disables it.
+    #[serde(default)]
+    pub build_cache: Option<PathBuf>,
+
+    /// Largest WASM and rWASM the build may produce, checked before artifacts are written
+    #[serde(default)]
+    pub size_limits: SizeLimits,
+}
+
+/// Time limits for slow or potentially hanging operations (None disables the limit)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Timeouts {
+    /// `cargo build` of the contract
+    pub cargo_build: Option<Duration>,
+    /// Each git command (status on a network filesystem can hang)
+    pub git: Option<Duration>,
+    /// WASM to rWASM translation
+    pub rwasm: Option<Duration>,
+    /// Each RPC request
+    pub rpc: Option<Duration>,
+    /// `cargo vendor` before an offline sandboxed build
+    pub cargo_vendor: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            cargo_build: Some(Duration::from_secs(30 * 60)),
+            git: Some(Duration::from_secs(60)),
+            rwasm: Some(Duration::from_secs(5 * 60)),
+            rpc: Some(Duration::from_secs(30)),
+            cargo_vendor: Some(Duration::from_secs(10 * 60)),
+        }
+    }
 }
 
 /// Controls which artifacts are generated during compilation
@@ -46,6 +136,125 @@ pub struct ArtifactsConfig {
 
     /// Pretty-print JSON files
     pub pretty_json: bool,
+
+    /// Naming of functions and parameters in the ABI and interface
+    #[serde(default)]
+    pub abi_naming: AbiNaming,
+
+    /// Save under `<name>/<rwasm hash prefix>/` with a `latest` symlink instead of `<name>.wasm/`,
+    /// so successive builds keep every bytecode variant
+    #[serde(default)]
+    pub content_addressed_output: bool,
+
+    /// What to do when two router functions share a 4-byte selector
+    #[serde(default)]
+    pub selector_collisions: SelectorCollisions,
+
+    /// Extra files of the source archive, as globs relative to the project root, recorded in
+    /// metadata
+    #[serde(default)]
+    pub archive_include: Vec<String>,
+
+    /// Files left out of the source archive, as globs relative to the project root, recorded in
+    /// metadata
+    #[serde(default)]
+    pub archive_exclude: Vec<String>,
+}
+
+impl ArtifactsConfig {
+    /// Hex digits of the rWASM sha256 naming content-addressed directories
+    pub const SHORT_HASH_LEN: usize = 12;
+
+    /// Directory the artifacts of `contract_name` with this rWASM bytecode are saved to
+    pub fn artifacts_dir(&self, output_dir: &Path, contract_name: &str, rwasm: &[u8]) -> PathBuf {
+        if self.content_addressed_output {
+            let hash = crate::HashAlgorithm::Sha256.hash(rwasm);
+            output_dir
+                .join(contract_name)
+                .join(&hash[..Self::SHORT_HASH_LEN])
+        } else {
+            output_dir.join(format!("{}.wasm", contract_name))
+        }
+    }
+}
+
+/// Handling of router functions that share a 4-byte selector, which the contract can't tell apart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectorCollisions {
+    /// Fail the build
+    #[default]
+    Error,
+    /// Log a warning and write the artifacts
+    Warn,
+}
+
+impl FromStr for SelectorCollisions {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(SelectorCollisions::Error),
+            "warn" => Ok(SelectorCollisions::Warn),
+            _ => Err(format!(
+                "unknown selector collision policy '{}', expected error or warn",
+                s
+            )),
+        }
+    }
+}
+
+/// Bytecode size limits; a contract over them can't be deployed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SizeLimits {
+    /// Largest lib.wasm, the code a deployment sends (None disables the check)
+    pub max_wasm_size: Option<usize>,
+    /// Largest lib.rwasm, the code the chain stores (None disables the check)
+    pub max_rwasm_size: Option<usize>,
+    /// What to do when the bytecode is over a limit
+    pub policy: SizeLimitPolicy,
+}
+
+impl SizeLimits {
+    /// Fluent's limit on the WASM code of a deployment, 3 MiB
+    pub const DEFAULT_MAX_WASM_SIZE: usize = fluentbase_types::WASM_MAX_CODE_SIZE;
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            max_wasm_size: Some(Self::DEFAULT_MAX_WASM_SIZE),
+            max_rwasm_size: None,
+            policy: SizeLimitPolicy::default(),
+        }
+    }
+}
+
+/// Handling of bytecode over a [`SizeLimits`] limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeLimitPolicy {
+    /// Fail the build
+    #[default]
+    Error,
+    /// Log a warning and write the artifacts
+    Warn,
+}
+
+impl FromStr for SizeLimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(SizeLimitPolicy::Error),
+            "warn" => Ok(SizeLimitPolicy::Warn),
+            _ => Err(format!(
+                "unknown size limit policy '{}', expected error or warn",
+                s
+            )),
+        }
+    }
 }
 
 impl Default for CompileConfig {
@@ -59,6 +268,17 @@ impl Default for CompileConfig {
             locked: true,
             artifacts: ArtifactsConfig::default(),
             use_git_source: true,
+            offline: false,
+            target_dir: None,
+            timeouts: Timeouts::default(),
+            target_name: None,
+            network: None,
+            extra_cargo_args: vec![],
+            rustflags: None,
+            compat: None,
+            strict: false,
+            build_cache: None,
+            size_limits: SizeLimits::default(),
         }
     }
 }
@@ -70,6 +290,11 @@ impl Default for ArtifactsConfig {
             generate_interface: true,
             generate_metadata: true,
             pretty_json: true,
+            abi_naming: AbiNaming::default(),
+            content_addressed_output: false,
+            selector_collisions: SelectorCollisions::default(),
+            archive_include: vec![],
+            archive_exclude: vec![],
         }
     }
 }
@@ -92,6 +317,21 @@ impl CompileConfig {
         }
     }
 
+    /// Get the cargo target directory
+    pub fn target_directory(&self) -> PathBuf {
+        let target_dir = self
+            .target_dir
+            .clone()
+            .or_else(|| std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("target"));
+
+        if target_dir.is_absolute() {
+            target_dir
+        } else {
+            self.project_root.join(target_dir)
+        }
+    }
+
     /// Get the target triple for WASM compilation
     pub fn target(&self) -> &str {
         "wasm32-unknown-unknown"
@@ -137,6 +377,20 @@ mod tests {
         assert_eq!(config.target(), "wasm32-unknown-unknown");
         assert!(config.no_default_features);
         assert!(config.artifacts.generate_metadata);
+        assert!(config.artifacts.abi_naming.is_preserve());
+        assert!(!config.offline);
+    }
+
+    #[test]
+    fn test_abi_naming() {
+        assert_eq!("camel-case".parse::<AbiNaming>(), Ok(AbiNaming::CamelCase));
+        assert_eq!("preserve".parse::<AbiNaming>(), Ok(AbiNaming::Preserve));
+        assert!("snake".parse::<AbiNaming>().is_err());
+        assert_eq!(
+            "warn".parse::<SelectorCollisions>(),
+            Ok(SelectorCollisions::Warn)
+        );
+        assert_eq!(SelectorCollisions::default(), SelectorCollisions::Error);
     }
 
     #[test]
@@ -168,4 +422,14 @@ mod tests {
         config.output_dir = PathBuf::from("/absolute/out");
         assert_eq!(config.output_directory(), PathBuf::from("/absolute/out"));
     }
+
+    #[test]
+    fn test_target_directory() {
+        let mut config = CompileConfig::new("/project");
+        config.target_dir = Some(PathBuf::from("/tmp/build"));
+        assert_eq!(config.target_directory(), PathBuf::from("/tmp/build"));
+
+        config.target_dir = Some(PathBuf::from("build"));
+        assert_eq!(config.target_directory(), PathBuf::from("/project/build"));
+    }
 }
@@ -0,0 +1,357 @@
+//! Pre-flight checks run before the (slow) cargo build
+
+use crate::{
+    builder::{find_main_source, parse_contract_info, ContractInfo},
+    config::CompileConfig,
+    error::BuilderError,
+};
+use eyre::{Context, Result};
+use std::path::Path;
+use syn::{visit::Visit, ItemFn, ItemMacro};
+
+/// Kind of cargo target producing the contract wasm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    /// The package library, built with `--lib`
+    Lib,
+    /// A binary target, built with `--bin <name>`
+    Bin,
+}
+
+/// Cargo target a contract (variant) is built from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildTarget {
+    pub kind: TargetKind,
+    /// Cargo target name
+    pub name: String,
+    /// Name of the artifact directory (`<artifact_name>.wasm`)
+    pub artifact_name: String,
+}
+
+impl BuildTarget {
+    /// Arguments selecting the target in `cargo build`
+    pub fn cargo_args(&self) -> Vec<String> {
+        match self.kind {
+            TargetKind::Lib => vec!["--lib".to_string()],
+            TargetKind::Bin => vec!["--bin".to_string(), self.name.clone()],
+        }
+    }
+
+    /// File name of the wasm cargo produces for the target
+    pub fn wasm_file_name(&self) -> String {
+        match self.kind {
+            TargetKind::Lib => format!("{}.wasm", self.name.replace('-', "_")),
+            TargetKind::Bin => format!("{}.wasm", self.name),
+        }
+    }
+}
+
+/// Resolve `CompileConfig::target_name` against the package's cargo targets
+///
+/// Without a target name the library is built. A name matching neither the
+/// library nor a binary is a feature-gated variant of the library and requires
+/// `features` to be set.
+pub fn resolve_build_target(config: &CompileConfig) -> Result<BuildTarget> {
+    let cargo_toml_path = config.project_root.join("Cargo.toml");
+    let manifest = read_manifest(&cargo_toml_path)?;
+
+    let package_name = manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| eyre::eyre!("No package.name in Cargo.toml"))?;
+    let lib_name = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("name"))
+        .and_then(|n| n.as_str())
+        .unwrap_or(package_name);
+    let bins = bin_targets(&config.project_root, &manifest);
+
+    let lib = |artifact_name: &str| BuildTarget {
+        kind: TargetKind::Lib,
+        name: lib_name.to_string(),
+        artifact_name: artifact_name.to_string(),
+    };
+
+    let Some(target_name) = config.target_name.as_deref() else {
+        return Ok(lib(package_name));
+    };
+
+    if bins.iter().any(|b| b == target_name) {
+        Ok(BuildTarget {
+            kind: TargetKind::Bin,
+            name: target_name.to_string(),
+            artifact_name: target_name.to_string(),
+        })
+    } else if target_name == lib_name || target_name == package_name || !config.features.is_empty()
+    {
+        Ok(lib(target_name))
+    } else {
+        let mut available = vec![format!("{} (lib)", lib_name)];
+        available.extend(bins.iter().map(|b| format!("{} (bin)", b)));
+        Err(eyre::eyre!(
+            "Unknown target '{}'. Available targets: {}.\n\
+             To build a feature-gated variant of the library, also pass its features",
+            target_name,
+            available.join(", ")
+        ))
+    }
+}
+
+/// Binary targets declared in `[[bin]]` or discovered in `src/bin`
+fn bin_targets(project_root: &Path, manifest: &toml::Value) -> Vec<String> {
+    let mut bins: Vec<String> = manifest
+        .get("bin")
+        .and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|bin| bin.get("name").and_then(|n| n.as_str()).map(str::to_string))
+        .collect();
+
+    if let Ok(entries) = std::fs::read_dir(project_root.join("src").join("bin")) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let name = if path.extension().is_some_and(|ext| ext == "rs") {
+                path.file_stem()
+            } else if path.join("main.rs").exists() {
+                path.file_name()
+            } else {
+                None
+            };
+            if let Some(name) = name {
+                bins.push(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    bins.sort();
+    bins.dedup();
+    bins
+}
+
+fn read_manifest(cargo_toml_path: &Path) -> Result<toml::Value> {
+    let content = std::fs::read_to_string(cargo_toml_path)
+        .with_context(|| format!("Failed to read {}", cargo_toml_path.display()))?;
+    toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", cargo_toml_path.display()))
+}
+
+/// Check that a project can produce a contract wasm
+///
+/// Verifies the fluentbase-sdk dependency, the selected target (a `cdylib`
+/// library or a binary) and the presence of an SDK entrypoint in its sources.
+pub fn validate_contract(config: &CompileConfig) -> Result<ContractInfo> {
+    let project_root = config.project_root.as_path();
+    let cargo_toml_path = project_root.join("Cargo.toml");
+    let mut contract = parse_contract_info(&cargo_toml_path)?;
+    let target = resolve_build_target(config)?;
+
+    let manifest = read_manifest(&cargo_toml_path)?;
+    let source_dir = match target.kind {
+        TargetKind::Lib => {
+            check_crate_type(&manifest)?;
+            let main_source = find_main_source(project_root, &cargo_toml_path)?;
+            main_source.parent().unwrap_or(project_root).to_path_buf()
+        }
+        // Binaries may share modules with the library, so scan all sources
+        TargetKind::Bin => project_root.join("src"),
+    };
+    if !has_entrypoint(&source_dir)? {
+        return Err(BuilderError::InvalidContract {
+            reason: format!(
+                "no entrypoint found in {}.\n\
+                 Add `basic_entrypoint!(YourContract);` or `entrypoint!(main, deploy);` from \
+                 fluentbase-sdk",
+                source_dir.display()
+            ),
+        }
+        .into());
+    }
+
+    contract.target = config.target_name.clone();
+    Ok(contract)
+}
+
+/// The wasm is only produced for `cdylib` libraries
+fn check_crate_type(manifest: &toml::Value) -> Result<()> {
+    let crate_types = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("crate-type"))
+        .and_then(|types| types.as_array());
+
+    let is_cdylib = crate_types
+        .map(|types| types.iter().any(|t| t.as_str() == Some("cdylib")))
+        .unwrap_or(false);
+
+    if !is_cdylib {
+        return Err(BuilderError::InvalidContract {
+            reason: "Cargo.toml must set crate-type = [\"cdylib\"] in [lib].\n\
+                     Run `fluent-builder migrate` to fix it"
+                .to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Look for an SDK entrypoint macro or exported `main`/`deploy` functions
+fn has_entrypoint(source_dir: &Path) -> Result<bool> {
+    let mut finder = EntrypointFinder::default();
+
+    for entry in walkdir::WalkDir::new(source_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "rs"))
+    {
+        let content = std::fs::read_to_string(entry.path())
+            .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+        // Files that don't parse are reported by the compiler
+        if let Ok(ast) = syn::parse_file(&content) {
+            finder.visit_file(&ast);
+        }
+        if finder.found() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+#[derive(Default)]
+struct EntrypointFinder {
+    macro_found: bool,
+    main: bool,
+    deploy: bool,
+}
+
+impl EntrypointFinder {
+    fn found(&self) -> bool {
+        self.macro_found || (self.main && self.deploy)
+    }
+}
+
+impl<'ast> Visit<'ast> for EntrypointFinder {
+    fn visit_item_macro(&mut self, node: &'ast ItemMacro) {
+        // basic_entrypoint!, entrypoint!, func_entrypoint!, ...
+        if node
+            .mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|s| s.ident.to_string().ends_with("entrypoint"))
+        {
+            self.macro_found = true;
+        }
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        let exported = node.attrs.iter().any(|attr| {
+            attr.path().is_ident("no_mangle")
+                || (attr.path().is_ident("unsafe")
+                    && attr
+                        .parse_args::<syn::Path>()
+                        .is_ok_and(|p| p.is_ident("no_mangle")))
+        });
+        if exported {
+            match node.sig.ident.to_string().as_str() {
+                "main" => self.main = true,
+                "deploy" => self.deploy = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn project(crate_type: &str, source: &str) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"token\"\nversion = \"0.1.0\"\n\n[lib]\n{}\n\n[dependencies]\n\
+                 fluentbase-sdk = \"0.4\"\n",
+                crate_type
+            ),
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), source).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_valid_contract() {
+        let dir = project(
+            "crate-type = [\"cdylib\", \"rlib\"]",
+            "struct Token;\nbasic_entrypoint!(Token);\n",
+        );
+        assert_eq!(
+            validate_contract(&CompileConfig::new(dir.path()))
+                .unwrap()
+                .name,
+            "token"
+        );
+
+        let dir = project(
+            "crate-type = [\"cdylib\"]",
+            "#[no_mangle]\nextern \"C\" fn main() {}\n#[unsafe(no_mangle)]\n\
+             extern \"C\" fn deploy() {}\n",
+        );
+        assert!(validate_contract(&CompileConfig::new(dir.path())).is_ok());
+    }
+
+    #[test]
+    fn test_missing_cdylib() {
+        let dir = project("crate-type = [\"rlib\"]", "basic_entrypoint!(Token);\n");
+        let err = validate_contract(&CompileConfig::new(dir.path())).unwrap_err();
+        assert!(err.to_string().contains("crate-type = [\"cdylib\"]"));
+        assert!(matches!(
+            BuilderError::find(&err),
+            Some(BuilderError::InvalidContract { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_build_target() {
+        let dir = project("crate-type = [\"cdylib\"]", "basic_entrypoint!(Token);\n");
+        std::fs::create_dir(dir.path().join("src/bin")).unwrap();
+        std::fs::write(dir.path().join("src/bin/token-lite.rs"), "").unwrap();
+
+        let mut config = CompileConfig::new(dir.path());
+        let target = resolve_build_target(&config).unwrap();
+        assert_eq!(target.cargo_args(), vec!["--lib"]);
+        assert_eq!(target.artifact_name, "token");
+
+        config.target_name = Some("token-lite".to_string());
+        let target = resolve_build_target(&config).unwrap();
+        assert_eq!(target.cargo_args(), vec!["--bin", "token-lite"]);
+        assert_eq!(target.wasm_file_name(), "token-lite.wasm");
+
+        // Feature-gated variants of the library need features
+        config.target_name = Some("token-permit".to_string());
+        assert!(resolve_build_target(&config).is_err());
+        config.features = vec!["permit".to_string()];
+        let target = resolve_build_target(&config).unwrap();
+        assert_eq!(target.kind, TargetKind::Lib);
+        assert_eq!(target.artifact_name, "token-permit");
+
+        let contract = validate_contract(&config).unwrap();
+        assert_eq!(contract.target.as_deref(), Some("token-permit"));
+        assert_eq!(contract.artifact_name(), "token-permit");
+    }
+
+    #[test]
+    fn test_missing_entrypoint() {
+        let dir = project(
+            "crate-type = [\"cdylib\"]",
+            "#[no_mangle]\nextern \"C\" fn main() {}\n",
+        );
+        let err = validate_contract(&CompileConfig::new(dir.path())).unwrap_err();
+        assert!(err.to_string().contains("no entrypoint found"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Release and deployment records kept in `deployments.json`
+
+use crate::build_info::{BuildFeatures, Provenance};
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Contents of a project's `deployments.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct Deployments {
+    /// Tagged releases, oldest first
+    #[serde(default)]
+    pub releases: Vec<ReleaseRecord>,
+    /// Verified on-chain deployments, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deployments: Vec<DeploymentRecord>,
+}
+
+/// A tagged release of the contract
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct ReleaseRecord {
+    /// Release version (also the git tag name)
+    pub version: String,
+    /// Contract name from Cargo.toml
+    pub contract: String,
+    /// Commit the release was built from
+    pub commit: String,
+    /// rWASM hash as recorded in metadata.json
+    pub rwasm_hash: String,
+    /// WASM hash as recorded in metadata.json
+    pub wasm_hash: String,
+    /// Artifacts directory relative to the project root
+    pub artifacts_dir: String,
+    /// Release timestamp
+    pub created_at: u64,
+}
+
+/// A verified deployment of the contract
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct DeploymentRecord {
+    pub chain_id: u64,
+    pub address: String,
+    /// Contract name from Cargo.toml
+    pub contract: String,
+    /// Hash of the deployed rWASM code
+    pub rwasm_hash: String,
+    /// Hex encoded constructor arguments, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constructor_args: Option<String>,
+    /// Block the code was fetched at, for an earlier version of the code at the address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<u64>,
+    /// Git tag or commit the code was verified against, if not the working tree
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    /// Commit `git_ref` resolved to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Builder version and options of the deployed build, from its metadata.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    /// Profile and features of the deployed build, from its metadata.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_features: Option<BuildFeatures>,
+    /// Verification timestamp
+    pub verified_at: u64,
+}
+
+impl Deployments {
+    /// File name inside the project root
+    pub const FILE_NAME: &'static str = "deployments.json";
+
+    /// Path of the deployments file for a project
+    pub fn path(project_root: &Path) -> PathBuf {
+        project_root.join(Self::FILE_NAME)
+    }
+
+    /// Load deployments, returning an empty record set if the file does not exist
+    pub fn load(project_root: &Path) -> Result<Self> {
+        let path = Self::path(project_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Write deployments back to the project root
+    pub fn save(&self, project_root: &Path) -> Result<()> {
+        let path = Self::path(project_root);
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Add a release, replacing any existing record for the same version
+    pub fn record_release(&mut self, release: ReleaseRecord) {
+        self.releases.retain(|r| r.version != release.version);
+        self.releases.push(release);
+    }
+
+    /// Add a deployment, replacing any existing record for the same chain, address and block
+    ///
+    /// Records of other blocks are kept, so the versions of an upgraded or
+    /// redeployed address accumulate.
+    pub fn record_deployment(&mut self, deployment: DeploymentRecord) {
+        self.deployments.retain(|d| {
+            d.chain_id != deployment.chain_id
+                || !d.address.eq_ignore_ascii_case(&deployment.address)
+                || d.block != deployment.block
+        });
+        self.deployments.push(deployment);
+    }
+
+    /// The deployment at an address, as of `block` or the current code if `None`
+    pub fn deployment(
+        &self,
+        chain_id: u64,
+        address: &str,
+        block: Option<u64>,
+    ) -> Option<&DeploymentRecord> {
+        self.deployments.iter().rev().find(|d| {
+            d.chain_id == chain_id && d.address.eq_ignore_ascii_case(address) && d.block == block
+        })
+    }
+
+    /// Find a release by version
+    pub fn release(&self, version: &str) -> Option<&ReleaseRecord> {
+        self.releases.iter().find(|r| r.version == version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn release(version: &str, hash: &str) -> ReleaseRecord {
+        ReleaseRecord {
+            version: version.to_string(),
+            contract: "token".to_string(),
+            commit: "abc".to_string(),
+            rwasm_hash: hash.to_string(),
+            wasm_hash: "sha256:00".to_string(),
+            artifacts_dir: format!("out/releases/{}/token.wasm", version),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_deployments_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            Deployments::load(dir.path()).unwrap(),
+            Deployments::default()
+        );
+
+        let mut deployments = Deployments::default();
+        deployments.record_release(release("v1.0.0", "sha256:aa"));
+        deployments.record_release(release("v1.0.0", "sha256:bb"));
+        deployments.save(dir.path()).unwrap();
+
+        let loaded = Deployments::load(dir.path()).unwrap();
+        assert_eq!(loaded.releases.len(), 1);
+        assert_eq!(loaded.release("v1.0.0").unwrap().rwasm_hash, "sha256:bb");
+    }
+
+    #[test]
+    fn test_record_deployment() {
+        let deployment = |address: &str, constructor_args: Option<&str>| DeploymentRecord {
+            chain_id: 20993,
+            address: address.to_string(),
+            contract: "token".to_string(),
+            rwasm_hash: "0xaa".to_string(),
+            constructor_args: constructor_args.map(str::to_string),
+            block: None,
+            git_ref: None,
+            commit: None,
+            provenance: None,
+            build_features: None,
+            verified_at: 0,
+        };
+        let mut deployments = Deployments::default();
+        deployments.record_deployment(deployment("0xABCD", None));
+        deployments.record_deployment(deployment("0xabcd", Some("0x01")));
+        assert_eq!(
+            deployments.deployments,
+            vec![deployment("0xabcd", Some("0x01"))]
+        );
+
+        // Earlier versions at the address are recorded separately
+        let v1 = DeploymentRecord {
+            block: Some(1000),
+            git_ref: Some("v1.0.0".to_string()),
+            rwasm_hash: "0x11".to_string(),
+            ..deployment("0xabcd", None)
+        };
+        deployments.record_deployment(v1.clone());
+        deployments.record_deployment(v1.clone());
+        assert_eq!(
+            deployments.deployments,
+            vec![deployment("0xabcd", Some("0x01")), v1]
+        );
+
+        let current = deployments.deployment(20993, "0xABCD", None).unwrap();
+        assert_eq!(current.rwasm_hash, "0xaa");
+        let earlier = deployments.deployment(20993, "0xabcd", Some(1000)).unwrap();
+        assert_eq!(earlier.rwasm_hash, "0x11");
+        assert!(deployments.deployment(20994, "0xabcd", None).is_none());
+    }
+}
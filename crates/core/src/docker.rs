@@ -0,0 +1,41 @@
+//! Paths and image names for builds in the pinned Docker image
+
+use crate::paths::strip_verbatim_prefix;
+use std::path::Path;
+
+/// Repository of the builder's base images, tagged by SDK release
+pub const BUILDER_IMAGE_REPOSITORY: &str = "fluentlabs/fluent-builder";
+
+/// Base Docker image of the builder for an SDK release tag, e.g. `v0.4.1`
+pub fn builder_image(sdk_tag: &str) -> String {
+    format!("{}:{}", BUILDER_IMAGE_REPOSITORY, sdk_tag)
+}
+
+/// Host path in the form Docker accepts as a bind mount source
+///
+/// Windows paths become `C:/Users/...`, which Docker Desktop understands.
+pub fn docker_host_path(path: &Path) -> String {
+    strip_verbatim_prefix(&path.to_string_lossy()).replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_image() {
+        assert_eq!(builder_image("v0.5.0"), "fluentlabs/fluent-builder:v0.5.0");
+    }
+
+    #[test]
+    fn test_docker_host_path() {
+        assert_eq!(
+            docker_host_path(Path::new(r"\\?\C:\Users\dev\token")),
+            "C:/Users/dev/token"
+        );
+        assert_eq!(
+            docker_host_path(Path::new("/home/dev/token")),
+            "/home/dev/token"
+        );
+    }
+}
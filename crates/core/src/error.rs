@@ -0,0 +1,137 @@
+//! Errors library consumers can handle programmatically
+//!
+//! Functions still return `eyre::Result`; the failures callers may want to
+//! react to carry a [`BuilderError`], which stays reachable through any
+//! context added on the way up. Use [`BuilderError::find`] to get it back.
+
+/// A failure of a build, verification or release step that callers can match on
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BuilderError {
+    /// The project does not depend on the Fluent SDK
+    #[error("Not a Fluent contract: {reason}")]
+    NotAFluentContract { reason: String },
+
+    /// The project is a Fluent contract but can't be built as one (crate type, entrypoint)
+    #[error("Invalid contract: {reason}")]
+    InvalidContract { reason: String },
+
+    /// The pinned Rust toolchain or the wasm target is not installed
+    #[error("Rust toolchain or target {toolchain} is not installed; install it with rustup")]
+    MissingToolchain { toolchain: String },
+
+    /// `cargo build` exited with an error
+    #[error("Cargo build failed:\n{stderr}")]
+    CargoBuildFailed { stderr: String },
+
+    /// The wasm could not be translated to rWASM
+    #[error("rWASM compilation failed: {reason}")]
+    RwasmCompilationFailed { reason: String },
+
+    #[error("Project is not in a Git repository")]
+    NotAGitRepository,
+
+    /// A clean checkout is required
+    #[error("Repository has {dirty_files} uncommitted changes")]
+    GitDirty { dirty_files: usize },
+
+    /// The container runtime is missing or its daemon is not running
+    #[error("{runtime} is not installed or not running")]
+    DockerUnavailable { runtime: String },
+
+    /// A container build or run failed
+    #[error("{reason}")]
+    DockerFailed { reason: String },
+
+    /// The operation needs the network, which `--offline` forbids
+    #[error("{operation} needs the network and cannot run with --offline")]
+    Offline { operation: String },
+}
+
+impl BuilderError {
+    /// The builder error behind `report`, if it has one
+    pub fn find(report: &eyre::Report) -> Option<&BuilderError> {
+        report.downcast_ref::<BuilderError>().or_else(|| {
+            report
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<BuilderError>())
+        })
+    }
+
+    /// Stable machine-readable name of the error, as in the CLI's JSON output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BuilderError::NotAFluentContract { .. } | BuilderError::InvalidContract { .. } => {
+                "invalid_contract"
+            }
+            BuilderError::MissingToolchain { .. } => "missing_toolchain",
+            BuilderError::CargoBuildFailed { .. } | BuilderError::RwasmCompilationFailed { .. } => {
+                "compilation_failed"
+            }
+            BuilderError::NotAGitRepository => "no_git_repository",
+            BuilderError::GitDirty { .. } => "git_dirty_state",
+            BuilderError::DockerUnavailable { .. } | BuilderError::DockerFailed { .. } => {
+                "docker_error"
+            }
+            BuilderError::Offline { .. } => "offline_unavailable",
+        }
+    }
+}
+
+/// The toolchain rustup reports as missing in cargo's stderr, e.g. `toolchain
+/// '1.83.0-x86_64-unknown-linux-gnu' is not installed`
+#[cfg(feature = "build")]
+pub(crate) fn missing_toolchain(stderr: &str) -> Option<String> {
+    stderr.lines().find_map(|line| {
+        if line.contains("target may not be installed") {
+            return Some("wasm32-unknown-unknown".to_string());
+        }
+        let rest = line.split("toolchain '").nth(1)?;
+        let (toolchain, after) = rest.split_once('\'')?;
+        after
+            .contains("is not installed")
+            .then(|| toolchain.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::WrapErr;
+
+    #[test]
+    fn test_find_through_context() {
+        let report: eyre::Result<()> = Err(BuilderError::GitDirty { dirty_files: 3 }.into());
+        let report = report
+            .context("Release failed")
+            .context("tag-release")
+            .unwrap_err();
+        assert_eq!(
+            BuilderError::find(&report),
+            Some(&BuilderError::GitDirty { dirty_files: 3 })
+        );
+        assert_eq!(
+            BuilderError::find(&report).unwrap().kind(),
+            "git_dirty_state"
+        );
+
+        assert_eq!(BuilderError::find(&eyre::eyre!("Something else")), None);
+    }
+
+    #[cfg(feature = "build")]
+    #[test]
+    fn test_missing_toolchain() {
+        let stderr =
+            "error: toolchain '1.83.0-x86_64-unknown-linux-gnu' is not installed\nhelp: run rustup";
+        assert_eq!(
+            missing_toolchain(stderr).as_deref(),
+            Some("1.83.0-x86_64-unknown-linux-gnu")
+        );
+        let stderr = "error[E0463]: can't find crate for `core`\n  = note: the \
+                      `wasm32-unknown-unknown` target may not be installed";
+        assert_eq!(
+            missing_toolchain(stderr).as_deref(),
+            Some("wasm32-unknown-unknown")
+        );
+        assert_eq!(missing_toolchain("error: could not compile `token`"), None);
+    }
+}
@@ -0,0 +1,207 @@
+//! Short-lived cache for slow environment checks
+//!
+//! Docker availability, local images and RPC chain ids rarely change between
+//! invocations but cost a process spawn or a network round trip each. Facts
+//! are kept in memory (long-running processes) and optionally persisted to
+//! disk (CLI invocations), and expire after a TTL.
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How long cached facts are trusted by default
+pub const DEFAULT_FACT_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Facts {
+    #[serde(default)]
+    entries: BTreeMap<String, Fact>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fact {
+    value: String,
+    /// Unix timestamp in seconds
+    stored_at: u64,
+}
+
+/// Cache of environment facts with a TTL
+#[derive(Debug)]
+pub struct FactCache {
+    path: Option<PathBuf>,
+    ttl: Duration,
+    enabled: bool,
+    facts: Mutex<Facts>,
+}
+
+impl FactCache {
+    /// Cache kept for the lifetime of the process
+    pub fn in_memory(ttl: Duration) -> Self {
+        Self {
+            path: None,
+            ttl,
+            enabled: true,
+            facts: Mutex::new(Facts::default()),
+        }
+    }
+
+    /// Cache shared between invocations through a file
+    pub fn on_disk(path: impl Into<PathBuf>, ttl: Duration) -> Self {
+        let path = path.into();
+        // A missing or corrupt cache file is an empty cache
+        let facts = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            ttl,
+            enabled: true,
+            facts: Mutex::new(facts),
+        }
+    }
+
+    /// Cache that never stores anything (`--no-cache`)
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::in_memory(Duration::ZERO)
+        }
+    }
+
+    /// Per-user cache file: `$XDG_CACHE_HOME/fluent-builder/facts.json`
+    pub fn default_path() -> PathBuf {
+        crate::cache::user_cache_dir().join("facts.json")
+    }
+
+    /// Cached value of `key`, if present and not expired
+    pub fn get(&self, key: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let facts = self.facts.lock().unwrap_or_else(|e| e.into_inner());
+        let fact = facts.entries.get(key)?;
+        let age = now().saturating_sub(fact.stored_at);
+        (Duration::from_secs(age) < self.ttl).then(|| fact.value.clone())
+    }
+
+    /// Store `value` under `key`
+    pub fn set(&self, key: &str, value: impl Into<String>) {
+        if !self.enabled {
+            return;
+        }
+        let mut facts = self.facts.lock().unwrap_or_else(|e| e.into_inner());
+        facts.entries.insert(
+            key.to_string(),
+            Fact {
+                value: value.into(),
+                stored_at: now(),
+            },
+        );
+        self.persist(&facts);
+    }
+
+    /// Forget `key`, e.g. after the underlying state was changed
+    pub fn invalidate(&self, key: &str) {
+        let mut facts = self.facts.lock().unwrap_or_else(|e| e.into_inner());
+        if facts.entries.remove(key).is_some() {
+            self.persist(&facts);
+        }
+    }
+
+    /// Cached value of `key`, computing and storing it when missing
+    pub fn get_or_try_insert(
+        &self,
+        key: &str,
+        compute: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        if let Some(value) = self.get(key) {
+            tracing::debug!("Using cached {}: {}", key, value);
+            return Ok(value);
+        }
+        let value = compute()?;
+        self.set(key, value.clone());
+        Ok(value)
+    }
+
+    /// Write the facts to disk; the cache is best-effort, so failures are only logged
+    fn persist(&self, facts: &Facts) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = (|| -> Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, serde_json::to_string_pretty(facts)?)
+                .with_context(|| format!("Failed to write {}", path.display()))
+        })();
+        if let Err(e) = result {
+            tracing::debug!("Failed to persist fact cache: {:#}", e);
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_on_disk_cache() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("facts.json");
+
+        let cache = FactCache::on_disk(&path, DEFAULT_FACT_TTL);
+        assert_eq!(cache.get("docker.available"), None);
+        let mut calls = 0;
+        for _ in 0..2 {
+            let value = cache
+                .get_or_try_insert("docker.available", || {
+                    calls += 1;
+                    Ok("true".to_string())
+                })
+                .unwrap();
+            assert_eq!(value, "true");
+        }
+        assert_eq!(calls, 1);
+
+        // Visible to the next invocation
+        let reloaded = FactCache::on_disk(&path, DEFAULT_FACT_TTL);
+        assert_eq!(reloaded.get("docker.available").as_deref(), Some("true"));
+
+        // Expired entries are ignored
+        assert_eq!(
+            FactCache::on_disk(&path, Duration::ZERO).get("docker.available"),
+            None
+        );
+
+        reloaded.invalidate("docker.available");
+        assert_eq!(
+            FactCache::on_disk(&path, DEFAULT_FACT_TTL).get("docker.available"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_disabled_cache() {
+        let cache = FactCache::disabled();
+        cache.set("rpc.chain_id", "20993");
+        assert_eq!(cache.get("rpc.chain_id"), None);
+        assert!(cache
+            .get_or_try_insert("rpc.chain_id", || Err(eyre::eyre!("offline")))
+            .is_err());
+    }
+}
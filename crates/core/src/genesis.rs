@@ -0,0 +1,134 @@
+//! Preloading built contracts into a local devnet genesis
+//!
+//! A genesis `alloc` maps addresses to accounts. A contract account holds the
+//! rWASM a deployment would have stored as its `code`; the `deploy` entrypoint
+//! does not run, so storage it would initialize has to be given explicitly.
+
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// An account of a genesis `alloc`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GenesisAccount {
+    /// Hex quantity in wei
+    pub balance: String,
+    /// Hex quantity; contracts start at 1, as if they had been created
+    pub nonce: String,
+    /// `0x`-prefixed rWASM
+    pub code: String,
+    /// Storage slots by slot, both 32-byte hex words
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<String, String>,
+}
+
+impl GenesisAccount {
+    /// Contract account running `rwasm`, with no balance and empty storage
+    pub fn contract(rwasm: &[u8]) -> Self {
+        Self {
+            balance: "0x0".to_string(),
+            nonce: "0x1".to_string(),
+            code: format!("0x{}", hex::encode(rwasm)),
+            storage: BTreeMap::new(),
+        }
+    }
+}
+
+/// Lowercase `0x`-prefixed form of a 20-byte hex address
+pub fn normalize_genesis_address(address: &str) -> Result<String> {
+    let hex_digits = address.trim().trim_start_matches("0x");
+    let bytes = hex::decode(hex_digits).with_context(|| format!("Invalid address {}", address))?;
+    eyre::ensure!(
+        bytes.len() == 20,
+        "Invalid address {}: expected 20 bytes, got {}",
+        address,
+        bytes.len()
+    );
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+/// Add `accounts` to the `alloc` of a genesis file
+///
+/// Fails if the genesis already allocates one of the addresses to another account.
+pub fn merge_into_genesis(
+    genesis: &mut Value,
+    accounts: &BTreeMap<String, GenesisAccount>,
+) -> Result<()> {
+    let alloc = genesis
+        .as_object_mut()
+        .ok_or_else(|| eyre::eyre!("Genesis is not a JSON object"))?
+        .entry("alloc")
+        .or_insert_with(|| Value::Object(Default::default()))
+        .as_object_mut()
+        .ok_or_else(|| eyre::eyre!("Genesis `alloc` is not a JSON object"))?;
+
+    for (address, account) in accounts {
+        let address = normalize_genesis_address(address)?;
+        let account = serde_json::to_value(account)?;
+        // Keys may be written with or without 0x, in any case
+        let existing = alloc
+            .iter()
+            .find(|(key, _)| normalize_genesis_address(key).is_ok_and(|key| key == address))
+            .map(|(key, value)| (key.clone(), value.clone()));
+        match existing {
+            Some((_, value)) if value == account => {}
+            Some((key, _)) => {
+                return Err(eyre::eyre!(
+                    "Genesis already allocates {} to another account",
+                    key
+                ));
+            }
+            None => {
+                alloc.insert(address, account);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ADDRESS: &str = "0x00000000000000000000000000000000000000AA";
+
+    #[test]
+    fn test_merge_into_genesis() {
+        let account = GenesisAccount::contract(&[0xef, 0x52, 0x00]);
+        let accounts = BTreeMap::from([(ADDRESS.to_string(), account.clone())]);
+        let mut genesis = json!({
+            "config": {"chainId": 1337},
+            "alloc": {"f39fd6e51aad88f6f4ce6ab8827279cfffb92266": {"balance": "0x1"}}
+        });
+
+        merge_into_genesis(&mut genesis, &accounts).unwrap();
+        let entry = &genesis["alloc"]["0x00000000000000000000000000000000000000aa"];
+        assert_eq!(
+            entry,
+            &json!({"balance": "0x0", "nonce": "0x1", "code": "0xef5200"})
+        );
+        assert_eq!(genesis["alloc"].as_object().unwrap().len(), 2);
+
+        // The same account again is fine, another one at the address is not
+        merge_into_genesis(&mut genesis, &accounts).unwrap();
+        let other = BTreeMap::from([(ADDRESS.to_string(), GenesisAccount::contract(&[0x00]))]);
+        assert!(merge_into_genesis(&mut genesis, &other).is_err());
+
+        let mut empty = json!({});
+        merge_into_genesis(&mut empty, &accounts).unwrap();
+        assert_eq!(empty["alloc"].as_object().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_normalize_genesis_address() {
+        assert_eq!(
+            normalize_genesis_address("00000000000000000000000000000000000000AA").unwrap(),
+            "0x00000000000000000000000000000000000000aa"
+        );
+        assert!(normalize_genesis_address("0x1234").is_err());
+        assert!(normalize_genesis_address("0xzz").is_err());
+    }
+}
@@ -1,8 +1,15 @@
 //! Git repository detection and information extraction
+//!
+//! HEAD, the branch, the remote, uncommitted changes and the working tree
+//! root are read with libgit2, so a build still knows its commit where no git
+//! binary is installed. Repositories libgit2 can't open, such as ones with the
+//! reftable format, are inspected with git commands instead.
 
+use crate::config::Timeouts;
 use eyre::{Context, Result};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output};
+use std::time::Duration;
 
 /// Git repository information
 #[derive(Debug, Clone)]
@@ -23,23 +30,90 @@ pub struct GitInfo {
 
 /// Detect if a directory is part of a Git repository and extract info
 pub fn detect_git_info(project_root: &Path) -> Result<Option<GitInfo>> {
+    detect_git_info_with_timeout(project_root, Timeouts::default().git)
+}
+
+/// Like [`detect_git_info`], with a time limit for each git command
+pub fn detect_git_info_with_timeout(
+    project_root: &Path,
+    timeout: Option<Duration>,
+) -> Result<Option<GitInfo>> {
+    match detect_with_git2(project_root) {
+        Ok(info) => Ok(info),
+        Err(e) => {
+            tracing::debug!(
+                "Reading {} with git commands: {}",
+                project_root.display(),
+                e.message()
+            );
+            detect_with_git(project_root, timeout)
+        }
+    }
+}
+
+/// [`detect_git_info_with_timeout`] with libgit2, without running git
+fn detect_with_git2(project_root: &Path) -> std::result::Result<Option<GitInfo>, git2::Error> {
+    let repository = match git2::Repository::discover(project_root) {
+        Ok(repository) => repository,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if repository.is_bare() {
+        return Ok(None);
+    }
+
+    let head = repository.head()?;
+    let commit_hash = head.peel_to_commit()?.id().to_string();
+    // Detached HEAD is reported as `HEAD`, like `git rev-parse --abbrev-ref HEAD`
+    let branch = if head.is_branch() {
+        head.shorthand().unwrap_or("HEAD")
+    } else {
+        "HEAD"
+    }
+    .to_string();
+    let remote_url = repository
+        .find_remote("origin")
+        .ok()
+        .and_then(|remote| remote.url().map(normalize_git_url))
+        .unwrap_or_default();
+
+    // The entries `git status --porcelain` prints: untracked directories count once
+    let mut options = git2::StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .include_ignored(false);
+    let dirty_files_count = repository.statuses(Some(&mut options))?.len();
+
+    Ok(Some(GitInfo {
+        remote_url,
+        commit_hash_short: commit_hash.chars().take(7).collect(),
+        commit_hash,
+        branch,
+        is_dirty: dirty_files_count > 0,
+        dirty_files_count,
+    }))
+}
+
+/// [`detect_git_info_with_timeout`] with git commands only
+fn detect_with_git(project_root: &Path, timeout: Option<Duration>) -> Result<Option<GitInfo>> {
     // Check if .git directory exists
-    if !is_git_repository(project_root) {
+    if !is_git_repository(project_root, timeout)? {
         return Ok(None);
     }
 
     // Get commit hash
-    let commit_hash = get_commit_hash(project_root)?;
+    let commit_hash = get_commit_hash(project_root, timeout)?;
     let commit_hash_short = commit_hash.chars().take(7).collect();
 
     // Get remote URL
-    let remote_url = get_remote_url(project_root).unwrap_or_default();
+    let remote_url = get_remote_url(project_root, timeout).unwrap_or_default();
 
     // Get current branch
-    let branch = get_current_branch(project_root).unwrap_or_else(|_| "HEAD".to_string());
+    let branch = get_current_branch(project_root, timeout).unwrap_or_else(|_| "HEAD".to_string());
 
     // Check for uncommitted changes
-    let (is_dirty, dirty_files_count) = check_dirty_state(project_root)?;
+    let (is_dirty, dirty_files_count) = check_dirty_state(project_root, timeout)?;
 
     Ok(Some(GitInfo {
         remote_url,
@@ -52,22 +126,93 @@ pub fn detect_git_info(project_root: &Path) -> Result<Option<GitInfo>> {
 }
 
 /// Check if directory is a Git repository
-fn is_git_repository(path: &Path) -> bool {
-    let output = Command::new("git")
-        .current_dir(path)
-        .args(["rev-parse", "--is-inside-work-tree"])
-        .output();
+///
+/// Timeouts are reported as errors rather than "not a repository".
+fn is_git_repository(path: &Path, timeout: Option<Duration>) -> Result<bool> {
+    match git(path, &["rev-parse", "--is-inside-work-tree"], timeout) {
+        Ok(output) => Ok(output.status.success()),
+        Err(e) if e.downcast_ref::<crate::process::ProcessError>().is_some() => Err(e),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Repository directories of a checkout, as absolute paths
+///
+/// In a `git worktree` or submodule checkout `.git` is a file pointing to a
+/// directory elsewhere, so the checkout alone is not a repository.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitDirs {
+    /// Root of the working tree
+    pub toplevel: PathBuf,
+    /// Repository directory of this checkout, e.g. `.git/worktrees/<name>` or `.git/modules/<name>`
+    pub git_dir: PathBuf,
+    /// Directory shared by all worktrees of the repository
+    pub common_dir: PathBuf,
+}
 
-    matches!(output, Ok(o) if o.status.success())
+impl GitDirs {
+    /// Whether `.git` is a file redirecting to a repository directory outside the working tree
+    pub fn is_redirected(&self) -> bool {
+        !self.common_dir.starts_with(&self.toplevel)
+    }
+}
+
+/// Resolve the repository directories of the checkout containing `path`
+pub fn git_dirs(path: &Path, timeout: Option<Duration>) -> Result<Option<GitDirs>> {
+    if !is_git_repository(path, timeout)? {
+        return Ok(None);
+    }
+
+    let output = git(
+        path,
+        &[
+            "rev-parse",
+            "--show-toplevel",
+            "--absolute-git-dir",
+            "--git-common-dir",
+        ],
+        timeout,
+    )?;
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "Failed to resolve git directories: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let mut lines = stdout.lines();
+    let mut next = |what: &str| {
+        lines
+            .next()
+            .map(PathBuf::from)
+            .ok_or_else(|| eyre::eyre!("git rev-parse did not print the {}", what))
+    };
+    let (toplevel, git_dir, common_dir) =
+        (next("toplevel")?, next("git dir")?, next("common dir")?);
+
+    // The common dir is printed relative to `path` unless it is elsewhere
+    let canonicalize = |dir: PathBuf| {
+        crate::paths::canonicalize_path(&path.join(dir))
+            .context("Failed to canonicalize git directory")
+    };
+    Ok(Some(GitDirs {
+        toplevel: canonicalize(toplevel)?,
+        git_dir: canonicalize(git_dir)?,
+        common_dir: canonicalize(common_dir)?,
+    }))
+}
+
+/// Run a git command in `path`, killing it after `timeout`
+fn git(path: &Path, args: &[&str], timeout: Option<Duration>) -> Result<Output> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(path).args(args);
+    crate::process::output_with_timeout(&mut cmd, &format!("git {}", args[0]), timeout)
 }
 
 /// Get current commit hash
-fn get_commit_hash(path: &Path) -> Result<String> {
-    let output = Command::new("git")
-        .current_dir(path)
-        .args(["rev-parse", "HEAD"])
-        .output()
-        .context("Failed to execute git rev-parse")?;
+fn get_commit_hash(path: &Path, timeout: Option<Duration>) -> Result<String> {
+    let output = git(path, &["rev-parse", "HEAD"], timeout)?;
 
     if !output.status.success() {
         return Err(eyre::eyre!("Failed to get commit hash"));
@@ -77,12 +222,8 @@ fn get_commit_hash(path: &Path) -> Result<String> {
 }
 
 /// Get remote repository URL
-fn get_remote_url(path: &Path) -> Result<String> {
-    let output = Command::new("git")
-        .current_dir(path)
-        .args(["config", "--get", "remote.origin.url"])
-        .output()
-        .context("Failed to get remote URL")?;
+fn get_remote_url(path: &Path, timeout: Option<Duration>) -> Result<String> {
+    let output = git(path, &["config", "--get", "remote.origin.url"], timeout)?;
 
     if !output.status.success() {
         return Err(eyre::eyre!("No remote origin found"));
@@ -95,12 +236,8 @@ fn get_remote_url(path: &Path) -> Result<String> {
 }
 
 /// Get current branch name
-fn get_current_branch(path: &Path) -> Result<String> {
-    let output = Command::new("git")
-        .current_dir(path)
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .output()
-        .context("Failed to get current branch")?;
+fn get_current_branch(path: &Path, timeout: Option<Duration>) -> Result<String> {
+    let output = git(path, &["rev-parse", "--abbrev-ref", "HEAD"], timeout)?;
 
     if !output.status.success() {
         return Err(eyre::eyre!("Failed to get branch name"));
@@ -110,22 +247,23 @@ fn get_current_branch(path: &Path) -> Result<String> {
 }
 
 /// Check if repository has uncommitted changes
-fn check_dirty_state(path: &Path) -> Result<(bool, usize)> {
+fn check_dirty_state(path: &Path, timeout: Option<Duration>) -> Result<(bool, usize)> {
     // Check for any changes (staged or unstaged)
-    let output = Command::new("git")
-        .current_dir(path)
-        .args(["status", "--porcelain"])
-        .output()
-        .context("Failed to check git status")?;
+    let output = git(path, &["status", "--porcelain"], timeout)?;
 
     if !output.status.success() {
         return Err(eyre::eyre!("Failed to get git status"));
     }
 
-    let status = String::from_utf8(output.stdout)?;
-    let dirty_files: Vec<&str> = status.lines().filter(|line| !line.is_empty()).collect();
+    count_dirty_files(&output.stdout)
+}
+
+/// Dirty state from the output of `git status --porcelain`
+fn count_dirty_files(status: &[u8]) -> Result<(bool, usize)> {
+    let status = std::str::from_utf8(status)?;
+    let dirty_files = status.lines().filter(|line| !line.is_empty()).count();
 
-    Ok((!dirty_files.is_empty(), dirty_files.len()))
+    Ok((dirty_files > 0, dirty_files))
 }
 
 /// Normalize Git URL to consistent format
@@ -154,27 +292,30 @@ fn normalize_git_url(url: &str) -> String {
 }
 
 /// Calculate project path relative to Git root
-pub fn get_project_path_in_repo(project_root: &Path) -> Result<String> {
-    // Get git root directory
-    let output = Command::new("git")
-        .current_dir(project_root)
-        .args(["rev-parse", "--show-toplevel"])
-        .output()
-        .context("Failed to get git root")?;
-
-    if !output.status.success() {
-        return Err(eyre::eyre!("Failed to get git root directory"));
-    }
-
-    let git_root = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+pub fn get_project_path_in_repo(project_root: &Path, timeout: Option<Duration>) -> Result<String> {
+    let git_root = match git2::Repository::discover(project_root) {
+        Ok(repository) => repository
+            .workdir()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| eyre::eyre!("Repository has no working tree"))?,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => {
+            return Err(eyre::eyre!("Project is not inside a git repository"))
+        }
+        Err(e) => {
+            tracing::debug!(
+                "Reading {} with git commands: {}",
+                project_root.display(),
+                e.message()
+            );
+            show_toplevel(project_root, timeout)?
+        }
+    };
 
     // Make both paths absolute for reliable comparison
-    let abs_project_root = project_root
-        .canonicalize()
+    let abs_project_root = crate::paths::canonicalize_path(project_root)
         .context("Failed to canonicalize project root")?;
-    let abs_git_root = git_root
-        .canonicalize()
-        .context("Failed to canonicalize git root")?;
+    let abs_git_root =
+        crate::paths::canonicalize_path(&git_root).context("Failed to canonicalize git root")?;
 
     // Calculate relative path
     let relative_path = abs_project_root
@@ -182,7 +323,7 @@ pub fn get_project_path_in_repo(project_root: &Path) -> Result<String> {
         .context("Project is not inside git repository")?;
 
     // Convert to string with forward slashes
-    let path_str = relative_path.to_string_lossy().replace('\\', "/");
+    let path_str = crate::paths::to_slash(relative_path);
 
     // Return "." if empty, otherwise return the path
     Ok(if path_str.is_empty() {
@@ -192,9 +333,312 @@ pub fn get_project_path_in_repo(project_root: &Path) -> Result<String> {
     })
 }
 
+/// Working tree root of the repository at `dir`, from `git rev-parse --show-toplevel`
+fn show_toplevel(dir: &Path, timeout: Option<Duration>) -> Result<PathBuf> {
+    let output = git(dir, &["rev-parse", "--show-toplevel"], timeout)?;
+
+    if !output.status.success() {
+        return Err(eyre::eyre!("Failed to get git root directory"));
+    }
+
+    Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+}
+
+/// Check that `commit` is a full commit hash, not a branch, tag or abbreviation that could
+/// resolve to other sources
+pub(crate) fn check_commit_hash(commit: &str) -> Result<()> {
+    if commit.len() != 40 || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(eyre::eyre!(
+            "{} is not a full commit hash (40 hex digits)",
+            commit
+        ));
+    }
+    Ok(())
+}
+
+/// Clone `repository` into `dest`, which must not exist yet, and check out `commit` detached
+pub(crate) fn clone_at_commit(
+    repository: &str,
+    commit: &str,
+    dest: &Path,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    check_commit_hash(commit)?;
+    let parent = dest
+        .parent()
+        .ok_or_else(|| eyre::eyre!("Clone destination has no parent directory"))?;
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("Failed to create {}", parent.display()))?;
+    let dest_arg = dest.to_string_lossy();
+    let output = git(
+        parent,
+        &[
+            "clone",
+            "--no-checkout",
+            "--quiet",
+            "--",
+            repository,
+            &dest_arg,
+        ],
+        timeout,
+    )?;
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "Failed to clone {}: {}",
+            repository,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let output = git(
+        dest,
+        &["switch", "--detach", "--quiet", "--", commit],
+        timeout,
+    )?;
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "Commit {} is not in {}: {}",
+            commit,
+            repository,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let head = get_commit_hash(dest, timeout)?;
+    if head != commit.to_lowercase() {
+        return Err(eyre::eyre!(
+            "{} resolved to {} in {}, not to a commit",
+            commit,
+            head,
+            repository
+        ));
+    }
+    Ok(())
+}
+
+/// Check whether a tag exists in the repository
+pub fn tag_exists(project_root: &Path, tag: &str, timeout: Option<Duration>) -> Result<bool> {
+    let reference = format!("refs/tags/{}", tag);
+    let output = git(
+        project_root,
+        &["rev-parse", "--verify", "--quiet", &reference],
+        timeout,
+    )?;
+
+    Ok(output.status.success())
+}
+
+/// Create an annotated tag pointing at HEAD
+pub fn create_annotated_tag(
+    project_root: &Path,
+    tag: &str,
+    message: &str,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let output = git(
+        project_root,
+        &["tag", "--annotate", tag, "--message", message],
+        timeout,
+    )?;
+
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "Failed to create tag {}: {}",
+            tag,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
+
+    fn run(dir: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args([
+                "-c",
+                "protocol.file.allow=always",
+                "-c",
+                "init.defaultBranch=main",
+            ])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?}: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    /// Repository with one commit of a Cargo.toml
+    fn repo(dir: &Path) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), "[package]\nname = \"token\"\n").unwrap();
+        run(dir, &["init", "-q"]);
+        run(dir, &["add", "."]);
+        run(dir, &["commit", "-q", "-m", "init"]);
+    }
+
+    #[test]
+    fn test_worktree_checkout() {
+        let tmp = TempDir::new().unwrap();
+        let main = tmp.path().join("main");
+        repo(&main);
+        let worktree = tmp.path().join("feature");
+        run(
+            &main,
+            &[
+                "worktree",
+                "add",
+                "-q",
+                "-b",
+                "feature",
+                worktree.to_str().unwrap(),
+            ],
+        );
+        assert!(worktree.join(".git").is_file());
+
+        let info = detect_git_info(&worktree)
+            .unwrap()
+            .expect("worktree is a repository");
+        assert_eq!(info.branch, "feature");
+        assert!(!info.is_dirty);
+        assert_eq!(get_project_path_in_repo(&worktree, None).unwrap(), ".");
+
+        let dirs = git_dirs(&worktree, None).unwrap().unwrap();
+        let main = crate::paths::canonicalize_path(&main).unwrap();
+        assert_eq!(dirs.common_dir, main.join(".git"));
+        assert_eq!(dirs.git_dir, main.join(".git/worktrees/feature"));
+        assert!(dirs.is_redirected());
+        assert!(!git_dirs(&main, None).unwrap().unwrap().is_redirected());
+    }
+
+    #[test]
+    fn test_submodule_checkout() {
+        let tmp = TempDir::new().unwrap();
+        let contract = tmp.path().join("contract");
+        repo(&contract);
+        let parent = tmp.path().join("parent");
+        repo(&parent);
+        run(
+            &parent,
+            &[
+                "submodule",
+                "add",
+                "-q",
+                contract.to_str().unwrap(),
+                "token",
+            ],
+        );
+        let submodule = parent.join("token");
+        assert!(submodule.join(".git").is_file());
+
+        let info = detect_git_info(&submodule)
+            .unwrap()
+            .expect("submodule is a repository");
+        assert_eq!(info.remote_url, contract.to_str().unwrap());
+        assert!(!info.is_dirty);
+
+        let dirs = git_dirs(&submodule, None).unwrap().unwrap();
+        let parent = crate::paths::canonicalize_path(&parent).unwrap();
+        assert_eq!(dirs.toplevel, parent.join("token"));
+        assert_eq!(dirs.common_dir, parent.join(".git/modules/token"));
+        assert!(dirs.is_redirected());
+
+        assert!(git_dirs(tmp.path(), None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_clone_at_commit() {
+        let tmp = TempDir::new().unwrap();
+        let origin = tmp.path().join("origin");
+        repo(&origin);
+        let first = get_commit_hash(&origin, None).unwrap();
+        std::fs::write(
+            origin.join("Cargo.toml"),
+            "[package]\nname = \"token-v2\"\n",
+        )
+        .unwrap();
+        run(&origin, &["commit", "-q", "-am", "v2"]);
+
+        let clone = tmp.path().join("clone");
+        clone_at_commit(origin.to_str().unwrap(), &first, &clone, None).unwrap();
+        assert_eq!(get_commit_hash(&clone, None).unwrap(), first);
+        assert!(std::fs::read_to_string(clone.join("Cargo.toml"))
+            .unwrap()
+            .contains("\"token\""));
+
+        let missing = clone_at_commit(
+            origin.to_str().unwrap(),
+            &"0123456789abcdef".repeat(3)[..40],
+            &tmp.path().join("missing"),
+            None,
+        );
+        assert!(missing.unwrap_err().to_string().contains("is not in"));
+        let abbreviated = clone_at_commit(
+            origin.to_str().unwrap(),
+            &first[..12],
+            &tmp.path().join("abbreviated"),
+            None,
+        );
+        assert!(abbreviated
+            .unwrap_err()
+            .to_string()
+            .contains("not a full commit hash"));
+    }
+
+    #[test]
+    fn test_git2_matches_git_commands() {
+        let tmp = TempDir::new().unwrap();
+        repo(tmp.path());
+        run(
+            tmp.path(),
+            &["remote", "add", "origin", "git@github.com:user/token.git"],
+        );
+        let contract = tmp.path().join("contracts/token");
+        std::fs::create_dir_all(&contract).unwrap();
+        let with_git = detect_with_git(&contract, None).unwrap().unwrap();
+
+        let info = detect_with_git2(&contract).unwrap().unwrap();
+        assert_eq!(info.commit_hash, with_git.commit_hash);
+        assert_eq!(info.branch, "main");
+        assert_eq!(info.remote_url, "https://github.com/user/token.git");
+        assert!(!info.is_dirty);
+        assert_eq!(
+            get_project_path_in_repo(&contract, None).unwrap(),
+            "contracts/token"
+        );
+        assert_eq!(
+            crate::canonicalize_path(&show_toplevel(&contract, None).unwrap()).unwrap(),
+            crate::canonicalize_path(tmp.path()).unwrap()
+        );
+
+        // Packed refs, a detached HEAD and an untracked directory
+        run(tmp.path(), &["pack-refs", "--all"]);
+        run(tmp.path(), &["checkout", "-q", "--detach"]);
+        std::fs::write(tmp.path().join("lib.rs"), "").unwrap();
+        std::fs::write(contract.join("a.rs"), "").unwrap();
+        std::fs::write(contract.join("b.rs"), "").unwrap();
+        let with_git = detect_with_git(&contract, None).unwrap().unwrap();
+        let info = detect_with_git2(&contract).unwrap().unwrap();
+        assert_eq!(info.commit_hash, with_git.commit_hash);
+        assert_eq!(
+            (info.branch.as_str(), info.is_dirty, info.dirty_files_count),
+            ("HEAD", true, 2)
+        );
+        assert_eq!(info.dirty_files_count, with_git.dirty_files_count);
+
+        assert!(detect_with_git2(TempDir::new().unwrap().path())
+            .unwrap()
+            .is_none());
+    }
 
     #[test]
     fn test_normalize_git_url() {
@@ -0,0 +1,172 @@
+//! Hash algorithms for bytecode comparison
+//!
+//! Hashes written to metadata.json are tagged with their algorithm
+//! (`sha256:<hex>`). Verification defaults to sha256 of the deployed code;
+//! keccak256 is the chain's native code hash, as returned by `EXTCODEHASH`.
+//!
+//! Files and readers are hashed in chunks, so large archives and artifacts
+//! are never held in memory whole.
+
+use eyre::Context;
+#[cfg(any(feature = "build", feature = "rpc"))]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use std::{
+    fmt,
+    io::{self, Read},
+    path::Path,
+    str::FromStr,
+};
+
+/// Size of the chunks files are read in while hashing
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Algorithm a bytecode hash was computed with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(any(feature = "build", feature = "rpc"), derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// Native code hash of the chain (`EXTCODEHASH`)
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    pub const ALL: &'static [HashAlgorithm] = &[HashAlgorithm::Sha256, HashAlgorithm::Keccak256];
+
+    /// Name used in tagged hashes and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Keccak256 => "keccak256",
+        }
+    }
+
+    /// Lowercase hex digest, without prefix
+    pub fn hash(&self, data: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Sha256 => hex::encode(Sha256::digest(data)),
+            HashAlgorithm::Keccak256 => hex::encode(Keccak256::digest(data)),
+        }
+    }
+
+    /// Lowercase hex digest of everything `reader` yields, read in chunks
+    pub fn hash_reader(&self, reader: impl Read) -> io::Result<String> {
+        Ok(match self {
+            HashAlgorithm::Sha256 => {
+                hex::encode(digest_reader(Sha256::new(), reader)?.0.finalize())
+            }
+            HashAlgorithm::Keccak256 => {
+                hex::encode(digest_reader(Keccak256::new(), reader)?.0.finalize())
+            }
+        })
+    }
+
+    /// Lowercase hex digest of a file, read in chunks
+    pub fn hash_file(&self, path: &Path) -> eyre::Result<String> {
+        std::fs::File::open(path)
+            .and_then(|file| self.hash_reader(file))
+            .with_context(|| format!("Failed to hash {}", path.display()))
+    }
+
+    /// Digest tagged with the algorithm, e.g. `keccak256:<hex>`
+    pub fn tagged(&self, data: &[u8]) -> String {
+        format!("{}:{}", self.name(), self.hash(data))
+    }
+
+    /// Split a tagged hash into its algorithm and digest; untagged hashes have no algorithm
+    pub fn split_tagged(hash: &str) -> (Option<Self>, &str) {
+        Self::ALL
+            .iter()
+            .find_map(|algorithm| {
+                hash.strip_prefix(algorithm.name())
+                    .and_then(|rest| rest.strip_prefix(':'))
+                    .map(|digest| (Some(*algorithm), digest))
+            })
+            .unwrap_or((None, hash))
+    }
+}
+
+/// Feed `reader` into `hasher` chunk by chunk, returning it with the number of bytes read
+pub(crate) fn digest_reader<D: Digest>(
+    mut hasher: D,
+    mut reader: impl Read,
+) -> io::Result<(D, u64)> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok((hasher, total)),
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                total += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|algorithm| algorithm.name() == s.to_lowercase())
+            .copied()
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "Unknown hash algorithm '{}', expected sha256 or keccak256",
+                    s
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_algorithms() {
+        assert_eq!(
+            HashAlgorithm::Sha256.hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        // EXTCODEHASH of an account with empty code
+        assert_eq!(
+            HashAlgorithm::Keccak256.tagged(b""),
+            "keccak256:c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+
+        // Inputs spanning several chunks hash the same when streamed
+        let data: Vec<u8> = (0..CHUNK_SIZE * 3 + 7).map(|i| i as u8).collect();
+        for algorithm in HashAlgorithm::ALL {
+            assert_eq!(
+                algorithm.hash_reader(data.as_slice()).unwrap(),
+                algorithm.hash(&data)
+            );
+        }
+
+        assert_eq!(
+            HashAlgorithm::split_tagged("keccak256:ab"),
+            (Some(HashAlgorithm::Keccak256), "ab")
+        );
+        assert_eq!(HashAlgorithm::split_tagged("0xab"), (None, "0xab"));
+        assert_eq!(
+            "KECCAK256".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Keccak256
+        );
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
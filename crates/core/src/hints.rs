@@ -0,0 +1,424 @@
+//! Likely causes of a verification mismatch
+//!
+//! Compares the metadata recorded for a deployment with the metadata of the
+//! local rebuild and ranks the differences that usually change the bytecode.
+
+#[cfg(feature = "build")]
+use crate::{BuildFeatures, Provenance};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "build")]
+use serde_json::Value;
+
+/// A difference between the recorded and the local build
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MismatchHint {
+    /// Short identifier, e.g. `sdk_version`
+    pub cause: String,
+    /// Human-readable explanation
+    pub message: String,
+    /// Higher is more likely to explain the mismatch
+    pub rank: u8,
+}
+
+#[cfg(feature = "build")]
+/// Rank the differences between recorded and local metadata, most likely first
+pub fn diagnose_mismatch(recorded: &Value, local: &Value) -> Vec<MismatchHint> {
+    let mut hints = Vec::new();
+    let mut hint = |cause: &str, rank: u8, message: String| {
+        hints.push(MismatchHint {
+            cause: cause.to_string(),
+            message,
+            rank,
+        })
+    };
+
+    let field = |metadata: &Value, pointer: &str| {
+        metadata.pointer(pointer).map(|v| match v {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    };
+    let differs = |pointer: &str| {
+        let recorded = field(recorded, pointer)?;
+        let local = field(local, pointer)?;
+        (recorded != local).then_some((recorded, local))
+    };
+
+    // Metadata without a network predates --network or was built without one
+    let network = |metadata: &Value| field(metadata, "/compilation_settings/build_cfg/network");
+    if let Some(recorded_network) = network(recorded) {
+        match network(local) {
+            Some(local_network) if local_network == recorded_network => {}
+            Some(local_network) => hint(
+                "network",
+                95,
+                format!(
+                    "metadata says the build was for network {}, local build was for {}",
+                    recorded_network, local_network
+                ),
+            ),
+            None => hint(
+                "network",
+                95,
+                format!(
+                    "metadata says the build was for network {}; pass --network {}",
+                    recorded_network, recorded_network
+                ),
+            ),
+        }
+    }
+
+    if let Some((recorded, local)) = differs("/compilation_settings/rust/version") {
+        hint(
+            "rust_version",
+            90,
+            format!(
+                "metadata says Rust {}, your rust-toolchain.toml has {}",
+                recorded, local
+            ),
+        );
+    }
+
+    let sdk_tag = differs("/compilation_settings/sdk/tag");
+    let sdk_commit = differs("/compilation_settings/sdk/commit");
+    if sdk_tag.is_some() || sdk_commit.is_some() {
+        let describe = |metadata: &Value| {
+            let tag = field(metadata, "/compilation_settings/sdk/tag").unwrap_or_default();
+            match field(metadata, "/compilation_settings/sdk/commit") {
+                Some(commit) if commit != "unknown" => {
+                    format!("{} via git ({})", tag, short(&commit))
+                }
+                _ => tag,
+            }
+        };
+        hint(
+            "sdk_version",
+            85,
+            format!(
+                "metadata says SDK {}, your lockfile has {}",
+                describe(recorded),
+                describe(local)
+            ),
+        );
+    }
+
+    if let (Ok(expected), Ok(actual)) = (
+        BuildFeatures::from_metadata(recorded),
+        BuildFeatures::from_metadata(local),
+    ) {
+        if expected.features != actual.features
+            || expected.no_default_features != actual.no_default_features
+        {
+            hint(
+                "features",
+                80,
+                format!("metadata says {}, local build used {}", expected, actual),
+            );
+        }
+        if expected.profile != actual.profile {
+            hint(
+                "profile",
+                80,
+                format!(
+                    "metadata says profile {}, local build used {}",
+                    expected.profile, actual.profile
+                ),
+            );
+        }
+    }
+
+    // Missing in metadata of builds without extra arguments
+    let cargo_args = |metadata: &Value| {
+        metadata
+            .pointer("/compilation_settings/build_cfg/extra_cargo_args")
+            .and_then(Value::as_array)
+            .map(|args| {
+                args.iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default()
+    };
+    let (recorded_args, local_args) = (cargo_args(recorded), cargo_args(local));
+    if recorded_args != local_args {
+        let describe = |args: &str| {
+            if args.is_empty() {
+                "none".to_string()
+            } else {
+                format!("`{}`", args)
+            }
+        };
+        hint(
+            "cargo_args",
+            80,
+            format!(
+                "metadata says extra cargo arguments {}, local build used {} (pass them after --)",
+                describe(&recorded_args),
+                describe(&local_args)
+            ),
+        );
+    }
+
+    let rustflags = |metadata: &Value| {
+        metadata
+            .pointer("/compilation_settings/build_cfg/rustflags")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+    let (recorded_flags, local_flags) = (rustflags(recorded), rustflags(local));
+    if recorded_flags != local_flags {
+        let describe = |flags: &str| {
+            if flags.is_empty() {
+                "none".to_string()
+            } else {
+                format!("`{}`", flags)
+            }
+        };
+        hint(
+            "rustflags",
+            80,
+            format!(
+                "metadata says RUSTFLAGS {}, local build used {} (pass them with --rustflags)",
+                describe(&recorded_flags),
+                describe(&local_flags)
+            ),
+        );
+    }
+
+    if let Some((recorded, local)) = differs("/dependencies/cargo_lock_hash") {
+        let message = if recorded.contains("no-cargo-lock") || local.contains("no-cargo-lock") {
+            "Cargo.lock is missing on one side; dependencies may have resolved differently"
+                .to_string()
+        } else {
+            format!(
+                "Cargo.lock differs from the recorded build ({} vs {})",
+                short(&recorded),
+                short(&local)
+            )
+        };
+        hint("lockfile", 70, message);
+    }
+
+    if let Some((recorded, local)) = differs("/builder_version") {
+        hint(
+            "builder_version",
+            60,
+            builder_version_message(&recorded, &local),
+        );
+    }
+
+    if let Some((recorded, local)) = differs("/compilation_settings/build_cfg/locked") {
+        hint(
+            "locked",
+            50,
+            format!(
+                "metadata says --locked={}, local build used --locked={}",
+                recorded, local
+            ),
+        );
+    }
+
+    if let Some((recorded, _)) = differs("/docker") {
+        let message = if recorded == "true" {
+            "the recorded build ran in Docker, the local build did not (try without --no-docker)"
+        } else {
+            "the local build ran in Docker, the recorded build did not"
+        };
+        hint("docker", 40, message.to_string());
+    }
+
+    if let Some((recorded, local)) = differs("/source_tree_hash") {
+        hint(
+            "sources",
+            30,
+            format!(
+                "source files differ from the recorded build ({} vs {})",
+                short(&recorded),
+                short(&local)
+            ),
+        );
+    }
+
+    hints.sort_by_key(|h| std::cmp::Reverse(h.rank));
+    hints
+}
+
+#[cfg(feature = "build")]
+/// Rank the differences between the provenance of deployed and local bytecode
+pub fn diagnose_provenance(deployed: &Provenance, local: &Provenance) -> Vec<MismatchHint> {
+    let mut hints = Vec::new();
+
+    if deployed.builder_version != local.builder_version {
+        hints.push(MismatchHint {
+            cause: "builder_version".to_string(),
+            message: builder_version_message(&deployed.builder_version, &local.builder_version),
+            rank: 60,
+        });
+    }
+    if deployed.target_name != local.target_name {
+        let describe = |target: &Option<String>| {
+            target
+                .clone()
+                .unwrap_or_else(|| "the default library".to_string())
+        };
+        hints.push(MismatchHint {
+            cause: "target".to_string(),
+            message: format!(
+                "the deployment was built from {}, the local build from {}",
+                describe(&deployed.target_name),
+                describe(&local.target_name)
+            ),
+            rank: 75,
+        });
+    }
+
+    hints.sort_by_key(|h| std::cmp::Reverse(h.rank));
+    hints
+}
+
+#[cfg(feature = "build")]
+fn builder_version_message(deployed: &str, local: &str) -> String {
+    format!(
+        "the deployment was built with fluent-builder {}, you are running {} (try `cargo install \
+         fluent-builder --version {}`)",
+        deployed, local, deployed
+    )
+}
+
+#[cfg(feature = "build")]
+/// First 12 characters of a hash, without the algorithm prefix
+fn short(hash: &str) -> &str {
+    let hash = hash.strip_prefix("sha256:").unwrap_or(hash);
+    &hash[..hash.len().min(12)]
+}
+
+#[cfg(all(test, feature = "build"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(sdk: &str, features: Value, docker: bool) -> Value {
+        json!({
+            "compilation_settings": {
+                "rust": {"version": "1.83.0", "target": "wasm32-unknown-unknown"},
+                "sdk": {"tag": sdk, "commit": "acbf4f6a26c2a441b101"},
+                "build_cfg": {
+                    "profile": "release",
+                    "features": features,
+                    "no_default_features": true,
+                    "locked": true
+                }
+            },
+            "dependencies": {"cargo_lock_hash": "sha256:aaaa"},
+            "source_tree_hash": "sha256:bbbb",
+            "docker": docker,
+            "builder_version": "0.1.0",
+        })
+    }
+
+    #[test]
+    fn test_identical_metadata_has_no_hints() {
+        let recorded = metadata("0.4.1", json!([]), true);
+        assert!(diagnose_mismatch(&recorded, &recorded).is_empty());
+    }
+
+    #[test]
+    fn test_hints_are_ranked() {
+        let recorded = metadata("0.4.1", json!([]), true);
+        let local = metadata("0.5.0", json!(["std"]), false);
+
+        let hints = diagnose_mismatch(&recorded, &local);
+        let causes: Vec<&str> = hints.iter().map(|h| h.cause.as_str()).collect();
+        assert_eq!(causes, vec!["sdk_version", "features", "docker"]);
+        assert_eq!(
+            hints[0].message,
+            "metadata says SDK 0.4.1 via git (acbf4f6a26c2), your lockfile has 0.5.0 via git \
+             (acbf4f6a26c2)"
+        );
+    }
+
+    #[test]
+    fn test_missing_fields_are_ignored() {
+        // Metadata written before the docker field existed
+        let mut recorded = metadata("0.4.1", json!([]), false);
+        recorded.as_object_mut().unwrap().remove("docker");
+        let local = metadata("0.4.1", json!([]), true);
+        assert!(diagnose_mismatch(&recorded, &local).is_empty());
+    }
+
+    #[test]
+    fn test_network_hint() {
+        let mut recorded = metadata("0.4.1", json!([]), true);
+        recorded["compilation_settings"]["build_cfg"]["network"] = json!("mainnet");
+        let mut local = metadata("0.4.1", json!([]), true);
+        assert_eq!(diagnose_mismatch(&recorded, &local)[0].cause, "network");
+
+        local["compilation_settings"]["build_cfg"]["network"] = json!("testnet");
+        let hints = diagnose_mismatch(&recorded, &local);
+        assert_eq!(
+            hints[0].message,
+            "metadata says the build was for network mainnet, local build was for testnet"
+        );
+
+        // Only the recorded side matters: builds for a network verify fine against older metadata
+        assert!(diagnose_mismatch(&local, &local).is_empty());
+        assert!(diagnose_mismatch(&metadata("0.4.1", json!([]), true), &local).is_empty());
+    }
+
+    #[test]
+    fn test_cargo_args_hint() {
+        let mut recorded = metadata("0.4.1", json!([]), true);
+        recorded["compilation_settings"]["build_cfg"]["extra_cargo_args"] = json!(["-Ztrim-paths"]);
+        let local = metadata("0.4.1", json!([]), true);
+
+        let hints = diagnose_mismatch(&recorded, &local);
+        assert_eq!(hints.len(), 1);
+        assert_eq!(
+            hints[0].message,
+            "metadata says extra cargo arguments `-Ztrim-paths`, local build used none (pass them \
+             after --)"
+        );
+        assert!(diagnose_mismatch(&recorded, &recorded).is_empty());
+
+        let mut local = recorded.clone();
+        local["compilation_settings"]["build_cfg"]["rustflags"] = json!("-C opt-level=s");
+        let hints = diagnose_mismatch(&recorded, &local);
+        assert_eq!(
+            hints[0].message,
+            "metadata says RUSTFLAGS none, local build used `-C opt-level=s` (pass them with \
+             --rustflags)"
+        );
+    }
+
+    #[test]
+    fn test_builder_version_hints() {
+        let recorded = metadata("0.4.1", json!([]), true);
+        let mut local = recorded.clone();
+        local["builder_version"] = json!("0.2.0");
+        let hints = diagnose_mismatch(&recorded, &local);
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0]
+            .message
+            .contains("fluent-builder 0.1.0, you are running 0.2.0"));
+
+        let deployed = Provenance {
+            builder_version: "0.1.0".to_string(),
+            locked: true,
+            target_name: None,
+        };
+        assert!(diagnose_provenance(&deployed, &deployed).is_empty());
+        let local = Provenance {
+            builder_version: "0.2.0".to_string(),
+            target_name: Some("token-lite".to_string()),
+            ..deployed.clone()
+        };
+        let causes: Vec<String> = diagnose_provenance(&deployed, &local)
+            .into_iter()
+            .map(|h| h.cause)
+            .collect();
+        assert_eq!(causes, vec!["target", "builder_version"]);
+    }
+}
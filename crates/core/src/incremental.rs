@@ -0,0 +1,144 @@
+//! ABI extraction for watch mode
+//!
+//! Sources are re-parsed only when their content changes, and a syntax error
+//! while the user is typing keeps the last ABI that could be extracted,
+//! marked stale, instead of failing.
+
+use crate::{
+    artifacts::{abi, sanitize::Sanitizer, Abi},
+    builder::find_main_source,
+    config::AbiNaming,
+    parser::{
+        parse_deploy_cached, parse_errors_cached, parse_routers_cached, Declarations, SourceCache,
+    },
+};
+use eyre::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// ABI of a project, kept up to date across edits
+pub struct AbiWatcher {
+    main_source: PathBuf,
+    naming: AbiNaming,
+    cache: SourceCache,
+    last_good: Option<AbiSnapshot>,
+}
+
+/// ABI as of the latest refresh
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AbiSnapshot {
+    pub abi: Abi,
+    /// Why the sources could not be parsed; `abi` is then the last good one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale: Option<String>,
+    /// Router functions sharing a selector, which `compile` rejects by default
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub selector_collisions: Vec<String>,
+}
+
+impl AbiWatcher {
+    /// Watch the main source of the project at `project_root`
+    pub fn new(project_root: &Path, naming: AbiNaming) -> Result<Self> {
+        Ok(Self {
+            main_source: find_main_source(project_root, &project_root.join("Cargo.toml"))?,
+            naming,
+            cache: SourceCache::default(),
+            last_good: None,
+        })
+    }
+
+    /// Extract the ABI from the current sources
+    ///
+    /// Fails only if the sources have never been parsed successfully.
+    pub fn refresh(&mut self) -> Result<AbiSnapshot> {
+        let parsed = parse_routers_cached(&self.main_source, &mut self.cache).and_then(|routers| {
+            let declarations = Declarations {
+                deploy: parse_deploy_cached(&self.main_source, &mut self.cache)?,
+                errors: parse_errors_cached(&self.main_source, &mut self.cache)?,
+            };
+            Ok((routers, declarations))
+        });
+        let error = match parsed {
+            Ok((routers, declarations)) => {
+                let mut abi =
+                    abi::apply_naming(&abi::generate(&routers, &declarations)?, self.naming);
+                Sanitizer::new(&abi).apply(&mut abi)?;
+                let snapshot = AbiSnapshot {
+                    abi,
+                    stale: None,
+                    selector_collisions: abi::router_collisions(&routers)?,
+                };
+                self.last_good = Some(snapshot.clone());
+                return Ok(snapshot);
+            }
+            Err(e) => e,
+        };
+        match &self.last_good {
+            Some(snapshot) => Ok(AbiSnapshot {
+                stale: Some(format!("{:#}", error)),
+                ..snapshot.clone()
+            }),
+            None => Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+        pub struct Token { supply: u64 }
+
+        impl Token {
+            pub fn total_supply(&self) -> u64 { self.supply }
+        }
+    "#;
+
+    #[test]
+    fn test_stale_abi_on_syntax_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let lib = dir.path().join("src/lib.rs");
+        std::fs::write(&lib, SOURCE).unwrap();
+
+        let mut watcher = AbiWatcher::new(dir.path(), AbiNaming::Preserve).unwrap();
+        let good = watcher.refresh().unwrap();
+        assert!(good.stale.is_none());
+        assert!(good.selector_collisions.is_empty());
+
+        // Unchanged sources are not parsed again
+        assert_eq!(watcher.refresh().unwrap(), good);
+        assert_eq!(watcher.cache.parses, 1);
+
+        std::fs::write(&lib, SOURCE.replace("self.supply }", "self.supply")).unwrap();
+        let stale = watcher.refresh().unwrap();
+        assert_eq!(stale.abi, good.abi);
+        assert!(stale.stale.unwrap().contains("Failed to parse Rust file"));
+
+        std::fs::write(&lib, SOURCE).unwrap();
+        // The broken version was never cached, so the restored file is not parsed again
+        assert_eq!(watcher.refresh().unwrap(), good);
+        assert_eq!(watcher.cache.parses, 2);
+    }
+
+    #[test]
+    fn test_error_without_previous_abi() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "fn broken(").unwrap();
+
+        let mut watcher = AbiWatcher::new(dir.path(), AbiNaming::Preserve).unwrap();
+        assert!(watcher.refresh().is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! SQLite store for verification jobs
+//!
+//! Lets the server report on jobs after a restart, resume the ones that were
+//! interrupted, and lets other processes (`fluent-builder job status`) read
+//! job state while the server is running. Verified results are cached by
+//! bytecode, sources and build settings until the builder version changes.
+
+use crate::api::{Job, JobOutcome, JobStatus};
+use eyre::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Mutex};
+
+/// Outcome of a verified job, reused for identical requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CachedResult {
+    /// Job that built and verified the contract
+    pub job_id: String,
+    /// Unix timestamp in seconds
+    pub verified_at: u64,
+    pub outcome: JobOutcome,
+}
+
+/// Persistent job records
+pub struct JobStore {
+    conn: Mutex<Connection>,
+}
+
+impl JobStore {
+    /// File name of the store inside the server work directory
+    pub const FILE_NAME: &'static str = "jobs.sqlite";
+
+    /// Open or create a store
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open job store {}", path.display()))?;
+
+        // WAL lets readers in other processes query while the server writes
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                job TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS jobs_status ON jobs (status);
+            CREATE TABLE IF NOT EXISTS results (
+                key TEXT PRIMARY KEY,
+                bytecode_hash TEXT NOT NULL,
+                builder_version TEXT NOT NULL,
+                result TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS results_bytecode_hash ON results (bytecode_hash);",
+        )
+        .context("Failed to initialize job store")?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Insert or update a job
+    pub fn save(&self, job: &Job) -> Result<()> {
+        let json = serde_json::to_string(job)?;
+        self.conn()
+            .execute(
+                "INSERT INTO jobs (id, status, created_at, job) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (id) DO UPDATE SET status = excluded.status, job = excluded.job",
+                params![job.id, status_name(job.status), job.created_at as i64, json],
+            )
+            .with_context(|| format!("Failed to save job {}", job.id))?;
+        Ok(())
+    }
+
+    /// Remove a job
+    pub fn delete(&self, id: &str) -> Result<()> {
+        self.conn()
+            .execute("DELETE FROM jobs WHERE id = ?1", params![id])
+            .with_context(|| format!("Failed to delete job {}", id))?;
+        Ok(())
+    }
+
+    /// Load a job by id
+    pub fn load(&self, id: &str) -> Result<Option<Job>> {
+        let json: Option<String> = self
+            .conn()
+            .query_row("SELECT job FROM jobs WHERE id = ?1", params![id], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        json.map(|json| {
+            serde_json::from_str(&json).with_context(|| format!("Corrupt job record {}", id))
+        })
+        .transpose()
+    }
+
+    /// Jobs that were queued or running, oldest first
+    pub fn unfinished(&self) -> Result<Vec<Job>> {
+        self.query(
+            "SELECT job FROM jobs WHERE status IN ('queued', 'running') ORDER BY created_at, id",
+        )
+    }
+
+    /// All jobs, oldest first
+    pub fn jobs(&self) -> Result<Vec<Job>> {
+        self.query("SELECT job FROM jobs ORDER BY created_at, id")
+    }
+
+    /// Cached result of this builder version for a cache key
+    pub fn cached_result(&self, key: &str) -> Result<Option<CachedResult>> {
+        let json: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT result FROM results WHERE key = ?1 AND builder_version = ?2",
+                params![key, crate::VERSION],
+                |row| row.get(0),
+            )
+            .optional()?;
+        json.map(|json| {
+            serde_json::from_str(&json).with_context(|| format!("Corrupt cached result {}", key))
+        })
+        .transpose()
+    }
+
+    /// Cache a verified result under `key`
+    pub fn cache_result(
+        &self,
+        key: &str,
+        bytecode_hash: &str,
+        result: &CachedResult,
+    ) -> Result<()> {
+        self.conn()
+            .execute(
+                "INSERT OR REPLACE INTO results (key, bytecode_hash, builder_version, result) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    key,
+                    bytecode_hash,
+                    crate::VERSION,
+                    serde_json::to_string(result)?
+                ],
+            )
+            .with_context(|| format!("Failed to cache result of job {}", result.job_id))?;
+        Ok(())
+    }
+
+    /// Remove cached results, all or those of one bytecode hash; returns how many were removed
+    pub fn purge_results(&self, bytecode_hash: Option<&str>) -> Result<usize> {
+        let conn = self.conn();
+        let removed = match bytecode_hash {
+            Some(hash) => conn.execute(
+                "DELETE FROM results WHERE bytecode_hash = ?1",
+                params![hash],
+            ),
+            None => conn.execute("DELETE FROM results", []),
+        };
+        removed.context("Failed to purge cached results")
+    }
+
+    /// Remove results cached by other builder versions, which may build differently
+    pub fn purge_stale_results(&self) -> Result<usize> {
+        self.conn()
+            .execute(
+                "DELETE FROM results WHERE builder_version != ?1",
+                params![crate::VERSION],
+            )
+            .context("Failed to purge cached results")
+    }
+
+    fn query(&self, sql: &str) -> Result<Vec<Job>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(sql)?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        rows.map(|json| Ok(serde_json::from_str(&json?)?)).collect()
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn status_name(status: JobStatus) -> &'static str {
+    match status {
+        JobStatus::Queued => "queued",
+        JobStatus::Running => "running",
+        JobStatus::Succeeded => "succeeded",
+        JobStatus::Failed => "failed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn job(id: &str, status: JobStatus) -> Job {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "request": {"project_root": "token", "deployed_bytecode_hash": "0xabc"},
+            "status": status,
+            "created_at": 1,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(JobStore::FILE_NAME);
+        let store = JobStore::open(&path).unwrap();
+
+        store.save(&job("a", JobStatus::Succeeded)).unwrap();
+        store.save(&job("b", JobStatus::Queued)).unwrap();
+        let mut running = job("b", JobStatus::Running);
+        running.logs.push("building".to_string());
+        store.save(&running).unwrap();
+
+        // A second connection sees the same state
+        let reader = JobStore::open(&path).unwrap();
+        assert_eq!(reader.load("b").unwrap(), Some(running.clone()));
+        assert_eq!(reader.load("missing").unwrap(), None);
+        assert_eq!(reader.unfinished().unwrap(), vec![running]);
+        assert_eq!(reader.jobs().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_result_cache() {
+        let dir = TempDir::new().unwrap();
+        let store = JobStore::open(&dir.path().join(JobStore::FILE_NAME)).unwrap();
+        let result: CachedResult = serde_json::from_value(serde_json::json!({
+            "job_id": "a",
+            "verified_at": 1,
+            "outcome": {
+                "verified": true,
+                "contract_name": "token",
+                "expected_hash": "0xabc",
+                "actual_hash": "0xabc"
+            },
+        }))
+        .unwrap();
+
+        store.cache_result("k1", "abc", &result).unwrap();
+        store.cache_result("k2", "def", &result).unwrap();
+        assert_eq!(store.cached_result("k1").unwrap(), Some(result.clone()));
+        assert_eq!(store.cached_result("missing").unwrap(), None);
+
+        assert_eq!(store.purge_results(Some("abc")).unwrap(), 1);
+        assert_eq!(store.cached_result("k1").unwrap(), None);
+
+        // Results of another builder version are never served and purged on request
+        store
+            .conn()
+            .execute("UPDATE results SET builder_version = '0.0.1'", [])
+            .unwrap();
+        assert_eq!(store.cached_result("k2").unwrap(), None);
+        assert_eq!(store.purge_stale_results().unwrap(), 1);
+        assert_eq!(store.purge_results(None).unwrap(), 0);
+    }
+}
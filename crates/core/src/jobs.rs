@@ -0,0 +1,969 @@
+//! Queue and worker pool for verification jobs
+//!
+//! Used by the verification server: requests are queued, run by a fixed
+//! number of workers and can be queried by id while they run. Every job gets
+//! its own directories and container name, so concurrent jobs never share a
+//! cargo target dir or outputs. With a [`JobStore`], job state survives
+//! restarts, interrupted jobs are queued again and verified results are
+//! reused for identical requests instead of rebuilding.
+
+use crate::{
+    api::{Job, JobOutcome, JobStatus, VerifyRequest},
+    job_store::CachedResult,
+    verify, CompileConfig, ConstructorArgs, HashAlgorithm, JobStore, Sandbox, VerificationStatus,
+    VerifyConfig,
+};
+use eyre::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static NEXT_JOB: AtomicU64 = AtomicU64::new(0);
+
+/// Log lines kept per job
+const MAX_LOG_LINES: usize = 200;
+
+/// Errors from submitting a job
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    /// All workers are busy and the queue is at capacity
+    #[error("Job queue is full ({capacity} jobs waiting)")]
+    QueueFull { capacity: usize },
+    /// The pool no longer accepts jobs
+    #[error("Worker pool is shutting down")]
+    ShutDown,
+    /// The request asks for something this server does not allow
+    #[error("Rejected: {reason}")]
+    Rejected { reason: String },
+}
+
+/// Worker pool settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolConfig {
+    /// Jobs running at the same time
+    pub workers: usize,
+    /// Jobs waiting for a worker before submissions are rejected
+    pub queue_capacity: usize,
+    /// Parent of the per-job directories
+    pub work_dir: PathBuf,
+    /// Job store file; jobs are only kept in memory without one
+    pub store: Option<PathBuf>,
+    /// Keep the directories of failed jobs for debugging
+    pub keep_failed: bool,
+    /// Jobs are built in a sandbox container, so build flags chosen by the client are allowed
+    pub sandboxed: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            workers: 2,
+            queue_capacity: 64,
+            work_dir: std::env::temp_dir().join("fluent-builder-jobs"),
+            store: None,
+            keep_failed: false,
+            sandboxed: false,
+        }
+    }
+}
+
+/// Runs a job in its sandbox, reporting progress through the log
+pub type JobExecutor =
+    dyn Fn(&VerifyRequest, &Sandbox, &JobLog) -> Result<JobOutcome> + Send + Sync;
+
+/// Jobs known to the pool, mirrored to the store
+struct JobTable {
+    jobs: Mutex<BTreeMap<String, Job>>,
+    store: Option<JobStore>,
+}
+
+impl JobTable {
+    fn get(&self, id: &str) -> Option<Job> {
+        if let Some(job) = lock(&self.jobs).get(id) {
+            return Some(job.clone());
+        }
+        // Finished before a restart
+        let store = self.store.as_ref()?;
+        store.load(id).unwrap_or_else(|e| {
+            tracing::warn!("{:#}", e);
+            None
+        })
+    }
+
+    fn insert(&self, job: Job) {
+        self.persist(&job);
+        lock(&self.jobs).insert(job.id.clone(), job);
+    }
+
+    fn remove(&self, id: &str) {
+        lock(&self.jobs).remove(id);
+        if let Some(store) = &self.store {
+            if let Err(e) = store.delete(id) {
+                tracing::warn!("{:#}", e);
+            }
+        }
+    }
+
+    /// Apply a change to a job and persist it; returns the updated job
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job)) -> Option<Job> {
+        let mut jobs = lock(&self.jobs);
+        let job = jobs.get_mut(id)?;
+        change(job);
+        let job = job.clone();
+        // Saved under the lock so readers always find the job in one of the two
+        self.persist(&job);
+        // Finished jobs are served from the store
+        if job.status.is_finished() && self.store.is_some() {
+            jobs.remove(id);
+        }
+        Some(job)
+    }
+
+    /// Hash of a request's sources, if results are cached
+    fn source_hash(&self, request: &VerifyRequest) -> Option<String> {
+        self.store.as_ref()?;
+        hash_tree(&request.project_root)
+            .map_err(|e| {
+                tracing::warn!(
+                    "Not caching the result of {}: {:#}",
+                    request.project_root.display(),
+                    e
+                )
+            })
+            .ok()
+    }
+
+    fn cached_result(&self, key: &str) -> Option<CachedResult> {
+        self.store.as_ref()?.cached_result(key).unwrap_or_else(|e| {
+            tracing::warn!("{:#}", e);
+            None
+        })
+    }
+
+    fn cache_result(&self, key: &str, result: &CachedResult) {
+        if let Some(store) = &self.store {
+            if let Err(e) =
+                store.cache_result(key, &normalize_hash(&result.outcome.expected_hash), result)
+            {
+                tracing::warn!("{:#}", e);
+            }
+        }
+    }
+
+    fn persist(&self, job: &Job) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save(job) {
+                tracing::warn!("{:#}", e);
+            }
+        }
+    }
+}
+
+/// Progress reporting for a running job
+pub struct JobLog {
+    id: String,
+    table: Arc<JobTable>,
+    /// Hash of the job's sources, taken before it runs
+    source_hash: Option<String>,
+}
+
+impl JobLog {
+    /// Record what the job is doing now
+    pub fn stage(&self, stage: &str) {
+        tracing::info!("Job {}: {}", self.id, stage);
+        self.table.update(&self.id, |job| {
+            job.stage = Some(stage.to_string());
+            push_log(job, stage.to_string());
+        });
+    }
+
+    /// Append a progress message
+    pub fn log(&self, line: impl Into<String>) {
+        self.table
+            .update(&self.id, |job| push_log(job, line.into()));
+    }
+
+    /// Cached outcome of an identical verification, for executors that resolve the bytecode hash
+    /// themselves
+    ///
+    /// Marks the job as served from the cache.
+    pub fn cached(&self, request: &VerifyRequest) -> Option<JobOutcome> {
+        let bytecode_hash = request.deployed_bytecode_hash.as_deref()?;
+        let source_hash = self.source_hash.as_deref()?;
+        let cached = self
+            .table
+            .cached_result(&cache_key(request, bytecode_hash, source_hash))?;
+        self.table.update(&self.id, |job| {
+            push_log(
+                job,
+                format!("Verified by job {}; reusing its result", cached.job_id),
+            );
+            job.cached_from = Some(cached.job_id.clone());
+        });
+        Some(cached.outcome)
+    }
+}
+
+fn push_log(job: &mut Job, line: String) {
+    job.logs.push(line);
+    if job.logs.len() > MAX_LOG_LINES {
+        job.logs.remove(0);
+    }
+}
+
+/// Bounded pool of verification workers
+pub struct WorkerPool {
+    sender: Option<SyncSender<String>>,
+    table: Arc<JobTable>,
+    workers: Vec<JoinHandle<()>>,
+    config: PoolConfig,
+}
+
+impl WorkerPool {
+    /// Start `config.workers` workers running jobs with `executor`
+    pub fn new(config: PoolConfig, executor: Arc<JobExecutor>) -> Result<Self> {
+        std::fs::create_dir_all(&config.work_dir)
+            .with_context(|| format!("Failed to create {}", config.work_dir.display()))?;
+
+        let store = config.store.as_deref().map(JobStore::open).transpose()?;
+        if let Some(store) = &store {
+            // Another builder version may build the same sources differently
+            let stale = store.purge_stale_results()?;
+            if stale > 0 {
+                tracing::info!("Dropped {} results cached by other builder versions", stale);
+            }
+        }
+        let unfinished = match &store {
+            Some(store) => store.unfinished()?,
+            None => Vec::new(),
+        };
+        let table = Arc::new(JobTable {
+            jobs: Mutex::default(),
+            store,
+        });
+
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        // Jobs interrupted by a restart run again from the start
+        for mut job in unfinished {
+            job.status = JobStatus::Queued;
+            job.started_at = None;
+            job.stage = None;
+            push_log(&mut job, "Resumed after restart".to_string());
+            let id = job.id.clone();
+            table.insert(job);
+            if sender.try_send(id.clone()).is_err() {
+                table.update(&id, |job| {
+                    job.status = JobStatus::Failed;
+                    job.finished_at = Some(now());
+                    job.error = Some("Job queue was full when resuming after restart".to_string());
+                });
+            }
+        }
+
+        let workers = (0..config.workers.max(1))
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let table = Arc::clone(&table);
+                let executor = Arc::clone(&executor);
+                let work_dir = config.work_dir.clone();
+                let keep_failed = config.keep_failed;
+                std::thread::spawn(move || {
+                    worker_loop(&receiver, &table, &*executor, &work_dir, keep_failed)
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            sender: Some(sender),
+            table,
+            workers,
+            config,
+        })
+    }
+
+    /// Queue a job, returning its id
+    ///
+    /// A request with the bytecode hash, sources and settings of an earlier
+    /// verified job is answered by a worker with that job's result, without
+    /// building. Sources are hashed by the worker, not the caller.
+    pub fn submit(&self, mut request: VerifyRequest) -> Result<String, JobError> {
+        let sender = self.sender.as_ref().ok_or(JobError::ShutDown)?;
+        request.project_root = self.admit(&request)?;
+        let id = new_job_id();
+        let job = Job {
+            id: id.clone(),
+            request,
+            status: JobStatus::Queued,
+            created_at: now(),
+            started_at: None,
+            finished_at: None,
+            stage: None,
+            logs: Vec::new(),
+            outcome: None,
+            error: None,
+            cached_from: None,
+        };
+        // Recorded before it can be picked up by a worker
+        self.table.insert(job);
+
+        match sender.try_send(id.clone()) {
+            Ok(()) => Ok(id),
+            Err(e) => {
+                self.table.remove(&id);
+                Err(match e {
+                    TrySendError::Full(_) => JobError::QueueFull {
+                        capacity: self.config.queue_capacity,
+                    },
+                    TrySendError::Disconnected(_) => JobError::ShutDown,
+                })
+            }
+        }
+    }
+
+    /// Check that `request` is allowed, returning its sources directory
+    ///
+    /// Client-chosen cargo arguments and RUSTFLAGS can run arbitrary commands,
+    /// so they need a sandbox. Sources are confined to the work directory.
+    fn admit(&self, request: &VerifyRequest) -> Result<PathBuf, JobError> {
+        let rejected = |reason: String| JobError::Rejected { reason };
+        if !self.config.sandboxed
+            && (!request.extra_cargo_args.is_empty() || request.rustflags.is_some())
+        {
+            return Err(rejected(
+                "extra_cargo_args and rustflags are only accepted by sandboxed servers".to_string(),
+            ));
+        }
+
+        let relative = &request.project_root;
+        if relative.as_os_str().is_empty()
+            || !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(rejected(format!(
+                "project_root must be a path inside the server's work directory, got {}",
+                relative.display()
+            )));
+        }
+        let project_root = self.config.work_dir.join(relative);
+        // Symlinks must not lead out of the work directory either
+        if let (Ok(work_dir), Ok(resolved)) = (
+            self.config.work_dir.canonicalize(),
+            project_root.canonicalize(),
+        ) {
+            if !resolved.starts_with(&work_dir) {
+                return Err(rejected(format!(
+                    "project_root {} leaves the work directory",
+                    relative.display()
+                )));
+            }
+        }
+        Ok(project_root)
+    }
+
+    /// Current state of a job
+    pub fn job(&self, id: &str) -> Option<Job> {
+        self.table.get(id)
+    }
+
+    /// Remove cached results, all or those of one bytecode hash; returns how many were removed
+    pub fn purge_cache(&self, bytecode_hash: Option<&str>) -> Result<usize> {
+        match &self.table.store {
+            Some(store) => store.purge_results(bytecode_hash.map(normalize_hash).as_deref()),
+            None => Ok(0),
+        }
+    }
+
+    /// Number of jobs waiting for a worker
+    pub fn queued(&self) -> usize {
+        lock(&self.table.jobs)
+            .values()
+            .filter(|job| job.status == JobStatus::Queued)
+            .count()
+    }
+
+    /// Stop accepting jobs and wait for queued and running jobs to finish
+    pub fn shutdown(mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker_loop(
+    receiver: &Mutex<Receiver<String>>,
+    table: &Arc<JobTable>,
+    executor: &JobExecutor,
+    work_dir: &Path,
+    keep_failed: bool,
+) {
+    loop {
+        // The lock is only held while waiting, not while the job runs
+        let Ok(id) = lock(receiver).recv() else {
+            return;
+        };
+        let Some(job) = table.update(&id, |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(now());
+        }) else {
+            continue;
+        };
+        let log = JobLog {
+            id: id.clone(),
+            table: Arc::clone(table),
+            // Hashed before building, so the cached result describes the sources that were built
+            source_hash: table.source_hash(&job.request),
+        };
+
+        let result = match log.cached(&job.request) {
+            Some(outcome) => Ok(outcome),
+            None => Sandbox::create(work_dir, &id).and_then(|sandbox| {
+                let sandbox = sandbox.keep_on_failure(keep_failed);
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    executor(&job.request, &sandbox, &log)
+                }))
+                .unwrap_or_else(|_| Err(eyre::eyre!("Job panicked")));
+                sandbox.finish(result.is_ok());
+                result
+            }),
+        };
+
+        if let (Ok(outcome), Some(source_hash)) = (&result, &log.source_hash) {
+            let reused = table.get(&id).is_some_and(|job| job.cached_from.is_some());
+            if outcome.verified && !reused {
+                let key = cache_key(&job.request, &outcome.expected_hash, source_hash);
+                table.cache_result(
+                    &key,
+                    &CachedResult {
+                        job_id: id.clone(),
+                        verified_at: now(),
+                        outcome: outcome.clone(),
+                    },
+                );
+            }
+        }
+
+        table.update(&id, |job| {
+            job.finished_at = Some(now());
+            job.stage = None;
+            match result {
+                Ok(outcome) => {
+                    job.status = JobStatus::Succeeded;
+                    job.outcome = Some(outcome);
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(format!("{:#}", e));
+                }
+            }
+        });
+    }
+}
+
+/// Verify a request with a known bytecode hash inside its sandbox
+pub fn run_verify_job(
+    request: &VerifyRequest,
+    sandbox: &Sandbox,
+    log: &JobLog,
+) -> Result<JobOutcome> {
+    let deployed_hash = request
+        .deployed_bytecode_hash
+        .clone()
+        .ok_or_else(|| eyre::eyre!("deployed_bytecode_hash is required"))?;
+
+    let mut compile_config = CompileConfig::new(&request.project_root);
+    compile_config.profile = request.profile.clone();
+    compile_config.features = request.features.clone();
+    compile_config.no_default_features = request.no_default_features;
+    compile_config.target_name = request.target_name.clone();
+    compile_config.extra_cargo_args = request.extra_cargo_args.clone();
+    compile_config.rustflags = request.rustflags.clone();
+    compile_config.use_git_source = false;
+    sandbox.configure(&mut compile_config);
+
+    let constructor_args = request
+        .constructor_args
+        .as_deref()
+        .map(|args| {
+            hex::decode(args.trim_start_matches("0x")).context("constructor_args is not valid hex")
+        })
+        .transpose()?
+        .map(ConstructorArgs::Encoded);
+
+    log.stage("building");
+    let result = verify(VerifyConfig {
+        source: request.project_root.clone().into(),
+        deployed_bytecode_hash: deployed_hash.clone(),
+        hash_algorithm: request.hash_algorithm,
+        compile_config: Some(compile_config),
+        recorded_metadata: None,
+        deployed_provenance: None,
+        deployed_unwrapping: None,
+        constructor_args,
+        deployment_data: None,
+    })?;
+
+    let actual_hash = match &result.status {
+        VerificationStatus::CompilationFailed(error) => {
+            return Err(eyre::eyre!("Compilation failed: {}", error))
+        }
+        VerificationStatus::Mismatch { actual, .. } => actual.clone(),
+        _ => result
+            .compilation_result
+            .as_ref()
+            .map(|r| result.hash_algorithm.hash(&r.outputs.rwasm))
+            .unwrap_or_default(),
+    };
+
+    Ok(JobOutcome {
+        verified: result.status.is_success(),
+        contract_name: result.contract_name,
+        expected_hash: deployed_hash,
+        actual_hash,
+        hints: result.hints,
+        constructor_args: result.constructor_args,
+    })
+}
+
+/// Cache key of a request: the bytecode and sources it verifies and every setting that changes the
+/// build
+fn cache_key(request: &VerifyRequest, bytecode_hash: &str, source_hash: &str) -> String {
+    let (tagged, _) = HashAlgorithm::split_tagged(bytecode_hash);
+    let key = serde_json::json!([
+        tagged.unwrap_or(request.hash_algorithm),
+        normalize_hash(bytecode_hash),
+        source_hash,
+        request.profile,
+        request.features,
+        request.no_default_features,
+        request.target_name,
+        request.constructor_args.as_deref().map(normalize_hash),
+        request.extra_cargo_args,
+        request.rustflags,
+    ]);
+    HashAlgorithm::Sha256.hash(key.to_string().as_bytes())
+}
+
+/// Hash of every file under `root` by relative path and content, in path order
+///
+/// Covers hidden files such as `.cargo/config.toml` and data read by
+/// `include_bytes!`, which change the build too. Only the `target` and `.git`
+/// directories at the top are left out.
+fn hash_tree(root: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    let entries = walkdir::WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            let relative = e.path().strip_prefix(root).unwrap_or(e.path());
+            !(e.depth() == 1 && (relative == Path::new("target") || relative == Path::new(".git")))
+        });
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read {}", root.display()))?;
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let content = if entry.file_type().is_symlink() {
+            std::fs::read_link(entry.path())?
+                .to_string_lossy()
+                .into_owned()
+                .into_bytes()
+        } else if entry.file_type().is_file() {
+            std::fs::read(entry.path())
+                .with_context(|| format!("Failed to read {}", entry.path().display()))?
+        } else {
+            continue;
+        };
+        hasher.update(crate::to_slash(relative).as_bytes());
+        hasher.update([u8::from(entry.file_type().is_symlink())]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Hex digits of a possibly tagged or 0x-prefixed hash, lowercase
+fn normalize_hash(hash: &str) -> String {
+    HashAlgorithm::split_tagged(hash)
+        .1
+        .trim_start_matches("0x")
+        .to_lowercase()
+}
+
+/// Unique, sortable job id
+fn new_job_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "{:x}-{:x}-{}",
+        nanos,
+        std::process::id(),
+        NEXT_JOB.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    fn request(name: &str) -> VerifyRequest {
+        serde_json::from_value(serde_json::json!({
+            "project_root": name,
+            "deployed_bytecode_hash": "0xabc",
+        }))
+        .unwrap()
+    }
+
+    fn wait_for(pool: &WorkerPool, id: &str, status: JobStatus) -> Job {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let job = pool.job(id).unwrap();
+            if job.status == status {
+                return job;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "job {} stuck in {:?}",
+                id,
+                job.status
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_jobs_run_in_isolated_sandboxes() {
+        let dir = TempDir::new().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let executor = {
+            let seen = Arc::clone(&seen);
+            Arc::new(
+                move |request: &VerifyRequest, sandbox: &Sandbox, _: &JobLog| {
+                    assert!(sandbox.target_dir.is_dir());
+                    lock(&seen).push(sandbox.clone());
+                    if request.project_root.ends_with("broken") {
+                        return Err(eyre::eyre!("no Cargo.toml"));
+                    }
+                    Ok(JobOutcome {
+                        verified: true,
+                        contract_name: request
+                            .project_root
+                            .file_name()
+                            .unwrap()
+                            .to_string_lossy()
+                            .to_string(),
+                        expected_hash: "0xabc".to_string(),
+                        actual_hash: "0xabc".to_string(),
+                        hints: Vec::new(),
+                        constructor_args: None,
+                    })
+                },
+            )
+        };
+        let pool = WorkerPool::new(
+            PoolConfig {
+                workers: 2,
+                queue_capacity: 8,
+                work_dir: dir.path().to_path_buf(),
+                store: None,
+                keep_failed: false,
+                sandboxed: false,
+            },
+            executor,
+        )
+        .unwrap();
+
+        let ids: Vec<String> = ["a", "b", "broken"]
+            .iter()
+            .map(|name| pool.submit(request(name)).unwrap())
+            .collect();
+
+        let job = wait_for(&pool, &ids[0], JobStatus::Succeeded);
+        assert_eq!(job.outcome.unwrap().contract_name, "a");
+        wait_for(&pool, &ids[1], JobStatus::Succeeded);
+        let failed = wait_for(&pool, &ids[2], JobStatus::Failed);
+        assert_eq!(failed.error.as_deref(), Some("no Cargo.toml"));
+        assert!(pool.job("unknown").is_none());
+        pool.shutdown();
+
+        let seen = lock(&seen);
+        let mut containers: Vec<&str> = seen.iter().map(|w| w.container_name.as_str()).collect();
+        containers.sort();
+        containers.dedup();
+        assert_eq!(containers.len(), 3);
+        // Sandboxes are removed once the job finishes
+        assert!(seen.iter().all(|w| !w.root.exists()));
+    }
+
+    #[test]
+    fn test_queue_is_bounded() {
+        let dir = TempDir::new().unwrap();
+        let (release, wait) = mpsc::channel::<()>();
+        let wait = Mutex::new(wait);
+        let executor = Arc::new(move |_: &VerifyRequest, _: &Sandbox, _: &JobLog| {
+            let _ = lock(&wait).recv();
+            Err(eyre::eyre!("cancelled"))
+        });
+        let pool = WorkerPool::new(
+            PoolConfig {
+                workers: 1,
+                queue_capacity: 1,
+                work_dir: dir.path().to_path_buf(),
+                store: None,
+                keep_failed: false,
+                sandboxed: false,
+            },
+            executor,
+        )
+        .unwrap();
+
+        let running = pool.submit(request("a")).unwrap();
+        wait_for(&pool, &running, JobStatus::Running);
+        let queued = pool.submit(request("b")).unwrap();
+        assert_eq!(pool.queued(), 1);
+        assert!(matches!(
+            pool.submit(request("c")),
+            Err(JobError::QueueFull { capacity: 1 })
+        ));
+
+        drop(release);
+        wait_for(&pool, &queued, JobStatus::Failed);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn test_requests_are_confined() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("work/token")).unwrap();
+        let executor =
+            Arc::new(|_: &VerifyRequest, _: &Sandbox, _: &JobLog| Err(eyre::eyre!("not run")));
+        let config = PoolConfig {
+            work_dir: dir.path().join("work"),
+            ..Default::default()
+        };
+        let pool = WorkerPool::new(config.clone(), executor.clone()).unwrap();
+
+        let id = pool.submit(request("token")).unwrap();
+        assert_eq!(
+            pool.job(&id).unwrap().request.project_root,
+            dir.path().join("work/token")
+        );
+        for outside in ["/etc", "../token", "token/../..", ""] {
+            assert!(
+                matches!(
+                    pool.submit(request(outside)),
+                    Err(JobError::Rejected { .. })
+                ),
+                "{}",
+                outside
+            );
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), dir.path().join("work/escape")).unwrap();
+            assert!(matches!(
+                pool.submit(request("escape")),
+                Err(JobError::Rejected { .. })
+            ));
+        }
+
+        // Build flags need a sandbox
+        let mut flags = request("token");
+        flags.extra_cargo_args = vec!["--config=build.rustc-wrapper='/bin/sh'".to_string()];
+        assert!(matches!(
+            pool.submit(flags.clone()),
+            Err(JobError::Rejected { .. })
+        ));
+        let mut rustflags = request("token");
+        rustflags.rustflags = Some("-C linker=/bin/sh".to_string());
+        assert!(matches!(
+            pool.submit(rustflags.clone()),
+            Err(JobError::Rejected { .. })
+        ));
+        pool.shutdown();
+        let sandboxed = WorkerPool::new(
+            PoolConfig {
+                sandboxed: true,
+                ..config
+            },
+            executor,
+        )
+        .unwrap();
+        assert!(sandboxed.submit(flags).is_ok());
+        assert!(sandboxed.submit(rustflags).is_ok());
+        sandboxed.shutdown();
+    }
+
+    #[test]
+    fn test_jobs_survive_restart() {
+        let dir = TempDir::new().unwrap();
+        let config = PoolConfig {
+            workers: 1,
+            queue_capacity: 4,
+            work_dir: dir.path().to_path_buf(),
+            store: Some(dir.path().join(JobStore::FILE_NAME)),
+            keep_failed: false,
+            sandboxed: false,
+        };
+
+        // A job left running by a previous server process
+        let mut interrupted: Job = serde_json::from_value(serde_json::json!({
+            "id": "interrupted",
+            "request": request("a"),
+            "status": "running",
+            "created_at": 1,
+            "stage": "building",
+        }))
+        .unwrap();
+        JobStore::open(config.store.as_ref().unwrap())
+            .unwrap()
+            .save(&interrupted)
+            .unwrap();
+
+        let executor = Arc::new(|request: &VerifyRequest, _: &Sandbox, log: &JobLog| {
+            log.stage("building");
+            log.log("compiled");
+            Ok(JobOutcome {
+                verified: false,
+                contract_name: request.project_root.display().to_string(),
+                expected_hash: "0xabc".to_string(),
+                actual_hash: "0xdef".to_string(),
+                hints: Vec::new(),
+                constructor_args: None,
+            })
+        });
+        let pool = WorkerPool::new(config.clone(), executor.clone()).unwrap();
+        let resumed = wait_for(&pool, "interrupted", JobStatus::Succeeded);
+        assert_eq!(
+            resumed.logs,
+            vec!["Resumed after restart", "building", "compiled"]
+        );
+        assert_eq!(resumed.stage, None);
+        let id = pool.submit(request("b")).unwrap();
+        wait_for(&pool, &id, JobStatus::Succeeded);
+        pool.shutdown();
+
+        // Finished jobs are reported after the next restart
+        let pool = WorkerPool::new(config, executor).unwrap();
+        interrupted = pool.job("interrupted").unwrap();
+        assert!(!interrupted.outcome.unwrap().verified);
+        assert_eq!(pool.job(&id).unwrap().status, JobStatus::Succeeded);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn test_verified_results_are_cached() {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path().join("work/token");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::write(project.join("Cargo.toml"), "[package]\nname = \"token\"\n").unwrap();
+        std::fs::write(project.join("src/lib.rs"), "").unwrap();
+
+        let builds = Arc::new(AtomicU64::new(0));
+        let executor = {
+            let builds = Arc::clone(&builds);
+            Arc::new(move |request: &VerifyRequest, _: &Sandbox, _: &JobLog| {
+                builds.fetch_add(1, Ordering::SeqCst);
+                Ok(JobOutcome {
+                    verified: true,
+                    contract_name: "token".to_string(),
+                    expected_hash: request.deployed_bytecode_hash.clone().unwrap(),
+                    actual_hash: "0xabc".to_string(),
+                    hints: Vec::new(),
+                    constructor_args: None,
+                })
+            })
+        };
+        let pool = WorkerPool::new(
+            PoolConfig {
+                workers: 1,
+                queue_capacity: 4,
+                work_dir: dir.path().join("work"),
+                store: Some(dir.path().join(JobStore::FILE_NAME)),
+                keep_failed: false,
+                sandboxed: false,
+            },
+            executor,
+        )
+        .unwrap();
+        let request = request("token");
+
+        let first = pool.submit(request.clone()).unwrap();
+        wait_for(&pool, &first, JobStatus::Succeeded);
+        // Served from the cache without building, whatever the hash's spelling
+        let mut uppercase = request.clone();
+        uppercase.deployed_bytecode_hash = Some("0xABC".to_string());
+        let cached = wait_for(
+            &pool,
+            &pool.submit(uppercase).unwrap(),
+            JobStatus::Succeeded,
+        );
+        assert_eq!(cached.cached_from.as_deref(), Some(first.as_str()));
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+
+        // Other settings or sources build again
+        let mut profile = request.clone();
+        profile.profile = "dev".to_string();
+        wait_for(&pool, &pool.submit(profile).unwrap(), JobStatus::Succeeded);
+        std::fs::write(project.join("src/lib.rs"), "// changed").unwrap();
+        wait_for(
+            &pool,
+            &pool.submit(request.clone()).unwrap(),
+            JobStatus::Succeeded,
+        );
+        assert_eq!(builds.load(Ordering::SeqCst), 3);
+
+        // A tree with the same file contents under other paths, then with a cargo config
+        let moved = dir.path().join("work/moved");
+        std::fs::create_dir_all(moved.join("src")).unwrap();
+        std::fs::write(moved.join("Cargo.toml"), "[package]\nname = \"token\"\n").unwrap();
+        std::fs::write(moved.join("src/token.rs"), "// changed").unwrap();
+        let moved_request = VerifyRequest {
+            project_root: "moved".into(),
+            ..request.clone()
+        };
+        wait_for(
+            &pool,
+            &pool.submit(moved_request.clone()).unwrap(),
+            JobStatus::Succeeded,
+        );
+        std::fs::create_dir_all(moved.join(".cargo")).unwrap();
+        std::fs::write(moved.join(".cargo/config.toml"), "[build]\n").unwrap();
+        wait_for(
+            &pool,
+            &pool.submit(moved_request).unwrap(),
+            JobStatus::Succeeded,
+        );
+        assert_eq!(builds.load(Ordering::SeqCst), 5);
+
+        assert_eq!(pool.purge_cache(Some("0xAbC")).unwrap(), 5);
+        wait_for(&pool, &pool.submit(request).unwrap(), JobStatus::Succeeded);
+        assert_eq!(builds.load(Ordering::SeqCst), 6);
+        pool.shutdown();
+    }
+}
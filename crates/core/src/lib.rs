@@ -2,33 +2,381 @@
 //!
 //! This library provides tools for compiling Rust smart contracts to WASM/rWASM,
 //! generating Solidity-compatible interfaces, and verifying deployed contracts.
+//!
+//! # Features
+//!
+//! - `abi`: parsing contract sources and generating ABI, interfaces and SDK clients
+//! - `build`: compiling, artifacts, verification and release tooling (implies `abi`)
+//! - `archive`: verification archives and source tarballs
+//! - `rpc`: request and response types of the verification server's HTTP API
+//! - `server`: the verification server's job queue and SQLite job store (implies
+//!   `build` and `rpc`)
+//! - `docker`: paths and image names for builds in the pinned Docker image
+//! - `watch`: rebuilding on source changes
+//!
+//! The default set is `build`, `archive`, `rpc`, `server` and `docker`. Tools
+//! that only generate ABIs or interfaces can use
+//! `default-features = false, features = ["abi"]`, which leaves out the rWASM
+//! compiler, the archive formats, the server and SQLite.
 
 // Internal modules
+#[cfg(feature = "rpc")]
+mod api;
+#[cfg(feature = "archive")]
 mod archive;
+#[cfg(feature = "abi")]
 mod artifacts;
+#[cfg(feature = "build")]
+mod backfill;
+#[cfg(feature = "build")]
+mod build_info;
+#[cfg(feature = "build")]
 mod builder;
+#[cfg(feature = "build")]
+mod cache;
+#[cfg(feature = "build")]
+mod changelog;
+#[cfg(feature = "build")]
+mod cleanup;
+#[cfg(feature = "build")]
+mod compat;
+#[cfg(feature = "build")]
 mod config;
+#[cfg(feature = "build")]
+mod contract;
+#[cfg(feature = "build")]
+mod deployments;
+#[cfg(feature = "docker")]
+mod docker;
+mod error;
+#[cfg(feature = "build")]
+mod facts;
+#[cfg(feature = "build")]
+mod genesis;
+#[cfg(feature = "build")]
 mod git;
+#[cfg(any(feature = "build", feature = "archive", feature = "rpc"))]
+mod hashing;
+#[cfg(any(feature = "build", feature = "rpc"))]
+mod hints;
+#[cfg(feature = "build")]
+mod incremental;
+#[cfg(feature = "server")]
+mod job_store;
+#[cfg(feature = "server")]
+mod jobs;
+#[cfg(feature = "build")]
+mod migrate;
+#[cfg(feature = "build")]
+mod mismatch_report;
+#[cfg(feature = "build")]
+mod output_cache;
+#[cfg(feature = "abi")]
 mod parser;
+#[cfg(any(feature = "build", feature = "archive", feature = "docker"))]
+mod paths;
+#[cfg(feature = "build")]
+mod precheck;
+#[cfg(feature = "build")]
+mod process;
+#[cfg(feature = "build")]
+mod registry;
+#[cfg(feature = "build")]
+mod release;
+#[cfg(feature = "build")]
+mod reproducibility_check;
+#[cfg(feature = "build")]
+mod scaffold;
+#[cfg(feature = "build")]
+mod scheduler;
+#[cfg(feature = "build")]
+mod schema;
+#[cfg(feature = "build")]
+mod sdk;
+#[cfg(feature = "build")]
+mod size_limits;
+#[cfg(feature = "build")]
+mod size_report;
+#[cfg(feature = "build")]
+mod standard_json;
+#[cfg(all(feature = "build", feature = "archive"))]
+mod support_bundle;
+#[cfg(any(feature = "build", feature = "rpc"))]
+mod unwrap;
+#[cfg(feature = "build")]
+mod upgrade;
+#[cfg(feature = "build")]
+mod vendor;
+#[cfg(feature = "build")]
 mod verify;
+#[cfg(feature = "watch")]
+mod watch;
+#[cfg(feature = "build")]
+mod workspace;
 
 // Public API - only expose what's necessary
 
+// Compiling contracts from another crate's build.rs
+#[cfg(feature = "build")]
+pub mod build_script;
+
+// Errors to match on
+pub use error::BuilderError;
+
 // Core compilation
+#[cfg(feature = "build")]
+pub use build_info::{BuildFeatures, Provenance};
+#[cfg(feature = "build")]
 pub use builder::{
-    build, get_rwasm_hash, get_wasm_hash, read_rust_toolchain_version,
-    read_sdk_version_from_cargo_lock, CompilationResult, ContractInfo,
+    build, compile_with_progress, get_rwasm_hash, get_wasm_hash, read_rust_toolchain_version,
+    read_sdk_version_from_cargo_lock, BuildEvent, CompilationOutputs, CompilationResult,
+    ContractInfo, BUILD_ENVIRONMENT_ENV, DOCKER_ENV, NIX_DERIVATION_ENV, NIX_INPUTS_ENV,
 };
-pub use config::{ArtifactsConfig, CompileConfig};
+#[cfg(feature = "build")]
+pub use config::{
+    ArtifactsConfig, CompileConfig, SelectorCollisions, SizeLimitPolicy, SizeLimits, Timeouts,
+};
+#[cfg(feature = "build")]
+pub use contract::{resolve_build_target, validate_contract, BuildTarget, TargetKind};
+#[cfg(feature = "watch")]
+pub use watch::watch;
 
 // Artifact management
-pub use artifacts::{metadata::Source, save_artifacts, Abi, ContractArtifacts, SavedPaths};
+#[cfg(feature = "abi")]
+pub use artifacts::{
+    abi::AbiNaming, extract_error_selectors, extract_event_topics, Abi, AbiConstructor, AbiEntry,
+    AbiError, AbiEvent, AbiFallback, AbiFunction, Param, StateMutability,
+};
+#[cfg(feature = "build")]
+pub use artifacts::{
+    metadata::{BuildEnvironment, Metadata, NixInput, Source},
+    save_artifacts, ContractArtifacts, FacetArtifacts, SavedPaths, CARGO_LOCK_FILE, LATEST_LINK,
+};
+
+// ABI and interface of contract sources, without compiling
+#[cfg(feature = "abi")]
+pub use artifacts::{
+    abi::{generate_from_source as generate_abi, selector_collisions},
+    interface::generate as generate_interface,
+};
+
+// Interface changes between two versions of an ABI
+#[cfg(feature = "abi")]
+pub use artifacts::abi::{diff as diff_abi, AbiChange, AbiDiff, ChangeKind};
+
+// Upgrading artifact directories of older builder versions
+#[cfg(feature = "build")]
+pub use artifacts::migrate::{plan_artifacts_migration, ArtifactsMigration, LEGACY_METADATA_FILE};
+
+// Human-readable ABI
+#[cfg(feature = "abi")]
+pub use artifacts::human_readable::{
+    format as format_human_readable_abi, parse as parse_human_readable_abi, HUMAN_READABLE_ABI_FILE,
+};
+
+// Callers of external contracts
+#[cfg(feature = "abi")]
+pub use artifacts::bindgen::generate_rust_client;
+
+// Typed ABI for TypeScript frontends
+#[cfg(feature = "abi")]
+pub use artifacts::typescript::{generate_typescript_bindings, TYPESCRIPT_BINDINGS_FILE};
+
+// alloy `sol!` bindings for off-chain Rust callers
+#[cfg(feature = "abi")]
+pub use artifacts::alloy::{generate_alloy_bindings, RUST_BINDINGS_FILE};
+
+// ABI extraction for watch mode
+#[cfg(feature = "build")]
+pub use incremental::{AbiSnapshot, AbiWatcher};
+
+// Deployment payload
+#[cfg(feature = "build")]
+pub use artifacts::deploy::{
+    create2_address, create_address, encode_constructor_args, DeployArtifact, DEPLOY_FILE,
+};
+
+// Contracts preloaded into a devnet genesis
+#[cfg(feature = "build")]
+pub use genesis::{merge_into_genesis, normalize_genesis_address, GenesisAccount};
+
+// Solidity-safe names
+#[cfg(feature = "abi")]
+pub use artifacts::sanitize::{IdentifierKind, RenamedIdentifier};
+
+// Reproducibility scorecard
+#[cfg(feature = "build")]
+pub use artifacts::nondeterminism::{NondeterminismKind, ReproducibilityWarning};
+#[cfg(feature = "build")]
+pub use artifacts::reproducibility::{
+    ReproducibilityCheck, ReproducibilityScorecard, REPRODUCIBILITY_FILE,
+};
+
+// ABI conformance
+#[cfg(feature = "abi")]
+pub use artifacts::conformance::{run_abi_self_test, ConformanceFailure, ConformanceReport};
+
+// Attributing bytecode size to crates and functions
+#[cfg(feature = "build")]
+pub use size_report::{analyze_wasm_size, SizeEntry, SizeReport, UNATTRIBUTED};
+
+// Building twice to catch non-determinism
+#[cfg(feature = "build")]
+pub use reproducibility_check::{
+    check_reproducibility, compare_builds, BuildDigest, EmbeddedKind, EmbeddedString,
+    ReproducibilityDiff,
+};
 
 // Verification
-pub use verify::{verify, VerificationResult, VerificationStatus, VerifyConfig};
+#[cfg(any(feature = "build", feature = "archive", feature = "rpc"))]
+pub use hashing::HashAlgorithm;
+#[cfg(any(feature = "build", feature = "rpc"))]
+pub use hints::MismatchHint;
+#[cfg(feature = "build")]
+pub use hints::{diagnose_mismatch, diagnose_provenance};
+#[cfg(feature = "build")]
+pub use mismatch_report::{BuildSnapshot, MismatchReport, MISMATCH_REPORT_FILE};
+#[cfg(any(feature = "build", feature = "rpc"))]
+pub use unwrap::{CodeUnwrap, Unwrapping};
+#[cfg(all(feature = "build", feature = "archive"))]
+pub use verify::verify_from_archive;
+#[cfg(feature = "build")]
+pub use verify::{
+    verify, ConstructorArgs, VerificationResult, VerificationStatus, VerifyConfig, VerifySource,
+};
+
+// Behavior of earlier builder versions
+#[cfg(feature = "build")]
+pub use compat::{Behavior, BehaviorChange, Compat, BEHAVIOR_CHANGES, UNVERSIONED};
+
+// Verification server jobs
+#[cfg(feature = "server")]
+pub use job_store::{CachedResult, JobStore};
+#[cfg(feature = "server")]
+pub use jobs::{run_verify_job, JobError, JobExecutor, JobLog, PoolConfig, WorkerPool};
+#[cfg(feature = "build")]
+pub use vendor::vendor_dependencies;
+
+// HTTP API of the verification server
+#[cfg(feature = "rpc")]
+pub use api::{
+    openapi, ApiError, CachePurged, HealthResponse, Job, JobAccepted, JobOutcome, JobStatus,
+    VerifyRequest, OPENAPI_PATH,
+};
+
+// Bulk export and import of verified builds
+#[cfg(all(feature = "archive", feature = "server"))]
+pub use backfill::import_verified_builds;
+#[cfg(feature = "build")]
+pub use backfill::{
+    export_verified_builds, read_dump_dir, read_ndjson, write_dump_dir, write_ndjson,
+    VerifiedBuild, BUILD_FILE,
+};
+
+// Checks of built artifacts before deployment
+#[cfg(feature = "build")]
+pub use precheck::{precheck, PrecheckIssue, PrecheckIssueKind, PrecheckReport};
+
+// Release tooling
+#[cfg(feature = "build")]
+pub use changelog::{
+    generate_changelog, Changelog, ContractSnapshot, DependencyChange, WorktreeGuard,
+};
+#[cfg(feature = "build")]
+pub use deployments::{DeploymentRecord, Deployments, ReleaseRecord};
+#[cfg(feature = "build")]
+pub use release::{
+    check_release_preconditions, finalize_release, find_previous_release, parse_release_tag,
+    release_dir, RELEASES_DIR,
+};
+
+// Upgrade-safety checks of proxied contracts
+#[cfg(feature = "build")]
+pub use upgrade::{
+    check_upgrade, find_artifacts_by_hash, parse_storage, project_storage, StorageField,
+    UpgradeIssue, UpgradeIssueKind, UpgradeReport,
+};
+
+// Project migrations
+#[cfg(feature = "build")]
+pub use migrate::{
+    apply_migration, parse_feature_rename, plan_migration, FileChange, DEFAULT_RUST_VERSION,
+};
+
+// Scaffolding of new projects
+#[cfg(feature = "build")]
+pub use scaffold::{new_project, ProjectTemplate};
+
+// SDK releases
+#[cfg(feature = "build")]
+pub use sdk::{
+    list_sdk_releases, resolve_sdk_release, upgrade_sdk, SdkRelease, SdkUpgrade, SDK_REPOSITORY,
+};
+
+// JSON Schemas of written files
+#[cfg(feature = "build")]
+pub use schema::{
+    breaking_changes, json_schema, serialized_schema, DEPLOYMENTS_SCHEMA, DEPLOY_SCHEMA,
+    METADATA_SCHEMA, REPRODUCIBILITY_SCHEMA, SCHEMA_NAMES, STANDARD_JSON_SCHEMA,
+    VERIFIED_BUILD_SCHEMA,
+};
+
+// solc-style standard JSON compilation
+#[cfg(feature = "build")]
+pub use standard_json::{
+    compile_standard_json, StandardJsonContract, StandardJsonError, StandardJsonInput,
+    StandardJsonOutput, STANDARD_JSON_LANGUAGE,
+};
 
+// Workspace batch builds and build sandboxes
+#[cfg(feature = "build")]
+pub use scheduler::{BuildOutcome, BuildScheduler, BuildStatus, ScheduledBuild};
+#[cfg(feature = "build")]
+pub use workspace::{BuildCache, CargoLayout, Sandbox, WorkspaceGraph, WorkspaceMember};
+
+// Contracts of a multi-contract project
+#[cfg(feature = "build")]
+pub use registry::{ContractRegistry, RegistryEntry, VerifyPolicy, REGISTRY_FILE};
+
+// Redacted bundles of failure context for bug reports
+#[cfg(all(feature = "build", feature = "archive"))]
+pub use support_bundle::{environment_report, redact, SupportBundle, REDACTED};
+
+// Cleanup of temporary state on failure or interruption
+#[cfg(feature = "build")]
+pub use cleanup::{output_interruptible, run_pending_cleanups, status_interruptible, CleanupGuard};
+
+// Garbage collection of managed caches
+#[cfg(feature = "build")]
+pub use cache::{
+    cache_dir_entries, format_size, parse_age, parse_size, parse_timestamp, plan_gc,
+    record_cache_use, user_cache_dir, CacheEntry, CacheKind, CacheUsage, GcLimits, CACHE_DIR_ENV,
+    CACHE_USAGE_FILE,
+};
+
+// Cache of compiled bytecode
+#[cfg(feature = "build")]
+pub use output_cache::{default_build_cache_dir, BUILD_CACHE_DIR_ENV};
+
+// Cached environment checks
+#[cfg(feature = "build")]
+pub use facts::{FactCache, DEFAULT_FACT_TTL};
+
+// Timeouts for external commands and slow operations
+#[cfg(feature = "build")]
+pub use process::{output_with_timeout, run_with_timeout, ProcessError};
+
+#[cfg(feature = "archive")]
 pub use archive::{create_verification_archive, ArchiveFormat, ArchiveInfo, ArchiveOptions};
-pub use git::{detect_git_info, get_project_path_in_repo, GitInfo};
+#[cfg(feature = "docker")]
+pub use docker::{builder_image, docker_host_path, BUILDER_IMAGE_REPOSITORY};
+#[cfg(feature = "build")]
+pub use git::{
+    detect_git_info, detect_git_info_with_timeout, get_project_path_in_repo, git_dirs, GitDirs,
+    GitInfo,
+};
+#[cfg(any(feature = "build", feature = "archive", feature = "docker"))]
+pub use paths::{canonicalize_path, to_slash};
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -42,8 +390,31 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 /// let result = build_at("./my-contract").unwrap();
 /// println!("Compiled: {} v{}", result.contract.name, result.contract.version);
 /// ```
+#[cfg(feature = "build")]
 pub fn build_at(project_root: impl Into<std::path::PathBuf>) -> eyre::Result<CompilationResult> {
-    let config = CompileConfig::new(project_root);
+    build_at_with(project_root, |_| {})
+}
+
+/// Compile a contract at the given path, adjusting the default settings first
+///
+/// # Example
+/// ```no_run
+/// use fluent_builder::build_at_with;
+///
+/// let result = build_at_with("./my-contract", |config| {
+///     config.profile = "debug".to_string();
+///     config.features.push("testnet".to_string());
+/// })
+/// .unwrap();
+/// println!("rWASM size: {}", result.outputs.rwasm.len());
+/// ```
+#[cfg(feature = "build")]
+pub fn build_at_with(
+    project_root: impl Into<std::path::PathBuf>,
+    configure: impl FnOnce(&mut CompileConfig),
+) -> eyre::Result<CompilationResult> {
+    let mut config = CompileConfig::new(project_root);
+    configure(&mut config);
     build(&config)
 }
 
@@ -56,16 +427,48 @@ pub fn build_at(project_root: impl Into<std::path::PathBuf>) -> eyre::Result<Com
 /// let matches = verify_at("./my-contract", "0xabc123...").unwrap();
 /// assert!(matches);
 /// ```
+#[cfg(feature = "build")]
 pub fn verify_at(
     project_root: impl Into<std::path::PathBuf>,
     deployed_bytecode_hash: &str,
+) -> eyre::Result<bool> {
+    verify_at_with(project_root, deployed_bytecode_hash, |_| {})
+}
+
+/// Verify a deployed contract matches the source code, rebuilt with adjusted settings
+///
+/// # Example
+/// ```no_run
+/// use fluent_builder::verify_at_with;
+///
+/// let matches = verify_at_with("./my-contract", "0xabc123...", |config| {
+///     config.features = vec!["mainnet".to_string()];
+/// })
+/// .unwrap();
+/// assert!(matches);
+/// ```
+#[cfg(feature = "build")]
+pub fn verify_at_with(
+    project_root: impl Into<std::path::PathBuf>,
+    deployed_bytecode_hash: &str,
+    configure: impl FnOnce(&mut CompileConfig),
 ) -> eyre::Result<bool> {
     use verify::VerifyConfig;
 
+    let project_root = project_root.into();
+    let mut compile_config = CompileConfig::new(project_root.clone());
+    configure(&mut compile_config);
+
     let config = VerifyConfig {
-        project_root: project_root.into(),
+        source: project_root.into(),
         deployed_bytecode_hash: deployed_bytecode_hash.to_string(),
-        compile_config: None,
+        hash_algorithm: HashAlgorithm::default(),
+        compile_config: Some(compile_config),
+        recorded_metadata: None,
+        deployed_provenance: None,
+        deployed_unwrapping: None,
+        constructor_args: None,
+        deployment_data: None,
     };
 
     let result = verify(config)?;
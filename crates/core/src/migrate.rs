@@ -0,0 +1,330 @@
+//! Structural fixes for projects created with older SDK/builder versions
+
+use eyre::{Context, Result};
+use similar::TextDiff;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, DocumentMut, Item};
+
+/// Toolchain pinned for projects that have none
+pub const DEFAULT_RUST_VERSION: &str = "1.83.0";
+
+/// fluentbase-sdk features renamed by SDK releases (old, new)
+///
+/// Renames of releases not listed here can be passed to [`plan_migration`].
+const FEATURE_RENAMES: &[(&str, &str)] = &[];
+
+/// A change to a single project file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// Content before the change (None if the file does not exist)
+    pub before: Option<String>,
+    /// Content after the change (None if the file is removed)
+    pub after: Option<String>,
+    /// What the change fixes
+    pub reason: String,
+}
+
+impl FileChange {
+    /// Unified diff of the change
+    pub fn diff(&self) -> String {
+        let path = crate::paths::to_slash(&self.path);
+        let before = self.before.as_deref().unwrap_or_default();
+        let after = self.after.as_deref().unwrap_or_default();
+        let old = if self.before.is_some() {
+            format!("a/{}", path)
+        } else {
+            "/dev/null".to_string()
+        };
+        let new = if self.after.is_some() {
+            format!("b/{}", path)
+        } else {
+            "/dev/null".to_string()
+        };
+
+        TextDiff::from_lines(before, after)
+            .unified_diff()
+            .header(&old, &new)
+            .to_string()
+    }
+}
+
+/// Inspect a project and compute the fixes it needs, without touching files
+///
+/// `feature_renames` (old, new) are applied in addition to the renames of
+/// known SDK releases.
+pub fn plan_migration(
+    project_root: &Path,
+    feature_renames: &[(String, String)],
+) -> Result<Vec<FileChange>> {
+    let mut changes = Vec::new();
+
+    changes.extend(toolchain_changes(project_root)?);
+
+    let manifest_path = project_root.join("Cargo.toml");
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    let renames: Vec<(&str, &str)> = feature_renames
+        .iter()
+        .map(|(old, new)| (old.as_str(), new.as_str()))
+        .chain(FEATURE_RENAMES.iter().copied())
+        .collect();
+    let (migrated, reasons) = migrate_manifest(&manifest, &renames)?;
+    if !reasons.is_empty() {
+        changes.push(FileChange {
+            path: PathBuf::from("Cargo.toml"),
+            before: Some(manifest),
+            after: Some(migrated),
+            reason: reasons.join("; "),
+        });
+    }
+
+    Ok(changes)
+}
+
+/// Write planned changes to disk
+pub fn apply_migration(project_root: &Path, changes: &[FileChange]) -> Result<()> {
+    for change in changes {
+        let path = project_root.join(&change.path);
+        match &change.after {
+            Some(content) => std::fs::write(&path, content),
+            None => std::fs::remove_file(&path),
+        }
+        .with_context(|| format!("Failed to update {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Pin the toolchain in rust-toolchain.toml
+fn toolchain_changes(project_root: &Path) -> Result<Vec<FileChange>> {
+    let toolchain_path = project_root.join("rust-toolchain.toml");
+    let legacy_path = project_root.join("rust-toolchain");
+
+    if toolchain_path.exists() {
+        let content = std::fs::read_to_string(&toolchain_path)?;
+        let mut doc: DocumentMut = content
+            .parse()
+            .context("Failed to parse rust-toolchain.toml")?;
+        let channel = doc
+            .get("toolchain")
+            .and_then(|t| t.get("channel"))
+            .and_then(Item::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        if !is_unpinned(&channel) {
+            return Ok(Vec::new());
+        }
+        doc["toolchain"]["channel"] = toml_edit::value(DEFAULT_RUST_VERSION);
+        return Ok(vec![FileChange {
+            path: PathBuf::from("rust-toolchain.toml"),
+            before: Some(content),
+            after: Some(doc.to_string()),
+            reason: format!(
+                "pin Rust toolchain to {} (was '{}')",
+                DEFAULT_RUST_VERSION, channel
+            ),
+        }]);
+    }
+
+    // Legacy file: keep a pinned channel, replace an unpinned one
+    let (channel, mut changes) = if legacy_path.exists() {
+        let legacy = std::fs::read_to_string(&legacy_path)?;
+        let channel = legacy.trim().to_string();
+        let removal = FileChange {
+            path: PathBuf::from("rust-toolchain"),
+            before: Some(legacy),
+            after: None,
+            reason: "replace legacy rust-toolchain file".to_string(),
+        };
+        let channel = if is_unpinned(&channel) {
+            DEFAULT_RUST_VERSION.to_string()
+        } else {
+            channel
+        };
+        (channel, vec![removal])
+    } else {
+        (DEFAULT_RUST_VERSION.to_string(), Vec::new())
+    };
+
+    changes.insert(
+        0,
+        FileChange {
+            path: PathBuf::from("rust-toolchain.toml"),
+            before: None,
+            after: Some(toolchain_file(&channel)),
+            reason: format!("pin Rust toolchain to {}", channel),
+        },
+    );
+    Ok(changes)
+}
+
+fn is_unpinned(channel: &str) -> bool {
+    channel.is_empty() || ["stable", "beta", "nightly"].contains(&channel)
+}
+
+pub(crate) fn toolchain_file(channel: &str) -> String {
+    format!(
+        "[toolchain]\nchannel = \"{}\"\ntargets = [\"wasm32-unknown-unknown\"]\n",
+        channel
+    )
+}
+
+/// Parse a feature rename given as `OLD=NEW`
+pub fn parse_feature_rename(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+            Ok((old.trim().to_string(), new.trim().to_string()))
+        }
+        _ => Err(eyre::eyre!("Expected OLD=NEW, got '{}'", s)),
+    }
+}
+
+/// Apply Cargo.toml fixes, returning the new manifest and what changed
+///
+/// Virtual workspace manifests have no crate to fix and are left alone.
+fn migrate_manifest(manifest: &str, renames: &[(&str, &str)]) -> Result<(String, Vec<String>)> {
+    let mut doc: DocumentMut = manifest.parse().context("Failed to parse Cargo.toml")?;
+    let mut reasons = Vec::new();
+    if !doc.contains_key("package") {
+        return Ok((manifest.to_string(), reasons));
+    }
+
+    // Contracts are loaded as cdylib wasm modules
+    let lib = doc.entry("lib").or_insert(toml_edit::table());
+    match lib.get_mut("crate-type").and_then(Item::as_array_mut) {
+        Some(types) if types.iter().any(|t| t.as_str() == Some("cdylib")) => {}
+        Some(types) => {
+            types.push("cdylib");
+            reasons.push("add cdylib crate-type".to_string());
+        }
+        None => {
+            lib["crate-type"] = toml_edit::value(Array::from_iter(["cdylib"]));
+            reasons.push("set crate-type = [\"cdylib\"]".to_string());
+        }
+    }
+
+    // Feature references like "fluentbase-sdk/old"
+    if let Some(features) = doc.get_mut("features").and_then(Item::as_table_like_mut) {
+        for (_, enabled) in features.iter_mut() {
+            let Some(list) = enabled.as_array_mut() else {
+                continue;
+            };
+            for value in list.iter_mut() {
+                let Some(reference) = value.as_str() else {
+                    continue;
+                };
+                for (old, new) in renames {
+                    if reference == format!("fluentbase-sdk/{}", old) {
+                        let renamed = format!("fluentbase-sdk/{}", new);
+                        reasons.push(format!("rename feature {} to {}", reference, renamed));
+                        *value = renamed.into();
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Features enabled on the dependency itself
+    if let Some(features) = doc
+        .get_mut("dependencies")
+        .and_then(|deps| deps.get_mut("fluentbase-sdk"))
+        .and_then(|sdk| sdk.get_mut("features"))
+        .and_then(Item::as_array_mut)
+    {
+        for value in features.iter_mut() {
+            let Some(name) = value.as_str() else {
+                continue;
+            };
+            if let Some((old, new)) = renames.iter().find(|(old, _)| *old == name) {
+                reasons.push(format!("rename fluentbase-sdk feature {} to {}", old, new));
+                *value = (*new).into();
+            }
+        }
+    }
+
+    Ok((doc.to_string(), reasons))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MANIFEST: &str = r#"[package]
+name = "token"
+version = "0.1.0"
+
+[dependencies]
+fluentbase-sdk = { git = "https://github.com/fluentlabs-xyz/fluentbase", features = ["evm"] }
+
+[features]
+default = ["std"]
+std = ["fluentbase-sdk/std-old"]
+"#;
+
+    #[test]
+    fn test_migrate_manifest() {
+        let (migrated, reasons) =
+            migrate_manifest(MANIFEST, &[("std-old", "std"), ("evm", "evm-compat")]).unwrap();
+        assert_eq!(reasons.len(), 3);
+        assert!(migrated.contains("[lib]\ncrate-type = [\"cdylib\"]"));
+        assert!(migrated.contains("std = [\"fluentbase-sdk/std\"]"));
+        assert!(migrated.contains("features = [\"evm-compat\"]"));
+
+        // Already migrated manifests are left alone
+        let (again, reasons) = migrate_manifest(&migrated, &[("std-old", "std")]).unwrap();
+        assert!(reasons.is_empty());
+        assert_eq!(again, migrated);
+    }
+
+    #[test]
+    fn test_virtual_manifest_is_left_alone() {
+        let manifest = "[workspace]\nmembers = [\"contracts/*\"]\n";
+        let (migrated, reasons) = migrate_manifest(manifest, &[("std-old", "std")]).unwrap();
+        assert!(reasons.is_empty());
+        assert_eq!(migrated, manifest);
+    }
+
+    #[test]
+    fn test_parse_feature_rename() {
+        assert_eq!(
+            parse_feature_rename("std-old=std").unwrap(),
+            ("std-old".to_string(), "std".to_string())
+        );
+        assert!(parse_feature_rename("std").is_err());
+        assert!(parse_feature_rename("=std").is_err());
+    }
+
+    #[test]
+    fn test_plan_and_apply_migration() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), MANIFEST).unwrap();
+        std::fs::write(dir.path().join("rust-toolchain"), "nightly\n").unwrap();
+
+        let renames = [("std-old".to_string(), "std".to_string())];
+        let changes = plan_migration(dir.path(), &renames).unwrap();
+        let paths: Vec<&str> = changes.iter().map(|c| c.path.to_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec!["rust-toolchain.toml", "rust-toolchain", "Cargo.toml"]
+        );
+        assert!(changes[0]
+            .diff()
+            .starts_with("--- /dev/null\n+++ b/rust-toolchain.toml"));
+        assert!(changes[2].diff().contains("+crate-type = [\"cdylib\"]"));
+        assert!(changes[2]
+            .reason
+            .contains("rename feature fluentbase-sdk/std-old"));
+
+        apply_migration(dir.path(), &changes).unwrap();
+        assert!(!dir.path().join("rust-toolchain").exists());
+        assert_eq!(
+            crate::read_rust_toolchain_version(dir.path()).unwrap(),
+            DEFAULT_RUST_VERSION
+        );
+        assert!(plan_migration(dir.path(), &renames).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,646 @@
+//! HTML report of a verification mismatch
+//!
+//! Puts the recorded build (the deployment's metadata.json, with the
+//! `lib.wasm` and `Cargo.lock` saved next to it) side by side with the local
+//! rebuild: wasm sections, functions whose bodies differ, metadata fields and
+//! dependency versions.
+
+use crate::{changelog::parse_cargo_lock, hashing::HashAlgorithm, CompilationResult, MismatchHint};
+use eyre::{Context, Result};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+const WASM_HEADER_LEN: usize = 8;
+const CUSTOM_SECTION_ID: u8 = 0;
+
+/// File name of the report in the output directory
+pub const MISMATCH_REPORT_FILE: &str = "mismatch-report.html";
+
+/// Differing functions listed at most
+const MAX_FUNCTION_ROWS: usize = 200;
+
+/// One side of a mismatch
+#[derive(Debug, Clone, Default)]
+pub struct BuildSnapshot {
+    pub label: String,
+    pub wasm: Option<Vec<u8>>,
+    pub metadata: Option<Value>,
+    /// Package versions from Cargo.lock (name -> versions)
+    pub dependencies: BTreeMap<String, Vec<String>>,
+}
+
+impl BuildSnapshot {
+    /// Recorded build from its metadata.json, with `lib.wasm` and `Cargo.lock` next to it if
+    /// present
+    pub fn from_metadata_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let metadata = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Ok(Self {
+            label: path.display().to_string(),
+            wasm: std::fs::read(dir.join("lib.wasm")).ok(),
+            metadata: Some(metadata),
+            dependencies: read_dependencies(&dir.join(crate::CARGO_LOCK_FILE))?,
+        })
+    }
+
+    /// Local rebuild of the contract at `project_root`
+    pub fn from_compilation(result: &CompilationResult, project_root: &Path) -> Result<Self> {
+        Ok(Self {
+            label: "local build".to_string(),
+            wasm: Some(result.outputs.wasm.clone()),
+            metadata: result
+                .artifacts
+                .as_ref()
+                .and_then(|artifacts| serde_json::to_value(&artifacts.metadata).ok()),
+            dependencies: read_dependencies(&crate::builder::cargo_lock_path(project_root))?,
+        })
+    }
+}
+
+fn read_dependencies(cargo_lock: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    match std::fs::read_to_string(cargo_lock) {
+        Ok(content) => parse_cargo_lock(&content),
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+/// Side-by-side comparison of the recorded and the local build of a contract
+#[derive(Debug, Clone)]
+pub struct MismatchReport {
+    pub contract: String,
+    /// Hash of the deployed code
+    pub expected_hash: String,
+    /// Hash of the local build
+    pub actual_hash: String,
+    pub hints: Vec<MismatchHint>,
+    pub recorded: BuildSnapshot,
+    pub local: BuildSnapshot,
+}
+
+impl MismatchReport {
+    /// Write the report to `output_dir`
+    pub fn write(&self, output_dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+        let path = output_dir.join(MISMATCH_REPORT_FILE);
+        std::fs::write(&path, self.to_html())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let title = format!("Verification mismatch: {}", self.contract);
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html><head><meta \
+             charset=\"utf-8\"><title>{}</title><style>{}</style></head><body>\n",
+            escape(&title),
+            STYLE
+        );
+        let _ = writeln!(html, "<h1>{}</h1>", escape(&title));
+        let _ = writeln!(
+            html,
+            "<p>Deployed <code>{}</code>, built <code>{}</code></p>",
+            escape(&self.expected_hash),
+            escape(&self.actual_hash)
+        );
+        if !self.hints.is_empty() {
+            html.push_str("<h2>Likely causes</h2>\n<ol>\n");
+            for hint in &self.hints {
+                let _ = writeln!(html, "<li>{}</li>", escape(&hint.message));
+            }
+            html.push_str("</ol>\n");
+        }
+
+        self.write_sections(&mut html);
+        self.write_functions(&mut html);
+        self.write_metadata(&mut html);
+        self.write_dependencies(&mut html);
+
+        html.push_str("</body></html>\n");
+        html
+    }
+
+    fn header(&self, html: &mut String, first: &str, columns: &[&str]) {
+        let _ = write!(html, "<table>\n<tr><th rowspan=\"2\">{}</th>", first);
+        for label in [&self.recorded.label, &self.local.label] {
+            let _ = write!(
+                html,
+                "<th colspan=\"{}\">{}</th>",
+                columns.len(),
+                escape(label)
+            );
+        }
+        html.push_str("</tr>\n<tr>");
+        for column in columns.iter().chain(columns) {
+            let _ = write!(html, "<th>{}</th>", column);
+        }
+        html.push_str("</tr>\n");
+    }
+
+    fn write_sections(&self, html: &mut String) {
+        html.push_str("<h2>WASM sections</h2>\n");
+        let (recorded, local) = match (
+            wasm_sections(&self.recorded.wasm),
+            wasm_sections(&self.local.wasm),
+        ) {
+            (Ok(recorded), Ok(local)) => (recorded, local),
+            (Err(e), _) | (_, Err(e)) => {
+                let _ = writeln!(html, "<p>{}</p>", escape(&format!("{:#}", e)));
+                return;
+            }
+        };
+        self.header(html, "Section", &["Size", "SHA-256"]);
+        for i in 0..recorded.len().max(local.len()) {
+            let (a, b) = (recorded.get(i), local.get(i));
+            let name = a.or(b).map(|s| s.name.as_str()).unwrap_or_default();
+            let cell = |section: Option<&WasmSection>| match section {
+                Some(s) => format!("<td>{}</td><td><code>{}</code></td>", s.size, &s.hash[..16]),
+                None => "<td>-</td><td>-</td>".to_string(),
+            };
+            let _ = writeln!(
+                html,
+                "<tr{}><td>{}</td>{}{}</tr>",
+                row_class(a != b),
+                escape(name),
+                cell(a),
+                cell(b)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    fn write_functions(&self, html: &mut String) {
+        html.push_str("<h2>Functions</h2>\n");
+        let (recorded, local) = match (
+            function_table(&self.recorded.wasm),
+            function_table(&self.local.wasm),
+        ) {
+            (Ok(recorded), Ok(local)) => (recorded, local),
+            (Err(e), _) | (_, Err(e)) => {
+                let _ = writeln!(html, "<p>{}</p>", escape(&format!("{:#}", e)));
+                return;
+            }
+        };
+        let names: BTreeSet<&String> = recorded.keys().chain(local.keys()).collect();
+        let differing: Vec<&String> = names
+            .iter()
+            .copied()
+            .filter(|name| recorded.get(*name) != local.get(*name))
+            .collect();
+        let _ = writeln!(
+            html,
+            "<p>{} of {} functions differ</p>",
+            differing.len(),
+            names.len()
+        );
+        if differing.is_empty() {
+            return;
+        }
+        self.header(html, "Function", &["Size"]);
+        let size = |body: Option<&(usize, String)>| {
+            body.map(|(size, _)| size.to_string()).unwrap_or("-".into())
+        };
+        for name in differing.iter().take(MAX_FUNCTION_ROWS) {
+            let _ = writeln!(
+                html,
+                "<tr class=\"diff\"><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                escape(name),
+                size(recorded.get(*name)),
+                size(local.get(*name))
+            );
+        }
+        html.push_str("</table>\n");
+        if differing.len() > MAX_FUNCTION_ROWS {
+            let _ = writeln!(
+                html,
+                "<p>{} more not shown</p>",
+                differing.len() - MAX_FUNCTION_ROWS
+            );
+        }
+    }
+
+    fn write_metadata(&self, html: &mut String) {
+        html.push_str("<h2>Metadata</h2>\n");
+        let (Some(recorded), Some(local)) = (&self.recorded.metadata, &self.local.metadata) else {
+            html.push_str("<p>Metadata of both builds is needed for a comparison</p>\n");
+            return;
+        };
+        let (mut a, mut b) = (BTreeMap::new(), BTreeMap::new());
+        flatten(recorded, String::new(), &mut a);
+        flatten(local, String::new(), &mut b);
+        let differing: Vec<&String> = a
+            .keys()
+            .chain(b.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|field| a.get(*field) != b.get(*field))
+            .collect();
+        if differing.is_empty() {
+            html.push_str("<p>No differences</p>\n");
+            return;
+        }
+        html.push_str("<table>\n<tr><th>Field</th>");
+        for label in [&self.recorded.label, &self.local.label] {
+            let _ = write!(html, "<th>{}</th>", escape(label));
+        }
+        html.push_str("</tr>\n");
+        for field in differing {
+            let value = |fields: &BTreeMap<String, String>| {
+                fields.get(field).map(|v| escape(v)).unwrap_or("-".into())
+            };
+            let _ = writeln!(
+                html,
+                "<tr class=\"diff\"><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                escape(field),
+                value(&a),
+                value(&b)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    fn write_dependencies(&self, html: &mut String) {
+        html.push_str("<h2>Dependencies</h2>\n");
+        let (a, b) = (&self.recorded.dependencies, &self.local.dependencies);
+        if a.is_empty() || b.is_empty() {
+            html.push_str("<p>Cargo.lock of both builds is needed for a comparison</p>\n");
+            return;
+        }
+        let differing: Vec<&String> = a
+            .keys()
+            .chain(b.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|name| a.get(*name) != b.get(*name))
+            .collect();
+        if differing.is_empty() {
+            html.push_str("<p>No differences</p>\n");
+            return;
+        }
+        html.push_str("<table>\n<tr><th>Crate</th>");
+        for label in [&self.recorded.label, &self.local.label] {
+            let _ = write!(html, "<th>{}</th>", escape(label));
+        }
+        html.push_str("</tr>\n");
+        for name in differing {
+            let versions = |deps: &BTreeMap<String, Vec<String>>| {
+                deps.get(name).map(|v| v.join(", ")).unwrap_or("-".into())
+            };
+            let _ = writeln!(
+                html,
+                "<tr class=\"diff\"><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(name),
+                versions(a),
+                versions(b)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+                     table{border-collapse:collapse;margin-bottom:1em}\
+                     th,td{border:1px solid #ccc;padding:2px 8px;text-align:left}\
+                     tr.diff{background:#fdd}code{font-size:90%}";
+
+fn row_class(differs: bool) -> &'static str {
+    if differs {
+        " class=\"diff\""
+    } else {
+        ""
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Leaf values of a JSON document by JSON pointer
+fn flatten(value: &Value, pointer: String, out: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                flatten(value, format!("{}/{}", pointer, key), out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, value) in items.iter().enumerate() {
+                flatten(value, format!("{}/{}", pointer, i), out);
+            }
+        }
+        Value::String(s) => {
+            out.insert(pointer, s.clone());
+        }
+        other => {
+            out.insert(pointer, other.to_string());
+        }
+    }
+}
+
+/// A section of a wasm module
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct WasmSection {
+    /// Section kind, or `custom:<name>`
+    pub(crate) name: String,
+    pub(crate) size: usize,
+    pub(crate) hash: String,
+}
+
+pub(crate) fn wasm_sections(wasm: &Option<Vec<u8>>) -> Result<Vec<WasmSection>> {
+    let Some(wasm) = wasm else {
+        return Ok(Vec::new());
+    };
+    raw_sections(wasm)?
+        .into_iter()
+        .map(|(id, payload)| {
+            let name = match id {
+                CUSTOM_SECTION_ID => format!("custom:{}", read_name(payload, &mut 0)?),
+                id => SECTION_NAMES
+                    .get(id as usize)
+                    .unwrap_or(&"unknown")
+                    .to_string(),
+            };
+            Ok(WasmSection {
+                name,
+                size: payload.len(),
+                hash: HashAlgorithm::Sha256.hash(payload),
+            })
+        })
+        .collect()
+}
+
+const SECTION_NAMES: &[&str] = &[
+    "custom",
+    "type",
+    "import",
+    "function",
+    "table",
+    "memory",
+    "global",
+    "export",
+    "start",
+    "element",
+    "code",
+    "data",
+    "datacount",
+    "tag",
+];
+
+/// Id and payload of each section
+fn raw_sections(wasm: &[u8]) -> Result<Vec<(u8, &[u8])>> {
+    if wasm.len() < WASM_HEADER_LEN || &wasm[..4] != b"\0asm" {
+        return Err(eyre::eyre!("Not a wasm module"));
+    }
+    let mut sections = Vec::new();
+    let mut pos = WASM_HEADER_LEN;
+    while pos < wasm.len() {
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_leb128(wasm, &mut pos)? as usize;
+        let end = pos
+            .checked_add(size)
+            .filter(|&end| end <= wasm.len())
+            .ok_or_else(|| eyre::eyre!("Truncated wasm section"))?;
+        sections.push((id, &wasm[pos..end]));
+        pos = end;
+    }
+    Ok(sections)
+}
+
+fn read_name(bytes: &[u8], pos: &mut usize) -> Result<String> {
+    let len = read_leb128(bytes, pos)? as usize;
+    let name = bytes
+        .get(*pos..*pos + len)
+        .ok_or_else(|| eyre::eyre!("Truncated wasm name"))?;
+    *pos += len;
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Size and hash of each defined function body, by name where the module has one
+pub(crate) fn function_table(wasm: &Option<Vec<u8>>) -> Result<BTreeMap<String, (usize, String)>> {
+    let Some(wasm) = wasm else {
+        return Ok(BTreeMap::new());
+    };
+    let sections = raw_sections(wasm)?;
+    let section = |id: u8| {
+        sections
+            .iter()
+            .find(|(section_id, _)| *section_id == id)
+            .map(|(_, p)| *p)
+    };
+    let imported = section(2).map(imported_functions).transpose()?.unwrap_or(0);
+
+    // Exported names, overridden by the name section's
+    let mut names = BTreeMap::new();
+    if let Some(exports) = section(7) {
+        let mut pos = 0;
+        for _ in 0..read_leb128(exports, &mut pos)? {
+            let name = read_name(exports, &mut pos)?;
+            let kind = *exports
+                .get(pos)
+                .ok_or_else(|| eyre::eyre!("Truncated export section"))?;
+            pos += 1;
+            let index = read_leb128(exports, &mut pos)?;
+            if kind == 0 {
+                names.insert(index, name);
+            }
+        }
+    }
+    for (_, payload) in sections.iter().filter(|(id, _)| *id == CUSTOM_SECTION_ID) {
+        let mut pos = 0;
+        if read_name(payload, &mut pos)? == "name" {
+            names.extend(function_names(&payload[pos..])?);
+        }
+    }
+
+    let mut table = BTreeMap::new();
+    let Some(code) = section(10) else {
+        return Ok(table);
+    };
+    let mut pos = 0;
+    for i in 0..read_leb128(code, &mut pos)? {
+        let size = read_leb128(code, &mut pos)? as usize;
+        let body = code
+            .get(pos..pos + size)
+            .ok_or_else(|| eyre::eyre!("Truncated code section"))?;
+        pos += size;
+
+        let index = imported + i;
+        let mut name = names
+            .get(&index)
+            .cloned()
+            .unwrap_or_else(|| format!("func[{}]", index));
+        if table.contains_key(&name) {
+            name = format!("{} #{}", name, index);
+        }
+        table.insert(name, (size, HashAlgorithm::Sha256.hash(body)));
+    }
+    Ok(table)
+}
+
+/// Number of imported functions, which come first in the function index space
+fn imported_functions(imports: &[u8]) -> Result<u32> {
+    let truncated = || eyre::eyre!("Truncated import section");
+    let mut pos = 0;
+    let mut functions = 0;
+    for _ in 0..read_leb128(imports, &mut pos)? {
+        read_name(imports, &mut pos)?;
+        read_name(imports, &mut pos)?;
+        let kind = *imports.get(pos).ok_or_else(truncated)?;
+        pos += 1;
+        match kind {
+            0 => {
+                read_leb128(imports, &mut pos)?;
+                functions += 1;
+            }
+            // Table: reference type, then limits
+            1 => {
+                pos += 1;
+                skip_limits(imports, &mut pos)?;
+            }
+            2 => skip_limits(imports, &mut pos)?,
+            // Global: value type and mutability
+            3 => pos += 2,
+            // Tag: attribute and type index
+            4 => {
+                pos += 1;
+                read_leb128(imports, &mut pos)?;
+            }
+            kind => return Err(eyre::eyre!("Unknown import kind {}", kind)),
+        }
+    }
+    Ok(functions)
+}
+
+fn skip_limits(bytes: &[u8], pos: &mut usize) -> Result<()> {
+    let flags = *bytes
+        .get(*pos)
+        .ok_or_else(|| eyre::eyre!("Truncated limits"))?;
+    *pos += 1;
+    read_leb128(bytes, pos)?;
+    if flags & 1 != 0 {
+        read_leb128(bytes, pos)?;
+    }
+    Ok(())
+}
+
+/// Function names subsection of a `name` custom section
+fn function_names(payload: &[u8]) -> Result<BTreeMap<u32, String>> {
+    let mut names = BTreeMap::new();
+    let mut pos = 0;
+    while pos < payload.len() {
+        let id = payload[pos];
+        pos += 1;
+        let size = read_leb128(payload, &mut pos)? as usize;
+        let end = pos + size;
+        if id == 1 {
+            let subsection = payload
+                .get(pos..end)
+                .ok_or_else(|| eyre::eyre!("Truncated name section"))?;
+            let mut sub_pos = 0;
+            for _ in 0..read_leb128(subsection, &mut sub_pos)? {
+                let index = read_leb128(subsection, &mut sub_pos)?;
+                names.insert(index, read_name(subsection, &mut sub_pos)?);
+            }
+        }
+        pos = end;
+    }
+    Ok(names)
+}
+
+fn read_leb128(bytes: &[u8], pos: &mut usize) -> Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes
+            .get(*pos)
+            .ok_or_else(|| eyre::eyre!("Truncated wasm section"))?;
+        *pos += 1;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(eyre::eyre!("Invalid LEB128 value in wasm"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![id, payload.len() as u8];
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// Module with two named `() -> ()` functions, the second with `body`
+    fn module(body: &[u8]) -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend(section(1, &[1, 0x60, 0, 0]));
+        wasm.extend(section(3, &[2, 0, 0]));
+        let mut code = vec![2, 2, 0, 0x0b, body.len() as u8];
+        code.extend_from_slice(body);
+        wasm.extend(section(10, &code));
+        let mut name = vec![4, b'n', b'a', b'm', b'e'];
+        name.extend(section(1, &[2, 0, 1, b'a', 1, 4, b'm', b'a', b'i', b'n']));
+        wasm.extend(section(0, &name));
+        wasm
+    }
+
+    #[test]
+    fn test_function_table() {
+        let recorded = function_table(&Some(module(&[0, 0x0b]))).unwrap();
+        let local = function_table(&Some(module(&[0, 0x01, 0x0b]))).unwrap();
+        assert_eq!(recorded.keys().collect::<Vec<_>>(), ["a", "main"]);
+        assert_eq!(recorded["a"], local["a"]);
+        assert_eq!(local["main"].0, 3);
+        assert_ne!(recorded["main"], local["main"]);
+
+        let sections = wasm_sections(&Some(module(&[0, 0x0b]))).unwrap();
+        let names: Vec<&str> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["type", "function", "code", "custom:name"]);
+    }
+
+    #[test]
+    fn test_mismatch_report_html() {
+        let snapshot = |label: &str, body: &[u8], sdk: &str, version: &str| BuildSnapshot {
+            label: label.to_string(),
+            wasm: Some(module(body)),
+            metadata: Some(
+                serde_json::json!({ "sdk": { "tag": sdk }, "contract": { "name": "token" } }),
+            ),
+            dependencies: BTreeMap::from([(
+                "fluentbase-sdk".to_string(),
+                vec![version.to_string()],
+            )]),
+        };
+        let report = MismatchReport {
+            contract: "token".to_string(),
+            expected_hash: "0xaa".to_string(),
+            actual_hash: "0xbb".to_string(),
+            hints: Vec::new(),
+            recorded: snapshot("recorded", &[0, 0x0b], "v0.5", "0.5.0"),
+            local: snapshot("local <build>", &[0, 0x01, 0x0b], "v0.6", "0.6.0"),
+        };
+
+        let html = report.to_html();
+        assert!(html.contains("1 of 2 functions differ"));
+        assert!(html.contains("<code>main</code>"));
+        assert!(html.contains("<td><code>/sdk/tag</code></td><td>v0.5</td><td>v0.6</td>"));
+        assert!(!html.contains("/contract/name"));
+        assert!(html.contains("<td>fluentbase-sdk</td><td>0.5.0</td><td>0.6.0</td>"));
+        assert!(html.contains("local &lt;build&gt;"));
+    }
+}
@@ -0,0 +1,350 @@
+//! Cache of compiled bytecode
+//!
+//! cargo and the rWASM translation take most of a build, and verification
+//! rebuilds identical sources over and over. Their outputs are stored under a
+//! key covering the project files, its path dependencies as cargo resolves
+//! them, the workspace root, manifest and lock file, cargo configuration, the
+//! toolchain and every setting that reaches the bytecode, so an unchanged
+//! project skips both. Artifacts are generated again on a hit: that takes milliseconds and
+//! keeps the git source and build time in metadata.json current.
+
+use crate::{
+    builder::{should_skip_path, CompilationOutputs},
+    compat::Compat,
+    CompileConfig,
+};
+use eyre::{Context, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Overrides the directory of the bytecode cache
+pub const BUILD_CACHE_DIR_ENV: &str = "FLUENT_BUILDER_BUILD_CACHE_DIR";
+
+/// Environment variables that change the produced bytecode
+const BUILD_ENV_VARS: &[&str] = &[
+    "RUSTFLAGS",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_BUILD_RUSTFLAGS",
+];
+
+/// `$FLUENT_BUILDER_BUILD_CACHE_DIR`, or `builds/` in the per-user cache directory
+pub fn default_build_cache_dir() -> PathBuf {
+    std::env::var_os(BUILD_CACHE_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| crate::user_cache_dir().join("builds"))
+}
+
+/// Key of the outputs `config` produces from the current sources
+pub(crate) fn cache_key(config: &CompileConfig, compat: &Compat) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(crate::VERSION.as_bytes());
+    hasher.update(serde_json::to_vec(&bytecode_settings(config))?);
+    hasher.update(serde_json::to_vec(compat)?);
+    let metadata = crate::workspace::resolved_cargo_metadata(&config.project_root, config.offline)?;
+    for dir in source_dirs(&metadata)? {
+        hash_dir(&mut hasher, &dir)?;
+    }
+    let workspace_root = Path::new(metadata["workspace_root"].as_str().unwrap_or_default());
+    // Paths in panic messages and debug info are relative to it
+    hasher.update(format!("workspace_root={};", workspace_root.display()).as_bytes());
+    let workspace_files = ["Cargo.toml", "Cargo.lock"]
+        .map(|name| (format!("workspace/{}", name), workspace_root.join(name)));
+    for (name, file) in workspace_files
+        .into_iter()
+        .chain(cargo_config_files(&config.project_root))
+    {
+        hash_file(&mut hasher, &name, &file)?;
+    }
+    hasher.update(rustc_version(&config.project_root)?.as_bytes());
+    for name in BUILD_ENV_VARS {
+        hasher.update(format!("{}={:?};", name, std::env::var_os(name)).as_bytes());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Outputs stored under `key`, if any
+pub(crate) fn lookup(cache_dir: &Path, key: &str) -> Option<CompilationOutputs> {
+    let dir = cache_dir.join(key);
+    let wasm = std::fs::read(dir.join("lib.wasm")).ok()?;
+    let rwasm = std::fs::read(dir.join("lib.rwasm")).ok()?;
+    Some(CompilationOutputs { wasm, rwasm })
+}
+
+/// Store outputs under `key`; concurrent builds of the same key both succeed
+pub(crate) fn store(cache_dir: &Path, key: &str, outputs: &CompilationOutputs) -> Result<()> {
+    // Unique per build, also for parallel builds of one process
+    static STORES: AtomicUsize = AtomicUsize::new(0);
+    let partial = cache_dir.join(format!(
+        ".{}.{}.{}",
+        key,
+        std::process::id(),
+        STORES.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&partial)
+        .with_context(|| format!("Failed to create {}", partial.display()))?;
+    let guard = crate::CleanupGuard::remove_path(&partial);
+    std::fs::write(partial.join("lib.wasm"), &outputs.wasm)?;
+    std::fs::write(partial.join("lib.rwasm"), &outputs.rwasm)?;
+
+    // Another build may have stored the same outputs in the meantime
+    let dir = cache_dir.join(key);
+    if !dir.exists() {
+        std::fs::rename(&partial, &dir)
+            .with_context(|| format!("Failed to write {}", dir.display()))?;
+    }
+    drop(guard);
+    Ok(())
+}
+
+/// The config without the settings that don't reach the bytecode
+fn bytecode_settings(config: &CompileConfig) -> CompileConfig {
+    let defaults = CompileConfig::default();
+    CompileConfig {
+        output_dir: defaults.output_dir,
+        artifacts: defaults.artifacts,
+        use_git_source: defaults.use_git_source,
+        offline: defaults.offline,
+        target_dir: defaults.target_dir,
+        timeouts: defaults.timeouts,
+        strict: defaults.strict,
+        build_cache: defaults.build_cache,
+        size_limits: defaults.size_limits,
+        ..config.clone()
+    }
+}
+
+/// Directories of the local packages the build compiles: the project and its path dependencies
+///
+/// Read from the resolved graph of `cargo metadata`, so `[patch]` sections,
+/// `workspace = true` and target-specific dependencies are followed the way
+/// cargo follows them. Dev-dependencies are left out; a virtual workspace
+/// covers all of its members.
+fn source_dirs(metadata: &Value) -> Result<BTreeSet<PathBuf>> {
+    let packages = metadata["packages"]
+        .as_array()
+        .ok_or_else(|| eyre::eyre!("cargo metadata has no packages"))?;
+    let nodes: BTreeMap<&str, &Value> = metadata["resolve"]["nodes"]
+        .as_array()
+        .ok_or_else(|| eyre::eyre!("cargo metadata has no dependency graph"))?
+        .iter()
+        .filter_map(|node| Some((node["id"].as_str()?, node)))
+        .collect();
+
+    let mut pending: Vec<&str> = match metadata["resolve"]["root"].as_str() {
+        Some(root) => vec![root],
+        None => metadata["workspace_members"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect(),
+    };
+    let mut reached = BTreeSet::new();
+    while let Some(id) = pending.pop() {
+        if !reached.insert(id) {
+            continue;
+        }
+        let deps = nodes.get(id).and_then(|node| node["deps"].as_array());
+        for dep in deps.into_iter().flatten() {
+            let kinds = dep["dep_kinds"].as_array().into_iter().flatten();
+            if kinds.clone().next().is_none() || kinds.clone().any(|k| k["kind"] != "dev") {
+                pending.extend(dep["pkg"].as_str());
+            }
+        }
+    }
+
+    let mut dirs = BTreeSet::new();
+    for package in packages {
+        let local = package["source"].is_null();
+        if !local
+            || !package["id"]
+                .as_str()
+                .is_some_and(|id| reached.contains(id))
+        {
+            continue;
+        }
+        if let Some(dir) = package["manifest_path"]
+            .as_str()
+            .and_then(|path| Path::new(path).parent())
+        {
+            dirs.insert(crate::canonicalize_path(dir)?);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Cargo configuration files a build in `project_root` may read, each with a name for the key
+///
+/// Cargo reads `.cargo/config.toml` (or `.cargo/config`) of every ancestor
+/// directory and of `$CARGO_HOME`; ancestors are named by their distance.
+fn cargo_config_files(project_root: &Path) -> Vec<(String, PathBuf)> {
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")));
+    let project_root =
+        crate::canonicalize_path(project_root).unwrap_or_else(|_| project_root.to_path_buf());
+    let ancestors = project_root
+        .ancestors()
+        .enumerate()
+        .map(|(depth, dir)| (depth.to_string(), dir.join(".cargo")));
+    let mut files = Vec::new();
+    for (name, dir) in ancestors.chain(cargo_home.map(|dir| ("cargo-home".to_string(), dir))) {
+        for file in ["config.toml", "config"] {
+            files.push((format!("{}/{}", name, file), dir.join(file)));
+        }
+    }
+    files
+}
+
+/// Hash an existing file by `name` and content
+fn hash_file(hasher: &mut Sha256, name: &str, path: &Path) -> Result<()> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    hasher.update(name.as_bytes());
+    hasher.update((content.len() as u64).to_le_bytes());
+    hasher.update(&content);
+    Ok(())
+}
+
+/// Hash every file of `dir` except build outputs and hidden files, by relative path and content
+fn hash_dir(hasher: &mut Sha256, dir: &Path) -> Result<()> {
+    let files = walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !should_skip_path(e.path().strip_prefix(dir).unwrap_or(e.path())))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file());
+    for file in files {
+        let relative = file.path().strip_prefix(dir).unwrap_or(file.path());
+        let content = std::fs::read(file.path())
+            .with_context(|| format!("Failed to read {}", file.path().display()))?;
+        hasher.update(crate::to_slash(relative).as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    Ok(())
+}
+
+/// `rustc -vV` of the toolchain the project builds with
+fn rustc_version(project_root: &Path) -> Result<String> {
+    let output = Command::new("rustc")
+        .current_dir(project_root)
+        .arg("-vV")
+        .output()
+        .context("Failed to run rustc")?;
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "rustc -vV failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A workspace with the contract in `token/`
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"token\"]\nresolver = \"2\"\n\
+             [workspace.dependencies]\nshared = { path = \"shared\" }\n",
+        );
+        write(
+            "token/Cargo.toml",
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\
+             [dependencies]\nshared.workspace = true\n\
+             [target.'cfg(target_arch = \"wasm32\")'.dependencies]\n\
+             wasm-only = { path = \"../wasm-only\" }\n\
+             [dev-dependencies]\ntesting = { path = \"../testing\" }\n",
+        );
+        write("token/src/lib.rs", "// token\n");
+        for name in ["shared", "wasm-only", "testing"] {
+            write(
+                &format!("{}/Cargo.toml", name),
+                &format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name),
+            );
+            write(&format!("{}/src/lib.rs", name), "");
+        }
+        dir
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let dir = project();
+        let config = CompileConfig::new(dir.path().join("token"));
+        let key = || cache_key(&config, &Compat::current()).unwrap();
+        let original = key();
+
+        // Build outputs, dev-dependencies and settings that don't reach the bytecode leave the key
+        // alone
+        std::fs::create_dir_all(dir.path().join("token/target")).unwrap();
+        std::fs::write(dir.path().join("token/target/lib.wasm"), "wasm").unwrap();
+        std::fs::write(dir.path().join("testing/src/lib.rs"), "// testing\n").unwrap();
+        let mut unrelated = config.clone();
+        unrelated.strict = true;
+        unrelated.artifacts.pretty_json = false;
+        assert_eq!(key(), original);
+        assert_eq!(cache_key(&unrelated, &Compat::current()).unwrap(), original);
+
+        let mut features = config.clone();
+        features.features.push("testnet".to_string());
+        assert_ne!(cache_key(&features, &Compat::current()).unwrap(), original);
+
+        // Assets, path dependencies of every kind, the workspace manifest and cargo configuration
+        // are sources too
+        let mut keys = BTreeSet::from([original]);
+        for (path, content) in [
+            ("token/src/abi.json", "[]"),
+            ("shared/src/lib.rs", "// shared\n"),
+            ("wasm-only/src/lib.rs", "// wasm\n"),
+            (
+                ".cargo/config.toml",
+                "[build]\nrustflags = [\"-Copt-level=s\"]\n",
+            ),
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+            assert!(keys.insert(key()), "{}", content);
+        }
+        let manifest = dir.path().join("Cargo.toml");
+        let workspace = std::fs::read_to_string(&manifest).unwrap();
+        std::fs::write(&manifest, workspace + "[profile.release]\nlto = true\n").unwrap();
+        assert!(keys.insert(key()));
+    }
+
+    #[test]
+    fn test_store_and_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let outputs = CompilationOutputs {
+            wasm: vec![0, 97, 115, 109],
+            rwasm: vec![0xef],
+        };
+        assert!(lookup(dir.path(), "key").is_none());
+
+        store(dir.path(), "key", &outputs).unwrap();
+        store(dir.path(), "key", &outputs).unwrap();
+        let cached = lookup(dir.path(), "key").unwrap();
+        assert_eq!((cached.wasm, cached.rwasm), (outputs.wasm, outputs.rwasm));
+        // Only the entry is left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -4,23 +4,49 @@ use eyre::{Context, Result};
 use fluentbase_sdk_derive_core::router::{process_router, Router};
 use proc_macro2::TokenStream as TokenStream2;
 use quote::ToTokens;
-use std::path::Path;
-use syn::{parse_file, visit::Visit, Attribute, ItemImpl};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use syn::{parse_file, visit::Visit, Attribute, ImplItem, ItemImpl, ItemTrait, TraitItem};
+
+/// A router implementation and the facet name it is exported under
+#[derive(Debug, Clone)]
+pub struct RouterFacet {
+    /// Trait name for `impl Trait for Type`, otherwise the type name
+    pub name: String,
+    pub router: Router,
+}
 
 /// Parses a Rust file and extracts all router implementations
-pub fn parse_routers(path: impl AsRef<Path>) -> Result<Vec<Router>> {
-    let path = path.as_ref();
-
-    // Read file content
-    let content = std::fs::read_to_string(path)
-        .with_context(|| format!("Failed to read file: {}", path.display()))?;
+///
+/// The modules the file declares (`mod name;`, following `#[path]`
+/// attributes) are scanned too, for routers and for the trait definitions
+/// whose default methods the routers inherit.
+pub fn parse_routers(path: impl AsRef<Path>) -> Result<Vec<RouterFacet>> {
+    parse_routers_cached(path.as_ref(), &mut SourceCache::default())
+}
 
-    // Parse Rust syntax
-    let ast = parse_file(&content).map_err(|e| eyre::eyre!("Failed to parse Rust file: {}", e))?;
+/// [`parse_routers`], parsing only the files that changed since they were cached
+pub(crate) fn parse_routers_cached(
+    path: &Path,
+    cache: &mut SourceCache,
+) -> Result<Vec<RouterFacet>> {
+    let files = module_tree(path, cache)?;
+
+    // Traits of the crate, for their default methods
+    let mut traits = TraitFinder::default();
+    for ast in &files {
+        traits.visit_file(ast);
+    }
 
     // Find routers
-    let mut finder = RouterFinder::new();
-    finder.visit_file(&ast);
+    let mut finder = RouterFinder::new(traits.traits);
+    for ast in &files {
+        finder.visit_file(ast);
+    }
 
     // Return first error if any occurred during processing
     if let Some(error) = finder.errors.into_iter().next() {
@@ -30,21 +56,173 @@ pub fn parse_routers(path: impl AsRef<Path>) -> Result<Vec<Router>> {
     Ok(finder.routers)
 }
 
+/// Parses the parameters of the contract's constructor
+///
+/// The constructor is the `constructor` method of the impl block the SDK's
+/// `#[constructor]` attribute declares; the attribute generates the `deploy`
+/// entrypoint that decodes them. It is described as a router of that single
+/// method, so the SDK maps its parameter types like those of any other
+/// method. `None` if no constructor taking arguments besides `self` is
+/// declared.
+pub fn parse_deploy(path: impl AsRef<Path>) -> Result<Option<Router>> {
+    parse_deploy_cached(path.as_ref(), &mut SourceCache::default())
+}
+
+/// [`parse_deploy`], parsing only the files that changed since they were cached
+pub(crate) fn parse_deploy_cached(path: &Path, cache: &mut SourceCache) -> Result<Option<Router>> {
+    let mut finder = ConstructorFinder::default();
+    for ast in module_tree(path, cache)? {
+        finder.visit_file(&ast);
+    }
+    finder
+        .constructor
+        .map(|(attr_tokens, impl_block)| {
+            process_router(attr_tokens, impl_block.to_token_stream())
+                .map_err(|e| eyre::eyre!("Failed to parse constructor parameters: {}", e))
+        })
+        .transpose()
+}
+
+/// What the sources declare for the ABI besides their routers
+#[derive(Debug, Clone, Default)]
+pub struct Declarations {
+    /// The constructor, if it takes parameters
+    pub deploy: Option<Router>,
+    /// Solidity custom errors, in declaration order
+    pub errors: Vec<CustomError>,
+}
+
+/// A Solidity custom error declared by a type deriving `SolidityError`
+#[derive(Debug, Clone)]
+pub struct CustomError {
+    /// Name of the struct or enum variant
+    pub name: String,
+    /// The fields as the parameters of a single router method
+    pub router: Router,
+    /// Tuple fields, which have no names in the ABI
+    pub unnamed: bool,
+}
+
+/// Parses the custom errors of the contract
+///
+/// A struct deriving `SolidityError` declares one error, an enum one per
+/// variant. Like the constructor, the fields are described as the parameters
+/// of a router method, so the SDK maps their types.
+pub fn parse_errors(path: impl AsRef<Path>) -> Result<Vec<CustomError>> {
+    parse_errors_cached(path.as_ref(), &mut SourceCache::default())
+}
+
+/// [`parse_errors`], parsing only the files that changed since they were cached
+pub(crate) fn parse_errors_cached(
+    path: &Path,
+    cache: &mut SourceCache,
+) -> Result<Vec<CustomError>> {
+    let mut finder = ErrorFinder::default();
+    for ast in module_tree(path, cache)? {
+        finder.visit_file(&ast);
+    }
+    finder
+        .errors
+        .into_iter()
+        .map(|(name, fields)| {
+            let params = fields.iter().enumerate().map(|(index, field)| {
+                let name = field
+                    .ident
+                    .clone()
+                    .unwrap_or_else(|| quote::format_ident!("_{}", index));
+                let ty = &field.ty;
+                quote::quote!(#name: #ty)
+            });
+            let impl_block =
+                quote::quote!(impl CustomError { pub fn error(&self, #(#params),*) {} });
+            let router = process_router(quote::quote!(mode = "solidity"), impl_block)
+                .map_err(|e| eyre::eyre!("Failed to parse the fields of error {}: {}", name, e))?;
+            Ok(CustomError {
+                unnamed: matches!(fields, syn::Fields::Unnamed(_)),
+                name,
+                router,
+            })
+        })
+        .collect()
+}
+
+/// Syntax trees of a source file and of the module files it declares, recursively, in declaration
+/// order
+pub(crate) fn module_tree(path: &Path, cache: &mut SourceCache) -> Result<Vec<Rc<syn::File>>> {
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        // `#[path]` attributes can point back at a file already scanned
+        if !visited.insert(path.canonicalize().unwrap_or_else(|_| path.clone())) {
+            continue;
+        }
+        let ast = match cache.parse(&path) {
+            Err(e) if !files.is_empty() => {
+                return Err(e.wrap_err(format!("Failed to parse module {}", path.display())))
+            }
+            ast => ast?,
+        };
+        pending.extend(module_files(&path, &ast).into_iter().rev());
+        files.push(ast);
+    }
+    Ok(files)
+}
+
+/// Parsed source files, reused while their content is unchanged
+#[derive(Default)]
+pub(crate) struct SourceCache {
+    /// Content hash and syntax tree by path
+    files: HashMap<PathBuf, (String, Rc<syn::File>)>,
+    /// Files parsed so far
+    pub(crate) parses: usize,
+}
+
+impl SourceCache {
+    fn parse(&mut self, path: &Path) -> Result<Rc<syn::File>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {}", path.display()))?;
+        let hash = hex::encode(Sha256::digest(content.as_bytes()));
+        if let Some((cached, ast)) = self.files.get(path) {
+            if *cached == hash {
+                return Ok(ast.clone());
+            }
+        }
+
+        self.parses += 1;
+        let ast = Rc::new(
+            parse_file(&content).map_err(|e| eyre::eyre!("Failed to parse Rust file: {}", e))?,
+        );
+        self.files.insert(path.to_path_buf(), (hash, ast.clone()));
+        Ok(ast)
+    }
+}
+
 /// Internal visitor for finding router implementations
 struct RouterFinder {
-    routers: Vec<Router>,
+    routers: Vec<RouterFacet>,
     errors: Vec<syn::Error>,
+    /// Trait definitions by name
+    traits: HashMap<String, ItemTrait>,
 }
 
 impl RouterFinder {
-    fn new() -> Self {
-        Self { routers: Vec::new(), errors: Vec::new() }
+    fn new(traits: HashMap<String, ItemTrait>) -> Self {
+        Self {
+            routers: Vec::new(),
+            errors: Vec::new(),
+            traits,
+        }
     }
 
     fn process_router_impl(&mut self, attr: &Attribute, impl_block: &ItemImpl) {
+        let impl_block = normalize_impl(impl_block, &self.traits);
         match extract_router_tokens(attr) {
             Ok(attr_tokens) => match process_router(attr_tokens, impl_block.to_token_stream()) {
-                Ok(router) => self.routers.push(router),
+                Ok(router) => {
+                    let name = unique_facet_name(&self.routers, facet_name(&impl_block));
+                    self.routers.push(RouterFacet { name, router });
+                }
                 Err(error) => self.errors.push(error),
             },
             Err(error) => self.errors.push(error),
@@ -68,6 +246,264 @@ impl<'ast> Visit<'ast> for RouterFinder {
     }
 }
 
+/// Finds the first `#[constructor]` impl whose constructor takes arguments, with the
+/// attribute's parameters
+#[derive(Default)]
+struct ConstructorFinder {
+    constructor: Option<(TokenStream2, ItemImpl)>,
+}
+
+impl<'ast> Visit<'ast> for ConstructorFinder {
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        if self.constructor.is_none() {
+            self.constructor = node
+                .attrs
+                .iter()
+                .find(|attr| is_constructor_attribute(attr))
+                .and_then(|attr| Some((constructor_tokens(attr), constructor_impl(node)?)));
+        }
+        syn::visit::visit_item_impl(self, node);
+    }
+}
+
+/// `#[constructor]` or `#[fluentbase_sdk::constructor]`
+fn is_constructor_attribute(attr: &Attribute) -> bool {
+    attr.path()
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "constructor")
+}
+
+/// Parameters of a constructor attribute; `mode = "solidity"`, the SDK's default, if it has none
+fn constructor_tokens(attr: &Attribute) -> TokenStream2 {
+    match &attr.meta {
+        syn::Meta::List(meta_list) if !meta_list.tokens.is_empty() => meta_list.tokens.clone(),
+        _ => quote::quote!(mode = "solidity"),
+    }
+}
+
+/// The `constructor` method of a `#[constructor]` impl block, alone in a copy of the block
+///
+/// The method is made public and its return type dropped, since only its
+/// parameters describe the constructor.
+fn constructor_impl(impl_block: &ItemImpl) -> Option<ItemImpl> {
+    let method = impl_block.items.iter().find_map(|item| match item {
+        ImplItem::Fn(method)
+            if method.sig.ident == "constructor" && method.sig.receiver().is_some() =>
+        {
+            Some(method)
+        }
+        _ => None,
+    })?;
+    if method.sig.inputs.len() < 2 {
+        return None;
+    }
+
+    let mut method = method.clone();
+    method.vis = syn::parse_quote!(pub);
+    method.sig.output = syn::ReturnType::Default;
+    let mut impl_block = impl_block.clone();
+    impl_block.attrs.clear();
+    impl_block.generics.where_clause = None;
+    impl_block.items = vec![ImplItem::Fn(method)];
+    Some(impl_block)
+}
+
+/// Collects the structs and enum variants of types deriving `SolidityError`
+#[derive(Default)]
+struct ErrorFinder {
+    errors: Vec<(String, syn::Fields)>,
+}
+
+impl<'ast> Visit<'ast> for ErrorFinder {
+    fn visit_item_struct(&mut self, node: &'ast syn::ItemStruct) {
+        if derives_solidity_error(&node.attrs) {
+            self.errors
+                .push((node.ident.to_string(), node.fields.clone()));
+        }
+        syn::visit::visit_item_struct(self, node);
+    }
+
+    fn visit_item_enum(&mut self, node: &'ast syn::ItemEnum) {
+        if derives_solidity_error(&node.attrs) {
+            for variant in &node.variants {
+                self.errors
+                    .push((variant.ident.to_string(), variant.fields.clone()));
+            }
+        }
+        syn::visit::visit_item_enum(self, node);
+    }
+}
+
+/// Whether `#[derive(...)]` lists `SolidityError`, by any path
+fn derives_solidity_error(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("derive"))
+        .any(|attr| {
+            attr.parse_args_with(
+                syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated,
+            )
+            .is_ok_and(|paths| {
+                paths.iter().any(|path| {
+                    path.segments
+                        .last()
+                        .is_some_and(|s| s.ident == "SolidityError")
+                })
+            })
+        })
+}
+
+/// Collects trait definitions, including those in nested modules
+#[derive(Default)]
+struct TraitFinder {
+    traits: HashMap<String, ItemTrait>,
+}
+
+impl<'ast> Visit<'ast> for TraitFinder {
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        self.traits.insert(node.ident.to_string(), node.clone());
+        syn::visit::visit_item_trait(self, node);
+    }
+}
+
+/// Files of the out-of-line modules (`mod name;`) declared in a source file, including in its
+/// inline modules
+fn module_files(path: &Path, ast: &syn::File) -> Vec<PathBuf> {
+    let Some(parent) = path.parent() else {
+        return Vec::new();
+    };
+    // lib.rs, main.rs and mod.rs own their directory; `foo.rs` owns `foo/`
+    let dir = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some("lib" | "main" | "mod") | None => parent.to_path_buf(),
+        Some(stem) => parent.join(stem),
+    };
+    let mut files = Vec::new();
+    collect_module_files(&ast.items, parent, &dir, &mut files);
+    files
+}
+
+/// `#[path]` attributes are relative to `path_dir`, other module files live in `dir`
+fn collect_module_files(
+    items: &[syn::Item],
+    path_dir: &Path,
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+) {
+    for item in items {
+        let syn::Item::Mod(module) = item else {
+            continue;
+        };
+        let name = module.ident.to_string();
+        let path_attribute = path_attribute(&module.attrs);
+        match &module.content {
+            Some((_, items)) => {
+                let nested = dir.join(path_attribute.unwrap_or(name));
+                collect_module_files(items, &nested, &nested, files);
+            }
+            None => {
+                let candidates = match path_attribute {
+                    Some(path) => vec![path_dir.join(path)],
+                    None => vec![
+                        dir.join(format!("{}.rs", name)),
+                        dir.join(&name).join("mod.rs"),
+                    ],
+                };
+                files.extend(candidates.into_iter().find(|candidate| candidate.is_file()));
+            }
+        }
+    }
+}
+
+/// Value of a `#[path = "..."]` attribute
+fn path_attribute(attrs: &[Attribute]) -> Option<String> {
+    attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("path"))
+        .and_then(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(path),
+                        ..
+                    }),
+                ..
+            }) => Some(path.value()),
+            _ => None,
+        })
+}
+
+/// Router impl block as the router macro should see it for ABI extraction
+///
+/// Default methods of the implemented trait that the impl does not override
+/// are added, and the where clause is dropped: bounds only matter to the
+/// generated code, not to the ABI.
+fn normalize_impl(impl_block: &ItemImpl, traits: &HashMap<String, ItemTrait>) -> ItemImpl {
+    let mut impl_block = impl_block.clone();
+    impl_block.generics.where_clause = None;
+
+    let trait_name = impl_block
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .map(|segment| segment.ident.to_string());
+    let Some(definition) = trait_name.and_then(|name| traits.get(&name)) else {
+        return impl_block;
+    };
+
+    let overridden: Vec<String> = impl_block
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(method) => Some(method.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect();
+    for item in &definition.items {
+        let TraitItem::Fn(method) = item else {
+            continue;
+        };
+        let Some(body) = &method.default else {
+            continue;
+        };
+        if overridden.contains(&method.sig.ident.to_string()) {
+            continue;
+        }
+        let mut sig = method.sig.clone();
+        sig.generics.where_clause = None;
+        impl_block.items.push(ImplItem::Fn(syn::ImplItemFn {
+            attrs: method.attrs.clone(),
+            vis: syn::Visibility::Inherited,
+            defaultness: None,
+            sig,
+            block: body.clone(),
+        }));
+    }
+    impl_block
+}
+
+/// Facet name of a router impl block
+fn facet_name(impl_block: &ItemImpl) -> String {
+    let ident = match (&impl_block.trait_, impl_block.self_ty.as_ref()) {
+        (Some((_, path, _)), _) => path.segments.last().map(|s| s.ident.to_string()),
+        (None, syn::Type::Path(ty)) => ty.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    };
+    ident.unwrap_or_else(|| "Router".to_string())
+}
+
+/// Disambiguate repeated facet names (e.g. two inherent impls on one type)
+fn unique_facet_name(existing: &[RouterFacet], name: String) -> String {
+    let taken = |candidate: &str| existing.iter().any(|f| f.name == candidate);
+    if !taken(&name) {
+        return name;
+    }
+    (2..)
+        .map(|i| format!("{}{}", name, i))
+        .find(|candidate| !taken(candidate))
+        .expect("unbounded range")
+}
+
 /// Checks if an attribute is a router attribute
 fn is_router_attribute(attr: &Attribute) -> bool {
     attr.path().is_ident("router")
@@ -159,7 +595,10 @@ mod tests {
     fn test_parse_routers_invalid_file_path() {
         let result = parse_routers("/non/existent/file.rs");
         assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Failed to read file"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to read file"));
     }
 
     #[test]
@@ -172,7 +611,10 @@ mod tests {
 
         let result = parse_routers(file.path());
         assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Failed to parse Rust file"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Failed to parse Rust file"));
     }
 
     #[test]
@@ -248,6 +690,8 @@ mod tests {
             Ok(routers) => {
                 // Should find 2 routers if SDK is available
                 tracing::info!("Found {} routers", routers.len());
+                let names: Vec<&str> = routers.iter().map(|r| r.name.as_str()).collect();
+                assert_eq!(names, vec!["API1", "API2"]);
             }
             Err(e) => {
                 // Expected during testing without SDK
@@ -255,4 +699,262 @@ mod tests {
             }
         }
     }
+
+    fn method_names(impl_block: &ItemImpl) -> Vec<String> {
+        impl_block
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ImplItem::Fn(method) => Some(method.sig.ident.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trait_default_methods() {
+        use syn::parse_quote;
+
+        let definition: ItemTrait = parse_quote! {
+            pub trait TokenAPI<SDK> {
+                fn balance(&self, owner: Address) -> U256;
+                fn decimals(&self) -> u8 {
+                    18
+                }
+                fn symbol(&self) -> String {
+                    "TKN".to_string()
+                }
+            }
+        };
+        let traits = HashMap::from([("TokenAPI".to_string(), definition)]);
+
+        let impl_block: ItemImpl = parse_quote! {
+            impl<SDK: SharedAPI> api::TokenAPI<SDK> for Token<SDK> {
+                fn balance(&self, owner: Address) -> U256 {
+                    U256::ZERO
+                }
+                fn symbol(&self) -> String {
+                    "TOKEN".to_string()
+                }
+            }
+        };
+        let normalized = normalize_impl(&impl_block, &traits);
+        assert_eq!(
+            method_names(&normalized),
+            vec!["balance", "symbol", "decimals"]
+        );
+
+        // Inherent impls and unknown traits are kept as they are
+        let inherent: ItemImpl = parse_quote!(
+            impl<SDK: SharedAPI> Token<SDK> {
+                pub fn mint(&mut self) {}
+            }
+        );
+        assert_eq!(
+            method_names(&normalize_impl(&inherent, &traits)),
+            vec!["mint"]
+        );
+    }
+
+    #[test]
+    fn test_generic_router_impls() {
+        use syn::parse_quote;
+
+        let impl_block: ItemImpl = parse_quote! {
+            impl<'a, SDK, S, const N: usize> VaultAPI<S> for Vault<'a, SDK, S, N>
+            where
+                SDK: SharedAPI + Clone + 'static,
+                S: Storage<Key = [u8; N]> + Default,
+                for<'b> &'b S: IntoIterator,
+            {
+                fn deposit(&mut self, amount: U256) {}
+                fn withdraw<T>(&mut self, amount: U256) -> bool where T: Into<U256> { true }
+            }
+        };
+        let normalized = normalize_impl(&impl_block, &HashMap::new());
+        assert!(normalized.generics.where_clause.is_none());
+        assert_eq!(normalized.generics.params.len(), 4);
+        assert_eq!(method_names(&normalized), vec!["deposit", "withdraw"]);
+        assert_eq!(facet_name(&normalized), "VaultAPI");
+    }
+
+    #[test]
+    fn test_traits_of_declared_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("api")).unwrap();
+        std::fs::write(
+            src.join("lib.rs"),
+            "mod api;\nmod inline { pub trait Inline {} }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            src.join("api.rs"),
+            "pub mod erc20;\npub trait Ownable { fn owner(&self) -> Address { Address::ZERO } }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            src.join("api").join("erc20.rs"),
+            "pub trait Erc20 { fn decimals(&self) -> u8 { 18 } }\n",
+        )
+        .unwrap();
+
+        let mut finder = TraitFinder::default();
+        for ast in module_tree(&src.join("lib.rs"), &mut SourceCache::default()).unwrap() {
+            finder.visit_file(&ast);
+        }
+        let mut names: Vec<&str> = finder.traits.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["Erc20", "Inline", "Ownable"]);
+    }
+
+    #[test]
+    fn test_module_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("handlers")).unwrap();
+        std::fs::create_dir_all(src.join("generated")).unwrap();
+        std::fs::write(
+            src.join("lib.rs"),
+            "mod handlers;\n#[path = \"generated/api.rs\"]\nmod api;\nmod inline { mod nested; }\n\
+             mod missing;\n",
+        )
+        .unwrap();
+        std::fs::write(src.join("handlers.rs"), "pub mod admin;\n").unwrap();
+        std::fs::write(src.join("handlers").join("admin.rs"), "pub struct Admin;\n").unwrap();
+        // Points back at the crate root, which is scanned once
+        std::fs::write(
+            src.join("generated").join("api.rs"),
+            "#[path = \"../lib.rs\"]\nmod root;\npub struct Api;\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(src.join("inline")).unwrap();
+        std::fs::write(src.join("inline").join("nested.rs"), "pub struct Nested;\n").unwrap();
+
+        let files = module_tree(&src.join("lib.rs"), &mut SourceCache::default()).unwrap();
+        let structs: Vec<String> = files
+            .iter()
+            .flat_map(|ast| &ast.items)
+            .filter_map(|item| match item {
+                syn::Item::Struct(item) => Some(item.ident.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(files.len(), 5);
+        assert_eq!(structs, ["Admin", "Api", "Nested"]);
+
+        std::fs::write(src.join("handlers").join("admin.rs"), "pub struct Admin\n").unwrap();
+        let Err(error) = module_tree(&src.join("lib.rs"), &mut SourceCache::default()) else {
+            panic!("expected a parse error");
+        };
+        assert!(format!("{:#}", error).contains("admin.rs"));
+    }
+
+    #[test]
+    fn test_constructor_impl() {
+        use syn::parse_quote;
+
+        let contract: ItemImpl = parse_quote! {
+            #[constructor(mode = "solidity")]
+            impl<SDK: SharedAPI> Token<SDK> where SDK: Clone {
+                pub fn new(sdk: SDK) -> Self { Self { sdk } }
+                fn constructor(&mut self, owner: Address, supply: U256) -> bool { true }
+            }
+        };
+        let constructor = constructor_impl(&contract).unwrap();
+        assert!(constructor.attrs.is_empty());
+        assert!(constructor.generics.where_clause.is_none());
+        let [ImplItem::Fn(method)] = constructor.items.as_slice() else {
+            panic!("expected only the constructor method");
+        };
+        assert!(matches!(method.vis, syn::Visibility::Public(_)));
+        assert_eq!(method.sig.inputs.len(), 3);
+        assert!(matches!(method.sig.output, syn::ReturnType::Default));
+
+        let without_args: ItemImpl = parse_quote!(
+            #[constructor]
+            impl<SDK: SharedAPI> Token<SDK> {
+                pub fn constructor(&self) {}
+            }
+        );
+        assert!(constructor_impl(&without_args).is_none());
+        assert_eq!(
+            constructor_tokens(&without_args.attrs[0]).to_string(),
+            quote::quote!(mode = "solidity").to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_deploy() {
+        let file = create_test_file(
+            r#"
+            pub struct Token<SDK> { sdk: SDK }
+
+            #[fluentbase_sdk::constructor(mode = "solidity")]
+            impl<SDK: SharedAPI> Token<SDK> {
+                pub fn constructor(&mut self, supply: U256) {}
+            }
+        "#,
+        );
+        assert!(parse_deploy(file.path()).unwrap().is_some());
+
+        // `deploy` of an impl without the constructor declaration is not the constructor
+        let file = create_test_file(
+            r#"
+            impl Factory {
+                pub fn deploy(&mut self, code: Bytes, salt: U256) -> Address { todo!() }
+            }
+        "#,
+        );
+        assert!(parse_deploy(file.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let file = create_test_file(
+            r#"
+            #[derive(Debug, SolidityError)]
+            pub struct Unauthorized { caller: Address }
+
+            #[derive(fluentbase_sdk::derive::SolidityError)]
+            pub enum TokenError {
+                InsufficientBalance { available: U256, required: U256 },
+                Paused,
+                Expired(u64),
+            }
+
+            #[derive(Debug)]
+            pub struct NotAnError { code: u32 }
+        "#,
+        );
+        let errors = parse_errors(file.path()).unwrap();
+        let names: Vec<(&str, bool)> = errors
+            .iter()
+            .map(|e| (e.name.as_str(), e.unnamed))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Unauthorized", false),
+                ("InsufficientBalance", false),
+                ("Paused", false),
+                ("Expired", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_facet_name() {
+        use syn::parse_quote;
+
+        let trait_impl: ItemImpl = parse_quote!(
+            impl<SDK: SharedAPI> api::TokenAPI for Token<SDK> {}
+        );
+        assert_eq!(facet_name(&trait_impl), "TokenAPI");
+
+        let inherent_impl: ItemImpl = parse_quote!(
+            impl<SDK: SharedAPI> Token<SDK> {}
+        );
+        assert_eq!(facet_name(&inherent_impl), "Token");
+    }
 }
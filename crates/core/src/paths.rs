@@ -0,0 +1,61 @@
+//! Platform-aware path handling
+
+use eyre::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Canonicalize a path
+///
+/// On Windows `std::fs::canonicalize` returns verbatim paths (`\\?\C:\...`)
+/// that cargo, git and Docker reject, so the prefix is stripped.
+pub fn canonicalize_path(path: &Path) -> Result<PathBuf> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve path: {}", path.display()))?;
+
+    Ok(match canonical.to_str() {
+        Some(s) => PathBuf::from(strip_verbatim_prefix(s)),
+        None => canonical,
+    })
+}
+
+/// Path as a string with forward slashes, as used in archives and metadata
+pub fn to_slash(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Strip the `\\?\` verbatim prefix from a Windows path string
+pub(crate) fn strip_verbatim_prefix(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = path.strip_prefix(r"\\?\") {
+        local.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_verbatim_prefix() {
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\C:\Users\dev\token"),
+            r"C:\Users\dev\token"
+        );
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\UNC\server\share\token"),
+            r"\\server\share\token"
+        );
+        assert_eq!(strip_verbatim_prefix("/home/dev/token"), "/home/dev/token");
+    }
+
+    #[test]
+    fn test_canonicalize_path() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let canonical = canonicalize_path(dir.path()).unwrap();
+        assert!(canonical.is_absolute());
+        assert!(!canonical.to_string_lossy().starts_with(r"\\?\"));
+    }
+}
@@ -0,0 +1,236 @@
+//! Read-only checks of built artifacts before they are deployed or released
+//!
+//! Re-hashes the bytecode in an artifacts directory and the project sources
+//! and compares them with metadata.json, so an artifact that was modified or
+//! built from older sources is not shipped under metadata that no longer
+//! describes it.
+
+use crate::{
+    artifacts::deploy::DEPLOY_FILE,
+    builder::{hash_bytes, hash_sources},
+    compat::{Compat, UNVERSIONED},
+    HashAlgorithm,
+};
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Why artifacts can't be deployed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrecheckIssueKind {
+    /// A file listed in metadata.json does not exist
+    Missing,
+    /// A file does not match the hash recorded in metadata.json
+    Modified,
+    /// The project sources changed since the artifacts were built
+    StaleSources,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PrecheckIssue {
+    pub kind: PrecheckIssueKind,
+    /// File relative to the artifacts directory, or the project root for sources
+    pub file: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for PrecheckIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+/// Result of checking an artifacts directory against its metadata.json
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct PrecheckReport {
+    pub contract: String,
+    pub issues: Vec<PrecheckIssue>,
+}
+
+impl PrecheckReport {
+    pub fn passed(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Fail with every issue unless the artifacts match their metadata
+    pub fn ensure_passed(&self) -> Result<()> {
+        if self.passed() {
+            return Ok(());
+        }
+        let issues: Vec<String> = self
+            .issues
+            .iter()
+            .map(|issue| format!("  - {}", issue))
+            .collect();
+        Err(eyre::eyre!(
+            "Artifacts of {} are stale or modified; rebuild before deploying:\n{}",
+            self.contract,
+            issues.join("\n")
+        ))
+    }
+}
+
+/// Compare the artifacts in `artifacts_dir` and the sources in `project_root` with metadata.json
+///
+/// Nothing is built or written.
+pub fn precheck(project_root: &Path, artifacts_dir: &Path) -> Result<PrecheckReport> {
+    let metadata_path = artifacts_dir.join("metadata.json");
+    let metadata: Value = serde_json::from_str(
+        &std::fs::read_to_string(&metadata_path)
+            .with_context(|| format!("Failed to read {}", metadata_path.display()))?,
+    )
+    .with_context(|| format!("Failed to parse {}", metadata_path.display()))?;
+
+    let mut issues = Vec::new();
+    let mut issue = |kind, file: &str, message: String| {
+        issues.push(PrecheckIssue {
+            kind,
+            file: file.to_string(),
+            message,
+        })
+    };
+
+    // Hashes are streamed; the wasm hash is kept to check deploy.json against
+    let mut wasm_hash = None;
+    for key in ["wasm", "rwasm"] {
+        let info = &metadata["bytecode"][key];
+        let (Some(file), Some(recorded)) = (info["path"].as_str(), info["hash"].as_str()) else {
+            continue;
+        };
+        let path = artifacts_dir.join(file);
+        if !path.is_file() {
+            issue(
+                PrecheckIssueKind::Missing,
+                file,
+                "listed in metadata.json but not found".to_string(),
+            );
+            continue;
+        }
+        let actual = format!("sha256:{}", HashAlgorithm::Sha256.hash_file(&path)?);
+        if actual != recorded {
+            issue(
+                PrecheckIssueKind::Modified,
+                file,
+                format!("hash is {}, metadata.json records {}", actual, recorded),
+            );
+        }
+        if key == "wasm" {
+            wasm_hash = Some(actual);
+        }
+    }
+
+    // Written since deploy.json exists; older artifacts don't have it
+    let deploy_path = artifacts_dir.join(DEPLOY_FILE);
+    if let (Some(wasm_hash), Ok(content)) = (&wasm_hash, std::fs::read_to_string(&deploy_path)) {
+        let deploy: Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", deploy_path.display()))?;
+        let init_code = deploy["init_code"]
+            .as_str()
+            .unwrap_or_default()
+            .trim_start_matches("0x");
+        let init_code_hash = hex::decode(init_code)
+            .ok()
+            .map(|code| format!("sha256:{}", hash_bytes(&code)));
+        if init_code_hash.as_ref() != Some(wasm_hash) {
+            issue(
+                PrecheckIssueKind::Modified,
+                DEPLOY_FILE,
+                "init code differs from lib.wasm".to_string(),
+            );
+        }
+    }
+
+    if let Some(recorded) = metadata["source_tree_hash"].as_str() {
+        // Hashed the way the builder of the artifacts did
+        let compat =
+            Compat::for_version(metadata["builder_version"].as_str().unwrap_or(UNVERSIONED))?;
+        let actual = format!("sha256:{}", hash_sources(project_root, &compat)?);
+        if actual != recorded {
+            issue(
+                PrecheckIssueKind::StaleSources,
+                ".",
+                format!(
+                    "sources hash to {}, the artifacts were built from {}",
+                    actual, recorded
+                ),
+            );
+        }
+    }
+
+    Ok(PrecheckReport {
+        contract: metadata["contract"]["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        issues,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::calculate_source_hash;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn write_artifacts(project: &Path, artifacts: &Path, wasm: &[u8]) {
+        std::fs::write(project.join("Cargo.toml"), "[package]\nname = \"token\"\n").unwrap();
+        std::fs::create_dir_all(artifacts).unwrap();
+        std::fs::write(artifacts.join("lib.wasm"), wasm).unwrap();
+        std::fs::write(artifacts.join("lib.rwasm"), b"rwasm").unwrap();
+        std::fs::write(
+            artifacts.join(DEPLOY_FILE),
+            json!({"init_code": format!("0x{}", hex::encode(wasm))}).to_string(),
+        )
+        .unwrap();
+        let metadata = json!({
+            "contract": {"name": "token"},
+            "builder_version": crate::VERSION,
+            "bytecode": {
+                "wasm": {"hash": format!("sha256:{}", hash_bytes(wasm)), "path": "lib.wasm"},
+                "rwasm": {"hash": format!("sha256:{}", hash_bytes(b"rwasm")), "path": "lib.rwasm"},
+            },
+            "source_tree_hash": format!("sha256:{}", calculate_source_hash(project).unwrap()),
+        });
+        std::fs::write(artifacts.join("metadata.json"), metadata.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_precheck() {
+        let dir = TempDir::new().unwrap();
+        let artifacts = dir.path().join("out/token.wasm");
+        write_artifacts(dir.path(), &artifacts, b"\0asm");
+
+        let report = precheck(dir.path(), &artifacts).unwrap();
+        assert!(report.passed(), "{:?}", report.issues);
+        assert!(report.ensure_passed().is_ok());
+
+        std::fs::write(artifacts.join("lib.wasm"), b"\0asm2").unwrap();
+        std::fs::remove_file(artifacts.join("lib.rwasm")).unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn changed() {}").unwrap();
+
+        let report = precheck(dir.path(), &artifacts).unwrap();
+        let issues: Vec<(PrecheckIssueKind, &str)> = report
+            .issues
+            .iter()
+            .map(|i| (i.kind, i.file.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (PrecheckIssueKind::Modified, "lib.wasm"),
+                (PrecheckIssueKind::Missing, "lib.rwasm"),
+                (PrecheckIssueKind::Modified, DEPLOY_FILE),
+                (PrecheckIssueKind::StaleSources, "."),
+            ]
+        );
+        assert!(report
+            .ensure_passed()
+            .unwrap_err()
+            .to_string()
+            .contains("lib.rwasm: listed in metadata.json"));
+    }
+}
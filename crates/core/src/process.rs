@@ -0,0 +1,261 @@
+//! Running external commands and slow operations with timeouts
+
+use crate::cleanup::terminate_on_cleanup;
+use eyre::{Context, Result};
+use std::{
+    io::{BufRead, BufReader, Read},
+    process::{Command, Output, Stdio},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+/// Errors from running bounded operations
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessError {
+    /// The operation did not finish in time (the process was killed)
+    #[error("{operation} timed out after {timeout:?}")]
+    TimedOut {
+        operation: String,
+        timeout: Duration,
+    },
+}
+
+/// Run a command to completion, capturing its output
+///
+/// The child is killed and [`ProcessError::TimedOut`] returned if it runs
+/// longer than `timeout`. It is also terminated if pending cleanups run.
+pub fn output_with_timeout(
+    cmd: &mut Command,
+    operation: &str,
+    timeout: Option<Duration>,
+) -> Result<Output> {
+    output_with_timeout_lines(cmd, operation, timeout, |_| {})
+}
+
+/// [`output_with_timeout`], passing each line of stderr to `on_stderr_line` as it is written
+///
+/// Lines are handed over on the calling thread; the full stderr is still captured.
+pub(crate) fn output_with_timeout_lines(
+    cmd: &mut Command,
+    operation: &str,
+    timeout: Option<Duration>,
+    mut on_stderr_line: impl FnMut(&str),
+) -> Result<Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to execute {}", operation))?;
+
+    let guard = terminate_on_cleanup(child.id());
+
+    // Drain pipes on separate threads so a chatty child can't block on a full pipe
+    let (lines_tx, lines) = mpsc::channel();
+    let stdout = drain(child.stdout.take());
+    let stderr = drain_lines(child.stderr.take(), lines_tx);
+
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let status = loop {
+        lines.try_iter().for_each(|line| on_stderr_line(&line));
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            let _ = child.kill();
+            let _ = child.wait();
+            guard.disarm();
+            finish_readers([stdout, stderr], operation);
+            return Err(ProcessError::TimedOut {
+                operation: operation.to_string(),
+                timeout: timeout.unwrap_or_default(),
+            }
+            .into());
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    guard.disarm();
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    lines.try_iter().for_each(|line| on_stderr_line(&line));
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Run an in-process operation, giving up after `timeout`
+///
+/// The work runs on a separate thread. Threads can't be killed, so on timeout
+/// the thread is left to finish in the background and its result is discarded.
+pub fn run_with_timeout<T: Send + 'static>(
+    operation: &str,
+    timeout: Option<Duration>,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return Ok(f());
+    };
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok(result) => Ok(result),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(ProcessError::TimedOut {
+            operation: operation.to_string(),
+            timeout,
+        }
+        .into()),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(eyre::eyre!("{} panicked", operation)),
+    }
+}
+
+/// How long the pipe readers of a killed process get to see the pipes close
+const READER_GRACE: Duration = Duration::from_secs(1);
+
+/// Join the pipe readers of a killed process, or detach the ones still blocked
+///
+/// Processes the child started may outlive it and keep the pipes open; their
+/// readers then exit only once those processes do, so they are not waited for.
+fn finish_readers(readers: [std::thread::JoinHandle<Vec<u8>>; 2], operation: &str) {
+    let deadline = Instant::now() + READER_GRACE;
+    while readers.iter().any(|r| !r.is_finished()) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    for reader in readers {
+        if reader.is_finished() {
+            let _ = reader.join();
+        } else {
+            tracing::debug!(
+                "Output of {} is still open after it was killed; detaching its reader",
+                operation
+            );
+        }
+    }
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// [`drain`], also sending each line as soon as it is complete
+fn drain_lines(
+    pipe: Option<impl Read + Send + 'static>,
+    lines: mpsc::Sender<String>,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(pipe) = pipe {
+            let mut reader = BufReader::new(pipe);
+            let mut line = Vec::new();
+            while reader.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+                let _ = lines.send(
+                    String::from_utf8_lossy(&line)
+                        .trim_end_matches(['\r', '\n'])
+                        .to_string(),
+                );
+                buf.append(&mut line);
+            }
+        }
+        buf
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_timeout(err: &eyre::Report) -> bool {
+        matches!(
+            err.downcast_ref::<ProcessError>(),
+            Some(ProcessError::TimedOut { .. })
+        )
+    }
+
+    #[test]
+    fn test_output_with_timeout() {
+        let output = output_with_timeout(
+            Command::new("git").arg("--version"),
+            "git --version",
+            Some(Duration::from_secs(30)),
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("git version"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_timeout_kills_process() {
+        let start = Instant::now();
+        let err = output_with_timeout(
+            Command::new("sleep").arg("30"),
+            "sleep",
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap_err();
+
+        assert!(is_timeout(&err));
+        assert_eq!(err.to_string(), "sleep timed out after 100ms");
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_timeout_with_orphaned_pipes() {
+        // The background sleep outlives the killed shell and keeps its pipes open
+        let start = Instant::now();
+        let err = output_with_timeout(
+            Command::new("sh").args(["-c", "sleep 5 & sleep 30"]),
+            "sh",
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap_err();
+
+        assert!(is_timeout(&err));
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_stderr_lines() {
+        let mut lines = Vec::new();
+        let output = output_with_timeout_lines(
+            Command::new("sh").args(["-c", "echo one >&2; echo out; printf 'two\\r\\nthree' >&2"]),
+            "sh",
+            Some(Duration::from_secs(30)),
+            |line| lines.push(line.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(lines, ["one", "two", "three"]);
+        assert_eq!(output.stderr, b"one\ntwo\r\nthree");
+        assert_eq!(output.stdout, b"out\n");
+    }
+
+    #[test]
+    fn test_run_with_timeout() {
+        assert_eq!(
+            run_with_timeout("add", Some(Duration::from_secs(5)), || 2 + 2).unwrap(),
+            4
+        );
+        assert_eq!(run_with_timeout("add", None, || 2 + 2).unwrap(), 4);
+
+        let err = run_with_timeout("slow", Some(Duration::from_millis(10)), || {
+            std::thread::sleep(Duration::from_millis(500))
+        })
+        .unwrap_err();
+        assert!(is_timeout(&err));
+    }
+}
@@ -0,0 +1,260 @@
+//! Contract registry of a multi-contract project (`contracts.toml`)
+//!
+//! Declares every contract of a monorepo once, at the workspace root, so
+//! `compile-all`, `verify-all` and `deploy` don't take per-contract paths:
+//!
+//! ```toml
+//! [[contract]]
+//! name = "token"
+//! path = "contracts/token"
+//! networks = ["testnet", "mainnet"]   # optional, all networks if omitted
+//! verify = "required"                 # required (default), optional or skip
+//! constructor_args = "0x..."          # optional, ABI encoded, used by deploy
+//! # or constructor_values = ["1000", "0x..."], encoded against the constructor of the ABI
+//! ```
+//!
+//! Contracts are built and deployed in the order they are declared.
+
+use crate::artifacts::{deploy::encode_constructor_args, Param};
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// File name of the registry at the workspace root
+pub const REGISTRY_FILE: &str = "contracts.toml";
+
+/// Whether a contract's deployments must verify
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyPolicy {
+    /// A mismatch fails `verify-all` and `deploy`
+    #[default]
+    Required,
+    /// A mismatch is reported but does not fail
+    Optional,
+    /// Deployments are not verified
+    Skip,
+}
+
+/// A `[[contract]]` entry of the registry
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryEntry {
+    pub name: String,
+    /// Contract directory, relative to the registry
+    pub path: PathBuf,
+    /// Networks the contract is deployed to; empty for all
+    #[serde(default)]
+    pub networks: Vec<String>,
+    #[serde(default)]
+    pub verify: VerifyPolicy,
+    /// Hex encoded constructor arguments passed on deployment
+    pub constructor_args: Option<String>,
+    /// Constructor arguments as values, e.g. `1000` or `0xabc...`
+    pub constructor_values: Option<Vec<String>>,
+}
+
+impl RegistryEntry {
+    /// Whether the contract is deployed to `network`
+    pub fn deploys_to(&self, network: &str) -> bool {
+        self.networks.is_empty() || self.networks.iter().any(|n| n == network)
+    }
+
+    /// ABI-encoded constructor arguments for a constructor taking `inputs`
+    pub fn encoded_constructor_args(&self, inputs: &[Param]) -> Result<Vec<u8>> {
+        match (&self.constructor_args, &self.constructor_values) {
+            (Some(args), _) => hex::decode(args.trim_start_matches("0x"))
+                .with_context(|| format!("constructor_args of {} is not valid hex", self.name)),
+            (None, Some(values)) => encode_constructor_args(inputs, values)
+                .with_context(|| format!("Invalid constructor_values of {}", self.name)),
+            (None, None) if !inputs.is_empty() => Err(eyre::eyre!(
+                "{} takes constructor arguments; set constructor_args or constructor_values in {}",
+                self.name,
+                REGISTRY_FILE
+            )),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryFile {
+    #[serde(default, rename = "contract")]
+    contracts: Vec<RegistryEntry>,
+}
+
+/// Contracts declared in a `contracts.toml`
+#[derive(Debug, Clone)]
+pub struct ContractRegistry {
+    /// Directory containing `contracts.toml`
+    pub root: PathBuf,
+    /// Contracts in declaration order
+    pub contracts: Vec<RegistryEntry>,
+}
+
+impl ContractRegistry {
+    /// Read `contracts.toml` in `root`
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(REGISTRY_FILE);
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&content, root)
+            .with_context(|| format!("Invalid contract registry {}", path.display()))
+    }
+
+    /// Load the registry of the closest directory at or above `start` that has one
+    pub fn find(start: &Path) -> Result<Option<Self>> {
+        start
+            .ancestors()
+            .find(|dir| dir.join(REGISTRY_FILE).is_file())
+            .map(Self::load)
+            .transpose()
+    }
+
+    fn parse(content: &str, root: &Path) -> Result<Self> {
+        let file: RegistryFile = toml::from_str(content)?;
+        let mut names = BTreeSet::new();
+        for entry in &file.contracts {
+            if !names.insert(entry.name.as_str()) {
+                return Err(eyre::eyre!("Contract {} is declared twice", entry.name));
+            }
+            if entry.constructor_args.is_some() && entry.constructor_values.is_some() {
+                return Err(eyre::eyre!(
+                    "Contract {} sets both constructor_args and constructor_values",
+                    entry.name
+                ));
+            }
+            if entry.path.is_absolute() {
+                return Err(eyre::eyre!(
+                    "Path of contract {} must be relative to the registry",
+                    entry.name
+                ));
+            }
+        }
+        Ok(Self {
+            root: root.to_path_buf(),
+            contracts: file.contracts,
+        })
+    }
+
+    /// Directory of a contract
+    pub fn contract_dir(&self, entry: &RegistryEntry) -> PathBuf {
+        self.root.join(&entry.path)
+    }
+
+    /// Contracts named in `names` (all if empty) that deploy to `network`, in declaration order
+    pub fn select(&self, names: &[String], network: Option<&str>) -> Result<Vec<&RegistryEntry>> {
+        if let Some(unknown) = names
+            .iter()
+            .find(|name| !self.contracts.iter().any(|c| &c.name == *name))
+        {
+            return Err(eyre::eyre!(
+                "Contract {} is not declared in {}",
+                unknown,
+                REGISTRY_FILE
+            ));
+        }
+        Ok(self
+            .contracts
+            .iter()
+            .filter(|c| names.is_empty() || names.contains(&c.name))
+            .filter(|c| network.is_none_or(|network| c.deploys_to(network)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTRY: &str = r#"
+[[contract]]
+name = "token"
+path = "contracts/token"
+
+[[contract]]
+name = "bridge"
+path = "contracts/bridge"
+networks = ["testnet"]
+verify = "optional"
+constructor_args = "0x01"
+"#;
+
+    #[test]
+    fn test_registry_selection() {
+        let registry = ContractRegistry::parse(REGISTRY, Path::new("/repo")).unwrap();
+        assert_eq!(registry.contracts[1].verify, VerifyPolicy::Optional);
+        assert_eq!(
+            registry.contract_dir(&registry.contracts[0]),
+            Path::new("/repo/contracts/token")
+        );
+
+        let names = |selected: Vec<&RegistryEntry>| {
+            selected.iter().map(|c| c.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(registry.select(&[], Some("testnet")).unwrap()),
+            ["token", "bridge"]
+        );
+        assert_eq!(
+            names(registry.select(&[], Some("mainnet")).unwrap()),
+            ["token"]
+        );
+        assert_eq!(
+            names(registry.select(&["bridge".to_string()], None).unwrap()),
+            ["bridge"]
+        );
+        assert!(registry.select(&["vault".to_string()], None).is_err());
+    }
+
+    #[test]
+    fn test_registry_validation() {
+        let duplicate = format!(
+            "{}\n[[contract]]\nname = \"token\"\npath = \"other\"\n",
+            REGISTRY
+        );
+        assert!(ContractRegistry::parse(&duplicate, Path::new("/repo")).is_err());
+        assert!(ContractRegistry::parse(
+            "[[contract]]\nname = \"a\"\npath = \"/abs\"\n",
+            Path::new("/repo")
+        )
+        .is_err());
+        assert!(
+            ContractRegistry::parse("[[contract]]\nname = \"a\"\n", Path::new("/repo")).is_err()
+        );
+        let both = "[[contract]]\nname = \"a\"\npath = \"a\"\nconstructor_args = \"0x01\"\n\
+                    constructor_values = [\"1\"]\n";
+        assert!(ContractRegistry::parse(both, Path::new("/repo")).is_err());
+    }
+
+    #[test]
+    fn test_encoded_constructor_args() {
+        let registry = ContractRegistry::parse(
+            &format!(
+                "{}\n[[contract]]\nname = \"vault\"\npath = \"vault\"\n\
+                 constructor_values = [\"1000\"]\n",
+                REGISTRY
+            ),
+            Path::new("/repo"),
+        )
+        .unwrap();
+        let inputs: Vec<Param> =
+            serde_json::from_value(serde_json::json!([{"name": "supply", "type": "uint256"}]))
+                .unwrap();
+
+        let [token, bridge, vault] = registry.contracts.as_slice() else {
+            panic!("expected three contracts");
+        };
+        assert_eq!(bridge.encoded_constructor_args(&inputs).unwrap(), [1]);
+        let encoded = vault.encoded_constructor_args(&inputs).unwrap();
+        assert_eq!(encoded.len(), 32);
+        assert_eq!(&encoded[30..], [0x03, 0xe8]);
+        assert!(token.encoded_constructor_args(&inputs).is_err());
+        assert!(token.encoded_constructor_args(&[]).unwrap().is_empty());
+        assert!(vault.encoded_constructor_args(&[]).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Tagged releases with versioned artifacts
+
+use crate::{
+    builder::parse_contract_info,
+    changelog::{generate_changelog, ContractSnapshot},
+    config::Timeouts,
+    deployments::{Deployments, ReleaseRecord},
+    error::BuilderError,
+    git,
+    precheck::precheck,
+};
+use eyre::{Context, Result};
+use semver::Version;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Directory holding release artifacts, relative to the project root
+pub const RELEASES_DIR: &str = "out/releases";
+
+/// Changelog file updated on every release
+const CHANGELOG_FILE: &str = "CHANGELOG.md";
+
+/// Parse a release tag (`vX.Y.Z` or `X.Y.Z`) into a normalized tag and version
+pub fn parse_release_tag(tag: &str) -> Result<(String, Version)> {
+    let version = Version::parse(tag.strip_prefix('v').unwrap_or(tag))
+        .with_context(|| format!("Invalid release version '{}': expected vX.Y.Z", tag))?;
+    Ok((format!("v{}", version), version))
+}
+
+/// Directory for the artifacts of a release
+pub fn release_dir(project_root: &Path, tag: &str) -> PathBuf {
+    project_root.join(RELEASES_DIR).join(tag)
+}
+
+/// Check that the project is ready to be released as `tag`
+///
+/// The working tree must be clean, the tag must not exist yet and the
+/// Cargo.toml version must match the release version.
+pub fn check_release_preconditions(
+    project_root: &Path,
+    tag: &str,
+    timeouts: &Timeouts,
+) -> Result<()> {
+    let (tag, version) = parse_release_tag(tag)?;
+
+    match git::detect_git_info_with_timeout(project_root, timeouts.git)? {
+        None => return Err(BuilderError::NotAGitRepository.into()),
+        Some(info) if info.is_dirty => {
+            return Err(eyre::Report::new(BuilderError::GitDirty {
+                dirty_files: info.dirty_files_count,
+            })
+            .wrap_err("Commit or stash the changes before releasing"))
+        }
+        Some(_) => {}
+    }
+
+    if git::tag_exists(project_root, &tag, timeouts.git)? {
+        return Err(eyre::eyre!("Tag {} already exists", tag));
+    }
+
+    let contract = parse_contract_info(&project_root.join("Cargo.toml"))?;
+    if contract.version != version.to_string() {
+        return Err(eyre::eyre!(
+            "Cargo.toml version {} does not match release {}",
+            contract.version,
+            tag
+        ));
+    }
+
+    Ok(())
+}
+
+/// Find the most recent release directory older than `version`
+pub fn find_previous_release(project_root: &Path, version: &Version) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(project_root.join(RELEASES_DIR)).ok()?;
+
+    entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let (_, release_version) = parse_release_tag(&name).ok()?;
+            (release_version < *version).then_some((release_version, e.path()))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, path)| path)
+}
+
+/// Finish a release whose artifacts were built into `artifacts_dir`
+///
+/// Creates the annotated tag, prepends release notes to `CHANGELOG.md` and
+/// records the release in `deployments.json`. Both files are left for the
+/// user to commit.
+pub fn finalize_release(
+    project_root: &Path,
+    tag: &str,
+    artifacts_dir: &Path,
+    timeouts: &Timeouts,
+) -> Result<ReleaseRecord> {
+    let (tag, version) = parse_release_tag(tag)?;
+
+    // Don't tag artifacts that no longer match their metadata or the sources
+    precheck(project_root, artifacts_dir)?.ensure_passed()?;
+
+    let metadata_path = artifacts_dir.join("metadata.json");
+    let metadata: Value = serde_json::from_str(
+        &std::fs::read_to_string(&metadata_path)
+            .with_context(|| format!("Failed to read {}", metadata_path.display()))?,
+    )
+    .with_context(|| format!("Failed to parse {}", metadata_path.display()))?;
+
+    let contract = metadata["contract"]["name"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let rwasm_hash = metadata["bytecode"]["rwasm"]["hash"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let wasm_hash = metadata["bytecode"]["wasm"]["hash"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+
+    // Keep the lockfile next to the artifacts so later changelogs can diff dependencies
+    let cargo_lock = project_root.join("Cargo.lock");
+    if cargo_lock.exists() {
+        std::fs::copy(&cargo_lock, artifacts_dir.join("Cargo.lock"))?;
+    }
+
+    let commit = git::detect_git_info_with_timeout(project_root, timeouts.git)?
+        .map(|info| info.commit_hash)
+        .ok_or(BuilderError::NotAGitRepository)?;
+
+    git::create_annotated_tag(
+        project_root,
+        &tag,
+        &release_tag_message(&contract, &tag, &rwasm_hash, &wasm_hash),
+        timeouts.git,
+    )?;
+
+    let new = ContractSnapshot {
+        label: tag.clone(),
+        ..ContractSnapshot::from_artifacts_dir(artifacts_dir)?
+    };
+    let notes = match find_previous_release(project_root, &version) {
+        Some(previous) => {
+            let previous_dir = previous.join(artifacts_dir.file_name().unwrap_or_default());
+            let old = ContractSnapshot::from_artifacts_dir(&previous_dir)?;
+            generate_changelog(&old, &new).to_release_notes(&tag)
+        }
+        None => format!("## {}\n\nInitial release.\n", tag),
+    };
+    prepend_changelog(project_root, &notes)?;
+
+    let record = ReleaseRecord {
+        version: tag,
+        contract,
+        commit,
+        rwasm_hash,
+        wasm_hash,
+        artifacts_dir: artifacts_dir
+            .strip_prefix(project_root)
+            .unwrap_or(artifacts_dir)
+            .display()
+            .to_string(),
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+
+    let mut deployments = Deployments::load(project_root)?;
+    deployments.record_release(record.clone());
+    deployments.save(project_root)?;
+
+    Ok(record)
+}
+
+/// Message for the annotated release tag
+fn release_tag_message(contract: &str, tag: &str, rwasm_hash: &str, wasm_hash: &str) -> String {
+    format!(
+        "{} {}\n\nrwasm-hash: {}\nwasm-hash: {}\n",
+        contract, tag, rwasm_hash, wasm_hash
+    )
+}
+
+/// Insert release notes below the top-level heading of `CHANGELOG.md`
+fn prepend_changelog(project_root: &Path, notes: &str) -> Result<()> {
+    let path = project_root.join(CHANGELOG_FILE);
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+
+    let rest = existing
+        .strip_prefix("# Changelog")
+        .unwrap_or(&existing)
+        .trim_start();
+
+    let mut content = format!("# Changelog\n\n{}", notes);
+    if !rest.is_empty() {
+        content.push('\n');
+        content.push_str(rest);
+    }
+
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_release_tag() {
+        let (tag, version) = parse_release_tag("v1.2.3").unwrap();
+        assert_eq!(tag, "v1.2.3");
+        assert_eq!(version, Version::new(1, 2, 3));
+
+        assert_eq!(parse_release_tag("1.2.3").unwrap().0, "v1.2.3");
+        assert!(parse_release_tag("v1.2").is_err());
+        assert!(parse_release_tag("latest").is_err());
+    }
+
+    #[test]
+    fn test_find_previous_release() {
+        let dir = TempDir::new().unwrap();
+        for tag in ["v0.9.0", "v1.0.0", "v1.10.0", "v2.0.0", "notes"] {
+            std::fs::create_dir_all(release_dir(dir.path(), tag)).unwrap();
+        }
+
+        let previous = find_previous_release(dir.path(), &Version::new(1, 11, 0)).unwrap();
+        assert!(previous.ends_with("v1.10.0"));
+        assert!(find_previous_release(dir.path(), &Version::new(0, 9, 0)).is_none());
+    }
+
+    #[test]
+    fn test_prepend_changelog() {
+        let dir = TempDir::new().unwrap();
+
+        prepend_changelog(dir.path(), "## v1.0.0\n\nInitial release.\n").unwrap();
+        prepend_changelog(dir.path(), "## v1.1.0\n\nChanges.\n").unwrap();
+
+        let content = std::fs::read_to_string(dir.path().join(CHANGELOG_FILE)).unwrap();
+        assert_eq!(
+            content,
+            "# Changelog\n\n## v1.1.0\n\nChanges.\n\n## v1.0.0\n\nInitial release.\n"
+        );
+    }
+}
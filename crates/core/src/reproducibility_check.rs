@@ -0,0 +1,266 @@
+//! Reproducibility self-check
+//!
+//! Builds a contract twice and compares the bytecode, so non-determinism
+//! shows up before deployment rather than when verification fails. When the
+//! builds differ, the strings only one of them embeds (absolute paths, build
+//! times) usually name the cause.
+
+use crate::{
+    build,
+    builder::{hash_bytes, CompilationOutputs},
+    mismatch_report::{function_table, wasm_sections},
+    CompilationResult, CompileConfig, Sandbox,
+};
+use eyre::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// Shortest run of printable bytes reported as an embedded string
+const MIN_STRING_LEN: usize = 8;
+
+/// Embedded strings listed at most per build
+const MAX_EMBEDDED_STRINGS: usize = 50;
+
+/// Comparison of two builds of the same contract
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ReproducibilityDiff {
+    /// Whether both builds produced the same rWASM
+    pub reproducible: bool,
+    pub first: BuildDigest,
+    pub second: BuildDigest,
+    /// WASM sections whose contents differ, e.g. `data` or `custom:.debug_str`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<String>,
+    /// Functions whose bodies differ
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<String>,
+    /// Strings found in only one of the builds
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub embedded: Vec<EmbeddedString>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BuildDigest {
+    /// Where the build ran, e.g. `local` or `docker`
+    pub label: String,
+    pub wasm_hash: String,
+    pub rwasm_hash: String,
+}
+
+/// A string embedded in the bytecode of one build only
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct EmbeddedString {
+    pub kind: EmbeddedKind,
+    /// Label of the build containing it
+    pub build: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddedKind {
+    /// A file system path, e.g. of a source file in a panic message
+    Path,
+    /// A date or time of day
+    Timestamp,
+    Other,
+}
+
+/// Build `config`, then again into a fresh target directory, and compare the rWASM
+///
+/// Returns the first build, with its artifacts, and the comparison. Neither
+/// build uses the build cache and the second one runs at another path, so
+/// timestamps, leaked target paths and other non-determinism make them
+/// differ. Both build the sources in place; compare against a Docker build
+/// with [`compare_builds`] to catch leaked source paths.
+pub fn check_reproducibility(
+    config: &CompileConfig,
+) -> Result<(CompilationResult, ReproducibilityDiff)> {
+    let mut config = config.clone();
+    config.build_cache = None;
+    let first = build(&config)?;
+
+    let sandbox = Sandbox::create(
+        &std::env::temp_dir(),
+        &format!("fluent-builder-reproducibility-{}", std::process::id()),
+    )?;
+    let cleanup = sandbox.cleanup_guard();
+    sandbox.configure(&mut config);
+    // Only the bytecode is compared
+    config.artifacts.generate_abi = false;
+    config.artifacts.generate_interface = false;
+    config.artifacts.generate_metadata = false;
+    let second = build(&config);
+    cleanup.disarm();
+    sandbox.finish(true);
+
+    let diff = compare_builds("first", &first.outputs, "second", &second?.outputs);
+    Ok((first, diff))
+}
+
+/// Compare the outputs of two builds of the same contract
+pub fn compare_builds(
+    first_label: &str,
+    first: &CompilationOutputs,
+    second_label: &str,
+    second: &CompilationOutputs,
+) -> ReproducibilityDiff {
+    let digest = |label: &str, outputs: &CompilationOutputs| BuildDigest {
+        label: label.to_string(),
+        wasm_hash: hash_bytes(&outputs.wasm),
+        rwasm_hash: hash_bytes(&outputs.rwasm),
+    };
+    let mut diff = ReproducibilityDiff {
+        reproducible: first.rwasm == second.rwasm,
+        first: digest(first_label, first),
+        second: digest(second_label, second),
+        sections: Vec::new(),
+        functions: Vec::new(),
+        embedded: Vec::new(),
+    };
+    if diff.reproducible || first.wasm == second.wasm {
+        return diff;
+    }
+
+    let (a, b) = (Some(first.wasm.clone()), Some(second.wasm.clone()));
+    if let (Ok(a), Ok(b)) = (wasm_sections(&a), wasm_sections(&b)) {
+        for i in 0..a.len().max(b.len()) {
+            let (x, y) = (a.get(i), b.get(i));
+            if x != y {
+                diff.sections
+                    .extend(x.or(y).map(|section| section.name.clone()));
+            }
+        }
+    }
+    if let (Ok(a), Ok(b)) = (function_table(&a), function_table(&b)) {
+        let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+        diff.functions = names
+            .into_iter()
+            .filter(|name| a.get(*name) != b.get(*name))
+            .cloned()
+            .collect();
+    }
+
+    let (a, b) = (
+        embedded_strings(&first.wasm),
+        embedded_strings(&second.wasm),
+    );
+    for (label, only) in [
+        (first_label, a.difference(&b)),
+        (second_label, b.difference(&a)),
+    ] {
+        diff.embedded
+            .extend(only.take(MAX_EMBEDDED_STRINGS).map(|value| EmbeddedString {
+                kind: EmbeddedKind::of(value),
+                build: label.to_string(),
+                value: value.clone(),
+            }));
+    }
+    diff
+}
+
+impl EmbeddedKind {
+    fn of(value: &str) -> Self {
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let date = value.as_bytes().windows(10).any(|w| {
+            let w = String::from_utf8_lossy(w);
+            digits(&w[..4])
+                && &w[4..5] == "-"
+                && digits(&w[5..7])
+                && &w[7..8] == "-"
+                && digits(&w[8..])
+        });
+        let time = value.as_bytes().windows(8).any(|w| {
+            let w = String::from_utf8_lossy(w);
+            digits(&w[..2])
+                && &w[2..3] == ":"
+                && digits(&w[3..5])
+                && &w[5..6] == ":"
+                && digits(&w[6..])
+        });
+        if value.starts_with('/')
+            || value.contains(":\\")
+            || (value.contains('/') && value.contains(".rs"))
+        {
+            EmbeddedKind::Path
+        } else if date || time {
+            EmbeddedKind::Timestamp
+        } else {
+            EmbeddedKind::Other
+        }
+    }
+}
+
+/// Runs of printable ASCII in `bytes`
+fn embedded_strings(bytes: &[u8]) -> BTreeSet<String> {
+    bytes
+        .split(|b| !(0x20..0x7f).contains(b))
+        .filter(|run| run.len() >= MIN_STRING_LEN)
+        .map(|run| String::from_utf8_lossy(run).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outputs(data: &str) -> CompilationOutputs {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend([11, data.len() as u8]);
+        wasm.extend(data.as_bytes());
+        CompilationOutputs {
+            rwasm: wasm.clone(),
+            wasm,
+        }
+    }
+
+    #[test]
+    fn test_compare_builds() {
+        let same = compare_builds("first", &outputs("token"), "second", &outputs("token"));
+        assert!(same.reproducible);
+        assert_eq!(same.first.rwasm_hash, same.second.rwasm_hash);
+        assert!(same.sections.is_empty() && same.embedded.is_empty());
+
+        let first = outputs("\0panicked at /home/alice/token/src/lib.rs\0built 2024-01-15\0");
+        let second = outputs("\0panicked at /workspace/src/lib.rs:12\0built 2024-01-16\0");
+        let diff = compare_builds("local", &first, "docker", &second);
+        assert!(!diff.reproducible);
+        assert_eq!(diff.sections, ["data"]);
+        let embedded: Vec<(EmbeddedKind, &str)> = diff
+            .embedded
+            .iter()
+            .map(|s| (s.kind, s.build.as_str()))
+            .collect();
+        assert_eq!(
+            embedded,
+            [
+                (EmbeddedKind::Timestamp, "local"),
+                (EmbeddedKind::Path, "local"),
+                (EmbeddedKind::Timestamp, "docker"),
+                (EmbeddedKind::Path, "docker"),
+            ]
+        );
+        assert_eq!(
+            diff.embedded[1].value,
+            "panicked at /home/alice/token/src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_embedded_kind() {
+        assert_eq!(
+            EmbeddedKind::of("/root/.cargo/registry/src/lib.rs"),
+            EmbeddedKind::Path
+        );
+        assert_eq!(EmbeddedKind::of("C:\\Users\\token"), EmbeddedKind::Path);
+        assert_eq!(
+            EmbeddedKind::of("compiled at 12:30:05"),
+            EmbeddedKind::Timestamp
+        );
+        assert_eq!(
+            EmbeddedKind::of("insufficient balance"),
+            EmbeddedKind::Other
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Scaffolding of new contract projects
+
+use crate::{migrate::toolchain_file, DEFAULT_RUST_VERSION, SDK_REPOSITORY};
+use convert_case::{Case, Casing};
+use eyre::{Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Starting point of a new project
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProjectTemplate {
+    /// A router with a single function
+    #[default]
+    Blank,
+    /// A token with balances, allowances and transfers
+    Erc20,
+    /// A contract storing one value
+    Storage,
+}
+
+impl ProjectTemplate {
+    fn source(&self) -> &'static str {
+        match self {
+            ProjectTemplate::Blank => include_str!("../templates/blank.rs"),
+            ProjectTemplate::Erc20 => include_str!("../templates/erc20.rs"),
+            ProjectTemplate::Storage => include_str!("../templates/storage.rs"),
+        }
+    }
+}
+
+impl FromStr for ProjectTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "blank" => Ok(ProjectTemplate::Blank),
+            "erc20" => Ok(ProjectTemplate::Erc20),
+            "storage" => Ok(ProjectTemplate::Storage),
+            _ => Err(format!(
+                "unknown template '{}', expected erc20, storage or blank",
+                s
+            )),
+        }
+    }
+}
+
+/// Create the project `name` in `dir`, pinned to the SDK release `sdk_tag`
+///
+/// `dir` must not exist or be empty. Returns the created files, relative to `dir`.
+pub fn new_project(
+    dir: &Path,
+    name: &str,
+    template: ProjectTemplate,
+    sdk_tag: &str,
+) -> Result<Vec<PathBuf>> {
+    let files = project_files(name, template, sdk_tag)?;
+    if dir
+        .read_dir()
+        .is_ok_and(|mut entries| entries.next().is_some())
+    {
+        return Err(eyre::eyre!(
+            "{} already exists and is not empty",
+            dir.display()
+        ));
+    }
+
+    for (path, content) in &files {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Files of a new project, by path relative to the project root
+fn project_files(
+    name: &str,
+    template: ProjectTemplate,
+    sdk_tag: &str,
+) -> Result<Vec<(PathBuf, String)>> {
+    validate_name(name)?;
+    let manifest = include_str!("../templates/Cargo.toml.template")
+        .replace("__NAME__", name)
+        .replace("__SDK_REPOSITORY__", SDK_REPOSITORY)
+        .replace("__SDK_TAG__", sdk_tag);
+    let source = template
+        .source()
+        .replace("__CONTRACT__", &name.to_case(Case::Pascal));
+
+    Ok(vec![
+        (PathBuf::from("Cargo.toml"), manifest),
+        (
+            PathBuf::from("rust-toolchain.toml"),
+            toolchain_file(DEFAULT_RUST_VERSION),
+        ),
+        (PathBuf::from("src/lib.rs"), source),
+        (PathBuf::from(".gitignore"), "/target\n/out\n".to_string()),
+    ])
+}
+
+/// Package names cargo accepts that also make a valid contract type name
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(eyre::eyre!(
+            "Invalid project name '{}': use ASCII letters, digits, '-' and '_', starting with a \
+             letter",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_files() {
+        let files = project_files("my-token", ProjectTemplate::Erc20, "v0.4.1").unwrap();
+        let file = |path: &str| &files.iter().find(|(p, _)| p == Path::new(path)).unwrap().1;
+
+        let manifest: toml_edit::DocumentMut = file("Cargo.toml").parse().unwrap();
+        assert_eq!(manifest["package"]["name"].as_str(), Some("my-token"));
+        assert_eq!(
+            manifest["dependencies"]["fluentbase-sdk"]["tag"].as_str(),
+            Some("v0.4.1")
+        );
+        assert!(file("rust-toolchain.toml").contains(DEFAULT_RUST_VERSION));
+        assert!(file("src/lib.rs").contains("impl<SDK: SharedAPI> MyTokenAPI for MyToken<SDK>"));
+        assert!(!file("src/lib.rs").contains("__CONTRACT__"));
+
+        for template in [
+            ProjectTemplate::Blank,
+            ProjectTemplate::Erc20,
+            ProjectTemplate::Storage,
+        ] {
+            let files = project_files("token", template, "v0.4.1").unwrap();
+            syn::parse_file(&files[2].1).unwrap();
+        }
+
+        for name in ["", "1token", "my token", "../token"] {
+            assert!(
+                project_files(name, ProjectTemplate::Blank, "v0.4.1").is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_new_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("counter");
+        let files = new_project(&project, "counter", ProjectTemplate::Storage, "v0.4.1").unwrap();
+        assert_eq!(files.len(), 4);
+        assert!(project.join("src/lib.rs").is_file());
+
+        let error =
+            new_project(&project, "counter", ProjectTemplate::Storage, "v0.4.1").unwrap_err();
+        assert!(error.to_string().contains("not empty"));
+    }
+}
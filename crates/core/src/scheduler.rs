@@ -0,0 +1,258 @@
+//! Parallel batch builds
+//!
+//! Contracts of a batch build run on a fixed number of threads. A contract
+//! waits for the contracts of the batch it depends on and is skipped if one of
+//! them failed; everything else keeps building, so one failure doesn't hide
+//! the state of the other contracts. Concurrent cargo builds sharing a target
+//! directory are serialized by cargo's own lock on it, so the scheduler only
+//! overlaps the rest of the pipeline (rWASM compilation, ABI and artifacts).
+
+use eyre::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A contract of a batch build
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledBuild {
+    pub name: String,
+    /// Contracts of the batch that must be built first
+    pub dependencies: Vec<String>,
+}
+
+/// How a scheduled build ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatus {
+    Succeeded,
+    Failed,
+    /// Not built because a dependency failed
+    Skipped,
+}
+
+/// Outcome of a scheduled build
+#[derive(Debug)]
+pub struct BuildOutcome<R> {
+    pub name: String,
+    pub status: BuildStatus,
+    /// Value of a successful build
+    pub value: Option<R>,
+    /// Error of a failed build, or why it was skipped
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+/// Runs the builds of a batch on up to `jobs` threads
+#[derive(Debug, Clone, Copy)]
+pub struct BuildScheduler {
+    jobs: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Pending,
+    Running,
+    Done(BuildStatus),
+}
+
+impl BuildScheduler {
+    /// A scheduler running up to `jobs` builds at once (at least one)
+    pub fn new(jobs: usize) -> Self {
+        Self { jobs: jobs.max(1) }
+    }
+
+    /// Run `build` for every entry of `builds`, returning the outcomes in the same order
+    ///
+    /// Builds start in the given order as soon as their dependencies succeeded.
+    /// `build` receives the index of the entry.
+    pub fn run<R: Send>(
+        &self,
+        builds: &[ScheduledBuild],
+        build: impl Fn(usize) -> Result<R> + Sync,
+    ) -> Vec<BuildOutcome<R>> {
+        let states = Mutex::new(vec![State::Pending; builds.len()]);
+        let changed = Condvar::new();
+        let outcomes: Mutex<Vec<Option<BuildOutcome<R>>>> =
+            Mutex::new((0..builds.len()).map(|_| None).collect());
+        let index_of = |name: &str| builds.iter().position(|b| b.name == name);
+
+        let worker = || loop {
+            let mut guard = states.lock().unwrap();
+            let next = loop {
+                match next_build(builds, &guard, &index_of) {
+                    Next::Build(index) => break index,
+                    Next::Skip(index, dependency) => {
+                        guard[index] = State::Done(BuildStatus::Skipped);
+                        outcomes.lock().unwrap()[index] = Some(BuildOutcome {
+                            name: builds[index].name.clone(),
+                            status: BuildStatus::Skipped,
+                            value: None,
+                            error: Some(format!("dependency {} was not built", dependency)),
+                            duration: Duration::ZERO,
+                        });
+                        changed.notify_all();
+                    }
+                    Next::Wait => guard = changed.wait(guard).unwrap(),
+                    Next::Finished => return,
+                }
+            };
+            guard[next] = State::Running;
+            drop(guard);
+
+            let started = Instant::now();
+            let result = build(next);
+            let (status, value, error) = match result {
+                Ok(value) => (BuildStatus::Succeeded, Some(value), None),
+                Err(e) => (BuildStatus::Failed, None, Some(format!("{:#}", e))),
+            };
+            outcomes.lock().unwrap()[next] = Some(BuildOutcome {
+                name: builds[next].name.clone(),
+                status,
+                value,
+                error,
+                duration: started.elapsed(),
+            });
+            states.lock().unwrap()[next] = State::Done(status);
+            changed.notify_all();
+        };
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.jobs.min(builds.len()) {
+                scope.spawn(worker);
+            }
+        });
+
+        outcomes
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|outcome| outcome.expect("every build has an outcome"))
+            .collect()
+    }
+}
+
+enum Next<'a> {
+    Build(usize),
+    /// Build whose dependency did not succeed
+    Skip(usize, &'a str),
+    Wait,
+    Finished,
+}
+
+/// First pending build whose dependencies are done
+fn next_build<'a>(
+    builds: &'a [ScheduledBuild],
+    states: &[State],
+    index_of: &impl Fn(&str) -> Option<usize>,
+) -> Next<'a> {
+    let mut unfinished = false;
+    for (index, build) in builds.iter().enumerate() {
+        match states[index] {
+            State::Pending => unfinished = true,
+            State::Running => {
+                unfinished = true;
+                continue;
+            }
+            State::Done(_) => continue,
+        }
+        let mut ready = true;
+        for dependency in &build.dependencies {
+            match index_of(dependency).map(|i| states[i]) {
+                // Not part of the batch
+                None | Some(State::Done(BuildStatus::Succeeded)) => {}
+                Some(State::Done(_)) => return Next::Skip(index, dependency),
+                Some(_) => ready = false,
+            }
+        }
+        if ready {
+            return Next::Build(index);
+        }
+    }
+    if unfinished {
+        Next::Wait
+    } else {
+        Next::Finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Barrier,
+    };
+
+    fn scheduled(name: &str, dependencies: &[&str]) -> ScheduledBuild {
+        ScheduledBuild {
+            name: name.to_string(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_builds_run_in_parallel() {
+        let builds: Vec<_> = (0..4).map(|i| scheduled(&format!("c{}", i), &[])).collect();
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        // Each build waits for another one to start, so one build at a time never finishes
+        let started = Barrier::new(2);
+        let outcomes = BuildScheduler::new(2).run(&builds, |index| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            started.wait();
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(index * 10)
+        });
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            outcomes.iter().map(|o| o.value).collect::<Vec<_>>(),
+            [Some(0), Some(10), Some(20), Some(30)]
+        );
+        assert!(outcomes.iter().all(|o| o.status == BuildStatus::Succeeded));
+    }
+
+    #[test]
+    fn test_failures_skip_dependents_only() {
+        let builds = vec![
+            scheduled("lib", &[]),
+            scheduled("token", &["lib"]),
+            scheduled("vault", &["token", "sdk"]),
+            scheduled("bridge", &[]),
+        ];
+        let built = Mutex::new(Vec::new());
+        let outcomes = BuildScheduler::new(4).run(&builds, |index| {
+            built.lock().unwrap().push(builds[index].name.clone());
+            match builds[index].name.as_str() {
+                "token" => Err(eyre::eyre!("cargo build failed")),
+                _ => Ok(()),
+            }
+        });
+
+        let statuses: Vec<_> = outcomes
+            .iter()
+            .map(|o| (o.name.as_str(), o.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("lib", BuildStatus::Succeeded),
+                ("token", BuildStatus::Failed),
+                ("vault", BuildStatus::Skipped),
+                ("bridge", BuildStatus::Succeeded)
+            ]
+        );
+        assert_eq!(outcomes[1].error.as_deref(), Some("cargo build failed"));
+        assert_eq!(
+            outcomes[2].error.as_deref(),
+            Some("dependency token was not built")
+        );
+        let built = built.into_inner().unwrap();
+        assert!(!built.contains(&"vault".to_string()));
+        assert!(built.iter().position(|n| n == "lib") < built.iter().position(|n| n == "token"));
+    }
+}
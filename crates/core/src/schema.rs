@@ -0,0 +1,279 @@
+//! JSON Schemas of the files fluent-builder writes
+//!
+//! Integrators should validate against these schemas instead of relying on
+//! the current shape of the Rust types. The schemas of each released format
+//! version are checked in under `schemas/`, and tests compare the current
+//! schemas against them with [`breaking_changes`].
+
+use crate::{
+    artifacts::metadata::Metadata, DeployArtifact, Deployments, ReproducibilityScorecard,
+    StandardJsonOutput, VerifiedBuild,
+};
+use schemars::{generate::SchemaSettings, JsonSchema};
+use serde_json::{Map, Value};
+
+/// Schema of `metadata.json`
+pub const METADATA_SCHEMA: &str = "metadata";
+
+/// Schema of `deployments.json`
+pub const DEPLOYMENTS_SCHEMA: &str = "deployments";
+
+/// Schema of `reproducibility.json`
+pub const REPRODUCIBILITY_SCHEMA: &str = "reproducibility";
+
+/// Schema of `deploy.json`
+pub const DEPLOY_SCHEMA: &str = "deploy";
+
+/// Schema of a line of a verified builds dump
+pub const VERIFIED_BUILD_SCHEMA: &str = "verified-build";
+
+/// Schema of the `compile --standard-json` output
+pub const STANDARD_JSON_SCHEMA: &str = "standard-json-output";
+
+/// Schemas published by the library
+pub const SCHEMA_NAMES: &[&str] = &[
+    METADATA_SCHEMA,
+    DEPLOYMENTS_SCHEMA,
+    REPRODUCIBILITY_SCHEMA,
+    DEPLOY_SCHEMA,
+    VERIFIED_BUILD_SCHEMA,
+    STANDARD_JSON_SCHEMA,
+];
+
+/// JSON Schema of a published document, by name
+pub fn json_schema(name: &str) -> Option<Value> {
+    match name {
+        METADATA_SCHEMA => Some(serialized_schema::<Metadata>()),
+        DEPLOYMENTS_SCHEMA => Some(serialized_schema::<Deployments>()),
+        REPRODUCIBILITY_SCHEMA => Some(serialized_schema::<ReproducibilityScorecard>()),
+        DEPLOY_SCHEMA => Some(serialized_schema::<DeployArtifact>()),
+        VERIFIED_BUILD_SCHEMA => Some(serialized_schema::<VerifiedBuild>()),
+        STANDARD_JSON_SCHEMA => Some(serialized_schema::<StandardJsonOutput>()),
+        _ => None,
+    }
+}
+
+/// Schema of `T` as written (fields skipped when empty are optional)
+pub fn serialized_schema<T: JsonSchema>() -> Value {
+    SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<T>()
+        .to_value()
+}
+
+/// Changes from `published` to `current` that break existing readers
+///
+/// Removed or retyped fields, fields that are no longer always present and
+/// removed enum variants are breaking; new fields and variants are not.
+pub fn breaking_changes(published: &Value, current: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    compare(
+        &Roots { published, current },
+        published,
+        current,
+        "$",
+        &mut changes,
+    );
+    changes
+}
+
+struct Roots<'a> {
+    published: &'a Value,
+    current: &'a Value,
+}
+
+fn compare(roots: &Roots<'_>, old: &Value, new: &Value, path: &str, changes: &mut Vec<String>) {
+    let old = resolve(roots.published, old);
+    let new = resolve(roots.current, new);
+
+    if let (Some(old_type), Some(new_type)) = (old.get("type"), new.get("type")) {
+        if old_type != new_type {
+            changes.push(format!(
+                "{}: type changed from {} to {}",
+                path, old_type, new_type
+            ));
+            return;
+        }
+    }
+
+    if let Some(old_props) = old.get("properties").and_then(Value::as_object) {
+        let empty = Map::new();
+        let new_props = new
+            .get("properties")
+            .and_then(Value::as_object)
+            .unwrap_or(&empty);
+        for (name, old_prop) in old_props {
+            let field = format!("{}.{}", path, name);
+            match new_props.get(name) {
+                Some(new_prop) => compare(roots, old_prop, new_prop, &field, changes),
+                None => changes.push(format!("{}: removed", field)),
+            }
+        }
+
+        let required = |schema: &Value| -> Vec<String> {
+            schema
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|r| {
+                    r.iter()
+                        .filter_map(|v| v.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let new_required = required(new);
+        for name in required(old) {
+            if new_props.contains_key(&name) && !new_required.contains(&name) {
+                changes.push(format!("{}.{}: no longer always present", path, name));
+            }
+        }
+    }
+
+    if let (Some(old_items), Some(new_items)) = (old.get("items"), new.get("items")) {
+        compare(roots, old_items, new_items, &format!("{}[]", path), changes);
+    }
+
+    for keyword in ["oneOf", "anyOf"] {
+        let Some(old_variants) = old.get(keyword).and_then(Value::as_array) else {
+            continue;
+        };
+        let new_variants = new
+            .get(keyword)
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        for old_variant in old_variants {
+            let tag = variant_tag(roots.published, old_variant);
+            let path = format!("{}<{}>", path, tag);
+            match new_variants
+                .iter()
+                .find(|v| variant_tag(roots.current, v) == tag)
+            {
+                Some(new_variant) => compare(roots, old_variant, new_variant, &path, changes),
+                None => changes.push(format!("{}: variant removed", path)),
+            }
+        }
+    }
+}
+
+/// Follow a local `$ref` to its definition
+fn resolve<'a>(root: &'a Value, schema: &'a Value) -> &'a Value {
+    schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix('#'))
+        .and_then(|pointer| root.pointer(pointer))
+        .unwrap_or(schema)
+}
+
+/// Identify an enum variant by its constant (tag) properties, or by its type
+fn variant_tag(root: &Value, variant: &Value) -> String {
+    let variant = resolve(root, variant);
+    let consts: Vec<String> = variant
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, prop)| Some(format!("{}={}", name, prop.get("const")?.as_str()?)))
+        .collect();
+    if consts.is_empty() {
+        variant
+            .get("type")
+            .map(Value::to_string)
+            .unwrap_or_default()
+    } else {
+        consts.join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_published_schemas() {
+        for name in SCHEMA_NAMES {
+            let schema = json_schema(name).unwrap();
+            assert_eq!(schema["type"], "object", "{}", name);
+        }
+        assert!(json_schema("unknown").is_none());
+
+        let metadata = json_schema(METADATA_SCHEMA).unwrap();
+        let required = metadata["required"].as_array().unwrap();
+        assert!(required.contains(&"schema_version".into()));
+        assert!(required.contains(&"builder_version".into()));
+    }
+
+    #[test]
+    fn test_published_schemas_are_compatible() {
+        let published: &[(&str, &str)] = &[
+            (METADATA_SCHEMA, include_str!("../schemas/metadata.v1.json")),
+            (
+                DEPLOYMENTS_SCHEMA,
+                include_str!("../schemas/deployments.v1.json"),
+            ),
+            (
+                REPRODUCIBILITY_SCHEMA,
+                include_str!("../schemas/reproducibility.v1.json"),
+            ),
+            (DEPLOY_SCHEMA, include_str!("../schemas/deploy.v1.json")),
+            (
+                VERIFIED_BUILD_SCHEMA,
+                include_str!("../schemas/verified-build.v1.json"),
+            ),
+            (
+                STANDARD_JSON_SCHEMA,
+                include_str!("../schemas/standard-json-output.v1.json"),
+            ),
+        ];
+        for (name, published) in published {
+            let published: Value = serde_json::from_str(published).unwrap();
+            let changes = breaking_changes(&published, &json_schema(name).unwrap());
+            assert!(
+                changes.is_empty(),
+                "breaking changes to {}: {:?}; bump its version instead",
+                name,
+                changes
+            );
+        }
+    }
+
+    #[test]
+    fn test_breaking_changes() {
+        let published = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "size": {"type": "integer"},
+                "kind": {"oneOf": [
+                    {"type": "object", "properties": {"kind": {"const": "a"}}},
+                    {"type": "object", "properties": {"kind": {"const": "b"}}}
+                ]}
+            },
+            "required": ["name", "size"]
+        });
+        assert!(breaking_changes(&published, &published).is_empty());
+
+        let current = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "integer"},
+                "size": {"type": "integer"},
+                "added": {"type": "string"},
+                "kind": {"oneOf": [
+                    {"type": "object", "properties": {"kind": {"const": "a"}}}
+                ]}
+            },
+            "required": ["name"]
+        });
+        assert_eq!(
+            breaking_changes(&published, &current),
+            vec![
+                "$.kind<kind=b>: variant removed",
+                "$.name: type changed from \"string\" to \"integer\"",
+                "$.size: no longer always present",
+            ]
+        );
+    }
+}
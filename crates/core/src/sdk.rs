@@ -0,0 +1,265 @@
+//! fluentbase-sdk releases and project upgrades
+//!
+//! SDK releases are the `vX.Y.Z` tags of the fluentbase repository. Each
+//! release determines the builder Docker image and the rWASM translator
+//! (`fluentbase-types` at the same tag) a contract must be built with.
+
+use crate::config::Timeouts;
+use eyre::{Context, Result};
+use semver::Version;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+use toml_edit::{DocumentMut, Item, Value};
+
+/// Git repository hosting fluentbase-sdk
+pub const SDK_REPOSITORY: &str = "https://github.com/fluentlabs-xyz/fluentbase";
+
+/// Dependency tables that may reference SDK crates
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// A published SDK version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdkRelease {
+    pub version: Version,
+    /// Git tag, e.g. `v0.4.1`
+    pub tag: String,
+    /// Commit the tag points to
+    pub commit: String,
+}
+
+impl SdkRelease {
+    /// Crate providing the WASM to rWASM translation for this SDK version
+    pub fn rwasm_translator(&self) -> String {
+        format!("fluentbase-types@{}", self.tag)
+    }
+}
+
+/// List SDK releases from the repository tags, oldest first
+///
+/// Fetching the tags is a network request, bounded by the RPC timeout.
+pub fn list_sdk_releases(timeouts: &Timeouts) -> Result<Vec<SdkRelease>> {
+    let mut cmd = Command::new("git");
+    cmd.args(["ls-remote", "--tags", "--refs", SDK_REPOSITORY]);
+    let output = crate::process::output_with_timeout(&mut cmd, "git ls-remote", timeouts.rpc)?;
+
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "Failed to list SDK releases: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(parse_release_tags(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse `git ls-remote --tags` output into releases, oldest first
+fn parse_release_tags(ls_remote: &str) -> Vec<SdkRelease> {
+    let mut releases: Vec<SdkRelease> = ls_remote
+        .lines()
+        .filter_map(|line| {
+            let (commit, reference) = line.split_once('\t')?;
+            let tag = reference.strip_prefix("refs/tags/")?;
+            let version = Version::parse(tag.strip_prefix('v')?).ok()?;
+            Some(SdkRelease {
+                version,
+                tag: tag.to_string(),
+                commit: commit.to_string(),
+            })
+        })
+        .collect();
+    releases.sort_by(|a, b| a.version.cmp(&b.version));
+    releases
+}
+
+/// Find a release by version (`0.6.0` or `v0.6.0`)
+pub fn resolve_sdk_release<'a>(
+    releases: &'a [SdkRelease],
+    version: &str,
+) -> Result<&'a SdkRelease> {
+    let wanted = Version::parse(version.trim_start_matches('v'))
+        .with_context(|| format!("Invalid SDK version: {}", version))?;
+
+    releases
+        .iter()
+        .find(|r| r.version == wanted)
+        .ok_or_else(|| eyre::eyre!("SDK version {} is not published", wanted))
+}
+
+/// Outcome of pinning a project to an SDK release
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdkUpgrade {
+    /// SDK crates whose requirement was changed
+    pub updated: Vec<String>,
+    pub manifest_path: PathBuf,
+}
+
+/// Point all fluentbase dependencies of a project at `release` and update Cargo.lock
+pub fn upgrade_sdk(
+    project_root: &Path,
+    release: &SdkRelease,
+    offline: bool,
+    timeouts: &Timeouts,
+) -> Result<SdkUpgrade> {
+    let manifest_path = project_root.join("Cargo.toml");
+    let content = std::fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+
+    let (updated_manifest, updated) = pin_sdk_dependencies(&content, release)?;
+    if updated.is_empty() {
+        return Err(eyre::eyre!(
+            "No fluentbase dependencies found in {}",
+            manifest_path.display()
+        ));
+    }
+
+    std::fs::write(&manifest_path, updated_manifest)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+
+    // Re-resolve only what the manifest change requires
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(project_root)
+        .args(["update", "--workspace"]);
+    if offline {
+        cmd.arg("--offline");
+    }
+    let output =
+        crate::process::output_with_timeout(&mut cmd, "cargo update", timeouts.cargo_build)?;
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "Failed to update Cargo.lock: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(SdkUpgrade {
+        updated,
+        manifest_path,
+    })
+}
+
+/// Rewrite fluentbase dependency requirements in a manifest, keeping formatting
+fn pin_sdk_dependencies(manifest: &str, release: &SdkRelease) -> Result<(String, Vec<String>)> {
+    let mut doc: DocumentMut = manifest.parse().context("Failed to parse Cargo.toml")?;
+    let mut updated = Vec::new();
+
+    for table_name in DEPENDENCY_TABLES {
+        let Some(table) = doc.get_mut(table_name).and_then(Item::as_table_like_mut) else {
+            continue;
+        };
+
+        for (name, item) in table.iter_mut() {
+            if !name.starts_with("fluentbase-") {
+                continue;
+            }
+            if pin_dependency(item, release) {
+                updated.push(name.to_string());
+            }
+        }
+    }
+
+    updated.sort();
+    updated.dedup();
+    Ok((doc.to_string(), updated))
+}
+
+/// Pin a single dependency; returns false if it is not an SDK git or registry dependency
+fn pin_dependency(item: &mut Item, release: &SdkRelease) -> bool {
+    // `fluentbase-sdk = "0.4"`
+    if let Some(Value::String(_)) = item.as_value() {
+        *item = toml_edit::value(release.version.to_string());
+        return true;
+    }
+
+    let Some(dependency) = item.as_table_like_mut() else {
+        return false;
+    };
+
+    let from_sdk_repo = dependency
+        .get("git")
+        .and_then(Item::as_str)
+        .is_some_and(|git| git.trim_end_matches(".git") == SDK_REPOSITORY);
+
+    if from_sdk_repo {
+        dependency.remove("rev");
+        dependency.remove("branch");
+        dependency.insert("tag", toml_edit::value(release.tag.clone()));
+        true
+    } else if dependency.contains_key("version") && !dependency.contains_key("path") {
+        dependency.insert("version", toml_edit::value(release.version.to_string()));
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(version: &str) -> SdkRelease {
+        SdkRelease {
+            version: Version::parse(version).unwrap(),
+            tag: format!("v{}", version),
+            commit: "acbf4f6a26c2a441b101c6d50e6cc096b1e7e4c7".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_release_tags() {
+        let output = "\
+            1111111111111111111111111111111111111111\trefs/tags/v0.5.0\n\
+            2222222222222222222222222222222222222222\trefs/tags/v0.1.0-dev\n\
+            3333333333333333333333333333333333333333\trefs/tags/nightly\n\
+            4444444444444444444444444444444444444444\trefs/tags/v0.4.1\n";
+
+        let releases = parse_release_tags(output);
+        let tags: Vec<&str> = releases.iter().map(|r| r.tag.as_str()).collect();
+        assert_eq!(tags, vec!["v0.1.0-dev", "v0.4.1", "v0.5.0"]);
+
+        assert_eq!(
+            resolve_sdk_release(&releases, "0.4.1").unwrap().commit,
+            "4".repeat(40)
+        );
+        assert_eq!(
+            resolve_sdk_release(&releases, "v0.5.0").unwrap().tag,
+            "v0.5.0"
+        );
+        assert!(resolve_sdk_release(&releases, "0.6.0").is_err());
+
+        assert_eq!(releases[2].rwasm_translator(), "fluentbase-types@v0.5.0");
+    }
+
+    #[test]
+    fn test_pin_sdk_dependencies() {
+        let manifest = r#"[package]
+name = "power-calculator"
+
+[dependencies]
+# The SDK
+fluentbase-sdk = { git = "https://github.com/fluentlabs-xyz/fluentbase", tag = "v0.1.0-dev", default-features = false }
+serde = "1"
+
+[dev-dependencies]
+fluentbase-sdk-testing = { git = "https://github.com/fluentlabs-xyz/fluentbase", rev = "19610a9", default-features = false }
+"#;
+
+        let (updated, names) = pin_sdk_dependencies(manifest, &release("0.6.0")).unwrap();
+        assert_eq!(names, vec!["fluentbase-sdk", "fluentbase-sdk-testing"]);
+        assert!(updated.contains("# The SDK"));
+        assert!(updated.contains(r#"tag = "v0.6.0", default-features = false }"#));
+        assert!(!updated.contains("rev ="));
+        assert!(updated.contains(r#"serde = "1""#));
+    }
+
+    #[test]
+    fn test_pin_registry_dependency() {
+        let manifest =
+            "[dependencies]\nfluentbase-sdk = \"0.4\"\nfluentbase-local = { path = \"../sdk\" }\n";
+        let (updated, names) = pin_sdk_dependencies(manifest, &release("0.6.0")).unwrap();
+        assert_eq!(names, vec!["fluentbase-sdk"]);
+        assert!(updated.contains("fluentbase-sdk = \"0.6.0\""));
+        assert!(updated.contains("fluentbase-local = { path = \"../sdk\" }"));
+    }
+}
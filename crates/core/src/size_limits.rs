@@ -0,0 +1,121 @@
+//! Bytecode size limits
+//!
+//! A contract over Fluent's deployment limit otherwise only fails when the
+//! deployment reverts. The check runs right after the build and names what
+//! takes the space: the largest WASM sections, crates and functions.
+
+use crate::{
+    cache::format_size,
+    config::{SizeLimitPolicy, SizeLimits},
+    size_report::{analyze_wasm_size, SizeEntry},
+};
+use eyre::Result;
+
+/// Sections, crates and functions listed in the breakdown
+const BREAKDOWN_ROWS: usize = 5;
+
+/// Fail or warn, per the policy, when the bytecode is over a limit
+pub(crate) fn check_size_limits(limits: &SizeLimits, wasm: &[u8], rwasm: &[u8]) -> Result<()> {
+    let exceeded = exceeded_limits(limits, wasm, rwasm);
+    if exceeded.is_empty() {
+        return Ok(());
+    }
+    let mut message = exceeded.join("; ");
+    for line in breakdown(wasm) {
+        message.push_str("\n  ");
+        message.push_str(&line);
+    }
+    match limits.policy {
+        SizeLimitPolicy::Error => Err(eyre::eyre!(
+            "{}\nShrink the contract (opt-level = \"z\", lto, no debug info) or raise the limit \
+             (--max-wasm-size, --max-rwasm-size)",
+            message
+        )),
+        SizeLimitPolicy::Warn => {
+            tracing::warn!("{}", message);
+            Ok(())
+        }
+    }
+}
+
+/// Description of each limit the bytecode is over
+fn exceeded_limits(limits: &SizeLimits, wasm: &[u8], rwasm: &[u8]) -> Vec<String> {
+    [
+        ("lib.wasm", wasm.len(), limits.max_wasm_size),
+        ("lib.rwasm", rwasm.len(), limits.max_rwasm_size),
+    ]
+    .into_iter()
+    .filter_map(|(file, size, limit)| {
+        let limit = limit.filter(|limit| size > *limit)?;
+        Some(format!(
+            "{} is {} bytes, {} over the limit of {}",
+            file,
+            size,
+            format_size((size - limit) as u64),
+            format_size(limit as u64)
+        ))
+    })
+    .collect()
+}
+
+/// Largest sections, crates and functions of the WASM module, if it parses
+fn breakdown(wasm: &[u8]) -> Vec<String> {
+    let Ok(report) = analyze_wasm_size(wasm) else {
+        return Vec::new();
+    };
+    let largest = |entries: &[SizeEntry]| {
+        entries
+            .iter()
+            .take(BREAKDOWN_ROWS)
+            .map(|entry| format!("{} {}", entry.name, format_size(entry.size as u64)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    [
+        ("sections", &report.sections),
+        ("crates", &report.crates),
+        ("functions", &report.functions),
+    ]
+    .into_iter()
+    .filter(|(_, entries)| !entries.is_empty())
+    .map(|(kind, entries)| format!("largest {}: {}", kind, largest(entries)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_limits() {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend([11, 100]);
+        wasm.extend([0; 100]);
+        let rwasm = vec![0; 200];
+        let mut limits = SizeLimits::default();
+        assert!(check_size_limits(&limits, &wasm, &rwasm).is_ok());
+
+        limits.max_wasm_size = Some(100);
+        limits.max_rwasm_size = Some(300);
+        let err = check_size_limits(&limits, &wasm, &rwasm)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with(concat!(
+                "lib.wasm is 110 bytes, 10 B over the limit of 100 B\n",
+                "  largest sections: data 100 B\n"
+            )),
+            "{}",
+            err
+        );
+
+        limits.max_rwasm_size = Some(150);
+        assert_eq!(
+            exceeded_limits(&limits, &wasm, &rwasm)[1],
+            "lib.rwasm is 200 bytes, 50 B over the limit of 150 B"
+        );
+
+        limits.policy = SizeLimitPolicy::Warn;
+        assert!(check_size_limits(&limits, &wasm, &rwasm).is_ok());
+    }
+}
@@ -0,0 +1,235 @@
+//! Where the bytes of a WASM module go
+//!
+//! Function bodies are attributed to crates by the paths in the name
+//! section, which rustc and wasm-ld keep unless the module is stripped, so a
+//! contract over its size limit shows which dependency is responsible.
+
+use crate::mismatch_report::{function_table, wasm_sections};
+use eyre::Result;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Label of functions without a crate path, e.g. `memcpy` or unnamed `func[12]`
+pub const UNATTRIBUTED: &str = "[unattributed]";
+
+/// Size of a WASM module by section, crate and function
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SizeReport {
+    /// Bytes of the module
+    pub total: usize,
+    /// Sections, largest first
+    pub sections: Vec<SizeEntry>,
+    /// Function bodies summed by crate, largest first
+    pub crates: Vec<SizeEntry>,
+    /// Function bodies, largest first
+    pub functions: Vec<SizeEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct SizeEntry {
+    pub name: String,
+    pub size: usize,
+    /// Share of the module, in percent
+    pub percent: f64,
+}
+
+impl SizeReport {
+    /// Keep the `top` largest crates and functions
+    pub fn truncate(&mut self, top: usize) {
+        self.crates.truncate(top);
+        self.functions.truncate(top);
+    }
+}
+
+/// Attribute the size of `wasm` to its sections, crates and functions
+pub fn analyze_wasm_size(wasm: &[u8]) -> Result<SizeReport> {
+    let module = Some(wasm.to_vec());
+    let total = wasm.len();
+    let entries = |sizes: Vec<(String, usize)>| {
+        let mut entries: Vec<SizeEntry> = sizes
+            .into_iter()
+            .map(|(name, size)| SizeEntry {
+                name,
+                size,
+                percent: size as f64 * 100.0 / total.max(1) as f64,
+            })
+            .collect();
+        entries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        entries
+    };
+
+    let sections = wasm_sections(&module)?
+        .into_iter()
+        .map(|section| (section.name, section.size))
+        .collect();
+    let functions: Vec<(String, usize)> = function_table(&module)?
+        .into_iter()
+        .map(|(name, (size, _))| (without_hash(&name).to_string(), size))
+        .collect();
+    let mut crates: BTreeMap<String, usize> = BTreeMap::new();
+    for (name, size) in &functions {
+        *crates
+            .entry(crate_of(name).unwrap_or(UNATTRIBUTED).to_string())
+            .or_default() += size;
+    }
+
+    Ok(SizeReport {
+        total,
+        sections: entries(sections),
+        crates: entries(crates.into_iter().collect()),
+        functions: entries(functions),
+    })
+}
+
+/// `core::fmt::write::h0123456789abcdef` -> `core::fmt::write`
+fn without_hash(name: &str) -> &str {
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            path
+        }
+        _ => name,
+    }
+}
+
+/// First path segment of a function name; an impl belongs to the crate of its type
+///
+/// `<alloc::vec::Vec<T> as core::ops::drop::Drop>::drop` is `alloc`, a trait
+/// impl for a primitive like `<u64 as core::fmt::Display>::fmt` is `core`.
+/// Names wasm-ld left mangled (`_ZN4core3fmt5write17h…E`) are read too.
+fn crate_of(name: &str) -> Option<&str> {
+    if let Some(mangled) = name.strip_prefix("_ZN") {
+        let digits = mangled.bytes().take_while(u8::is_ascii_digit).count();
+        let len: usize = mangled[..digits].parse().ok()?;
+        return mangled
+            .get(digits..digits + len)
+            .filter(|segment| is_identifier(segment));
+    }
+    let path = match name.strip_prefix('<') {
+        Some(qualified) => {
+            let (self_type, trait_path) = qualified.split_once(" as ").unwrap_or((qualified, ""));
+            let self_type = self_type
+                .trim_start_matches('&')
+                .trim_start_matches("mut ")
+                .trim_start_matches("dyn ");
+            if self_type.contains("::") {
+                self_type
+            } else {
+                trait_path
+            }
+        }
+        None => name,
+    };
+    path.split_once("::")
+        .map(|(segment, _)| segment)
+        .filter(|segment| is_identifier(segment))
+}
+
+fn is_identifier(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![id];
+        let mut len = payload.len();
+        while len >= 0x80 {
+            bytes.push(len as u8 | 0x80);
+            len >>= 7;
+        }
+        bytes.push(len as u8);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// Module defining a `() -> ()` function of each name, with a body of `size` bytes
+    fn module(functions: &[(&str, usize)]) -> Vec<u8> {
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend(section(1, &[1, 0x60, 0, 0]));
+        let mut types = vec![functions.len() as u8];
+        types.extend(std::iter::repeat_n(0, functions.len()));
+        wasm.extend(section(3, &types));
+        let mut code = vec![functions.len() as u8];
+        let mut names = vec![functions.len() as u8];
+        for (i, (name, size)) in functions.iter().enumerate() {
+            code.push(*size as u8);
+            code.push(0);
+            code.extend(std::iter::repeat_n(0x01, size - 2));
+            code.push(0x0b);
+            names.extend([i as u8, name.len() as u8]);
+            names.extend(name.as_bytes());
+        }
+        wasm.extend(section(10, &code));
+        let mut name = vec![4, b'n', b'a', b'm', b'e'];
+        name.extend(section(1, &names));
+        wasm.extend(section(0, &name));
+        wasm
+    }
+
+    #[test]
+    fn test_analyze_wasm_size() {
+        let wasm = module(&[
+            ("token::transfer::h0123456789abcdef", 40),
+            ("core::fmt::write::h0123456789abcdef", 20),
+            ("<alloc::vec::Vec<T> as core::ops::drop::Drop>::drop", 10),
+            ("<u64 as core::fmt::Display>::fmt", 10),
+            ("memcpy", 5),
+        ]);
+        let mut report = analyze_wasm_size(&wasm).unwrap();
+        assert_eq!(report.total, wasm.len());
+        assert_eq!(report.sections[0].name, "custom:name");
+
+        let sizes = |entries: &[SizeEntry]| {
+            entries
+                .iter()
+                .map(|e| (e.name.clone(), e.size))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sizes(&report.crates),
+            [
+                ("token".to_string(), 40),
+                ("core".to_string(), 30),
+                ("alloc".to_string(), 10),
+                (UNATTRIBUTED.to_string(), 5)
+            ]
+        );
+        assert_eq!(report.functions[0].name, "token::transfer");
+        assert_eq!(
+            report.functions[0].percent,
+            40.0 * 100.0 / wasm.len() as f64
+        );
+
+        report.truncate(2);
+        assert_eq!(report.crates.len(), 2);
+        assert_eq!(report.functions.len(), 2);
+    }
+
+    #[test]
+    fn test_crate_of() {
+        assert_eq!(
+            crate_of("fluentbase_sdk::entrypoint::main"),
+            Some("fluentbase_sdk")
+        );
+        assert_eq!(
+            crate_of("<&mut T as core::fmt::Write>::write_str"),
+            Some("core")
+        );
+        assert_eq!(
+            crate_of("_ZN5alloc7raw_vec11finish_grow17h0123456789abcdefE"),
+            Some("alloc")
+        );
+        assert_eq!(crate_of("func[12]"), None);
+        assert_eq!(
+            without_hash("core::fmt::write::h0123456789abcdef"),
+            "core::fmt::write"
+        );
+        assert_eq!(without_hash("token::h::hash"), "token::h::hash");
+    }
+}
@@ -0,0 +1,438 @@
+//! Standard JSON compilation, as `solc --standard-json` does it
+//!
+//! One JSON document with the sources inlined goes in, one JSON result with
+//! bytecodes, ABI, metadata and errors comes out, so verification backends
+//! and explorers that already drive solc this way can build Fluent contracts
+//! too. Sources are written to a sandbox directory and built; failures are
+//! reported in `errors` rather than as an error of the call.
+//!
+//! Build scripts and procedural macros of the inlined sources run as part of
+//! the build, so untrusted requests must be built inside a sandbox container.
+//! [`compile_standard_json`] builds in the current process and only accepts
+//! `extraCargoArgs` and `rustflags` when told it runs in such a container.
+
+use crate::{
+    artifacts::{metadata::Metadata, Abi},
+    build,
+    builder::hash_bytes,
+    AbiNaming, CompileConfig, ContractArtifacts, Sandbox,
+};
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+
+/// Language accepted in the `language` field
+pub const STANDARD_JSON_LANGUAGE: &str = "Rust";
+
+/// Root source file of a library crate without a `[lib] path`
+const DEFAULT_LIB_PATH: &str = "src/lib.rs";
+
+/// A compilation request
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StandardJsonInput {
+    /// Always `Rust`
+    pub language: String,
+    /// Project files by path relative to the project root, e.g. `Cargo.toml` and `src/lib.rs`
+    pub sources: BTreeMap<String, StandardJsonSource>,
+    #[serde(default)]
+    pub settings: StandardJsonSettings,
+    /// Written as `rust-toolchain.toml` unless the sources include one
+    #[serde(default)]
+    pub toolchain: Option<StandardJsonToolchain>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StandardJsonSource {
+    pub content: String,
+}
+
+/// Build settings; the defaults match `compile`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct StandardJsonSettings {
+    pub profile: String,
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    /// Target of a multi-contract package
+    pub target_name: Option<String>,
+    pub abi_naming: AbiNaming,
+    /// Arguments passed to `cargo build` as-is
+    pub extra_cargo_args: Vec<String>,
+    /// RUSTFLAGS of the build
+    pub rustflags: Option<String>,
+    /// Accepted for compatibility with solc requests and ignored; every output is produced
+    pub output_selection: Option<Value>,
+}
+
+impl Default for StandardJsonSettings {
+    fn default() -> Self {
+        let config = CompileConfig::default();
+        Self {
+            profile: config.profile,
+            features: config.features,
+            no_default_features: config.no_default_features,
+            target_name: config.target_name,
+            abi_naming: config.artifacts.abi_naming,
+            extra_cargo_args: config.extra_cargo_args,
+            rustflags: config.rustflags,
+            output_selection: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StandardJsonToolchain {
+    /// Rust toolchain channel, e.g. `1.83.0` or `nightly-2024-01-15`
+    pub rust: String,
+}
+
+impl StandardJsonInput {
+    /// Parse and validate a request
+    pub fn parse(input: &str) -> Result<Self> {
+        let input: Self = serde_json::from_str(input).context("Invalid standard JSON input")?;
+        if input.language != STANDARD_JSON_LANGUAGE {
+            eyre::bail!(
+                "Unsupported language {:?}, expected {:?}",
+                input.language,
+                STANDARD_JSON_LANGUAGE
+            );
+        }
+        if !input.sources.contains_key("Cargo.toml") {
+            eyre::bail!("sources must include Cargo.toml");
+        }
+        for path in input.sources.keys() {
+            source_path(path)?;
+        }
+        Ok(input)
+    }
+
+    /// Whether the request passes arguments or flags to cargo and rustc, which need a sandbox
+    pub fn has_build_flags(&self) -> bool {
+        !self.settings.extra_cargo_args.is_empty() || self.settings.rustflags.is_some()
+    }
+
+    /// Source file declaring the contract, the `[lib] path` of Cargo.toml
+    pub fn contract_file(&self) -> String {
+        self.sources["Cargo.toml"]
+            .content
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|manifest| {
+                manifest
+                    .get("lib")?
+                    .get("path")?
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .unwrap_or_else(|| DEFAULT_LIB_PATH.to_string())
+    }
+
+    /// Write the sources, and `rust-toolchain.toml` for `toolchain`, to `sources_dir`
+    pub fn write_sources(&self, sources_dir: &Path) -> Result<()> {
+        for (path, source) in &self.sources {
+            let path = sources_dir.join(source_path(path)?);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            std::fs::write(&path, &source.content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        if let Some(toolchain) = self
+            .toolchain
+            .as_ref()
+            .filter(|_| !self.sources.contains_key("rust-toolchain.toml"))
+        {
+            let content = format!(
+                "[toolchain]\nchannel = {:?}\ntargets = [\"wasm32-unknown-unknown\"]\n",
+                toolchain.rust
+            );
+            std::fs::write(sources_dir.join("rust-toolchain.toml"), content)
+                .context("Failed to write rust-toolchain.toml")?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of a compilation request
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct StandardJsonOutput {
+    /// Compiled contracts by source file and name, as solc reports them, e.g.
+    /// `contracts["src/lib.rs"]["token"]`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub contracts: BTreeMap<String, BTreeMap<String, StandardJsonContract>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<StandardJsonError>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StandardJsonContract {
+    pub abi: Abi,
+    /// Solidity interface
+    pub interface: String,
+    pub metadata: Metadata,
+    /// `0x`-prefixed hex
+    pub wasm: String,
+    /// `0x`-prefixed hex
+    pub rwasm: String,
+    pub rwasm_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct StandardJsonError {
+    /// Always `error`
+    pub severity: String,
+    /// `JSONError` for an invalid request, `IOError` when the sources can't be written, else
+    /// `CompilerError`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub message: String,
+}
+
+impl StandardJsonContract {
+    fn new(artifacts: &ContractArtifacts, wasm: &[u8], rwasm: &[u8]) -> Self {
+        Self {
+            abi: artifacts.abi.clone(),
+            interface: artifacts.interface.clone(),
+            metadata: artifacts.metadata.clone(),
+            wasm: format!("0x{}", hex::encode(wasm)),
+            rwasm: format!("0x{}", hex::encode(rwasm)),
+            rwasm_hash: hash_bytes(rwasm),
+        }
+    }
+}
+
+impl StandardJsonOutput {
+    /// A failed request; `kind` is the `type` of the error, e.g. `CompilerError`
+    pub fn error(kind: &str, error: eyre::Report) -> Self {
+        Self {
+            contracts: BTreeMap::new(),
+            errors: vec![StandardJsonError {
+                severity: "error".to_string(),
+                kind: kind.to_string(),
+                message: format!("{:#}", error),
+            }],
+        }
+    }
+
+    /// The contract of a build declared in `file`
+    fn contract(file: String, artifacts: &ContractArtifacts, wasm: &[u8], rwasm: &[u8]) -> Self {
+        let name = artifacts.metadata.contract.artifact_name().to_string();
+        let contract = StandardJsonContract::new(artifacts, wasm, rwasm);
+        Self {
+            contracts: BTreeMap::from([(file, BTreeMap::from([(name, contract)]))]),
+            errors: Vec::new(),
+        }
+    }
+
+    /// The contract declared in `file` from the artifacts a build saved in `artifacts_dir`
+    ///
+    /// For builds made elsewhere, e.g. in a sandbox container.
+    pub fn from_artifacts(file: &str, artifacts_dir: &Path) -> Result<Self> {
+        let artifacts = ContractArtifacts::load(artifacts_dir)?;
+        let read = |name: &str| {
+            let path = artifacts_dir.join(name);
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+        };
+        Ok(Self::contract(
+            file.to_string(),
+            &artifacts,
+            &read("lib.wasm")?,
+            &read("lib.rwasm")?,
+        ))
+    }
+}
+
+/// Compile a standard JSON request in a sandbox directory under `work_dir`
+///
+/// The build runs in this process. `sandboxed` says the process runs in a
+/// sandbox container; otherwise requests with `extraCargoArgs` or
+/// `rustflags` are rejected, and the sources must be trusted.
+pub fn compile_standard_json(
+    input: &str,
+    work_dir: &Path,
+    offline: bool,
+    sandboxed: bool,
+) -> StandardJsonOutput {
+    let input = match StandardJsonInput::parse(input) {
+        Ok(input) => input,
+        Err(e) => return StandardJsonOutput::error("JSONError", e),
+    };
+    if input.has_build_flags() && !sandboxed {
+        return StandardJsonOutput::error(
+            "JSONError",
+            eyre::eyre!("extraCargoArgs and rustflags are only accepted in a sandbox"),
+        );
+    }
+
+    let sandbox = match Sandbox::create(
+        work_dir,
+        &format!("fluent-builder-standard-json-{}", std::process::id()),
+    ) {
+        Ok(sandbox) => sandbox,
+        Err(e) => return StandardJsonOutput::error("IOError", e),
+    };
+    let cleanup = sandbox.cleanup_guard();
+    let output = build_sources(input, &sandbox, offline);
+    cleanup.disarm();
+    sandbox.finish(output.errors.is_empty());
+    output
+}
+
+fn build_sources(input: StandardJsonInput, sandbox: &Sandbox, offline: bool) -> StandardJsonOutput {
+    if let Err(e) = input.write_sources(&sandbox.sources_dir) {
+        return StandardJsonOutput::error("IOError", e);
+    }
+
+    let file = input.contract_file();
+    let mut config = CompileConfig::new(&sandbox.sources_dir);
+    config.profile = input.settings.profile;
+    config.features = input.settings.features;
+    config.no_default_features = input.settings.no_default_features;
+    config.target_name = input.settings.target_name;
+    config.artifacts.abi_naming = input.settings.abi_naming;
+    config.extra_cargo_args = input.settings.extra_cargo_args;
+    config.rustflags = input.settings.rustflags;
+    config.offline = offline;
+    // Inlined sources have no repository
+    config.use_git_source = false;
+    sandbox.configure(&mut config);
+
+    let result = match build(&config) {
+        Ok(result) => result,
+        Err(e) => return StandardJsonOutput::error("CompilerError", e),
+    };
+    let Some(artifacts) = result.artifacts.as_ref() else {
+        return StandardJsonOutput::error(
+            "CompilerError",
+            eyre::eyre!("No artifacts were generated"),
+        );
+    };
+    StandardJsonOutput::contract(file, artifacts, &result.outputs.wasm, &result.outputs.rwasm)
+}
+
+/// A source path, which must stay inside the project
+fn source_path(path: &str) -> Result<PathBuf> {
+    let relative = PathBuf::from(path);
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        eyre::bail!(
+            "Source path {:?} must be relative and inside the project",
+            path
+        );
+    }
+    Ok(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_invalid_requests() {
+        let dir = TempDir::new().unwrap();
+        let compile = |input: serde_json::Value| {
+            compile_standard_json(&input.to_string(), dir.path(), true, false)
+        };
+
+        let output = compile_standard_json("not json", dir.path(), true, false);
+        assert_eq!(output.errors[0].kind, "JSONError");
+        assert!(output.contracts.is_empty());
+
+        let output =
+            compile(json!({"language": "Solidity", "sources": {"Cargo.toml": {"content": ""}}}));
+        assert_eq!(
+            output.errors[0].message,
+            r#"Unsupported language "Solidity", expected "Rust""#
+        );
+
+        let output =
+            compile(json!({"language": "Rust", "sources": {"src/lib.rs": {"content": ""}}}));
+        assert_eq!(output.errors[0].message, "sources must include Cargo.toml");
+
+        for path in ["../escape.rs", "/etc/passwd", "src/../../escape.rs"] {
+            let output = compile(json!({
+                "language": "Rust",
+                "sources": {"Cargo.toml": {"content": ""}, path: {"content": ""}}
+            }));
+            assert_eq!(output.errors[0].kind, "JSONError", "{}", path);
+        }
+        assert!(!dir.path().join("escape.rs").exists());
+
+        // Build flags of an untrusted request need a sandbox
+        for settings in [
+            json!({"extraCargoArgs": ["--locked"]}),
+            json!({"rustflags": "-C x"}),
+        ] {
+            let output = compile(json!({
+                "language": "Rust",
+                "sources": {"Cargo.toml": {"content": ""}},
+                "settings": settings
+            }));
+            assert_eq!(
+                output.errors[0].message,
+                "extraCargoArgs and rustflags are only accepted in a sandbox"
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_sources() {
+        let input = StandardJsonInput::parse(
+            &json!({
+                "language": "Rust",
+                "sources": {
+                    "Cargo.toml": {"content": "[package]\n"},
+                    "src/lib.rs": {"content": "// token\n"}
+                },
+                "settings": {
+                    "features": ["testnet"],
+                    "abiNaming": "camel_case",
+                    "outputSelection": {"*": {"*": ["abi", "evm.bytecode"]}}
+                },
+                "toolchain": {"rust": "1.83.0"}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(input.settings.features, ["testnet"]);
+        assert_eq!(input.settings.abi_naming, AbiNaming::CamelCase);
+        assert_eq!(input.settings.profile, "release");
+        assert!(input.settings.no_default_features);
+        assert!(!input.has_build_flags());
+        assert_eq!(input.contract_file(), "src/lib.rs");
+
+        let dir = TempDir::new().unwrap();
+        input.write_sources(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/lib.rs")).unwrap(),
+            "// token\n"
+        );
+        let toolchain = std::fs::read_to_string(dir.path().join("rust-toolchain.toml")).unwrap();
+        assert!(toolchain.contains("channel = \"1.83.0\""));
+
+        let input = StandardJsonInput::parse(
+            &json!({
+                "language": "Rust",
+                "sources": {"Cargo.toml": {"content": "[lib]\npath = \"contract.rs\"\n"}}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(input.contract_file(), "contract.rs");
+    }
+}
@@ -0,0 +1,143 @@
+//! Unwrapping of deployed code returned by a node
+//!
+//! Some accounts store their code behind a header, and `eth_getCode` returns
+//! the header with it. Such code never matches a correct build, so the known
+//! headers are stripped before hashing and reported with the result.
+
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// A header a Fluent node may return in front of the deployed code
+struct KnownWrapper {
+    name: &'static str,
+    magic: &'static [u8],
+    /// Header length including the magic
+    header_len: usize,
+}
+
+/// Headers detected in `auto` mode
+const KNOWN_WRAPPERS: &[KnownWrapper] = &[
+    // Ownable account: magic, version byte and the 20-byte owner address
+    KnownWrapper {
+        name: "ownable_account",
+        magic: &[0xef, 0x44],
+        header_len: 2 + 1 + 20,
+    },
+];
+
+/// How deployed code is unwrapped before hashing
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeUnwrap {
+    /// Strip a known Fluent header if the code starts with one
+    #[default]
+    Auto,
+    /// Hash the code as returned
+    None,
+    /// Strip this hex encoded prefix, failing if the code doesn't start with it
+    Prefix(String),
+}
+
+/// A header stripped from deployed code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Unwrapping {
+    /// Known wrapper, e.g. `ownable_account`, or `prefix` for a configured one
+    pub wrapper: String,
+    /// Hex encoded header that was removed
+    pub header: String,
+}
+
+impl fmt::Display for Unwrapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} header (0x{}, {} bytes)",
+            self.wrapper,
+            self.header,
+            self.header.len() / 2
+        )
+    }
+}
+
+impl CodeUnwrap {
+    /// Code to hash, and the header stripped from it, if any
+    pub fn apply<'a>(&self, code: &'a [u8]) -> Result<(&'a [u8], Option<Unwrapping>)> {
+        let strip = |wrapper: &str, header_len: usize| {
+            let (header, inner) = code.split_at(header_len);
+            let unwrapping = Unwrapping {
+                wrapper: wrapper.to_string(),
+                header: hex::encode(header),
+            };
+            (inner, Some(unwrapping))
+        };
+        match self {
+            CodeUnwrap::None => Ok((code, None)),
+            CodeUnwrap::Auto => Ok(KNOWN_WRAPPERS
+                .iter()
+                .find(|w| code.starts_with(w.magic) && code.len() > w.header_len)
+                .map(|w| strip(w.name, w.header_len))
+                .unwrap_or((code, None))),
+            CodeUnwrap::Prefix(prefix) => {
+                let prefix = hex::decode(prefix.trim_start_matches("0x"))
+                    .context("Unwrap prefix is not valid hex")?;
+                if !code.starts_with(&prefix) {
+                    return Err(eyre::eyre!(
+                        "Deployed code does not start with the prefix 0x{}",
+                        hex::encode(&prefix)
+                    ));
+                }
+                Ok(strip("prefix", prefix.len()))
+            }
+        }
+    }
+}
+
+impl FromStr for CodeUnwrap {
+    type Err = eyre::Report;
+
+    /// `auto`, `none` or a hex prefix
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(CodeUnwrap::Auto),
+            "none" => Ok(CodeUnwrap::None),
+            prefix => {
+                hex::decode(prefix.trim_start_matches("0x"))
+                    .with_context(|| format!("Expected auto, none or a hex prefix, got '{}'", s))?;
+                Ok(CodeUnwrap::Prefix(prefix.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_unwrap() {
+        let rwasm = [0xef, 0x52, 0x01, 0x02];
+        let mut wrapped = vec![0xef, 0x44, 0x00];
+        wrapped.extend([0x11; 20]);
+        wrapped.extend(rwasm);
+
+        let (code, unwrapping) = CodeUnwrap::Auto.apply(&wrapped).unwrap();
+        assert_eq!(code, rwasm);
+        let unwrapping = unwrapping.unwrap();
+        assert_eq!(unwrapping.wrapper, "ownable_account");
+        assert_eq!(unwrapping.header.len(), 46);
+
+        // Unwrapped code is left alone
+        assert_eq!(CodeUnwrap::Auto.apply(&rwasm).unwrap(), (&rwasm[..], None));
+        assert_eq!(
+            CodeUnwrap::None.apply(&wrapped).unwrap(),
+            (&wrapped[..], None)
+        );
+
+        let prefix: CodeUnwrap = "0xef52".parse().unwrap();
+        assert_eq!(prefix.apply(&rwasm).unwrap().0, [0x01, 0x02]);
+        assert!(prefix.apply(&wrapped).is_err());
+        assert!("fast".parse::<CodeUnwrap>().is_err());
+    }
+}
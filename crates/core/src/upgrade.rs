@@ -0,0 +1,310 @@
+//! Upgrade-safety checks between a deployed implementation and its successor
+//!
+//! A proxy keeps its storage when it switches implementations, so the new
+//! implementation must declare the storage of the old one in the same slots,
+//! and callers of the proxy must still find every function they used. Storage
+//! is read from the `solidity_storage!` declarations of the sources each
+//! implementation was built from, in the main file and the modules it declares.
+
+use crate::{
+    artifacts::metadata::Source,
+    backfill::{find_artifacts, hash_digits},
+    builder::find_main_source,
+    changelog::{generate_changelog, ContractSnapshot, WorktreeGuard},
+    parser::{module_tree, SourceCache},
+};
+use eyre::{Context, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use syn::visit::Visit;
+
+/// A value declared in `solidity_storage!`, in slot order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct StorageField {
+    pub slot: usize,
+    pub name: String,
+    /// Declared type, e.g. `mapping(Address => U256)`
+    pub ty: String,
+}
+
+/// Why an implementation can't replace another one behind a proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UpgradeIssueKind {
+    /// A function of the old ABI is gone
+    FunctionRemoved,
+    /// A storage slot of the old implementation is gone, renamed or retyped
+    StorageChanged,
+    /// The storage of the old implementation can't be read
+    StorageUnknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct UpgradeIssue {
+    pub kind: UpgradeIssueKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for UpgradeIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Result of checking an upgrade
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct UpgradeReport {
+    /// Storage of the new implementation
+    pub storage: Vec<StorageField>,
+    pub issues: Vec<UpgradeIssue>,
+}
+
+impl UpgradeReport {
+    pub fn passed(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check that the artifacts in `next_dir`, built from `project_root`, can replace
+/// the implementation whose artifacts are in `current_dir`
+///
+/// The storage of the current implementation is read from the commit its
+/// metadata.json records; a build from an archive can't be checked.
+pub fn check_upgrade(
+    project_root: &Path,
+    current_dir: &Path,
+    next_dir: &Path,
+) -> Result<UpgradeReport> {
+    let current = ContractSnapshot::from_artifacts_dir(current_dir)?;
+    let next = ContractSnapshot::from_artifacts_dir(next_dir)?;
+    let mut issues: Vec<UpgradeIssue> = generate_changelog(&current, &next)
+        .functions_removed
+        .into_iter()
+        .map(|signature| UpgradeIssue {
+            kind: UpgradeIssueKind::FunctionRemoved,
+            message: format!(
+                "{} was removed; callers of the proxy would revert",
+                signature
+            ),
+        })
+        .collect();
+
+    let storage = project_storage(project_root)?;
+    match previous_storage(project_root, current_dir)? {
+        Some(previous) => issues.extend(storage_issues(&previous, &storage)),
+        None => issues.push(UpgradeIssue {
+            kind: UpgradeIssueKind::StorageUnknown,
+            message: format!(
+                "{} was not built from a git commit, so its storage layout is unknown",
+                current_dir.display()
+            ),
+        }),
+    }
+
+    Ok(UpgradeReport { storage, issues })
+}
+
+/// Artifacts directory under `out_dir` whose rWASM hash is `rwasm_hash`
+pub fn find_artifacts_by_hash(out_dir: &Path, rwasm_hash: &str) -> Option<PathBuf> {
+    find_artifacts(out_dir).remove(&hash_digits(rwasm_hash))
+}
+
+/// Storage declared in the main source file of a project and the modules it declares
+pub fn project_storage(project_root: &Path) -> Result<Vec<StorageField>> {
+    let main_source = find_main_source(project_root, &project_root.join("Cargo.toml"))?;
+    let files = module_tree(&main_source, &mut SourceCache::default())
+        .with_context(|| format!("Failed to parse {}", main_source.display()))?;
+    storage_fields(files.iter().map(|file| &**file))
+}
+
+/// Storage of the build in `artifacts_dir`, from a checkout of its commit
+fn previous_storage(
+    project_root: &Path,
+    artifacts_dir: &Path,
+) -> Result<Option<Vec<StorageField>>> {
+    let path = artifacts_dir.join("metadata.json");
+    let metadata: Value = serde_json::from_str(
+        &std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+    )?;
+    let Ok(Source::Git {
+        commit,
+        project_path,
+        ..
+    }) = serde_json::from_value(metadata["source"].clone())
+    else {
+        return Ok(None);
+    };
+
+    let worktree = WorktreeGuard::create(project_root, &commit)?;
+    project_storage(&worktree.path.join(project_path)).map(Some)
+}
+
+/// Fields of every `solidity_storage!` invocation, numbered in declaration order
+pub fn parse_storage(source: &str) -> Result<Vec<StorageField>> {
+    let ast =
+        syn::parse_file(source).map_err(|e| eyre::eyre!("Failed to parse Rust file: {}", e))?;
+    storage_fields([&ast])
+}
+
+/// Fields of the `solidity_storage!` invocations of `files`, numbered in file and declaration
+/// order
+fn storage_fields<'a>(files: impl IntoIterator<Item = &'a syn::File>) -> Result<Vec<StorageField>> {
+    struct Finder(Vec<String>);
+    impl<'ast> Visit<'ast> for Finder {
+        fn visit_macro(&mut self, node: &'ast syn::Macro) {
+            if node
+                .path
+                .segments
+                .last()
+                .is_some_and(|s| s.ident == "solidity_storage")
+            {
+                self.0.push(node.tokens.to_string());
+            }
+        }
+    }
+
+    let mut finder = Finder(Vec::new());
+    for file in files {
+        finder.visit_file(file);
+    }
+
+    let declarations = finder
+        .0
+        .iter()
+        .flat_map(|tokens| tokens.split(';').map(str::trim));
+    let mut fields = Vec::new();
+    for declaration in declarations.filter(|d| !d.is_empty()) {
+        let (ty, name) = declaration
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| eyre::eyre!("Unexpected storage declaration `{}`", declaration))?;
+        fields.push(StorageField {
+            slot: fields.len(),
+            name: name.to_string(),
+            ty: normalize_type(ty),
+        });
+    }
+    Ok(fields)
+}
+
+/// Token streams print as `mapping (Address => U256)`; declarations read `mapping(Address => U256)`
+fn normalize_type(ty: &str) -> String {
+    ty.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace(" (", "(")
+        .replace("( ", "(")
+        .replace(" )", ")")
+}
+
+/// Slots of `previous` that `next` does not keep as they were; appending is allowed
+fn storage_issues(previous: &[StorageField], next: &[StorageField]) -> Vec<UpgradeIssue> {
+    previous
+        .iter()
+        .filter_map(|old| {
+            let message = match next.get(old.slot) {
+                None => format!(
+                    "storage slot {} ({} {}) was removed",
+                    old.slot, old.ty, old.name
+                ),
+                Some(new) if new.name != old.name || new.ty != old.ty => format!(
+                    "storage slot {} changed from {} {} to {} {}",
+                    old.slot, old.ty, old.name, new.ty, new.name
+                ),
+                Some(_) => return None,
+            };
+            Some(UpgradeIssue {
+                kind: UpgradeIssueKind::StorageChanged,
+                message,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORAGE: &str = r#"
+        use fluentbase_sdk::derive::solidity_storage;
+
+        solidity_storage! {
+            mapping(Address => U256) Balance;
+            mapping(Address => mapping(Address => U256)) Allowance;
+            U256 TotalSupply;
+        }
+    "#;
+
+    #[test]
+    fn test_parse_storage() {
+        let fields = parse_storage(STORAGE).unwrap();
+        let declared: Vec<(usize, &str, &str)> = fields
+            .iter()
+            .map(|f| (f.slot, f.ty.as_str(), f.name.as_str()))
+            .collect();
+        assert_eq!(
+            declared,
+            vec![
+                (0, "mapping(Address => U256)", "Balance"),
+                (
+                    1,
+                    "mapping(Address => mapping(Address => U256))",
+                    "Allowance"
+                ),
+                (2, "U256", "TotalSupply"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_project_storage() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "mod storage;\nsolidity_storage! { Address Owner; }\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/storage.rs"), STORAGE).unwrap();
+
+        let names: Vec<String> = project_storage(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|field| format!("{} {}", field.slot, field.name))
+            .collect();
+        assert_eq!(
+            names,
+            ["0 Owner", "1 Balance", "2 Allowance", "3 TotalSupply"]
+        );
+    }
+
+    #[test]
+    fn test_storage_issues() {
+        let previous = parse_storage(STORAGE).unwrap();
+
+        let appended = STORAGE.replace("U256 TotalSupply;", "U256 TotalSupply;\n Address Owner;");
+        assert!(storage_issues(&previous, &parse_storage(&appended).unwrap()).is_empty());
+
+        // Inserting before existing fields shifts their slots
+        let inserted = STORAGE.replace("U256 TotalSupply;", "Address Owner;\n U256 TotalSupply;");
+        let issues = storage_issues(&previous, &parse_storage(&inserted).unwrap());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].message,
+            "storage slot 2 changed from U256 TotalSupply to Address Owner"
+        );
+
+        let removed = STORAGE.replace("U256 TotalSupply;", "");
+        assert_eq!(
+            storage_issues(&previous, &parse_storage(&removed).unwrap()).len(),
+            1
+        );
+    }
+}
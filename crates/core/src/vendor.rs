@@ -0,0 +1,150 @@
+//! Vendoring dependencies for builds without network access
+//!
+//! Untrusted sources are built offline in a sandbox. Their dependencies are
+//! fetched beforehand by `cargo vendor` on the trusted host, pinned by the
+//! project's Cargo.lock, so the build can't fetch anything itself.
+//!
+//! Cargo and rustup read `.cargo/config.toml` and `rust-toolchain.toml` from
+//! the working directory and its parents, and both can name programs to run
+//! (credential providers, custom toolchains). `cargo vendor` therefore runs
+//! next to the vendor directory, outside the project.
+
+use eyre::{Context, Result};
+use std::{path::Path, process::Command, time::Duration};
+
+/// Vendor the locked dependencies of a project into `vendor_dir`
+///
+/// Returns the cargo configuration replacing the registry and git sources
+/// with the vendored copies, with the directory given as `mounted_at` (the
+/// path the offline build sees).
+pub fn vendor_dependencies(
+    project_root: &Path,
+    vendor_dir: &Path,
+    mounted_at: &str,
+    timeout: Option<Duration>,
+) -> Result<String> {
+    let manifest_path = project_root.join("Cargo.toml");
+    if !project_root.join("Cargo.lock").exists() {
+        return Err(eyre::eyre!(
+            "Cargo.lock is required to vendor dependencies of {}; commit it with the sources",
+            project_root.display()
+        ));
+    }
+
+    // Outside the project, so none of its configuration applies
+    let work_dir = vendor_dir.parent().unwrap_or(vendor_dir);
+    std::fs::create_dir_all(work_dir)
+        .with_context(|| format!("Failed to create {}", work_dir.display()))?;
+
+    let mut cmd = Command::new("cargo");
+    cmd.arg("vendor")
+        .arg("--locked")
+        .arg("--manifest-path")
+        .arg(&manifest_path)
+        .arg(vendor_dir)
+        .current_dir(work_dir);
+    tracing::debug!("Running: {:?}", cmd);
+
+    let output = crate::process::output_with_timeout(&mut cmd, "cargo vendor", timeout)?;
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "cargo vendor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    vendored_sources_config(&String::from_utf8_lossy(&output.stdout), mounted_at)
+}
+
+/// Point the source replacement printed by `cargo vendor` at `directory`
+fn vendored_sources_config(cargo_vendor_output: &str, directory: &str) -> Result<String> {
+    let mut config: toml::Table =
+        toml::from_str(cargo_vendor_output).context("Unexpected output from cargo vendor")?;
+
+    if let Some(sources) = config.get_mut("source").and_then(|s| s.as_table_mut()) {
+        for source in sources.iter_mut().filter_map(|(_, s)| s.as_table_mut()) {
+            if source.contains_key("directory") {
+                source.insert("directory".to_string(), directory.into());
+            }
+        }
+    }
+    // Fail rather than reach for the network if anything was not vendored
+    let net = config
+        .entry("net")
+        .or_insert_with(|| toml::Table::new().into());
+    if let Some(net) = net.as_table_mut() {
+        net.insert("offline".to_string(), true.into());
+    }
+
+    Ok(toml::to_string(&config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_vendored_sources_config() {
+        let output = r#"
+[source.crates-io]
+replace-with = "vendored-sources"
+
+[source."git+https://github.com/fluentlabs-xyz/fluentbase?tag=v0.4.0"]
+git = "https://github.com/fluentlabs-xyz/fluentbase"
+tag = "v0.4.0"
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "/home/server/jobs/42/vendor"
+"#;
+        let config: toml::Table =
+            toml::from_str(&vendored_sources_config(output, "/vendor").unwrap()).unwrap();
+        assert_eq!(
+            config["source"]["vendored-sources"]["directory"].as_str(),
+            Some("/vendor")
+        );
+        assert_eq!(
+            config["source"]["crates-io"]["replace-with"].as_str(),
+            Some("vendored-sources")
+        );
+        assert_eq!(config["net"]["offline"].as_bool(), Some(true));
+
+        let config: toml::Table =
+            toml::from_str(&vendored_sources_config("", "/vendor").unwrap()).unwrap();
+        assert!(config.get("source").is_none());
+    }
+
+    #[test]
+    fn test_project_config_is_not_applied() {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path().join("sources");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::create_dir_all(project.join(".cargo")).unwrap();
+        std::fs::write(
+            project.join("Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::write(project.join("src/lib.rs"), "").unwrap();
+        std::fs::write(
+            project.join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        // Cargo refuses to run at all with this config in effect
+        std::fs::write(project.join(".cargo/config.toml"), "not toml [").unwrap();
+
+        let config =
+            vendor_dependencies(&project, &dir.path().join("vendor"), "/vendor", None).unwrap();
+        assert!(config.contains("offline = true"));
+    }
+
+    #[test]
+    fn test_lockfile_required() {
+        let dir = TempDir::new().unwrap();
+        let err = vendor_dependencies(dir.path(), &dir.path().join("vendor"), "/vendor", None)
+            .unwrap_err();
+        assert!(err.to_string().contains("Cargo.lock is required"));
+    }
+}
@@ -1,19 +1,75 @@
 //! Contract verification functionality
 
-use crate::{build, CompilationResult, CompileConfig};
+use crate::{
+    artifacts::deploy, build, compat::Compat, config::Timeouts, hints, workspace::Sandbox,
+    BuildFeatures, BuilderError, CompilationResult, CompileConfig, HashAlgorithm, Provenance,
+    SelectorCollisions, SizeLimitPolicy, Unwrapping,
+};
 use eyre::Result;
-use std::path::PathBuf;
+use serde_json::Value;
+use std::{
+    path::{Component, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Where the sources to verify come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifySource {
+    /// A project directory on disk
+    Local(PathBuf),
+    /// A commit of a repository, cloned into a temporary directory for the build
+    Git {
+        repository: String,
+        /// Full commit hash, 40 hex digits
+        commit: String,
+        /// Project directory relative to the repository root, `.` for the root
+        project_path: String,
+    },
+}
+
+impl From<PathBuf> for VerifySource {
+    fn from(project_root: PathBuf) -> Self {
+        VerifySource::Local(project_root)
+    }
+}
 
 /// Configuration for contract verification
 pub struct VerifyConfig {
-    /// Path to the project root directory
-    pub project_root: PathBuf,
+    /// Project directory, or the repository commit to clone and build
+    pub source: VerifySource,
 
-    /// Deployed bytecode hash to verify against
+    /// Deployed bytecode hash to verify against, optionally tagged (`keccak256:...`)
     pub deployed_bytecode_hash: String,
 
+    /// Algorithm of an untagged `deployed_bytecode_hash`
+    pub hash_algorithm: HashAlgorithm,
+
     /// Optional compilation config override
     pub compile_config: Option<CompileConfig>,
+
+    /// metadata.json recorded for the deployment, used to explain mismatches
+    pub recorded_metadata: Option<Value>,
+
+    /// Provenance recorded for the deployment, in its metadata.json or deployments.json
+    pub deployed_provenance: Option<Provenance>,
+
+    /// Header stripped from the deployed code before it was hashed
+    pub deployed_unwrapping: Option<Unwrapping>,
+
+    /// Constructor arguments the contract was deployed with
+    pub constructor_args: Option<ConstructorArgs>,
+
+    /// Data of the deployment transaction, to check the constructor arguments against
+    pub deployment_data: Option<Vec<u8>>,
+}
+
+/// Constructor arguments supplied for verification
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstructorArgs {
+    /// Already ABI-encoded
+    Encoded(Vec<u8>),
+    /// Values encoded with the constructor inputs of the compiled ABI
+    Values(Vec<String>),
 }
 
 /// Result of contract verification
@@ -26,6 +82,21 @@ pub struct VerificationResult {
 
     /// Full compilation result (if needed for debugging)
     pub compilation_result: Option<CompilationResult>,
+
+    /// Likely causes of a mismatch, most likely first
+    pub hints: Vec<hints::MismatchHint>,
+
+    /// Hex encoded constructor arguments of the deployment, if known
+    pub constructor_args: Option<String>,
+
+    /// Earlier fluent-builder version whose behavior the rebuild reproduced
+    pub legacy_builder: Option<String>,
+
+    /// Algorithm the bytecode hashes were compared with
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Header stripped from the deployed code before it was hashed
+    pub unwrapping: Option<Unwrapping>,
 }
 
 /// Verification status
@@ -37,6 +108,15 @@ pub enum VerificationStatus {
     /// Bytecode mismatch
     Mismatch { expected: String, actual: String },
 
+    /// Bytecode mismatch explained by different features or profile
+    BuildSettingsMismatch {
+        expected: BuildFeatures,
+        actual: BuildFeatures,
+    },
+
+    /// Bytecode matches, but the deployment used other constructor arguments
+    ConstructorArgsMismatch { expected: String, actual: String },
+
     /// Compilation failed
     CompilationFailed(String),
 }
@@ -50,10 +130,116 @@ impl VerificationStatus {
 
 /// Verify that source code matches deployed bytecode
 pub fn verify(config: VerifyConfig) -> Result<VerificationResult> {
+    match config.source.clone() {
+        VerifySource::Local(_) => verify_project(config, None),
+        VerifySource::Git {
+            repository,
+            commit,
+            project_path,
+        } => {
+            if config.compile_config.as_ref().is_some_and(|c| c.offline) {
+                return Err(BuilderError::Offline {
+                    operation: format!("Cloning {}", repository),
+                }
+                .into());
+            }
+            crate::git::check_commit_hash(&commit)?;
+            let project = std::path::Path::new(&project_path);
+            eyre::ensure!(
+                project
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir)),
+                "Project path {} is not inside the repository",
+                project_path
+            );
+
+            let sandbox = Sandbox::create(
+                &std::env::temp_dir(),
+                &format!(
+                    "{}-{}",
+                    unique_sandbox_name("fluent-builder-clone"),
+                    commit
+                        .chars()
+                        .filter(char::is_ascii_alphanumeric)
+                        .take(12)
+                        .collect::<String>()
+                ),
+            )?;
+            let cleanup = sandbox.cleanup_guard();
+            let git_timeout = config
+                .compile_config
+                .as_ref()
+                .map_or(Timeouts::default().git, |c| c.timeouts.git);
+            let result = crate::git::clone_at_commit(
+                &repository,
+                &commit,
+                &sandbox.sources_dir,
+                git_timeout,
+            )
+            .and_then(|()| {
+                verify_project(config, Some((&sandbox, sandbox.sources_dir.join(project))))
+            });
+            cleanup.disarm();
+            sandbox.finish(result.is_ok());
+            result
+        }
+    }
+}
+
+/// Verify the local project, or the checkout at `cloned` in its sandbox
+fn verify_project(
+    config: VerifyConfig,
+    cloned: Option<(&Sandbox, PathBuf)>,
+) -> Result<VerificationResult> {
     // Build compilation config
-    let compile_config = config
-        .compile_config
-        .unwrap_or_else(|| CompileConfig::new(config.project_root.clone()));
+    let mut compile_config = match (&config.source, config.compile_config) {
+        (_, Some(compile_config)) => compile_config,
+        (VerifySource::Local(project_root), None) => CompileConfig::new(project_root.clone()),
+        (VerifySource::Git { .. }, None) => CompileConfig::new(PathBuf::new()),
+    };
+    if let Some((sandbox, project_root)) = cloned {
+        compile_config.project_root = project_root;
+        sandbox.configure(&mut compile_config);
+    }
+
+    // Rebuild the way the builder of the deployment did
+    if compile_config.compat.is_none() {
+        let compat = Compat::of_deployment(
+            config.deployed_provenance.as_ref(),
+            config.recorded_metadata.as_ref(),
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                "Unknown builder version of the deployment, rebuilding with the current one: {}",
+                e
+            );
+            None
+        });
+        if let Some(compat) = compat.filter(Compat::is_legacy) {
+            for change in compat.legacy_behaviors() {
+                tracing::info!(
+                    "fluent-builder {} predates {} (added in {}), rebuilding without it",
+                    compat.builder_version,
+                    change.description,
+                    change.since
+                );
+            }
+            compile_config.compat = Some(compat);
+        }
+    }
+    let legacy_builder = compile_config
+        .compat
+        .as_ref()
+        .map(|compat| compat.builder_version.clone());
+    // The deployed contract dispatches the way it does; colliding selectors don't make it
+    // unverifiable
+    compile_config.artifacts.selector_collisions = SelectorCollisions::Warn;
+    // Neither does a size limit, the contract is deployed already
+    compile_config.size_limits.policy = SizeLimitPolicy::Warn;
+
+    // A tagged hash names its algorithm
+    let (tagged, deployed_hash) = HashAlgorithm::split_tagged(config.deployed_bytecode_hash.trim());
+    let hash_algorithm = tagged.unwrap_or(config.hash_algorithm);
 
     // Compile the contract
     let compilation_result = match build(&compile_config) {
@@ -63,31 +249,232 @@ pub fn verify(config: VerifyConfig) -> Result<VerificationResult> {
                 status: VerificationStatus::CompilationFailed(e.to_string()),
                 contract_name: String::new(),
                 compilation_result: None,
+                hints: Vec::new(),
+                constructor_args: None,
+                legacy_builder,
+                hash_algorithm,
+                unwrapping: config.deployed_unwrapping,
             });
         }
     };
 
     // Get hashes
-    let expected_hash = normalize_hash(&config.deployed_bytecode_hash);
-    let actual_hash = normalize_hash(&get_rwasm_hash(&compilation_result));
+    let expected_hash = normalize_hash(deployed_hash);
+    let actual_hash = hash_algorithm.hash(&compilation_result.outputs.rwasm);
 
     // Compare
-    let status = if expected_hash == actual_hash {
-        VerificationStatus::Success
-    } else {
+    let recorded = config.recorded_metadata.as_ref();
+    let mut constructor_args = None;
+    let wasm = &compilation_result.outputs.wasm;
+    // The deployment transaction must have deployed the compiled wasm
+    let deployed_args = config
+        .deployment_data
+        .as_deref()
+        .map(|data| (data, deploy::strip_init_code(data, wasm)));
+    let status = if expected_hash != actual_hash {
+        match build_settings_mismatch(recorded, &compile_config, &expected_hash) {
+            Some((expected, actual)) => {
+                VerificationStatus::BuildSettingsMismatch { expected, actual }
+            }
+            None => VerificationStatus::Mismatch {
+                expected: expected_hash,
+                actual: actual_hash,
+            },
+        }
+    } else if let Some((data, None)) = deployed_args {
         VerificationStatus::Mismatch {
-            expected: expected_hash,
-            actual: actual_hash,
+            expected: hash_algorithm.hash(&data[..data.len().min(wasm.len())]),
+            actual: hash_algorithm.hash(wasm),
+        }
+    } else {
+        let supplied =
+            encode_constructor_args(config.constructor_args.as_ref(), &compilation_result)?;
+        match (deployed_args.and_then(|(_, args)| args), supplied) {
+            (Some(deployed), Some(supplied)) if deployed != supplied.as_slice() => {
+                VerificationStatus::ConstructorArgsMismatch {
+                    expected: to_hex(deployed),
+                    actual: to_hex(&supplied),
+                }
+            }
+            (deployed, supplied) => {
+                constructor_args = deployed.map(to_hex).or(supplied.as_deref().map(to_hex));
+                VerificationStatus::Success
+            }
         }
     };
 
+    // Explain the mismatch by comparing against the recorded build
+    let bytecode_matches = matches!(
+        status,
+        VerificationStatus::Success | VerificationStatus::ConstructorArgsMismatch { .. }
+    );
+    let mut hints = match recorded {
+        Some(recorded) if !bytecode_matches => local_metadata(&compilation_result)
+            .map(|local| hints::diagnose_mismatch(recorded, &local))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    if let (false, Some((_, None))) = (bytecode_matches, deployed_args) {
+        hints.push(hints::MismatchHint {
+            cause: "deployment_data".to_string(),
+            message: "The deployment transaction does not start with the compiled wasm".to_string(),
+            rank: u8::MAX,
+        });
+    }
+    if let VerificationStatus::ConstructorArgsMismatch { expected, actual } = &status {
+        hints.push(hints::MismatchHint {
+            cause: "constructor_args".to_string(),
+            message: format!(
+                "Bytecode matches, but the contract was deployed with constructor arguments {} \
+                 instead of {}",
+                expected, actual
+            ),
+            rank: u8::MAX,
+        });
+    }
+    if let (false, Some(deployed)) = (bytecode_matches, &config.deployed_provenance) {
+        let local = Provenance::from_config(&compile_config);
+        for hint in hints::diagnose_provenance(deployed, &local) {
+            if !hints.iter().any(|h| h.cause == hint.cause) {
+                hints.push(hint);
+            }
+        }
+        hints.sort_by_key(|h| std::cmp::Reverse(h.rank));
+    }
+
     Ok(VerificationResult {
         status,
         contract_name: compilation_result.contract.name.clone(),
         compilation_result: Some(compilation_result),
+        hints,
+        constructor_args,
+        legacy_builder,
+        hash_algorithm,
+        unwrapping: config.deployed_unwrapping,
     })
 }
 
+/// Encode supplied constructor arguments against the compiled ABI
+fn encode_constructor_args(
+    args: Option<&ConstructorArgs>,
+    result: &CompilationResult,
+) -> Result<Option<Vec<u8>>> {
+    match args {
+        None => Ok(None),
+        Some(ConstructorArgs::Encoded(encoded)) => Ok(Some(encoded.clone())),
+        Some(ConstructorArgs::Values(values)) => {
+            let artifacts = result.artifacts.as_ref().ok_or_else(|| {
+                eyre::eyre!(
+                    "Constructor values need the contract ABI; pass encoded arguments instead"
+                )
+            })?;
+            deploy::encode_constructor_args(&artifacts.deploy.constructor.inputs, values).map(Some)
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Compare the build settings recorded for the deployed code with those of the rebuild
+///
+/// Recorded metadata only describes the deployment if one of its rWASM hashes
+/// is the deployed one. Without such settings the result is reported as a plain
+/// hash mismatch.
+fn build_settings_mismatch(
+    recorded: Option<&Value>,
+    compile_config: &CompileConfig,
+    deployed_hash: &str,
+) -> Option<(BuildFeatures, BuildFeatures)> {
+    let recorded = recorded?;
+    let recorded_hashes: Vec<String> = ["/bytecode/rwasm/hash", "/bytecode/rwasm/code_hash"]
+        .iter()
+        .filter_map(|pointer| recorded.pointer(pointer)?.as_str())
+        .map(|hash| normalize_hash(HashAlgorithm::split_tagged(hash).1))
+        .collect();
+    if !recorded_hashes.is_empty() && !recorded_hashes.iter().any(|hash| hash == deployed_hash) {
+        return None;
+    }
+    let expected = BuildFeatures::from_metadata(recorded).ok()?;
+    let actual = BuildFeatures::from_config(compile_config);
+
+    (expected != actual).then_some((expected, actual))
+}
+
+/// Metadata of the local build as JSON, if artifacts were generated
+fn local_metadata(result: &CompilationResult) -> Option<Value> {
+    let artifacts = result.artifacts.as_ref()?;
+    serde_json::to_value(&artifacts.metadata).ok()
+}
+
+/// Verify the sources in a verification archive against a deployed bytecode hash
+///
+/// The archive, a `.tar.gz` or `.zip` written by `create_verification_archive`,
+/// is extracted into a temporary directory that is removed afterwards.
+#[cfg(feature = "archive")]
+pub fn verify_from_archive(
+    archive_path: &std::path::Path,
+    deployed_hash: &str,
+) -> Result<VerificationResult> {
+    use eyre::Context;
+
+    let sandbox = Sandbox::create(
+        &std::env::temp_dir(),
+        &unique_sandbox_name("fluent-builder-verify"),
+    )?;
+    let cleanup = sandbox.cleanup_guard();
+    let result = extract_project(&sandbox, archive_path).and_then(|project_root| {
+        let mut compile_config = CompileConfig::new(&project_root);
+        sandbox.configure(&mut compile_config);
+        verify(VerifyConfig {
+            source: VerifySource::Local(project_root),
+            deployed_bytecode_hash: deployed_hash.to_string(),
+            hash_algorithm: HashAlgorithm::default(),
+            compile_config: Some(compile_config),
+            recorded_metadata: None,
+            deployed_provenance: None,
+            deployed_unwrapping: None,
+            constructor_args: None,
+            deployment_data: None,
+        })
+    });
+    cleanup.disarm();
+    sandbox.finish(result.is_ok());
+    result.with_context(|| format!("Failed to verify {}", archive_path.display()))
+}
+
+/// Sandbox name unique to this call, so concurrent verifications never share a directory
+fn unique_sandbox_name(prefix: &str) -> String {
+    static NEXT_SANDBOX: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{}-{}-{}",
+        prefix,
+        std::process::id(),
+        NEXT_SANDBOX.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Extract an archive and return the project directory it holds
+#[cfg(feature = "archive")]
+fn extract_project(sandbox: &Sandbox, archive_path: &std::path::Path) -> Result<PathBuf> {
+    let sources = sandbox.extract_sources(archive_path)?;
+    // Archives hold a single directory named after the project
+    let mut entries = std::fs::read_dir(&sources)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let project_root = match entries.as_slice() {
+        [dir] if dir.is_dir() => entries.remove(0),
+        _ => sources,
+    };
+    eyre::ensure!(
+        project_root.join("Cargo.toml").is_file(),
+        "{} does not contain a Cargo project",
+        archive_path.display()
+    );
+    Ok(project_root)
+}
+
 /// Normalize hash format (remove 0x prefix, lowercase)
 pub fn normalize_hash(hash: &str) -> String {
     hash.trim()
@@ -96,11 +483,6 @@ pub fn normalize_hash(hash: &str) -> String {
         .to_lowercase()
 }
 
-/// Get rWASM hash from compilation result
-fn get_rwasm_hash(result: &CompilationResult) -> String {
-    crate::builder::hash_bytes(&result.outputs.rwasm)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,6 +495,79 @@ mod tests {
         assert_eq!(normalize_hash("ABCDEF123456"), "abcdef123456");
     }
 
+    #[test]
+    fn test_unique_sandbox_names() {
+        let first = unique_sandbox_name("fluent-builder-verify");
+        assert!(first.starts_with(&format!("fluent-builder-verify-{}-", std::process::id())));
+        assert_ne!(first, unique_sandbox_name("fluent-builder-verify"));
+    }
+
+    #[test]
+    fn test_verify_from_git() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let origin = tmp.path().join("origin");
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .current_dir(&origin)
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?}", args);
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        std::fs::create_dir_all(origin.join("token/src")).unwrap();
+        std::fs::write(
+            origin.join("token/Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(origin.join("token/src/lib.rs"), "").unwrap();
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "token"]);
+        let with_token = git(&["rev-parse", "HEAD"]);
+        git(&["rm", "-q", "-r", "token"]);
+        git(&["commit", "-q", "-m", "remove token"]);
+        let without_token = git(&["rev-parse", "HEAD"]);
+
+        let verify_at = |commit: &str, project_path: &str| {
+            verify(VerifyConfig {
+                source: VerifySource::Git {
+                    repository: origin.to_string_lossy().into_owned(),
+                    commit: commit.to_string(),
+                    project_path: project_path.to_string(),
+                },
+                deployed_bytecode_hash: "0xabc".to_string(),
+                hash_algorithm: HashAlgorithm::default(),
+                compile_config: None,
+                recorded_metadata: None,
+                deployed_provenance: None,
+                deployed_unwrapping: None,
+                constructor_args: None,
+                deployment_data: None,
+            })
+        };
+        let failure = |commit: &str| match verify_at(commit, "token").unwrap().status {
+            VerificationStatus::CompilationFailed(message) => message,
+            status => panic!("expected a compilation failure, got {:?}", status),
+        };
+        // Each commit is checked out as it was
+        assert!(failure(&with_token).contains("no fluentbase-sdk dependency"));
+        assert!(failure(&without_token).contains("does not exist"));
+
+        let error = |commit: &str, project_path: &str| {
+            verify_at(commit, project_path)
+                .err()
+                .expect("verification should fail")
+                .to_string()
+        };
+        for commit in [&with_token[..12], "--upload-pack=touch x", "HEAD"] {
+            assert!(error(commit, "token").contains("not a full commit hash"));
+        }
+        assert!(error(&with_token, "../token").contains("is not inside the repository"));
+    }
+
     #[test]
     fn test_verification_status_is_success() {
         assert!(VerificationStatus::Success.is_success());
@@ -123,4 +578,73 @@ mod tests {
         .is_success());
         assert!(!VerificationStatus::CompilationFailed("error".to_string()).is_success());
     }
+
+    #[test]
+    fn test_build_settings_mismatch() {
+        let recorded = serde_json::json!({
+            "bytecode": {"rwasm": {"hash": "sha256:0xAA", "code_hash": "keccak256:0xbb"}},
+            "compilation_settings": {
+                "build_cfg": {
+                    "profile": "release",
+                    "features": ["testnet"],
+                    "no_default_features": true
+                }
+            }
+        });
+        let mut config = CompileConfig::new(".");
+        config.no_default_features = true;
+
+        let (expected, actual) = build_settings_mismatch(Some(&recorded), &config, "aa").unwrap();
+        assert_eq!(expected.features, vec!["testnet"]);
+        assert!(actual.features.is_empty());
+        // Matched by the chain's code hash as well
+        assert!(build_settings_mismatch(Some(&recorded), &config, "bb").is_some());
+
+        // Metadata of another build says nothing about the deployed code
+        assert_eq!(
+            build_settings_mismatch(Some(&recorded), &config, "cc"),
+            None
+        );
+        assert_eq!(build_settings_mismatch(None, &config, "aa"), None);
+
+        config.features = vec!["testnet".to_string()];
+        assert_eq!(
+            build_settings_mismatch(Some(&recorded), &config, "aa"),
+            None
+        );
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_extract_project_from_archive() -> Result<()> {
+        use crate::{create_verification_archive, ArchiveFormat, ArchiveOptions};
+
+        let dir = tempfile::tempdir()?;
+        let project = dir.path().join("token");
+        std::fs::create_dir_all(project.join("src"))?;
+        std::fs::write(project.join("Cargo.toml"), "[package]\nname = \"token\"")?;
+        std::fs::write(project.join("src/lib.rs"), "// token")?;
+
+        for (name, format) in [
+            ("token.tar.gz", ArchiveFormat::TarGz),
+            ("token.zip", ArchiveFormat::Zip),
+        ] {
+            let archive = dir.path().join(name);
+            let options = ArchiveOptions {
+                format,
+                ..Default::default()
+            };
+            create_verification_archive(&project, &archive, &options)?;
+
+            let sandbox = Sandbox::create(&dir.path().join("builds"), name)?;
+            let extracted = extract_project(&sandbox, &archive)?;
+            assert!(extracted.ends_with("token"));
+            assert_eq!(
+                std::fs::read_to_string(extracted.join("src/lib.rs"))?,
+                "// token"
+            );
+            sandbox.finish(true);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,97 @@
+//! Recompiling a contract whenever its sources change
+//!
+//! `src/`, `Cargo.toml` and the toolchain file are watched with the platform's
+//! file notifications. A burst of writes, such as an editor saving several
+//! files, triggers a single build.
+
+use crate::{build, CompilationResult, CompileConfig};
+use eyre::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::{ops::ControlFlow, path::Path, sync::mpsc, time::Duration};
+
+/// How long the sources must be left alone before a rebuild starts
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Files directly in the project root that affect the build
+const WATCHED_FILES: &[&str] = &["Cargo.toml", "rust-toolchain.toml", "rust-toolchain"];
+
+/// Build the project, then rebuild it each time its sources change
+///
+/// `on_build` gets the result of every build, failed ones included, and
+/// stops watching by returning [`ControlFlow::Break`].
+pub fn watch(
+    config: &CompileConfig,
+    mut on_build: impl FnMut(Result<CompilationResult>) -> ControlFlow<()>,
+) -> Result<()> {
+    let project_root = crate::paths::canonicalize_path(&config.project_root)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).context("Failed to start the file watcher")?;
+    watcher
+        .watch(&project_root, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", project_root.display()))?;
+    let src = project_root.join("src");
+    if src.is_dir() {
+        watcher
+            .watch(&src, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", src.display()))?;
+    }
+
+    loop {
+        if on_build(build(config)).is_break() {
+            return Ok(());
+        }
+        loop {
+            let event = rx
+                .recv()
+                .context("File watcher stopped")?
+                .context("File watcher failed")?;
+            if affects_build(&project_root, &event) {
+                break;
+            }
+        }
+        while rx.recv_timeout(SETTLE_TIME).is_ok() {}
+    }
+}
+
+/// Whether `event` changed a file the build reads
+fn affects_build(project_root: &Path, event: &Event) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+    event
+        .paths
+        .iter()
+        .any(|path| match path.strip_prefix(project_root) {
+            Ok(relative) => {
+                relative.starts_with("src")
+                    || WATCHED_FILES.iter().any(|file| relative == Path::new(file))
+            }
+            Err(_) => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, ModifyKind};
+
+    #[test]
+    fn test_affects_build() {
+        let root = Path::new("/work/token");
+        let modified =
+            |path: &str| Event::new(EventKind::Modify(ModifyKind::Any)).add_path(root.join(path));
+
+        assert!(affects_build(root, &modified("src/lib.rs")));
+        assert!(affects_build(root, &modified("src/storage/mod.rs")));
+        assert!(affects_build(root, &modified("Cargo.toml")));
+        assert!(affects_build(root, &modified("rust-toolchain.toml")));
+
+        // Build outputs and unrelated files
+        assert!(!affects_build(root, &modified("out/token.wasm/lib.wasm")));
+        assert!(!affects_build(root, &modified("README.md")));
+        assert!(!affects_build(root, &modified("Cargo.lock")));
+        let read = Event::new(EventKind::Access(AccessKind::Any)).add_path(root.join("src/lib.rs"));
+        assert!(!affects_build(root, &read));
+    }
+}
@@ -0,0 +1,630 @@
+//! Workspace dependency graph, incremental batch builds and build sandboxes
+
+use crate::{
+    builder::{calculate_source_hash, should_skip_path},
+    CleanupGuard, CompileConfig,
+};
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A package in a Cargo workspace
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceMember {
+    /// Package name
+    pub name: String,
+    /// Directory containing the package's Cargo.toml
+    pub manifest_dir: PathBuf,
+    /// Names of workspace members this package depends on by path
+    pub path_dependencies: Vec<String>,
+    /// Whether the package is a Fluent contract (depends on fluentbase-sdk)
+    pub is_contract: bool,
+}
+
+/// Path-dependency graph between workspace members
+#[derive(Debug, Clone)]
+pub struct WorkspaceGraph {
+    /// Workspace root directory
+    pub root: PathBuf,
+    /// Members sorted by name
+    pub members: Vec<WorkspaceMember>,
+}
+
+impl WorkspaceGraph {
+    /// Load the graph from `cargo metadata`
+    pub fn load(workspace_root: &Path) -> Result<Self> {
+        Self::from_metadata(&cargo_metadata(workspace_root)?)
+    }
+
+    /// Build the graph from `cargo metadata --no-deps` output
+    pub fn from_metadata(metadata: &Value) -> Result<Self> {
+        let root = metadata["workspace_root"]
+            .as_str()
+            .map(PathBuf::from)
+            .ok_or_else(|| eyre::eyre!("cargo metadata has no workspace_root"))?;
+
+        let packages = metadata["packages"]
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("cargo metadata has no packages"))?;
+
+        // Map member directories to names so path dependencies can be resolved
+        let mut dirs = BTreeMap::new();
+        for package in packages {
+            let name = package["name"].as_str().unwrap_or_default();
+            if let Some(dir) = package["manifest_path"]
+                .as_str()
+                .and_then(|p| Path::new(p).parent())
+            {
+                dirs.insert(dir.to_path_buf(), name.to_string());
+            }
+        }
+
+        let mut members: Vec<WorkspaceMember> = packages
+            .iter()
+            .filter_map(|package| {
+                let name = package["name"].as_str()?.to_string();
+                let manifest_dir = Path::new(package["manifest_path"].as_str()?)
+                    .parent()?
+                    .to_path_buf();
+                let dependencies = package["dependencies"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+
+                // Dev-dependencies do not affect the contract build
+                let build_dependencies = dependencies.iter().filter(|d| d["kind"] != "dev");
+
+                let mut path_dependencies: Vec<String> = build_dependencies
+                    .clone()
+                    .filter_map(|d| d["path"].as_str())
+                    .filter_map(|p| dirs.get(Path::new(p)).cloned())
+                    .collect();
+                path_dependencies.sort();
+                path_dependencies.dedup();
+
+                Some(WorkspaceMember {
+                    name,
+                    manifest_dir,
+                    path_dependencies,
+                    is_contract: build_dependencies
+                        .clone()
+                        .any(|d| d["name"] == "fluentbase-sdk"),
+                })
+            })
+            .collect();
+        members.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self { root, members })
+    }
+
+    /// Find a member by name
+    pub fn member(&self, name: &str) -> Option<&WorkspaceMember> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    /// Members ordered so that every package comes after its path dependencies
+    pub fn build_order(&self) -> Result<Vec<&WorkspaceMember>> {
+        let mut order = Vec::new();
+        let mut done = BTreeSet::new();
+        let mut visiting = BTreeSet::new();
+
+        for member in &self.members {
+            self.visit(member, &mut visiting, &mut done, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    fn visit<'a>(
+        &'a self,
+        member: &'a WorkspaceMember,
+        visiting: &mut BTreeSet<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+        order: &mut Vec<&'a WorkspaceMember>,
+    ) -> Result<()> {
+        if done.contains(member.name.as_str()) {
+            return Ok(());
+        }
+        if !visiting.insert(member.name.as_str()) {
+            return Err(eyre::eyre!("Dependency cycle involving {}", member.name));
+        }
+
+        for dependency in &member.path_dependencies {
+            if let Some(dependency) = self.member(dependency) {
+                self.visit(dependency, visiting, done, order)?;
+            }
+        }
+
+        visiting.remove(member.name.as_str());
+        done.insert(member.name.as_str());
+        order.push(member);
+        Ok(())
+    }
+
+    /// Names of the given members and everything that depends on them, directly or not
+    pub fn dependents_closure(&self, changed: &BTreeSet<String>) -> BTreeSet<String> {
+        let mut affected = changed.clone();
+
+        loop {
+            let before = affected.len();
+            for member in &self.members {
+                if member
+                    .path_dependencies
+                    .iter()
+                    .any(|d| affected.contains(d))
+                {
+                    affected.insert(member.name.clone());
+                }
+            }
+            if affected.len() == before {
+                return affected;
+            }
+        }
+    }
+
+    /// Source fingerprints of all members
+    pub fn fingerprints(&self) -> Result<BTreeMap<String, String>> {
+        self.members
+            .iter()
+            .map(|m| Ok((m.name.clone(), calculate_source_hash(&m.manifest_dir)?)))
+            .collect()
+    }
+
+    /// Contracts that need rebuilding, in build order
+    ///
+    /// A contract is affected if its own sources or those of any path
+    /// dependency changed since the fingerprints stored in `cache`.
+    pub fn affected_contracts(
+        &self,
+        fingerprints: &BTreeMap<String, String>,
+        cache: &BuildCache,
+    ) -> Result<Vec<&WorkspaceMember>> {
+        let changed: BTreeSet<String> = fingerprints
+            .iter()
+            .filter(|(name, hash)| cache.fingerprints.get(*name) != Some(*hash))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let affected = self.dependents_closure(&changed);
+
+        Ok(self
+            .build_order()?
+            .into_iter()
+            .filter(|m| m.is_contract && affected.contains(&m.name))
+            .collect())
+    }
+}
+
+/// Source fingerprints from the last successful batch build
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BuildCache {
+    /// Member name -> source hash
+    #[serde(default)]
+    pub fingerprints: BTreeMap<String, String>,
+}
+
+impl BuildCache {
+    /// Cache file location inside the workspace target directory
+    pub fn path(workspace_root: &Path) -> PathBuf {
+        CompileConfig::new(workspace_root)
+            .target_directory()
+            .join("fluent-builder")
+            .join("build-cache.json")
+    }
+
+    /// Load the cache, returning an empty one if missing or unreadable
+    pub fn load(workspace_root: &Path) -> Self {
+        std::fs::read_to_string(Self::path(workspace_root))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Persist the cache
+    pub fn save(&self, workspace_root: &Path) -> Result<()> {
+        let path = Self::path(workspace_root);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Where cargo keeps the lockfile and build outputs of a package
+///
+/// A contract inside a workspace shares `Cargo.lock` and `target/` with the
+/// other members, at the workspace root rather than next to its Cargo.toml.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CargoLayout {
+    /// Root of the workspace the package belongs to; the package itself outside a workspace
+    pub workspace_root: PathBuf,
+    /// Target directory, including `CARGO_TARGET_DIR` and `build.target-dir` settings
+    pub target_directory: PathBuf,
+}
+
+impl CargoLayout {
+    /// Ask `cargo metadata` where the package at `project_root` is built
+    pub fn locate(project_root: &Path) -> Result<Self> {
+        Self::from_metadata(&cargo_metadata(project_root)?)
+    }
+
+    /// Read the layout from `cargo metadata` output
+    pub fn from_metadata(metadata: &Value) -> Result<Self> {
+        let path = |key: &str| {
+            metadata[key]
+                .as_str()
+                .map(PathBuf::from)
+                .ok_or_else(|| eyre::eyre!("cargo metadata has no {}", key))
+        };
+        Ok(Self {
+            workspace_root: path("workspace_root")?,
+            target_directory: path("target_directory")?,
+        })
+    }
+
+    /// The workspace's Cargo.lock
+    pub fn cargo_lock(&self) -> PathBuf {
+        self.workspace_root.join("Cargo.lock")
+    }
+
+    /// Workspace root relative to `project_root`, e.g. `../..`; None if the package is the
+    /// workspace root
+    pub fn relative_workspace_root(&self, project_root: &Path) -> Option<String> {
+        let project_root =
+            crate::canonicalize_path(project_root).unwrap_or_else(|_| project_root.to_path_buf());
+        let depth = project_root
+            .strip_prefix(&self.workspace_root)
+            .ok()?
+            .components()
+            .count();
+        (depth > 0).then(|| vec![".."; depth].join("/"))
+    }
+}
+
+/// `cargo metadata --no-deps` of the package or workspace at `dir`
+fn cargo_metadata(dir: &Path) -> Result<Value> {
+    run_cargo_metadata(dir, &["--no-deps"])
+}
+
+/// `cargo metadata` of the package or workspace at `dir` with the resolved dependency graph
+pub(crate) fn resolved_cargo_metadata(dir: &Path, offline: bool) -> Result<Value> {
+    run_cargo_metadata(dir, if offline { &["--offline"] } else { &[] })
+}
+
+fn run_cargo_metadata(dir: &Path, args: &[&str]) -> Result<Value> {
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(dir)
+        .args(["metadata", "--format-version", "1"])
+        .args(args);
+    // Inside a build script, report the contract's own target directory, not the host crate's
+    if crate::build_script::in_build_script() {
+        for var in crate::build_script::OUTER_CARGO_ENV {
+            cmd.env_remove(var);
+        }
+    }
+    let output = cmd.output().context("Failed to execute cargo metadata")?;
+
+    if !output.status.success() {
+        return Err(eyre::eyre!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout).context("Failed to parse cargo metadata")
+}
+
+/// Isolated directories of a single build
+///
+/// Everything lives under `<parent>/<name>/`: `sources/` for copied or
+/// extracted sources, `target/`, `out/` and `tmp/`. Names are chosen by the
+/// caller, so the layout of a build is known in advance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sandbox {
+    pub root: PathBuf,
+    pub sources_dir: PathBuf,
+    pub target_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub temp_dir: PathBuf,
+    /// Name for a Docker container started by the build
+    pub container_name: String,
+    /// Leave the directories in place when the build fails, for debugging
+    pub keep_on_failure: bool,
+}
+
+impl Sandbox {
+    /// Create empty directories for build `name` under `parent`, replacing leftovers of an earlier
+    /// run
+    pub fn create(parent: &Path, name: &str) -> Result<Self> {
+        let root = parent.join(name);
+        if root.exists() {
+            std::fs::remove_dir_all(&root)
+                .with_context(|| format!("Failed to remove {}", root.display()))?;
+        }
+        let sandbox = Self {
+            sources_dir: root.join("sources"),
+            target_dir: root.join("target"),
+            output_dir: root.join("out"),
+            temp_dir: root.join("tmp"),
+            container_name: format!("fluent-builder-{}", name),
+            keep_on_failure: false,
+            root,
+        };
+        for dir in [&sandbox.target_dir, &sandbox.output_dir, &sandbox.temp_dir] {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        Ok(sandbox)
+    }
+
+    pub fn keep_on_failure(mut self, keep: bool) -> Self {
+        self.keep_on_failure = keep;
+        self
+    }
+
+    /// Copy the sources of `project_root` (without build outputs and hidden files) into `sources/`
+    pub fn copy_sources(&self, project_root: &Path) -> Result<PathBuf> {
+        for entry in walkdir::WalkDir::new(project_root)
+            .into_iter()
+            .filter_entry(|e| {
+                !should_skip_path(e.path().strip_prefix(project_root).unwrap_or(e.path()))
+            })
+        {
+            let entry =
+                entry.with_context(|| format!("Failed to read {}", project_root.display()))?;
+            let relative = entry
+                .path()
+                .strip_prefix(project_root)
+                .unwrap_or(entry.path());
+            let destination = self.sources_dir.join(relative);
+            if entry.file_type().is_dir() {
+                std::fs::create_dir_all(&destination)?;
+            } else if entry.file_type().is_file() {
+                std::fs::copy(entry.path(), &destination)
+                    .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+            }
+        }
+        Ok(self.sources_dir.clone())
+    }
+
+    /// Unpack a `.tar.gz` or `.zip` source archive into `sources/`
+    #[cfg(feature = "archive")]
+    pub fn extract_sources(&self, archive: &Path) -> Result<PathBuf> {
+        let file = std::fs::File::open(archive)
+            .with_context(|| format!("Failed to open {}", archive.display()))?;
+        if archive.extension().is_some_and(|ext| ext == "zip") {
+            zip::ZipArchive::new(file)
+                .and_then(|mut zip| zip.extract(&self.sources_dir))
+                .with_context(|| format!("Failed to extract {}", archive.display()))?;
+        } else {
+            tar::Archive::new(flate2::read::GzDecoder::new(file))
+                .unpack(&self.sources_dir)
+                .with_context(|| format!("Failed to extract {}", archive.display()))?;
+        }
+        Ok(self.sources_dir.clone())
+    }
+
+    /// Build into the sandbox's target and output directories
+    pub fn configure(&self, config: &mut CompileConfig) {
+        config.target_dir = Some(self.target_dir.clone());
+        config.output_dir = self.output_dir.clone();
+    }
+
+    /// Guard removing the sandbox if the process is interrupted before [`Sandbox::finish`]
+    pub fn cleanup_guard(&self) -> CleanupGuard {
+        CleanupGuard::remove_path(&self.root)
+    }
+
+    /// Remove the directories, unless the build failed and they are kept for debugging
+    pub fn finish(&self, succeeded: bool) {
+        if !succeeded && self.keep_on_failure {
+            tracing::info!(
+                "Kept the failed build's directories at {}",
+                self.root.display()
+            );
+            return;
+        }
+        if let Err(e) = std::fs::remove_dir_all(&self.root) {
+            tracing::warn!("Failed to remove {}: {}", self.root.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn package(name: &str, deps: Value) -> Value {
+        json!({
+            "name": name,
+            "manifest_path": format!("/ws/{}/Cargo.toml", name),
+            "dependencies": deps,
+        })
+    }
+
+    fn graph() -> WorkspaceGraph {
+        let metadata = json!({
+            "workspace_root": "/ws",
+            "packages": [
+                package("token", json!([
+                    {"name": "fluentbase-sdk", "kind": null},
+                    {"name": "math", "kind": null, "path": "/ws/math"},
+                ])),
+                package("vault", json!([
+                    {"name": "fluentbase-sdk", "kind": null},
+                    {"name": "token", "kind": null, "path": "/ws/token"},
+                    {"name": "testkit", "kind": "dev", "path": "/ws/testkit"},
+                ])),
+                package("math", json!([])),
+                package("testkit", json!([])),
+                package("oracle", json!([{"name": "fluentbase-sdk", "kind": null}])),
+            ],
+        });
+        WorkspaceGraph::from_metadata(&metadata).unwrap()
+    }
+
+    #[test]
+    fn test_graph_from_metadata() {
+        let graph = graph();
+        assert_eq!(graph.members.len(), 5);
+        assert_eq!(
+            graph.member("vault").unwrap().path_dependencies,
+            vec!["token"]
+        );
+        assert!(graph.member("token").unwrap().is_contract);
+        assert!(!graph.member("math").unwrap().is_contract);
+
+        let order: Vec<&str> = graph
+            .build_order()
+            .unwrap()
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(order, vec!["math", "oracle", "testkit", "token", "vault"]);
+    }
+
+    #[test]
+    fn test_affected_contracts() {
+        let graph = graph();
+        let fingerprints: BTreeMap<String, String> = graph
+            .members
+            .iter()
+            .map(|m| (m.name.clone(), "h1".to_string()))
+            .collect();
+
+        let mut cache = BuildCache {
+            fingerprints: fingerprints.clone(),
+        };
+        assert!(graph
+            .affected_contracts(&fingerprints, &cache)
+            .unwrap()
+            .is_empty());
+
+        cache
+            .fingerprints
+            .insert("math".to_string(), "h0".to_string());
+        let affected: Vec<&str> = graph
+            .affected_contracts(&fingerprints, &cache)
+            .unwrap()
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(affected, vec!["token", "vault"]);
+
+        // Dev-dependency changes do not trigger rebuilds
+        cache.fingerprints = fingerprints.clone();
+        cache
+            .fingerprints
+            .insert("testkit".to_string(), "h0".to_string());
+        assert!(graph
+            .affected_contracts(&fingerprints, &cache)
+            .unwrap()
+            .is_empty());
+
+        // Everything is affected on a cold cache
+        let cold = graph
+            .affected_contracts(&fingerprints, &BuildCache::default())
+            .unwrap();
+        assert_eq!(cold.len(), 3);
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let metadata = json!({
+            "workspace_root": "/ws",
+            "packages": [
+                package("a", json!([{"name": "b", "path": "/ws/b"}])),
+                package("b", json!([{"name": "a", "path": "/ws/a"}])),
+            ],
+        });
+        let graph = WorkspaceGraph::from_metadata(&metadata).unwrap();
+        assert!(graph.build_order().is_err());
+    }
+
+    #[test]
+    fn test_cargo_layout() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = crate::canonicalize_path(dir.path()).unwrap();
+        let member = root.join("contracts/token");
+        std::fs::create_dir_all(member.join("src")).unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"contracts/token\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"token\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(member.join("src/lib.rs"), "").unwrap();
+        std::fs::write(
+            root.join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"fluentbase-sdk\"\nversion = \"0.4.1\"\n",
+        )
+        .unwrap();
+
+        let layout = CargoLayout::locate(&member).unwrap();
+        assert_eq!(layout.workspace_root, root);
+        assert_eq!(layout.cargo_lock(), root.join("Cargo.lock"));
+        assert_eq!(
+            layout.relative_workspace_root(&member).as_deref(),
+            Some("../..")
+        );
+        assert_eq!(layout.relative_workspace_root(&root), None);
+        assert_eq!(
+            crate::read_sdk_version_from_cargo_lock(&member).unwrap(),
+            "0.4.1"
+        );
+
+        let layout = CargoLayout::from_metadata(&json!({
+            "workspace_root": "/ws",
+            "target_directory": "/cache/target",
+        }))
+        .unwrap();
+        assert_eq!(layout.target_directory, PathBuf::from("/cache/target"));
+        assert!(CargoLayout::from_metadata(&json!({"workspace_root": "/ws"})).is_err());
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        std::fs::create_dir_all(project.join("target/release")).unwrap();
+        std::fs::write(project.join("Cargo.toml"), "[package]").unwrap();
+        std::fs::write(project.join("src/lib.rs"), "").unwrap();
+        std::fs::write(project.join("target/release/lib.wasm"), "").unwrap();
+
+        let sandbox = Sandbox::create(&dir.path().join("builds"), "token").unwrap();
+        assert_eq!(sandbox.target_dir, dir.path().join("builds/token/target"));
+        assert!(sandbox.output_dir.is_dir());
+
+        let sources = sandbox.copy_sources(&project).unwrap();
+        assert!(sources.join("src/lib.rs").exists());
+        assert!(!sources.join("target").exists());
+
+        let mut config = CompileConfig::new(&sources);
+        sandbox.configure(&mut config);
+        assert_eq!(config.target_directory(), sandbox.target_dir);
+
+        // Failed builds are kept on request, everything else is removed
+        let kept = sandbox.clone().keep_on_failure(true);
+        kept.finish(false);
+        assert!(kept.root.exists());
+        kept.finish(true);
+        assert!(!kept.root.exists());
+
+        // Leftovers of an earlier run are cleared
+        let sandbox = Sandbox::create(&dir.path().join("builds"), "token").unwrap();
+        assert!(!sandbox.sources_dir.exists());
+        sandbox.finish(false);
+        assert!(!sandbox.root.exists());
+    }
+}
@@ -0,0 +1,33 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+extern crate alloc;
+extern crate fluentbase_sdk;
+
+use fluentbase_sdk::{
+    basic_entrypoint,
+    derive::{router, Contract},
+    SharedAPI, U256,
+};
+
+#[derive(Contract, Default)]
+struct __CONTRACT__<SDK> {
+    sdk: SDK,
+}
+
+pub trait __CONTRACT__API {
+    /// Replace with the functions of your contract
+    fn version(&self) -> U256;
+}
+
+#[router(mode = "solidity")]
+impl<SDK: SharedAPI> __CONTRACT__API for __CONTRACT__<SDK> {
+    fn version(&self) -> U256 {
+        U256::from(1)
+    }
+}
+
+impl<SDK: SharedAPI> __CONTRACT__<SDK> {
+    pub fn deploy(&self) {}
+}
+
+basic_entrypoint!(__CONTRACT__);
@@ -0,0 +1,91 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+extern crate alloc;
+extern crate fluentbase_sdk;
+
+use fluentbase_sdk::{
+    basic_entrypoint,
+    derive::{router, solidity_storage, Contract},
+    Address, ContextReader, SharedAPI, U256,
+};
+
+/// Tokens minted to the deployer
+const INITIAL_SUPPLY: u64 = 1_000_000;
+
+solidity_storage! {
+    U256 TotalSupply;
+    mapping(Address => U256) Balance;
+    mapping(Address => mapping(Address => U256)) Allowance;
+}
+
+#[derive(Contract, Default)]
+struct __CONTRACT__<SDK> {
+    sdk: SDK,
+}
+
+pub trait __CONTRACT__API {
+    fn total_supply(&self) -> U256;
+    fn balance_of(&self, owner: Address) -> U256;
+    fn allowance(&self, owner: Address, spender: Address) -> U256;
+    fn transfer(&mut self, to: Address, amount: U256) -> bool;
+    fn approve(&mut self, spender: Address, amount: U256) -> bool;
+    fn transfer_from(&mut self, from: Address, to: Address, amount: U256) -> bool;
+}
+
+#[router(mode = "solidity")]
+impl<SDK: SharedAPI> __CONTRACT__API for __CONTRACT__<SDK> {
+    fn total_supply(&self) -> U256 {
+        TotalSupply::get(&self.sdk)
+    }
+
+    fn balance_of(&self, owner: Address) -> U256 {
+        Balance::get(&self.sdk, owner)
+    }
+
+    fn allowance(&self, owner: Address, spender: Address) -> U256 {
+        Allowance::get(&self.sdk, owner, spender)
+    }
+
+    fn transfer(&mut self, to: Address, amount: U256) -> bool {
+        let from = self.sdk.context().contract_caller();
+        self.move_tokens(from, to, amount)
+    }
+
+    fn approve(&mut self, spender: Address, amount: U256) -> bool {
+        let owner = self.sdk.context().contract_caller();
+        Allowance::set(&mut self.sdk, owner, spender, amount);
+        true
+    }
+
+    fn transfer_from(&mut self, from: Address, to: Address, amount: U256) -> bool {
+        let spender = self.sdk.context().contract_caller();
+        let allowed = Allowance::get(&self.sdk, from, spender);
+        if allowed < amount {
+            panic!("insufficient allowance");
+        }
+        Allowance::set(&mut self.sdk, from, spender, allowed - amount);
+        self.move_tokens(from, to, amount)
+    }
+}
+
+impl<SDK: SharedAPI> __CONTRACT__<SDK> {
+    pub fn deploy(&mut self) {
+        let owner = self.sdk.context().contract_caller();
+        let supply = U256::from(INITIAL_SUPPLY);
+        TotalSupply::set(&mut self.sdk, supply);
+        Balance::set(&mut self.sdk, owner, supply);
+    }
+
+    fn move_tokens(&mut self, from: Address, to: Address, amount: U256) -> bool {
+        let balance = Balance::get(&self.sdk, from);
+        if balance < amount {
+            panic!("insufficient balance");
+        }
+        Balance::set(&mut self.sdk, from, balance - amount);
+        let received = Balance::get(&self.sdk, to) + amount;
+        Balance::set(&mut self.sdk, to, received);
+        true
+    }
+}
+
+basic_entrypoint!(__CONTRACT__);
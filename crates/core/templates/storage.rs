@@ -0,0 +1,43 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+extern crate alloc;
+extern crate fluentbase_sdk;
+
+use fluentbase_sdk::{
+    basic_entrypoint,
+    derive::{router, solidity_storage, Contract},
+    SharedAPI, U256,
+};
+
+solidity_storage! {
+    U256 Value;
+}
+
+#[derive(Contract, Default)]
+struct __CONTRACT__<SDK> {
+    sdk: SDK,
+}
+
+pub trait __CONTRACT__API {
+    /// Stored value
+    fn get(&self) -> U256;
+    /// Replace the stored value
+    fn set(&mut self, value: U256);
+}
+
+#[router(mode = "solidity")]
+impl<SDK: SharedAPI> __CONTRACT__API for __CONTRACT__<SDK> {
+    fn get(&self) -> U256 {
+        Value::get(&self.sdk)
+    }
+
+    fn set(&mut self, value: U256) {
+        Value::set(&mut self.sdk, value);
+    }
+}
+
+impl<SDK: SharedAPI> __CONTRACT__<SDK> {
+    pub fn deploy(&self) {}
+}
+
+basic_entrypoint!(__CONTRACT__);